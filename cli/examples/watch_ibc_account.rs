@@ -0,0 +1,37 @@
+//! Prints changes to the IBC storage account as they land.
+//!
+//! ```sh
+//! cargo run -p eclipse-ibc-cli --example watch_ibc_account -- \
+//!     http://127.0.0.1:8899 ws://127.0.0.1:8900
+//! ```
+
+use {
+    eclipse_ibc_cli::account_watcher::{self, IbcAccountWatcher},
+    futures::StreamExt,
+    solana_client::nonblocking::rpc_client::RpcClient,
+    std::{env, sync::Arc, time::Duration},
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::try_init()?;
+
+    let mut args = env::args().skip(1);
+    let endpoint = args
+        .next()
+        .unwrap_or_else(|| "http://127.0.0.1:8899".to_owned());
+    let ws_endpoint = args
+        .next()
+        .unwrap_or_else(|| "ws://127.0.0.1:8900".to_owned());
+
+    let rpc_client = Arc::new(RpcClient::new(endpoint));
+    let updates = account_watcher::subscribe(rpc_client, ws_endpoint, Duration::from_secs(2));
+    let mut changes = Box::pin(IbcAccountWatcher::new().watch(Box::pin(updates)));
+    while let Some(event) = changes.next().await {
+        match event {
+            Ok(event) => println!("slot {}: {:?}", event.slot, event.change),
+            Err(err) => eprintln!("{err:#}"),
+        }
+    }
+    Ok(())
+}
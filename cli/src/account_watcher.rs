@@ -0,0 +1,453 @@
+//! Typed change events for the IBC storage account, for indexers and bots that
+//! would rather subscribe to IBC state changes than poll for them.
+
+use {
+    anyhow::anyhow,
+    eclipse_ibc_state::{IbcAccountData, IbcState, IbcStore, PathChange},
+    futures::{stream, Stream, StreamExt},
+    ibc::core::{
+        ics04_channel::packet::Sequence,
+        ics24_host::{
+            identifier::{ChannelId, ClientId, ConnectionId, PortId},
+            path::{
+                AckPath, ChannelEndPath, ClientStatePath, CommitmentPath, ConnectionPath, Path,
+                ReceiptPath,
+            },
+        },
+    },
+    ibc_proto::ibc::core::channel::v1::{Channel as RawChannel, State as RawChannelState},
+    log::{info, warn},
+    prost::Message as _,
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::{
+        nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+        rpc_config::RpcAccountInfoConfig,
+    },
+    solana_sdk::{
+        clock::Slot,
+        hash::{hash, Hash},
+    },
+    std::{sync::Arc, time::Duration},
+    tokio::sync::mpsc,
+};
+
+/// An update to the storage account, as seen by a subscription to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AccountUpdate {
+    /// The account data as of `slot`.
+    Data { slot: Slot, data: Vec<u8> },
+    /// The subscription was lost. Updates may have been missed until the next
+    /// `Data`, which is diffed against the last snapshot in full.
+    Disconnected,
+}
+
+/// A change to the IBC state, derived from the paths whose values changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IbcChange {
+    ClientCreated {
+        client_id: ClientId,
+    },
+    ClientUpdated {
+        client_id: ClientId,
+    },
+    ConnectionUpdated {
+        connection_id: ConnectionId,
+    },
+    ChannelOpened {
+        port_id: PortId,
+        channel_id: ChannelId,
+    },
+    /// A channel was created or changed state other than by opening.
+    ChannelUpdated {
+        port_id: PortId,
+        channel_id: ChannelId,
+    },
+    PacketCommitted {
+        port_id: PortId,
+        channel_id: ChannelId,
+        sequence: Sequence,
+    },
+    /// The commitment of a sent packet was removed, once it was acknowledged or
+    /// timed out.
+    PacketCommitmentCleared {
+        port_id: PortId,
+        channel_id: ChannelId,
+        sequence: Sequence,
+    },
+    PacketReceived {
+        port_id: PortId,
+        channel_id: ChannelId,
+        sequence: Sequence,
+    },
+    PacketAcknowledged {
+        port_id: PortId,
+        channel_id: ChannelId,
+        sequence: Sequence,
+    },
+    /// Any other path, such as sequences, consensus states and program-internal
+    /// paths.
+    Other {
+        path: String,
+    },
+}
+
+/// An [`IbcChange`] along with the slot of the account update it was seen in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IbcChangeEvent {
+    pub slot: Slot,
+    pub change: IbcChange,
+}
+
+fn channel_state(value: &Option<Vec<u8>>) -> Option<i32> {
+    let channel = RawChannel::decode(&**value.as_ref()?).ok()?;
+    Some(channel.state)
+}
+
+impl IbcChange {
+    fn of_path_change(
+        PathChange {
+            path,
+            old_value,
+            new_value,
+        }: PathChange,
+    ) -> Self {
+        let created = old_value.is_none();
+        match path.parse::<Path>() {
+            Ok(Path::ClientState(ClientStatePath(client_id))) if created => {
+                Self::ClientCreated { client_id }
+            }
+            Ok(Path::ClientState(ClientStatePath(client_id))) => Self::ClientUpdated { client_id },
+            Ok(Path::Connection(ConnectionPath(connection_id))) => {
+                Self::ConnectionUpdated { connection_id }
+            }
+            Ok(Path::ChannelEnd(ChannelEndPath(port_id, channel_id))) => {
+                let open = Some(RawChannelState::Open as i32);
+                if channel_state(&new_value) == open && channel_state(&old_value) != open {
+                    Self::ChannelOpened {
+                        port_id,
+                        channel_id,
+                    }
+                } else {
+                    Self::ChannelUpdated {
+                        port_id,
+                        channel_id,
+                    }
+                }
+            }
+            Ok(Path::Commitment(CommitmentPath {
+                port_id,
+                channel_id,
+                sequence,
+            })) => match new_value {
+                Some(_) => Self::PacketCommitted {
+                    port_id,
+                    channel_id,
+                    sequence,
+                },
+                None => Self::PacketCommitmentCleared {
+                    port_id,
+                    channel_id,
+                    sequence,
+                },
+            },
+            Ok(Path::Receipt(ReceiptPath {
+                port_id,
+                channel_id,
+                sequence,
+            })) if created => Self::PacketReceived {
+                port_id,
+                channel_id,
+                sequence,
+            },
+            Ok(Path::Ack(AckPath {
+                port_id,
+                channel_id,
+                sequence,
+            })) if created => Self::PacketAcknowledged {
+                port_id,
+                channel_id,
+                sequence,
+            },
+            _ => Self::Other { path },
+        }
+    }
+}
+
+/// The last storage account data the watcher decoded, which updates are diffed
+/// against.
+struct Snapshot {
+    data_hash: Hash,
+    store: IbcStore,
+}
+
+impl Snapshot {
+    fn state(&self) -> anyhow::Result<IbcState<'_>> {
+        let latest_version = self
+            .store
+            .read()
+            .latest_version()
+            .ok_or_else(|| anyhow!("IBC store is missing latest version"))?;
+        Ok(IbcState::new(&self.store, latest_version))
+    }
+}
+
+/// Turns successive states of the storage account into [`IbcChange`]s by diffing
+/// each against the previous one. The first state is the baseline and yields no
+/// changes. Since every state is diffed in full, updates missed while a
+/// subscription was down show up in the diff of the next one.
+#[derive(Default)]
+pub struct IbcAccountWatcher {
+    snapshot: Option<Snapshot>,
+    disconnected: bool,
+}
+
+impl IbcAccountWatcher {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Changes since the previous account data. Data identical to the previous
+    /// data is skipped without decoding the store, since the account metadata
+    /// has no write counter to tell unchanged stores apart by. Data that fails
+    /// to decode is an error and leaves the previous snapshot in place.
+    pub fn apply(&mut self, data: &[u8]) -> anyhow::Result<Vec<IbcChange>> {
+        let data_hash = hash(data);
+        if let Some(snapshot) = &self.snapshot {
+            if snapshot.data_hash == data_hash {
+                return Ok(vec![]);
+            }
+        }
+
+        let IbcAccountData { store, .. } = IbcAccountData::from_bytes_verified(data)?;
+        let snapshot = Snapshot { data_hash, store };
+        let changes = match &self.snapshot {
+            Some(previous) => previous
+                .state()?
+                .diff(&snapshot.state()?)?
+                .into_iter()
+                .map(IbcChange::of_path_change)
+                .collect(),
+            None => vec![],
+        };
+        self.snapshot = Some(snapshot);
+        Ok(changes)
+    }
+
+    fn apply_update(&mut self, update: AccountUpdate) -> Vec<anyhow::Result<IbcChangeEvent>> {
+        match update {
+            AccountUpdate::Data { slot, data } => {
+                if self.disconnected {
+                    info!("Resyncing IBC account changes at slot {slot}");
+                    self.disconnected = false;
+                }
+                match self.apply(&data) {
+                    Ok(changes) => changes
+                        .into_iter()
+                        .map(|change| Ok(IbcChangeEvent { slot, change }))
+                        .collect(),
+                    Err(err) => vec![Err(
+                        err.context(format!("failed to decode IBC account at slot {slot}"))
+                    )],
+                }
+            }
+            AccountUpdate::Disconnected => {
+                self.disconnected = true;
+                vec![]
+            }
+        }
+    }
+
+    /// Streams the changes in `updates`. Decode errors are yielded without
+    /// ending the stream, which ends when `updates` does.
+    pub fn watch<S>(self, updates: S) -> impl Stream<Item = anyhow::Result<IbcChangeEvent>>
+    where
+        S: Stream<Item = AccountUpdate> + Unpin,
+    {
+        stream::unfold((self, updates), |(mut watcher, mut updates)| async move {
+            let update = updates.next().await?;
+            let events = watcher.apply_update(update);
+            Some((stream::iter(events), (watcher, updates)))
+        })
+        .flatten()
+    }
+}
+
+/// Subscribes to the storage account over the websocket endpoint `ws_url`,
+/// resubscribing `retry_interval` after the subscription is lost. The account
+/// is fetched in full from `rpc_client` on every (re)subscription, so that
+/// nothing missed in between goes unseen. The stream ends once it is dropped.
+pub fn subscribe(
+    rpc_client: Arc<RpcClient>,
+    ws_url: String,
+    retry_interval: Duration,
+) -> impl Stream<Item = AccountUpdate> {
+    let (sender, receiver) = mpsc::channel(16);
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = forward_subscription(&rpc_client, &ws_url, &sender).await {
+                warn!("IBC account subscription failed: {err:#}");
+            }
+            if sender.send(AccountUpdate::Disconnected).await.is_err() {
+                return;
+            }
+            tokio::time::sleep(retry_interval).await;
+        }
+    });
+    stream::unfold(receiver, |mut receiver| async move {
+        let update = receiver.recv().await?;
+        Some((update, receiver))
+    })
+}
+
+/// Forwards the account data until the subscription ends or the receiver is
+/// dropped.
+async fn forward_subscription(
+    rpc_client: &RpcClient,
+    ws_url: &str,
+    sender: &mpsc::Sender<AccountUpdate>,
+) -> anyhow::Result<()> {
+    let pubsub_client = PubsubClient::new(ws_url).await?;
+    let (mut notifications, unsubscribe) = pubsub_client
+        .account_subscribe(
+            &eclipse_ibc_program::STORAGE_KEY,
+            Some(RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(rpc_client.commitment()),
+                ..RpcAccountInfoConfig::default()
+            }),
+        )
+        .await?;
+
+    // Fetched after subscribing, so that no update lands in between
+    let response = rpc_client
+        .get_account_with_commitment(&eclipse_ibc_program::STORAGE_KEY, rpc_client.commitment())
+        .await?;
+    let account = response
+        .value
+        .ok_or_else(|| anyhow!("IBC storage account does not exist"))?;
+    let update = AccountUpdate::Data {
+        slot: response.context.slot,
+        data: account.data,
+    };
+    if sender.send(update).await.is_err() {
+        unsubscribe().await;
+        return Ok(());
+    }
+
+    while let Some(notification) = notifications.next().await {
+        let data = notification
+            .value
+            .data
+            .decode()
+            .ok_or_else(|| anyhow!("Account notification has undecodable data"))?;
+        let update = AccountUpdate::Data {
+            slot: notification.context.slot,
+            data,
+        };
+        if sender.send(update).await.is_err() {
+            break;
+        }
+    }
+    unsubscribe().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, eclipse_ibc_fixtures::FixtureStore};
+
+    fn data(fixture_store: FixtureStore) -> AccountUpdate {
+        AccountUpdate::Data {
+            slot: 0,
+            data: fixture_store.build().to_bytes().unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn scripted_updates_yield_typed_changes() {
+        let port_id = PortId::transfer();
+        let mut slot = 0;
+        let updates = [
+            data(FixtureStore::with_clients(1)),
+            data(FixtureStore::with_clients(1)),
+            data(FixtureStore::with_clients(2)),
+            AccountUpdate::Disconnected,
+            // Channels opened and packets sent while disconnected
+            data(
+                FixtureStore::with_clients(2)
+                    .with_channels(1)
+                    .with_packets(1),
+            ),
+            AccountUpdate::Data {
+                slot: 0,
+                data: vec![0xff; 16],
+            },
+            data(FixtureStore::with_clients(2).with_channels(1)),
+        ]
+        .map(|update| match update {
+            AccountUpdate::Data { data, .. } => {
+                slot += 1;
+                AccountUpdate::Data { slot, data }
+            }
+            AccountUpdate::Disconnected => AccountUpdate::Disconnected,
+        });
+
+        let events = IbcAccountWatcher::new()
+            .watch(stream::iter(updates))
+            .collect::<Vec<_>>()
+            .await;
+        let (events, errors) = events
+            .into_iter()
+            .partition::<Vec<_>, _>(anyhow::Result::is_ok);
+        assert_eq!(errors.len(), 1);
+        let events = events
+            .into_iter()
+            .map(anyhow::Result::unwrap)
+            .filter(|event| !matches!(event.change, IbcChange::Other { .. }))
+            .collect::<Vec<_>>();
+
+        let channel_id = ChannelId::new(0);
+        let sequence = Sequence::from(1);
+        assert_eq!(
+            events,
+            [
+                IbcChangeEvent {
+                    slot: 3,
+                    change: IbcChange::ClientCreated {
+                        client_id: "xx-eclipse-1".parse().unwrap(),
+                    },
+                },
+                IbcChangeEvent {
+                    slot: 4,
+                    change: IbcChange::ChannelOpened {
+                        port_id: port_id.clone(),
+                        channel_id: channel_id.clone(),
+                    },
+                },
+                IbcChangeEvent {
+                    slot: 4,
+                    change: IbcChange::PacketCommitted {
+                        port_id: port_id.clone(),
+                        channel_id: channel_id.clone(),
+                        sequence,
+                    },
+                },
+                IbcChangeEvent {
+                    slot: 4,
+                    change: IbcChange::ConnectionUpdated {
+                        connection_id: ConnectionId::new(0),
+                    },
+                },
+                IbcChangeEvent {
+                    slot: 6,
+                    change: IbcChange::PacketCommitmentCleared {
+                        port_id,
+                        channel_id,
+                        sequence,
+                    },
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Address format helpers for fields that may refer to accounts on either side of
+//! a channel. Fields on our chain are always base58 Solana pubkeys, while fields
+//! destined for a Cosmos counterparty (e.g. ICS-20 receivers) are bech32.
+
+use {
+    eclipse_ibc_light_client::eclipse_chain, ibc::core::ics24_host::identifier::ChainId,
+    solana_sdk::pubkey::Pubkey, std::str::FromStr,
+};
+
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32_MAX_LEN: usize = 90;
+const BECH32_CHECKSUM_LEN: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressFormat {
+    Base58Pubkey,
+    Bech32,
+}
+
+#[must_use]
+pub fn is_valid_base58_pubkey(address: &str) -> bool {
+    Pubkey::from_str(address).is_ok()
+}
+
+/// Returns the human-readable part of `address` if it is shaped like a bech32
+/// address. The checksum is not verified, only the structure and charset.
+#[must_use]
+pub fn bech32_hrp(address: &str) -> Option<&str> {
+    if address.len() > BECH32_MAX_LEN {
+        return None;
+    }
+    let has_lower = address.chars().any(|c| c.is_ascii_lowercase());
+    let has_upper = address.chars().any(|c| c.is_ascii_uppercase());
+    if has_lower && has_upper {
+        return None;
+    }
+
+    let (hrp, data) = address.rsplit_once('1')?;
+    if hrp.is_empty() || !hrp.chars().all(|c| ('!'..='~').contains(&c)) {
+        return None;
+    }
+    if data.len() < BECH32_CHECKSUM_LEN
+        || !data
+            .chars()
+            .all(|c| BECH32_CHARSET.contains(c.to_ascii_lowercase()))
+    {
+        return None;
+    }
+    Some(hrp)
+}
+
+#[must_use]
+pub fn is_plausible_bech32(address: &str) -> bool {
+    bech32_hrp(address).is_some()
+}
+
+#[must_use]
+pub fn address_format(address: &str) -> Option<AddressFormat> {
+    if is_valid_base58_pubkey(address) {
+        Some(AddressFormat::Base58Pubkey)
+    } else if is_plausible_bech32(address) {
+        Some(AddressFormat::Bech32)
+    } else {
+        None
+    }
+}
+
+#[must_use]
+pub fn is_eclipse_chain_id(chain_id: &ChainId) -> bool {
+    chain_id
+        .as_str()
+        .starts_with(&format!("{}-", eclipse_chain::CHAIN_NAME_PREFIX))
+}
+
+/// Checks that an address destined for the chain identified by `cpty_chain_id`
+/// has a recognizable format. Returns a warning when the format is valid but
+/// looks like it belongs to the other kind of chain.
+pub fn check_cpty_address(
+    address: &str,
+    cpty_chain_id: &ChainId,
+) -> anyhow::Result<Option<String>> {
+    let format = address_format(address).ok_or_else(|| {
+        anyhow::anyhow!("{address} is neither a base58 pubkey nor a bech32 address")
+    })?;
+
+    let warning = match (format, is_eclipse_chain_id(cpty_chain_id)) {
+        (AddressFormat::Base58Pubkey, false) => Some(format!(
+            "{address} looks like a Solana pubkey, but counterparty chain {cpty_chain_id} is not an Eclipse chain"
+        )),
+        (AddressFormat::Bech32, true) => Some(format!(
+            "{address} looks like a bech32 address, but counterparty chain {cpty_chain_id} is an Eclipse chain"
+        )),
+        _ => None,
+    };
+    Ok(warning)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COSMOS_ADDRESS: &str = "cosmos1hsk6jryyqjfhp5dhc55tc9jtckygx0eph6dd02";
+    const OSMO_ADDRESS: &str = "osmo1clpqr4nrk4khgkxj78fcwwh6dl3uw4epasmvnj";
+    const SOLANA_ADDRESS: &str = "A7NJxtiKpEFL4TSTygkKSkf5b2g719DJbvQPRr4moUHD";
+
+    #[test]
+    fn detect_address_formats() {
+        assert_eq!(bech32_hrp(COSMOS_ADDRESS), Some("cosmos"));
+        assert_eq!(bech32_hrp(OSMO_ADDRESS), Some("osmo"));
+        assert!(is_valid_base58_pubkey(SOLANA_ADDRESS));
+        assert!(!is_valid_base58_pubkey(COSMOS_ADDRESS));
+        assert!(!is_plausible_bech32(SOLANA_ADDRESS));
+        assert!(!is_plausible_bech32(
+            "Cosmos1hsk6jryyqjfhp5dhc55tc9jtckygx0eph6dd02"
+        ));
+        assert_eq!(address_format("not an address"), None);
+    }
+
+    #[test]
+    fn warn_on_mismatched_cpty_address() {
+        let cosmos_chain_id = ChainId::new("cosmoshub".to_owned(), 4);
+        let eclipse_chain_id = eclipse_chain::chain_id("apricot");
+
+        assert_eq!(
+            check_cpty_address(COSMOS_ADDRESS, &cosmos_chain_id).unwrap(),
+            None
+        );
+        assert_eq!(
+            check_cpty_address(SOLANA_ADDRESS, &eclipse_chain_id).unwrap(),
+            None
+        );
+        assert!(check_cpty_address(SOLANA_ADDRESS, &cosmos_chain_id)
+            .unwrap()
+            .is_some());
+        assert!(check_cpty_address(OSMO_ADDRESS, &eclipse_chain_id)
+            .unwrap()
+            .is_some());
+        assert!(check_cpty_address("???", &cosmos_chain_id).is_err());
+    }
+}
@@ -0,0 +1,57 @@
+//! What this build of the CLI was built from, as generated by
+//! `scripts/build_info.rs`, for telling which build produced a message.
+
+use serde::{Deserialize, Serialize};
+
+include!(concat!(env!("OUT_DIR"), "/build_info.rs"));
+
+/// The build info of a CLI, as recorded in the provenance of the messages it
+/// generates.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct BuildInfo {
+    pub(crate) version: String,
+    pub(crate) git_hash: String,
+    pub(crate) build_date: String,
+    pub(crate) ibc_version: String,
+    pub(crate) ibc_proto_version: String,
+}
+
+impl BuildInfo {
+    /// The build info of this CLI.
+    pub(crate) fn current() -> Self {
+        Self {
+            version: VERSION.to_owned(),
+            git_hash: GIT_HASH.to_owned(),
+            build_date: BUILD_DATE.to_owned(),
+            ibc_version: IBC_VERSION.to_owned(),
+            ibc_proto_version: IBC_PROTO_VERSION.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_info_is_populated_and_stable() {
+        let build_info = BuildInfo::current();
+        assert_eq!(build_info.version, env!("CARGO_PKG_VERSION"));
+        for field in [
+            &build_info.git_hash,
+            &build_info.build_date,
+            &build_info.ibc_version,
+            &build_info.ibc_proto_version,
+        ] {
+            assert!(!field.is_empty(), "{build_info:?}");
+        }
+        // The dependencies are locked, so they are always known
+        assert_ne!(build_info.ibc_version, "unknown");
+        assert_ne!(build_info.ibc_proto_version, "unknown");
+        assert_eq!(BuildInfo::current(), build_info);
+
+        let mut lines = LONG_VERSION.lines();
+        assert_eq!(lines.next(), Some(VERSION));
+        assert!(lines.any(|line| line == format!("git hash: {GIT_HASH}")));
+    }
+}
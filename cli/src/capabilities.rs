@@ -0,0 +1,111 @@
+//! Checks of the message families a deployed program advertises at
+//! `CapabilitiesPath`, so that messages it cannot decode are flagged before they
+//! are sent rather than failing with an unknown type URL on submission.
+
+use {
+    crate::generate,
+    eclipse_ibc_state::{internal_path::CapabilitiesPath, IbcState, IbcStore},
+    log::{info, warn},
+    std::future::Future,
+};
+
+/// Warns about each of `type_urls` that the program of chain `chain`, whose IBC
+/// state is `ibc_state`, does not advertise, and returns them. Programs that
+/// predate capabilities advertise nothing, so nothing can be checked against
+/// them.
+pub(crate) fn warn_unadvertised<'a>(
+    chain: &str,
+    ibc_state: &IbcState<'_>,
+    type_urls: impl IntoIterator<Item = &'a str>,
+) -> anyhow::Result<Vec<&'a str>> {
+    let capabilities = match ibc_state.get(&CapabilitiesPath)? {
+        Some(capabilities) => capabilities,
+        None => {
+            info!(
+                "The program on {chain} does not advertise its capabilities, so the messages \
+                 it handles cannot be checked"
+            );
+            return Ok(vec![]);
+        }
+    };
+    let unadvertised = type_urls
+        .into_iter()
+        .filter(|type_url| !capabilities.supports(type_url))
+        .collect::<Vec<_>>();
+    for type_url in &unadvertised {
+        warn!(
+            "The program on {chain} (version {}) does not advertise {type_url}, so it may \
+             fail to decode the message; upgrade the program and run `tx admin \
+             refresh-capabilities` if it is out of date",
+            capabilities.program_version
+        );
+    }
+    Ok(unadvertised)
+}
+
+/// Like [`warn_unadvertised`], against the latest version of `ibc_store`. The
+/// check only warns, so failing to read the store is logged rather than returned.
+pub(crate) async fn check_store(
+    chain: &str,
+    ibc_store: impl Future<Output = anyhow::Result<IbcStore>>,
+    type_urls: &[&str],
+) {
+    let result = async {
+        let ibc_store = ibc_store.await?;
+        let ibc_state = generate::get_ibc_state(&ibc_store)?;
+        warn_unadvertised(chain, &ibc_state, type_urls.iter().copied())
+    }
+    .await;
+    if let Err(err) = result {
+        warn!("Failed to check the capabilities of the program on {chain}: {err:#}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, eclipse_ibc_extra_types::Capabilities,
+        eclipse_ibc_program::capabilities::capabilities,
+    };
+
+    const RECV_PACKET: &str = "/ibc.core.channel.v1.MsgRecvPacket";
+    const UPDATE_CLIENT: &str = "/ibc.core.client.v1.MsgUpdateClient";
+
+    #[test]
+    fn unadvertised_message_families_are_flagged() {
+        let store = IbcStore::default();
+        let mut ibc_state = IbcState::new(&store, 1);
+        let type_urls = [UPDATE_CLIENT, RECV_PACKET];
+
+        // Nothing to check against
+        assert_eq!(
+            warn_unadvertised("A", &ibc_state, type_urls).unwrap(),
+            Vec::<&str>::new()
+        );
+
+        // An older program that handles client messages and handshakes, but not
+        // packets yet
+        ibc_state.set(
+            &CapabilitiesPath,
+            Capabilities {
+                type_url_prefixes: vec![
+                    "/ibc.core.client.v1.".to_owned(),
+                    "/ibc.core.connection.v1.".to_owned(),
+                    "/ibc.core.channel.v1.MsgChannel".to_owned(),
+                ],
+                program_version: "0.0.1".to_owned(),
+                build_id: String::new(),
+            },
+        );
+        assert_eq!(
+            warn_unadvertised("A", &ibc_state, type_urls).unwrap(),
+            [RECV_PACKET]
+        );
+
+        ibc_state.set(&CapabilitiesPath, capabilities());
+        assert_eq!(
+            warn_unadvertised("A", &ibc_state, type_urls).unwrap(),
+            Vec::<&str>::new()
+        );
+    }
+}
@@ -1,20 +1,42 @@
 use {
-    anyhow::anyhow,
+    anyhow::{anyhow, bail},
+    core::time::Duration,
+    eclipse_ibc_extra_types::{ChainParams, PendingAdminChange},
     eclipse_ibc_light_client::{
-        eclipse_chain, EclipseClientState, EclipseConsensusState, EclipseHeader,
+        eclipse_chain::{self, EclipseHeight},
+        EclipseClientState, EclipseConsensusState, EclipseHeader,
+    },
+    eclipse_ibc_state::{
+        internal_path::{AdminPath, ChainParamsPath, ConsensusHeightsPath, PendingAdminChangePath},
+        IbcAccountData, IbcMetadata, IbcState, IbcStore,
+    },
+    ibc::core::{
+        ics02_client::height::Height,
+        ics24_host::{identifier::ClientId, path::ClientStatePath},
+        timestamp::Timestamp,
+    },
+    log::warn,
+    solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig},
+    solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcAccountInfoConfig},
+    solana_sdk::{
+        account::{from_account, Account},
+        clock::{Clock, Slot},
+        hash::Hash,
+        sysvar,
     },
-    eclipse_ibc_state::{IbcAccountData, IbcState},
-    ibc::core::ics02_client::height::Height,
-    solana_client::nonblocking::rpc_client::RpcClient,
     tendermint::time::Time as TendermintTime,
 };
 
+/// Slots searched back from a skipped slot for the block the slot takes its time
+/// from.
+const SKIPPED_SLOT_LOOKBACK: Slot = 1024;
+
 pub(crate) async fn get_consensus_state(
     rpc_client: &RpcClient,
     height: Height,
 ) -> anyhow::Result<EclipseConsensusState> {
-    let slot = eclipse_chain::slot_of_height(height)?;
-    let block = rpc_client.get_block(slot).await?;
+    let slot = height.to_slot()?;
+    let timestamp = get_slot_time(rpc_client, slot).await?;
 
     let raw_account_data = rpc_client
         .get_account_data(&eclipse_ibc_program::STORAGE_KEY)
@@ -22,31 +44,196 @@ pub(crate) async fn get_consensus_state(
 
     let IbcAccountData {
         store: ibc_store, ..
-    } = bincode::deserialize(&raw_account_data)?;
+    } = IbcAccountData::from_bytes_verified(&raw_account_data)?;
+
+    consensus_state_at(&ibc_store, slot, timestamp)
+}
 
+/// Queries the canonical time of `slot`, the block time of its block (see
+/// `eclipse_chain::slot_time`). A skipped slot has no block and takes the time of
+/// the latest block before it: that block's IBC state version is the one in
+/// effect at the skipped slot, the same version `find_version` picks for it.
+pub(crate) async fn get_slot_time(
+    rpc_client: &RpcClient,
+    slot: Slot,
+) -> anyhow::Result<TendermintTime> {
+    let block_slot = rpc_client
+        .get_blocks(slot.saturating_sub(SKIPPED_SLOT_LOOKBACK), Some(slot))
+        .await?
+        .last()
+        .copied()
+        .ok_or_else(|| {
+            anyhow!("No block found in the {SKIPPED_SLOT_LOOKBACK} slots up to {slot}")
+        })?;
+    let block_time = rpc_client.get_block_time(block_slot).await?;
+    eclipse_chain::slot_time(block_time)
+        .ok_or_else(|| anyhow!("Block time {block_time} of slot {block_slot} is out of range"))
+}
+
+/// The host consensus state at `slot`, given the canonical time of the slot.
+pub(crate) fn consensus_state_at(
+    ibc_store: &IbcStore,
+    slot: Slot,
+    timestamp: TendermintTime,
+) -> anyhow::Result<EclipseConsensusState> {
     let version = ibc_store
-        .read()?
+        .read()
         .find_version(slot)
         .ok_or_else(|| anyhow!("No IBC state versions found"))?;
-    let ibc_state = IbcState::new(&ibc_store, version);
+    let ibc_state = IbcState::new(ibc_store, version);
     let commitment_root = ibc_state
-        .get_root_option(version)?
+        .get_commitment_root_option(version)?
         .ok_or_else(|| anyhow!("No commitment root found for slot {slot}"))?;
 
-    let timestamp = TendermintTime::from_unix_timestamp(
-        block
-            .block_time
-            .ok_or_else(|| anyhow!("Block timestamp should not be missing"))?,
-        0,
-    )
-    .expect("Block time should be valid");
-
     Ok(EclipseConsensusState {
         commitment_root,
         timestamp,
     })
 }
 
+/// Reads the chain params as of the latest IBC state version, or the defaults if
+/// they have never been set.
+pub(crate) async fn get_chain_params(rpc_client: &RpcClient) -> anyhow::Result<ChainParams> {
+    let raw_account_data = rpc_client
+        .get_account_data(&eclipse_ibc_program::STORAGE_KEY)
+        .await?;
+
+    let IbcAccountData {
+        store: ibc_store, ..
+    } = IbcAccountData::from_bytes_verified(&raw_account_data)?;
+
+    let latest_version = ibc_store
+        .read()
+        .latest_version()
+        .ok_or_else(|| anyhow!("IBC store is missing latest version"))?;
+    let ibc_state = IbcState::new(&ibc_store, latest_version);
+
+    Ok(ibc_state.get(&ChainParamsPath)?.unwrap_or_default())
+}
+
+/// Reads the admin and the admin change waiting to be accepted, as of the latest
+/// IBC state version.
+pub(crate) async fn get_admin(
+    rpc_client: &RpcClient,
+) -> anyhow::Result<(Option<String>, Option<PendingAdminChange>)> {
+    let raw_account_data = rpc_client
+        .get_account_data(&eclipse_ibc_program::STORAGE_KEY)
+        .await?;
+
+    let IbcAccountData {
+        store: ibc_store, ..
+    } = IbcAccountData::from_bytes_verified(&raw_account_data)?;
+
+    let latest_version = ibc_store
+        .read()
+        .latest_version()
+        .ok_or_else(|| anyhow!("IBC store is missing latest version"))?;
+    let ibc_state = IbcState::new(&ibc_store, latest_version);
+
+    Ok((
+        ibc_state.get(&AdminPath)?,
+        ibc_state.get(&PendingAdminChangePath)?,
+    ))
+}
+
+/// Reads the IBC metadata, fetching only the leading bytes of the storage account
+/// that hold it rather than the whole store.
+pub(crate) async fn get_ibc_metadata(rpc_client: &RpcClient) -> anyhow::Result<IbcMetadata> {
+    let head = get_storage_account_head(rpc_client).await?.data;
+    let data = match IbcAccountData::metadata_end(&head)? {
+        Some(_) => head,
+        // The legacy layout puts the metadata after the store
+        None => {
+            rpc_client
+                .get_account_data(&eclipse_ibc_program::STORAGE_KEY)
+                .await?
+        }
+    };
+    IbcAccountData::read_metadata_only(&data)
+}
+
+/// Height of `slot` in the revision the IBC program is at, which it records in
+/// the IBC metadata.
+pub(crate) async fn get_host_height(rpc_client: &RpcClient, slot: Slot) -> anyhow::Result<Height> {
+    let revision_number = get_ibc_metadata(rpc_client).await?.revision_number;
+    Ok(eclipse_chain::height_of_slot_with_revision(
+        slot,
+        revision_number,
+    )?)
+}
+
+/// `slot`, or the first slot the RPC node still has a block for if it has purged
+/// `slot` from its block store, with a warning.
+pub(crate) async fn nearest_available_slot(
+    rpc_client: &RpcClient,
+    slot: Slot,
+) -> anyhow::Result<Slot> {
+    let first_available_slot = rpc_client.get_first_available_block().await?;
+    if slot >= first_available_slot {
+        return Ok(slot);
+    }
+    warn!(
+        "Slot {slot} was purged from the RPC node's block store; using the nearest available \
+         slot {first_available_slot} instead"
+    );
+    Ok(first_available_slot)
+}
+
+/// The storage account with its data cut to the header and metadata, or to the
+/// header if the data is in the legacy layout, which puts the metadata after the
+/// store.
+pub(crate) async fn get_storage_account_head(rpc_client: &RpcClient) -> anyhow::Result<Account> {
+    let account = get_storage_account_prefix(rpc_client, IbcAccountData::HEADER_LEN).await?;
+    match IbcAccountData::metadata_end(&account.data)? {
+        Some(metadata_end) => get_storage_account_prefix(rpc_client, metadata_end).await,
+        None => Ok(account),
+    }
+}
+
+/// The storage account with its data cut to the first `len` bytes.
+async fn get_storage_account_prefix(rpc_client: &RpcClient, len: usize) -> anyhow::Result<Account> {
+    let account = rpc_client
+        .get_account_with_config(
+            &eclipse_ibc_program::STORAGE_KEY,
+            RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                data_slice: Some(UiDataSliceConfig {
+                    offset: 0,
+                    length: len,
+                }),
+                commitment: Some(rpc_client.commitment()),
+                ..RpcAccountInfoConfig::default()
+            },
+        )
+        .await?
+        .value
+        .ok_or_else(|| anyhow!("IBC storage account does not exist"))?;
+    Ok(account)
+}
+
+/// Reads the whole IBC storage account, store and metadata.
+pub(crate) async fn get_ibc_account_data(rpc_client: &RpcClient) -> anyhow::Result<IbcAccountData> {
+    let raw_account_data = rpc_client
+        .get_account_data(&eclipse_ibc_program::STORAGE_KEY)
+        .await?;
+    IbcAccountData::from_bytes_verified(&raw_account_data)
+}
+
+/// Reads the clock sysvar, which the IBC program takes the host height and time
+/// from.
+pub(crate) async fn get_clock(rpc_client: &RpcClient) -> anyhow::Result<Clock> {
+    let clock_account = rpc_client.get_account(&sysvar::clock::id()).await?;
+    from_account::<Clock, _>(&clock_account)
+        .ok_or_else(|| anyhow!("Clock sysvar account could not be decoded"))
+}
+
+/// Reads the current time of the host clock, which the IBC program records
+/// client update times by.
+pub(crate) async fn get_host_time(rpc_client: &RpcClient) -> anyhow::Result<Timestamp> {
+    let clock = get_clock(rpc_client).await?;
+    Ok(eclipse_chain::tendermint_time_from_clock(&clock).into())
+}
+
 pub(crate) fn header_from_consensus_state(
     EclipseConsensusState {
         commitment_root,
@@ -61,13 +248,301 @@ pub(crate) fn header_from_consensus_state(
     }
 }
 
+/// Parameters of a new client that its latest header does not determine.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ClientStateParams {
+    /// How long the client stays valid after each update, or the light client's
+    /// default if `None`
+    pub(crate) valid_duration: Option<Duration>,
+    /// Whether the client is created frozen at its latest height, which only
+    /// test scenarios want
+    pub(crate) frozen: bool,
+}
+
 pub(crate) fn client_state_from_header(
     latest_header: EclipseHeader,
     chain_name: &str,
+    genesis_hash: Hash,
+    ClientStateParams {
+        valid_duration,
+        frozen,
+    }: ClientStateParams,
 ) -> EclipseClientState {
+    let revision_number = latest_header.height.revision_number();
     EclipseClientState {
-        chain_id: eclipse_chain::chain_id(chain_name),
+        chain_id: eclipse_chain::chain_id_with_revision(chain_name, revision_number),
+        frozen_height: frozen.then_some(latest_header.height),
         latest_header,
-        frozen_height: None,
+        genesis_hash: Some(genesis_hash),
+        revision_number,
+        valid_duration,
+    }
+}
+
+/// Latest height that `client_id` has a consensus state at, as indexed by its
+/// `ConsensusHeightsPath`. Clients created before the index existed, or whose
+/// index was emptied, fall back to scanning the paths of their consensus states,
+/// which only covers paths written since the store recorded them.
+pub(crate) fn get_latest_consensus_height(
+    ibc_state: &IbcState,
+    client_id: &str,
+) -> anyhow::Result<Height> {
+    let client_id = client_id.parse::<ClientId>()?;
+    if ibc_state
+        .get_raw(&ClientStatePath::new(&client_id))?
+        .is_none()
+    {
+        bail!("Client {client_id} is unknown");
+    }
+
+    let index_problem = match ibc_state.get(&ConsensusHeightsPath(client_id.clone()))? {
+        None => "missing",
+        Some(consensus_heights) => match consensus_heights.heights.last() {
+            Some(height) => return Ok(*height),
+            None => "empty",
+        },
+    };
+
+    let prefix = format!("clients/{client_id}/consensusStates/");
+    let scanned_height = ibc_state
+        .paths_with_prefix(&prefix)?
+        .iter()
+        .map(|path| {
+            path[prefix.len()..]
+                .parse::<Height>()
+                .map_err(|err| anyhow!("Malformed consensus state path {path}: {err}"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .into_iter()
+        .max();
+    match scanned_height {
+        Some(height) => {
+            warn!(
+                "Consensus heights index of client {client_id} is {index_problem}; using its \
+                 latest consensus state path instead. Run `query check-invariants` to \
+                 check the IBC store."
+            );
+            Ok(height)
+        }
+        None => bail!(
+            "Consensus heights index of client {client_id} is {index_problem}, and no consensus \
+             state paths of the client were recorded"
+        ),
+    }
+}
+
+/// Describes why headers from the cluster with `endpoint_genesis_hash` should not
+/// be used to update `client_id`, if the client recorded a different cluster.
+pub(crate) fn genesis_hash_mismatch(
+    client_id: &str,
+    client_state: &EclipseClientState,
+    endpoint_genesis_hash: &Hash,
+) -> Option<String> {
+    let recorded_genesis_hash = client_state.genesis_hash?;
+    (recorded_genesis_hash != *endpoint_genesis_hash).then(|| {
+        format!(
+            "client {client_id} was created for the cluster with genesis hash \
+             {recorded_genesis_hash}, but the endpoint has genesis hash {endpoint_genesis_hash}; \
+             its proofs will not verify"
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        eclipse_ibc_fixtures::FixtureStore,
+        eclipse_ibc_known_proto::KnownAnyProto,
+        ibc::core::{
+            ics02_client::client_state::ClientState, ics23_commitment::commitment::CommitmentRoot,
+            ics24_host::path::ClientConsensusStatePath,
+        },
+    };
+
+    fn client_state(genesis_hash: Option<Hash>) -> EclipseClientState {
+        EclipseClientState {
+            chain_id: eclipse_chain::chain_id("test"),
+            latest_header: EclipseHeader {
+                height: Height::new(0, 1).unwrap(),
+                commitment_root: CommitmentRoot::from_bytes(&[0; 32]),
+                timestamp: TendermintTime::unix_epoch(),
+            },
+            frozen_height: None,
+            genesis_hash,
+            revision_number: eclipse_chain::DEFAULT_REVISION_NUMBER,
+            valid_duration: None,
+        }
+    }
+
+    #[test]
+    fn warns_on_genesis_hash_mismatch() {
+        let devnet = Hash::new_from_array([1; 32]);
+        let mainnet = Hash::new_from_array([2; 32]);
+
+        let warning =
+            genesis_hash_mismatch("xx-eclipse-0", &client_state(Some(devnet)), &mainnet).unwrap();
+        assert!(warning.contains(&devnet.to_string()), "{warning}");
+        assert!(warning.contains(&mainnet.to_string()), "{warning}");
+
+        assert_eq!(
+            genesis_hash_mismatch("xx-eclipse-0", &client_state(Some(devnet)), &devnet),
+            None
+        );
+        assert_eq!(
+            genesis_hash_mismatch("xx-eclipse-0", &client_state(None), &mainnet),
+            None
+        );
+    }
+
+    #[test]
+    fn client_state_params_are_embedded() {
+        let header = client_state(None).latest_header;
+        let genesis_hash = Hash::new_from_array([1; 32]);
+
+        let default = client_state_from_header(
+            header.clone(),
+            "test",
+            genesis_hash,
+            ClientStateParams::default(),
+        );
+        assert_eq!(default.frozen_height, None);
+        assert!(!default.expired(eclipse_chain::IBC_MESSAGE_VALID_DURATION));
+
+        let client_state = client_state_from_header(
+            header.clone(),
+            "test",
+            genesis_hash,
+            ClientStateParams {
+                valid_duration: Some(Duration::from_secs(60)),
+                frozen: true,
+            },
+        );
+        assert_eq!(client_state.frozen_height, Some(header.height));
+        assert!(!client_state.expired(Duration::from_secs(60)));
+        assert!(client_state.expired(Duration::from_secs(61)));
+        assert_eq!(
+            EclipseClientState::try_from(client_state.clone().encode_as_any()).unwrap(),
+            client_state
+        );
+    }
+
+    #[test]
+    fn slot_time_sources_agree_on_the_fixture_slot() {
+        let account_data = FixtureStore::canonical().build();
+        let store = &account_data.store;
+        let slot = store.read().latest_version().unwrap();
+        // The Clock while the fixture slot's block is produced, whose timestamp RPC
+        // nodes then report as the block time of the slot
+        let clock = Clock {
+            slot,
+            unix_timestamp: 1_700_000_000,
+            ..Clock::default()
+        };
+        let block_time = clock.unix_timestamp;
+
+        // `query host-consensus-state`, and the headers the CLI generates
+        let queried =
+            consensus_state_at(store, slot, eclipse_chain::slot_time(block_time).unwrap()).unwrap();
+        // `get_host_time`, which client update times are compared against
+        let host_time = Timestamp::from(eclipse_chain::tendermint_time_from_clock(&clock));
+        // The handler's host consensus state at the current slot, which it records
+        // as the slot time of the version it commits
+        let handler_time = eclipse_chain::tendermint_time_from_clock(&clock);
+
+        assert_eq!(queried.timestamp, handler_time);
+        assert_eq!(Timestamp::from(queried.timestamp), host_time);
+        assert_eq!(
+            Some(queried.commitment_root.clone()),
+            IbcState::new(store, slot)
+                .get_commitment_root_option(slot)
+                .unwrap()
+        );
+
+        // A skipped slot takes the time and state of the latest block before it
+        let skipped = consensus_state_at(store, slot + 1, queried.timestamp).unwrap();
+        assert_eq!(skipped, queried);
+    }
+
+    #[test]
+    fn latest_consensus_height_falls_back_to_consensus_state_paths() {
+        let account_data = FixtureStore::with_clients(2).build();
+        let store = &account_data.store;
+        let version = store.read().latest_version().unwrap();
+        let client_id = |index| format!("xx-eclipse-{index}").parse::<ClientId>().unwrap();
+        let fixture_height = Height::new(0, 1).unwrap();
+        let later_height = Height::new(0, 5).unwrap();
+
+        // The index wins over consensus states it does not list
+        let mut ibc_state = IbcState::new(store, version + 1);
+        ibc_state
+            .set_any(
+                &ClientConsensusStatePath::new(&client_id(0), &later_height),
+                EclipseConsensusState {
+                    commitment_root: CommitmentRoot::from_bytes(&[5; 32]),
+                    timestamp: TendermintTime::unix_epoch(),
+                }
+                .into(),
+            )
+            .unwrap();
+        ibc_state.commit().unwrap();
+        let ibc_state = IbcState::new(store, version + 1);
+        assert_eq!(
+            get_latest_consensus_height(&ibc_state, "xx-eclipse-0").unwrap(),
+            fixture_height
+        );
+        // xx-eclipse-0 loses its index, and xx-eclipse-1 has its index emptied
+        let mut ibc_state = IbcState::new(store, version + 2);
+        ibc_state.remove(&ConsensusHeightsPath(client_id(0)));
+        ibc_state
+            .update(&ConsensusHeightsPath(client_id(1)), |consensus_heights| {
+                consensus_heights.heights.clear();
+            })
+            .unwrap();
+        ibc_state.commit().unwrap();
+
+        let ibc_state = IbcState::new(store, version + 2);
+        assert_eq!(
+            get_latest_consensus_height(&ibc_state, "xx-eclipse-0").unwrap(),
+            later_height
+        );
+        assert_eq!(
+            get_latest_consensus_height(&ibc_state, "xx-eclipse-1").unwrap(),
+            fixture_height
+        );
+        let err = get_latest_consensus_height(&ibc_state, "xx-eclipse-2")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Client xx-eclipse-2 is unknown");
+
+        // Without recorded paths there is nothing to fall back to
+        let bare_store = IbcStore::default();
+        let mut ibc_state = IbcState::new(&bare_store, 1);
+        ibc_state
+            .set_any(
+                &ClientStatePath::new(&client_id(0)),
+                eclipse_ibc_fixtures::clients::eclipse_client_state(1).into(),
+            )
+            .unwrap();
+        ibc_state.commit().unwrap();
+        let ibc_state = IbcState::new(&bare_store, 1);
+        let err = get_latest_consensus_height(&ibc_state, "xx-eclipse-0")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("index of client xx-eclipse-0 is missing"),
+            "{err}"
+        );
+        let mut ibc_state = IbcState::new(&bare_store, 2);
+        ibc_state.set(&ConsensusHeightsPath(client_id(0)), Default::default());
+        ibc_state.commit().unwrap();
+        let ibc_state = IbcState::new(&bare_store, 2);
+        let err = get_latest_consensus_height(&ibc_state, "xx-eclipse-0")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("index of client xx-eclipse-0 is empty"),
+            "{err}"
+        );
     }
 }
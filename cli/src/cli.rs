@@ -1,29 +1,161 @@
 use {
-    crate::{generate, query, tx},
+    crate::{build_info, doctor, generate, query, relay, serve, tx, watch},
+    anyhow::anyhow,
     clap::{Parser, Subcommand},
 };
 
+/// Whether a subcommand only reads chain state or may sign and send transactions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Access {
+    Readonly,
+    Mutating,
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand)]
 enum CliSubcommand {
+    /// Runs every health check and prints a single pass/warn/fail report [readonly]
+    Doctor(doctor::Args),
+    /// Generates IBC messages from the state of both chains [readonly]
     Generate(generate::Args),
+    /// Queries the IBC state of a chain [readonly]
     Query(query::Args),
+    /// Relays packets and acknowledgements between two chains over a channel
+    /// [mutating]
+    Relay(relay::Args),
+    /// Serves the ibc-go gRPC query services that relayers such as Hermes use
+    /// [readonly]
+    Serve(serve::Args),
+    /// Signs and sends IBC transactions [mutating]
     Tx(tx::Args),
+    /// Follows the IBC events and logs of a chain [readonly]
+    Watch(watch::Args),
+}
+
+impl CliSubcommand {
+    /// Matches the tag at the end of each subcommand's help.
+    fn access(&self) -> Access {
+        match self {
+            Self::Doctor(_)
+            | Self::Generate(_)
+            | Self::Query(_)
+            | Self::Serve(_)
+            | Self::Watch(_) => Access::Readonly,
+            Self::Relay(_) | Self::Tx(_) => Access::Mutating,
+        }
+    }
 }
 
 #[derive(Debug, Parser)]
-#[command(version, about)]
+#[command(version, long_version = build_info::LONG_VERSION, about)]
 struct Args {
+    /// Refuse to sign or send any transaction, for pointing the CLI at endpoints it
+    /// must not change
+    #[arg(long, global = true, env = "ECLIPSE_IBC_READONLY")]
+    readonly: bool,
+
     #[command(subcommand)]
     subcommand: CliSubcommand,
 }
 
+/// Rejects mutating subcommands up front in readonly mode. Readonly subcommands
+/// that end up signing anyway are stopped by the guard in `tx`.
+fn check_access(readonly: bool, subcommand: &CliSubcommand) -> anyhow::Result<()> {
+    if readonly && subcommand.access() == Access::Mutating {
+        return Err(anyhow!(
+            "This subcommand signs and sends transactions, which readonly mode does not allow"
+        ));
+    }
+    Ok(())
+}
+
 pub async fn run() -> anyhow::Result<()> {
-    let Args { subcommand } = Args::try_parse()?;
+    let Args {
+        readonly,
+        subcommand,
+    } = Args::try_parse()?;
+    tx::set_readonly(readonly);
+    check_access(readonly, &subcommand)?;
 
     match subcommand {
+        CliSubcommand::Doctor(sub_args) => doctor::run(sub_args).await,
         CliSubcommand::Generate(sub_args) => generate::run(sub_args).await,
         CliSubcommand::Query(sub_args) => query::run(sub_args).await,
+        CliSubcommand::Relay(sub_args) => relay::run(sub_args).await,
+        CliSubcommand::Serve(sub_args) => serve::run(sub_args).await,
         CliSubcommand::Tx(sub_args) => tx::run(sub_args).await,
+        CliSubcommand::Watch(sub_args) => watch::run(sub_args).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Args {
+        Args::try_parse_from(["eclipse-ibc"].iter().chain(args)).unwrap()
+    }
+
+    #[test]
+    fn readonly_mode_rejects_tx_and_relay() {
+        let relay = [
+            "relay",
+            "--cpty-endpoint",
+            "http://127.0.0.1:8898",
+            "xx-eclipse-0",
+            "xx-eclipse-1",
+            "connection-0",
+            "connection-1",
+            "transfer",
+            "channel-0",
+            "transfer",
+            "channel-1",
+        ];
+        for (args, access) in [
+            (&["tx", "admin", "freeze"][..], Access::Mutating),
+            (&relay[..], Access::Mutating),
+            (&["query", "status"][..], Access::Readonly),
+            (
+                &[
+                    "generate",
+                    "packet",
+                    "ack-auto",
+                    "xx-eclipse-0",
+                    "transfer",
+                    "channel-1",
+                    "--sequence",
+                    "3",
+                ][..],
+                Access::Readonly,
+            ),
+        ] {
+            let Args {
+                readonly,
+                subcommand,
+            } = parse(args);
+            assert!(!readonly);
+            assert_eq!(subcommand.access(), access, "{args:?}");
+            check_access(readonly, &subcommand).unwrap();
+
+            // The flag is global, so it is accepted after the subcommand as well
+            let Args {
+                readonly,
+                subcommand,
+            } = parse(&[args, &["--readonly"][..]].concat());
+            assert!(readonly);
+            assert_eq!(
+                check_access(readonly, &subcommand).is_err(),
+                access == Access::Mutating,
+                "{args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn readonly_mode_stops_signing() {
+        tx::set_readonly(true);
+        let err = tx::read_payer(None).unwrap_err();
+        tx::set_readonly(false);
+        assert!(err.to_string().contains("readonly mode"), "{err}");
     }
 }
@@ -0,0 +1,377 @@
+use {
+    crate::chain_state,
+    anyhow::{anyhow, bail},
+    eclipse_ibc_known_proto::KnownProto,
+    eclipse_ibc_light_client::{
+        canonical_type_url, eclipse_chain, EclipseClientState, ECLIPSE_CLIENT_STATE_TYPE_URL,
+    },
+    ibc::core::{
+        ics02_client::height::Height,
+        ics23_commitment::commitment::CommitmentRoot,
+        ics24_host::identifier::{ChainId, ClientId},
+    },
+    ibc_proto::{
+        google::protobuf::Any,
+        ibc::core::client::v1::{
+            query_client::QueryClient, Height as RawHeight, QueryClientStateRequest,
+        },
+    },
+    prost::Message as _,
+    serde::Serialize,
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::hash::Hash,
+};
+
+const WASM_CLIENT_STATE_TYPE_URL: &str = "/ibc.lightclients.wasm.v1.ClientState";
+
+/// Client state of the 08-wasm light client, which is how Cosmos chains run the
+/// Eclipse light client. Only the fields needed to reach the wrapped state are kept.
+#[derive(Clone, PartialEq, prost::Message)]
+struct RawWasmClientState {
+    #[prost(bytes = "vec", tag = "1")]
+    data: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    checksum: Vec<u8>,
+    #[prost(message, optional, tag = "3")]
+    latest_height: Option<RawHeight>,
+}
+
+/// Eclipse client state hosted by a counterparty, along with the latest height
+/// the 08-wasm wrapper reports, if the client is wrapped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CounterpartyClientState {
+    pub(crate) client_state: EclipseClientState,
+    pub(crate) wasm_latest_height: Option<Height>,
+}
+
+/// What the counterparty's client of this chain should agree with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ChainInfo {
+    pub(crate) chain_id: ChainId,
+    pub(crate) genesis_hash: Hash,
+    pub(crate) host_height: Height,
+    /// Commitment root of this chain at the client's latest height, if the IBC
+    /// state for that height has not been pruned.
+    pub(crate) commitment_root: Option<CommitmentRoot>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct CompatReport {
+    pub(crate) compatible: bool,
+    pub(crate) mismatches: Vec<String>,
+    pub(crate) warnings: Vec<String>,
+}
+
+/// Decodes the Eclipse client state from a counterparty's client state, which is
+/// either the Eclipse client state itself or an 08-wasm client state wrapping it.
+pub(crate) fn decode_counterparty_client_state(
+    any: Any,
+) -> anyhow::Result<CounterpartyClientState> {
+    // Counterparties may still store client states created with the legacy type URL
+    match canonical_type_url(&any.type_url).unwrap_or(&any.type_url) {
+        ECLIPSE_CLIENT_STATE_TYPE_URL => Ok(CounterpartyClientState {
+            client_state: EclipseClientState::decode(&*any.value)?,
+            wasm_latest_height: None,
+        }),
+        WASM_CLIENT_STATE_TYPE_URL => {
+            let RawWasmClientState {
+                data,
+                latest_height,
+                ..
+            } = RawWasmClientState::decode(&*any.value)?;
+
+            // The wasm contract may store the client state bare or wrapped in an `Any`.
+            let client_state = match <Any as prost::Message>::decode(&*data) {
+                Ok(inner)
+                    if canonical_type_url(&inner.type_url).unwrap_or(&inner.type_url)
+                        == ECLIPSE_CLIENT_STATE_TYPE_URL =>
+                {
+                    EclipseClientState::decode(&*inner.value)?
+                }
+                _ => EclipseClientState::decode(&*data)?,
+            };
+            let wasm_latest_height = latest_height
+                .map(Height::try_from)
+                .transpose()
+                .map_err(|err| anyhow!("Invalid latest height in wasm client state: {err}"))?;
+
+            Ok(CounterpartyClientState {
+                client_state,
+                wasm_latest_height,
+            })
+        }
+        type_url => bail!(
+            "Client state has type {type_url}, which is neither \
+             {ECLIPSE_CLIENT_STATE_TYPE_URL} nor {WASM_CLIENT_STATE_TYPE_URL}"
+        ),
+    }
+}
+
+/// Compares a counterparty's client of this chain against this chain's info.
+///
+/// Proof specs and the commitment prefix are not recorded in the Eclipse client
+/// state; they are fixed by the light client code, so a client that decodes here
+/// uses `eclipse_chain::proof_specs` as long as it runs the same light client.
+pub(crate) fn compare(
+    CounterpartyClientState {
+        client_state,
+        wasm_latest_height,
+    }: &CounterpartyClientState,
+    chain_info: &ChainInfo,
+) -> CompatReport {
+    let mut mismatches = Vec::new();
+    let mut warnings = Vec::new();
+
+    if client_state.chain_id != chain_info.chain_id {
+        mismatches.push(format!(
+            "chain id is {}, expected {}",
+            client_state.chain_id, chain_info.chain_id
+        ));
+    }
+
+    let latest_height = client_state.latest_header.height;
+    if latest_height.revision_number() != chain_info.host_height.revision_number() {
+        mismatches.push(format!(
+            "latest height has revision {}, expected {}",
+            latest_height.revision_number(),
+            chain_info.host_height.revision_number()
+        ));
+    }
+    if client_state.chain_id.version() != chain_info.host_height.revision_number() {
+        mismatches.push(format!(
+            "chain id has revision {}, expected {}",
+            client_state.chain_id.version(),
+            chain_info.host_height.revision_number()
+        ));
+    }
+    if latest_height > chain_info.host_height {
+        mismatches.push(format!(
+            "latest height {latest_height} is ahead of the host height {}",
+            chain_info.host_height
+        ));
+    }
+
+    match client_state.genesis_hash {
+        Some(genesis_hash) if genesis_hash != chain_info.genesis_hash => {
+            mismatches.push(format!(
+                "genesis hash is {genesis_hash}, expected {}",
+                chain_info.genesis_hash
+            ));
+        }
+        Some(_) => {}
+        None => warnings.push(
+            "client does not record a genesis hash, so the cluster it tracks cannot be checked"
+                .to_owned(),
+        ),
+    }
+
+    match &chain_info.commitment_root {
+        Some(commitment_root) if *commitment_root != client_state.latest_header.commitment_root => {
+            mismatches.push(format!(
+                "commitment root at height {latest_height} is {}, expected {}",
+                hex::encode(client_state.latest_header.commitment_root.as_bytes()),
+                hex::encode(commitment_root.as_bytes())
+            ));
+        }
+        Some(_) => {}
+        None => warnings.push(format!(
+            "commitment root at height {latest_height} is no longer available to compare"
+        )),
+    }
+
+    if let Some(frozen_height) = client_state.frozen_height {
+        mismatches.push(format!("client is frozen at height {frozen_height}"));
+    }
+
+    if let Some(wasm_latest_height) = wasm_latest_height {
+        if *wasm_latest_height != latest_height {
+            mismatches.push(format!(
+                "wasm client reports latest height {wasm_latest_height}, but the wrapped client \
+                 state has {latest_height}"
+            ));
+        }
+    }
+
+    CompatReport {
+        compatible: mismatches.is_empty(),
+        mismatches,
+        warnings,
+    }
+}
+
+/// Fetches a client state from a Cosmos chain's gRPC endpoint.
+pub(crate) async fn query_client_state(
+    grpc_endpoint: String,
+    client_id: String,
+) -> anyhow::Result<Any> {
+    let mut query_client = QueryClient::connect(grpc_endpoint).await?;
+    let response = query_client
+        .query_client_state(QueryClientStateRequest { client_id })
+        .await?
+        .into_inner();
+    response
+        .client_state
+        .ok_or_else(|| anyhow!("Counterparty returned no client state"))
+}
+
+/// Fetches a Cosmos counterparty's client of this chain and compares it against
+/// this chain, named `chain_name` as when the client was created.
+pub(crate) async fn check_counterparty_client(
+    rpc_client: &RpcClient,
+    grpc_endpoint: String,
+    client_id: &ClientId,
+    chain_name: &str,
+) -> anyhow::Result<CompatReport> {
+    let any = query_client_state(grpc_endpoint, client_id.to_string()).await?;
+    let counterparty_client_state = decode_counterparty_client_state(any)?;
+
+    let latest_height = counterparty_client_state.client_state.latest_header.height;
+    // The state at the client's latest height may have been pruned or never
+    // existed; `compare` reports that as a warning.
+    let commitment_root = chain_state::get_consensus_state(rpc_client, latest_height)
+        .await
+        .ok()
+        .map(|consensus_state| consensus_state.commitment_root);
+    let host_height =
+        chain_state::get_host_height(rpc_client, rpc_client.get_slot().await?).await?;
+    let chain_info = ChainInfo {
+        chain_id: eclipse_chain::chain_id_with_revision(chain_name, host_height.revision_number()),
+        genesis_hash: rpc_client.get_genesis_hash().await?,
+        host_height,
+        commitment_root,
+    };
+
+    Ok(compare(&counterparty_client_state, &chain_info))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, eclipse_ibc_light_client::EclipseHeader, tendermint::time::Time as TendermintTime,
+    };
+
+    fn root(byte: u8) -> CommitmentRoot {
+        CommitmentRoot::from_bytes(&[byte; 32])
+    }
+
+    fn chain_info() -> ChainInfo {
+        ChainInfo {
+            chain_id: eclipse_chain::chain_id("test"),
+            genesis_hash: Hash::new_from_array([1; 32]),
+            host_height: Height::new(0, 100).unwrap(),
+            commitment_root: Some(root(7)),
+        }
+    }
+
+    fn client_state() -> EclipseClientState {
+        EclipseClientState {
+            chain_id: eclipse_chain::chain_id("test"),
+            latest_header: EclipseHeader {
+                height: Height::new(0, 50).unwrap(),
+                commitment_root: root(7),
+                timestamp: TendermintTime::unix_epoch(),
+            },
+            frozen_height: None,
+            genesis_hash: Some(Hash::new_from_array([1; 32])),
+            revision_number: eclipse_chain::DEFAULT_REVISION_NUMBER,
+            valid_duration: None,
+        }
+    }
+
+    fn counterparty(client_state: EclipseClientState) -> CounterpartyClientState {
+        CounterpartyClientState {
+            client_state,
+            wasm_latest_height: None,
+        }
+    }
+
+    #[test]
+    fn matching_client_is_compatible() {
+        let report = compare(&counterparty(client_state()), &chain_info());
+        assert!(report.compatible, "{report:?}");
+        assert!(report.warnings.is_empty(), "{report:?}");
+    }
+
+    #[test]
+    fn mismatches_are_reported() {
+        let mut client_state = client_state();
+        client_state.chain_id = eclipse_chain::chain_id("other");
+        client_state.genesis_hash = Some(Hash::new_from_array([2; 32]));
+        client_state.latest_header.commitment_root = root(8);
+        client_state.frozen_height = Some(Height::new(0, 40).unwrap());
+        let report = compare(
+            &CounterpartyClientState {
+                client_state,
+                wasm_latest_height: Some(Height::new(0, 49).unwrap()),
+            },
+            &chain_info(),
+        );
+
+        assert!(!report.compatible);
+        let expected = [
+            "chain id",
+            "genesis hash",
+            "commitment root",
+            "frozen",
+            "wasm client",
+        ];
+        assert_eq!(report.mismatches.len(), expected.len(), "{report:?}");
+        for (mismatch, expected) in report.mismatches.iter().zip(expected) {
+            assert!(mismatch.contains(expected), "{mismatch}");
+        }
+    }
+
+    #[test]
+    fn wrong_revision_and_future_height_are_reported() {
+        let mut client_state = client_state();
+        client_state.chain_id = ChainId::new("eclipse-test".to_owned(), 1);
+        client_state.latest_header.height = Height::new(1, 200).unwrap();
+        let report = compare(&counterparty(client_state), &chain_info());
+
+        assert!(!report.compatible);
+        assert_eq!(report.mismatches.len(), 4, "{report:?}");
+        assert!(report.mismatches[1].contains("latest height has revision 1"));
+        assert!(report.mismatches[2].contains("chain id has revision 1"));
+        assert!(report.mismatches[3].contains("ahead of the host height"));
+    }
+
+    #[test]
+    fn missing_context_is_a_warning() {
+        let mut client_state = client_state();
+        client_state.genesis_hash = None;
+        let chain_info = ChainInfo {
+            commitment_root: None,
+            ..chain_info()
+        };
+        let report = compare(&counterparty(client_state), &chain_info);
+
+        assert!(report.compatible, "{report:?}");
+        assert_eq!(report.warnings.len(), 2, "{report:?}");
+    }
+
+    #[test]
+    fn wasm_wrapped_client_state_is_decoded() {
+        let client_state = client_state();
+        let wasm = RawWasmClientState {
+            data: client_state.clone().encode(),
+            checksum: vec![0; 32],
+            latest_height: Some(client_state.latest_header.height.into()),
+        };
+        let decoded = decode_counterparty_client_state(Any {
+            type_url: WASM_CLIENT_STATE_TYPE_URL.to_owned(),
+            value: wasm.encode_to_vec(),
+        })
+        .unwrap();
+        assert_eq!(decoded.client_state, client_state);
+        assert_eq!(
+            decoded.wasm_latest_height,
+            Some(client_state.latest_header.height)
+        );
+
+        decode_counterparty_client_state(Any {
+            type_url: "/ibc.lightclients.tendermint.v1.ClientState".to_owned(),
+            value: vec![],
+        })
+        .unwrap_err();
+    }
+}
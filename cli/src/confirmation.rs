@@ -0,0 +1,333 @@
+//! Waiting for a sent transaction to be confirmed. [`Confirmation`] decides what
+//! became of the transaction from the events [`confirm_transaction`] feeds it,
+//! signature notifications and status polls, so that the decisions can be tested
+//! without a cluster.
+
+use {
+    futures::StreamExt as _,
+    log::warn,
+    serde::Serialize,
+    solana_client::{
+        nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+        rpc_config::RpcSignatureSubscribeConfig,
+        rpc_response::{ProcessedSignatureResult, RpcSignatureResult},
+    },
+    solana_sdk::{signature::Signature, transaction::TransactionError},
+    std::{future, time::Duration},
+};
+
+/// How long to wait for a transaction to be confirmed when no timeout is given.
+pub(crate) const DEFAULT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often the status of the transaction and the block height are polled,
+/// in case a notification is missed or the websocket endpoint is unavailable.
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Status of a signature, from a notification or a status poll.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum SignatureStatus {
+    /// Unknown to the node
+    NotFound,
+    /// Processed, but not yet at the commitment being waited for
+    Processing,
+    /// Landed at the commitment being waited for, with the transaction's result
+    Landed(Result<(), TransactionError>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ConfirmationEvent {
+    Status(SignatureStatus),
+    /// The block height passed the last one the transaction's blockhash is
+    /// valid for, so it can no longer be included in a block
+    BlockhashExpired,
+    DeadlineReached,
+}
+
+/// What became of a transaction.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TxOutcome {
+    Confirmed,
+    /// Landed, but failed
+    Failed(TransactionError),
+    /// Not found after its blockhash expired, so it will never land and can be
+    /// sent again with a new blockhash
+    Dropped,
+    /// Neither confirmed nor known to be dropped by the deadline, so it may
+    /// still land
+    Unknown,
+}
+
+/// Decides what became of a transaction from what is seen of it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Confirmation {
+    blockhash_expired: bool,
+    seen: bool,
+    outcome: Option<TxOutcome>,
+}
+
+impl Confirmation {
+    /// Takes in `event`, returning the outcome once it is settled. Events after
+    /// that do not change it.
+    pub(crate) fn on_event(&mut self, event: ConfirmationEvent) -> Option<TxOutcome> {
+        if self.outcome.is_none() {
+            self.outcome = self.settle(event);
+        }
+        self.outcome.clone()
+    }
+
+    fn settle(&mut self, event: ConfirmationEvent) -> Option<TxOutcome> {
+        match event {
+            ConfirmationEvent::Status(SignatureStatus::Landed(Ok(()))) => {
+                Some(TxOutcome::Confirmed)
+            }
+            ConfirmationEvent::Status(SignatureStatus::Landed(Err(err))) => {
+                Some(TxOutcome::Failed(err))
+            }
+            ConfirmationEvent::Status(SignatureStatus::Processing) => {
+                self.seen = true;
+                None
+            }
+            // Only a lookup made after the blockhash expired shows that the
+            // transaction can never land. One that was processed may have been on
+            // a fork that is yet to be settled, so it is left ambiguous.
+            ConfirmationEvent::Status(SignatureStatus::NotFound) => {
+                (self.blockhash_expired && !self.seen).then_some(TxOutcome::Dropped)
+            }
+            ConfirmationEvent::BlockhashExpired => {
+                self.blockhash_expired = true;
+                None
+            }
+            ConfirmationEvent::DeadlineReached => Some(TxOutcome::Unknown),
+        }
+    }
+}
+
+/// Websocket endpoint of the RPC node at `rpc_url`, on the next port as the
+/// Solana CLI assumes.
+pub(crate) fn websocket_url(rpc_url: &str) -> String {
+    let (scheme, rest) = rpc_url.split_once("://").unwrap_or(("http", rpc_url));
+    let ws_scheme = if scheme == "https" { "wss" } else { "ws" };
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let authority = match authority.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => format!("{host}:{}", port.saturating_add(1)),
+            // The colon of an IPv6 address without a port
+            Err(_) => authority.to_owned(),
+        },
+        None => authority.to_owned(),
+    };
+    format!("{ws_scheme}://{authority}{path}")
+}
+
+fn notification_status(result: RpcSignatureResult) -> SignatureStatus {
+    match result {
+        RpcSignatureResult::ProcessedSignature(ProcessedSignatureResult { err }) => {
+            SignatureStatus::Landed(err.map_or(Ok(()), Err))
+        }
+        RpcSignatureResult::ReceivedSignature(_) => SignatureStatus::Processing,
+    }
+}
+
+/// Polls the block height, then the status of `signature`, in that order so that
+/// a status is only taken as final once the blockhash has expired. Failed
+/// requests are skipped, since the next poll makes up for them.
+async fn poll_events(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+    last_valid_block_height: u64,
+) -> Vec<ConfirmationEvent> {
+    let mut events = vec![];
+    match rpc_client.get_block_height().await {
+        Ok(block_height) if block_height > last_valid_block_height => {
+            events.push(ConfirmationEvent::BlockhashExpired);
+        }
+        Ok(_) => {}
+        Err(err) => warn!("Failed to get the block height: {err}"),
+    }
+    match rpc_client.get_signature_statuses(&[*signature]).await {
+        Ok(response) => {
+            let status = match response.value.into_iter().next().flatten() {
+                None => SignatureStatus::NotFound,
+                Some(status) if status.satisfies_commitment(rpc_client.commitment()) => {
+                    SignatureStatus::Landed(status.err.map_or(Ok(()), Err))
+                }
+                Some(_) => SignatureStatus::Processing,
+            };
+            events.push(ConfirmationEvent::Status(status));
+        }
+        Err(err) => warn!("Failed to get the status of transaction {signature}: {err}"),
+    }
+    events
+}
+
+/// Waits up to `timeout` for the transaction `signature`, whose blockhash is
+/// valid up to `last_valid_block_height`, to land at the commitment of
+/// `rpc_client`. Notifications of the signature over the websocket endpoint
+/// settle it as soon as it lands, while polling its status settles it without
+/// them.
+pub(crate) async fn confirm_transaction(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+    last_valid_block_height: u64,
+    timeout: Duration,
+) -> TxOutcome {
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+
+    let ws_url = websocket_url(&rpc_client.url());
+    let pubsub_client = match PubsubClient::new(&ws_url).await {
+        Ok(pubsub_client) => Some(pubsub_client),
+        Err(err) => {
+            warn!("Failed to connect to {ws_url}, polling for confirmation instead: {err}");
+            None
+        }
+    };
+    let mut subscription = match &pubsub_client {
+        Some(pubsub_client) => {
+            let config = RpcSignatureSubscribeConfig {
+                commitment: Some(rpc_client.commitment()),
+                enable_received_notification: Some(false),
+            };
+            match pubsub_client
+                .signature_subscribe(signature, Some(config))
+                .await
+            {
+                Ok(subscription) => Some(subscription),
+                Err(err) => {
+                    warn!("Failed to subscribe to transaction {signature}: {err}");
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    let mut confirmation = Confirmation::default();
+    let mut poll_interval = tokio::time::interval(STATUS_POLL_INTERVAL);
+    let outcome = loop {
+        let events = tokio::select! {
+            () = &mut deadline => vec![ConfirmationEvent::DeadlineReached],
+            Some(notification) = async {
+                match &mut subscription {
+                    Some((notifications, _)) => notifications.next().await,
+                    None => future::pending().await,
+                }
+            } => {
+                vec![ConfirmationEvent::Status(notification_status(notification.value))]
+            }
+            _ = poll_interval.tick() => {
+                poll_events(rpc_client, signature, last_valid_block_height).await
+            }
+        };
+        if let Some(outcome) = events
+            .into_iter()
+            .find_map(|event| confirmation.on_event(event))
+        {
+            break outcome;
+        }
+    };
+
+    if let Some((notifications, unsubscribe)) = subscription {
+        drop(notifications);
+        unsubscribe().await;
+    }
+    if let Some(pubsub_client) = pubsub_client {
+        if let Err(err) = pubsub_client.shutdown().await {
+            warn!("Failed to close the websocket connection to {ws_url}: {err}");
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::instruction::InstructionError};
+
+    fn outcome_of(events: impl IntoIterator<Item = ConfirmationEvent>) -> Option<TxOutcome> {
+        let mut confirmation = Confirmation::default();
+        events
+            .into_iter()
+            .find_map(|event| confirmation.on_event(event))
+    }
+
+    #[test]
+    fn landed_transactions_settle_as_soon_as_they_land() {
+        use {ConfirmationEvent::*, SignatureStatus::*};
+
+        assert_eq!(
+            outcome_of([Status(NotFound), Status(Processing), Status(Landed(Ok(())))]),
+            Some(TxOutcome::Confirmed)
+        );
+        let err = TransactionError::InstructionError(0, InstructionError::Custom(0xa0));
+        assert_eq!(
+            outcome_of([Status(Landed(Err(err.clone())))]),
+            Some(TxOutcome::Failed(err))
+        );
+        // Even after the blockhash expired
+        assert_eq!(
+            outcome_of([BlockhashExpired, Status(Landed(Ok(())))]),
+            Some(TxOutcome::Confirmed)
+        );
+    }
+
+    #[test]
+    fn only_transactions_not_found_after_blockhash_expiry_are_dropped() {
+        use {ConfirmationEvent::*, SignatureStatus::*};
+
+        assert_eq!(outcome_of([Status(NotFound), Status(NotFound)]), None);
+        assert_eq!(
+            outcome_of([Status(NotFound), BlockhashExpired, Status(NotFound)]),
+            Some(TxOutcome::Dropped)
+        );
+        // A processed transaction may still be confirmed on another fork
+        assert_eq!(
+            outcome_of([
+                Status(Processing),
+                BlockhashExpired,
+                Status(NotFound),
+                DeadlineReached
+            ]),
+            Some(TxOutcome::Unknown)
+        );
+    }
+
+    #[test]
+    fn transactions_unsettled_by_the_deadline_are_unknown() {
+        use {ConfirmationEvent::*, SignatureStatus::*};
+
+        assert_eq!(
+            outcome_of([Status(NotFound), DeadlineReached]),
+            Some(TxOutcome::Unknown)
+        );
+        assert_eq!(
+            outcome_of([BlockhashExpired, DeadlineReached]),
+            Some(TxOutcome::Unknown)
+        );
+
+        // The first outcome sticks
+        let mut confirmation = Confirmation::default();
+        confirmation.on_event(DeadlineReached);
+        assert_eq!(
+            confirmation.on_event(Status(Landed(Ok(())))),
+            Some(TxOutcome::Unknown)
+        );
+    }
+
+    #[test]
+    fn websocket_url_is_on_the_next_port() {
+        assert_eq!(
+            websocket_url("http://127.0.0.1:8899"),
+            "ws://127.0.0.1:8900"
+        );
+        assert_eq!(
+            websocket_url("https://api.devnet.solana.com"),
+            "wss://api.devnet.solana.com"
+        );
+        assert_eq!(
+            websocket_url("https://rpc.example.com:443/key"),
+            "wss://rpc.example.com:444/key"
+        );
+        assert_eq!(websocket_url("http://[::1]:8899"), "ws://[::1]:8900");
+    }
+}
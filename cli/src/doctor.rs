@@ -0,0 +1,555 @@
+use {
+    crate::{chain_state, client_compat},
+    anyhow::Context as _,
+    clap::{Parser, ValueEnum},
+    eclipse_ibc_state::{IbcAccountData, IbcMetadata},
+    ibc::core::ics24_host::identifier::ClientId,
+    serde::Serialize,
+    solana_client::nonblocking::rpc_client::RpcClient,
+    std::{
+        fmt,
+        future::Future,
+        io::{self, Write as _},
+        process,
+    },
+};
+
+/// Result of a single check, from best to worst. A check that could not run at
+/// all is an error rather than a failure, since it says nothing about the chain,
+/// but a failure found by another check still outranks it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Pass,
+    Skip,
+    Warn,
+    Error,
+    Fail,
+}
+
+impl Status {
+    /// Exit code of a report whose worst check has this status.
+    fn exit_code(self) -> i32 {
+        match self {
+            Self::Pass | Self::Skip => 0,
+            Self::Warn => 1,
+            Self::Fail => 2,
+            Self::Error => 3,
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            Self::Pass => "PASS",
+            Self::Skip => "SKIP",
+            Self::Warn => "WARN",
+            Self::Error => "ERROR",
+            Self::Fail => "FAIL",
+        };
+        f.pad(status)
+    }
+}
+
+/// What a check found, before it is named in the report.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Outcome {
+    status: Status,
+    details: Vec<String>,
+}
+
+impl Outcome {
+    fn pass() -> Self {
+        Self {
+            status: Status::Pass,
+            details: vec![],
+        }
+    }
+
+    fn new(status: Status, details: Vec<String>) -> Self {
+        Self { status, details }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+struct CheckReport {
+    name: &'static str,
+    status: Status,
+    details: Vec<String>,
+}
+
+impl CheckReport {
+    /// A check that returned an error could not run, e.g. because an endpoint was
+    /// unreachable; the remaining checks still run.
+    fn new(name: &'static str, outcome: anyhow::Result<Outcome>) -> Self {
+        let Outcome { status, details } = outcome.unwrap_or_else(|err| Outcome {
+            status: Status::Error,
+            details: vec![format!("{err:#}")],
+        });
+        Self {
+            name,
+            status,
+            details,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+struct DoctorReport {
+    status: Status,
+    checks: Vec<CheckReport>,
+}
+
+impl DoctorReport {
+    fn new(checks: Vec<CheckReport>) -> Self {
+        let status = checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(Status::Pass);
+        Self { status, checks }
+    }
+
+    fn exit_code(&self) -> i32 {
+        self.status.exit_code()
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for CheckReport {
+            name,
+            status,
+            details,
+        } in &self.checks
+        {
+            writeln!(f, "{status:<5} {name}")?;
+            for detail in details {
+                writeln!(f, "      {detail}")?;
+            }
+        }
+        write!(f, "overall: {}", self.status)
+    }
+}
+
+async fn run_check<F>(name: &'static str, check: F) -> CheckReport
+where
+    F: Future<Output = anyhow::Result<Outcome>>,
+{
+    CheckReport::new(name, check.await)
+}
+
+/// The storage account exists, is owned by the IBC program and was fully
+/// initialized.
+async fn check_storage_account(rpc_client: &RpcClient) -> anyhow::Result<Outcome> {
+    let account = rpc_client
+        .get_account(&eclipse_ibc_program::STORAGE_KEY)
+        .await?;
+    if account.owner != eclipse_ibc_program::id() {
+        return Ok(Outcome::new(
+            Status::Fail,
+            vec![format!("storage account is owned by {}", account.owner)],
+        ));
+    }
+    Ok(match IbcAccountData::check_initialized(&account.data) {
+        Ok(()) => Outcome::pass(),
+        Err(err) => Outcome::new(Status::Fail, vec![format!("{err:#}")]),
+    })
+}
+
+/// The IBC store matches its latest root, as the program checks on load, and the
+/// metadata's commit checksum matches the store.
+async fn check_store_invariants(rpc_client: &RpcClient) -> anyhow::Result<Outcome> {
+    let raw_account_data = rpc_client
+        .get_account_data(&eclipse_ibc_program::STORAGE_KEY)
+        .await?;
+    let ibc_account_data = IbcAccountData::from_bytes(&raw_account_data)?;
+    let details = [
+        ibc_account_data.verify_latest_root(),
+        ibc_account_data.verify_checksum(),
+    ]
+    .into_iter()
+    .filter_map(|result| Some(format!("{:#}", result.err()?)))
+    .collect::<Vec<_>>();
+    Ok(if details.is_empty() {
+        Outcome::pass()
+    } else {
+        Outcome::new(Status::Fail, details)
+    })
+}
+
+/// IBC is not frozen and no messages are disabled.
+async fn check_chain_params(rpc_client: &RpcClient) -> anyhow::Result<Outcome> {
+    let chain_params = chain_state::get_chain_params(rpc_client).await?;
+    let mut details = vec![];
+    if chain_params.frozen {
+        details.push("IBC is frozen until an admin unfreezes it".to_owned());
+    }
+    if !chain_params.disabled_messages.is_empty() {
+        details.push(format!(
+            "messages are disabled: {}",
+            chain_params.disabled_messages.join(", ")
+        ));
+    }
+    Ok(if details.is_empty() {
+        Outcome::pass()
+    } else {
+        Outcome::new(Status::Warn, details)
+    })
+}
+
+/// A Cosmos counterparty's client of this chain tracks this chain.
+async fn check_proof_spec_compat(
+    rpc_client: &RpcClient,
+    counterparty: Option<(String, ClientId, String)>,
+) -> anyhow::Result<Outcome> {
+    let (grpc_endpoint, client_id, chain_name) = match counterparty {
+        Some(counterparty) => counterparty,
+        None => {
+            return Ok(Outcome::new(
+                Status::Skip,
+                vec![
+                    "needs --counterparty-grpc-endpoint, --counterparty-client-id and \
+                     --chain-name"
+                        .to_owned(),
+                ],
+            ))
+        }
+    };
+
+    let report = client_compat::check_counterparty_client(
+        rpc_client,
+        grpc_endpoint,
+        &client_id,
+        &chain_name,
+    )
+    .await?;
+    let status = if !report.compatible {
+        Status::Fail
+    } else if !report.warnings.is_empty() {
+        Status::Warn
+    } else {
+        Status::Pass
+    };
+    Ok(Outcome::new(
+        status,
+        [report.mismatches, report.warnings].concat(),
+    ))
+}
+
+/// Every RPC endpoint holds the same IBC state, as far as the metadata of their
+/// storage accounts tells.
+async fn check_endpoint_agreement(endpoints: &[String]) -> anyhow::Result<Outcome> {
+    if endpoints.len() < 2 {
+        return Ok(Outcome::new(
+            Status::Skip,
+            vec!["needs at least two endpoints in --compare-endpoints".to_owned()],
+        ));
+    }
+
+    let mut metadata_by_endpoint = Vec::with_capacity(endpoints.len());
+    for endpoint in endpoints {
+        let rpc_client = RpcClient::new(endpoint.clone());
+        let metadata = chain_state::get_ibc_metadata(&rpc_client)
+            .await
+            .with_context(|| format!("Could not fetch the IBC metadata from {endpoint}"))?;
+        metadata_by_endpoint.push((endpoint.as_str(), metadata));
+    }
+    Ok(compare_metadata(&metadata_by_endpoint))
+}
+
+/// Fields of the metadata that the commit checksum covers, along with the
+/// checksum itself, as they are reported.
+fn metadata_fields(metadata: &IbcMetadata) -> [(&'static str, String); 7] {
+    [
+        ("checksum", format!("{:016x}", metadata.checksum)),
+        ("write_sequence", metadata.write_sequence.to_string()),
+        ("root_hash", hex::encode(metadata.root_hash)),
+        ("client_id_counter", metadata.client_id_counter.to_string()),
+        (
+            "connection_id_counter",
+            metadata.connection_id_counter.to_string(),
+        ),
+        (
+            "channel_id_counter",
+            metadata.channel_id_counter.to_string(),
+        ),
+        ("revision_number", metadata.revision_number.to_string()),
+    ]
+}
+
+/// Compares the metadata of every endpoint against the first one's. Endpoints at
+/// another write sequence may only be behind, which is a warning, but differing
+/// at the same write sequence means their states diverged.
+fn compare_metadata(metadata_by_endpoint: &[(&str, IbcMetadata)]) -> Outcome {
+    let mut status = Status::Pass;
+    let mut details = vec![];
+    for (endpoint, metadata) in metadata_by_endpoint {
+        if metadata.has_checksum() && metadata.checksum != metadata.compute_checksum() {
+            status = Status::Fail;
+            details.push(format!(
+                "{endpoint}: checksum {:016x} does not match the fields it covers, which hash \
+                 to {:016x}",
+                metadata.checksum,
+                metadata.compute_checksum()
+            ));
+        }
+    }
+
+    let (reference_endpoint, reference) = match metadata_by_endpoint.split_first() {
+        Some(((endpoint, metadata), _)) => (endpoint, metadata),
+        None => return Outcome::pass(),
+    };
+    let reference_fields = metadata_fields(reference);
+    for (endpoint, metadata) in &metadata_by_endpoint[1..] {
+        let mut diverged = false;
+        for ((field, value), (_, reference_value)) in
+            metadata_fields(metadata).iter().zip(&reference_fields)
+        {
+            if value != reference_value {
+                diverged = true;
+                details.push(format!(
+                    "{endpoint}: {field} is {value}, but {reference_endpoint} has \
+                     {reference_value}"
+                ));
+            }
+        }
+        if diverged {
+            status = status.max(if metadata.write_sequence == reference.write_sequence {
+                Status::Fail
+            } else {
+                Status::Warn
+            });
+        }
+    }
+    Outcome::new(status, details)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct Args {
+    /// Endpoint to send a request to
+    #[arg(long, default_value = "http://127.0.0.1:8899")]
+    endpoint: String,
+
+    /// gRPC endpoint of a Cosmos counterparty whose client of this chain to check
+    #[arg(long, requires_all = ["counterparty_client_id", "chain_name"])]
+    counterparty_grpc_endpoint: Option<String>,
+
+    /// Client ID the counterparty assigned to this chain
+    #[arg(long, requires = "counterparty_grpc_endpoint")]
+    counterparty_client_id: Option<ClientId>,
+
+    /// Name of this chain, as passed to `generate client create`
+    #[arg(long, requires = "counterparty_grpc_endpoint")]
+    chain_name: Option<String>,
+
+    /// Comma-separated RPC endpoints, such as those of every node of an operator,
+    /// whose IBC metadata to compare. Only the metadata is fetched from each.
+    #[arg(long, value_delimiter = ',')]
+    compare_endpoints: Vec<String>,
+
+    /// Output format of the report
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+}
+
+/// Runs every health check and prints a single report. The process exits with 0
+/// if every check passed or was skipped, 1 on warnings, 2 on failures and 3 if a
+/// check could not run but none failed.
+pub(crate) async fn run(
+    Args {
+        endpoint,
+        counterparty_grpc_endpoint,
+        counterparty_client_id,
+        chain_name,
+        compare_endpoints,
+        output,
+    }: Args,
+) -> anyhow::Result<()> {
+    let rpc_client = RpcClient::new(endpoint);
+    let counterparty = counterparty_grpc_endpoint
+        .zip(counterparty_client_id)
+        .zip(chain_name)
+        .map(|((grpc_endpoint, client_id), chain_name)| (grpc_endpoint, client_id, chain_name));
+
+    let report = DoctorReport::new(vec![
+        run_check("storage-account", check_storage_account(&rpc_client)).await,
+        run_check("store-invariants", check_store_invariants(&rpc_client)).await,
+        run_check("chain-params", check_chain_params(&rpc_client)).await,
+        run_check(
+            "proof-spec-compat",
+            check_proof_spec_compat(&rpc_client, counterparty),
+        )
+        .await,
+        run_check(
+            "endpoint-agreement",
+            check_endpoint_agreement(&compare_endpoints),
+        )
+        .await,
+    ]);
+
+    match output {
+        OutputFormat::Text => writeln!(io::stdout(), "{report}")?,
+        OutputFormat::Json => {
+            let json_str = colored_json::to_colored_json_auto(&serde_json::to_value(&report)?)?;
+            writeln!(io::stdout(), "{json_str}")?;
+        }
+    }
+
+    let exit_code = report.exit_code();
+    if exit_code != 0 {
+        io::stdout().flush()?;
+        process::exit(exit_code);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        anyhow::{anyhow, bail},
+    };
+
+    fn report(outcomes: Vec<anyhow::Result<Outcome>>) -> DoctorReport {
+        DoctorReport::new(
+            outcomes
+                .into_iter()
+                .map(|outcome| CheckReport::new("check", outcome))
+                .collect(),
+        )
+    }
+
+    fn outcome(status: Status) -> anyhow::Result<Outcome> {
+        Ok(Outcome::new(status, vec![]))
+    }
+
+    fn committed_metadata(root_byte: u8, channels: u64) -> IbcMetadata {
+        let mut metadata = IbcMetadata {
+            channel_id_counter: channels,
+            ..IbcMetadata::default()
+        };
+        metadata.record_commit(jmt::RootHash([root_byte; 32]));
+        metadata
+    }
+
+    #[test]
+    fn worst_status_decides_the_exit_code() {
+        assert_eq!(report(vec![]).exit_code(), 0);
+        assert_eq!(
+            report(vec![outcome(Status::Pass), outcome(Status::Skip)]).exit_code(),
+            0
+        );
+        assert_eq!(
+            report(vec![outcome(Status::Pass), outcome(Status::Warn)]).exit_code(),
+            1
+        );
+        assert_eq!(
+            report(vec![outcome(Status::Warn), outcome(Status::Fail)]).exit_code(),
+            2
+        );
+        assert_eq!(
+            report(vec![outcome(Status::Warn), outcome(Status::Error)]).exit_code(),
+            3
+        );
+        assert_eq!(
+            report(vec![outcome(Status::Fail), outcome(Status::Error)]).exit_code(),
+            2
+        );
+    }
+
+    #[test]
+    fn checks_that_cannot_run_are_errors() {
+        let report = report(vec![
+            outcome(Status::Pass),
+            Err(anyhow!("connection refused").context("Could not fetch the storage account")),
+        ]);
+
+        assert_eq!(report.status, Status::Error);
+        assert_eq!(report.checks[0].status, Status::Pass);
+        assert_eq!(
+            report.checks[1].details,
+            ["Could not fetch the storage account: connection refused"]
+        );
+    }
+
+    #[test]
+    fn endpoint_metadata_divergence_is_reported_by_field() {
+        let metadata = committed_metadata(1, 2);
+        assert_eq!(
+            compare_metadata(&[("a", metadata.clone()), ("b", metadata.clone())]),
+            Outcome::pass()
+        );
+
+        // An endpoint one commit behind is only a warning
+        let mut behind = committed_metadata(1, 2);
+        let mut ahead = behind.clone();
+        ahead.record_commit(jmt::RootHash([2; 32]));
+        let outcome = compare_metadata(&[("a", ahead.clone()), ("b", behind.clone())]);
+        assert_eq!(outcome.status, Status::Warn);
+        assert!(outcome
+            .details
+            .iter()
+            .any(|detail| detail.starts_with("b: write_sequence is 1")));
+
+        // Another root at the same write sequence is a divergence
+        behind.record_commit(jmt::RootHash([3; 32]));
+        let outcome = compare_metadata(&[("a", ahead.clone()), ("b", behind)]);
+        assert_eq!(outcome.status, Status::Fail);
+        assert_eq!(
+            outcome
+                .details
+                .iter()
+                .map(|detail| detail.split(' ').take(2).collect::<Vec<_>>().join(" "))
+                .collect::<Vec<_>>(),
+            ["b: checksum", "b: root_hash"]
+        );
+
+        // So is a checksum that does not match its own metadata
+        let mut corrupted = ahead.clone();
+        corrupted.checksum ^= 1;
+        let outcome = compare_metadata(&[("a", ahead), ("b", corrupted)]);
+        assert_eq!(outcome.status, Status::Fail);
+        assert!(outcome.details[0].starts_with("b: checksum"));
+        assert!(outcome.details[0].contains("does not match"));
+    }
+
+    #[tokio::test]
+    async fn failing_check_does_not_stop_later_checks() {
+        let report = DoctorReport::new(vec![
+            run_check("first", async { bail!("unreachable") }).await,
+            run_check("second", async {
+                Ok(Outcome::new(Status::Warn, vec!["frozen".to_owned()]))
+            })
+            .await,
+        ]);
+
+        assert_eq!(
+            report
+                .checks
+                .iter()
+                .map(|check| check.status)
+                .collect::<Vec<_>>(),
+            [Status::Error, Status::Warn]
+        );
+        assert_eq!(
+            report.to_string(),
+            "ERROR first\n      unreachable\nWARN  second\n      frozen\noverall: ERROR"
+        );
+        assert_eq!(
+            serde_json::to_value(&report).unwrap()["checks"][1],
+            serde_json::json!({
+                "name": "second",
+                "status": "warn",
+                "details": ["frozen"],
+            })
+        );
+    }
+}
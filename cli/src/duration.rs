@@ -0,0 +1,244 @@
+//! Human-readable durations and timeouts for CLI flags and query output. Durations
+//! are written as a sequence of `<integer><unit>` terms such as `400ms`, `30s` or
+//! `1h30m`, and must fit in a `u64` nanosecond count since that is how IBC stores
+//! them on-chain.
+
+use {
+    anyhow::{anyhow, bail},
+    core::time::Duration,
+    serde_json::Value,
+    std::time::{SystemTime, UNIX_EPOCH},
+    tendermint::time::Time as TendermintTime,
+};
+
+const NANOS_PER_UNIT: [(&str, u64); 7] = [
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+/// Query output fields holding a bare nanosecond count.
+const NANOS_FIELDS: [&str; 1] = ["delay_period"];
+
+/// Query output fields holding a `google.protobuf.Duration`.
+const PROTO_DURATION_FIELDS: [&str; 3] = ["trusting_period", "unbonding_period", "max_clock_drift"];
+
+fn nanos_per_unit(unit: &str) -> Option<u64> {
+    NANOS_PER_UNIT
+        .iter()
+        .find_map(|&(name, nanos)| (name == unit).then_some(nanos))
+}
+
+/// Parses a duration such as `400ms`, `30s`, `14d` or `1h30m`.
+///
+/// # Errors
+/// Returns an error if a term is missing its number or unit, uses an unknown unit,
+/// or if the total does not fit in a `u64` nanosecond count.
+pub fn parse_duration(duration: &str) -> anyhow::Result<Duration> {
+    if duration.is_empty() {
+        bail!("duration cannot be empty");
+    }
+
+    let mut total_nanos: u64 = 0;
+    let mut rest = duration;
+    while !rest.is_empty() {
+        let digits_len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(|| anyhow!("duration is missing a unit: {duration}"))?;
+        if digits_len == 0 {
+            bail!("duration term must start with a number: {duration}");
+        }
+        let (digits, after_digits) = rest.split_at(digits_len);
+        let unit_len = after_digits
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(after_digits.len());
+        let (unit, after_unit) = after_digits.split_at(unit_len);
+
+        let nanos_per_unit = nanos_per_unit(unit)
+            .ok_or_else(|| anyhow!("unknown duration unit `{unit}` in {duration}"))?;
+        let overflow = || anyhow!("duration does not fit in u64 nanoseconds: {duration}");
+        let count: u64 = digits.parse().map_err(|_| overflow())?;
+        total_nanos = count
+            .checked_mul(nanos_per_unit)
+            .and_then(|nanos| total_nanos.checked_add(nanos))
+            .ok_or_else(overflow)?;
+
+        rest = after_unit;
+    }
+
+    Ok(Duration::from_nanos(total_nanos))
+}
+
+/// Parses a timeout as either an absolute RFC3339 timestamp or a duration relative
+/// to now, written with a leading `+` (e.g. `+10m`).
+///
+/// # Errors
+/// Returns an error if the timeout is neither a valid timestamp nor a valid
+/// relative duration.
+pub fn parse_timeout(timeout: &str) -> anyhow::Result<TendermintTime> {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    let now = TendermintTime::from_unix_timestamp(
+        since_epoch.as_secs().try_into()?,
+        since_epoch.subsec_nanos(),
+    )?;
+    parse_timeout_at(timeout, now)
+}
+
+fn parse_timeout_at(timeout: &str, now: TendermintTime) -> anyhow::Result<TendermintTime> {
+    match timeout.strip_prefix('+') {
+        Some(relative) => now
+            .checked_add(parse_duration(relative)?)
+            .ok_or_else(|| anyhow!("timeout is out of range: {timeout}")),
+        None => TendermintTime::parse_from_rfc3339(timeout).map_err(|err| {
+            anyhow!("timeout must be an RFC3339 timestamp or start with `+`: {timeout}: {err}")
+        }),
+    }
+}
+
+/// Formats a duration in the same form accepted by [`parse_duration`], e.g. `1h30m`.
+#[must_use]
+pub fn format_duration(duration: Duration) -> String {
+    let mut remaining_nanos = match u64::try_from(duration.as_nanos()) {
+        Ok(nanos) => nanos,
+        Err(_) => return format!("{}s", duration.as_secs()),
+    };
+    if remaining_nanos == 0 {
+        return "0s".to_owned();
+    }
+
+    let mut formatted = String::new();
+    for (unit, nanos_per_unit) in NANOS_PER_UNIT {
+        let count = remaining_nanos / nanos_per_unit;
+        if count > 0 {
+            formatted.push_str(&format!("{count}{unit}"));
+            remaining_nanos %= nanos_per_unit;
+        }
+    }
+    formatted
+}
+
+fn json_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(number) => number.as_u64(),
+        Value::String(string) => string.parse().ok(),
+        _ => None,
+    }
+}
+
+fn proto_duration(value: &Value) -> Option<Duration> {
+    let seconds = value.get("seconds").map_or(Some(0), json_u64)?;
+    let nanos = value.get("nanos").map_or(Some(0), json_u64)?;
+    Some(Duration::new(seconds, nanos.try_into().ok()?))
+}
+
+/// Rewrites known duration fields of a query result, which are stored as raw
+/// nanosecond counts or `google.protobuf.Duration`s, in human-readable form.
+pub fn humanize_durations(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                let duration = if NANOS_FIELDS.contains(&name.as_str()) {
+                    json_u64(field).map(Duration::from_nanos)
+                } else if PROTO_DURATION_FIELDS.contains(&name.as_str()) {
+                    proto_duration(field)
+                } else {
+                    None
+                };
+                match duration {
+                    Some(duration) => *field = Value::String(format_duration(duration)),
+                    None => humanize_durations(field),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(humanize_durations),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    #[test]
+    fn check_parse_duration() {
+        assert_eq!(parse_duration("400ms").unwrap(), Duration::from_millis(400));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(
+            parse_duration("14d").unwrap(),
+            Duration::from_secs(14 * 86_400)
+        );
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5_400));
+        assert_eq!(parse_duration("0s").unwrap(), Duration::ZERO);
+        assert_eq!(
+            parse_duration("5us7ns").unwrap(),
+            Duration::from_nanos(5_007)
+        );
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid_units() {
+        for invalid in ["", "30", "s", "30x", "30 s", "1.5s", "-1s", "30S", "1h30"] {
+            assert!(
+                parse_duration(invalid).is_err(),
+                "{invalid:?} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_duration_rejects_overflow() {
+        // u64::MAX nanoseconds is about 213503 days
+        assert!(parse_duration("213503d").is_ok());
+        assert!(parse_duration("213504d").is_err());
+        assert!(parse_duration("18446744073709551615ns").is_ok());
+        assert!(parse_duration("18446744073709551616ns").is_err());
+        assert!(parse_duration("18446744073709551615ns1ns").is_err());
+    }
+
+    #[test]
+    fn check_parse_timeout() {
+        let now = TendermintTime::parse_from_rfc3339("2023-06-01T00:00:00Z").unwrap();
+        assert_eq!(
+            parse_timeout_at("+10m", now).unwrap(),
+            TendermintTime::parse_from_rfc3339("2023-06-01T00:10:00Z").unwrap(),
+        );
+        assert_eq!(
+            parse_timeout_at("2023-06-02T12:00:00Z", now).unwrap(),
+            TendermintTime::parse_from_rfc3339("2023-06-02T12:00:00Z").unwrap(),
+        );
+        assert!(parse_timeout_at("10m", now).is_err());
+        assert!(parse_timeout_at("+10", now).is_err());
+        assert!(parse_timeout_at("tomorrow", now).is_err());
+    }
+
+    #[test]
+    fn format_duration_round_trips() {
+        for duration in ["0s", "400ms", "30s", "14d", "1h30m", "1d2h3m4s5ms6us7ns"] {
+            assert_eq!(format_duration(parse_duration(duration).unwrap()), duration);
+        }
+    }
+
+    #[test]
+    fn check_humanize_durations() {
+        let mut connection = json!({
+            "client_id": "07-tendermint-0",
+            "delay_period": 30_000_000_000_u64,
+            "counterparty": { "client_id": "eclipse-0" },
+        });
+        humanize_durations(&mut connection);
+        assert_eq!(connection["delay_period"], "30s");
+        assert_eq!(connection["client_id"], "07-tendermint-0");
+
+        let mut client_state = json!({
+            "trusting_period": { "seconds": 1_209_600, "nanos": 0 },
+            "max_clock_drift": { "seconds": 0, "nanos": 500_000_000 },
+        });
+        humanize_durations(&mut client_state);
+        assert_eq!(client_state["trusting_period"], "14d");
+        assert_eq!(client_state["max_clock_drift"], "500ms");
+    }
+}
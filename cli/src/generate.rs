@@ -1,29 +1,58 @@
 use {
-    crate::chain_state,
-    anyhow::{anyhow, bail},
-    clap::{Parser, Subcommand},
+    crate::{
+        address,
+        build_info::BuildInfo,
+        capabilities,
+        chain_state::{self, ClientStateParams},
+        duration,
+        proof::existence_proof_to_merkle_proof,
+        provenance::Provenance,
+        relay,
+    },
+    anyhow::{anyhow, bail, Context as _},
+    clap::{Parser, Subcommand, ValueEnum},
+    eclipse_ibc_extra_types::ChainParams,
     eclipse_ibc_known_proto::KnownAnyProto,
-    eclipse_ibc_light_client::eclipse_chain,
-    eclipse_ibc_state::{internal_path::ConsensusHeightsPath, IbcAccountData, IbcState, IbcStore},
+    eclipse_ibc_light_client::{
+        canonical_type_url,
+        eclipse_chain::{self, EclipseHeight},
+        EclipseClientState, EclipseHeader, EclipseHeaderBatch, EclipseMisbehaviour,
+        ECLIPSE_CLIENT_STATE_TYPE_URL, MAX_HEADERS_PER_BATCH,
+    },
+    eclipse_ibc_state::{
+        compute_ack_commitment, compute_packet_commitment, decode_client_state,
+        encode_client_state, tracked_chain_id, IbcAccountData, IbcState, IbcStore, LoadChainParams,
+    },
     ibc::core::{
         ics02_client::height::Height,
         ics03_connection::version::{get_compatible_versions, Version as ConnectionVersion},
-        ics24_host::path::{
-            ChannelEndPath, ClientConsensusStatePath, ClientStatePath, ConnectionPath,
+        ics04_channel::timeout::TimeoutHeight,
+        ics24_host::{
+            identifier::{ClientId, PortId},
+            path::{
+                AckPath, ChannelEndPath, ClientConsensusStatePath, ClientStatePath, CommitmentPath,
+                ConnectionPath, ReceiptPath, SeqRecvPath, UpgradeClientPath,
+            },
         },
+        timestamp::Timestamp,
     },
     ibc_proto::{
+        google::protobuf,
         ibc::core::{
             channel::v1::{
                 Channel as RawChannel, Counterparty as RawChannelCounterparty,
+                MsgAcknowledgement as RawMsgAcknowledgement,
                 MsgChannelOpenAck as RawMsgChannelOpenAck,
                 MsgChannelOpenConfirm as RawMsgChannelOpenConfirm,
                 MsgChannelOpenInit as RawMsgChannelOpenInit,
-                MsgChannelOpenTry as RawMsgChannelOpenTry, Order as RawOrder, State as RawState,
+                MsgChannelOpenTry as RawMsgChannelOpenTry, MsgRecvPacket as RawMsgRecvPacket,
+                MsgTimeout as RawMsgTimeout, MsgTimeoutOnClose as RawMsgTimeoutOnClose,
+                Order as RawOrder, Packet as RawPacket, State as RawState,
             },
             client::v1::{
-                MsgCreateClient as RawMsgCreateClient, MsgUpdateClient as RawMsgUpdateClient,
-                MsgUpgradeClient as RawMsgUpgradeClient,
+                MsgCreateClient as RawMsgCreateClient,
+                MsgSubmitMisbehaviour as RawMsgSubmitMisbehaviour,
+                MsgUpdateClient as RawMsgUpdateClient, MsgUpgradeClient as RawMsgUpgradeClient,
             },
             commitment::v1::{MerklePrefix as RawMerklePrefix, MerkleProof as RawMerkleProof},
             connection::v1::{
@@ -31,42 +60,86 @@ use {
                 MsgConnectionOpenAck as RawMsgConnectionOpenAck,
                 MsgConnectionOpenConfirm as RawMsgConnectionOpenConfirm,
                 MsgConnectionOpenInit as RawMsgConnectionOpenInit,
-                MsgConnectionOpenTry as RawMsgConnectionOpenTry,
+                MsgConnectionOpenTry as RawMsgConnectionOpenTry, State as RawConnectionState,
+                Version as RawConnectionVersion,
             },
         },
-        ics23::CommitmentProof as IbcRawCommitmentProof,
     },
-    ics23::{commitment_proof, CommitmentProof, ExistenceProof},
-    log::info,
+    log::{info, warn},
     prost::Message as _,
     serde::Serialize,
     solana_client::nonblocking::rpc_client::RpcClient,
-    std::io::{self, Write as _},
+    std::{
+        fs,
+        io::{self, Write as _},
+        path::{Path, PathBuf},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
 };
 
-const DELAY_PERIOD_NANOS: u64 = 0;
-
-async fn get_ibc_store(rpc_client: &RpcClient) -> anyhow::Result<IbcStore> {
+pub(crate) async fn get_ibc_store(rpc_client: &RpcClient) -> anyhow::Result<IbcStore> {
     let raw_account_data = rpc_client
         .get_account_data(&eclipse_ibc_program::STORAGE_KEY)
         .await?;
 
     let IbcAccountData {
         store: ibc_store, ..
-    } = bincode::deserialize(&raw_account_data)?;
+    } = IbcAccountData::from_bytes_verified(&raw_account_data)?;
 
     Ok(ibc_store)
 }
 
-fn get_ibc_state(ibc_store: &IbcStore) -> anyhow::Result<IbcState> {
+pub(crate) fn get_ibc_state(ibc_store: &IbcStore) -> anyhow::Result<IbcState> {
     let latest_version = ibc_store
-        .read()?
+        .read()
         .latest_version()
         .ok_or_else(|| anyhow!("IBC store is missing latest version"))?;
 
     Ok(IbcState::new(ibc_store, latest_version))
 }
 
+/// Where a chain's IBC storage account is read from: a live endpoint, or a file
+/// holding the account data, such as one written by `solana account
+/// <STORAGE_KEY> --output-file`, for generating messages without network access.
+enum StoreSource {
+    Rpc(RpcClient),
+    File(PathBuf),
+}
+
+impl StoreSource {
+    fn new(endpoint: String, file: Option<PathBuf>) -> Self {
+        match file {
+            Some(file) => Self::File(file),
+            None => Self::Rpc(RpcClient::new(endpoint)),
+        }
+    }
+
+    async fn ibc_store(&self) -> anyhow::Result<IbcStore> {
+        match self {
+            Self::Rpc(rpc_client) => get_ibc_store(rpc_client).await,
+            Self::File(path) => {
+                let raw_account_data =
+                    fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+                let IbcAccountData {
+                    store: ibc_store, ..
+                } = IbcAccountData::from_bytes_verified(&raw_account_data)?;
+                Ok(ibc_store)
+            }
+        }
+    }
+
+    /// The endpoint, for messages that need more than the IBC storage account.
+    fn rpc_client(&self) -> anyhow::Result<&RpcClient> {
+        match self {
+            Self::Rpc(rpc_client) => Ok(rpc_client),
+            Self::File(path) => bail!(
+                "This message needs a live endpoint and cannot be generated from {}",
+                path.display()
+            ),
+        }
+    }
+}
+
 fn print_json<T>(msg: T) -> anyhow::Result<()>
 where
     T: Serialize,
@@ -76,73 +149,386 @@ where
     Ok(())
 }
 
-fn existence_proof_to_merkle_proof(existence_proof: ExistenceProof) -> RawMerkleProof {
-    let commitment_proof = CommitmentProof {
-        proof: Some(commitment_proof::Proof::Exist(existence_proof)),
-    };
-    let ibc_commitment_proof = IbcRawCommitmentProof::decode(&*commitment_proof.encode_to_vec())
-        .expect("CommitmentProof should be the same between ics23 and ibc-proto");
-
-    RawMerkleProof {
-        proofs: vec![ibc_commitment_proof],
-    }
-}
-
-fn get_latest_consensus_height(ibc_state: &IbcState, client_id: &str) -> anyhow::Result<Height> {
-    Ok(*ibc_state
-        .get(&ConsensusHeightsPath(client_id.parse()?))?
-        .ok_or_else(|| anyhow!("Consensus heights not found for client ID {client_id}"))?
-        .heights
-        .last()
-        .ok_or_else(|| anyhow!("No consensus heights found for client ID {client_id}"))?)
-}
-
+/// Finds the height at which the counterparty's client `client_id_on_cpty` can
+/// verify proofs of the latest state in `ibc_store`, along with the provenance of
+/// those proofs.
 async fn get_and_verify_consensus_height_on_cpty(
     ibc_store: &IbcStore,
-    cpty_rpc_client: &RpcClient,
+    cpty_source: &StoreSource,
     client_id_on_cpty: &str,
-) -> anyhow::Result<Height> {
+) -> anyhow::Result<(Height, Provenance)> {
     let ibc_latest_version = ibc_store
-        .read()?
+        .read()
         .latest_version()
         .ok_or_else(|| anyhow!("No IBC state versions found"))?;
-    let ibc_latest_height = eclipse_chain::height_of_slot(ibc_latest_version)?;
 
-    let cpty_ibc_store = get_ibc_store(cpty_rpc_client).await?;
+    let cpty_ibc_store = cpty_source.ibc_store().await?;
     let cpty_ibc_state = get_ibc_state(&cpty_ibc_store)?;
 
-    let consensus_height_on_cpty = get_latest_consensus_height(&cpty_ibc_state, client_id_on_cpty)?;
+    let ibc_state = get_ibc_state(ibc_store)?;
+    let chain_params = ChainParams::load_or_default(&ibc_state)?;
+    check_client_tracks_chain(
+        &cpty_ibc_state,
+        client_id_on_cpty,
+        chain_params.chain_name.as_deref(),
+    )?;
 
-    if consensus_height_on_cpty < ibc_latest_height {
+    let consensus_height_on_cpty =
+        chain_state::get_latest_consensus_height(&cpty_ibc_state, client_id_on_cpty)?;
+
+    // Compared by slot, since the consensus height may be of an earlier revision
+    let consensus_slot_on_cpty = consensus_height_on_cpty.to_slot()?;
+    if consensus_slot_on_cpty < ibc_latest_version {
         bail!(
             "Height of chain (client ID {client_id_on_cpty}) on cpty chain is not recent enough; \
-               {consensus_height_on_cpty} < {ibc_latest_height}"
+               {consensus_height_on_cpty} is of slot {consensus_slot_on_cpty}, before the latest \
+               IBC slot {ibc_latest_version}"
+        );
+    }
+
+    let commitment_root = ibc_state
+        .get_commitment_root_option(ibc_latest_version)?
+        .ok_or_else(|| anyhow!("IBC store has no root at version {ibc_latest_version}"))?;
+    let provenance = Provenance {
+        client_id_on_cpty: client_id_on_cpty.to_owned(),
+        proof_height: consensus_height_on_cpty.to_string(),
+        commitment_root: hex::encode(commitment_root.as_bytes()),
+        source_version: ibc_latest_version,
+        expires_at_unix: None,
+        generated_by: Some(BuildInfo::current()),
+    };
+    provenance.check_consensus_state(&cpty_ibc_state)?;
+
+    Ok((consensus_height_on_cpty, provenance))
+}
+
+/// Checks that the counterparty's client `client_id_on_cpty` tracks this chain,
+/// named `chain_name`. Client and connection IDs are numbered the same way on
+/// every chain, so a counterparty endpoint pointing at the wrong chain would
+/// otherwise only be caught when the proofs fail to verify there. Chains without
+/// a configured name cannot be told apart by chain ID and are not checked.
+fn check_client_tracks_chain(
+    cpty_ibc_state: &IbcState,
+    client_id_on_cpty: &str,
+    chain_name: Option<&str>,
+) -> anyhow::Result<()> {
+    let chain_name = match chain_name {
+        Some(chain_name) => chain_name,
+        None => return Ok(()),
+    };
+    let client_id = client_id_on_cpty.parse()?;
+    let client_state = cpty_ibc_state
+        .get_any(&ClientStatePath::new(&client_id))?
+        .ok_or_else(|| anyhow!("No client state found for client ID {client_id} on cpty chain"))?;
+    let tracked_chain_id = tracked_chain_id(&*decode_client_state(client_state)?)
+        .ok_or_else(|| anyhow!("Client {client_id} on cpty chain has an unsupported type"))?;
+
+    // Clients keep the revision they were last updated in, which may predate the
+    // chain's latest revision bump
+    let expected_chain_id =
+        eclipse_chain::chain_id_with_revision(chain_name, tracked_chain_id.version());
+    if tracked_chain_id != expected_chain_id {
+        bail!(
+            "Client {client_id} on cpty chain tracks {tracked_chain_id}, not this chain \
+             {expected_chain_id}; check that the endpoints are the intended chains"
         );
     }
+    Ok(())
+}
 
-    Ok(consensus_height_on_cpty)
+/// Reads the client state tracking the counterparty and re-encodes it canonically
+/// for the `client_state` field of a connection handshake message. The
+/// counterparty endpoint is an Eclipse chain, so the client must be an Eclipse
+/// client; anything else would only be rejected once the message reaches it.
+fn get_counterparty_client_state(
+    ibc_state: &IbcState,
+    client_id: &str,
+) -> anyhow::Result<protobuf::Any> {
+    let client_id = client_id.parse()?;
+    let client_state = ibc_state
+        .get_any(&ClientStatePath::new(&client_id))?
+        .ok_or_else(|| anyhow!("No client state found for client ID {client_id}"))?;
+    reencode_counterparty_client_state(&client_id, client_state)
 }
 
+fn reencode_counterparty_client_state(
+    client_id: &ClientId,
+    client_state: protobuf::Any,
+) -> anyhow::Result<protobuf::Any> {
+    let type_url = canonical_type_url(&client_state.type_url).unwrap_or(&client_state.type_url);
+    if type_url != ECLIPSE_CLIENT_STATE_TYPE_URL {
+        bail!(
+            "Client {client_id} has a client state of type {}, but the counterparty is an \
+             Eclipse chain and expects {ECLIPSE_CLIENT_STATE_TYPE_URL}",
+            client_state.type_url
+        );
+    }
+    let client_state = decode_client_state(client_state)
+        .map_err(|err| anyhow!("Stored client state of {client_id} is invalid: {err}"))?;
+    info!(
+        "Using client state of {client_id} with latest height {}",
+        client_state.latest_height()
+    );
+    encode_client_state(client_state)
+        .map_err(|err| anyhow!("Could not re-encode client state of {client_id}: {err}"))
+}
+
+/// A field that the destination chain validates, which generate fills in with a
+/// default unless `flag` is given.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ExplicitField {
+    flag: &'static str,
+    example: &'static str,
+    given: bool,
+}
+
+impl ExplicitField {
+    fn new<T>(flag: &'static str, example: &'static str, value: &Option<T>) -> Self {
+        Self {
+            flag,
+            example,
+            given: value.is_some(),
+        }
+    }
+}
+
+/// Refuses to generate a message in strict mode if any of `fields` would be
+/// defaulted, listing the flags that are missing. Otherwise only warns, since
+/// strict mode will become the default.
+fn enforce_explicit_fields(strict: bool, fields: &[ExplicitField]) -> anyhow::Result<()> {
+    let missing = fields
+        .iter()
+        .filter(|field| !field.given)
+        .map(|ExplicitField { flag, example, .. }| format!("\n  {flag} {example}"))
+        .collect::<String>();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    if strict {
+        bail!("Refusing to generate a message with defaulted fields; missing flags:{missing}");
+    }
+    warn!(
+        "Generating a message with defaulted fields, which will be an error by default in a \
+         future release; pass --strict to opt in now. Missing flags:{missing}"
+    );
+    Ok(())
+}
+
+/// Parses a connection version such as `1:ORDER_ORDERED,ORDER_UNORDERED`.
+fn parse_connection_version(version: &str) -> anyhow::Result<ConnectionVersion> {
+    let (identifier, features) = version.split_once(':').ok_or_else(|| {
+        anyhow!("connection version must look like `1:ORDER_ORDERED,ORDER_UNORDERED`")
+    })?;
+    let raw_version = RawConnectionVersion {
+        identifier: identifier.to_owned(),
+        features: features.split(',').map(str::to_owned).collect(),
+    };
+    Ok(raw_version.try_into()?)
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ChannelOrdering {
+    Ordered,
+    Unordered,
+}
+
+impl From<ChannelOrdering> for RawOrder {
+    fn from(ordering: ChannelOrdering) -> Self {
+        match ordering {
+            ChannelOrdering::Ordered => Self::Ordered,
+            ChannelOrdering::Unordered => Self::Unordered,
+        }
+    }
+}
+
+const CONNECTION_VERSION_EXAMPLE: &str = "1:ORDER_ORDERED,ORDER_UNORDERED";
+const CHANNEL_VERSION_EXAMPLE: &str = "ics20-1";
+const CHANNEL_ORDERING_EXAMPLE: &str = "unordered";
+
 #[derive(Clone, Debug, Subcommand)]
 enum ClientMsg {
     Create {
         chain_name: String,
+
+        /// Revision height to anchor the client at, in the chain's current
+        /// revision. Defaults to the latest slot's height.
+        #[arg(long)]
+        height: Option<u64>,
+
+        /// How long the client stays valid after each update, in seconds.
+        /// Defaults to the light client's default of one hour.
+        #[arg(long)]
+        valid_duration_secs: Option<u64>,
+
+        /// Creates the client frozen at its latest height, for testing how
+        /// frozen clients are handled
+        #[arg(long)]
+        frozen: bool,
     },
     Update {
         client_id: String,
     },
+    /// Updates the counterparty's client `client_id` with this chain's headers
+    /// from `--start-height` to `--end-height`, every `--step` slots, for catching
+    /// up a client while keeping consensus states at heights in between, such as
+    /// those of pending packets. Prints a `MsgUpdateClient` per header, or with
+    /// `--as-batch` a single one carrying them all.
+    UpdateRange {
+        client_id: String,
+
+        /// First revision height to update the client to, in the chain's current
+        /// revision
+        #[arg(long)]
+        start_height: u64,
+
+        /// Last revision height to update the client to. Defaults to the latest
+        /// slot's height.
+        #[arg(long)]
+        end_height: Option<u64>,
+
+        /// Slots between consecutive headers
+        #[arg(long, default_value_t = 1)]
+        step: u64,
+
+        /// Batches the headers into one message, which only Eclipse clients
+        /// accept
+        #[arg(long)]
+        as_batch: bool,
+    },
+    /// Upgrades the counterparty's client `client_id` to the upgrade written with
+    /// `tx admin write-upgrade`. The client must be updated to the upgrade height
+    /// first, since the upgrade is proven against its consensus state there.
     Upgrade {
-        chain_name: String,
         client_id: String,
+
+        /// Height the upgrade was written for, e.g. `0-1000`
+        #[arg(long)]
+        upgrade_height: Height,
+    },
+    /// Submits evidence to the counterparty's client `client_id` that this chain
+    /// committed to two different roots at the same height. Header files hold
+    /// the JSON of a header `Any`, like the `header` that `client update`
+    /// prints. Eclipse headers are not signed, so the Eclipse client rejects
+    /// the evidence instead of freezing; it is for counterparties that can
+    /// check it some other way.
+    Misbehaviour {
+        client_id: String,
+
+        /// First of the conflicting headers. Defaults to this chain's own header
+        /// at the height of the second.
+        #[arg(long)]
+        header_1_file: Option<PathBuf>,
+
+        /// Second of the conflicting headers
+        #[arg(long)]
+        header_2_file: PathBuf,
     },
 }
 
+/// Revision heights from `start_height` to `end_height`, `step` apart, ending
+/// at `end_height` even if it is not a whole number of steps from the start.
+fn range_heights(start_height: u64, end_height: u64, step: u64) -> anyhow::Result<Vec<u64>> {
+    if step == 0 {
+        bail!("The step must be at least one slot");
+    }
+    if start_height > end_height {
+        bail!("Start height {start_height} is past the end height {end_height}");
+    }
+    let mut heights = (start_height..=end_height)
+        .step_by(usize::try_from(step)?)
+        .collect::<Vec<_>>();
+    if heights.last() != Some(&end_height) {
+        heights.push(end_height);
+    }
+    Ok(heights)
+}
+
+/// Reads a header of this chain from a file holding the JSON of its `Any`.
+fn read_header_file(path: &Path) -> anyhow::Result<EclipseHeader> {
+    let json =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut header: protobuf::Any = serde_json::from_str(&json)
+        .with_context(|| format!("{} does not hold the JSON of an Any", path.display()))?;
+    if let Some(type_url) = canonical_type_url(&header.type_url) {
+        header.type_url = type_url.to_owned();
+    }
+    EclipseHeader::try_from(header)
+        .map_err(|err| anyhow!("{} does not hold an Eclipse header: {err}", path.display()))
+}
+
+/// Warns if the client being updated on the counterparty was created from a
+/// different cluster than the one `rpc_client` points at.
+async fn warn_on_genesis_hash_mismatch(
+    rpc_client: &RpcClient,
+    cpty_source: &StoreSource,
+    client_id: &str,
+) -> anyhow::Result<()> {
+    let cpty_ibc_store = cpty_source.ibc_store().await?;
+    let cpty_ibc_state = get_ibc_state(&cpty_ibc_store)?;
+    let client_state = cpty_ibc_state
+        .get_raw(&ClientStatePath::new(&client_id.parse()?))?
+        .ok_or_else(|| anyhow!("client {client_id} not found on counterparty"))?;
+    // Only Eclipse clients record a genesis hash
+    let client_state = match EclipseClientState::try_from(client_state) {
+        Ok(client_state) => client_state,
+        Err(_) => return Ok(()),
+    };
+
+    let genesis_hash = rpc_client.get_genesis_hash().await?;
+    if let Some(warning) =
+        chain_state::genesis_hash_mismatch(client_id, &client_state, &genesis_hash)
+    {
+        warn!("{warning}");
+    }
+    Ok(())
+}
+
 impl ClientMsg {
-    async fn generate(&self, rpc_client: &RpcClient) -> anyhow::Result<()> {
+    /// Fields besides the signer that must be given explicitly in strict mode.
+    fn explicit_fields(&self) -> Vec<ExplicitField> {
         match self {
-            Self::Create { chain_name } => {
-                let latest_slot = rpc_client.get_slot().await?;
-                let latest_height = eclipse_chain::height_of_slot(latest_slot)?;
+            Self::Create { .. }
+            | Self::Update { .. }
+            | Self::UpdateRange { .. }
+            | Self::Upgrade { .. }
+            | Self::Misbehaviour { .. } => vec![],
+        }
+    }
+
+    async fn generate(
+        &self,
+        source: &StoreSource,
+        cpty_source: Option<&StoreSource>,
+        signer: String,
+    ) -> anyhow::Result<Option<Provenance>> {
+        match self {
+            Self::Create {
+                chain_name,
+                height,
+                valid_duration_secs,
+                frozen,
+            } => {
+                let valid_duration = match valid_duration_secs {
+                    Some(0) => bail!("The valid duration must be at least one second"),
+                    valid_duration_secs => valid_duration_secs.map(Duration::from_secs),
+                };
+                let rpc_client = source.rpc_client()?;
+                let tip_slot = rpc_client.get_slot().await?;
+                let tip_height = chain_state::get_host_height(rpc_client, tip_slot).await?;
+                let latest_height = match height {
+                    Some(height) => {
+                        let height = Height::new(tip_height.revision_number(), *height)?;
+                        if height > tip_height {
+                            bail!("Height {height} is past the chain tip at {tip_height}");
+                        }
+                        let slot =
+                            chain_state::nearest_available_slot(rpc_client, height.to_slot()?)
+                                .await?;
+                        eclipse_chain::height_of_slot_with_revision(slot, height.revision_number())?
+                    }
+                    None => tip_height,
+                };
                 let consensus_state =
                     chain_state::get_consensus_state(rpc_client, latest_height).await?;
 
@@ -150,20 +536,34 @@ impl ClientMsg {
                     consensus_state.clone(),
                     latest_height,
                 );
-                let client_state = chain_state::client_state_from_header(latest_header, chain_name);
+                let genesis_hash = rpc_client.get_genesis_hash().await?;
+                let client_state = chain_state::client_state_from_header(
+                    latest_header,
+                    chain_name,
+                    genesis_hash,
+                    ClientStateParams {
+                        valid_duration,
+                        frozen: *frozen,
+                    },
+                );
 
                 let msg = RawMsgCreateClient {
                     client_state: Some(client_state.encode_as_any()),
                     consensus_state: Some(consensus_state.encode_as_any()),
-                    signer: "".to_owned(),
+                    signer,
                 };
 
                 print_json(msg)?;
-                Ok(())
+                Ok(None)
             }
             Self::Update { client_id } => {
+                let rpc_client = source.rpc_client()?;
+                if let Some(cpty_source) = cpty_source {
+                    warn_on_genesis_hash_mismatch(rpc_client, cpty_source, client_id).await?;
+                }
+
                 let latest_slot = rpc_client.get_slot().await?;
-                let latest_height = eclipse_chain::height_of_slot(latest_slot)?;
+                let latest_height = chain_state::get_host_height(rpc_client, latest_slot).await?;
                 let consensus_state =
                     chain_state::get_consensus_state(rpc_client, latest_height).await?;
                 let latest_header =
@@ -172,60 +572,223 @@ impl ClientMsg {
                 let msg = RawMsgUpdateClient {
                     client_id: client_id.clone(),
                     header: Some(latest_header.encode_as_any()),
-                    signer: "".to_owned(),
+                    signer,
                 };
 
                 print_json(msg)?;
-                Ok(())
+                Ok(None)
+            }
+            Self::UpdateRange {
+                client_id,
+                start_height,
+                end_height,
+                step,
+                as_batch,
+            } => {
+                let rpc_client = source.rpc_client()?;
+                if let Some(cpty_source) = cpty_source {
+                    warn_on_genesis_hash_mismatch(rpc_client, cpty_source, client_id).await?;
+                }
+
+                let tip_slot = rpc_client.get_slot().await?;
+                let tip_height = chain_state::get_host_height(rpc_client, tip_slot).await?;
+                let end_height = match end_height {
+                    Some(end_height) if *end_height > tip_height.revision_height() => {
+                        bail!("End height {end_height} is past the chain tip at {tip_height}")
+                    }
+                    Some(end_height) => *end_height,
+                    None => tip_height.revision_height(),
+                };
+                let revision_heights = range_heights(*start_height, end_height, *step)?;
+                if *as_batch && revision_heights.len() > MAX_HEADERS_PER_BATCH {
+                    bail!(
+                        "The range holds {} headers, more than the {MAX_HEADERS_PER_BATCH} a \
+                         batch may hold; raise --step or split the range",
+                        revision_heights.len()
+                    );
+                }
+
+                let ibc_store = source.ibc_store().await?;
+                let mut headers = Vec::with_capacity(revision_heights.len());
+                for revision_height in revision_heights {
+                    let height = Height::new(tip_height.revision_number(), revision_height)?;
+                    let slot = height.to_slot()?;
+                    let timestamp = chain_state::get_slot_time(rpc_client, slot).await?;
+                    let consensus_state =
+                        chain_state::consensus_state_at(&ibc_store, slot, timestamp)?;
+                    headers.push(chain_state::header_from_consensus_state(
+                        consensus_state,
+                        height,
+                    ));
+                }
+
+                if *as_batch {
+                    let batch = EclipseHeaderBatch::new(headers)
+                        .map_err(|err| anyhow!("The headers cannot be batched: {err}"))?;
+                    print_json(RawMsgUpdateClient {
+                        client_id: client_id.clone(),
+                        header: Some(batch.encode_as_any()),
+                        signer,
+                    })?;
+                } else {
+                    let msgs = headers
+                        .into_iter()
+                        .map(|header| RawMsgUpdateClient {
+                            client_id: client_id.clone(),
+                            header: Some(header.encode_as_any()),
+                            signer: signer.clone(),
+                        })
+                        .collect::<Vec<_>>();
+                    print_json(msgs)?;
+                }
+                Ok(None)
             }
             Self::Upgrade {
-                chain_name,
                 client_id,
+                upgrade_height,
             } => {
-                let latest_slot = rpc_client.get_slot().await?;
-                let latest_height = eclipse_chain::height_of_slot(latest_slot)?;
-                let consensus_state =
-                    chain_state::get_consensus_state(rpc_client, latest_height).await?;
+                let ibc_store = source.ibc_store().await?;
+                let (msg, provenance) =
+                    upgrade_client_msg(&ibc_store, client_id, *upgrade_height, signer)?;
+                if let Some(cpty_source) = cpty_source {
+                    let cpty_ibc_store = cpty_source.ibc_store().await?;
+                    let cpty_ibc_state = get_ibc_state(&cpty_ibc_store)?;
+                    let client_height =
+                        chain_state::get_latest_consensus_height(&cpty_ibc_state, client_id)?;
+                    if client_height != *upgrade_height {
+                        bail!(
+                            "Client {client_id} on cpty chain is at {client_height}; update it \
+                             to the upgrade height {upgrade_height} first"
+                        );
+                    }
+                    provenance.check_consensus_state(&cpty_ibc_state)?;
+                }
 
-                let latest_header = chain_state::header_from_consensus_state(
-                    consensus_state.clone(),
-                    latest_height,
-                );
-                let client_state = chain_state::client_state_from_header(latest_header, chain_name);
+                print_json(msg)?;
+                Ok(Some(provenance))
+            }
+            Self::Misbehaviour {
+                client_id,
+                header_1_file,
+                header_2_file,
+            } => {
+                let header_2 = read_header_file(header_2_file)?;
+                let header_1 = match header_1_file {
+                    Some(header_1_file) => read_header_file(header_1_file)?,
+                    None => {
+                        let consensus_state =
+                            chain_state::get_consensus_state(source.rpc_client()?, header_2.height)
+                                .await?;
+                        chain_state::header_from_consensus_state(consensus_state, header_2.height)
+                    }
+                };
+                let misbehaviour = EclipseMisbehaviour::new(client_id.clone(), header_1, header_2)
+                    .map_err(|err| anyhow!("The headers are not misbehaviour evidence: {err}"))?;
 
-                let msg = RawMsgUpgradeClient {
+                let msg = RawMsgSubmitMisbehaviour {
                     client_id: client_id.clone(),
-                    client_state: Some(client_state.encode_as_any()),
-                    consensus_state: Some(consensus_state.encode_as_any()),
-                    proof_upgrade_client: vec![],
-                    proof_upgrade_consensus_state: vec![],
-                    signer: "".to_owned(),
+                    misbehaviour: Some(misbehaviour.encode_as_any()),
+                    signer,
                 };
 
                 print_json(msg)?;
-                Ok(())
+                Ok(None)
             }
         }
     }
 }
 
+/// Builds the `MsgUpgradeClient` of the upgrade written for `upgrade_height`,
+/// proven in the state version that the counterparty's client `client_id` has
+/// the consensus state of once updated to that height.
+fn upgrade_client_msg(
+    ibc_store: &IbcStore,
+    client_id: &str,
+    upgrade_height: Height,
+    signer: String,
+) -> anyhow::Result<(RawMsgUpgradeClient, Provenance)> {
+    let version = ibc_store
+        .read()
+        .find_version(upgrade_height.to_slot()?)
+        .ok_or_else(|| anyhow!("IBC store has no version at or before {upgrade_height}"))?;
+    let ibc_state = IbcState::new(ibc_store, version);
+    let client_state_path =
+        UpgradeClientPath::UpgradedClientState(upgrade_height.revision_height());
+    let consensus_state_path =
+        UpgradeClientPath::UpgradedClientConsensusState(upgrade_height.revision_height());
+    let (client_state, consensus_state) = match (
+        ibc_state.get_any(&client_state_path)?,
+        ibc_state.get_any(&consensus_state_path)?,
+    ) {
+        (Some(client_state), Some(consensus_state)) => (client_state, consensus_state),
+        _ => bail!("No upgrade was written for {upgrade_height}"),
+    };
+    let commitment_root = ibc_state
+        .get_commitment_root_option(version)?
+        .ok_or_else(|| anyhow!("IBC store has no root at version {version}"))?;
+
+    let msg = RawMsgUpgradeClient {
+        client_id: client_id.to_owned(),
+        client_state: Some(client_state),
+        consensus_state: Some(consensus_state),
+        proof_upgrade_client: existence_proof_to_merkle_proof(
+            ibc_state.get_proof(&client_state_path)?,
+        )
+        .encode_to_vec(),
+        proof_upgrade_consensus_state: existence_proof_to_merkle_proof(
+            ibc_state.get_proof(&consensus_state_path)?,
+        )
+        .encode_to_vec(),
+        signer,
+    };
+    let provenance = Provenance {
+        client_id_on_cpty: client_id.to_owned(),
+        proof_height: upgrade_height.to_string(),
+        commitment_root: hex::encode(commitment_root.as_bytes()),
+        source_version: version,
+        expires_at_unix: None,
+        generated_by: Some(BuildInfo::current()),
+    };
+    Ok((msg, provenance))
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Clone, Debug, Subcommand)]
 enum ConnectionMsg {
     OpenInit {
         client_id_on_a: String,
         client_id_on_b: String,
+
+        /// Connection delay period, e.g. `0s` or `10m`
+        #[arg(long, default_value = "0s", value_parser = duration::parse_duration)]
+        delay_period: Duration,
+
+        /// Connection version to propose, e.g. `1:ORDER_ORDERED,ORDER_UNORDERED`
+        #[arg(long, value_parser = parse_connection_version)]
+        connection_version: Option<ConnectionVersion>,
     },
     OpenTry {
         client_id_on_b: String,
         client_id_on_a: String,
         connection_id_on_a: String,
+
+        /// Connection delay period, which must match the one in `OpenInit`
+        #[arg(long, default_value = "0s", value_parser = duration::parse_duration)]
+        delay_period: Duration,
+
+        /// Connection version proposed by the counterparty in `OpenInit`
+        #[arg(long, value_parser = parse_connection_version)]
+        connection_version: Option<ConnectionVersion>,
     },
     OpenAck {
         client_id_on_a: String,
         connection_id_on_a: String,
         client_id_on_b: String,
         connection_id_on_b: String,
+
+        /// Connection version selected by the counterparty in `OpenTry`
+        #[arg(long, value_parser = parse_connection_version)]
+        connection_version: Option<ConnectionVersion>,
     },
     OpenConfirm {
         client_id_on_b: String,
@@ -235,15 +798,38 @@ enum ConnectionMsg {
 }
 
 impl ConnectionMsg {
+    /// Fields besides the signer that must be given explicitly in strict mode.
+    fn explicit_fields(&self) -> Vec<ExplicitField> {
+        match self {
+            Self::OpenInit {
+                connection_version, ..
+            }
+            | Self::OpenTry {
+                connection_version, ..
+            }
+            | Self::OpenAck {
+                connection_version, ..
+            } => vec![ExplicitField::new(
+                "--connection-version",
+                CONNECTION_VERSION_EXAMPLE,
+                connection_version,
+            )],
+            Self::OpenConfirm { .. } => vec![],
+        }
+    }
+
     async fn generate(
         &self,
-        rpc_client: &RpcClient,
-        cpty_rpc_client: &RpcClient,
-    ) -> anyhow::Result<()> {
+        source: &StoreSource,
+        cpty_source: &StoreSource,
+        signer: String,
+    ) -> anyhow::Result<Option<Provenance>> {
         match self {
             Self::OpenInit {
                 client_id_on_a,
                 client_id_on_b,
+                delay_period,
+                connection_version,
             } => {
                 let counterparty = RawConnectionCounterparty {
                     client_id: client_id_on_b.clone(),
@@ -256,18 +842,20 @@ impl ConnectionMsg {
                 let msg = RawMsgConnectionOpenInit {
                     client_id: client_id_on_a.clone(),
                     counterparty: Some(counterparty),
-                    version: Some(ConnectionVersion::default().into()),
-                    delay_period: DELAY_PERIOD_NANOS,
-                    signer: "".to_owned(),
+                    version: Some(connection_version.clone().unwrap_or_default().into()),
+                    delay_period: delay_period.as_nanos().try_into()?,
+                    signer,
                 };
 
                 print_json(msg)?;
-                Ok(())
+                Ok(None)
             }
             Self::OpenTry {
                 client_id_on_b,
                 client_id_on_a,
                 connection_id_on_a,
+                delay_period,
+                connection_version,
             } => {
                 let counterparty = RawConnectionCounterparty {
                     client_id: client_id_on_a.clone(),
@@ -277,13 +865,12 @@ impl ConnectionMsg {
                     }),
                 };
 
-                let ibc_store = get_ibc_store(rpc_client).await?;
+                let ibc_store = source.ibc_store().await?;
                 let ibc_state = get_ibc_state(&ibc_store)?;
 
-                let client_state =
-                    ibc_state.get_raw(&ClientStatePath::new(&client_id_on_a.parse()?))?;
+                let client_state = get_counterparty_client_state(&ibc_state, client_id_on_a)?;
                 let consensus_height_of_b_on_a =
-                    get_latest_consensus_height(&ibc_state, client_id_on_a)?;
+                    chain_state::get_latest_consensus_height(&ibc_state, client_id_on_a)?;
 
                 let proof_init = existence_proof_to_merkle_proof(
                     ibc_state.get_proof(&ConnectionPath::new(&connection_id_on_a.parse()?))?,
@@ -298,21 +885,24 @@ impl ConnectionMsg {
                     ),
                 )?);
 
-                let consensus_height_of_a_on_b = get_and_verify_consensus_height_on_cpty(
-                    &ibc_store,
-                    cpty_rpc_client,
-                    client_id_on_b,
-                )
-                .await?;
+                let (consensus_height_of_a_on_b, provenance) =
+                    get_and_verify_consensus_height_on_cpty(
+                        &ibc_store,
+                        cpty_source,
+                        client_id_on_b,
+                    )
+                    .await?;
 
                 #[allow(deprecated)]
                 let msg = RawMsgConnectionOpenTry {
                     client_id: client_id_on_b.clone(),
                     previous_connection_id: "".to_owned(),
-                    client_state,
+                    client_state: Some(client_state),
                     counterparty: Some(counterparty),
-                    delay_period: DELAY_PERIOD_NANOS,
-                    counterparty_versions: get_compatible_versions()
+                    delay_period: delay_period.as_nanos().try_into()?,
+                    counterparty_versions: connection_version
+                        .clone()
+                        .map_or_else(get_compatible_versions, |version| vec![version])
                         .into_iter()
                         .map(ConnectionVersion::into)
                         .collect(),
@@ -321,25 +911,25 @@ impl ConnectionMsg {
                     proof_client: proof_client.encode_to_vec(),
                     proof_consensus: proof_consensus.encode_to_vec(),
                     consensus_height: Some(consensus_height_of_b_on_a.into()),
-                    signer: "".to_owned(),
+                    signer,
                 };
 
                 print_json(msg)?;
-                Ok(())
+                Ok(Some(provenance))
             }
             Self::OpenAck {
                 client_id_on_a,
                 connection_id_on_a,
                 client_id_on_b,
                 connection_id_on_b,
+                connection_version,
             } => {
-                let ibc_store = get_ibc_store(rpc_client).await?;
+                let ibc_store = source.ibc_store().await?;
                 let ibc_state = get_ibc_state(&ibc_store)?;
 
-                let client_state =
-                    ibc_state.get_raw(&ClientStatePath::new(&client_id_on_b.parse()?))?;
+                let client_state = get_counterparty_client_state(&ibc_state, client_id_on_b)?;
                 let consensus_height_of_a_on_b =
-                    get_latest_consensus_height(&ibc_state, client_id_on_b)?;
+                    chain_state::get_latest_consensus_height(&ibc_state, client_id_on_b)?;
 
                 let proof_try = existence_proof_to_merkle_proof(
                     ibc_state.get_proof(&ConnectionPath::new(&connection_id_on_b.parse()?))?,
@@ -354,76 +944,128 @@ impl ConnectionMsg {
                     ),
                 )?);
 
-                let consensus_height_of_b_on_a = get_and_verify_consensus_height_on_cpty(
-                    &ibc_store,
-                    cpty_rpc_client,
-                    client_id_on_a,
-                )
-                .await?;
+                let (consensus_height_of_b_on_a, provenance) =
+                    get_and_verify_consensus_height_on_cpty(
+                        &ibc_store,
+                        cpty_source,
+                        client_id_on_a,
+                    )
+                    .await?;
 
                 let msg = RawMsgConnectionOpenAck {
                     connection_id: connection_id_on_a.clone(),
                     counterparty_connection_id: connection_id_on_b.clone(),
-                    version: Some(ConnectionVersion::default().into()),
-                    client_state,
+                    version: Some(connection_version.clone().unwrap_or_default().into()),
+                    client_state: Some(client_state),
                     proof_height: Some(consensus_height_of_b_on_a.into()),
                     proof_try: proof_try.encode_to_vec(),
                     proof_client: proof_client.encode_to_vec(),
                     proof_consensus: proof_consensus.encode_to_vec(),
                     consensus_height: Some(consensus_height_of_a_on_b.into()),
-                    signer: "".to_owned(),
+                    signer,
                 };
 
                 print_json(msg)?;
-                Ok(())
+                Ok(Some(provenance))
             }
             Self::OpenConfirm {
                 client_id_on_b,
                 connection_id_on_b,
                 connection_id_on_a,
             } => {
-                let ibc_store = get_ibc_store(rpc_client).await?;
+                let ibc_store = source.ibc_store().await?;
                 let ibc_state = get_ibc_state(&ibc_store)?;
 
                 let proof_ack = existence_proof_to_merkle_proof(
                     ibc_state.get_proof(&ConnectionPath::new(&connection_id_on_a.parse()?))?,
                 );
 
-                let consensus_height_of_a_on_b = get_and_verify_consensus_height_on_cpty(
-                    &ibc_store,
-                    cpty_rpc_client,
-                    client_id_on_b,
-                )
-                .await?;
+                let (consensus_height_of_a_on_b, provenance) =
+                    get_and_verify_consensus_height_on_cpty(
+                        &ibc_store,
+                        cpty_source,
+                        client_id_on_b,
+                    )
+                    .await?;
 
                 let msg = RawMsgConnectionOpenConfirm {
                     connection_id: connection_id_on_b.clone(),
                     proof_ack: proof_ack.encode_to_vec(),
                     proof_height: Some(consensus_height_of_a_on_b.into()),
-                    signer: "".to_owned(),
+                    signer,
                 };
 
                 print_json(msg)?;
-                Ok(())
+                Ok(Some(provenance))
             }
         }
     }
 }
 
+/// Checks that every connection hop of a channel exists and is open on the chain
+/// of `ibc_store`, where the channel handshake message is sent.
+fn check_connection_hops_open(
+    ibc_store: &IbcStore,
+    connection_hops: &[String],
+) -> anyhow::Result<()> {
+    let ibc_state = get_ibc_state(ibc_store)?;
+    for (index, connection_id) in connection_hops.iter().enumerate() {
+        let connection_path = ConnectionPath::new(
+            &connection_id
+                .parse()
+                .with_context(|| format!("Invalid connection hop {index}: {connection_id}"))?,
+        );
+        let connection = ibc_state.get_raw(&connection_path)?.ok_or_else(|| {
+            anyhow!("Connection hop {index} ({connection_id}) not found on the cpty chain")
+        })?;
+        let state = RawConnectionState::from_i32(connection.state).unwrap_or_default();
+        if state != RawConnectionState::Open {
+            bail!(
+                "Connection hop {index} ({connection_id}) is not open on the cpty chain: {state:?}"
+            );
+        }
+    }
+    Ok(())
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Clone, Debug, Subcommand)]
 enum ChannelMsg {
     OpenInit {
-        connection_id_on_a: String,
+        /// Connection hops of the channel, comma-separated, starting from this end
+        #[arg(num_args = 1, value_delimiter = ',', required = true)]
+        connection_id_on_a: Vec<String>,
         port_id_on_a: String,
         port_id_on_b: String,
+
+        /// Channel ordering, which defaults to ordered
+        #[arg(long, value_enum)]
+        ordering: Option<ChannelOrdering>,
+
+        /// Application version of the channel, e.g. `ics20-1`
+        #[arg(long)]
+        channel_version: Option<String>,
     },
     OpenTry {
         client_id_on_b: String,
-        connection_id_on_b: String,
+        /// Connection hops of the channel, comma-separated, starting from this end
+        #[arg(num_args = 1, value_delimiter = ',', required = true)]
+        connection_id_on_b: Vec<String>,
         port_id_on_b: String,
         port_id_on_a: String,
         channel_id_on_a: String,
+
+        /// Channel ordering, which must match the one in `OpenInit`
+        #[arg(long, value_enum)]
+        ordering: Option<ChannelOrdering>,
+
+        /// Application version of the channel on this end
+        #[arg(long)]
+        channel_version: Option<String>,
+
+        /// Application version of the channel on the counterparty
+        #[arg(long)]
+        counterparty_version: Option<String>,
     },
     OpenAck {
         client_id_on_a: String,
@@ -431,6 +1073,10 @@ enum ChannelMsg {
         channel_id_on_a: String,
         port_id_on_b: String,
         channel_id_on_b: String,
+
+        /// Application version of the channel on the counterparty
+        #[arg(long)]
+        counterparty_version: Option<String>,
     },
     OpenConfirm {
         client_id_on_b: String,
@@ -442,17 +1088,69 @@ enum ChannelMsg {
 }
 
 impl ChannelMsg {
+    /// Fields besides the signer that must be given explicitly in strict mode.
+    fn explicit_fields(&self) -> Vec<ExplicitField> {
+        let ordering_field = |ordering: &Option<ChannelOrdering>| {
+            ExplicitField::new("--ordering", CHANNEL_ORDERING_EXAMPLE, ordering)
+        };
+        let channel_version_field = |channel_version: &Option<String>| {
+            ExplicitField::new(
+                "--channel-version",
+                CHANNEL_VERSION_EXAMPLE,
+                channel_version,
+            )
+        };
+        let counterparty_version_field = |counterparty_version: &Option<String>| {
+            ExplicitField::new(
+                "--counterparty-version",
+                CHANNEL_VERSION_EXAMPLE,
+                counterparty_version,
+            )
+        };
+
+        match self {
+            Self::OpenInit {
+                ordering,
+                channel_version,
+                ..
+            } => vec![
+                ordering_field(ordering),
+                channel_version_field(channel_version),
+            ],
+            Self::OpenTry {
+                ordering,
+                channel_version,
+                counterparty_version,
+                ..
+            } => vec![
+                ordering_field(ordering),
+                channel_version_field(channel_version),
+                counterparty_version_field(counterparty_version),
+            ],
+            Self::OpenAck {
+                counterparty_version,
+                ..
+            } => vec![counterparty_version_field(counterparty_version)],
+            Self::OpenConfirm { .. } => vec![],
+        }
+    }
+
     async fn generate(
         &self,
-        rpc_client: &RpcClient,
-        cpty_rpc_client: &RpcClient,
-    ) -> anyhow::Result<()> {
+        source: &StoreSource,
+        cpty_source: &StoreSource,
+        signer: String,
+    ) -> anyhow::Result<Option<Provenance>> {
         match self {
             Self::OpenInit {
                 connection_id_on_a,
                 port_id_on_a,
                 port_id_on_b,
+                ordering,
+                channel_version,
             } => {
+                check_connection_hops_open(&cpty_source.ibc_store().await?, connection_id_on_a)?;
+
                 let counterparty = RawChannelCounterparty {
                     port_id: port_id_on_b.clone(),
                     channel_id: "".to_owned(),
@@ -460,20 +1158,20 @@ impl ChannelMsg {
 
                 let channel = RawChannel {
                     state: RawState::Init.into(),
-                    ordering: RawOrder::Ordered.into(),
+                    ordering: RawOrder::from(ordering.unwrap_or(ChannelOrdering::Ordered)).into(),
                     counterparty: Some(counterparty),
-                    connection_hops: vec![connection_id_on_a.clone()],
-                    version: "".to_owned(),
+                    connection_hops: connection_id_on_a.clone(),
+                    version: channel_version.clone().unwrap_or_default(),
                 };
 
                 let msg = RawMsgChannelOpenInit {
                     port_id: port_id_on_a.clone(),
                     channel: Some(channel),
-                    signer: "".to_owned(),
+                    signer,
                 };
 
                 print_json(msg)?;
-                Ok(())
+                Ok(None)
             }
             Self::OpenTry {
                 client_id_on_b,
@@ -481,7 +1179,12 @@ impl ChannelMsg {
                 port_id_on_b,
                 port_id_on_a,
                 channel_id_on_a,
+                ordering,
+                channel_version,
+                counterparty_version,
             } => {
+                check_connection_hops_open(&cpty_source.ibc_store().await?, connection_id_on_b)?;
+
                 let counterparty = RawChannelCounterparty {
                     port_id: port_id_on_a.clone(),
                     channel_id: channel_id_on_a.clone(),
@@ -489,39 +1192,40 @@ impl ChannelMsg {
 
                 let channel = RawChannel {
                     state: RawState::Tryopen.into(),
-                    ordering: RawOrder::Ordered.into(),
+                    ordering: RawOrder::from(ordering.unwrap_or(ChannelOrdering::Ordered)).into(),
                     counterparty: Some(counterparty),
-                    connection_hops: vec![connection_id_on_b.clone()],
-                    version: "".to_owned(),
+                    connection_hops: connection_id_on_b.clone(),
+                    version: channel_version.clone().unwrap_or_default(),
                 };
 
-                let ibc_store = get_ibc_store(rpc_client).await?;
+                let ibc_store = source.ibc_store().await?;
                 let ibc_state = get_ibc_state(&ibc_store)?;
 
                 let proof_init = existence_proof_to_merkle_proof(ibc_state.get_proof(
                     &ChannelEndPath::new(&port_id_on_a.parse()?, &channel_id_on_a.parse()?),
                 )?);
 
-                let consensus_height_of_a_on_b = get_and_verify_consensus_height_on_cpty(
-                    &ibc_store,
-                    cpty_rpc_client,
-                    client_id_on_b,
-                )
-                .await?;
+                let (consensus_height_of_a_on_b, provenance) =
+                    get_and_verify_consensus_height_on_cpty(
+                        &ibc_store,
+                        cpty_source,
+                        client_id_on_b,
+                    )
+                    .await?;
 
                 #[allow(deprecated)]
                 let msg = RawMsgChannelOpenTry {
                     port_id: port_id_on_b.clone(),
                     previous_channel_id: "".to_owned(),
                     channel: Some(channel),
-                    counterparty_version: "".to_owned(),
+                    counterparty_version: counterparty_version.clone().unwrap_or_default(),
                     proof_init: proof_init.encode_to_vec(),
                     proof_height: Some(consensus_height_of_a_on_b.into()),
-                    signer: "".to_owned(),
+                    signer,
                 };
 
                 print_json(msg)?;
-                Ok(())
+                Ok(Some(provenance))
             }
             Self::OpenAck {
                 client_id_on_a,
@@ -529,33 +1233,35 @@ impl ChannelMsg {
                 channel_id_on_a,
                 port_id_on_b,
                 channel_id_on_b,
+                counterparty_version,
             } => {
-                let ibc_store = get_ibc_store(rpc_client).await?;
+                let ibc_store = source.ibc_store().await?;
                 let ibc_state = get_ibc_state(&ibc_store)?;
 
                 let proof_try = existence_proof_to_merkle_proof(ibc_state.get_proof(
                     &ChannelEndPath::new(&port_id_on_b.parse()?, &channel_id_on_b.parse()?),
                 )?);
 
-                let consensus_height_of_b_on_a = get_and_verify_consensus_height_on_cpty(
-                    &ibc_store,
-                    cpty_rpc_client,
-                    client_id_on_a,
-                )
-                .await?;
+                let (consensus_height_of_b_on_a, provenance) =
+                    get_and_verify_consensus_height_on_cpty(
+                        &ibc_store,
+                        cpty_source,
+                        client_id_on_a,
+                    )
+                    .await?;
 
                 let msg = RawMsgChannelOpenAck {
                     port_id: port_id_on_a.clone(),
                     channel_id: channel_id_on_a.clone(),
                     counterparty_channel_id: channel_id_on_b.clone(),
-                    counterparty_version: "".to_owned(),
+                    counterparty_version: counterparty_version.clone().unwrap_or_default(),
                     proof_try: proof_try.encode_to_vec(),
                     proof_height: Some(consensus_height_of_b_on_a.into()),
-                    signer: "".to_owned(),
+                    signer,
                 };
 
                 print_json(msg)?;
-                Ok(())
+                Ok(Some(provenance))
             }
             Self::OpenConfirm {
                 client_id_on_b,
@@ -564,30 +1270,427 @@ impl ChannelMsg {
                 port_id_on_a,
                 channel_id_on_a,
             } => {
-                let ibc_store = get_ibc_store(rpc_client).await?;
+                let ibc_store = source.ibc_store().await?;
                 let ibc_state = get_ibc_state(&ibc_store)?;
 
                 let proof_ack = existence_proof_to_merkle_proof(ibc_state.get_proof(
                     &ChannelEndPath::new(&port_id_on_a.parse()?, &channel_id_on_a.parse()?),
                 )?);
 
-                let consensus_height_of_a_on_b = get_and_verify_consensus_height_on_cpty(
-                    &ibc_store,
-                    cpty_rpc_client,
-                    client_id_on_b,
-                )
-                .await?;
+                let (consensus_height_of_a_on_b, provenance) =
+                    get_and_verify_consensus_height_on_cpty(
+                        &ibc_store,
+                        cpty_source,
+                        client_id_on_b,
+                    )
+                    .await?;
 
                 let msg = RawMsgChannelOpenConfirm {
                     port_id: port_id_on_b.clone(),
                     channel_id: channel_id_on_b.clone(),
                     proof_ack: proof_ack.encode_to_vec(),
                     proof_height: Some(consensus_height_of_a_on_b.into()),
-                    signer: "".to_owned(),
+                    signer,
                 };
 
                 print_json(msg)?;
-                Ok(())
+                Ok(Some(provenance))
+            }
+        }
+    }
+}
+
+/// Fields of the packet being relayed from chain A to chain B.
+#[derive(Clone, Debug, clap::Args)]
+struct PacketArgs {
+    port_id_on_a: String,
+    channel_id_on_a: String,
+    port_id_on_b: String,
+    channel_id_on_b: String,
+    sequence: u64,
+
+    /// Hex-encoded packet data
+    #[arg(long)]
+    packet_data: String,
+
+    /// Timeout height of the packet, if it has one
+    #[arg(long)]
+    timeout_height: Option<Height>,
+
+    /// Timeout timestamp of the packet, in nanoseconds since the Unix epoch. Zero
+    /// means the packet has no timeout timestamp.
+    #[arg(long, default_value_t = 0)]
+    timeout_timestamp: u64,
+}
+
+impl PacketArgs {
+    fn timeout_height(&self) -> TimeoutHeight {
+        self.timeout_height
+            .map_or(TimeoutHeight::Never, TimeoutHeight::At)
+    }
+
+    fn to_raw(&self) -> anyhow::Result<RawPacket> {
+        Ok(RawPacket {
+            sequence: self.sequence,
+            source_port: self.port_id_on_a.clone(),
+            source_channel: self.channel_id_on_a.clone(),
+            destination_port: self.port_id_on_b.clone(),
+            destination_channel: self.channel_id_on_b.clone(),
+            data: hex::decode(&self.packet_data)?,
+            timeout_height: self.timeout_height.map(Into::into),
+            timeout_timestamp: self.timeout_timestamp,
+        })
+    }
+
+    /// Checks that the packet committed on chain A is the one described by these
+    /// fields, so that chain B does not reject the proof.
+    fn verify_commitment(&self, ibc_state: &IbcState) -> anyhow::Result<()> {
+        let commitment_path = CommitmentPath::new(
+            &self.port_id_on_a.parse()?,
+            &self.channel_id_on_a.parse()?,
+            self.sequence.into(),
+        );
+        let commitment = ibc_state
+            .get(&commitment_path)?
+            .ok_or_else(|| anyhow!("No packet commitment found at {commitment_path}"))?;
+        let expected = compute_packet_commitment(
+            &hex::decode(&self.packet_data)?,
+            &self.timeout_height(),
+            &Timestamp::from_nanoseconds(self.timeout_timestamp)?,
+        );
+        if commitment.into_vec() != expected.into_vec() {
+            bail!("Packet fields do not match the commitment at {commitment_path}");
+        }
+        Ok(())
+    }
+
+    /// Checks the receiver of an ICS-20 transfer against the format of chain B,
+    /// whose ID is tracked by the client under the channel on chain A. A receiver
+    /// of neither format is an error, and one that looks like it belongs to the
+    /// other kind of chain is only warned about.
+    fn check_transfer_receiver(&self, ibc_state: &IbcState) -> anyhow::Result<()> {
+        if self.port_id_on_a != PortId::transfer().as_str() {
+            return Ok(());
+        }
+        let packet_data: serde_json::Value =
+            serde_json::from_slice(&hex::decode(&self.packet_data)?).map_err(|err| {
+                anyhow!("Packet data on the transfer port is not ICS-20 JSON: {err}")
+            })?;
+        let receiver = packet_data["receiver"]
+            .as_str()
+            .ok_or_else(|| anyhow!("ICS-20 packet data has no receiver"))?;
+
+        let channel_end_path =
+            ChannelEndPath::new(&self.port_id_on_a.parse()?, &self.channel_id_on_a.parse()?);
+        let channel_on_a = ibc_state
+            .get_raw(&channel_end_path)?
+            .ok_or_else(|| anyhow!("No channel found at {channel_end_path}"))?;
+        let connection_id_on_a = channel_on_a
+            .connection_hops
+            .first()
+            .ok_or_else(|| anyhow!("Channel {channel_end_path} has no connection hops"))?;
+        let connection_path = ConnectionPath::new(&connection_id_on_a.parse()?);
+        let connection_on_a = ibc_state
+            .get_raw(&connection_path)?
+            .ok_or_else(|| anyhow!("No connection found at {connection_path}"))?;
+        let client_id = connection_on_a.client_id.parse()?;
+        let client_state = ibc_state
+            .get_any(&ClientStatePath::new(&client_id))?
+            .ok_or_else(|| anyhow!("No client state found for client ID {client_id}"))?;
+        let chain_id_of_b = tracked_chain_id(&*decode_client_state(client_state)?)
+            .ok_or_else(|| anyhow!("Client {client_id} has an unsupported type"))?;
+
+        if let Some(warning) = address::check_cpty_address(receiver, &chain_id_of_b)? {
+            warn!("{warning}");
+        }
+        Ok(())
+    }
+
+    /// Proves on chain B that the packet was not received, returning the proof and
+    /// the next sequence to be received on the channel.
+    fn unreceived_proof(&self, ibc_state: &IbcState) -> anyhow::Result<(RawMerkleProof, u64)> {
+        let port_id_on_b = self.port_id_on_b.parse()?;
+        let channel_id_on_b = self.channel_id_on_b.parse()?;
+
+        let channel_end_path = ChannelEndPath::new(&port_id_on_b, &channel_id_on_b);
+        let channel_on_b = ibc_state
+            .get_raw(&channel_end_path)?
+            .ok_or_else(|| anyhow!("No channel found at {channel_end_path}"))?;
+
+        let seq_recv_path = SeqRecvPath::new(&port_id_on_b, &channel_id_on_b);
+        let next_sequence_recv = ibc_state
+            .get(&seq_recv_path)?
+            .ok_or_else(|| anyhow!("No next receive sequence found at {seq_recv_path}"))?;
+        let next_sequence_recv = u64::from(next_sequence_recv);
+
+        if channel_on_b.ordering != RawOrder::Ordered as i32 {
+            let receipt_path =
+                ReceiptPath::new(&port_id_on_b, &channel_id_on_b, self.sequence.into());
+            if ibc_state.get(&receipt_path)?.is_some() {
+                bail!("Packet was already received; found a receipt at {receipt_path}");
+            }
+            bail!(
+                "Channel {channel_end_path} is unordered, so proving that the packet was not \
+                 received needs a non-membership proof of {receipt_path}, which the IBC store \
+                 cannot produce"
+            );
+        }
+
+        if next_sequence_recv > self.sequence {
+            bail!(
+                "Packet was already received; next receive sequence at {seq_recv_path} is \
+                 {next_sequence_recv}"
+            );
+        }
+
+        let proof_unreceived =
+            existence_proof_to_merkle_proof(ibc_state.get_proof(&seq_recv_path)?);
+        Ok((proof_unreceived, next_sequence_recv))
+    }
+
+    fn warn_if_not_timed_out(&self, proof_height: Height) {
+        if self.timeout_timestamp == 0 && !self.timeout_height().has_expired(proof_height) {
+            warn!(
+                "Packet has not timed out at proof height {proof_height}, so chain A will \
+                 reject the timeout"
+            );
+        }
+    }
+}
+
+/// Builds a message acknowledging `packet` on chain A, after checking that
+/// `acknowledgement` matches the commitment chain B stored for it.
+async fn ack_msg(
+    ibc_store: &IbcStore,
+    ibc_state: &IbcState<'_>,
+    cpty_source: &StoreSource,
+    client_id_on_a: &str,
+    packet: RawPacket,
+    acknowledgement: Vec<u8>,
+    signer: String,
+) -> anyhow::Result<(RawMsgAcknowledgement, Provenance)> {
+    let ack_path = AckPath::new(
+        &packet.destination_port.parse()?,
+        &packet.destination_channel.parse()?,
+        packet.sequence.into(),
+    );
+    let ack_commitment = ibc_state
+        .get(&ack_path)?
+        .ok_or_else(|| anyhow!("No acknowledgement found at {ack_path}"))?;
+    if ack_commitment.into_vec() != compute_ack_commitment(&acknowledgement).into_vec() {
+        bail!("Acknowledgement does not match the commitment at {ack_path}");
+    }
+    let proof_acked = existence_proof_to_merkle_proof(ibc_state.get_proof(&ack_path)?);
+
+    let (consensus_height_of_b_on_a, provenance) =
+        get_and_verify_consensus_height_on_cpty(ibc_store, cpty_source, client_id_on_a).await?;
+
+    let msg = RawMsgAcknowledgement {
+        packet: Some(packet),
+        acknowledgement,
+        proof_acked: proof_acked.encode_to_vec(),
+        proof_height: Some(consensus_height_of_b_on_a.into()),
+        signer,
+    };
+    Ok((msg, provenance))
+}
+
+/// Packet messages. `--endpoint` is the chain whose state is proven and
+/// `--cpty-endpoint` is the chain the message is sent to.
+#[derive(Clone, Debug, Subcommand)]
+enum PacketMsg {
+    /// Receive a packet on chain B, proving its commitment on chain A
+    Recv {
+        client_id_on_b: String,
+        #[command(flatten)]
+        packet: PacketArgs,
+    },
+    /// Acknowledge a packet on chain A, proving the acknowledgement on chain B
+    Ack {
+        client_id_on_a: String,
+        #[command(flatten)]
+        packet: PacketArgs,
+
+        /// Hex-encoded acknowledgement written by chain B
+        #[arg(long)]
+        ack: String,
+    },
+    /// Like `ack`, but reads the packet and its acknowledgement from chain B's
+    /// `write_acknowledgement` event instead of taking them as arguments
+    AckAuto {
+        client_id_on_a: String,
+        port_id_on_b: String,
+        channel_id_on_b: String,
+
+        #[arg(long)]
+        sequence: u64,
+
+        /// Number of past IBC transactions on chain B to search for the event
+        #[arg(long, default_value_t = 1000)]
+        history_limit: usize,
+    },
+    /// Time out a packet on chain A, proving it was not received on chain B
+    Timeout {
+        client_id_on_a: String,
+        #[command(flatten)]
+        packet: PacketArgs,
+    },
+    /// Time out a packet on chain A after its channel was closed on chain B
+    TimeoutOnClose {
+        client_id_on_a: String,
+        #[command(flatten)]
+        packet: PacketArgs,
+    },
+}
+
+impl PacketMsg {
+    async fn generate(
+        &self,
+        source: &StoreSource,
+        cpty_source: &StoreSource,
+        signer: String,
+    ) -> anyhow::Result<Option<Provenance>> {
+        let ibc_store = source.ibc_store().await?;
+        let ibc_state = get_ibc_state(&ibc_store)?;
+
+        match self {
+            Self::Recv {
+                client_id_on_b,
+                packet,
+            } => {
+                packet.verify_commitment(&ibc_state)?;
+                packet.check_transfer_receiver(&ibc_state)?;
+                let proof_commitment =
+                    existence_proof_to_merkle_proof(ibc_state.get_proof(&CommitmentPath::new(
+                        &packet.port_id_on_a.parse()?,
+                        &packet.channel_id_on_a.parse()?,
+                        packet.sequence.into(),
+                    ))?);
+
+                let (consensus_height_of_a_on_b, provenance) =
+                    get_and_verify_consensus_height_on_cpty(
+                        &ibc_store,
+                        cpty_source,
+                        client_id_on_b,
+                    )
+                    .await?;
+
+                let msg = RawMsgRecvPacket {
+                    packet: Some(packet.to_raw()?),
+                    proof_commitment: proof_commitment.encode_to_vec(),
+                    proof_height: Some(consensus_height_of_a_on_b.into()),
+                    signer,
+                };
+
+                print_json(msg)?;
+                Ok(Some(provenance))
+            }
+            Self::Ack {
+                client_id_on_a,
+                packet,
+                ack,
+            } => {
+                let (msg, provenance) = ack_msg(
+                    &ibc_store,
+                    &ibc_state,
+                    cpty_source,
+                    client_id_on_a,
+                    packet.to_raw()?,
+                    hex::decode(ack)?,
+                    signer,
+                )
+                .await?;
+
+                print_json(msg)?;
+                Ok(Some(provenance))
+            }
+            Self::AckAuto {
+                client_id_on_a,
+                port_id_on_b,
+                channel_id_on_b,
+                sequence,
+                history_limit,
+            } => {
+                let port_id_on_b = port_id_on_b.parse()?;
+                let channel_id_on_b = channel_id_on_b.parse()?;
+                let (packet, acknowledgement) = relay::find_written_ack(
+                    source.rpc_client()?,
+                    &port_id_on_b,
+                    &channel_id_on_b,
+                    *sequence,
+                    *history_limit,
+                )
+                .await?;
+                let (msg, provenance) = ack_msg(
+                    &ibc_store,
+                    &ibc_state,
+                    cpty_source,
+                    client_id_on_a,
+                    packet,
+                    acknowledgement,
+                    signer,
+                )
+                .await?;
+
+                print_json(msg)?;
+                Ok(Some(provenance))
+            }
+            Self::Timeout {
+                client_id_on_a,
+                packet,
+            } => {
+                let (proof_unreceived, next_sequence_recv) = packet.unreceived_proof(&ibc_state)?;
+
+                let (consensus_height_of_b_on_a, provenance) =
+                    get_and_verify_consensus_height_on_cpty(
+                        &ibc_store,
+                        cpty_source,
+                        client_id_on_a,
+                    )
+                    .await?;
+                packet.warn_if_not_timed_out(consensus_height_of_b_on_a);
+
+                let msg = RawMsgTimeout {
+                    packet: Some(packet.to_raw()?),
+                    proof_unreceived: proof_unreceived.encode_to_vec(),
+                    proof_height: Some(consensus_height_of_b_on_a.into()),
+                    next_sequence_recv,
+                    signer,
+                };
+
+                print_json(msg)?;
+                Ok(Some(provenance))
+            }
+            Self::TimeoutOnClose {
+                client_id_on_a,
+                packet,
+            } => {
+                let (proof_unreceived, next_sequence_recv) = packet.unreceived_proof(&ibc_state)?;
+                let proof_close =
+                    existence_proof_to_merkle_proof(ibc_state.get_proof(&ChannelEndPath::new(
+                        &packet.port_id_on_b.parse()?,
+                        &packet.channel_id_on_b.parse()?,
+                    ))?);
+
+                let (consensus_height_of_b_on_a, provenance) =
+                    get_and_verify_consensus_height_on_cpty(
+                        &ibc_store,
+                        cpty_source,
+                        client_id_on_a,
+                    )
+                    .await?;
+
+                let msg = RawMsgTimeoutOnClose {
+                    packet: Some(packet.to_raw()?),
+                    proof_unreceived: proof_unreceived.encode_to_vec(),
+                    proof_close: proof_close.encode_to_vec(),
+                    proof_height: Some(consensus_height_of_b_on_a.into()),
+                    next_sequence_recv,
+                    signer,
+                };
+
+                print_json(msg)?;
+                Ok(Some(provenance))
             }
         }
     }
@@ -601,6 +1704,62 @@ enum MsgKind {
     Connection(ConnectionMsg),
     #[command(subcommand)]
     Channel(ChannelMsg),
+    #[command(subcommand)]
+    Packet(PacketMsg),
+}
+
+impl MsgKind {
+    /// Type URL of the generated message.
+    fn type_url(&self) -> &'static str {
+        match self {
+            Self::Client(ClientMsg::Create { .. }) => "/ibc.core.client.v1.MsgCreateClient",
+            Self::Client(ClientMsg::Update { .. } | ClientMsg::UpdateRange { .. }) => {
+                "/ibc.core.client.v1.MsgUpdateClient"
+            }
+            Self::Client(ClientMsg::Upgrade { .. }) => "/ibc.core.client.v1.MsgUpgradeClient",
+            Self::Client(ClientMsg::Misbehaviour { .. }) => {
+                "/ibc.core.client.v1.MsgSubmitMisbehaviour"
+            }
+            Self::Connection(ConnectionMsg::OpenInit { .. }) => {
+                "/ibc.core.connection.v1.MsgConnectionOpenInit"
+            }
+            Self::Connection(ConnectionMsg::OpenTry { .. }) => {
+                "/ibc.core.connection.v1.MsgConnectionOpenTry"
+            }
+            Self::Connection(ConnectionMsg::OpenAck { .. }) => {
+                "/ibc.core.connection.v1.MsgConnectionOpenAck"
+            }
+            Self::Connection(ConnectionMsg::OpenConfirm { .. }) => {
+                "/ibc.core.connection.v1.MsgConnectionOpenConfirm"
+            }
+            Self::Channel(ChannelMsg::OpenInit { .. }) => "/ibc.core.channel.v1.MsgChannelOpenInit",
+            Self::Channel(ChannelMsg::OpenTry { .. }) => "/ibc.core.channel.v1.MsgChannelOpenTry",
+            Self::Channel(ChannelMsg::OpenAck { .. }) => "/ibc.core.channel.v1.MsgChannelOpenAck",
+            Self::Channel(ChannelMsg::OpenConfirm { .. }) => {
+                "/ibc.core.channel.v1.MsgChannelOpenConfirm"
+            }
+            Self::Packet(PacketMsg::Recv { .. }) => "/ibc.core.channel.v1.MsgRecvPacket",
+            Self::Packet(PacketMsg::Ack { .. } | PacketMsg::AckAuto { .. }) => {
+                "/ibc.core.channel.v1.MsgAcknowledgement"
+            }
+            Self::Packet(PacketMsg::Timeout { .. }) => "/ibc.core.channel.v1.MsgTimeout",
+            Self::Packet(PacketMsg::TimeoutOnClose { .. }) => {
+                "/ibc.core.channel.v1.MsgTimeoutOnClose"
+            }
+        }
+    }
+
+    /// Fields that must be given explicitly in strict mode.
+    fn explicit_fields(&self, signer: &Option<String>) -> Vec<ExplicitField> {
+        let mut explicit_fields = vec![ExplicitField::new("--signer", "$(solana address)", signer)];
+        explicit_fields.extend(match self {
+            Self::Client(msg) => msg.explicit_fields(),
+            Self::Connection(msg) => msg.explicit_fields(),
+            Self::Channel(msg) => msg.explicit_fields(),
+            Self::Packet(_) => vec![],
+        });
+        explicit_fields
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -613,6 +1772,36 @@ pub(crate) struct Args {
     #[arg(long)]
     cpty_endpoint: Option<String>,
 
+    /// Read this chain's IBC storage account data from a file instead of
+    /// `--endpoint`. Client messages and `ack-auto` still need the endpoint.
+    #[arg(long)]
+    from_file: Option<PathBuf>,
+
+    /// Read the counterparty's IBC storage account data from a file instead of
+    /// `--cpty-endpoint`
+    #[arg(long, conflicts_with = "cpty_endpoint")]
+    cpty_from_file: Option<PathBuf>,
+
+    /// Write what the proofs of the message verify against to this file, for
+    /// `tx --verify-against` to check before submitting the message
+    #[arg(long)]
+    provenance: Option<PathBuf>,
+
+    /// How long after now the message in `--provenance` stays valid to submit,
+    /// which defaults to how long a header of this chain is trusted after it is
+    /// verified
+    #[arg(long, requires = "provenance", value_parser = duration::parse_duration)]
+    valid_for: Option<Duration>,
+
+    /// Signer to put in the generated message
+    #[arg(long)]
+    signer: Option<String>,
+
+    /// Refuse to generate a message if any field the destination chain validates
+    /// would be defaulted
+    #[arg(long)]
+    strict: bool,
+
     /// Message kind to generate
     #[command(subcommand)]
     kind: MsgKind,
@@ -622,29 +1811,699 @@ pub(crate) async fn run(
     Args {
         endpoint,
         cpty_endpoint,
+        from_file,
+        cpty_from_file,
+        provenance: provenance_path,
+        valid_for,
+        signer,
+        strict,
         kind,
     }: Args,
 ) -> anyhow::Result<()> {
-    let rpc_client = RpcClient::new(endpoint);
+    enforce_explicit_fields(strict, &kind.explicit_fields(&signer))?;
+    let signer = signer.unwrap_or_default();
+
+    let source = StoreSource::new(endpoint, from_file);
+    let cpty_source = match (cpty_endpoint, cpty_from_file) {
+        (_, Some(cpty_from_file)) => Some(StoreSource::File(cpty_from_file)),
+        (Some(cpty_endpoint), None) => Some(StoreSource::Rpc(RpcClient::new(cpty_endpoint))),
+        (None, None) => None,
+    };
+    let require_cpty_source = || {
+        cpty_source
+            .as_ref()
+            .ok_or_else(|| anyhow!("Must specify counterparty endpoint or file"))
+    };
+
+    // The message is sent to the counterparty, so that is the program to check
+    if let Some(cpty_source) = &cpty_source {
+        capabilities::check_store(
+            "the cpty chain",
+            cpty_source.ibc_store(),
+            &[kind.type_url()],
+        )
+        .await;
+    }
 
     info!("Generating IBC tx: {kind:?}");
-    match kind {
-        MsgKind::Client(msg) => {
-            msg.generate(&rpc_client).await?;
-        }
+    let provenance = match kind {
+        MsgKind::Client(msg) => msg.generate(&source, cpty_source.as_ref(), signer).await?,
         MsgKind::Connection(msg) => {
-            let cpty_endpoint =
-                cpty_endpoint.ok_or_else(|| anyhow!("Must specify counterparty endpoint"))?;
-            let cpty_rpc_client = RpcClient::new(cpty_endpoint);
-            msg.generate(&rpc_client, &cpty_rpc_client).await?;
+            msg.generate(&source, require_cpty_source()?, signer)
+                .await?
         }
         MsgKind::Channel(msg) => {
-            let cpty_endpoint =
-                cpty_endpoint.ok_or_else(|| anyhow!("Must specify counterparty endpoint"))?;
-            let cpty_rpc_client = RpcClient::new(cpty_endpoint);
-            msg.generate(&rpc_client, &cpty_rpc_client).await?;
+            msg.generate(&source, require_cpty_source()?, signer)
+                .await?
+        }
+        MsgKind::Packet(msg) => {
+            msg.generate(&source, require_cpty_source()?, signer)
+                .await?
         }
+    };
+
+    if let Some(provenance_path) = provenance_path {
+        let mut provenance = provenance
+            .ok_or_else(|| anyhow!("This message carries no proofs to record provenance of"))?;
+        let generated_at = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        provenance.expire_after(
+            i64::try_from(generated_at.as_secs())?,
+            valid_for.unwrap_or(eclipse_chain::IBC_MESSAGE_VALID_DURATION),
+        );
+        fs::write(
+            &provenance_path,
+            serde_json::to_string_pretty(&provenance)? + "\n",
+        )
+        .with_context(|| format!("Failed to write {}", provenance_path.display()))?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::provenance::parse_provenances,
+        eclipse_ibc_fixtures::{clients, FixtureStore},
+        eclipse_ibc_light_client::{
+            eclipse_chain::IBC_MESSAGE_VALID_DURATION, EclipseConsensusState, EclipseHeader,
+        },
+        eclipse_ibc_state::internal_path::{ClientUpdateTimePath, ConsensusHeightsPath},
+        ibc::core::{
+            ics02_client::client_state::ClientState as _,
+            ics23_commitment::commitment::CommitmentRoot,
+            ics24_host::identifier::{ChannelId, PortId},
+        },
+        solana_sdk::clock::Slot,
+        tendermint::time::Time as TendermintTime,
+    };
+
+    fn missing_flags(args: &[&str]) -> Vec<&'static str> {
+        let Args { signer, kind, .. } =
+            Args::try_parse_from(["generate"].iter().chain(args)).unwrap();
+        kind.explicit_fields(&signer)
+            .into_iter()
+            .filter(|field| !field.given)
+            .map(|field| field.flag)
+            .collect()
+    }
+
+    #[test]
+    fn client_msgs_require_signer() {
+        for args in [
+            &["client", "create", "devnet"][..],
+            &["client", "update", "07-tendermint-0"][..],
+            &[
+                "client",
+                "update-range",
+                "xx-eclipse-0",
+                "--start-height",
+                "10",
+            ][..],
+            &[
+                "client",
+                "upgrade",
+                "07-tendermint-0",
+                "--upgrade-height",
+                "0-100",
+            ][..],
+            &[
+                "client",
+                "misbehaviour",
+                "07-tendermint-0",
+                "--header-2-file",
+                "header.json",
+            ][..],
+        ] {
+            assert_eq!(missing_flags(args), ["--signer"], "{args:?}");
+        }
+        assert!(
+            missing_flags(&["--signer", "payer", "client", "update", "07-tendermint-0"]).is_empty()
+        );
+    }
+
+    #[test]
+    fn connection_msgs_require_version() {
+        let open_init = ["connection", "open-init", "xx-eclipse-0", "07-tendermint-0"];
+        let open_try = [
+            "connection",
+            "open-try",
+            "07-tendermint-0",
+            "xx-eclipse-0",
+            "connection-0",
+        ];
+        let open_ack = [
+            "connection",
+            "open-ack",
+            "xx-eclipse-0",
+            "connection-0",
+            "07-tendermint-0",
+            "connection-1",
+        ];
+        for args in [&open_init[..], &open_try[..], &open_ack[..]] {
+            assert_eq!(
+                missing_flags(args),
+                ["--signer", "--connection-version"],
+                "{args:?}"
+            );
+        }
+        assert_eq!(
+            missing_flags(&[
+                "connection",
+                "open-confirm",
+                "07-tendermint-0",
+                "connection-1",
+                "connection-0",
+            ]),
+            ["--signer"]
+        );
+
+        let explicit = [
+            &["--signer", "payer"][..],
+            &open_init[..],
+            &["--connection-version", CONNECTION_VERSION_EXAMPLE][..],
+        ]
+        .concat();
+        assert!(missing_flags(&explicit).is_empty());
+        parse_connection_version("1").unwrap_err();
+    }
+
+    #[test]
+    fn channel_msgs_require_versions_and_ordering() {
+        assert_eq!(
+            missing_flags(&[
+                "channel",
+                "open-init",
+                "connection-0",
+                "transfer",
+                "transfer"
+            ]),
+            ["--signer", "--ordering", "--channel-version"]
+        );
+        assert_eq!(
+            missing_flags(&[
+                "channel",
+                "open-try",
+                "07-tendermint-0",
+                "connection-0",
+                "transfer",
+                "transfer",
+                "channel-0",
+            ]),
+            [
+                "--signer",
+                "--ordering",
+                "--channel-version",
+                "--counterparty-version"
+            ]
+        );
+        assert_eq!(
+            missing_flags(&[
+                "channel",
+                "open-ack",
+                "07-tendermint-0",
+                "transfer",
+                "channel-0",
+                "transfer",
+                "channel-1",
+            ]),
+            ["--signer", "--counterparty-version"]
+        );
+        assert_eq!(
+            missing_flags(&[
+                "channel",
+                "open-confirm",
+                "07-tendermint-0",
+                "transfer",
+                "channel-1",
+                "transfer",
+                "channel-0",
+            ]),
+            ["--signer"]
+        );
+
+        assert!(missing_flags(&[
+            "--signer",
+            "payer",
+            "channel",
+            "open-init",
+            "connection-0",
+            "transfer",
+            "transfer",
+            "--ordering",
+            CHANNEL_ORDERING_EXAMPLE,
+            "--channel-version",
+            CHANNEL_VERSION_EXAMPLE,
+        ])
+        .is_empty());
+    }
+
+    #[test]
+    fn packet_msgs_take_packet_fields() {
+        let packet_args = [
+            "transfer",
+            "channel-0",
+            "transfer",
+            "channel-1",
+            "3",
+            "--packet-data",
+            "0102",
+            "--timeout-height",
+            "0-100",
+        ];
+        let args = [&["packet", "recv", "07-tendermint-0"][..], &packet_args[..]].concat();
+        assert_eq!(missing_flags(&args), ["--signer"]);
+
+        let Args { kind, .. } = Args::try_parse_from(["generate"].iter().chain(&args)).unwrap();
+        let packet = match kind {
+            MsgKind::Packet(PacketMsg::Recv { packet, .. }) => packet,
+            kind => panic!("expected a recv packet message, got {kind:?}"),
+        };
+        let raw_packet = packet.to_raw().unwrap();
+        assert_eq!(raw_packet.sequence, 3);
+        assert_eq!(raw_packet.destination_channel, "channel-1");
+        assert_eq!(raw_packet.data, [1, 2]);
+        assert_eq!(
+            raw_packet.timeout_height,
+            Some(Height::new(0, 100).unwrap().into())
+        );
+
+        let ack = [&["packet", "ack", "xx-eclipse-0"][..], &packet_args[..]].concat();
+        Args::try_parse_from(["generate"].iter().chain(&ack)).unwrap_err();
+        let ack = [&ack[..], &["--ack", "01"][..]].concat();
+        Args::try_parse_from(["generate"].iter().chain(&ack)).unwrap();
+
+        let ack_auto = [
+            "packet",
+            "ack-auto",
+            "xx-eclipse-0",
+            "transfer",
+            "channel-1",
+        ];
+        Args::try_parse_from(["generate"].iter().chain(&ack_auto)).unwrap_err();
+        let ack_auto = [&ack_auto[..], &["--sequence", "3"][..]].concat();
+        Args::try_parse_from(["generate"].iter().chain(&ack_auto)).unwrap();
+    }
+
+    #[test]
+    fn connection_client_state_is_reencoded_eclipse_client_state() {
+        let client_id = "xx-eclipse-0".parse::<ClientId>().unwrap();
+        let client_state = clients::eclipse_client_state(5);
+
+        let any = client_state.clone().encode_as_any();
+        assert_eq!(
+            reencode_counterparty_client_state(&client_id, any.clone()).unwrap(),
+            any
+        );
+
+        // Client states stored wrapped in a second `Any` are unwrapped
+        let store = IbcStore::default();
+        let mut ibc_state = IbcState::new(&store, 0);
+        ibc_state.set(
+            &ClientStatePath::new(&client_id),
+            protobuf::Any {
+                type_url: any.type_url.clone(),
+                value: any.encode_to_vec(),
+            },
+        );
+        assert_eq!(
+            get_counterparty_client_state(&ibc_state, client_id.as_str()).unwrap(),
+            any
+        );
+        get_counterparty_client_state(&ibc_state, "xx-eclipse-1").unwrap_err();
+    }
+
+    #[test]
+    fn connection_client_state_rejects_tendermint_and_corrupted_client_states() {
+        let client_id = "07-tendermint-0".parse::<ClientId>().unwrap();
+        let tendermint_client_state = clients::tendermint_client_state_any(100);
+        let err = reencode_counterparty_client_state(&client_id, tendermint_client_state)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("counterparty is an Eclipse chain"), "{err}");
+
+        let corrupted_client_state = protobuf::Any {
+            type_url: ECLIPSE_CLIENT_STATE_TYPE_URL.to_owned(),
+            value: vec![0xff; 8],
+        };
+        let err = reencode_counterparty_client_state(&client_id, corrupted_client_state)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("Stored client state of 07-tendermint-0 is invalid"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn cpty_client_must_track_this_chain() {
+        let client_id = "xx-eclipse-0".parse::<ClientId>().unwrap();
+        let store = IbcStore::default();
+        let mut ibc_state = IbcState::new(&store, 0);
+        ibc_state.set(
+            &ClientStatePath::new(&client_id),
+            clients::eclipse_client_state(5).encode_as_any(),
+        );
+
+        check_client_tracks_chain(&ibc_state, client_id.as_str(), Some(clients::CHAIN_NAME))
+            .unwrap();
+        check_client_tracks_chain(&ibc_state, client_id.as_str(), None).unwrap();
+        let err = check_client_tracks_chain(&ibc_state, client_id.as_str(), Some("other"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("not this chain eclipse-other"), "{err}");
+    }
+
+    #[test]
+    fn strict_mode_lists_missing_flags() {
+        let fields = [
+            ExplicitField::new("--signer", "$(solana address)", &Some("payer")),
+            ExplicitField::new(
+                "--channel-version",
+                CHANNEL_VERSION_EXAMPLE,
+                &None::<String>,
+            ),
+        ];
+        let err = enforce_explicit_fields(true, &fields)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("--channel-version ics20-1"), "{err}");
+        assert!(!err.contains("--signer"), "{err}");
+
+        enforce_explicit_fields(false, &fields).unwrap();
+        enforce_explicit_fields(true, &fields[..1]).unwrap();
+    }
+
+    /// Writes `account_data` where `--from-file` can read it, as `solana account
+    /// --output-file` would.
+    fn snapshot_file(name: &str, account_data: &IbcAccountData) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("eclipse-ibc-{name}-{}.bin", std::process::id()));
+        fs::write(&path, account_data.to_bytes().unwrap()).unwrap();
+        path
+    }
+
+    /// Updates the client `client_id` on chain B to a header of chain A at
+    /// `height` with `commitment_root`, in slot `slot` of chain B.
+    fn update_client_on_b(
+        account_data_b: &IbcAccountData,
+        slot: Slot,
+        client_id: &ClientId,
+        height: Height,
+        commitment_root: CommitmentRoot,
+        updated_at: Timestamp,
+    ) {
+        let header = EclipseHeader {
+            height,
+            commitment_root,
+            timestamp: TendermintTime::unix_epoch(),
+        };
+        let client_state = EclipseClientState {
+            latest_header: header.clone(),
+            ..clients::eclipse_client_state(1)
+        };
+        let mut state = IbcState::new(&account_data_b.store, slot);
+        state
+            .set_any(
+                &ClientStatePath::new(client_id),
+                client_state.encode_as_any(),
+            )
+            .unwrap();
+        state
+            .set_any(
+                &ClientConsensusStatePath::new(client_id, &height),
+                EclipseConsensusState::from(header).encode_as_any(),
+            )
+            .unwrap();
+        state
+            .update(
+                &ConsensusHeightsPath(client_id.clone()),
+                |consensus_heights| {
+                    consensus_heights.heights.insert(height);
+                },
+            )
+            .unwrap();
+        state.set(&ClientUpdateTimePath(client_id.clone(), height), updated_at);
+        state.commit().unwrap();
+    }
+
+    #[tokio::test]
+    async fn channel_open_init_checks_every_connection_hop() {
+        let args = Args::try_parse_from([
+            "generate",
+            "channel",
+            "open-init",
+            "connection-0,connection-7,connection-0",
+            "transfer",
+            "transfer",
+        ])
+        .unwrap();
+        let msg = match args.kind {
+            MsgKind::Channel(msg) => msg,
+            kind => panic!("{kind:?}"),
+        };
+        match &msg {
+            ChannelMsg::OpenInit {
+                connection_id_on_a, ..
+            } => assert_eq!(
+                connection_id_on_a,
+                &["connection-0", "connection-7", "connection-0"]
+            ),
+            msg => panic!("{msg:?}"),
+        }
+
+        // Chain A, where `OpenInit` is sent, only has an open `connection-0`
+        let account_data_a = FixtureStore::with_clients(1).with_channels(1).build();
+        let source = StoreSource::File(snapshot_file("hops-b", &FixtureStore::default().build()));
+        let cpty_source = StoreSource::File(snapshot_file("hops-a", &account_data_a));
+        let err = msg
+            .generate(&source, &cpty_source, "signer".to_owned())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Connection hop 1 (connection-7) not found"),
+            "{err}"
+        );
+
+        let msg = ChannelMsg::OpenInit {
+            connection_id_on_a: vec!["connection-0".to_owned()],
+            port_id_on_a: "transfer".to_owned(),
+            port_id_on_b: "transfer".to_owned(),
+            ordering: None,
+            channel_version: None,
+        };
+        assert!(msg
+            .generate(&source, &cpty_source, "signer".to_owned())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn messages_generated_from_snapshots_are_revalidated_before_submitting() {
+        // Chain A has `connection-0`, which chain B's client `xx-eclipse-0` of A
+        // verifies in `OpenConfirm`
+        let account_data_a = FixtureStore::with_clients(1).with_channels(1).build();
+        let version_a = account_data_a.store.read().latest_version().unwrap();
+        let height_a = Height::of_slot(version_a).unwrap();
+        let root_a = IbcState::new(&account_data_a.store, version_a)
+            .get_commitment_root_option(version_a)
+            .unwrap()
+            .unwrap();
+
+        let account_data_b = FixtureStore::with_clients(1).build();
+        let client_id = "xx-eclipse-0".parse::<ClientId>().unwrap();
+        let updated_at = Timestamp::from_nanoseconds(1_000_000_000_000).unwrap();
+        update_client_on_b(
+            &account_data_b,
+            3,
+            &client_id,
+            height_a,
+            root_a.clone(),
+            updated_at,
+        );
+
+        // Offline: both chains are read from snapshots
+        let source = StoreSource::File(snapshot_file("snapshot-a", &account_data_a));
+        let cpty_source = StoreSource::File(snapshot_file("snapshot-b", &account_data_b));
+        let msg = ConnectionMsg::OpenConfirm {
+            client_id_on_b: client_id.to_string(),
+            connection_id_on_b: "connection-0".to_owned(),
+            connection_id_on_a: "connection-0".to_owned(),
+        };
+        let provenance = msg
+            .generate(&source, &cpty_source, "signer".to_owned())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(provenance.proof_height, height_a.to_string());
+        assert_eq!(provenance.commitment_root, hex::encode(root_a.as_bytes()));
+
+        // Client messages need the chain itself, not just its IBC storage account
+        let err = ClientMsg::Update {
+            client_id: client_id.to_string(),
+        }
+        .generate(&source, Some(&cpty_source), "signer".to_owned())
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("needs a live endpoint"), "{err}");
+
+        // Online: the provenance file is checked against chain B's live state
+        let provenances =
+            parse_provenances(&serde_json::to_vec(&[provenance.clone()]).unwrap()).unwrap();
+        assert_eq!(provenances, [provenance.clone()]);
+        let state_b = get_ibc_state(&account_data_b.store).unwrap();
+        provenance.check_still_valid(&state_b, updated_at).unwrap();
+
+        let expired_at =
+            (updated_at + (IBC_MESSAGE_VALID_DURATION + Duration::from_secs(1))).unwrap();
+        let err = provenance
+            .check_still_valid(&state_b, expired_at)
+            .unwrap_err();
+        assert!(err.to_string().contains("Expired"), "{err}");
+
+        // A consensus state at the proof height that no longer matches the proven
+        // root is refused too
+        update_client_on_b(
+            &account_data_b,
+            4,
+            &client_id,
+            height_a,
+            CommitmentRoot::from_bytes(&[9; 32]),
+            updated_at,
+        );
+        let state_b = get_ibc_state(&account_data_b.store).unwrap();
+        let err = provenance
+            .check_still_valid(&state_b, updated_at)
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("proofs were generated against"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn written_upgrades_are_proven_at_the_upgrade_height() {
+        let account_data = FixtureStore::with_clients(1).build();
+        let version = account_data.store.read().latest_version().unwrap();
+        let upgrade_height = Height::of_slot(version + 5).unwrap();
+        let revision_height = upgrade_height.revision_height();
+        let upgraded_client_state = clients::eclipse_client_state(revision_height + 1);
+        let upgraded_consensus_state =
+            EclipseConsensusState::from(clients::eclipse_header(revision_height + 1));
+
+        // What `MsgWriteUpgrade` stores, followed by unrelated writes before the
+        // upgrade height
+        let mut state = IbcState::new(&account_data.store, version + 1);
+        state
+            .set_any(
+                &UpgradeClientPath::UpgradedClientState(revision_height),
+                upgraded_client_state.clone().encode_as_any(),
+            )
+            .unwrap();
+        state
+            .set_any(
+                &UpgradeClientPath::UpgradedClientConsensusState(revision_height),
+                upgraded_consensus_state.encode_as_any(),
+            )
+            .unwrap();
+        state.commit().unwrap();
+        let mut state = IbcState::new(&account_data.store, version + 3);
+        state.set(
+            &SeqRecvPath::new(&PortId::transfer(), &ChannelId::new(0)),
+            1.into(),
+        );
+        state.commit().unwrap();
+
+        let (msg, provenance) = upgrade_client_msg(
+            &account_data.store,
+            "xx-eclipse-0",
+            upgrade_height,
+            "signer".to_owned(),
+        )
+        .unwrap();
+        assert_eq!(provenance.source_version, version + 3);
+        assert_eq!(provenance.proof_height, upgrade_height.to_string());
+        assert_eq!(
+            msg.client_state,
+            Some(upgraded_client_state.encode_as_any())
+        );
+        let root = CommitmentRoot::from_bytes(&hex::decode(&provenance.commitment_root).unwrap());
+
+        let verify = |client_height| {
+            clients::eclipse_client_state(client_height).verify_upgrade_client(
+                msg.client_state.clone().unwrap(),
+                msg.consensus_state.clone().unwrap(),
+                RawMerkleProof::decode(&*msg.proof_upgrade_client).unwrap(),
+                RawMerkleProof::decode(&*msg.proof_upgrade_consensus_state).unwrap(),
+                &root,
+            )
+        };
+        verify(revision_height).unwrap();
+        // Clients look the upgrade up at their latest height
+        verify(revision_height - 1).unwrap_err();
+
+        let err = upgrade_client_msg(
+            &account_data.store,
+            "xx-eclipse-0",
+            upgrade_height.succ().unwrap(),
+            "signer".to_owned(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("No upgrade was written"), "{err}");
+    }
+
+    #[test]
+    fn update_ranges_end_at_the_end_height() {
+        assert_eq!(range_heights(10, 50, 10).unwrap(), [10, 20, 30, 40, 50]);
+        assert_eq!(range_heights(10, 45, 10).unwrap(), [10, 20, 30, 40, 45]);
+        assert_eq!(range_heights(10, 10, 3).unwrap(), [10]);
+        range_heights(10, 9, 1).unwrap_err();
+        range_heights(10, 20, 0).unwrap_err();
+
+        let Args { kind, .. } = Args::try_parse_from([
+            "generate",
+            "client",
+            "update-range",
+            "xx-eclipse-0",
+            "--start-height",
+            "10",
+            "--as-batch",
+        ])
+        .unwrap();
+        assert!(matches!(
+            kind,
+            MsgKind::Client(ClientMsg::UpdateRange {
+                end_height: None,
+                step: 1,
+                as_batch: true,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn header_files_hold_the_json_of_a_header_any() {
+        let header = clients::eclipse_header(10);
+        let path = std::env::temp_dir().join(format!(
+            "eclipse-ibc-misbehaviour-header-{}.json",
+            std::process::id()
+        ));
+        // As printed in the `header` of `generate client update`
+        let mut any = header.clone().encode_as_any();
+        fs::write(&path, serde_json::to_string(&any).unwrap()).unwrap();
+        assert_eq!(read_header_file(&path).unwrap(), header);
+
+        // Headers generated by older CLIs carry the legacy type URL
+        any.type_url = "/eclipse.ibc.v1.chain.Header".to_owned();
+        fs::write(&path, serde_json::to_string(&any).unwrap()).unwrap();
+        assert_eq!(read_header_file(&path).unwrap(), header);
+
+        any.type_url = clients::TENDERMINT_CLIENT_STATE_TYPE_URL.to_owned();
+        fs::write(&path, serde_json::to_string(&any).unwrap()).unwrap();
+        assert!(read_header_file(&path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
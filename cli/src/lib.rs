@@ -1,7 +1,24 @@
+pub mod account_watcher;
+pub mod address;
+mod build_info;
+mod capabilities;
 mod chain_state;
 mod cli;
+mod client_compat;
+mod confirmation;
+mod doctor;
+pub mod duration;
 mod generate;
+mod proof;
+mod provenance;
 mod query;
+mod relay;
+mod relay_schedule;
+mod relay_spend;
+mod serve;
+mod store_cache;
+mod store_write_batch;
 mod tx;
+mod watch;
 
 pub use cli::run;
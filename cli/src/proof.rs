@@ -0,0 +1,333 @@
+use {
+    anyhow::{anyhow, bail},
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine as _},
+    eclipse_ibc_known_path::KnownPath,
+    eclipse_ibc_light_client::eclipse_chain::{self, EclipseHeight},
+    eclipse_ibc_state::IbcState,
+    ibc::core::ics02_client::height::Height,
+    ibc_proto::ibc::core::commitment::v1::MerkleProof as RawMerkleProof,
+    ics23::{
+        calculate_existence_root, commitment_proof, CommitmentProof, ExistenceProof, HashOp,
+        HostFunctionsManager, LengthOp,
+    },
+    prost::Message as _,
+    serde_json::{json, Value},
+    sha2::{Digest as _, Sha256},
+};
+
+pub(crate) fn existence_proof_to_merkle_proof(existence_proof: ExistenceProof) -> RawMerkleProof {
+    eclipse_chain::merkle_proof(existence_proof)
+}
+
+/// Shows the membership proof of `key` in the state at the slot of
+/// `proof_height` as a relayer would hand it to the counterparty, along with that
+/// height and the root it verifies against.
+pub(crate) fn membership_proof_json<K>(
+    ibc_state: &IbcState<'_>,
+    proof_height: Height,
+    key: &K,
+) -> anyhow::Result<Value>
+where
+    K: KnownPath,
+{
+    let version = proof_height.to_slot()?;
+    if ibc_state.get_raw(key)?.is_none() {
+        bail!("No value found for key: {key}; proving absence is not supported yet");
+    }
+    let merkle_proof = existence_proof_to_merkle_proof(ibc_state.get_proof(key)?);
+    let commitment_root = ibc_state
+        .get_commitment_root_option(version)?
+        .ok_or_else(|| anyhow!("IBC store has no root at version {version}"))?;
+
+    Ok(json!({
+        "path": key.to_string(),
+        "proof": hex::encode(merkle_proof.encode_to_vec()),
+        "proof_height": proof_height.to_string(),
+        "commitment_root": hex::encode(commitment_root.as_bytes()),
+    }))
+}
+
+/// Reads proof bytes written as hex or base64 text, as in JSON messages, or as
+/// raw binary. Text that decodes as neither is taken as binary.
+pub(crate) fn proof_bytes(input: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let text = match std::str::from_utf8(input) {
+        Ok(text) => text.trim(),
+        Err(_) => return Ok(input.to_vec()),
+    };
+    if text.is_empty() {
+        bail!("Proof is empty");
+    }
+    if text.len() % 2 == 0 && text.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Ok(hex::decode(text)?);
+    }
+    Ok(BASE64.decode(text).unwrap_or_else(|_| input.to_vec()))
+}
+
+/// Whether a decoded proof has the fields a real one of its kind always has,
+/// which tells the encodings of `MerkleProof` and `CommitmentProof` apart.
+fn is_plausible(proof: &CommitmentProof) -> bool {
+    match &proof.proof {
+        Some(commitment_proof::Proof::Exist(exist)) => {
+            !exist.key.is_empty() && exist.leaf.is_some()
+        }
+        Some(commitment_proof::Proof::Nonexist(nonexist)) => !nonexist.key.is_empty(),
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Decodes an encoded `MerkleProof`, as attached to IBC messages, or a single
+/// `CommitmentProof`. Returns the name of the encoding along with the proofs,
+/// innermost first.
+fn decode_commitment_proofs(bytes: &[u8]) -> anyhow::Result<(&'static str, Vec<CommitmentProof>)> {
+    if let Ok(merkle_proof) = RawMerkleProof::decode(bytes) {
+        let proofs = merkle_proof
+            .proofs
+            .iter()
+            .map(|proof| CommitmentProof::decode(&*proof.encode_to_vec()))
+            .collect::<Result<Vec<_>, _>>();
+        if let Ok(proofs) = proofs {
+            if !proofs.is_empty() && proofs.iter().all(is_plausible) {
+                return Ok(("merkle_proof", proofs));
+            }
+        }
+    }
+
+    let proof = CommitmentProof::decode(bytes)
+        .map_err(|err| anyhow!("Proof is neither a MerkleProof nor a CommitmentProof: {err}"))?;
+    if !is_plausible(&proof) {
+        bail!("Proof is neither a MerkleProof nor a CommitmentProof");
+    }
+    Ok(("commitment_proof", vec![proof]))
+}
+
+fn hash_op_name(op: i32) -> String {
+    HashOp::from_i32(op).map_or_else(|| format!("unknown ({op})"), |op| format!("{op:?}"))
+}
+
+fn length_op_name(op: i32) -> String {
+    LengthOp::from_i32(op).map_or_else(|| format!("unknown ({op})"), |op| format!("{op:?}"))
+}
+
+/// Applies the prehash op of a leaf to a key or value. Only the ops that the
+/// proof specs of this chain and of Cosmos chains prehash with are supported;
+/// others give `None`.
+fn prehash(op: i32, data: &[u8]) -> Option<Vec<u8>> {
+    match HashOp::from_i32(op)? {
+        HashOp::NoHash => Some(data.to_vec()),
+        HashOp::Sha256 => Some(Sha256::digest(data).to_vec()),
+        _ => None,
+    }
+}
+
+/// Shows an existence proof step by step, with the root ics23 computes from it.
+fn existence_proof_json(proof: &ExistenceProof) -> Value {
+    let leaf = proof.leaf.as_ref();
+    let mut json = json!({
+        "key": hex::encode(&proof.key),
+        "key_utf8": std::str::from_utf8(&proof.key).ok(),
+        "value": hex::encode(&proof.value),
+        "leaf": leaf.map(|leaf| json!({
+            "hash": hash_op_name(leaf.hash),
+            "prehash_key": hash_op_name(leaf.prehash_key),
+            "prehash_value": hash_op_name(leaf.prehash_value),
+            "length": length_op_name(leaf.length),
+            "prefix": hex::encode(&leaf.prefix),
+        })),
+        "key_hash": leaf
+            .and_then(|leaf| prehash(leaf.prehash_key, &proof.key))
+            .map(hex::encode),
+        "value_hash": leaf
+            .and_then(|leaf| prehash(leaf.prehash_value, &proof.value))
+            .map(hex::encode),
+        "path_steps": proof.path.len(),
+        "path": proof
+            .path
+            .iter()
+            .map(|step| json!({
+                "hash": hash_op_name(step.hash),
+                "prefix": hex::encode(&step.prefix),
+                "suffix": hex::encode(&step.suffix),
+            }))
+            .collect::<Vec<_>>(),
+    });
+    match calculate_existence_root::<HostFunctionsManager>(proof) {
+        Ok(root) => json["computed_root"] = Value::String(hex::encode(root)),
+        Err(err) => {
+            json["computed_root"] = Value::Null;
+            json["error"] = Value::String(format!("{err:#}"));
+        }
+    }
+    json
+}
+
+fn commitment_proof_json(proof: &CommitmentProof) -> Value {
+    match &proof.proof {
+        Some(commitment_proof::Proof::Exist(exist)) => {
+            let mut json = existence_proof_json(exist);
+            json["kind"] = json!("exist");
+            json
+        }
+        Some(commitment_proof::Proof::Nonexist(nonexist)) => json!({
+            "kind": "nonexist",
+            "key": hex::encode(&nonexist.key),
+            "key_utf8": std::str::from_utf8(&nonexist.key).ok(),
+            "left": nonexist.left.as_ref().map(existence_proof_json),
+            "right": nonexist.right.as_ref().map(existence_proof_json),
+        }),
+        Some(commitment_proof::Proof::Batch(batch)) => json!({
+            "kind": "batch",
+            "entries": batch.entries.len(),
+        }),
+        Some(commitment_proof::Proof::Compressed(compressed)) => json!({
+            "kind": "compressed",
+            "entries": compressed.entries.len(),
+        }),
+        None => json!({ "kind": "empty" }),
+    }
+}
+
+/// Shows the structure of an encoded proof for debugging: each layer with its
+/// leaf spec, key and value hashes and path, and the root ics23 computes from
+/// it. Each layer past the first should prove the root of the one before it,
+/// which `value_is_previous_root` checks.
+pub(crate) fn decode_proof_json(bytes: &[u8]) -> anyhow::Result<Value> {
+    let (format, proofs) = decode_commitment_proofs(bytes)?;
+
+    let mut previous_root = None::<Value>;
+    let layers = proofs
+        .iter()
+        .map(|proof| {
+            let mut json = commitment_proof_json(proof);
+            if let Some(Value::String(previous_root)) = previous_root.take() {
+                json["value_is_previous_root"] = json!(json["value"] == previous_root);
+            }
+            previous_root = json.get("computed_root").cloned();
+            json
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json!({
+        "format": format,
+        "layers": layers.len(),
+        "proofs": layers,
+        "computed_root": previous_root,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        eclipse_ibc_fixtures::{clients, proof_bundle::ProofBundle, FixtureStore},
+        eclipse_ibc_light_client::EclipseClientState,
+        ibc::core::{
+            ics02_client::client_state::ClientState,
+            ics04_channel::packet::Sequence,
+            ics23_commitment::commitment::{CommitmentProofBytes, CommitmentRoot},
+            ics24_host::{
+                identifier::{ChannelId, PortId},
+                path::{CommitmentPath, Path},
+            },
+        },
+    };
+
+    #[test]
+    fn membership_proofs_verify_against_their_root() {
+        let account_data = FixtureStore::with_clients(1)
+            .with_channels(1)
+            .with_packets(2)
+            .build();
+        let version = account_data.store.read().latest_version().unwrap();
+        let ibc_state = IbcState::new(&account_data.store, version);
+
+        let commitment_path =
+            CommitmentPath::new(&PortId::transfer(), &ChannelId::new(0), Sequence::from(2));
+        // Proofs are at the height of their slot in the chain's current revision
+        let proof_height = eclipse_chain::height_of_slot_with_revision(version, 1).unwrap();
+        let json = membership_proof_json(&ibc_state, proof_height, &commitment_path).unwrap();
+        assert_eq!(json["path"], commitment_path.to_string());
+        assert_eq!(json["proof_height"], proof_height.to_string());
+
+        let commitment_root = CommitmentRoot::from_bytes(
+            &hex::decode(json["commitment_root"].as_str().unwrap()).unwrap(),
+        );
+        let proof =
+            CommitmentProofBytes::try_from(hex::decode(json["proof"].as_str().unwrap()).unwrap())
+                .unwrap();
+        let prefix = eclipse_chain::commitment_prefix();
+        let client_state = clients::eclipse_client_state(version);
+        for (value, verifies) in [(vec![2; 32], true), (vec![1; 32], false)] {
+            let result = client_state.verify_membership(
+                &prefix,
+                &proof,
+                &commitment_root,
+                Path::Commitment(commitment_path.clone()),
+                value,
+            );
+            assert_eq!(result.is_ok(), verifies);
+        }
+
+        // Absence cannot be proven yet
+        let absent_path =
+            CommitmentPath::new(&PortId::transfer(), &ChannelId::new(0), Sequence::from(3));
+        let err = membership_proof_json(&ibc_state, proof_height, &absent_path).unwrap_err();
+        assert!(
+            err.to_string().contains("proving absence is not supported"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn decoded_proofs_compute_the_committed_root() {
+        let bundle = ProofBundle::packet_commitment();
+        let root = hex::encode(bundle.header.commitment_root.as_bytes());
+
+        let json = decode_proof_json(&bundle.proof).unwrap();
+        assert_eq!(json["format"], "merkle_proof");
+        assert_eq!(json["layers"], 2);
+        assert_eq!(json["computed_root"], root);
+        let layer = &json["proofs"][0];
+        assert_eq!(layer["kind"], "exist");
+        assert_eq!(layer["key_utf8"], bundle.path.to_string());
+        assert_eq!(layer["leaf"]["hash"], "Sha256");
+        assert_eq!(layer["path_steps"], layer["path"].as_array().unwrap().len());
+        let prefix_layer = &json["proofs"][1];
+        assert_eq!(prefix_layer["key_utf8"], "ibc");
+        assert_eq!(prefix_layer["value_is_previous_root"], true);
+
+        // The store's commitment proof inside decodes the same, from hex or base64
+        let merkle_proof = RawMerkleProof::decode(&*bundle.proof).unwrap();
+        let commitment_proof = merkle_proof.proofs[0].encode_to_vec();
+        for input in [
+            hex::encode(&commitment_proof),
+            BASE64.encode(&commitment_proof),
+        ] {
+            let json = decode_proof_json(&proof_bytes(input.as_bytes()).unwrap()).unwrap();
+            assert_eq!(json["format"], "commitment_proof");
+            assert_eq!(json["computed_root"], layer["computed_root"]);
+        }
+    }
+
+    #[test]
+    fn corrupt_proofs_are_told_apart_from_wrong_roots() {
+        let bundle = ProofBundle::packet_commitment();
+        let root = hex::encode(bundle.state_root.as_bytes());
+
+        let truncated = &bundle.proof[..bundle.proof.len() / 2];
+        decode_proof_json(truncated).unwrap_err();
+
+        // A changed value still decodes, but proves a different root
+        let mut proof = CommitmentProof::decode(
+            &*RawMerkleProof::decode(&*bundle.proof).unwrap().proofs[0].encode_to_vec(),
+        )
+        .unwrap();
+        match &mut proof.proof {
+            Some(commitment_proof::Proof::Exist(exist)) => exist.value[0] ^= 1,
+            _ => panic!("expected an existence proof"),
+        }
+        let json = decode_proof_json(&proof.encode_to_vec()).unwrap();
+        assert_ne!(json["computed_root"], root);
+        assert!(json["computed_root"].is_string(), "{json}");
+    }
+}
@@ -0,0 +1,158 @@
+use {
+    crate::{build_info::BuildInfo, duration},
+    anyhow::{anyhow, bail},
+    eclipse_ibc_state::{client_status, decode_consensus_state, ClientStatus, IbcState},
+    ibc::core::{
+        ics02_client::height::Height,
+        ics24_host::{identifier::ClientId, path::ClientConsensusStatePath},
+        timestamp::Timestamp,
+    },
+    serde::{Deserialize, Serialize},
+    serde_json::Value,
+    solana_sdk::clock::Slot,
+    std::time::Duration,
+};
+
+/// What the proofs of a generated message verify against on the destination
+/// chain. `generate --provenance` writes it next to the message, so that `tx
+/// --verify-against` can check the message is still valid right before submitting
+/// it, possibly much later and from another machine.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Provenance {
+    /// Client on the destination chain that verifies the proofs
+    pub(crate) client_id_on_cpty: String,
+    /// Height of the consensus state of that client the proofs verify against
+    pub(crate) proof_height: String,
+    /// Hex-encoded commitment root of the proven IBC state
+    pub(crate) commitment_root: String,
+    /// Latest version of the proven IBC store
+    pub(crate) source_version: Slot,
+    /// Unix time in seconds from which the message is too stale to submit. Absent
+    /// in provenance written before messages expired.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) expires_at_unix: Option<i64>,
+    /// Build of the CLI that generated the message. Absent in provenance written
+    /// before builds were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) generated_by: Option<BuildInfo>,
+}
+
+impl Provenance {
+    /// Sets the message to expire `valid_for` after `generated_at_unix`.
+    pub(crate) fn expire_after(&mut self, generated_at_unix: i64, valid_for: Duration) {
+        let valid_for = i64::try_from(valid_for.as_secs()).unwrap_or(i64::MAX);
+        self.expires_at_unix = Some(generated_at_unix.saturating_add(valid_for));
+    }
+
+    /// Checks that the message has not expired at `now_unix`, naming the inputs to
+    /// regenerate it from if it has.
+    pub(crate) fn check_not_expired(&self, now_unix: i64) -> anyhow::Result<()> {
+        let expires_at_unix = match self.expires_at_unix {
+            Some(expires_at_unix) if expires_at_unix <= now_unix => expires_at_unix,
+            _ => return Ok(()),
+        };
+        let stale_for = Duration::from_secs(now_unix.abs_diff(expires_at_unix));
+        bail!(
+            "Message expired {} ago, at unix time {expires_at_unix}; regenerate it from a state \
+             of the source chain newer than version {} and a consensus state of client {} on \
+             cpty chain newer than {}",
+            duration::format_duration(stale_for),
+            self.source_version,
+            self.client_id_on_cpty,
+            self.proof_height,
+        )
+    }
+
+    /// Checks that the destination chain, whose IBC state is `cpty_ibc_state`,
+    /// still has the consensus state the proofs verify against. Consensus states
+    /// may be pruned or, after misbehaviour, the client frozen between generating
+    /// a message and submitting it.
+    pub(crate) fn check_consensus_state(&self, cpty_ibc_state: &IbcState) -> anyhow::Result<()> {
+        let client_id = self.client_id_on_cpty.parse::<ClientId>()?;
+        let proof_height = self.proof_height.parse::<Height>()?;
+        let consensus_state_path = ClientConsensusStatePath::new(&client_id, &proof_height);
+        let consensus_state = cpty_ibc_state.get(&consensus_state_path)?.ok_or_else(|| {
+            anyhow!(
+                "Client {client_id} on cpty chain has no consensus state at proof height \
+                 {proof_height}; it may have been pruned"
+            )
+        })?;
+        let root = hex::encode(decode_consensus_state(consensus_state)?.root().as_bytes());
+        if root != self.commitment_root {
+            bail!(
+                "Consensus state of client {client_id} on cpty chain at {proof_height} has root \
+                 {root}, but the proofs were generated against {}",
+                self.commitment_root
+            );
+        }
+        Ok(())
+    }
+
+    /// Like [`Self::check_consensus_state`], and also checks that the client is
+    /// still active at `now`, a time of the destination chain's clock.
+    pub(crate) fn check_still_valid(
+        &self,
+        cpty_ibc_state: &IbcState,
+        now: Timestamp,
+    ) -> anyhow::Result<()> {
+        self.check_consensus_state(cpty_ibc_state)?;
+        let client_id = self.client_id_on_cpty.parse::<ClientId>()?;
+        match client_status(cpty_ibc_state, &client_id, now)? {
+            ClientStatus::Active => Ok(()),
+            status => bail!("Client {client_id} on cpty chain is {status}"),
+        }
+    }
+}
+
+/// Reads provenance written by `generate --provenance`: a single object, or an
+/// array of them for messages submitted together with `tx --batch`.
+pub(crate) fn parse_provenances(json: &[u8]) -> anyhow::Result<Vec<Provenance>> {
+    match serde_json::from_slice(json)? {
+        Value::Array(values) => values
+            .into_iter()
+            .map(|value| Ok(serde_json::from_value(value)?))
+            .collect(),
+        value => Ok(vec![serde_json::from_value(value)?]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_expire_at_the_end_of_their_validity_window() {
+        let mut provenance = Provenance {
+            client_id_on_cpty: "xx-eclipse-0".to_owned(),
+            proof_height: "0-12".to_owned(),
+            commitment_root: "00".to_owned(),
+            source_version: 11,
+            expires_at_unix: None,
+            generated_by: Some(BuildInfo::current()),
+        };
+        // Provenance written before messages expired never expires
+        provenance.check_not_expired(i64::MAX).unwrap();
+        let json = serde_json::to_value(&provenance).unwrap();
+        assert!(json.get("expires_at_unix").is_none(), "{json}");
+
+        provenance.expire_after(1_000, Duration::from_secs(3_600));
+        assert_eq!(provenance.expires_at_unix, Some(4_600));
+        provenance.check_not_expired(4_599).unwrap();
+        let err = provenance.check_not_expired(4_600).unwrap_err();
+        assert!(
+            err.to_string().starts_with("Message expired 0s ago"),
+            "{err}"
+        );
+        let err = provenance.check_not_expired(4_690).unwrap_err().to_string();
+        assert!(err.contains("expired 1m30s ago"), "{err}");
+        assert!(err.contains("newer than version 11"), "{err}");
+        assert!(err.contains("client xx-eclipse-0"), "{err}");
+        assert!(err.contains("newer than 0-12"), "{err}");
+
+        let parsed = parse_provenances(&serde_json::to_vec(&provenance).unwrap()).unwrap();
+        assert_eq!(parsed, [provenance.clone()]);
+
+        provenance.expire_after(1_000, Duration::MAX);
+        assert_eq!(provenance.expires_at_unix, Some(i64::MAX));
+    }
+}
@@ -1,20 +1,34 @@
 use {
-    crate::chain_state,
-    anyhow::anyhow,
+    crate::{
+        chain_state, client_compat, duration, generate,
+        proof::{self, membership_proof_json},
+        relay,
+        watch::{self, ObjectFilter},
+    },
+    anyhow::{anyhow, bail},
     clap::{Parser, Subcommand},
+    eclipse_ibc_extra_types::{ClientConnections, DenomTrace, IBC_DENOM_PREFIX},
     eclipse_ibc_known_path::KnownPath,
     eclipse_ibc_known_proto::KnownProto,
     eclipse_ibc_light_client::eclipse_chain,
+    eclipse_ibc_program::ibc_events::{parse_event_log, UpdateClientPayload},
     eclipse_ibc_state::{
+        check_client_connections, client_status, compute_ack_commitment, compute_packet_commitment,
         decode_client_state, decode_consensus_state,
         internal_path::{
-            AllModulesPath, ClientUpdateHeightPath, ClientUpdateTimePath, ConsensusHeightsPath,
+            AllModulesPath, CapabilitiesPath, ChainParamsPath, ClientTypeUrlPath,
+            ClientUpdateHeightPath, ClientUpdateTimePath, ConsensusHeightsPath, DenomTracePath,
+            InflightPacketsPath, LegacyTypeUrlCountPath,
         },
-        IbcAccountData, IbcState,
+        stored_value_json, ClientConnectionsCheck, EventJournal, IbcAccountData, IbcState,
     },
     ibc::core::{
         ics02_client::height::Height,
-        ics04_channel::packet::Sequence,
+        ics03_connection::connection::ConnectionEnd,
+        ics04_channel::{
+            channel::ChannelEnd, commitment::PacketCommitment, packet::Sequence,
+            timeout::TimeoutHeight,
+        },
         ics24_host::{
             identifier::{ChannelId, ClientId, ConnectionId, PortId},
             path::{
@@ -23,14 +37,19 @@ use {
                 SeqRecvPath, SeqSendPath,
             },
         },
+        timestamp::Timestamp,
     },
     serde::Serialize,
+    serde_json::{json, Map, Value},
     solana_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::{clock::Slot, signature::Signature},
     std::{
-        collections::HashMap,
-        io::{self, Write as _},
+        fs,
+        io::{self, Read as _, Write as _},
+        path::{Path, PathBuf},
         sync::Arc,
     },
+    tendermint::abci,
 };
 
 fn print_json<T>(msg: T) -> anyhow::Result<()>
@@ -56,6 +75,10 @@ enum MerkleStateKind {
     ClientState {
         client_id: ClientId,
     },
+    /// Type URL of a client's state, read from the client type index
+    ClientType {
+        client_id: ClientId,
+    },
     ConsensusState {
         client_id: ClientId,
         height: Height,
@@ -86,6 +109,19 @@ enum MerkleStateKind {
         port_id: PortId,
         channel_id: ChannelId,
         sequence: Sequence,
+
+        /// Hex-encoded packet data to check the commitment against
+        #[arg(long)]
+        packet_data: Option<String>,
+
+        /// Timeout height of the packet being checked, if it has one
+        #[arg(long, requires = "packet_data")]
+        timeout_height: Option<Height>,
+
+        /// Timeout timestamp of the packet being checked, in nanoseconds since the Unix
+        /// epoch. Zero means the packet has no timeout timestamp.
+        #[arg(long, default_value_t = 0, requires = "packet_data")]
+        timeout_timestamp: u64,
     },
     PacketReceipt {
         port_id: PortId,
@@ -96,6 +132,10 @@ enum MerkleStateKind {
         port_id: PortId,
         channel_id: ChannelId,
         sequence: Sequence,
+
+        /// Hex-encoded acknowledgement to check the commitment against
+        #[arg(long)]
+        ack: Option<String>,
     },
     Port {
         port_id: PortId,
@@ -112,16 +152,76 @@ enum MerkleStateKind {
         client_id: ClientId,
     },
     AllModules,
+    ChainParams,
+    /// Message type URL prefixes the deployed program handles, and its version
+    Capabilities,
+    /// Packet flow counters of a channel
+    ChannelStats {
+        port_id: PortId,
+        channel_id: ChannelId,
+    },
+    /// Every client's state, keyed by path. Like the other listings, this only
+    /// covers paths written since the program started recording them.
+    Clients,
+    /// Every connection end, keyed by path
+    Connections,
+    /// Every channel end, keyed by path
+    Channels,
+    /// Every outstanding packet commitment of a channel, keyed by path
+    PacketCommitments {
+        port_id: PortId,
+        channel_id: ChannelId,
+    },
+    /// Full trace of an ICS-20 voucher denom
+    DenomTrace {
+        /// Hash of the trace, with or without the `ibc/` prefix
+        #[arg(value_parser = parse_denom_hash)]
+        hash: String,
+    },
+    /// Every denom trace of vouchers minted by the ICS-20 module, keyed by path
+    DenomTraces,
+    /// Number of legacy Eclipse type URLs the program has rewritten in client
+    /// messages, which stops growing once every tool submitting them is upgraded
+    LegacyTypeUrlCount,
+}
+
+/// Normalizes a denom hash to the uppercase hex it is stored under.
+fn parse_denom_hash(hash: &str) -> anyhow::Result<String> {
+    let hash = hash.strip_prefix(IBC_DENOM_PREFIX).unwrap_or(hash);
+    let bytes = hex::decode(hash).map_err(|err| anyhow!("Invalid denom hash {hash}: {err}"))?;
+    if bytes.len() != 32 {
+        bail!("Denom hash {hash} is not a SHA-256 hash");
+    }
+    Ok(hex::encode_upper(bytes))
 }
 
 impl MerkleStateKind {
-    fn get_json_str(&self, ibc_state: &IbcState<'_>) -> anyhow::Result<String> {
+    fn get_json_value(&self, ibc_state: &IbcState<'_>) -> anyhow::Result<Value> {
         match self {
             Self::ClientState { client_id } => get_json_with_decode(
                 ibc_state,
                 &ClientStatePath::new(client_id),
                 decode_client_state,
             ),
+            Self::ClientType { client_id } => {
+                // Clients stored before the index existed are only indexed once the
+                // program looks them up, so fall back to the stored `Any`.
+                let indexed = ibc_state.get(&ClientTypeUrlPath(client_id.clone()))?;
+                let is_indexed = indexed.is_some();
+                let type_url = match indexed {
+                    Some(type_url) => type_url,
+                    None => {
+                        ibc_state
+                            .get_any(&ClientStatePath::new(client_id))?
+                            .ok_or_else(|| anyhow!("No client state found for {client_id}"))?
+                            .type_url
+                    }
+                };
+                Ok(json!({
+                    "type_url": type_url,
+                    "indexed": is_indexed,
+                }))
+            }
             Self::ConsensusState { client_id, height } => get_json_with_decode(
                 ibc_state,
                 &ClientConsensusStatePath::new(client_id, height),
@@ -130,9 +230,7 @@ impl MerkleStateKind {
             Self::Connection { connection_id } => {
                 get_json(ibc_state, &ConnectionPath::new(connection_id))
             }
-            Self::ClientConnections { client_id } => {
-                get_json(ibc_state, &ClientConnectionPath::new(client_id))
-            }
+            Self::ClientConnections { client_id } => client_connections_json(ibc_state, client_id),
             Self::Channel {
                 port_id,
                 channel_id,
@@ -153,10 +251,37 @@ impl MerkleStateKind {
                 port_id,
                 channel_id,
                 sequence,
-            } => get_json(
-                ibc_state,
-                &CommitmentPath::new(port_id, channel_id, *sequence),
-            ),
+                packet_data,
+                timeout_height,
+                timeout_timestamp,
+            } => {
+                let commitment_path = CommitmentPath::new(port_id, channel_id, *sequence);
+                let commitment = get_value(ibc_state, &commitment_path)?.into_vec();
+                let json = match packet_data {
+                    None => commitment_json(&commitment, None),
+                    Some(packet_data) => {
+                        let timeout_height =
+                            timeout_height.map_or(TimeoutHeight::Never, TimeoutHeight::At);
+                        let timeout_timestamp = Timestamp::from_nanoseconds(*timeout_timestamp)?;
+                        let expected = compute_packet_commitment(
+                            &hex::decode(packet_data)?,
+                            &timeout_height,
+                            &timeout_timestamp,
+                        );
+
+                        let mut json = commitment_json(&commitment, Some(&expected.into_vec()));
+                        json["timeout_height"] = match timeout_height {
+                            TimeoutHeight::Never => Value::Null,
+                            TimeoutHeight::At(height) => Value::String(height.to_string()),
+                        };
+                        json["timeout_timestamp"] = timeout_timestamp
+                            .into_tm_time()
+                            .map_or(Value::Null, |time| Value::String(time.to_rfc3339()));
+                        json
+                    }
+                };
+                Ok(json)
+            }
             Self::PacketReceipt {
                 port_id,
                 channel_id,
@@ -166,7 +291,17 @@ impl MerkleStateKind {
                 port_id,
                 channel_id,
                 sequence,
-            } => get_json(ibc_state, &AckPath::new(port_id, channel_id, *sequence)),
+                ack,
+            } => {
+                let ack_path = AckPath::new(port_id, channel_id, *sequence);
+                let ack_commitment = get_value(ibc_state, &ack_path)?.into_vec();
+                let expected = ack
+                    .as_ref()
+                    .map(|ack| anyhow::Ok(compute_ack_commitment(&hex::decode(ack)?).into_vec()))
+                    .transpose()?;
+                let json = commitment_json(&ack_commitment, expected.as_deref());
+                Ok(json)
+            }
             Self::Port { port_id } => get_json(ibc_state, &PortPath(port_id.clone())),
             Self::ClientUpdateTime { client_id, height } => {
                 get_json(ibc_state, &ClientUpdateTimePath(client_id.clone(), *height))
@@ -179,37 +314,294 @@ impl MerkleStateKind {
                 get_json(ibc_state, &ConsensusHeightsPath(client_id.clone()))
             }
             Self::AllModules => get_json(ibc_state, &AllModulesPath),
+            // Chain params are only stored once they are first updated
+            Self::ChainParams => Ok(serde_json::to_value(
+                ibc_state.get_raw(&ChainParamsPath)?.unwrap_or_default(),
+            )?),
+            Self::Capabilities => {
+                if ibc_state.get_raw(&CapabilitiesPath)?.is_none() {
+                    bail!(
+                        "The program does not advertise its capabilities; it predates them, or \
+                         `tx admin refresh-capabilities` has not been run since it was upgraded"
+                    );
+                }
+                get_json(ibc_state, &CapabilitiesPath)
+            }
+            Self::ChannelStats {
+                port_id,
+                channel_id,
+            } => {
+                // Counted from the commitments, as the program does, until the
+                // channel's packets are first counted
+                let inflight_packets = match ibc_state
+                    .get(&InflightPacketsPath(port_id.clone(), channel_id.clone()))?
+                {
+                    Some(inflight_packets) => inflight_packets,
+                    None => ibc_state
+                        .paths_with_prefix(&format!(
+                            "commitments/ports/{port_id}/channels/{channel_id}/sequences/"
+                        ))?
+                        .len() as u64,
+                };
+                let chain_params = ibc_state.get(&ChainParamsPath)?.unwrap_or_default();
+                Ok(json!({
+                    "inflight_packets": inflight_packets,
+                    "max_inflight_packets": chain_params.max_inflight_packets_per_channel,
+                }))
+            }
+            Self::Clients => {
+                let mut json = Map::new();
+                for path in ibc_state.paths_with_prefix("clients/")? {
+                    let client_id = match path
+                        .strip_prefix("clients/")
+                        .and_then(|rest| rest.strip_suffix("/clientState"))
+                    {
+                        Some(client_id) => client_id.parse::<ClientId>()?,
+                        None => continue,
+                    };
+                    let raw = ibc_state
+                        .get_raw(&ClientStatePath::new(&client_id))?
+                        .ok_or_else(|| anyhow!("No value found for key: {path}"))?;
+                    json.insert(path, serde_json::to_value(decode_client_state(raw)?)?);
+                }
+                let mut json = Value::Object(json);
+                duration::humanize_durations(&mut json);
+                Ok(json)
+            }
+            Self::Connections => list_json::<ConnectionEnd>(ibc_state, "connections/"),
+            Self::Channels => list_json::<ChannelEnd>(ibc_state, "channelEnds/ports/"),
+            Self::PacketCommitments {
+                port_id,
+                channel_id,
+            } => {
+                let prefix =
+                    format!("commitments/ports/{port_id}/channels/{channel_id}/sequences/");
+                let json = ibc_state
+                    .iter_paths::<PacketCommitment>(&prefix)?
+                    .into_iter()
+                    .map(|(path, commitment)| {
+                        (path, Value::String(hex::encode(commitment.into_vec())))
+                    })
+                    .collect::<Map<_, _>>();
+                Ok(Value::Object(json))
+            }
+            Self::DenomTrace { hash } => {
+                let trace = get_value(ibc_state, &DenomTracePath(hash.clone()))?;
+                Ok(denom_trace_json(trace))
+            }
+            Self::DenomTraces => {
+                let json = ibc_state
+                    .iter_paths::<DenomTrace>("internal/ics20/denomTraces/")?
+                    .into_iter()
+                    .map(|(path, trace)| (path, denom_trace_json(trace)))
+                    .collect::<Map<_, _>>();
+                Ok(Value::Object(json))
+            }
+            Self::LegacyTypeUrlCount => Ok(json!(ibc_state
+                .get(&LegacyTypeUrlCountPath)?
+                .unwrap_or_default())),
         }
     }
 
-    async fn run(self, rpc_client: &RpcClient) -> anyhow::Result<()> {
+    /// Shows the membership proof of the single path the query reads.
+    fn get_proof_json(
+        &self,
+        ibc_state: &IbcState<'_>,
+        proof_height: Height,
+    ) -> anyhow::Result<Value> {
+        match self {
+            Self::ClientState { client_id } => {
+                membership_proof_json(ibc_state, proof_height, &ClientStatePath::new(client_id))
+            }
+            Self::ClientType { client_id } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &ClientTypeUrlPath(client_id.clone()),
+            ),
+            Self::ConsensusState { client_id, height } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &ClientConsensusStatePath::new(client_id, height),
+            ),
+            Self::Connection { connection_id } => {
+                membership_proof_json(ibc_state, proof_height, &ConnectionPath::new(connection_id))
+            }
+            Self::ClientConnections { client_id } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &ClientConnectionPath::new(client_id),
+            ),
+            Self::Channel {
+                port_id,
+                channel_id,
+            } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &ChannelEndPath::new(port_id, channel_id),
+            ),
+            Self::NextSequenceSend {
+                port_id,
+                channel_id,
+            } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &SeqSendPath::new(port_id, channel_id),
+            ),
+            Self::NextSequenceRecv {
+                port_id,
+                channel_id,
+            } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &SeqRecvPath::new(port_id, channel_id),
+            ),
+            Self::NextSequenceAck {
+                port_id,
+                channel_id,
+            } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &SeqAckPath::new(port_id, channel_id),
+            ),
+            Self::PacketCommitment {
+                port_id,
+                channel_id,
+                sequence,
+                ..
+            } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &CommitmentPath::new(port_id, channel_id, *sequence),
+            ),
+            Self::PacketReceipt {
+                port_id,
+                channel_id,
+                sequence,
+            } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &ReceiptPath::new(port_id, channel_id, *sequence),
+            ),
+            Self::PacketAcknowledgement {
+                port_id,
+                channel_id,
+                sequence,
+                ..
+            } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &AckPath::new(port_id, channel_id, *sequence),
+            ),
+            Self::Port { port_id } => {
+                membership_proof_json(ibc_state, proof_height, &PortPath(port_id.clone()))
+            }
+            Self::ClientUpdateTime { client_id, height } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &ClientUpdateTimePath(client_id.clone(), *height),
+            ),
+            Self::ClientUpdateHeight { client_id, height } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &ClientUpdateHeightPath(client_id.clone(), *height),
+            ),
+            Self::ConsensusHeights { client_id } => membership_proof_json(
+                ibc_state,
+                proof_height,
+                &ConsensusHeightsPath(client_id.clone()),
+            ),
+            Self::AllModules => membership_proof_json(ibc_state, proof_height, &AllModulesPath),
+            Self::ChainParams => membership_proof_json(ibc_state, proof_height, &ChainParamsPath),
+            Self::Capabilities => membership_proof_json(ibc_state, proof_height, &CapabilitiesPath),
+            Self::DenomTrace { hash } => {
+                membership_proof_json(ibc_state, proof_height, &DenomTracePath(hash.clone()))
+            }
+            Self::LegacyTypeUrlCount => {
+                membership_proof_json(ibc_state, proof_height, &LegacyTypeUrlCountPath)
+            }
+            Self::ChannelStats { .. }
+            | Self::Clients
+            | Self::Connections
+            | Self::Channels
+            | Self::PacketCommitments { .. }
+            | Self::DenomTraces => {
+                bail!("--with-proof only applies to queries that read a single path")
+            }
+        }
+    }
+
+    async fn run(self, rpc_client: &RpcClient, with_proof: bool) -> anyhow::Result<()> {
         let raw_account_data = rpc_client
             .get_account_data(&eclipse_ibc_program::STORAGE_KEY)
             .await?;
 
         let IbcAccountData {
-            store: ibc_store, ..
-        } = bincode::deserialize(&raw_account_data)?;
+            store: ibc_store,
+            metadata,
+            ..
+        } = IbcAccountData::from_bytes_verified(&raw_account_data)?;
 
         let latest_version = ibc_store
-            .read()?
+            .read()
             .latest_version()
             .ok_or_else(|| anyhow!("IBC store is missing latest version"))?;
 
         let ibc_state = IbcState::new(&ibc_store, latest_version);
 
-        let json_str = self.get_json_str(&ibc_state)?;
-        writeln!(io::stdout(), "{json_str}")?;
+        let json = if with_proof {
+            // The proof is checked before the value so that listings fail up front
+            let proof_height = eclipse_chain::height_of_slot_with_revision(
+                latest_version,
+                metadata.revision_number,
+            )?;
+            let proof = self.get_proof_json(&ibc_state, proof_height)?;
+            json!({
+                "value": self.get_json_value(&ibc_state)?,
+                "proof": proof,
+            })
+        } else {
+            self.get_json_value(&ibc_state)?
+        };
+        print_json(json)?;
 
         Ok(())
     }
 }
 
+fn denom_trace_json(trace: DenomTrace) -> Value {
+    json!({
+        "ibc_denom": trace.ibc_denom(),
+        "full_path": trace.full_path(),
+        "path": trace.path,
+        "base_denom": trace.base_denom,
+    })
+}
+
+fn get_value<K>(ibc_state: &IbcState<'_>, key: &K) -> anyhow::Result<K::Value>
+where
+    K: KnownPath,
+{
+    ibc_state
+        .get(key)?
+        .ok_or_else(|| anyhow!("No value found for key: {key}"))
+}
+
+/// Shows a stored commitment as hex and, if the commitment expected from the
+/// committed value is known, whether the two match.
+fn commitment_json(commitment: &[u8], expected: Option<&[u8]>) -> Value {
+    let mut json = json!({ "commitment": hex::encode(commitment) });
+    if let Some(expected) = expected {
+        json["expected"] = Value::String(hex::encode(expected));
+        json["matches"] = Value::Bool(commitment == expected);
+    }
+    json
+}
+
 fn get_json_with_decode<K, T, E>(
     ibc_state: &IbcState<'_>,
     key: &K,
     decode: impl FnOnce(<K::Value as KnownProto>::Raw) -> Result<T, E>,
-) -> anyhow::Result<String>
+) -> anyhow::Result<Value>
 where
     K: KnownPath,
     T: Serialize,
@@ -218,13 +610,66 @@ where
     let raw = ibc_state
         .get_raw(key)?
         .ok_or_else(|| anyhow!("No value found for key: {key}"))?;
-    let decoded_raw = decode(raw)?;
-    Ok(colored_json::to_colored_json_auto(&serde_json::to_value(
-        &decoded_raw,
-    )?)?)
+    let mut json = serde_json::to_value(decode(raw)?)?;
+    duration::humanize_durations(&mut json);
+    Ok(json)
+}
+
+/// Shows every value stored under `prefix`, keyed by path.
+fn list_json<V>(ibc_state: &IbcState<'_>, prefix: &str) -> anyhow::Result<Value>
+where
+    V: KnownProto,
+    V::Raw: Serialize,
+{
+    let json = ibc_state
+        .iter_paths::<V>(prefix)?
+        .into_iter()
+        .map(|(path, value)| anyhow::Ok((path, serde_json::to_value(value.into_raw())?)))
+        .collect::<anyhow::Result<Map<_, _>>>()?;
+    Ok(Value::Object(json))
+}
+
+/// The connections indexed for `client_id`, cross-checked against the stored
+/// connection ends. Indexed connections that do not exist or belong to another
+/// client are listed as phantom, and connections of the client the index
+/// leaves out as missing.
+fn client_connections_json(
+    ibc_state: &IbcState<'_>,
+    client_id: &ClientId,
+) -> anyhow::Result<Value> {
+    let ClientConnections { connections } = ibc_state
+        .get(&ClientConnectionPath::new(client_id))?
+        .unwrap_or_default();
+    let mut connections = connections
+        .iter()
+        .map(ConnectionId::to_string)
+        .collect::<Vec<_>>();
+    connections.sort();
+
+    let check = check_client_connections(ibc_state)?;
+    let of_client = |entries: &[(ClientId, ConnectionId)]| {
+        entries
+            .iter()
+            .filter(|(indexed_client_id, _)| indexed_client_id == client_id)
+            .map(|(_, connection_id)| connection_id.to_string())
+            .collect::<Vec<_>>()
+    };
+    let phantom = of_client(&check.phantom);
+    let missing = of_client(&check.missing);
+    if !phantom.is_empty() || !missing.is_empty() {
+        eprintln!(
+            "The connection index of {client_id} is inconsistent; \
+             `tx admin repair-indices client-connections` rebuilds it"
+        );
+    }
+    Ok(json!({
+        "connections": connections,
+        "phantom_connections": phantom,
+        "missing_connections": missing,
+    }))
 }
 
-fn get_json<K>(ibc_state: &IbcState<'_>, key: &K) -> anyhow::Result<String>
+fn get_json<K>(ibc_state: &IbcState<'_>, key: &K) -> anyhow::Result<Value>
 where
     K: KnownPath,
     <K::Value as KnownProto>::Raw: Serialize,
@@ -235,9 +680,114 @@ where
 #[derive(Clone, Debug, Subcommand)]
 enum ChainStateKind {
     HostHeight,
-    HostConsensusState { height: Height },
+    HostConsensusState {
+        height: Height,
+    },
     IbcMetadata,
+    /// Every entry of the IBC state, as its path and value decoded by the path.
+    /// Values that cannot be decoded are shown as hex
     IbcState,
+    /// Counts of the IBC store's nodes, value history entries, preimages and
+    /// versions, with an estimate of its size, as JSON
+    IbcStoreStats,
+    /// Whether IBC is frozen, along with the host height, disabled messages and
+    /// the checksum of the last commit
+    Status,
+    /// Checks that the latest root of the IBC store is the one recorded at its
+    /// last commit, as the program does on load
+    CheckInvariants {
+        /// Check the root of every stored version against every key's value and
+        /// the tree nodes along its path, which reads the whole store
+        #[arg(long)]
+        deep: bool,
+    },
+    /// Decodes the IBC events logged by a transaction, or with `--from-slot` those
+    /// kept in the event journal of the storage account
+    Events {
+        #[arg(required_unless_present = "from_slot", conflicts_with = "from_slot")]
+        signature: Option<Signature>,
+
+        /// Show the journaled events emitted at or after this slot, along with
+        /// their slot and journal sequence
+        #[arg(long)]
+        from_slot: Option<Slot>,
+
+        #[command(flatten)]
+        filter: ObjectFilter,
+    },
+    /// Checks that a Cosmos counterparty's client of this chain tracks this chain
+    ProofSpecCompat {
+        /// gRPC endpoint of the counterparty chain
+        #[arg(long)]
+        counterparty_grpc_endpoint: String,
+
+        /// Client ID the counterparty assigned to this chain
+        client_id: ClientId,
+
+        /// Name of this chain, as passed to `generate client create`
+        #[arg(long)]
+        chain_name: String,
+    },
+    /// Whether a client is active, expired or frozen, with its latest height and
+    /// the timestamp of its latest consensus state
+    ClientStatus {
+        client_id: ClientId,
+    },
+    /// Decodes a proof and shows its layers, leaf specs, key and value hashes and
+    /// path, with the root it computes. Does not query the chain.
+    DecodeProof {
+        /// File holding the encoded `MerkleProof` or `CommitmentProof` as binary,
+        /// hex or base64, or `-` for stdin
+        #[arg(
+            long,
+            required_unless_present = "from_msg",
+            conflicts_with = "from_msg"
+        )]
+        file: Option<PathBuf>,
+
+        /// JSON message file, such as one made by `generate`, to take the proof from
+        #[arg(long, requires = "field")]
+        from_msg: Option<PathBuf>,
+
+        /// Field of the message holding the proof, such as `proof_init`
+        #[arg(long, requires = "from_msg")]
+        field: Option<String>,
+    },
+    /// Sequences of packets the counterparty sent on a channel that this chain
+    /// has not received, as a JSON array. Candidates are `--sequences`, or else
+    /// the counterparty's outstanding packet commitments.
+    UnreceivedPackets {
+        port: PortId,
+
+        channel: ChannelId,
+
+        /// Comma-separated sequences to check, e.g. `1,2,3`
+        #[arg(long, value_delimiter = ',')]
+        sequences: Option<Vec<u64>>,
+
+        /// Endpoint of the counterparty chain, whose packet commitments are
+        /// scanned when `--sequences` is not given
+        #[arg(long, required_unless_present = "sequences")]
+        cpty_endpoint: Option<String>,
+    },
+    /// Sequences of packets sent on a channel whose acknowledgements this chain
+    /// has not received, as a JSON array. Candidates are `--sequences`, or else
+    /// this chain's outstanding packet commitments that the counterparty
+    /// acknowledged.
+    UnreceivedAcks {
+        port: PortId,
+
+        channel: ChannelId,
+
+        /// Comma-separated sequences of acknowledgements to check, e.g. `1,2,3`
+        #[arg(long, value_delimiter = ',')]
+        sequences: Option<Vec<u64>>,
+
+        /// Endpoint of the counterparty chain, whose acknowledgements are looked
+        /// up when `--sequences` is not given
+        #[arg(long, required_unless_present = "sequences")]
+        cpty_endpoint: Option<String>,
+    },
 }
 
 impl ChainStateKind {
@@ -245,7 +795,7 @@ impl ChainStateKind {
         match self {
             Self::HostHeight => {
                 let slot = rpc_client.get_slot().await?;
-                let height = eclipse_chain::height_of_slot(slot)?;
+                let height = chain_state::get_host_height(rpc_client, slot).await?;
                 writeln!(io::stdout(), "{height}")?;
 
                 Ok(())
@@ -260,10 +810,7 @@ impl ChainStateKind {
                     .get_account_data(&eclipse_ibc_program::STORAGE_KEY)
                     .await?;
 
-                let IbcAccountData {
-                    metadata: ibc_metadata,
-                    ..
-                } = bincode::deserialize(&raw_account_data)?;
+                let ibc_metadata = IbcAccountData::read_metadata_only(&raw_account_data)?;
 
                 print_json(ibc_metadata)?;
                 Ok(())
@@ -275,52 +822,426 @@ impl ChainStateKind {
 
                 let IbcAccountData {
                     store: ibc_store, ..
-                } = bincode::deserialize(&raw_account_data)?;
+                } = IbcAccountData::from_bytes_verified(&raw_account_data)?;
 
                 let latest_version = ibc_store
-                    .read()?
+                    .read()
                     .latest_version()
                     .ok_or_else(|| anyhow!("IBC store is missing latest version"))?;
 
+                let ibc_store = Arc::new(ibc_store);
                 let ibc_jmt_iter = jmt::JellyfishMerkleIterator::new_by_index(
-                    Arc::new(ibc_store),
+                    Arc::clone(&ibc_store),
                     latest_version,
                     0,
                 )?;
 
-                let ibc_state_map = ibc_jmt_iter
-                    .inspect(|result| {
-                        if let Err(err) = result {
+                // Entries whose path was never recorded can only be shown by
+                // their key hash
+                let mut entries = vec![];
+                let mut unknown_entries = vec![];
+                for result in ibc_jmt_iter {
+                    let (key_hash, value) = match result {
+                        Ok(entry) => entry,
+                        Err(err) => {
                             eprintln!("{err}");
+                            continue;
+                        }
+                    };
+                    match ibc_store.preimage(key_hash)? {
+                        Some(path) => {
+                            let path = String::from_utf8(path)?;
+                            let value = stored_value_json(&path, value);
+                            entries.push((path, value));
                         }
+                        None => unknown_entries.push(json!({
+                            "key_hash": hex::encode(key_hash.0),
+                            "value": hex::encode(value),
+                        })),
+                    }
+                }
+                entries.sort_by(|(path_a, _), (path_b, _)| path_a.cmp(path_b));
+
+                let entries = entries
+                    .into_iter()
+                    .map(|(path, value)| json!({ "path": path, "value": value }))
+                    .chain(unknown_entries)
+                    .collect::<Vec<_>>();
+                print_json(entries)?;
+                Ok(())
+            }
+            Self::IbcStoreStats => {
+                let raw_account_data = rpc_client
+                    .get_account_data(&eclipse_ibc_program::STORAGE_KEY)
+                    .await?;
+
+                let IbcAccountData {
+                    store: ibc_store, ..
+                } = IbcAccountData::from_bytes(&raw_account_data)?;
+
+                print_json(ibc_store.stats()?)?;
+                Ok(())
+            }
+            Self::Status => {
+                let slot = rpc_client.get_slot().await?;
+                let chain_params = chain_state::get_chain_params(rpc_client).await?;
+                if chain_params.frozen {
+                    eprintln!("IBC is FROZEN until an admin unfreezes it");
+                }
+
+                let (admin, pending_admin_change) = chain_state::get_admin(rpc_client).await?;
+                let ibc_metadata = chain_state::get_ibc_metadata(rpc_client).await?;
+
+                print_json(json!({
+                    "frozen": chain_params.frozen,
+                    "host_height": eclipse_chain::height_of_slot_with_revision(
+                        slot,
+                        ibc_metadata.revision_number,
+                    )?
+                    .to_string(),
+                    "revision_number": ibc_metadata.revision_number,
+                    "disabled_messages": chain_params.disabled_messages,
+                    "write_sequence": ibc_metadata.write_sequence,
+                    "store_checksum": format!("{:016x}", ibc_metadata.checksum),
+                    "admin": admin,
+                    "pending_admin_change": pending_admin_change.map(|pending| json!({
+                        "new_admin": pending.new_admin,
+                        "effective_after_slot": pending.effective_after_slot,
+                    })),
+                }))?;
+                Ok(())
+            }
+            Self::CheckInvariants { deep } => {
+                let raw_account_data = rpc_client
+                    .get_account_data(&eclipse_ibc_program::STORAGE_KEY)
+                    .await?;
+
+                // Not `from_bytes_verified`, so that every failing version is reported
+                let account_data = IbcAccountData::from_bytes(&raw_account_data)?;
+                let ibc_store = &account_data.store;
+
+                let versions = {
+                    let inner_store = ibc_store.read();
+                    if deep {
+                        inner_store.versions().to_vec()
+                    } else {
+                        inner_store.latest_version().into_iter().collect()
+                    }
+                };
+                let verify_root = |version| {
+                    if deep {
+                        ibc_store.verify_root(version)
+                    } else {
+                        account_data.verify_latest_root()
+                    }
+                };
+                let corrupted_versions = versions
+                    .iter()
+                    .filter_map(|&version| {
+                        let err = verify_root(version).err()?;
+                        Some(json!({ "version": version, "error": format!("{err:#}") }))
                     })
-                    .filter_map(Result::ok)
-                    .map(|(key_hash, value)| (hex::encode(key_hash.0), hex::encode(value)))
-                    .collect::<HashMap<_, _>>();
+                    .collect::<Vec<_>>();
+
+                // Every connection must be in its client's index, and the index
+                // must not list connections that are not the client's
+                let index_check = match versions.last() {
+                    Some(&version) => check_client_connections(&IbcState::new(ibc_store, version))?,
+                    None => ClientConnectionsCheck::default(),
+                };
+                let index_entries_json = |entries: &[(ClientId, ConnectionId)]| {
+                    entries
+                        .iter()
+                        .map(|(client_id, connection_id)| {
+                            json!({
+                                "client_id": client_id.to_string(),
+                                "connection_id": connection_id.to_string(),
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                };
+
+                let corrupted = !corrupted_versions.is_empty();
+                print_json(json!({
+                    "checked_versions": versions.len(),
+                    "corrupted_versions": corrupted_versions,
+                    "client_connections": {
+                        "phantom": index_entries_json(&index_check.phantom),
+                        "missing": index_entries_json(&index_check.missing),
+                    },
+                }))?;
+                if corrupted {
+                    bail!("IBC store does not match its stored roots");
+                }
+                if !index_check.is_consistent() {
+                    bail!(
+                        "The client connections index does not match the stored connections; \
+                         `tx admin repair-indices client-connections` rebuilds it"
+                    );
+                }
+                Ok(())
+            }
+            Self::Events {
+                signature: Some(signature),
+                filter,
+                ..
+            } => {
+                let events = watch::get_logs(rpc_client, &signature)
+                    .await?
+                    .iter()
+                    .filter(|log| filter.matches(log))
+                    .filter_map(|log| parse_event_log(log).transpose())
+                    .map(|event| event_json(&event?))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                print_json(events)?;
+                Ok(())
+            }
+            Self::Events {
+                signature: None,
+                from_slot,
+                filter,
+            } => {
+                let raw_account_data = rpc_client
+                    .get_account_data(&eclipse_ibc_program::STORAGE_KEY)
+                    .await?;
+                let IbcAccountData { event_journal, .. } =
+                    IbcAccountData::from_bytes(&raw_account_data)?;
+                let events =
+                    journaled_events_json(&event_journal, from_slot.unwrap_or_default(), &filter)?;
+                print_json(events)?;
+                Ok(())
+            }
+            Self::ProofSpecCompat {
+                counterparty_grpc_endpoint,
+                client_id,
+                chain_name,
+            } => {
+                let report = client_compat::check_counterparty_client(
+                    rpc_client,
+                    counterparty_grpc_endpoint,
+                    &client_id,
+                    &chain_name,
+                )
+                .await?;
+                print_json(&report)?;
+                if !report.compatible {
+                    bail!(
+                        "Client {client_id} on the counterparty is not compatible with this chain"
+                    );
+                }
+                Ok(())
+            }
+            Self::ClientStatus { client_id } => {
+                let ibc_store = generate::get_ibc_store(rpc_client).await?;
+                let ibc_state = generate::get_ibc_state(&ibc_store)?;
+                let now = chain_state::get_host_time(rpc_client).await?;
+                let status = client_status(&ibc_state, &client_id, now)?;
 
-                print_json(ibc_state_map)?;
+                let client_state = ibc_state
+                    .get(&ClientStatePath::new(&client_id))?
+                    .ok_or_else(|| anyhow!("No client state found for {client_id}"))?;
+                let latest_height = decode_client_state(client_state)?.latest_height();
+                let consensus_state_path =
+                    ClientConsensusStatePath::new(&client_id, &latest_height);
+                let consensus_state = ibc_state
+                    .get(&consensus_state_path)?
+                    .ok_or_else(|| anyhow!("No consensus state found at {consensus_state_path}"))?;
+                let latest_timestamp = decode_consensus_state(consensus_state)?.timestamp();
+
+                print_json(json!({
+                    "status": status.to_string(),
+                    "latest_height": latest_height.to_string(),
+                    "latest_timestamp": latest_timestamp.to_string(),
+                }))?;
+                Ok(())
+            }
+            Self::DecodeProof {
+                file,
+                from_msg,
+                field,
+            } => {
+                let input = match (file, from_msg, field) {
+                    (Some(file), _, _) => read_file_or_stdin(&file)?,
+                    (None, Some(from_msg), Some(field)) => {
+                        let msg = serde_json::from_slice::<Value>(&read_file_or_stdin(&from_msg)?)?;
+                        msg.get(&field)
+                            .and_then(Value::as_str)
+                            .ok_or_else(|| anyhow!("Message has no string field {field}"))?
+                            .as_bytes()
+                            .to_vec()
+                    }
+                    _ => bail!("Pass either --file or --from-msg with --field"),
+                };
+                print_json(proof::decode_proof_json(&proof::proof_bytes(&input)?)?)?;
+                Ok(())
+            }
+            Self::UnreceivedPackets {
+                port,
+                channel,
+                sequences,
+                cpty_endpoint,
+            } => {
+                let ibc_store = generate::get_ibc_store(rpc_client).await?;
+                let ibc_state = generate::get_ibc_state(&ibc_store)?;
+                let sequences = match (sequences, cpty_endpoint) {
+                    (Some(sequences), _) => sequences,
+                    (None, Some(cpty_endpoint)) => {
+                        let (cpty_port, cpty_channel) =
+                            counterparty_channel(&ibc_state, &port, &channel)?;
+                        let cpty_ibc_store =
+                            generate::get_ibc_store(&RpcClient::new(cpty_endpoint)).await?;
+                        let cpty_ibc_state = generate::get_ibc_state(&cpty_ibc_store)?;
+                        relay::committed_sequences(&cpty_ibc_state, &cpty_port, &cpty_channel)?
+                    }
+                    (None, None) => bail!("Pass either --sequences or --cpty-endpoint"),
+                };
+
+                let unreceived =
+                    relay::filter_unreceived_packets(&ibc_state, &port, &channel, &sequences)?;
+                print_json(unreceived)?;
+                Ok(())
+            }
+            Self::UnreceivedAcks {
+                port,
+                channel,
+                sequences,
+                cpty_endpoint,
+            } => {
+                let ibc_store = generate::get_ibc_store(rpc_client).await?;
+                let ibc_state = generate::get_ibc_state(&ibc_store)?;
+                let unreceived = match (sequences, cpty_endpoint) {
+                    (Some(sequences), _) => {
+                        relay::filter_unreceived_acks(&ibc_state, &port, &channel, &sequences)?
+                    }
+                    (None, Some(cpty_endpoint)) => {
+                        let (cpty_port, cpty_channel) =
+                            counterparty_channel(&ibc_state, &port, &channel)?;
+                        let cpty_ibc_store =
+                            generate::get_ibc_store(&RpcClient::new(cpty_endpoint)).await?;
+                        let cpty_ibc_state = generate::get_ibc_state(&cpty_ibc_store)?;
+                        relay::unreceived_acks(
+                            &ibc_state,
+                            &port,
+                            &channel,
+                            &cpty_ibc_state,
+                            &cpty_port,
+                            &cpty_channel,
+                        )?
+                    }
+                    (None, None) => bail!("Pass either --sequences or --cpty-endpoint"),
+                };
+                print_json(unreceived)?;
                 Ok(())
             }
         }
     }
 }
 
+/// Port and channel IDs of the other end of `port`/`channel`.
+fn counterparty_channel(
+    ibc_state: &IbcState<'_>,
+    port: &PortId,
+    channel: &ChannelId,
+) -> anyhow::Result<(PortId, ChannelId)> {
+    let channel_end_path = ChannelEndPath::new(port, channel);
+    let counterparty = ibc_state
+        .get_raw(&channel_end_path)?
+        .ok_or_else(|| anyhow!("No channel found at {channel_end_path}"))?
+        .counterparty
+        .ok_or_else(|| anyhow!("Channel {channel_end_path} has no counterparty"))?;
+    Ok((
+        counterparty.port_id.parse()?,
+        counterparty.channel_id.parse()?,
+    ))
+}
+
+/// Reads all of `path`, or of stdin if it is `-`.
+fn read_file_or_stdin(path: &Path) -> anyhow::Result<Vec<u8>> {
+    if path == Path::new("-") {
+        let mut input = vec![];
+        io::stdin().read_to_end(&mut input)?;
+        Ok(input)
+    } else {
+        Ok(fs::read(path)?)
+    }
+}
+
+/// Shows an IBC event with its attributes by key. `update_client` events also
+/// get the fields relayers chain client updates with.
+fn event_json(event: &abci::Event) -> anyhow::Result<Value> {
+    let attributes = event
+        .attributes
+        .iter()
+        .map(|attribute| {
+            (
+                attribute.key.clone(),
+                Value::String(attribute.value.clone()),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>();
+    let mut json = json!({ "kind": event.kind, "attributes": attributes });
+    if let Some(payload) = UpdateClientPayload::from_event(event)? {
+        json["update_client"] = serde_json::to_value(payload)?;
+    }
+    Ok(json)
+}
+
+/// Shows the journaled events emitted at or after `from_slot`, with the sequence
+/// the next event will get. If the oldest event shown has a later sequence than
+/// the one a reader resumes from, the events in between were evicted unread.
+fn journaled_events_json(
+    event_journal: &EventJournal,
+    from_slot: Slot,
+    filter: &ObjectFilter,
+) -> anyhow::Result<Value> {
+    let events = event_journal
+        .entries_from(from_slot)
+        .filter(|entry| filter.matches(&entry.event_log))
+        .map(|entry| {
+            let event = parse_event_log(&entry.event_log)?
+                .ok_or_else(|| anyhow!("Journal entry {} is not an IBC event", entry.sequence))?;
+            let mut json = event_json(&event)?;
+            json["slot"] = entry.slot.into();
+            json["sequence"] = entry.sequence.into();
+            Ok(json)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(json!({
+        "next_sequence": event_journal.next_sequence(),
+        "events": events,
+    }))
+}
+
 #[derive(Debug, Parser)]
 pub(crate) struct Args {
     /// Endpoint to send a request to
     #[arg(long, default_value = "http://127.0.0.1:8899")]
     endpoint: String,
 
+    /// Include the membership proof of the queried path, with the height it is
+    /// proven at and the commitment root it verifies against
+    #[arg(long, global = true)]
+    with_proof: bool,
+
     /// State kind to query
     #[command(subcommand)]
     kind: StateKind,
 }
 
-pub(crate) async fn run(Args { endpoint, kind }: Args) -> anyhow::Result<()> {
+pub(crate) async fn run(
+    Args {
+        endpoint,
+        with_proof,
+        kind,
+    }: Args,
+) -> anyhow::Result<()> {
     let rpc_client = RpcClient::new(endpoint);
 
     match kind {
-        StateKind::Merkle(merkle_kind) => merkle_kind.run(&rpc_client).await?,
+        StateKind::Merkle(merkle_kind) => merkle_kind.run(&rpc_client, with_proof).await?,
+        StateKind::Chain(_) if with_proof => {
+            bail!("--with-proof only applies to queries of the IBC state's paths")
+        }
         StateKind::Chain(chain_kind) => chain_kind.run(&rpc_client).await?,
     }
 
@@ -0,0 +1,1471 @@
+use {
+    crate::{
+        capabilities, chain_state, duration,
+        generate::{self, get_ibc_state},
+        proof::existence_proof_to_merkle_proof,
+        relay_schedule::RelaySchedule,
+        relay_spend::{payer_spend, BudgetExceeded, SpendTracker},
+        store_cache::{StoreCache, StoreSnapshot},
+        tx::{self, SentTx},
+        watch,
+    },
+    anyhow::{anyhow, bail},
+    clap::Parser,
+    core::time::Duration,
+    eclipse_ibc_extra_types::ChainParams,
+    eclipse_ibc_known_proto::KnownAnyProto,
+    eclipse_ibc_program::ibc_events::{self, parse_event_log},
+    eclipse_ibc_state::{
+        client_status, compute_ack_commitment, compute_packet_commitment, ClientStatus, IbcState,
+        IbcStore, LoadChainParams,
+    },
+    futures::stream::{FuturesUnordered, StreamExt as _},
+    ibc::core::{
+        ics02_client::height::Height,
+        ics04_channel::{commitment::PacketCommitment, timeout::TimeoutHeight},
+        ics24_host::{
+            identifier::{ChannelId, ClientId, ConnectionId, PortId},
+            path::{
+                AckPath, ChannelEndPath, CommitmentPath, ConnectionPath, ReceiptPath, SeqRecvPath,
+                SeqSendPath,
+            },
+        },
+        timestamp::Timestamp,
+    },
+    ibc_proto::{
+        google::protobuf,
+        ibc::core::{
+            channel::v1::{
+                MsgAcknowledgement as RawMsgAcknowledgement, MsgRecvPacket as RawMsgRecvPacket,
+                Order as RawOrder, Packet as RawPacket, State as RawState,
+            },
+            client::v1::MsgUpdateClient as RawMsgUpdateClient,
+        },
+    },
+    log::{debug, info, warn},
+    prost::Message as _,
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind},
+        nonblocking::rpc_client::RpcClient,
+        rpc_client::GetConfirmedSignaturesForAddress2Config,
+    },
+    solana_sdk::{
+        signature::Signature,
+        signer::{keypair::Keypair, Signer as _},
+    },
+    solana_transaction_status::UiTransactionEncoding,
+    std::{
+        collections::BTreeMap,
+        future::Future,
+        io::{self, Write as _},
+        num::NonZeroUsize,
+        path::PathBuf,
+        str::FromStr,
+        sync::{Arc, Mutex, MutexGuard, PoisonError},
+        time::{Instant, SystemTime, UNIX_EPOCH},
+    },
+    tendermint::abci,
+};
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Times a packet message is submitted before the relayer gives up on it until
+/// the next poll.
+const MAX_RELAY_ATTEMPTS: u32 = 3;
+
+/// Exit code of `--once` runs stopped by `--max-spend-lamports`.
+const BUDGET_EXCEEDED_EXIT_CODE: i32 = 3;
+
+const UPDATE_CLIENT_TYPE_URL: &str = "/ibc.core.client.v1.MsgUpdateClient";
+const RECV_PACKET_TYPE_URL: &str = "/ibc.core.channel.v1.MsgRecvPacket";
+const ACKNOWLEDGEMENT_TYPE_URL: &str = "/ibc.core.channel.v1.MsgAcknowledgement";
+
+/// Every message the relayer submits, to either chain.
+const RELAYED_TYPE_URLS: [&str; 3] = [
+    UPDATE_CLIENT_TYPE_URL,
+    RECV_PACKET_TYPE_URL,
+    ACKNOWLEDGEMENT_TYPE_URL,
+];
+
+/// Delay before retrying a request that failed `attempt` times in a row, doubling
+/// from `INITIAL_BACKOFF` up to `MAX_BACKOFF`.
+pub(crate) fn backoff_delay(attempt: u32) -> Duration {
+    INITIAL_BACKOFF
+        .checked_mul(2_u32.saturating_pow(attempt))
+        .map_or(MAX_BACKOFF, |delay| delay.min(MAX_BACKOFF))
+}
+
+/// Connection failures and timeouts are worth retrying; anything else, such as a
+/// transaction the program rejected, would only fail again. Transactions rejected
+/// while a store write batch is being applied, or dropped before landing, are
+/// already retried by `tx::submit_ibc_msg`, and those that may still land are
+/// not retried at all.
+fn is_transient(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause.downcast_ref::<ClientError>().map_or(false, |err| {
+            matches!(
+                err.kind(),
+                ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_)
+            )
+        })
+    })
+}
+
+async fn with_retries<T, F, Fut>(what: &str, max_retries: u32, mut request: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match request().await {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < max_retries && is_transient(&err) => {
+                let delay = backoff_delay(attempt);
+                warn!("Failed to {what}, retrying in {delay:?}: {err:#}");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err.context(format!("Failed to {what}"))),
+        }
+    }
+}
+
+fn attribute<'a>(event: &'a abci::Event, key: &str) -> anyhow::Result<&'a str> {
+    let value = event
+        .attributes
+        .iter()
+        .find(|attribute| attribute.key == key)
+        .map(|attribute| attribute.value.as_str())
+        .ok_or_else(|| anyhow!("{} event is missing attribute {key}", event.kind))?;
+    if ibc_events::is_truncated(value) {
+        bail!(
+            "{} event attribute {key} was truncated in the program log",
+            event.kind
+        );
+    }
+    Ok(value)
+}
+
+/// A packet as recorded in the events of the chain that sent it, which is the
+/// only place its data can be recovered from; the store only keeps a commitment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SentPacket {
+    sequence: u64,
+    src_port_id: PortId,
+    src_channel_id: ChannelId,
+    dst_port_id: PortId,
+    dst_channel_id: ChannelId,
+    data: Vec<u8>,
+    timeout_height: TimeoutHeight,
+    timeout_timestamp: u64,
+}
+
+impl SentPacket {
+    fn from_event(event: &abci::Event) -> anyhow::Result<Self> {
+        // A packet without a timeout height is logged with a zero height, which
+        // `Height` itself cannot represent
+        let timeout_height = match attribute(event, "packet_timeout_height")? {
+            "0-0" => TimeoutHeight::Never,
+            height => TimeoutHeight::At(height.parse()?),
+        };
+        Ok(Self {
+            sequence: attribute(event, "packet_sequence")?.parse()?,
+            src_port_id: attribute(event, "packet_src_port")?.parse()?,
+            src_channel_id: attribute(event, "packet_src_channel")?.parse()?,
+            dst_port_id: attribute(event, "packet_dst_port")?.parse()?,
+            dst_channel_id: attribute(event, "packet_dst_channel")?.parse()?,
+            data: hex::decode(attribute(event, "packet_data_hex")?)?,
+            timeout_height,
+            timeout_timestamp: attribute(event, "packet_timeout_timestamp")?.parse()?,
+        })
+    }
+
+    fn to_raw(&self) -> RawPacket {
+        RawPacket {
+            sequence: self.sequence,
+            source_port: self.src_port_id.to_string(),
+            source_channel: self.src_channel_id.to_string(),
+            destination_port: self.dst_port_id.to_string(),
+            destination_channel: self.dst_channel_id.to_string(),
+            data: self.data.clone(),
+            timeout_height: match self.timeout_height {
+                TimeoutHeight::Never => None,
+                TimeoutHeight::At(height) => Some(height.into()),
+            },
+            timeout_timestamp: self.timeout_timestamp,
+        }
+    }
+
+    fn commitment_path(&self) -> CommitmentPath {
+        CommitmentPath::new(
+            &self.src_port_id,
+            &self.src_channel_id,
+            self.sequence.into(),
+        )
+    }
+
+    /// Checks that the sending chain committed to this packet, rather than to a
+    /// different one logged under the same sequence.
+    fn verify_commitment(&self, ibc_state: &IbcState) -> anyhow::Result<()> {
+        let commitment_path = self.commitment_path();
+        let commitment = ibc_state
+            .get(&commitment_path)?
+            .ok_or_else(|| anyhow!("No packet commitment found at {commitment_path}"))?;
+        let expected = compute_packet_commitment(
+            &self.data,
+            &self.timeout_height,
+            &Timestamp::from_nanoseconds(self.timeout_timestamp)?,
+        );
+        if commitment.into_vec() != expected.into_vec() {
+            bail!("Logged packet does not match the commitment at {commitment_path}");
+        }
+        Ok(())
+    }
+
+    /// Whether the receiving chain, at `height` and `now_nanos`, would reject the
+    /// packet as timed out.
+    fn has_timed_out(&self, height: Height, now_nanos: u64) -> bool {
+        self.timeout_height.has_expired(height)
+            || (self.timeout_timestamp != 0 && now_nanos >= self.timeout_timestamp)
+    }
+}
+
+/// Sequences in `[next_sequence_recv, next_sequence_send)` that the sending chain
+/// still has a commitment for and the receiving chain has not received.
+fn unreceived_sequences(
+    next_sequence_recv: u64,
+    next_sequence_send: u64,
+    mut is_committed: impl FnMut(u64) -> anyhow::Result<bool>,
+    mut is_received: impl FnMut(u64) -> anyhow::Result<bool>,
+) -> anyhow::Result<Vec<u64>> {
+    let mut sequences = vec![];
+    for sequence in next_sequence_recv..next_sequence_send {
+        if is_committed(sequence)? && !is_received(sequence)? {
+            sequences.push(sequence);
+        }
+    }
+    Ok(sequences)
+}
+
+/// Sequences of the packet commitments that `ibc_state` has on
+/// `port_id`/`channel_id`, in order. Commitments are listed by path, so ones
+/// written before the store recorded paths are missed.
+pub(crate) fn committed_sequences(
+    ibc_state: &IbcState,
+    port_id: &PortId,
+    channel_id: &ChannelId,
+) -> anyhow::Result<Vec<u64>> {
+    let prefix = format!("commitments/ports/{port_id}/channels/{channel_id}/sequences/");
+    let mut sequences = ibc_state
+        .iter_paths::<PacketCommitment>(&prefix)?
+        .into_iter()
+        .map(|(path, _)| {
+            path[prefix.len()..]
+                .parse::<u64>()
+                .map_err(|err| anyhow!("Malformed packet commitment path {path}: {err}"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    sequences.sort_unstable();
+    Ok(sequences)
+}
+
+/// Sequences of packets sent on `port_id`/`channel_id` that `ibc_state` still has
+/// a commitment for and that `cpty_state` wrote an acknowledgement for, i.e. whose
+/// acknowledgements are yet to be relayed.
+pub(crate) fn unreceived_acks(
+    ibc_state: &IbcState,
+    port_id: &PortId,
+    channel_id: &ChannelId,
+    cpty_state: &IbcState,
+    cpty_port_id: &PortId,
+    cpty_channel_id: &ChannelId,
+) -> anyhow::Result<Vec<u64>> {
+    let mut sequences = vec![];
+    for sequence in committed_sequences(ibc_state, port_id, channel_id)? {
+        let ack_path = AckPath::new(cpty_port_id, cpty_channel_id, sequence.into());
+        if cpty_state.get(&ack_path)?.is_some() {
+            sequences.push(sequence);
+        }
+    }
+    Ok(sequences)
+}
+
+/// Those of `sequences` that `ibc_state`, the receiving end of
+/// `port_id`/`channel_id`, has not received, as ibc-go's `UnreceivedPackets`
+/// query. Ordered channels have received every sequence below their next receive
+/// sequence; unordered ones keep a receipt of each packet.
+pub(crate) fn filter_unreceived_packets(
+    ibc_state: &IbcState,
+    port_id: &PortId,
+    channel_id: &ChannelId,
+    sequences: &[u64],
+) -> anyhow::Result<Vec<u64>> {
+    let channel_end_path = ChannelEndPath::new(port_id, channel_id);
+    let channel_end = ibc_state
+        .get_raw(&channel_end_path)?
+        .ok_or_else(|| anyhow!("No channel found at {channel_end_path}"))?;
+    if channel_end.ordering == RawOrder::Ordered as i32 {
+        let next_sequence_recv = u64::from(
+            ibc_state
+                .get(&SeqRecvPath::new(port_id, channel_id))?
+                .ok_or_else(|| anyhow!("No next receive sequence found for {channel_id}"))?,
+        );
+        return Ok(sequences
+            .iter()
+            .copied()
+            .filter(|&sequence| sequence >= next_sequence_recv)
+            .collect());
+    }
+
+    let mut unreceived = vec![];
+    for &sequence in sequences {
+        let receipt_path = ReceiptPath::new(port_id, channel_id, sequence.into());
+        if ibc_state.get(&receipt_path)?.is_none() {
+            unreceived.push(sequence);
+        }
+    }
+    Ok(unreceived)
+}
+
+/// Those of `sequences`, of acknowledgements the counterparty wrote, whose packet
+/// commitment `ibc_state`, the sending end of `port_id`/`channel_id`, still has,
+/// as ibc-go's `UnreceivedAcks` query. The commitment is deleted once the
+/// acknowledgement is received.
+pub(crate) fn filter_unreceived_acks(
+    ibc_state: &IbcState,
+    port_id: &PortId,
+    channel_id: &ChannelId,
+    sequences: &[u64],
+) -> anyhow::Result<Vec<u64>> {
+    let mut unreceived = vec![];
+    for &sequence in sequences {
+        let commitment_path = CommitmentPath::new(port_id, channel_id, sequence.into());
+        if ibc_state.get(&commitment_path)?.is_some() {
+            unreceived.push(sequence);
+        }
+    }
+    Ok(unreceived)
+}
+
+/// Packet events on one end of the channel, as logged by the IBC program.
+#[derive(Debug, Default)]
+struct EventCache {
+    last_signature: Option<Signature>,
+    /// Packets sent on this end, by sequence
+    sent: BTreeMap<u64, SentPacket>,
+    /// Acknowledgements written on this end, by sequence of the received packet
+    acks: BTreeMap<u64, (SentPacket, Vec<u8>)>,
+}
+
+impl EventCache {
+    fn record(&mut self, event: &abci::Event, port_id: &PortId, channel_id: &ChannelId) {
+        let result = match event.kind.as_str() {
+            "send_packet" => SentPacket::from_event(event).map(|packet| {
+                if packet.src_port_id == *port_id && packet.src_channel_id == *channel_id {
+                    self.sent.insert(packet.sequence, packet);
+                }
+            }),
+            "write_acknowledgement" => SentPacket::from_event(event).and_then(|packet| {
+                if packet.dst_port_id == *port_id && packet.dst_channel_id == *channel_id {
+                    let ack = hex::decode(attribute(event, "packet_ack_hex")?)?;
+                    self.acks.insert(packet.sequence, (packet, ack));
+                }
+                Ok(())
+            }),
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            warn!("Skipping malformed {} event: {err:#}", event.kind);
+        }
+    }
+}
+
+/// Fetches the IBC events logged after `until`, oldest first, along with the
+/// newest signature scanned. Without `until`, at most `history_limit` transactions
+/// are scanned back.
+async fn fetch_events(
+    rpc_client: &RpcClient,
+    until: Option<Signature>,
+    history_limit: usize,
+) -> anyhow::Result<(Vec<abci::Event>, Option<Signature>)> {
+    let mut statuses = vec![];
+    let mut before = None;
+    loop {
+        let page = rpc_client
+            .get_signatures_for_address_with_config(
+                &eclipse_ibc_program::STORAGE_KEY,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    ..GetConfirmedSignaturesForAddress2Config::default()
+                },
+            )
+            .await?;
+        let oldest = match page.last() {
+            Some(oldest) => Signature::from_str(&oldest.signature)?,
+            None => break,
+        };
+        before = Some(oldest);
+        statuses.extend(page);
+        if until.is_none() && statuses.len() >= history_limit {
+            statuses.truncate(history_limit);
+            break;
+        }
+    }
+
+    let last_signature = match statuses.first() {
+        Some(newest) => Some(Signature::from_str(&newest.signature)?),
+        None => until,
+    };
+
+    // Signatures are returned newest first
+    let mut events = vec![];
+    for status in statuses.into_iter().rev() {
+        if status.err.is_some() {
+            continue;
+        }
+        let signature = Signature::from_str(&status.signature)?;
+        for log in watch::get_logs(rpc_client, &signature).await? {
+            match parse_event_log(&log) {
+                Ok(Some(event)) => events.push(event),
+                Ok(None) => (),
+                Err(err) => warn!("Skipping malformed IBC event log in {signature}: {err:#}"),
+            }
+        }
+    }
+    Ok((events, last_signature))
+}
+
+/// Finds the acknowledgement written on `port_id`/`channel_id` for packet
+/// `sequence` among the last `history_limit` IBC transactions, along with the
+/// packet it acknowledges. The store only keeps a commitment to it.
+pub(crate) async fn find_written_ack(
+    rpc_client: &RpcClient,
+    port_id: &PortId,
+    channel_id: &ChannelId,
+    sequence: u64,
+    history_limit: usize,
+) -> anyhow::Result<(RawPacket, Vec<u8>)> {
+    let (events, _) = fetch_events(rpc_client, None, history_limit).await?;
+    let mut event_cache = EventCache::default();
+    for event in &events {
+        event_cache.record(event, port_id, channel_id);
+    }
+    let (packet, ack) = event_cache.acks.remove(&sequence).ok_or_else(|| {
+        anyhow!(
+            "No write_acknowledgement event found for packet {sequence} on {channel_id} in \
+             the last {history_limit} IBC transactions"
+        )
+    })?;
+    Ok((packet.to_raw(), ack))
+}
+
+/// One end of the channel being relayed.
+struct ChannelSide {
+    /// Name of the chain in logs and spend totals
+    name: &'static str,
+    rpc_client: RpcClient,
+    /// Client on this chain tracking the other chain
+    client_id: ClientId,
+    connection_id: ConnectionId,
+    port_id: PortId,
+    channel_id: ChannelId,
+    events: EventCache,
+    store_cache: StoreCache,
+}
+
+impl ChannelSide {
+    async fn poll_events(&mut self, history_limit: usize, max_retries: u32) -> anyhow::Result<()> {
+        let (events, last_signature) = with_retries("fetch IBC events", max_retries, || {
+            fetch_events(&self.rpc_client, self.events.last_signature, history_limit)
+        })
+        .await?;
+        for event in &events {
+            self.events.record(event, &self.port_id, &self.channel_id);
+        }
+        self.events.last_signature = last_signature;
+        Ok(())
+    }
+
+    async fn get_ibc_store(&self, max_retries: u32) -> anyhow::Result<Arc<StoreSnapshot>> {
+        with_retries("fetch the IBC store", max_retries, || {
+            self.store_cache.get(&self.rpc_client)
+        })
+        .await
+    }
+
+    /// Whether IBC is frozen on this side's chain, which then rejects every
+    /// message the relayer would submit to it.
+    async fn is_frozen(&self, max_retries: u32) -> anyhow::Result<bool> {
+        is_frozen(&self.get_ibc_store(max_retries).await?.store)
+    }
+
+    /// Checks that the channel is open on the given connection and client,
+    /// returning whether it is ordered.
+    fn check_channel(&self, ibc_state: &IbcState) -> anyhow::Result<bool> {
+        let channel_end_path = ChannelEndPath::new(&self.port_id, &self.channel_id);
+        let channel = ibc_state
+            .get_raw(&channel_end_path)?
+            .ok_or_else(|| anyhow!("No channel found at {channel_end_path}"))?;
+        if channel.state != RawState::Open as i32 {
+            bail!("Channel {channel_end_path} is not open");
+        }
+        if channel.connection_hops != [self.connection_id.to_string()] {
+            bail!(
+                "Channel {channel_end_path} is on connection hops {:?}, not {}",
+                channel.connection_hops,
+                self.connection_id
+            );
+        }
+
+        let connection_path = ConnectionPath::new(&self.connection_id);
+        let connection = ibc_state
+            .get_raw(&connection_path)?
+            .ok_or_else(|| anyhow!("No connection found at {connection_path}"))?;
+        if connection.client_id != self.client_id.as_str() {
+            bail!(
+                "Connection {connection_path} is on client {}, not {}",
+                connection.client_id,
+                self.client_id
+            );
+        }
+
+        Ok(channel.ordering == RawOrder::Ordered as i32)
+    }
+}
+
+/// Kinds of messages relayed to a chain, each with sequences of its own that
+/// must land in order on ordered channels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum RelayLane {
+    Recv,
+    Ack,
+}
+
+/// A packet message to relay, proven against the state of the chain it is
+/// relayed from.
+enum PacketMsg {
+    Recv(SentPacket),
+    Ack {
+        packet: SentPacket,
+        ack: Vec<u8>,
+        ack_path: AckPath,
+    },
+}
+
+impl PacketMsg {
+    fn encode(
+        &self,
+        src_state: &IbcState,
+        proof_height: Height,
+        signer: String,
+    ) -> anyhow::Result<protobuf::Any> {
+        match self {
+            Self::Recv(packet) => {
+                let proof_commitment = existence_proof_to_merkle_proof(
+                    src_state.get_proof(&packet.commitment_path())?,
+                );
+                let msg = RawMsgRecvPacket {
+                    packet: Some(packet.to_raw()),
+                    proof_commitment: proof_commitment.encode_to_vec(),
+                    proof_height: Some(proof_height.into()),
+                    signer,
+                };
+                Ok(protobuf::Any {
+                    type_url: RECV_PACKET_TYPE_URL.to_owned(),
+                    value: msg.encode_to_vec(),
+                })
+            }
+            Self::Ack {
+                packet,
+                ack,
+                ack_path,
+            } => {
+                let proof_acked = existence_proof_to_merkle_proof(src_state.get_proof(ack_path)?);
+                let msg = RawMsgAcknowledgement {
+                    packet: Some(packet.to_raw()),
+                    acknowledgement: ack.clone(),
+                    proof_acked: proof_acked.encode_to_vec(),
+                    proof_height: Some(proof_height.into()),
+                    signer,
+                };
+                Ok(protobuf::Any {
+                    type_url: ACKNOWLEDGEMENT_TYPE_URL.to_owned(),
+                    value: msg.encode_to_vec(),
+                })
+            }
+        }
+    }
+}
+
+struct Relayer {
+    payer: Arc<Keypair>,
+    max_retries: u32,
+    /// Most messages in flight at once
+    max_parallel: usize,
+    spend: Mutex<SpendTracker>,
+}
+
+impl Relayer {
+    fn signer(&self) -> String {
+        self.payer.pubkey().to_string()
+    }
+
+    fn spend(&self) -> MutexGuard<'_, SpendTracker> {
+        self.spend.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Submits `msg` to `dst`, recording what its transactions cost the payer,
+    /// whether or not they succeeded. Fails without submitting anything once the
+    /// spend budget is used up.
+    async fn submit(
+        &self,
+        src: &ChannelSide,
+        dst: &ChannelSide,
+        msg: protobuf::Any,
+    ) -> anyhow::Result<()> {
+        self.spend().check_budget()?;
+
+        let what = format!("submit {}", msg.type_url);
+        let kind = msg
+            .type_url
+            .rsplit('.')
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+        let sent = Mutex::default();
+        let result = with_retries(&what, self.max_retries, || {
+            tx::submit_ibc_msg(&dst.rpc_client, &self.payer, msg.clone(), &sent)
+        })
+        .await;
+
+        let direction = format!("{} to {}", src.name, dst.name);
+        for SentTx { signature, .. } in sent.into_inner().unwrap_or_else(PoisonError::into_inner) {
+            let meta = match dst
+                .rpc_client
+                .get_transaction(&signature, UiTransactionEncoding::Json)
+                .await
+            {
+                Ok(tx) => tx.transaction.meta,
+                Err(err) => {
+                    // Transactions that never landed did not cost anything
+                    debug!("Could not fetch transaction {signature}: {err}");
+                    continue;
+                }
+            };
+            let lamports = match meta.as_ref().map(payer_spend) {
+                Some(Ok(lamports)) => lamports,
+                Some(Err(err)) => {
+                    warn!("Could not read the cost of transaction {signature}: {err:#}");
+                    continue;
+                }
+                None => {
+                    warn!("Transaction {signature} has no status meta");
+                    continue;
+                }
+            };
+            if let Err(err) = self.spend().record(&direction, &kind, lamports) {
+                warn!("Failed to save relayer spend: {err}");
+            }
+        }
+
+        result
+    }
+
+    /// Returns a height of `src` that `dst` has a consensus state for and whose
+    /// root proves the latest version of `src_store`, updating the client on `dst`
+    /// first if it is behind.
+    async fn proof_height(
+        &self,
+        src: &ChannelSide,
+        src_store: &IbcStore,
+        dst: &ChannelSide,
+        dst_state: &IbcState<'_>,
+    ) -> anyhow::Result<Height> {
+        let src_version = src_store
+            .read()
+            .latest_version()
+            .ok_or_else(|| anyhow!("No IBC state versions found"))?;
+        let src_height = with_retries("fetch the host height", self.max_retries, || {
+            chain_state::get_host_height(&src.rpc_client, src_version)
+        })
+        .await?;
+
+        // Newer consensus states prove the same state as long as no IBC
+        // transaction landed on `src` since its store was fetched
+        let consensus_height =
+            chain_state::get_latest_consensus_height(dst_state, dst.client_id.as_str())?;
+        if consensus_height >= src_height {
+            return Ok(consensus_height);
+        }
+
+        // Updates of frozen or expired clients are rejected, so fail before paying
+        // for one
+        let now = with_retries("fetch the host time", self.max_retries, || {
+            chain_state::get_host_time(&dst.rpc_client)
+        })
+        .await?;
+        let status = client_status(dst_state, &dst.client_id, now)?;
+        if status != ClientStatus::Active {
+            bail!(
+                "Client {} on chain {} is {status} and cannot be updated",
+                dst.client_id,
+                dst.name
+            );
+        }
+
+        info!(
+            "Updating client {} from {consensus_height} to {src_height}",
+            dst.client_id
+        );
+        let consensus_state = with_retries("fetch the consensus state", self.max_retries, || {
+            chain_state::get_consensus_state(&src.rpc_client, src_height)
+        })
+        .await?;
+        let header = chain_state::header_from_consensus_state(consensus_state, src_height);
+        let msg = RawMsgUpdateClient {
+            client_id: dst.client_id.to_string(),
+            header: Some(header.encode_as_any()),
+            signer: self.signer(),
+        };
+        self.submit(
+            src,
+            dst,
+            protobuf::Any {
+                type_url: UPDATE_CLIENT_TYPE_URL.to_owned(),
+                value: msg.encode_to_vec(),
+            },
+        )
+        .await?;
+        Ok(src_height)
+    }
+
+    /// Receives the packets sent on `src` on `dst`, and acknowledges on `dst` the
+    /// packets from `dst` that `src` wrote acknowledgements for.
+    async fn relay(&self, src: &mut ChannelSide, dst: &ChannelSide) -> anyhow::Result<()> {
+        let src_snapshot = src.get_ibc_store(self.max_retries).await?;
+        let src_state = get_ibc_state(&src_snapshot.store)?;
+        let dst_snapshot = dst.get_ibc_store(self.max_retries).await?;
+        let dst_state = get_ibc_state(&dst_snapshot.store)?;
+
+        src.check_channel(&src_state)?;
+        let dst_ordered = dst.check_channel(&dst_state)?;
+
+        let next_sequence_send = u64::from(
+            src_state
+                .get(&SeqSendPath::new(&src.port_id, &src.channel_id))?
+                .ok_or_else(|| anyhow!("No next send sequence found for {}", src.channel_id))?,
+        );
+        let next_sequence_recv = u64::from(
+            dst_state
+                .get(&SeqRecvPath::new(&dst.port_id, &dst.channel_id))?
+                .ok_or_else(|| anyhow!("No next receive sequence found for {}", dst.channel_id))?,
+        );
+        let unreceived = unreceived_sequences(
+            next_sequence_recv,
+            next_sequence_send,
+            |sequence| {
+                Ok(src_state
+                    .get(&CommitmentPath::new(
+                        &src.port_id,
+                        &src.channel_id,
+                        sequence.into(),
+                    ))?
+                    .is_some())
+            },
+            |sequence| {
+                // Ordered channels only advance the next receive sequence
+                Ok(!dst_ordered
+                    && dst_state
+                        .get(&ReceiptPath::new(
+                            &dst.port_id,
+                            &dst.channel_id,
+                            sequence.into(),
+                        ))?
+                        .is_some())
+            },
+        )?;
+        src.events
+            .sent
+            .retain(|sequence, _| unreceived.contains(sequence));
+
+        let dst_slot = with_retries("fetch the latest slot", self.max_retries, || async {
+            Ok(dst.rpc_client.get_slot().await?)
+        })
+        .await?;
+        let dst_height = with_retries("fetch the host height", self.max_retries, || {
+            chain_state::get_host_height(&dst.rpc_client, dst_slot)
+        })
+        .await?;
+        let now_nanos = u64::try_from(SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos())?;
+
+        let mut to_receive = vec![];
+        for sequence in unreceived {
+            let packet = match src.events.sent.get(&sequence) {
+                Some(packet) => packet,
+                None => {
+                    warn!(
+                        "Packet {sequence} on {} is unreceived but its send_packet event was not \
+                         found; raise --history-limit to relay it",
+                        src.channel_id
+                    );
+                    continue;
+                }
+            };
+            if let Err(err) = packet.verify_commitment(&src_state) {
+                warn!("Skipping packet {sequence}: {err:#}");
+                continue;
+            }
+            if packet.has_timed_out(dst_height, now_nanos) {
+                warn!(
+                    "Packet {sequence} on {} has timed out on the receiving chain and must be \
+                     timed out instead",
+                    src.channel_id
+                );
+                continue;
+            }
+            to_receive.push(packet.clone());
+        }
+
+        let unacknowledged = unreceived_acks(
+            &dst_state,
+            &dst.port_id,
+            &dst.channel_id,
+            &src_state,
+            &src.port_id,
+            &src.channel_id,
+        )?;
+        src.events
+            .acks
+            .retain(|sequence, _| unacknowledged.contains(sequence));
+
+        let mut to_acknowledge = vec![];
+        for sequence in unacknowledged {
+            let (packet, ack) = match src.events.acks.get(&sequence) {
+                Some(packet_and_ack) => packet_and_ack,
+                None => {
+                    warn!(
+                        "Packet {sequence} on {} is acknowledged but its write_acknowledgement \
+                         event was not found; raise --history-limit to relay it",
+                        src.channel_id
+                    );
+                    continue;
+                }
+            };
+            let ack_path = AckPath::new(&src.port_id, &src.channel_id, sequence.into());
+            match src_state.get(&ack_path)? {
+                Some(commitment)
+                    if commitment.into_vec() == compute_ack_commitment(ack).into_vec() =>
+                {
+                    to_acknowledge.push((packet.clone(), ack.clone(), ack_path));
+                }
+                _ => warn!("Logged acknowledgement does not match the commitment at {ack_path}"),
+            }
+        }
+
+        if to_receive.is_empty() && to_acknowledge.is_empty() {
+            return Ok(());
+        }
+        let proof_height = self
+            .proof_height(src, &src_snapshot.store, dst, &dst_state)
+            .await?;
+        let (src, src_state) = (&*src, &src_state);
+
+        let mut schedule = RelaySchedule::new(self.max_parallel, MAX_RELAY_ATTEMPTS);
+        let mut msgs = BTreeMap::new();
+        for packet in to_receive {
+            schedule.push(RelayLane::Recv, dst_ordered, packet.sequence);
+            msgs.insert((RelayLane::Recv, packet.sequence), PacketMsg::Recv(packet));
+        }
+        for (packet, ack, ack_path) in to_acknowledge {
+            schedule.push(RelayLane::Ack, dst_ordered, packet.sequence);
+            msgs.insert(
+                (RelayLane::Ack, packet.sequence),
+                PacketMsg::Ack {
+                    packet,
+                    ack,
+                    ack_path,
+                },
+            );
+        }
+
+        // Messages are proven and submitted concurrently, each with its own
+        // blockhash, and confirmed as they land
+        let mut in_flight = FuturesUnordered::new();
+        let mut budget_exceeded = None;
+        loop {
+            while let Some(work) = schedule.next_ready() {
+                let msg = &msgs[&(work.lane, work.sequence)];
+                in_flight.push(async move {
+                    let result = async {
+                        let msg = msg.encode(src_state, proof_height, self.signer())?;
+                        self.submit(src, dst, msg).await
+                    }
+                    .await;
+                    (work, result)
+                });
+            }
+            let (work, result) = match in_flight.next().await {
+                Some(done) => done,
+                None => break,
+            };
+            let what = match work.lane {
+                RelayLane::Recv => "packet",
+                RelayLane::Ack => "acknowledgement of packet",
+            };
+            match result {
+                Ok(()) => {
+                    schedule.complete(&work);
+                    info!(
+                        "Relayed {what} {} from {} to {}",
+                        work.sequence, src.channel_id, dst.channel_id
+                    );
+                }
+                Err(err) if err.is::<BudgetExceeded>() => {
+                    // Let the transactions already sent finish so their cost is recorded
+                    schedule.complete(&work);
+                    schedule.cancel_pending();
+                    budget_exceeded.get_or_insert(err);
+                }
+                Err(err) => {
+                    warn!("Failed to relay {what} {}: {err:#}", work.sequence);
+                    let given_up = schedule.fail(&work);
+                    if !given_up.is_empty() {
+                        warn!(
+                            "Giving up on relaying {what}s {given_up:?} from {} until the next poll",
+                            src.channel_id
+                        );
+                    }
+                }
+            }
+        }
+
+        match budget_exceeded {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct Args {
+    /// Endpoint of chain A
+    #[arg(long, default_value = "http://127.0.0.1:8899")]
+    endpoint: String,
+
+    /// Endpoint of chain B
+    #[arg(long)]
+    cpty_endpoint: String,
+
+    /// File path to payer keypair, which pays for transactions on both chains
+    #[arg(long)]
+    payer: Option<PathBuf>,
+
+    /// Client on chain A tracking chain B
+    client_id_on_a: ClientId,
+    /// Client on chain B tracking chain A
+    client_id_on_b: ClientId,
+    connection_id_on_a: ConnectionId,
+    connection_id_on_b: ConnectionId,
+    port_id_on_a: PortId,
+    channel_id_on_a: ChannelId,
+    port_id_on_b: PortId,
+    channel_id_on_b: ChannelId,
+
+    /// Relay whatever is pending once and exit, instead of polling until
+    /// interrupted
+    #[arg(long)]
+    once: bool,
+
+    /// Time to wait between polls, e.g. `2s` or `500ms`
+    #[arg(long, default_value = "2s", value_parser = duration::parse_duration)]
+    poll_interval: Duration,
+
+    /// Number of times to retry an RPC request that failed to connect or timed out
+    #[arg(long, default_value_t = 5)]
+    max_retries: u32,
+
+    /// Most packet messages to prove and submit at once. Messages on ordered
+    /// channels are still submitted one at a time, in sequence order.
+    #[arg(long, default_value = "8")]
+    max_parallel: NonZeroUsize,
+
+    /// Number of past IBC transactions to scan on each chain for packet events on
+    /// startup. Packets sent before those cannot be relayed, since only their
+    /// events hold the packet data.
+    #[arg(long, default_value_t = 1000)]
+    history_limit: usize,
+
+    /// Decode each chain's IBC store again after this long, e.g. `1m`, even if the
+    /// storage account is unchanged
+    #[arg(long, default_value = "1m", value_parser = duration::parse_duration)]
+    max_store_staleness: Duration,
+
+    /// Stop submitting transactions once the payer has spent this many lamports
+    /// on them, counting fees and rent kept by the program. The relayer then asks
+    /// whether to allow as much again, or exits with code 3 with `--once`.
+    #[arg(long)]
+    max_spend_lamports: Option<u64>,
+
+    /// File to keep the lamports spent in, so that restarts do not reset the
+    /// budget. Defaults to `~/.config/eclipse-ibc/relay-spend.json`; delete it to
+    /// start a new budget window.
+    #[arg(long)]
+    spend_file: Option<PathBuf>,
+
+    /// Time between summaries of the lamports spent, e.g. `10m`
+    #[arg(long, default_value = "10m", value_parser = duration::parse_duration)]
+    spend_summary_interval: Duration,
+}
+
+fn default_spend_file() -> anyhow::Result<PathBuf> {
+    let mut path =
+        dirs_next::home_dir().ok_or_else(|| anyhow!("Could not retrieve home directory"))?;
+    path.extend([".config", "eclipse-ibc", "relay-spend.json"]);
+    Ok(path)
+}
+
+fn log_spend_summary(relayer: &Relayer) {
+    for line in relayer.spend().ledger().summary() {
+        info!("Spent: {line}");
+    }
+}
+
+/// Asks the operator whether to allow the relayer to spend its budget again.
+async fn confirm_budget_extension(err: &anyhow::Error) -> anyhow::Result<bool> {
+    write!(
+        io::stdout(),
+        "{err:#}. Allow spending as much again? [y/N] "
+    )?;
+    io::stdout().flush()?;
+    let answer = tokio::task::spawn_blocking(|| {
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer).map(|_| answer)
+    })
+    .await??;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Whether IBC is frozen in the latest state of `ibc_store`.
+fn is_frozen(ibc_store: &IbcStore) -> anyhow::Result<bool> {
+    let latest_version = ibc_store
+        .read()
+        .latest_version()
+        .ok_or_else(|| anyhow!("No IBC state versions found"))?;
+    Ok(ChainParams::load_or_default(&IbcState::new(ibc_store, latest_version))?.frozen)
+}
+
+/// Relays packets and acknowledgements both ways over a channel between two
+/// Eclipse chains, updating each side's client as needed.
+pub(crate) async fn run(
+    Args {
+        endpoint,
+        cpty_endpoint,
+        payer,
+        client_id_on_a,
+        client_id_on_b,
+        connection_id_on_a,
+        connection_id_on_b,
+        port_id_on_a,
+        channel_id_on_a,
+        port_id_on_b,
+        channel_id_on_b,
+        once,
+        poll_interval,
+        max_retries,
+        max_parallel,
+        history_limit,
+        max_store_staleness,
+        max_spend_lamports,
+        spend_file,
+        spend_summary_interval,
+    }: Args,
+) -> anyhow::Result<()> {
+    let spend_file = match spend_file {
+        Some(spend_file) => spend_file,
+        None => default_spend_file()?,
+    };
+    let relayer = Relayer {
+        payer: tx::read_payer(payer)?,
+        max_retries,
+        max_parallel: max_parallel.get(),
+        spend: Mutex::new(SpendTracker::load(spend_file, max_spend_lamports)?),
+    };
+    let mut side_a = ChannelSide {
+        name: "A",
+        rpc_client: RpcClient::new(endpoint),
+        client_id: client_id_on_a,
+        connection_id: connection_id_on_a,
+        port_id: port_id_on_a,
+        channel_id: channel_id_on_a,
+        events: EventCache::default(),
+        store_cache: StoreCache::new(max_store_staleness),
+    };
+    let mut side_b = ChannelSide {
+        name: "B",
+        rpc_client: RpcClient::new(cpty_endpoint),
+        client_id: client_id_on_b,
+        connection_id: connection_id_on_b,
+        port_id: port_id_on_b,
+        channel_id: channel_id_on_b,
+        events: EventCache::default(),
+        store_cache: StoreCache::new(max_store_staleness),
+    };
+
+    for side in [&side_a, &side_b] {
+        capabilities::check_store(
+            &format!("chain {}", side.name),
+            generate::get_ibc_store(&side.rpc_client),
+            &RELAYED_TYPE_URLS,
+        )
+        .await;
+    }
+
+    let mut last_spend_summary = Instant::now();
+    loop {
+        // Nothing lands on a frozen chain until an admin unfreezes it, and packets
+        // relayed to the other side would only be acknowledged or timed out later
+        for side in [&side_a, &side_b] {
+            if side.is_frozen(max_retries).await? {
+                log_spend_summary(&relayer);
+                bail!(
+                    "IBC is frozen on chain {}; stopping until an admin unfreezes it",
+                    side.name
+                );
+            }
+        }
+
+        let result = async {
+            side_a.poll_events(history_limit, max_retries).await?;
+            side_b.poll_events(history_limit, max_retries).await?;
+            relayer.relay(&mut side_a, &side_b).await?;
+            relayer.relay(&mut side_b, &side_a).await
+        }
+        .await;
+
+        for side in [&side_a, &side_b] {
+            let (hits, misses) = side.store_cache.stats();
+            debug!(
+                "IBC store cache on chain {}: {hits} hits, {misses} misses",
+                side.name
+            );
+        }
+        if once || last_spend_summary.elapsed() >= spend_summary_interval {
+            log_spend_summary(&relayer);
+            last_spend_summary = Instant::now();
+        }
+
+        match result {
+            Err(err) if err.is::<BudgetExceeded>() => {
+                warn!("{err:#}");
+                if once {
+                    std::process::exit(BUDGET_EXCEEDED_EXIT_CODE);
+                }
+                if !confirm_budget_extension(&err).await? {
+                    return Err(err);
+                }
+                relayer.spend().extend_budget();
+                continue;
+            }
+            Err(err) if once => return Err(err),
+            Err(err) => warn!("Failed to relay: {err:#}"),
+            Ok(()) if once => return Ok(()),
+            Ok(()) => (),
+        }
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+            _ = tokio::time::sleep(poll_interval) => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        eclipse_ibc_fixtures::FixtureStore,
+        eclipse_ibc_state::internal_path::ChainParamsPath,
+        ibc::core::{
+            events::IbcEvent,
+            ics04_channel::{
+                channel::{ChannelEnd, Order},
+                events::{SendPacket, WriteAcknowledgement},
+                packet::{Packet, Receipt},
+            },
+        },
+        ibc_proto::ibc::core::{
+            channel::v1::Channel as RawChannel, client::v1::Height as RawHeight,
+        },
+    };
+
+    fn raw_packet(timeout_height: Option<RawHeight>, timeout_timestamp: u64) -> RawPacket {
+        RawPacket {
+            sequence: 7,
+            source_port: "transfer".to_owned(),
+            source_channel: "channel-0".to_owned(),
+            destination_port: "transfer".to_owned(),
+            destination_channel: "channel-3".to_owned(),
+            data: br#"{"amount":"1"}"#.to_vec(),
+            timeout_height,
+            timeout_timestamp,
+        }
+    }
+
+    fn send_packet_event(raw_packet: RawPacket) -> abci::Event {
+        let packet = Packet::try_from(raw_packet).unwrap();
+        IbcEvent::SendPacket(SendPacket::new(
+            packet,
+            Order::Unordered,
+            ConnectionId::default(),
+        ))
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn sent_packets_are_read_from_events() {
+        for raw in [
+            raw_packet(
+                Some(RawHeight {
+                    revision_number: 0,
+                    revision_height: 100,
+                }),
+                0,
+            ),
+            raw_packet(None, 1_700_000_000_000_000_000),
+        ] {
+            let packet = SentPacket::from_event(&send_packet_event(raw.clone())).unwrap();
+            assert_eq!(packet.to_raw(), raw);
+        }
+    }
+
+    #[test]
+    fn events_are_cached_by_channel_end() {
+        let raw = raw_packet(None, 1);
+        let ack = br#"{"result":"AQ=="}"#.to_vec();
+        let write_ack_event =
+            abci::Event::try_from(IbcEvent::WriteAcknowledgement(WriteAcknowledgement::new(
+                Packet::try_from(raw.clone()).unwrap(),
+                ack.clone().try_into().unwrap(),
+                ConnectionId::default(),
+            )))
+            .unwrap();
+
+        let port_id = PortId::transfer();
+        let mut sender = EventCache::default();
+        sender.record(
+            &send_packet_event(raw.clone()),
+            &port_id,
+            &ChannelId::new(0),
+        );
+        sender.record(&write_ack_event, &port_id, &ChannelId::new(0));
+        assert_eq!(sender.sent.keys().collect::<Vec<_>>(), [&7]);
+        assert!(sender.acks.is_empty());
+
+        let mut receiver = EventCache::default();
+        receiver.record(&send_packet_event(raw), &port_id, &ChannelId::new(3));
+        receiver.record(&write_ack_event, &port_id, &ChannelId::new(3));
+        assert!(receiver.sent.is_empty());
+        assert_eq!(receiver.acks[&7].1, ack);
+    }
+
+    #[test]
+    fn timeouts_are_checked_against_the_receiving_chain() {
+        let packet = SentPacket::from_event(&send_packet_event(raw_packet(
+            Some(RawHeight {
+                revision_number: 0,
+                revision_height: 100,
+            }),
+            1_000,
+        )))
+        .unwrap();
+
+        assert!(!packet.has_timed_out(Height::new(0, 99).unwrap(), 999));
+        assert!(packet.has_timed_out(Height::new(0, 100).unwrap(), 999));
+        assert!(packet.has_timed_out(Height::new(0, 99).unwrap(), 1_000));
+    }
+
+    #[test]
+    fn unreceived_sequences_skip_cleared_and_received_packets() {
+        // 2 was acknowledged or timed out, 4 was received out of order
+        let unreceived = unreceived_sequences(
+            1,
+            6,
+            |sequence| Ok(sequence != 2),
+            |sequence| Ok(sequence == 4),
+        )
+        .unwrap();
+        assert_eq!(unreceived, [1, 3, 5]);
+
+        assert_eq!(
+            unreceived_sequences(6, 6, |_| Ok(true), |_| Ok(false)).unwrap(),
+            Vec::<u64>::new()
+        );
+    }
+
+    #[test]
+    fn unreceived_acks_need_a_commitment_and_a_counterparty_ack() {
+        let (port_id, channel_id, cpty_channel_id) =
+            (PortId::transfer(), ChannelId::new(0), ChannelId::new(3));
+        let commitment =
+            || compute_packet_commitment(b"data", &TimeoutHeight::Never, &Timestamp::none());
+        let ack_commitment = || compute_ack_commitment(br#"{"result":"AQ=="}"#);
+
+        // Packets 1 to 5 were sent on channel-0, and 4 was already acknowledged.
+        // Packet 9 was sent on channel-10, whose paths share a prefix.
+        let store = IbcStore::default();
+        let mut ibc_state = IbcState::new(&store, 1);
+        for sequence in 1..=5 {
+            ibc_state.set(
+                &CommitmentPath::new(&port_id, &channel_id, sequence.into()),
+                commitment(),
+            );
+        }
+        ibc_state.set(
+            &CommitmentPath::new(&port_id, &ChannelId::new(10), 9.into()),
+            commitment(),
+        );
+        ibc_state.commit().unwrap();
+        let mut ibc_state = IbcState::new(&store, 2);
+        ibc_state.remove(&CommitmentPath::new(&port_id, &channel_id, 4.into()));
+        ibc_state.commit().unwrap();
+
+        // The counterparty acknowledged 2, 3, 4 and 9, and 5 on another channel
+        let cpty_store = IbcStore::default();
+        let mut cpty_state = IbcState::new(&cpty_store, 1);
+        for sequence in [3, 2, 4, 9] {
+            cpty_state.set(
+                &AckPath::new(&port_id, &cpty_channel_id, sequence.into()),
+                ack_commitment(),
+            );
+        }
+        cpty_state.set(
+            &AckPath::new(&port_id, &ChannelId::new(4), 5.into()),
+            ack_commitment(),
+        );
+        cpty_state.commit().unwrap();
+
+        let ibc_state = IbcState::new(&store, 2);
+        let cpty_state = IbcState::new(&cpty_store, 1);
+        assert_eq!(
+            unreceived_acks(
+                &ibc_state,
+                &port_id,
+                &channel_id,
+                &cpty_state,
+                &port_id,
+                &cpty_channel_id
+            )
+            .unwrap(),
+            [2, 3]
+        );
+
+        // Nothing was sent the other way
+        assert_eq!(
+            unreceived_acks(
+                &cpty_state,
+                &port_id,
+                &cpty_channel_id,
+                &ibc_state,
+                &port_id,
+                &channel_id
+            )
+            .unwrap(),
+            Vec::<u64>::new()
+        );
+    }
+
+    #[test]
+    fn frozen_chains_are_detected_in_their_latest_state() {
+        let account_data = FixtureStore::with_clients(1).build();
+        let store = &account_data.store;
+        assert!(!is_frozen(store).unwrap());
+
+        let version = store.read().latest_version().unwrap();
+        let mut ibc_state = IbcState::new(store, version + 1);
+        ibc_state.set(
+            &ChainParamsPath,
+            ChainParams {
+                frozen: true,
+                ..ChainParams::default()
+            },
+        );
+        ibc_state.commit().unwrap();
+        assert!(is_frozen(store).unwrap());
+    }
+
+    #[test]
+    fn unreceived_queries_follow_receipts_commitments_and_ordering() {
+        let (port_id, channel_id) = (PortId::transfer(), ChannelId::new(0));
+        let account_data = FixtureStore::with_clients(1)
+            .with_channels(1)
+            .with_packets(3)
+            .build();
+        let version = account_data.store.read().latest_version().unwrap();
+        let mut ibc_state = IbcState::new(&account_data.store, version + 1);
+        assert_eq!(
+            committed_sequences(&ibc_state, &port_id, &channel_id).unwrap(),
+            [1, 2, 3]
+        );
+        // Acknowledgements of 4 were already received, which cleared its commitment
+        assert_eq!(
+            filter_unreceived_acks(&ibc_state, &port_id, &channel_id, &[3, 1, 4]).unwrap(),
+            [3, 1]
+        );
+
+        ibc_state.set(
+            &ReceiptPath::new(&port_id, &channel_id, 2.into()),
+            Receipt::Ok,
+        );
+        assert_eq!(
+            filter_unreceived_packets(&ibc_state, &port_id, &channel_id, &[1, 2, 3]).unwrap(),
+            [1, 3]
+        );
+
+        // Ordered channels go by the next receive sequence alone
+        let channel_end_path = ChannelEndPath::new(&port_id, &channel_id);
+        let channel_end = RawChannel {
+            ordering: RawOrder::Ordered as i32,
+            ..ibc_state.get_raw(&channel_end_path).unwrap().unwrap()
+        };
+        ibc_state.set(
+            &channel_end_path,
+            ChannelEnd::try_from(channel_end).unwrap(),
+        );
+        ibc_state.set(&SeqRecvPath::new(&port_id, &channel_id), 3.into());
+        assert_eq!(
+            filter_unreceived_packets(&ibc_state, &port_id, &channel_id, &[1, 2, 3, 4]).unwrap(),
+            [3, 4]
+        );
+
+        let unknown_channel = ChannelId::new(7);
+        filter_unreceived_packets(&ibc_state, &port_id, &unknown_channel, &[1]).unwrap_err();
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff_delay(0), INITIAL_BACKOFF);
+        assert_eq!(backoff_delay(1), INITIAL_BACKOFF * 2);
+        assert_eq!(backoff_delay(3), INITIAL_BACKOFF * 8);
+        assert_eq!(backoff_delay(10), MAX_BACKOFF);
+        assert_eq!(backoff_delay(u32::MAX), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn only_transient_failures_are_retried() {
+        let mut attempts = 0;
+        let result: anyhow::Result<()> = with_retries("do something", 3, || {
+            attempts += 1;
+            async { bail!("rejected") }
+        })
+        .await;
+        assert_eq!(attempts, 1);
+        assert_eq!(
+            format!("{:#}", result.unwrap_err()),
+            "Failed to do something: rejected"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, VecDeque};
+
+/// A sequence to relay on a lane, such as receiving packets on one channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Work<L> {
+    pub(crate) lane: L,
+    pub(crate) sequence: u64,
+    /// Number of earlier attempts that failed
+    attempts: u32,
+}
+
+#[derive(Debug)]
+struct Lane {
+    /// Whether sequences must land in order, as on ordered channels
+    ordered: bool,
+    /// Sequences waiting to be handed out, with their failed attempts, in order
+    pending: VecDeque<(u64, u32)>,
+    in_flight: usize,
+}
+
+impl Lane {
+    fn is_ready(&self) -> bool {
+        !self.pending.is_empty() && !(self.ordered && self.in_flight > 0)
+    }
+}
+
+/// Hands out relay work to run concurrently, at most `max_parallel` at a time.
+/// Ordered lanes have at most one sequence in flight, handed out in sequence
+/// order, while unordered lanes and separate lanes run side by side.
+///
+/// Failed work is queued again ahead of the rest of its lane until it fails
+/// `max_attempts` times. Giving up on a sequence of an ordered lane also gives
+/// up on the sequences after it, since they cannot land before it does.
+#[derive(Debug)]
+pub(crate) struct RelaySchedule<L> {
+    lanes: BTreeMap<L, Lane>,
+    max_parallel: usize,
+    max_attempts: u32,
+    in_flight: usize,
+}
+
+impl<L: Clone + Ord> RelaySchedule<L> {
+    pub(crate) fn new(max_parallel: usize, max_attempts: u32) -> Self {
+        Self {
+            lanes: BTreeMap::new(),
+            max_parallel,
+            max_attempts,
+            in_flight: 0,
+        }
+    }
+
+    /// Queues `sequence` on `lane`. Whether the lane is ordered is set by the first
+    /// sequence queued on it.
+    pub(crate) fn push(&mut self, lane: L, ordered: bool, sequence: u64) {
+        let lane = self.lanes.entry(lane).or_insert_with(|| Lane {
+            ordered,
+            pending: VecDeque::new(),
+            in_flight: 0,
+        });
+        let index = lane
+            .pending
+            .partition_point(|&(pending, _)| pending < sequence);
+        if lane.pending.get(index).map(|&(pending, _)| pending) != Some(sequence) {
+            lane.pending.insert(index, (sequence, 0));
+        }
+    }
+
+    /// Takes the next work to start, if any may start now. Lanes with the least
+    /// work in flight go first, so that one busy lane does not hold up the others.
+    pub(crate) fn next_ready(&mut self) -> Option<Work<L>> {
+        if self.in_flight >= self.max_parallel {
+            return None;
+        }
+        let (lane_id, lane) = self
+            .lanes
+            .iter_mut()
+            .filter(|(_, lane)| lane.is_ready())
+            .min_by_key(|(_, lane)| lane.in_flight)?;
+        let (sequence, attempts) = lane.pending.pop_front()?;
+        lane.in_flight += 1;
+        self.in_flight += 1;
+        Some(Work {
+            lane: lane_id.clone(),
+            sequence,
+            attempts,
+        })
+    }
+
+    fn finish(&mut self, work: &Work<L>) -> Option<&mut Lane> {
+        let lane = self.lanes.get_mut(&work.lane)?;
+        lane.in_flight = lane.in_flight.saturating_sub(1);
+        self.in_flight = self.in_flight.saturating_sub(1);
+        Some(lane)
+    }
+
+    /// Records that `work` landed.
+    pub(crate) fn complete(&mut self, work: &Work<L>) {
+        self.finish(work);
+    }
+
+    /// Records that `work` failed, queueing it again unless it is out of attempts.
+    /// Returns the sequences given up on.
+    pub(crate) fn fail(&mut self, work: &Work<L>) -> Vec<u64> {
+        let max_attempts = self.max_attempts;
+        let lane = match self.finish(work) {
+            Some(lane) => lane,
+            None => return vec![],
+        };
+        let attempts = work.attempts + 1;
+        if attempts < max_attempts {
+            lane.pending.push_front((work.sequence, attempts));
+            return vec![];
+        }
+
+        let mut given_up = vec![work.sequence];
+        if lane.ordered {
+            given_up.extend(lane.pending.drain(..).map(|(sequence, _)| sequence));
+        }
+        given_up
+    }
+
+    /// Stops handing out queued work. Work already in flight still needs to be
+    /// completed or failed.
+    pub(crate) fn cancel_pending(&mut self) {
+        for lane in self.lanes.values_mut() {
+            lane.pending.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `schedule` to completion, finishing the oldest work in flight first
+    /// and failing the work `fails` says to. Returns the batches of work started
+    /// together.
+    fn drain(
+        schedule: &mut RelaySchedule<&'static str>,
+        mut fails: impl FnMut(&Work<&'static str>) -> bool,
+    ) -> Vec<Vec<(&'static str, u64)>> {
+        let mut rounds = vec![];
+        let mut in_flight = VecDeque::new();
+        loop {
+            let mut started = vec![];
+            while let Some(work) = schedule.next_ready() {
+                started.push((work.lane, work.sequence));
+                in_flight.push_back(work);
+            }
+            if !started.is_empty() {
+                rounds.push(started);
+            }
+            assert_eq!(schedule.in_flight, in_flight.len());
+            assert!(in_flight.len() <= schedule.max_parallel);
+            match in_flight.pop_front() {
+                Some(work) if fails(&work) => {
+                    schedule.fail(&work);
+                }
+                Some(work) => schedule.complete(&work),
+                None => return rounds,
+            }
+        }
+    }
+
+    #[test]
+    fn ordered_lanes_run_one_sequence_at_a_time_in_order() {
+        let mut schedule = RelaySchedule::new(4, 3);
+        for sequence in [3, 1, 2] {
+            schedule.push("ordered", true, sequence);
+        }
+        for sequence in 1..=5 {
+            schedule.push("unordered", false, sequence);
+        }
+
+        let rounds = drain(&mut schedule, |_| false);
+        assert!(rounds.iter().all(|round| round.len() <= 4), "{rounds:?}");
+        for round in &rounds {
+            let ordered = round.iter().filter(|(lane, _)| *lane == "ordered").count();
+            assert!(ordered <= 1, "{rounds:?}");
+        }
+        let ordered = rounds
+            .concat()
+            .into_iter()
+            .filter(|(lane, _)| *lane == "ordered")
+            .map(|(_, sequence)| sequence)
+            .collect::<Vec<_>>();
+        assert_eq!(ordered, [1, 2, 3]);
+        assert_eq!(
+            rounds[0],
+            [
+                ("ordered", 1),
+                ("unordered", 1),
+                ("unordered", 2),
+                ("unordered", 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn failures_are_retried_before_later_sequences() {
+        let mut schedule = RelaySchedule::new(2, 2);
+        for sequence in 1..=3 {
+            schedule.push("ordered", true, sequence);
+        }
+        let mut failed_once = false;
+        let rounds = drain(&mut schedule, |work| {
+            let fail = work.sequence == 2 && !failed_once;
+            failed_once |= fail;
+            fail
+        });
+        assert_eq!(
+            rounds.concat(),
+            [
+                ("ordered", 1),
+                ("ordered", 2),
+                ("ordered", 2),
+                ("ordered", 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn giving_up_on_an_ordered_sequence_drops_the_rest_of_its_lane() {
+        let mut schedule = RelaySchedule::new(8, 1);
+        for sequence in 1..=3 {
+            schedule.push("ordered", true, sequence);
+            schedule.push("unordered", false, sequence);
+        }
+        let first = schedule.next_ready().unwrap();
+        assert_eq!((first.lane, first.sequence), ("ordered", 1));
+        assert_eq!(schedule.fail(&first), [1, 2, 3]);
+
+        let unordered = schedule.next_ready().unwrap();
+        assert_eq!(schedule.fail(&unordered), [unordered.sequence]);
+
+        let rest = drain(&mut schedule, |_| false).concat();
+        assert_eq!(rest, [("unordered", 2), ("unordered", 3)]);
+    }
+}
@@ -0,0 +1,229 @@
+use {
+    anyhow::anyhow,
+    serde::{Deserialize, Serialize},
+    solana_transaction_status::UiTransactionStatusMeta,
+    std::{collections::BTreeMap, fs, io, path::PathBuf},
+    thiserror::Error,
+};
+
+/// Lamports the fee payer, which is always the first account, lost in a
+/// transaction. This includes rent paid for accounts the transaction created,
+/// and is negative if the payer was refunded more than the fee, as when closing
+/// a tx buffer.
+pub(crate) fn payer_spend(meta: &UiTransactionStatusMeta) -> anyhow::Result<i64> {
+    match (meta.pre_balances.first(), meta.post_balances.first()) {
+        (Some(&pre_balance), Some(&post_balance)) => {
+            Ok(i64::try_from(pre_balance)? - i64::try_from(post_balance)?)
+        }
+        _ => Err(anyhow!(
+            "Transaction meta is missing the fee payer's balances"
+        )),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct SpendTotal {
+    pub(crate) txs: u64,
+    pub(crate) lamports: i64,
+}
+
+#[derive(Debug, Error)]
+#[error("relayer spent {spent} lamports, which reaches the limit of {limit} lamports")]
+pub(crate) struct BudgetExceeded {
+    pub(crate) spent: i64,
+    pub(crate) limit: i64,
+}
+
+/// Lamports spent by the relayer, by direction and then by message kind.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct SpendLedger {
+    totals: BTreeMap<String, BTreeMap<String, SpendTotal>>,
+}
+
+impl SpendLedger {
+    pub(crate) fn record(&mut self, direction: &str, kind: &str, lamports: i64) {
+        let total = self
+            .totals
+            .entry(direction.to_owned())
+            .or_default()
+            .entry(kind.to_owned())
+            .or_default();
+        total.txs += 1;
+        total.lamports = total.lamports.saturating_add(lamports);
+    }
+
+    pub(crate) fn total(&self) -> i64 {
+        self.totals
+            .values()
+            .flat_map(BTreeMap::values)
+            .fold(0, |sum, total| sum.saturating_add(total.lamports))
+    }
+
+    /// One line per direction and message kind, followed by the overall total.
+    pub(crate) fn summary(&self) -> Vec<String> {
+        let mut lines = self
+            .totals
+            .iter()
+            .flat_map(|(direction, by_kind)| {
+                by_kind
+                    .iter()
+                    .map(move |(kind, SpendTotal { txs, lamports })| {
+                        format!("{direction} {kind}: {lamports} lamports over {txs} txs")
+                    })
+            })
+            .collect::<Vec<_>>();
+        lines.push(format!("Total: {} lamports", self.total()));
+        lines
+    }
+}
+
+/// Keeps the relayer's spend in a file, so that restarting the relayer does not
+/// reset its budget, and stops it once the spend reaches a limit.
+#[derive(Debug)]
+pub(crate) struct SpendTracker {
+    path: PathBuf,
+    ledger: SpendLedger,
+    max_spend: Option<u64>,
+    limit: Option<i64>,
+}
+
+impl SpendTracker {
+    /// Loads the spend recorded in `path`, if it exists.
+    pub(crate) fn load(path: PathBuf, max_spend: Option<u64>) -> anyhow::Result<Self> {
+        let ledger = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|err| anyhow!("Invalid spend file {}: {err}", path.display()))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => SpendLedger::default(),
+            Err(err) => return Err(err.into()),
+        };
+        let limit = max_spend.map(i64::try_from).transpose()?;
+        Ok(Self {
+            path,
+            ledger,
+            max_spend,
+            limit,
+        })
+    }
+
+    pub(crate) fn ledger(&self) -> &SpendLedger {
+        &self.ledger
+    }
+
+    /// Records the spend of a transaction and saves the new totals.
+    pub(crate) fn record(&mut self, direction: &str, kind: &str, lamports: i64) -> io::Result<()> {
+        self.ledger.record(direction, kind, lamports);
+        self.save()
+    }
+
+    pub(crate) fn check_budget(&self) -> Result<(), BudgetExceeded> {
+        let spent = self.ledger.total();
+        match self.limit {
+            Some(limit) if spent >= limit => Err(BudgetExceeded { spent, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Allows spending up to the maximum again, counting from what has been
+    /// spent so far.
+    pub(crate) fn extend_budget(&mut self) {
+        if let Some(max_spend) = self.max_spend {
+            let max_spend = i64::try_from(max_spend).unwrap_or(i64::MAX);
+            self.limit = Some(self.ledger.total().saturating_add(max_spend));
+        }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first so that a crash cannot leave the
+        // totals half written
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(&self.ledger)?)?;
+        fs::rename(tmp_path, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_transaction_status::TransactionStatusMeta};
+
+    fn meta(pre_balances: Vec<u64>, post_balances: Vec<u64>) -> UiTransactionStatusMeta {
+        TransactionStatusMeta {
+            fee: 5000,
+            pre_balances,
+            post_balances,
+            ..TransactionStatusMeta::default()
+        }
+        .into()
+    }
+
+    fn spend_file(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("eclipse-ibc-{name}-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn spend_is_the_fee_payer_balance_change() {
+        assert_eq!(
+            payer_spend(&meta(vec![1_000_000, 0], vec![995_000, 0])).unwrap(),
+            5000
+        );
+        // Creating a tx buffer also pays its rent, which closing it refunds
+        assert_eq!(
+            payer_spend(&meta(vec![1_000_000, 0], vec![895_000, 100_000])).unwrap(),
+            105_000,
+        );
+        assert_eq!(
+            payer_spend(&meta(vec![895_000], vec![990_000])).unwrap(),
+            -95_000
+        );
+        payer_spend(&meta(vec![], vec![])).unwrap_err();
+    }
+
+    #[test]
+    fn totals_are_aggregated_by_direction_and_kind() {
+        let mut ledger = SpendLedger::default();
+        ledger.record("A to B", "MsgRecvPacket", 5000);
+        ledger.record("A to B", "MsgRecvPacket", 7000);
+        ledger.record("A to B", "MsgUpdateClient", 5000);
+        ledger.record("B to A", "MsgAcknowledgement", 5000);
+        assert_eq!(ledger.total(), 22_000);
+        assert_eq!(
+            ledger.summary(),
+            [
+                "A to B MsgRecvPacket: 12000 lamports over 2 txs",
+                "A to B MsgUpdateClient: 5000 lamports over 1 txs",
+                "B to A MsgAcknowledgement: 5000 lamports over 1 txs",
+                "Total: 22000 lamports",
+            ],
+        );
+    }
+
+    #[test]
+    fn totals_and_budget_survive_restarts() {
+        let path = spend_file("budget");
+        let mut tracker = SpendTracker::load(path.clone(), Some(10_000)).unwrap();
+        tracker.record("A to B", "MsgRecvPacket", 6000).unwrap();
+        tracker.check_budget().unwrap();
+
+        let mut tracker = SpendTracker::load(path.clone(), Some(10_000)).unwrap();
+        assert_eq!(tracker.ledger().total(), 6000);
+        tracker.record("A to B", "MsgRecvPacket", 6000).unwrap();
+        let BudgetExceeded { spent, limit } = tracker.check_budget().unwrap_err();
+        assert_eq!((spent, limit), (12_000, 10_000));
+
+        tracker.extend_budget();
+        tracker.check_budget().unwrap();
+        tracker
+            .record("B to A", "MsgAcknowledgement", 10_000)
+            .unwrap();
+        tracker.check_budget().unwrap_err();
+
+        let unlimited = SpendTracker::load(path.clone(), None).unwrap();
+        unlimited.check_budget().unwrap();
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,646 @@
+use {
+    crate::{
+        chain_state::get_latest_consensus_height,
+        duration,
+        proof::existence_proof_to_merkle_proof,
+        store_cache::{StoreCache, StoreSnapshot},
+    },
+    clap::Parser,
+    core::{fmt::Display, str::FromStr, time::Duration},
+    eclipse_ibc_known_path::KnownPath,
+    eclipse_ibc_known_proto::KnownProto,
+    eclipse_ibc_light_client::eclipse_chain,
+    eclipse_ibc_state::IbcState,
+    ibc::core::{
+        ics02_client::height::Height,
+        ics04_channel::packet::Sequence,
+        ics24_host::{
+            identifier::{ChannelId, ClientId, ConnectionId, PortId},
+            path::{
+                AckPath, ChannelEndPath, ClientConsensusStatePath, ClientStatePath, CommitmentPath,
+                ConnectionPath, SeqRecvPath,
+            },
+        },
+    },
+    ibc_proto::ibc::core::{
+        channel::v1::{
+            query_server::{Query as ChannelQuery, QueryServer as ChannelQueryServer},
+            QueryChannelClientStateRequest, QueryChannelClientStateResponse,
+            QueryChannelConsensusStateRequest, QueryChannelConsensusStateResponse,
+            QueryChannelRequest, QueryChannelResponse, QueryChannelsRequest, QueryChannelsResponse,
+            QueryConnectionChannelsRequest, QueryConnectionChannelsResponse,
+            QueryNextSequenceReceiveRequest, QueryNextSequenceReceiveResponse,
+            QueryPacketAcknowledgementRequest, QueryPacketAcknowledgementResponse,
+            QueryPacketAcknowledgementsRequest, QueryPacketAcknowledgementsResponse,
+            QueryPacketCommitmentRequest, QueryPacketCommitmentResponse,
+            QueryPacketCommitmentsRequest, QueryPacketCommitmentsResponse,
+            QueryPacketReceiptRequest, QueryPacketReceiptResponse, QueryUnreceivedAcksRequest,
+            QueryUnreceivedAcksResponse, QueryUnreceivedPacketsRequest,
+            QueryUnreceivedPacketsResponse,
+        },
+        client::v1::{
+            query_server::{Query as ClientQuery, QueryServer as ClientQueryServer},
+            Height as RawHeight, QueryClientParamsRequest, QueryClientParamsResponse,
+            QueryClientStateRequest, QueryClientStateResponse, QueryClientStatesRequest,
+            QueryClientStatesResponse, QueryClientStatusRequest, QueryClientStatusResponse,
+            QueryConsensusStateHeightsRequest, QueryConsensusStateHeightsResponse,
+            QueryConsensusStateRequest, QueryConsensusStateResponse, QueryConsensusStatesRequest,
+            QueryConsensusStatesResponse, QueryUpgradedClientStateRequest,
+            QueryUpgradedClientStateResponse, QueryUpgradedConsensusStateRequest,
+            QueryUpgradedConsensusStateResponse,
+        },
+        connection::v1::{
+            query_server::{Query as ConnectionQuery, QueryServer as ConnectionQueryServer},
+            QueryClientConnectionsRequest, QueryClientConnectionsResponse,
+            QueryConnectionClientStateRequest, QueryConnectionClientStateResponse,
+            QueryConnectionConsensusStateRequest, QueryConnectionConsensusStateResponse,
+            QueryConnectionParamsRequest, QueryConnectionParamsResponse, QueryConnectionRequest,
+            QueryConnectionResponse, QueryConnectionsRequest, QueryConnectionsResponse,
+        },
+    },
+    log::{debug, info, warn},
+    prost::Message as _,
+    solana_client::nonblocking::rpc_client::RpcClient,
+    std::{net::SocketAddr, sync::Arc},
+    tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+    },
+    tonic::{transport::Server, Request, Response, Status},
+};
+
+/// A value read from the latest version of the IBC store, along with its proof
+/// against that version's root and the height of that root.
+struct Proven<T> {
+    value: T,
+    proof: Vec<u8>,
+    proof_height: RawHeight,
+}
+
+fn internal_error(err: anyhow::Error) -> Status {
+    Status::internal(format!("{err:#}"))
+}
+
+fn parse_id<T>(kind: &str, id: &str) -> Result<T, Status>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    id.parse()
+        .map_err(|err| Status::invalid_argument(format!("invalid {kind} {id:?}: {err}")))
+}
+
+fn unsupported<T>(query: &str) -> Result<Response<T>, Status> {
+    Err(Status::unimplemented(format!(
+        "{query} queries are not supported"
+    )))
+}
+
+/// Serves the ibc-go client, connection and channel query services from the IBC
+/// store of a single chain.
+struct QueryService {
+    rpc_client: RpcClient,
+    store_cache: StoreCache,
+}
+
+impl QueryService {
+    /// Fetches a snapshot of the IBC store and the metadata read with it. Each
+    /// request fetches one, so that its response describes a single version.
+    async fn get_snapshot(&self) -> Result<Arc<StoreSnapshot>, Status> {
+        self.store_cache
+            .get(&self.rpc_client)
+            .await
+            .map_err(|err| Status::unavailable(format!("could not fetch IBC store: {err:#}")))
+    }
+
+    /// Reads `path` at the latest version of a freshly fetched snapshot, as
+    /// [`read_proven`] does.
+    async fn get_proven<K>(&self, path: &K) -> Result<Proven<<K::Value as KnownProto>::Raw>, Status>
+    where
+        K: KnownPath,
+    {
+        read_proven(&*self.get_snapshot().await?, path)
+    }
+}
+
+fn latest_version(snapshot: &StoreSnapshot) -> Result<u64, Status> {
+    snapshot
+        .store
+        .read()
+        .latest_version()
+        .ok_or_else(|| Status::unavailable("IBC store is missing latest version"))
+}
+
+/// Reads `path` at the latest version of `snapshot`, failing with `NOT_FOUND` if
+/// it is not set. The value is returned in its stored encoding, which is what the
+/// proof commits to.
+fn read_proven<K>(
+    snapshot: &StoreSnapshot,
+    path: &K,
+) -> Result<Proven<<K::Value as KnownProto>::Raw>, Status>
+where
+    K: KnownPath,
+{
+    let latest_version = latest_version(snapshot)?;
+    let ibc_state = IbcState::new(&snapshot.store, latest_version);
+
+    let value = ibc_state
+        .get_raw(path)
+        .map_err(internal_error)?
+        .ok_or_else(|| Status::not_found(format!("{path} not found")))?;
+    let proof = ibc_state.get_proof(path).map_err(internal_error)?;
+    let proof_height = eclipse_chain::height_of_slot_with_revision(
+        latest_version,
+        snapshot.metadata.revision_number,
+    )
+    .map_err(|err| Status::internal(err.to_string()))?;
+
+    Ok(Proven {
+        value,
+        proof: existence_proof_to_merkle_proof(proof).encode_to_vec(),
+        proof_height: proof_height.into(),
+    })
+}
+
+fn latest_consensus_height(
+    snapshot: &StoreSnapshot,
+    client_id: &ClientId,
+) -> Result<Height, Status> {
+    let ibc_state = IbcState::new(&snapshot.store, latest_version(snapshot)?);
+    get_latest_consensus_height(&ibc_state, client_id.as_str())
+        .map_err(|err| Status::not_found(format!("{err:#}")))
+}
+
+#[tonic::async_trait]
+impl ClientQuery for QueryService {
+    async fn client_state(
+        &self,
+        request: Request<QueryClientStateRequest>,
+    ) -> Result<Response<QueryClientStateResponse>, Status> {
+        let QueryClientStateRequest { client_id } = request.into_inner();
+        let client_id = parse_id::<ClientId>("client ID", &client_id)?;
+        let Proven {
+            value,
+            proof,
+            proof_height,
+        } = self.get_proven(&ClientStatePath::new(&client_id)).await?;
+        Ok(Response::new(QueryClientStateResponse {
+            client_state: Some(value),
+            proof,
+            proof_height: Some(proof_height),
+        }))
+    }
+
+    async fn client_states(
+        &self,
+        _request: Request<QueryClientStatesRequest>,
+    ) -> Result<Response<QueryClientStatesResponse>, Status> {
+        unsupported("ClientStates")
+    }
+
+    async fn consensus_state(
+        &self,
+        request: Request<QueryConsensusStateRequest>,
+    ) -> Result<Response<QueryConsensusStateResponse>, Status> {
+        let QueryConsensusStateRequest {
+            client_id,
+            revision_number,
+            revision_height,
+            latest_height,
+        } = request.into_inner();
+        let client_id = parse_id::<ClientId>("client ID", &client_id)?;
+        let snapshot = self.get_snapshot().await?;
+        let height = if latest_height {
+            latest_consensus_height(&snapshot, &client_id)?
+        } else {
+            Height::new(revision_number, revision_height)
+                .map_err(|err| Status::invalid_argument(err.to_string()))?
+        };
+        let Proven {
+            value,
+            proof,
+            proof_height,
+        } = read_proven(
+            &snapshot,
+            &ClientConsensusStatePath::new(&client_id, &height),
+        )?;
+        Ok(Response::new(QueryConsensusStateResponse {
+            consensus_state: Some(value),
+            proof,
+            proof_height: Some(proof_height),
+        }))
+    }
+
+    async fn consensus_states(
+        &self,
+        _request: Request<QueryConsensusStatesRequest>,
+    ) -> Result<Response<QueryConsensusStatesResponse>, Status> {
+        unsupported("ConsensusStates")
+    }
+
+    async fn consensus_state_heights(
+        &self,
+        _request: Request<QueryConsensusStateHeightsRequest>,
+    ) -> Result<Response<QueryConsensusStateHeightsResponse>, Status> {
+        unsupported("ConsensusStateHeights")
+    }
+
+    async fn client_status(
+        &self,
+        _request: Request<QueryClientStatusRequest>,
+    ) -> Result<Response<QueryClientStatusResponse>, Status> {
+        unsupported("ClientStatus")
+    }
+
+    async fn client_params(
+        &self,
+        _request: Request<QueryClientParamsRequest>,
+    ) -> Result<Response<QueryClientParamsResponse>, Status> {
+        unsupported("ClientParams")
+    }
+
+    async fn upgraded_client_state(
+        &self,
+        _request: Request<QueryUpgradedClientStateRequest>,
+    ) -> Result<Response<QueryUpgradedClientStateResponse>, Status> {
+        unsupported("UpgradedClientState")
+    }
+
+    async fn upgraded_consensus_state(
+        &self,
+        _request: Request<QueryUpgradedConsensusStateRequest>,
+    ) -> Result<Response<QueryUpgradedConsensusStateResponse>, Status> {
+        unsupported("UpgradedConsensusState")
+    }
+}
+
+#[tonic::async_trait]
+impl ConnectionQuery for QueryService {
+    async fn connection(
+        &self,
+        request: Request<QueryConnectionRequest>,
+    ) -> Result<Response<QueryConnectionResponse>, Status> {
+        let QueryConnectionRequest { connection_id } = request.into_inner();
+        let connection_id = parse_id::<ConnectionId>("connection ID", &connection_id)?;
+        let Proven {
+            value,
+            proof,
+            proof_height,
+        } = self
+            .get_proven(&ConnectionPath::new(&connection_id))
+            .await?;
+        Ok(Response::new(QueryConnectionResponse {
+            connection: Some(value),
+            proof,
+            proof_height: Some(proof_height),
+        }))
+    }
+
+    async fn connections(
+        &self,
+        _request: Request<QueryConnectionsRequest>,
+    ) -> Result<Response<QueryConnectionsResponse>, Status> {
+        unsupported("Connections")
+    }
+
+    async fn client_connections(
+        &self,
+        _request: Request<QueryClientConnectionsRequest>,
+    ) -> Result<Response<QueryClientConnectionsResponse>, Status> {
+        unsupported("ClientConnections")
+    }
+
+    async fn connection_client_state(
+        &self,
+        _request: Request<QueryConnectionClientStateRequest>,
+    ) -> Result<Response<QueryConnectionClientStateResponse>, Status> {
+        unsupported("ConnectionClientState")
+    }
+
+    async fn connection_consensus_state(
+        &self,
+        _request: Request<QueryConnectionConsensusStateRequest>,
+    ) -> Result<Response<QueryConnectionConsensusStateResponse>, Status> {
+        unsupported("ConnectionConsensusState")
+    }
+
+    async fn connection_params(
+        &self,
+        _request: Request<QueryConnectionParamsRequest>,
+    ) -> Result<Response<QueryConnectionParamsResponse>, Status> {
+        unsupported("ConnectionParams")
+    }
+}
+
+#[tonic::async_trait]
+impl ChannelQuery for QueryService {
+    async fn channel(
+        &self,
+        request: Request<QueryChannelRequest>,
+    ) -> Result<Response<QueryChannelResponse>, Status> {
+        let QueryChannelRequest {
+            port_id,
+            channel_id,
+        } = request.into_inner();
+        let port_id = parse_id::<PortId>("port ID", &port_id)?;
+        let channel_id = parse_id::<ChannelId>("channel ID", &channel_id)?;
+        let Proven {
+            value,
+            proof,
+            proof_height,
+        } = self
+            .get_proven(&ChannelEndPath::new(&port_id, &channel_id))
+            .await?;
+        Ok(Response::new(QueryChannelResponse {
+            channel: Some(value),
+            proof,
+            proof_height: Some(proof_height),
+        }))
+    }
+
+    async fn channels(
+        &self,
+        _request: Request<QueryChannelsRequest>,
+    ) -> Result<Response<QueryChannelsResponse>, Status> {
+        unsupported("Channels")
+    }
+
+    async fn connection_channels(
+        &self,
+        _request: Request<QueryConnectionChannelsRequest>,
+    ) -> Result<Response<QueryConnectionChannelsResponse>, Status> {
+        unsupported("ConnectionChannels")
+    }
+
+    async fn channel_client_state(
+        &self,
+        _request: Request<QueryChannelClientStateRequest>,
+    ) -> Result<Response<QueryChannelClientStateResponse>, Status> {
+        unsupported("ChannelClientState")
+    }
+
+    async fn channel_consensus_state(
+        &self,
+        _request: Request<QueryChannelConsensusStateRequest>,
+    ) -> Result<Response<QueryChannelConsensusStateResponse>, Status> {
+        unsupported("ChannelConsensusState")
+    }
+
+    async fn packet_commitment(
+        &self,
+        request: Request<QueryPacketCommitmentRequest>,
+    ) -> Result<Response<QueryPacketCommitmentResponse>, Status> {
+        let QueryPacketCommitmentRequest {
+            port_id,
+            channel_id,
+            sequence,
+        } = request.into_inner();
+        let port_id = parse_id::<PortId>("port ID", &port_id)?;
+        let channel_id = parse_id::<ChannelId>("channel ID", &channel_id)?;
+        let Proven {
+            value,
+            proof,
+            proof_height,
+        } = self
+            .get_proven(&CommitmentPath::new(
+                &port_id,
+                &channel_id,
+                Sequence::from(sequence),
+            ))
+            .await?;
+        Ok(Response::new(QueryPacketCommitmentResponse {
+            commitment: value,
+            proof,
+            proof_height: Some(proof_height),
+        }))
+    }
+
+    async fn packet_commitments(
+        &self,
+        _request: Request<QueryPacketCommitmentsRequest>,
+    ) -> Result<Response<QueryPacketCommitmentsResponse>, Status> {
+        unsupported("PacketCommitments")
+    }
+
+    async fn packet_receipt(
+        &self,
+        _request: Request<QueryPacketReceiptRequest>,
+    ) -> Result<Response<QueryPacketReceiptResponse>, Status> {
+        unsupported("PacketReceipt")
+    }
+
+    async fn packet_acknowledgement(
+        &self,
+        request: Request<QueryPacketAcknowledgementRequest>,
+    ) -> Result<Response<QueryPacketAcknowledgementResponse>, Status> {
+        let QueryPacketAcknowledgementRequest {
+            port_id,
+            channel_id,
+            sequence,
+        } = request.into_inner();
+        let port_id = parse_id::<PortId>("port ID", &port_id)?;
+        let channel_id = parse_id::<ChannelId>("channel ID", &channel_id)?;
+        let Proven {
+            value,
+            proof,
+            proof_height,
+        } = self
+            .get_proven(&AckPath::new(
+                &port_id,
+                &channel_id,
+                Sequence::from(sequence),
+            ))
+            .await?;
+        Ok(Response::new(QueryPacketAcknowledgementResponse {
+            acknowledgement: value,
+            proof,
+            proof_height: Some(proof_height),
+        }))
+    }
+
+    async fn packet_acknowledgements(
+        &self,
+        _request: Request<QueryPacketAcknowledgementsRequest>,
+    ) -> Result<Response<QueryPacketAcknowledgementsResponse>, Status> {
+        unsupported("PacketAcknowledgements")
+    }
+
+    async fn unreceived_packets(
+        &self,
+        _request: Request<QueryUnreceivedPacketsRequest>,
+    ) -> Result<Response<QueryUnreceivedPacketsResponse>, Status> {
+        unsupported("UnreceivedPackets")
+    }
+
+    async fn unreceived_acks(
+        &self,
+        _request: Request<QueryUnreceivedAcksRequest>,
+    ) -> Result<Response<QueryUnreceivedAcksResponse>, Status> {
+        unsupported("UnreceivedAcks")
+    }
+
+    async fn next_sequence_receive(
+        &self,
+        request: Request<QueryNextSequenceReceiveRequest>,
+    ) -> Result<Response<QueryNextSequenceReceiveResponse>, Status> {
+        let QueryNextSequenceReceiveRequest {
+            port_id,
+            channel_id,
+        } = request.into_inner();
+        let port_id = parse_id::<PortId>("port ID", &port_id)?;
+        let channel_id = parse_id::<ChannelId>("channel ID", &channel_id)?;
+        let Proven {
+            value,
+            proof,
+            proof_height,
+        } = self
+            .get_proven(&SeqRecvPath::new(&port_id, &channel_id))
+            .await?;
+        Ok(Response::new(QueryNextSequenceReceiveResponse {
+            next_sequence_receive: value,
+            proof,
+            proof_height: Some(proof_height),
+        }))
+    }
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct Args {
+    /// Endpoint of the chain to serve queries for
+    #[arg(long, default_value = "http://127.0.0.1:8899")]
+    endpoint: String,
+
+    /// Address to serve the gRPC query services on
+    #[arg(long, default_value = "127.0.0.1:9090")]
+    listen: SocketAddr,
+
+    /// How long to reuse the decoded IBC store for while the data of the
+    /// storage account is unchanged
+    #[arg(long, default_value = "1m", value_parser = duration::parse_duration)]
+    max_store_staleness: Duration,
+
+    /// Address to serve the IBC store cache's hit and miss counts on, at
+    /// `/metrics` in the Prometheus text format
+    #[arg(long)]
+    metrics_listen: Option<SocketAddr>,
+}
+
+/// The IBC store cache's counters in the Prometheus text format.
+fn metrics_text(store_cache: &StoreCache) -> String {
+    let (hits, misses) = store_cache.stats();
+    format!(
+        "# HELP eclipse_ibc_store_cache_hits_total Queries answered from the cached IBC store\n\
+         # TYPE eclipse_ibc_store_cache_hits_total counter\n\
+         eclipse_ibc_store_cache_hits_total {hits}\n\
+         # HELP eclipse_ibc_store_cache_misses_total Queries that fetched and decoded the IBC \
+         store\n\
+         # TYPE eclipse_ibc_store_cache_misses_total counter\n\
+         eclipse_ibc_store_cache_misses_total {misses}\n"
+    )
+}
+
+/// Answers a single HTTP/1.1 request on `stream`, with the metrics for
+/// `GET /metrics` and `404 Not Found` otherwise, then closes the connection.
+async fn respond_with_metrics(stream: TcpStream, store_cache: &StoreCache) -> anyhow::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut request_line = String::new();
+    stream.read_line(&mut request_line).await?;
+    // The headers are read so that closing the connection does not reset it
+    let mut header = String::new();
+    while stream.read_line(&mut header).await? > 0 && !header.trim_end().is_empty() {
+        header.clear();
+    }
+
+    let response = if request_line.starts_with("GET /metrics ") {
+        let body = metrics_text(store_cache);
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned()
+    };
+    stream.get_mut().write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+async fn serve_metrics(listener: TcpListener, service: Arc<QueryService>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                warn!("Failed to accept a metrics connection: {err}");
+                continue;
+            }
+        };
+        let service = Arc::clone(&service);
+        tokio::spawn(async move {
+            if let Err(err) = respond_with_metrics(stream, &service.store_cache).await {
+                debug!("Failed to serve metrics: {err:#}");
+            }
+        });
+    }
+}
+
+pub(crate) async fn run(
+    Args {
+        endpoint,
+        listen,
+        max_store_staleness,
+        metrics_listen,
+    }: Args,
+) -> anyhow::Result<()> {
+    let service = Arc::new(QueryService {
+        rpc_client: RpcClient::new(endpoint),
+        store_cache: StoreCache::new(max_store_staleness),
+    });
+
+    if let Some(metrics_listen) = metrics_listen {
+        let listener = TcpListener::bind(metrics_listen).await?;
+        info!("Serving IBC store cache metrics on http://{metrics_listen}/metrics");
+        tokio::spawn(serve_metrics(listener, Arc::clone(&service)));
+    }
+
+    info!("Serving IBC queries on {listen}");
+    Server::builder()
+        .add_service(ClientQueryServer::from_arc(Arc::clone(&service)))
+        .add_service(ConnectionQueryServer::from_arc(Arc::clone(&service)))
+        .add_service(ChannelQueryServer::from_arc(service))
+        .serve_with_shutdown(listen, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tokio::io::AsyncReadExt};
+
+    #[tokio::test]
+    async fn metrics_are_served_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = Arc::new(QueryService {
+            rpc_client: RpcClient::new("http://127.0.0.1:1".to_owned()),
+            store_cache: StoreCache::new(Duration::ZERO),
+        });
+        tokio::spawn(serve_metrics(listener, service));
+
+        let get = |path: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        let response = get("/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\r\n\r\n# HELP eclipse_ibc_store_cache_hits_total "));
+        assert!(response.ends_with("eclipse_ibc_store_cache_misses_total 0\n"));
+        assert!(get("/").await.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
@@ -0,0 +1,259 @@
+use {
+    crate::chain_state,
+    eclipse_ibc_state::{IbcAccountData, IbcMetadata, IbcStore},
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::account::Account,
+    std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex, MutexGuard, PoisonError,
+        },
+        time::{Duration, Instant},
+    },
+};
+
+/// Reads of the storage account that [`StoreCache`] makes, so that tests can
+/// stand in for the RPC node.
+#[tonic::async_trait]
+pub(crate) trait ChainReader: Sync {
+    /// The storage account with its data cut to the header and metadata.
+    async fn get_storage_account_head(&self) -> anyhow::Result<Account>;
+
+    /// The storage account with all of its data.
+    async fn get_storage_account(&self) -> anyhow::Result<Account>;
+}
+
+#[tonic::async_trait]
+impl ChainReader for RpcClient {
+    async fn get_storage_account_head(&self) -> anyhow::Result<Account> {
+        chain_state::get_storage_account_head(self).await
+    }
+
+    async fn get_storage_account(&self) -> anyhow::Result<Account> {
+        Ok(self.get_account(&eclipse_ibc_program::STORAGE_KEY).await?)
+    }
+}
+
+/// An IBC store decoded from the storage account, along with the metadata read
+/// with it, so that a response built from both describes a single version.
+pub(crate) struct StoreSnapshot {
+    pub(crate) store: IbcStore,
+    pub(crate) metadata: IbcMetadata,
+}
+
+/// What tells storage account data apart without fetching all of it: its
+/// lamports, which change as the account is resized, and its header and
+/// metadata, whose checksum changes with every commit that changes the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct AccountProbe {
+    lamports: u64,
+    head: Vec<u8>,
+}
+
+impl AccountProbe {
+    /// The probe of `account`, whose data may be cut to the head. `None` for data
+    /// in the legacy layout, which has its metadata after the store.
+    fn of(account: &Account) -> anyhow::Result<Option<Self>> {
+        let head = IbcAccountData::metadata_end(&account.data)?
+            .and_then(|metadata_end| account.data.get(..metadata_end));
+        Ok(head.map(|head| Self {
+            lamports: account.lamports,
+            head: head.to_vec(),
+        }))
+    }
+}
+
+/// The last snapshot decoded from the storage account, along with the probe of
+/// the account data it was decoded from.
+struct CachedSnapshot {
+    probe: AccountProbe,
+    decoded_at: Instant,
+    snapshot: Arc<StoreSnapshot>,
+}
+
+/// Reuses the decoded IBC store while the storage account data is unchanged, so
+/// that repeated reads skip fetching the store, decoding it and checking its
+/// root, which dominate for multi-megabyte accounts. Whether the data changed is
+/// told by fetching only its header and metadata.
+pub(crate) struct StoreCache {
+    /// Decode the store again after this long even if the data is unchanged,
+    /// which also picks up commits that left the root as it was.
+    max_staleness: Duration,
+    cached: Mutex<Option<CachedSnapshot>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl StoreCache {
+    pub(crate) fn new(max_staleness: Duration) -> Self {
+        Self {
+            max_staleness,
+            cached: Mutex::new(None),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns a snapshot of the storage account read through `chain`, fetching
+    /// and decoding the whole account only if its probe differs from the cached
+    /// snapshot's or the cached snapshot is too old.
+    pub(crate) async fn get(&self, chain: &impl ChainReader) -> anyhow::Result<Arc<StoreSnapshot>> {
+        let probe = AccountProbe::of(&chain.get_storage_account_head().await?)?;
+        if let Some(snapshot) = probe.and_then(|probe| self.fresh_snapshot(&probe)) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(snapshot);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let account = chain.get_storage_account().await?;
+        let IbcAccountData {
+            store, metadata, ..
+        } = IbcAccountData::from_bytes_verified(&account.data)?;
+        let snapshot = Arc::new(StoreSnapshot { store, metadata });
+        // Keyed on the fetched data, which may be newer than the probed head
+        if let Some(probe) = AccountProbe::of(&account)? {
+            *self.lock() = Some(CachedSnapshot {
+                probe,
+                decoded_at: Instant::now(),
+                snapshot: Arc::clone(&snapshot),
+            });
+        }
+        Ok(snapshot)
+    }
+
+    /// Numbers of cache hits and misses so far.
+    pub(crate) fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    fn fresh_snapshot(&self, probe: &AccountProbe) -> Option<Arc<StoreSnapshot>> {
+        self.lock()
+            .as_ref()
+            .filter(|cached| {
+                cached.probe == *probe && cached.decoded_at.elapsed() < self.max_staleness
+            })
+            .map(|cached| Arc::clone(&cached.snapshot))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<CachedSnapshot>> {
+        // The cache only ever holds a fully decoded snapshot, so it is still
+        // usable if another thread panicked while holding the lock
+        self.cached.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        eclipse_ibc_state::{internal_path::StateInitializedPath, IbcState},
+        std::sync::atomic::AtomicUsize,
+    };
+
+    /// A storage account whose data the tests change, counting full fetches.
+    struct MockChain {
+        account: Mutex<Account>,
+        full_fetches: AtomicUsize,
+    }
+
+    impl MockChain {
+        fn new(data: Vec<u8>) -> Self {
+            Self {
+                account: Mutex::new(Account {
+                    lamports: 1,
+                    data,
+                    ..Account::default()
+                }),
+                full_fetches: AtomicUsize::new(0),
+            }
+        }
+
+        fn set_data(&self, data: Vec<u8>) {
+            self.account.lock().unwrap().data = data;
+        }
+
+        fn full_fetches(&self) -> usize {
+            self.full_fetches.load(Ordering::Relaxed)
+        }
+    }
+
+    #[tonic::async_trait]
+    impl ChainReader for MockChain {
+        async fn get_storage_account_head(&self) -> anyhow::Result<Account> {
+            let mut account = self.account.lock().unwrap().clone();
+            let metadata_end = IbcAccountData::metadata_end(&account.data)?;
+            account.data.truncate(metadata_end.unwrap_or(0));
+            Ok(account)
+        }
+
+        async fn get_storage_account(&self) -> anyhow::Result<Account> {
+            self.full_fetches.fetch_add(1, Ordering::Relaxed);
+            Ok(self.account.lock().unwrap().clone())
+        }
+    }
+
+    fn account_data(version: u64) -> Vec<u8> {
+        let mut account_data = IbcAccountData::default();
+        let mut state = IbcState::new(&account_data.store, version);
+        state.set(&StateInitializedPath, ());
+        let root_hash = state.commit().unwrap();
+        account_data.metadata.record_commit(root_hash);
+        account_data.to_bytes().unwrap()
+    }
+
+    fn latest_version(snapshot: &StoreSnapshot) -> Option<u64> {
+        snapshot.store.read().latest_version()
+    }
+
+    #[tokio::test]
+    async fn unchanged_data_is_fetched_once() {
+        let cache = StoreCache::new(Duration::from_secs(60));
+        let chain = MockChain::new(account_data(1));
+        let first = cache.get(&chain).await.unwrap();
+        let second = cache.get(&chain).await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.stats(), (1, 1));
+        assert_eq!(chain.full_fetches(), 1);
+
+        chain.set_data(account_data(2));
+        let changed = cache.get(&chain).await.unwrap();
+        assert_eq!(latest_version(&changed), Some(2));
+        assert_eq!(cache.stats(), (1, 2));
+        assert_eq!(chain.full_fetches(), 2);
+
+        // Resizing the account changes its lamports
+        chain.account.lock().unwrap().lamports += 1;
+        cache.get(&chain).await.unwrap();
+        assert_eq!(chain.full_fetches(), 3);
+    }
+
+    #[tokio::test]
+    async fn stale_snapshots_are_fetched_again() {
+        let cache = StoreCache::new(Duration::ZERO);
+        let chain = MockChain::new(account_data(1));
+        let first = cache.get(&chain).await.unwrap();
+        let second = cache.get(&chain).await.unwrap();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.stats(), (0, 2));
+        assert_eq!(chain.full_fetches(), 2);
+    }
+
+    #[tokio::test]
+    async fn undecodable_data_is_not_cached() {
+        let cache = StoreCache::new(Duration::from_secs(60));
+        let data = account_data(1);
+        let chain = MockChain::new(data.clone());
+        cache.get(&chain).await.unwrap();
+        // A truncated store behind the same head
+        chain.set_data(data[..data.len() / 2].to_vec());
+        chain.account.lock().unwrap().lamports += 1;
+        cache.get(&chain).await.unwrap_err();
+        chain.set_data(data);
+        cache.get(&chain).await.unwrap();
+        assert_eq!(cache.stats(), (0, 3));
+        assert_eq!(chain.full_fetches(), 3);
+    }
+}
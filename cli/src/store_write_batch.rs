@@ -0,0 +1,224 @@
+use {
+    crate::generate,
+    anyhow::{anyhow, bail, Context as _},
+    eclipse_ibc_known_proto::{KnownAnyProto, KnownProto},
+    eclipse_ibc_program::ibc_instruction::msgs::{MsgStoreWriteBatch, StoreWrite},
+    eclipse_ibc_state::{
+        internal_path::{
+            AdminPath, CapabilitiesPath, ChainParamsPath, PendingAdminChangePath, SlotTimePath,
+            StateInitializedPath, StoreWriteBatchCursorPath,
+        },
+        IbcAccountData, IbcState,
+    },
+    serde::Deserialize,
+    std::{
+        fs::{self, File},
+        io::BufReader,
+        path::Path,
+    },
+};
+
+/// Prefixes of the internal paths that track the tx buffers of an endpoint.
+const TX_BUFFER_PATH_PREFIXES: [&str; 2] = ["internal/txBuffers/", "internal/txBufferPayers/"];
+
+/// A store write as given in the JSON input file. `value` is hex-encoded, and a
+/// `null` value removes the entry.
+#[derive(Debug, Deserialize)]
+struct StoreWriteEntry {
+    path: String,
+    value: Option<String>,
+}
+
+pub(crate) fn read_store_writes(file: &Path) -> anyhow::Result<Vec<StoreWrite>> {
+    let entries: Vec<StoreWriteEntry> = serde_json::from_reader(BufReader::new(File::open(file)?))?;
+    entries
+        .into_iter()
+        .map(|StoreWriteEntry { path, value }| raw_entry_write(path, value.as_deref()))
+        .collect()
+}
+
+/// A store write setting the raw entry at `path` to the hex-encoded `value`, or
+/// removing the entry if there is no value.
+pub(crate) fn raw_entry_write(path: String, value: Option<&str>) -> anyhow::Result<StoreWrite> {
+    let value = value
+        .map(hex::decode)
+        .transpose()
+        .map_err(|err| anyhow!("invalid hex value for path {path}: {err}"))?;
+    Ok(StoreWrite { path, value })
+}
+
+/// Store writes importing the latest IBC state of `snapshot`, a file holding the
+/// data of a storage account, as in [`genesis_writes`].
+pub(crate) fn read_genesis_writes(snapshot: &Path) -> anyhow::Result<Vec<StoreWrite>> {
+    let raw_account_data =
+        fs::read(snapshot).with_context(|| format!("Failed to read {}", snapshot.display()))?;
+    let IbcAccountData { store, .. } = IbcAccountData::from_bytes_verified(&raw_account_data)?;
+    genesis_writes(&generate::get_ibc_state(&store)?)
+}
+
+/// Store writes copying every entry of `source` into the endpoint they are applied
+/// to, except those describing the endpoint itself rather than its IBC state: its
+/// admin, chain params, capabilities, slot time, tx buffers and any batch in
+/// progress. Only
+/// paths whose preimages `source` recorded are copied.
+fn genesis_writes(source: &IbcState<'_>) -> anyhow::Result<Vec<StoreWrite>> {
+    let endpoint_paths = [
+        StateInitializedPath.to_string(),
+        AdminPath.to_string(),
+        PendingAdminChangePath.to_string(),
+        ChainParamsPath.to_string(),
+        CapabilitiesPath.to_string(),
+        SlotTimePath.to_string(),
+        StoreWriteBatchCursorPath.to_string(),
+    ];
+    source
+        .paths_with_prefix("")?
+        .into_iter()
+        .filter(|path| {
+            !endpoint_paths.contains(path)
+                && !TX_BUFFER_PATH_PREFIXES
+                    .iter()
+                    .any(|prefix| path.starts_with(prefix))
+        })
+        .map(|path| {
+            let value = source.read_raw_entry(&path)?;
+            Ok(StoreWrite { path, value })
+        })
+        .collect()
+}
+
+fn encoded_len(chunk: &MsgStoreWriteBatch) -> usize {
+    chunk.clone().encode_as_any().encode().len()
+}
+
+/// Splits `writes` into as few chunks as possible such that every encoded chunk is
+/// at most `max_instruction_size` bytes, and links the chunks together by hash so
+/// that the program only accepts them in order.
+pub(crate) fn plan_chunks(
+    writes: Vec<StoreWrite>,
+    max_instruction_size: usize,
+) -> anyhow::Result<Vec<MsgStoreWriteBatch>> {
+    if writes.is_empty() {
+        bail!("no store writes to apply");
+    }
+
+    // Every chunk is planned as if it had a next chunk hash, since it is only known
+    // once the following chunks are final.
+    let new_chunk = |chunk_index| MsgStoreWriteBatch {
+        chunk_index,
+        writes: vec![],
+        next_chunk_hash: Some([0; 32]),
+    };
+
+    let mut chunks = vec![];
+    let mut chunk = new_chunk(0);
+    for write in writes {
+        chunk.writes.push(write);
+        if encoded_len(&chunk) <= max_instruction_size {
+            continue;
+        }
+
+        let write = chunk.writes.pop().expect("write was just pushed");
+        if chunk.writes.is_empty() {
+            bail!(
+                "store write for path {} does not fit in a single instruction",
+                write.path
+            );
+        }
+        let next_chunk = new_chunk(chunk.chunk_index + 1);
+        chunks.push(std::mem::replace(&mut chunk, next_chunk));
+        chunk.writes.push(write);
+        if encoded_len(&chunk) > max_instruction_size {
+            bail!(
+                "store write for path {} does not fit in a single instruction",
+                chunk.writes[0].path
+            );
+        }
+    }
+    chunks.push(chunk);
+
+    let mut next_chunk_hash = None;
+    for chunk in chunks.iter_mut().rev() {
+        chunk.next_chunk_hash = next_chunk_hash;
+        next_chunk_hash = Some(chunk.chunk_hash());
+    }
+
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        eclipse_ibc_extra_types::ChainParams,
+        eclipse_ibc_state::{internal_path::TxBufferPayerPath, IbcStore},
+        ibc::core::{
+            ics04_channel::packet::Sequence,
+            ics24_host::{
+                identifier::{ChannelId, PortId},
+                path::SeqSendPath,
+            },
+        },
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    fn write(i: usize) -> StoreWrite {
+        StoreWrite {
+            path: format!("nextSequenceSend/ports/transfer/channels/channel-{i}"),
+            value: (i % 2 == 0).then(|| vec![8, i as u8]),
+        }
+    }
+
+    #[test]
+    fn chunks_fit_and_are_linked() {
+        let writes: Vec<_> = (0..50).map(write).collect();
+        let chunks = plan_chunks(writes.clone(), 300).unwrap();
+
+        assert!(chunks.len() > 1);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.chunk_index, i as u64);
+            assert!(encoded_len(chunk) <= 300);
+        }
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].next_chunk_hash, Some(pair[1].chunk_hash()));
+        }
+        assert_eq!(chunks.last().unwrap().next_chunk_hash, None);
+
+        let planned_writes: Vec<_> = chunks.into_iter().flat_map(|chunk| chunk.writes).collect();
+        assert_eq!(planned_writes, writes);
+    }
+
+    #[test]
+    fn genesis_imports_leave_the_endpoint_alone() {
+        let store = IbcStore::default();
+        let mut state = IbcState::new(&store, 0);
+        state.set(&StateInitializedPath, ());
+        state.set(&AdminPath, Pubkey::new_unique().to_string());
+        state.set(&ChainParamsPath, ChainParams::default());
+        state.set(
+            &TxBufferPayerPath(Pubkey::new_unique()),
+            Pubkey::new_unique().to_string(),
+        );
+        let seq_send_path = SeqSendPath(PortId::transfer(), ChannelId::default());
+        state.set(&seq_send_path, Sequence::from(3));
+        state.commit().unwrap();
+
+        assert_eq!(
+            genesis_writes(&IbcState::new(&store, 0)).unwrap(),
+            [StoreWrite {
+                path: seq_send_path.to_string(),
+                value: Some(Sequence::from(3).encode()),
+            }]
+        );
+    }
+
+    #[test]
+    fn oversized_write_is_rejected() {
+        let writes = vec![StoreWrite {
+            path: "clients/07-tendermint-0/clientState".to_owned(),
+            value: Some(vec![0; 1000]),
+        }];
+        assert!(plan_chunks(writes, 825).is_err());
+        assert!(plan_chunks(vec![], 825).is_err());
+    }
+}
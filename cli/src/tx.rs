@@ -1,26 +1,60 @@
 use {
+    crate::{
+        capabilities, chain_state,
+        confirmation::{self, TxOutcome},
+        duration, generate,
+        provenance::parse_provenances,
+        relay::backoff_delay,
+        store_write_batch,
+    },
     anyhow::anyhow,
-    borsh::BorshSerialize,
-    clap::{Parser, Subcommand},
+    clap::{ArgAction, Parser, Subcommand},
+    eclipse_ibc_client::{
+        build_admin_instruction, build_port_instruction, build_router_instruction,
+        split_large_instruction, InstructionAccounts, PlannedInstruction,
+        MAX_SINGLE_INSTRUCTION_SIZE,
+    },
+    eclipse_ibc_extra_types::{
+        ChainParams, ModuleAccountTemplates, PdaSeedTemplate, SeedPart, ValueSizeCap,
+    },
     eclipse_ibc_known_proto::{KnownAnyProto, KnownProto},
+    eclipse_ibc_light_client::{
+        eclipse_chain::EclipseHeight, EclipseConsensusState, EclipseHeader,
+    },
     eclipse_ibc_program::{
-        ibc_contract_instruction::IbcContractInstruction,
-        ibc_instruction::msgs::{
-            MsgBindPort, MsgInitStorageAccount, MsgReleasePort, MsgWriteTxBuffer,
-            MsgWriteTxBufferMode,
+        account_spec::{account_mismatches, ExpectedAccount, IBC_HANDLER_ACCOUNTS},
+        dry_run::{self, DryRun},
+        errors::{self, IbcProgramError},
+        ibc_instruction::{
+            msgs::{
+                MsgAbortStoreWriteBatch, MsgAcceptAdminChange, MsgBindPort, MsgCancelAdminChange,
+                MsgClaimAdmin, MsgCloseTxBuffer, MsgForceReleasePort, MsgInitStorageAccount,
+                MsgProposeAdminChange, MsgPruneState, MsgReallocStorageAccount,
+                MsgRefreshCapabilities, MsgReleasePort, MsgRepairIndices, MsgSetIbcFrozen,
+                MsgSetRevisionNumber, MsgTransferPort, MsgUpdateChainParams, MsgWriteUpgrade,
+                StoreWrite,
+            },
+            IbcInstruction,
         },
+        PRUNED_NODES_PER_INSTRUCTION, STORAGE_KEY,
+    },
+    eclipse_ibc_state::{index_rebuild_writes, IndexKind},
+    ibc::core::{
+        ics02_client::height::Height, ics23_commitment::commitment::CommitmentRoot,
+        ics24_host::identifier::PortId,
     },
-    ibc::core::ics24_host::identifier::PortId,
     ibc_proto::{
         google::protobuf,
         ibc::core::{
             channel::v1::{
+                MsgAcknowledgement as RawMsgAcknowledgement,
                 MsgChannelCloseConfirm as RawMsgChannelCloseConfirm,
                 MsgChannelCloseInit as RawMsgChannelCloseInit,
                 MsgChannelOpenAck as RawMsgChannelOpenAck,
                 MsgChannelOpenConfirm as RawMsgChannelOpenConfirm,
                 MsgChannelOpenInit as RawMsgChannelOpenInit,
-                MsgChannelOpenTry as RawMsgChannelOpenTry,
+                MsgChannelOpenTry as RawMsgChannelOpenTry, MsgRecvPacket as RawMsgRecvPacket,
+                MsgTimeout as RawMsgTimeout, MsgTimeoutOnClose as RawMsgTimeoutOnClose,
             },
             client::v1::{
                 MsgCreateClient as RawMsgCreateClient,
@@ -35,28 +69,38 @@ use {
             },
         },
     },
-    log::info,
-    serde::de::DeserializeOwned,
+    log::{info, warn},
+    serde::{de::DeserializeOwned, Serialize},
     solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig},
     solana_sdk::{
-        instruction::{AccountMeta, Instruction},
+        entrypoint::MAX_PERMITTED_DATA_INCREASE,
+        instruction::InstructionError,
         message::Message,
         pubkey::Pubkey,
+        signature::Signature,
         signer::{
             keypair::{read_keypair_file, Keypair},
             Signer as _,
         },
-        system_program,
-        sysvar::{clock, rent},
-        transaction::Transaction,
+        system_instruction,
+        transaction::{Transaction, TransactionError},
     },
     std::{
-        io::{self, BufReader},
-        path::PathBuf,
-        sync::Arc,
+        fmt::Write as _,
+        fs::{self, File},
+        io::{self, BufReader, Write as _},
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex, PoisonError,
+        },
+        time::Duration,
     },
 };
 
+/// Commitment root of upgraded consensus states, which ibc-go uses as well
+const UPGRADE_SENTINEL_ROOT: &[u8] = b"sentinel_root";
+
 // Setting `skip_preflight: true` lets us see `ic_msg` log messages for failed txs.
 const RPC_SEND_TRANSACTION_CONFIG: RpcSendTransactionConfig = RpcSendTransactionConfig {
     skip_preflight: true,
@@ -66,27 +110,450 @@ const RPC_SEND_TRANSACTION_CONFIG: RpcSendTransactionConfig = RpcSendTransaction
     min_context_slot: None,
 };
 
+/// Times a transaction rejected because a store write batch is being applied is
+/// resubmitted, with backoff, before giving up. Batches are applied by an admin
+/// one chunk per transaction, so this waits out a batch of a few dozen chunks.
+const MAX_MIGRATION_RETRIES: u32 = 8;
+
+/// Times a transaction dropped before landing is resent with a new blockhash
+/// before giving up.
+const MAX_DROPPED_RETRIES: u32 = 3;
+
 #[derive(Clone, Debug, Subcommand)]
 enum AdminTx {
-    InitStorageAccount,
+    /// Creates the IBC storage account, recording the endpoint's genesis hash
+    InitStorageAccount {
+        /// Succeed without changes if the storage account is already initialized
+        #[arg(long)]
+        idempotent: bool,
+    },
+    /// Applies raw store writes from a JSON file of `{"path", "value"}` entries, where
+    /// `value` is hex-encoded or `null` to remove the entry. The writes are split into
+    /// chunks that are each submitted in their own transaction.
+    StoreWriteBatch {
+        file: PathBuf,
+
+        /// Chunk to start from, for resuming a batch after a failed chunk
+        #[arg(long, default_value_t = 0)]
+        start_chunk: usize,
+    },
+    /// Drops the store write batch in progress, such as one whose remaining
+    /// chunks were lost, so that other messages are accepted again. Chunks
+    /// already applied stay applied.
+    AbortStoreWriteBatch,
+    /// Imports the IBC state of `snapshot`, a file holding the data of another
+    /// endpoint's storage account, such as one written by `solana account
+    /// <STORAGE_KEY> --output-file`, as a store write batch. The admin, chain
+    /// params, capabilities and tx buffers of this endpoint are kept.
+    ImportGenesis {
+        snapshot: PathBuf,
+
+        /// Chunk to start from, for resuming an import after a failed chunk
+        #[arg(long, default_value_t = 0)]
+        start_chunk: usize,
+    },
+    /// Overwrites the raw entry at `path` with the hex-encoded `value`, or
+    /// removes the entry if no value is given, as a store write batch. For
+    /// repairing entries that no message can fix.
+    RepairEntry { path: String, value: Option<String> },
+    /// Replaces the list of disabled message type URL prefixes. Passing no prefixes
+    /// re-enables every message.
+    SetDisabledMessages { prefixes: Vec<String> },
+    /// Sets the maximum number of unacknowledged packets per channel. Omitting the
+    /// limit removes it.
+    SetMaxInflightPackets { limit: Option<u64> },
+    /// Sets whether the program skips checking the IBC store against its latest
+    /// root when loading it
+    SetSkipRootCheckOnLoad {
+        #[arg(action = ArgAction::Set)]
+        skip: bool,
+    },
+    /// Sets whether the program logs error details and the messages ibc-rs logs
+    SetVerboseLogs {
+        #[arg(action = ArgAction::Set)]
+        verbose: bool,
+    },
+    /// Sets the largest tx buffer the program creates and the most tx buffer bytes
+    /// a payer may have open. An omitted limit goes back to its default.
+    SetTxBufferLimits {
+        #[arg(long)]
+        max_size: Option<u64>,
+
+        #[arg(long)]
+        max_bytes_per_payer: Option<u64>,
+    },
+    /// Sets the name this chain is known by, which connection handshakes check the
+    /// chain ID of its counterparty clients against. Omitting the name accepts any
+    /// Eclipse chain ID.
+    SetChainName { name: Option<String> },
+    /// Sets the most IBC events the event journal of the storage account keeps.
+    /// Omitting the capacity goes back to the default.
+    SetEventJournalCapacity { capacity: Option<u64> },
+    /// Sets the most consensus states kept per client, beyond which the oldest
+    /// ones are evicted. Omitting the count goes back to the default.
+    SetMaxConsensusStatesPerClient { count: Option<u64> },
+    /// Sets whether client messages with the legacy Eclipse type URLs that older
+    /// CLIs generate are rejected instead of rewritten. Check `query
+    /// legacy-type-url-count` has stopped growing before turning this on.
+    SetRejectLegacyTypeUrls {
+        #[arg(action = ArgAction::Set)]
+        reject: bool,
+    },
+    /// Sets the size in bytes above which values at internal paths of the IBC
+    /// state are stored compressed. Omitting the size stores new values
+    /// uncompressed, while already compressed ones still read back.
+    SetCompressValuesAbove { size: Option<u64> },
+    /// Sets the largest value an instruction may store at paths under
+    /// `path_prefix`. Omitting the size removes the cap on the prefix.
+    SetValueSizeCap {
+        path_prefix: String,
+        max_size: Option<u64>,
+    },
+    /// Sets the most bytes of values an instruction may store in total. Omitting
+    /// the size removes the limit.
+    SetMaxCommitBytes { size: Option<u64> },
+    /// Replaces the list of path prefixes no instruction may write to. Passing no
+    /// prefixes allows writes everywhere again.
+    SetForbiddenWritePrefixes { prefixes: Vec<String> },
+    /// Closes a tx buffer the payer created, such as one left behind by a failed
+    /// transaction
+    CloseTxBuffer { buffer: Pubkey },
+    /// Pauses all IBC activity until `unfreeze` is sent
+    Freeze,
+    /// Resumes IBC activity after `freeze`
+    Unfreeze,
+    /// Drops IBC state versions from before the last `retain_slots` slots, so that
+    /// the storage account stops growing. Host consensus states of the dropped
+    /// slots can no longer be proven to counterparties. Client update times and
+    /// heights whose connection delay windows have passed are dropped too. Each
+    /// message drops a bounded number of tree nodes, so as many are sent as the
+    /// nodes to drop take.
+    PruneState {
+        #[arg(long)]
+        retain_slots: u64,
+    },
+    /// Grows the storage account to `new_size` bytes, by at most 10 KiB at a time,
+    /// paying for the extra rent. Needed once the IBC state no longer fits.
+    ReallocStorageAccount {
+        #[arg(long)]
+        new_size: u64,
+    },
+    /// Proposes handing the admin authority to `new_admin`, such as a multisig
+    /// vault, which can accept it once the slot is past `effective_after_slot`.
+    /// Replaces any pending proposal.
+    ProposeAdminChange {
+        new_admin: Pubkey,
+
+        #[arg(long)]
+        effective_after_slot: u64,
+    },
+    /// Accepts the pending admin change, with the payer as the new admin
+    AcceptAdminChange,
+    /// Cancels the pending admin change before it is accepted
+    CancelAdminChange,
+    /// Makes the payer the admin of an endpoint that has none, such as one
+    /// initialized before the admin was recorded. The payer must be the upgrade
+    /// authority of the IBC program.
+    ClaimAdmin,
+    /// Stores the client state counterparty clients of this chain upgrade to once
+    /// they are updated to `upgrade_height`, e.g. `0-1000`, which must not be in
+    /// the past. The upgraded client tracks `chain_name` from the height after
+    /// the upgrade height, and needs an update before it verifies proofs again.
+    WriteUpgrade {
+        chain_name: String,
+
+        #[arg(long)]
+        upgrade_height: Height,
+    },
+    /// Rewrites the message families the program advertises with those of the
+    /// running program, after upgrading it
+    RefreshCapabilities,
+    /// Unbinds `port_id` even though its module still has open channels on it,
+    /// which keep calling back into that module
+    ForceReleasePort { port_id: PortId },
+    /// Moves this chain's IBC heights to `revision_number`, which must be past
+    /// the current revision. Clients of this chain follow with their next update.
+    SetRevisionNumber { revision_number: u64 },
+    /// Rebuilds the `kind` index, e.g. `client-connections`, from the primary
+    /// data it is derived from. Entries that disagree with that data are
+    /// rewritten in transactions of at most `max_writes` entries each.
+    RepairIndices {
+        kind: IndexKind,
+
+        #[arg(long, default_value_t = 32)]
+        max_writes: u32,
+    },
 }
 
 impl AdminTx {
-    fn encode_as_any(&self) -> protobuf::Any {
+    async fn encode_as_anys(&self, rpc_client: &RpcClient) -> anyhow::Result<Vec<protobuf::Any>> {
         match self {
-            Self::InitStorageAccount => MsgInitStorageAccount.encode_as_any(),
+            Self::InitStorageAccount { idempotent } => {
+                let genesis_hash = rpc_client.get_genesis_hash().await?;
+                Ok(vec![MsgInitStorageAccount {
+                    genesis_hash: Some(genesis_hash.to_bytes()),
+                    idempotent: *idempotent,
+                }
+                .encode_as_any()])
+            }
+            Self::StoreWriteBatch { file, start_chunk } => {
+                store_write_batch_chunks(store_write_batch::read_store_writes(file)?, *start_chunk)
+            }
+            Self::AbortStoreWriteBatch => Ok(vec![MsgAbortStoreWriteBatch.encode_as_any()]),
+            Self::ImportGenesis {
+                snapshot,
+                start_chunk,
+            } => store_write_batch_chunks(
+                store_write_batch::read_genesis_writes(snapshot)?,
+                *start_chunk,
+            ),
+            Self::RepairEntry { path, value } => store_write_batch_chunks(
+                vec![store_write_batch::raw_entry_write(
+                    path.clone(),
+                    value.as_deref(),
+                )?],
+                0,
+            ),
+            Self::SetDisabledMessages { prefixes } => {
+                // The whole params are replaced, so keep everything else as it is
+                let params = ChainParams {
+                    disabled_messages: prefixes.clone(),
+                    ..chain_state::get_chain_params(rpc_client).await?
+                };
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::SetMaxInflightPackets { limit } => {
+                let params = ChainParams {
+                    max_inflight_packets_per_channel: *limit,
+                    ..chain_state::get_chain_params(rpc_client).await?
+                };
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::SetSkipRootCheckOnLoad { skip } => {
+                let params = ChainParams {
+                    skip_root_check_on_load: *skip,
+                    ..chain_state::get_chain_params(rpc_client).await?
+                };
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::SetVerboseLogs { verbose } => {
+                let params = ChainParams {
+                    verbose_logs: *verbose,
+                    ..chain_state::get_chain_params(rpc_client).await?
+                };
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::SetTxBufferLimits {
+                max_size,
+                max_bytes_per_payer,
+            } => {
+                let params = ChainParams {
+                    max_tx_buffer_size: *max_size,
+                    max_tx_buffer_bytes_per_payer: *max_bytes_per_payer,
+                    ..chain_state::get_chain_params(rpc_client).await?
+                };
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::SetChainName { name } => {
+                let params = ChainParams {
+                    chain_name: name.clone(),
+                    ..chain_state::get_chain_params(rpc_client).await?
+                };
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::SetEventJournalCapacity { capacity } => {
+                let params = ChainParams {
+                    event_journal_capacity: *capacity,
+                    ..chain_state::get_chain_params(rpc_client).await?
+                };
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::SetMaxConsensusStatesPerClient { count } => {
+                let params = ChainParams {
+                    max_consensus_states_per_client: *count,
+                    ..chain_state::get_chain_params(rpc_client).await?
+                };
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::SetRejectLegacyTypeUrls { reject } => {
+                let params = ChainParams {
+                    reject_legacy_type_urls: *reject,
+                    ..chain_state::get_chain_params(rpc_client).await?
+                };
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::SetCompressValuesAbove { size } => {
+                let params = ChainParams {
+                    compress_values_above: *size,
+                    ..chain_state::get_chain_params(rpc_client).await?
+                };
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::SetValueSizeCap {
+                path_prefix,
+                max_size,
+            } => {
+                let mut params = chain_state::get_chain_params(rpc_client).await?;
+                params
+                    .value_size_caps
+                    .retain(|cap| cap.path_prefix != *path_prefix);
+                if let Some(max_size) = max_size {
+                    params.value_size_caps.push(ValueSizeCap {
+                        path_prefix: path_prefix.clone(),
+                        max_size: *max_size,
+                    });
+                }
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::SetMaxCommitBytes { size } => {
+                let params = ChainParams {
+                    max_commit_bytes: *size,
+                    ..chain_state::get_chain_params(rpc_client).await?
+                };
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::SetForbiddenWritePrefixes { prefixes } => {
+                let params = ChainParams {
+                    forbidden_write_prefixes: prefixes.clone(),
+                    ..chain_state::get_chain_params(rpc_client).await?
+                };
+                Ok(vec![MsgUpdateChainParams { params }.encode_as_any()])
+            }
+            Self::CloseTxBuffer { .. } => Ok(vec![MsgCloseTxBuffer.encode_as_any()]),
+            Self::Freeze => Ok(vec![MsgSetIbcFrozen { frozen: true }.encode_as_any()]),
+            Self::Unfreeze => Ok(vec![MsgSetIbcFrozen { frozen: false }.encode_as_any()]),
+            Self::PruneState { retain_slots } => {
+                // Each message drops the stalest nodes still left when it runs,
+                // so one message per `PRUNED_NODES_PER_INSTRUCTION` nodes found now
+                let slot = rpc_client.get_slot().await?;
+                let ibc_store = generate::get_ibc_store(rpc_client).await?;
+                let node_count = ibc_store.prunable_node_count(slot.saturating_sub(*retain_slots));
+                let num_msgs = ((node_count + PRUNED_NODES_PER_INSTRUCTION - 1)
+                    / PRUNED_NODES_PER_INSTRUCTION)
+                    .max(1);
+                let msg = MsgPruneState {
+                    retain_slots: *retain_slots,
+                };
+                Ok(vec![msg.encode_as_any(); num_msgs])
+            }
+            Self::ReallocStorageAccount { new_size } => Ok(vec![MsgReallocStorageAccount {
+                new_size: *new_size,
+            }
+            .encode_as_any()]),
+            Self::ProposeAdminChange {
+                new_admin,
+                effective_after_slot,
+            } => Ok(vec![MsgProposeAdminChange {
+                new_admin: *new_admin,
+                effective_after_slot: *effective_after_slot,
+            }
+            .encode_as_any()]),
+            Self::AcceptAdminChange => Ok(vec![MsgAcceptAdminChange.encode_as_any()]),
+            Self::CancelAdminChange => Ok(vec![MsgCancelAdminChange.encode_as_any()]),
+            Self::ClaimAdmin => Ok(vec![MsgClaimAdmin.encode_as_any()]),
+            Self::WriteUpgrade {
+                chain_name,
+                upgrade_height,
+            } => {
+                // As in ibc-go, the root of the upgraded consensus state is a
+                // sentinel, since the state at the upgraded height is not known yet
+                let upgraded_header = EclipseHeader {
+                    height: upgrade_height.succ()?,
+                    commitment_root: CommitmentRoot::from_bytes(UPGRADE_SENTINEL_ROOT),
+                    timestamp: chain_state::get_host_time(rpc_client)
+                        .await?
+                        .into_tm_time()
+                        .ok_or_else(|| anyhow!("Host time is unset"))?,
+                };
+                let genesis_hash = rpc_client.get_genesis_hash().await?;
+                let upgraded_client_state = chain_state::client_state_from_header(
+                    upgraded_header.clone(),
+                    chain_name,
+                    genesis_hash,
+                    chain_state::ClientStateParams::default(),
+                );
+                Ok(vec![MsgWriteUpgrade {
+                    upgrade_height: upgrade_height.revision_height(),
+                    upgraded_client_state: upgraded_client_state.encode_as_any(),
+                    upgraded_consensus_state: EclipseConsensusState::from(upgraded_header)
+                        .encode_as_any(),
+                }
+                .encode_as_any()])
+            }
+            Self::RefreshCapabilities => Ok(vec![MsgRefreshCapabilities.encode_as_any()]),
+            Self::ForceReleasePort { port_id } => Ok(vec![MsgForceReleasePort {
+                port_id: port_id.clone(),
+            }
+            .encode_as_any()]),
+            Self::SetRevisionNumber { revision_number } => Ok(vec![MsgSetRevisionNumber {
+                revision_number: *revision_number,
+            }
+            .encode_as_any()]),
+            Self::RepairIndices { kind, max_writes } => {
+                if *max_writes == 0 {
+                    return Err(anyhow!("--max-writes must be positive"));
+                }
+                // Each message repairs the first entries still out of date when
+                // it runs, so one message per `max_writes` entries found now
+                let ibc_store = generate::get_ibc_store(rpc_client).await?;
+                let writes = index_rebuild_writes(&generate::get_ibc_state(&ibc_store)?, *kind)?;
+                if writes.is_empty() {
+                    return Err(anyhow!("The {kind} index is already consistent"));
+                }
+                let max_writes_per_msg = usize::try_from(*max_writes)?;
+                let num_msgs = (writes.len() + max_writes_per_msg - 1) / max_writes_per_msg;
+                let msg = MsgRepairIndices {
+                    kind: *kind,
+                    max_writes: *max_writes,
+                };
+                Ok(vec![msg.encode_as_any(); num_msgs])
+            }
         }
     }
 }
 
-fn stdin_json_to_any<T>(
+/// Reads the JSON of a message from `input`, or from stdin if it is not given.
+/// The chunks of a store write batch applying `writes`, from `start_chunk` on.
+fn store_write_batch_chunks(
+    writes: Vec<StoreWrite>,
+    start_chunk: usize,
+) -> anyhow::Result<Vec<protobuf::Any>> {
+    let chunks = store_write_batch::plan_chunks(writes, MAX_SINGLE_INSTRUCTION_SIZE)?;
+    if start_chunk >= chunks.len() {
+        return Err(anyhow!(
+            "start chunk {start_chunk} is out of range; the batch has {} chunks",
+            chunks.len()
+        ));
+    }
+    Ok(chunks
+        .into_iter()
+        .skip(start_chunk)
+        .map(KnownAnyProto::encode_as_any)
+        .collect())
+}
+
+fn read_msg_json(input: Option<&Path>) -> anyhow::Result<serde_json::Value> {
+    match input {
+        Some(input) => Ok(serde_json::from_reader(BufReader::new(File::open(input)?))?),
+        None => Ok(serde_json::from_reader(BufReader::new(io::stdin()))?),
+    }
+}
+
+/// Reads a JSON array of messages, each of which is submitted on its own.
+fn read_msg_batch(batch: &Path) -> anyhow::Result<Vec<serde_json::Value>> {
+    serde_json::from_reader(BufReader::new(File::open(batch)?))
+        .map_err(|err| anyhow!("Batch file must hold a JSON array of messages: {err}"))
+}
+
+fn json_to_any<T>(
     type_url: &str,
+    json: serde_json::Value,
     modify_msg: impl FnOnce(&mut T),
 ) -> anyhow::Result<protobuf::Any>
 where
     T: DeserializeOwned + prost::Message,
 {
-    let mut msg = serde_json::from_reader(BufReader::new(io::stdin()))?;
+    let mut msg = serde_json::from_value(json)?;
     modify_msg(&mut msg);
 
     Ok(protobuf::Any {
@@ -106,44 +573,47 @@ enum ChannelTx {
 }
 
 impl ChannelTx {
-    fn encode_as_any(&self, signer: ibc::Signer) -> anyhow::Result<protobuf::Any> {
+    fn type_url(&self) -> &'static str {
         match self {
-            Self::OpenInit => stdin_json_to_any::<RawMsgChannelOpenInit>(
-                "/ibc.core.channel.v1.MsgChannelOpenInit",
-                |msg| {
-                    msg.signer = signer.to_string();
-                },
-            ),
-            Self::OpenTry => stdin_json_to_any::<RawMsgChannelOpenTry>(
-                "/ibc.core.channel.v1.MsgChannelOpenTry",
-                |msg| {
-                    msg.signer = signer.to_string();
-                },
-            ),
-            Self::OpenAck => stdin_json_to_any::<RawMsgChannelOpenAck>(
-                "/ibc.core.channel.v1.MsgChannelOpenAck",
-                |msg| {
-                    msg.signer = signer.to_string();
-                },
-            ),
-            Self::OpenConfirm => stdin_json_to_any::<RawMsgChannelOpenConfirm>(
-                "/ibc.core.channel.v1.MsgChannelOpenConfirm",
-                |msg| {
+            Self::OpenInit => "/ibc.core.channel.v1.MsgChannelOpenInit",
+            Self::OpenTry => "/ibc.core.channel.v1.MsgChannelOpenTry",
+            Self::OpenAck => "/ibc.core.channel.v1.MsgChannelOpenAck",
+            Self::OpenConfirm => "/ibc.core.channel.v1.MsgChannelOpenConfirm",
+            Self::CloseInit => "/ibc.core.channel.v1.MsgChannelCloseInit",
+            Self::CloseConfirm => "/ibc.core.channel.v1.MsgChannelCloseConfirm",
+        }
+    }
+
+    fn encode_as_any(
+        &self,
+        signer: ibc::Signer,
+        json: serde_json::Value,
+    ) -> anyhow::Result<protobuf::Any> {
+        match self {
+            Self::OpenInit => json_to_any::<RawMsgChannelOpenInit>(self.type_url(), json, |msg| {
+                msg.signer = signer.to_string();
+            }),
+            Self::OpenTry => json_to_any::<RawMsgChannelOpenTry>(self.type_url(), json, |msg| {
+                msg.signer = signer.to_string();
+            }),
+            Self::OpenAck => json_to_any::<RawMsgChannelOpenAck>(self.type_url(), json, |msg| {
+                msg.signer = signer.to_string();
+            }),
+            Self::OpenConfirm => {
+                json_to_any::<RawMsgChannelOpenConfirm>(self.type_url(), json, |msg| {
                     msg.signer = signer.to_string();
-                },
-            ),
-            Self::CloseInit => stdin_json_to_any::<RawMsgChannelCloseInit>(
-                "/ibc.core.channel.v1.MsgChannelCloseInit",
-                |msg| {
+                })
+            }
+            Self::CloseInit => {
+                json_to_any::<RawMsgChannelCloseInit>(self.type_url(), json, |msg| {
                     msg.signer = signer.to_string();
-                },
-            ),
-            Self::CloseConfirm => stdin_json_to_any::<RawMsgChannelCloseConfirm>(
-                "/ibc.core.channel.v1.MsgChannelCloseConfirm",
-                |msg| {
+                })
+            }
+            Self::CloseConfirm => {
+                json_to_any::<RawMsgChannelCloseConfirm>(self.type_url(), json, |msg| {
                     msg.signer = signer.to_string();
-                },
-            ),
+                })
+            }
         }
     }
 }
@@ -157,32 +627,35 @@ enum ClientTx {
 }
 
 impl ClientTx {
-    fn encode_as_any(&self, signer: ibc::Signer) -> anyhow::Result<protobuf::Any> {
+    fn type_url(&self) -> &'static str {
         match self {
-            Self::Create => stdin_json_to_any::<RawMsgCreateClient>(
-                "/ibc.core.client.v1.MsgCreateClient",
-                |msg| {
-                    msg.signer = signer.to_string();
-                },
-            ),
-            Self::Update => stdin_json_to_any::<RawMsgUpdateClient>(
-                "/ibc.core.client.v1.MsgUpdateClient",
-                |msg| {
-                    msg.signer = signer.to_string();
-                },
-            ),
-            Self::Misbehaviour => stdin_json_to_any::<RawMsgSubmitMisbehaviour>(
-                "/ibc.core.client.v1.MsgSubmitMisbehaviour",
-                |msg| {
-                    msg.signer = signer.to_string();
-                },
-            ),
-            Self::Upgrade => stdin_json_to_any::<RawMsgUpgradeClient>(
-                "/ibc.core.client.v1.MsgUpgradeClient",
-                |msg| {
+            Self::Create => "/ibc.core.client.v1.MsgCreateClient",
+            Self::Update => "/ibc.core.client.v1.MsgUpdateClient",
+            Self::Misbehaviour => "/ibc.core.client.v1.MsgSubmitMisbehaviour",
+            Self::Upgrade => "/ibc.core.client.v1.MsgUpgradeClient",
+        }
+    }
+
+    fn encode_as_any(
+        &self,
+        signer: ibc::Signer,
+        json: serde_json::Value,
+    ) -> anyhow::Result<protobuf::Any> {
+        match self {
+            Self::Create => json_to_any::<RawMsgCreateClient>(self.type_url(), json, |msg| {
+                msg.signer = signer.to_string();
+            }),
+            Self::Update => json_to_any::<RawMsgUpdateClient>(self.type_url(), json, |msg| {
+                msg.signer = signer.to_string();
+            }),
+            Self::Misbehaviour => {
+                json_to_any::<RawMsgSubmitMisbehaviour>(self.type_url(), json, |msg| {
                     msg.signer = signer.to_string();
-                },
-            ),
+                })
+            }
+            Self::Upgrade => json_to_any::<RawMsgUpgradeClient>(self.type_url(), json, |msg| {
+                msg.signer = signer.to_string();
+            }),
         }
     }
 }
@@ -197,53 +670,141 @@ enum ConnectionTx {
 }
 
 impl ConnectionTx {
-    fn encode_as_any(&self, signer: ibc::Signer) -> anyhow::Result<protobuf::Any> {
+    fn type_url(&self) -> &'static str {
         match self {
-            Self::OpenInit => stdin_json_to_any::<RawMsgConnectionOpenInit>(
-                "/ibc.core.connection.v1.MsgConnectionOpenInit",
-                |msg| {
-                    msg.signer = signer.to_string();
-                },
-            ),
-            Self::OpenTry => stdin_json_to_any::<RawMsgConnectionOpenTry>(
-                "/ibc.core.connection.v1.MsgConnectionOpenTry",
-                |msg| {
+            Self::OpenInit => "/ibc.core.connection.v1.MsgConnectionOpenInit",
+            Self::OpenTry => "/ibc.core.connection.v1.MsgConnectionOpenTry",
+            Self::OpenAck => "/ibc.core.connection.v1.MsgConnectionOpenAck",
+            Self::OpenConfirm => "/ibc.core.connection.v1.MsgConnectionOpenConfirm",
+        }
+    }
+
+    fn encode_as_any(
+        &self,
+        signer: ibc::Signer,
+        json: serde_json::Value,
+    ) -> anyhow::Result<protobuf::Any> {
+        match self {
+            Self::OpenInit => {
+                json_to_any::<RawMsgConnectionOpenInit>(self.type_url(), json, |msg| {
                     msg.signer = signer.to_string();
-                },
-            ),
-            Self::OpenAck => stdin_json_to_any::<RawMsgConnectionOpenAck>(
-                "/ibc.core.connection.v1.MsgConnectionOpenAck",
-                |msg| {
+                })
+            }
+            Self::OpenTry => json_to_any::<RawMsgConnectionOpenTry>(self.type_url(), json, |msg| {
+                msg.signer = signer.to_string();
+            }),
+            Self::OpenAck => json_to_any::<RawMsgConnectionOpenAck>(self.type_url(), json, |msg| {
+                msg.signer = signer.to_string();
+            }),
+            Self::OpenConfirm => {
+                json_to_any::<RawMsgConnectionOpenConfirm>(self.type_url(), json, |msg| {
                     msg.signer = signer.to_string();
-                },
-            ),
-            Self::OpenConfirm => stdin_json_to_any::<RawMsgConnectionOpenConfirm>(
-                "/ibc.core.connection.v1.MsgConnectionOpenConfirm",
-                |msg| {
+                })
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Subcommand)]
+enum PacketTx {
+    Recv,
+    Ack,
+    Timeout,
+    TimeoutOnClose,
+}
+
+impl PacketTx {
+    fn type_url(&self) -> &'static str {
+        match self {
+            Self::Recv => "/ibc.core.channel.v1.MsgRecvPacket",
+            Self::Ack => "/ibc.core.channel.v1.MsgAcknowledgement",
+            Self::Timeout => "/ibc.core.channel.v1.MsgTimeout",
+            Self::TimeoutOnClose => "/ibc.core.channel.v1.MsgTimeoutOnClose",
+        }
+    }
+
+    fn encode_as_any(
+        &self,
+        signer: ibc::Signer,
+        json: serde_json::Value,
+    ) -> anyhow::Result<protobuf::Any> {
+        match self {
+            Self::Recv => json_to_any::<RawMsgRecvPacket>(self.type_url(), json, |msg| {
+                msg.signer = signer.to_string();
+            }),
+            Self::Ack => json_to_any::<RawMsgAcknowledgement>(self.type_url(), json, |msg| {
+                msg.signer = signer.to_string();
+            }),
+            Self::Timeout => json_to_any::<RawMsgTimeout>(self.type_url(), json, |msg| {
+                msg.signer = signer.to_string();
+            }),
+            Self::TimeoutOnClose => {
+                json_to_any::<RawMsgTimeoutOnClose>(self.type_url(), json, |msg| {
                     msg.signer = signer.to_string();
-                },
-            ),
+                })
+            }
         }
     }
 }
 
 #[derive(Clone, Debug, Subcommand)]
 enum PortTx {
-    Bind { port_id: PortId },
+    Bind {
+        port_id: PortId,
+        /// Seeds of a PDA of the module's program that is passed to its callbacks,
+        /// separated by commas. `{port}`, `{channel}` and `{sequence}` are filled in
+        /// from the callback; anything else is a literal seed. May be repeated.
+        #[arg(long = "account-template", value_parser = parse_account_template)]
+        account_templates: Vec<PdaSeedTemplate>,
+    },
+    /// Unbinds a port bound by the payer's program, which is refused while the
+    /// program has open channels on it unless the admin sends
+    /// `admin force-release-port`
     Release { port_id: PortId },
+    /// Hands a port bound by the payer's program to another program
+    Transfer {
+        port_id: PortId,
+        new_module_pubkey: Pubkey,
+    },
+}
+
+fn parse_account_template(template: &str) -> anyhow::Result<PdaSeedTemplate> {
+    let seeds = template
+        .split(',')
+        .map(|seed| match seed {
+            "{port}" => SeedPart::PortId,
+            "{channel}" => SeedPart::ChannelId,
+            "{sequence}" => SeedPart::Sequence,
+            literal => SeedPart::Literal(literal.as_bytes().to_vec()),
+        })
+        .collect();
+    // Round trip through the raw message to apply the same checks as the program
+    PdaSeedTemplate::decode(&*PdaSeedTemplate { seeds }.encode())
 }
 
 impl PortTx {
-    fn encode_as_any(&self) -> protobuf::Any {
+    fn encode_as_any(&self) -> anyhow::Result<protobuf::Any> {
         match self {
-            Self::Bind { port_id } => MsgBindPort {
+            Self::Bind {
+                port_id,
+                account_templates,
+            } => Ok(MsgBindPort {
+                port_id: port_id.clone(),
+                account_templates: ModuleAccountTemplates::new(account_templates.clone())?,
+            }
+            .encode_as_any()),
+            Self::Release { port_id } => Ok(MsgReleasePort {
                 port_id: port_id.clone(),
             }
-            .encode_as_any(),
-            Self::Release { port_id } => MsgReleasePort {
+            .encode_as_any()),
+            Self::Transfer {
+                port_id,
+                new_module_pubkey,
+            } => Ok(MsgTransferPort {
                 port_id: port_id.clone(),
+                new_module_pubkey: *new_module_pubkey,
             }
-            .encode_as_any(),
+            .encode_as_any()),
         }
     }
 }
@@ -259,116 +820,173 @@ enum TxKind {
     #[command(subcommand)]
     Connection(ConnectionTx),
     #[command(subcommand)]
+    Packet(PacketTx),
+    #[command(subcommand)]
     Port(PortTx),
 }
 
 impl TxKind {
-    fn encode_as_any(&self, signer: ibc::Signer) -> anyhow::Result<protobuf::Any> {
+    /// Type URL of the messages of a kind that is given as JSON.
+    fn json_type_url(&self) -> Option<&'static str> {
         match self {
-            Self::Admin(tx) => Ok(tx.encode_as_any()),
-            Self::Channel(tx) => tx.encode_as_any(signer),
-            Self::Client(tx) => tx.encode_as_any(signer),
-            Self::Connection(tx) => tx.encode_as_any(signer),
-            Self::Port(tx) => Ok(tx.encode_as_any()),
+            Self::Channel(tx) => Some(tx.type_url()),
+            Self::Client(tx) => Some(tx.type_url()),
+            Self::Connection(tx) => Some(tx.type_url()),
+            Self::Packet(tx) => Some(tx.type_url()),
+            Self::Admin(_) | Self::Port(_) => None,
         }
     }
 
-    fn instruction_data(&self, payer_key: Pubkey) -> anyhow::Result<Vec<u8>> {
-        let signer = payer_key.to_string().into();
-        Ok(self.encode_as_any(signer)?.encode())
+    /// Encodes a message of a kind that is given as JSON, filling in the signer.
+    fn encode_json_as_any(
+        &self,
+        signer: ibc::Signer,
+        json: serde_json::Value,
+    ) -> anyhow::Result<protobuf::Any> {
+        match self {
+            Self::Channel(tx) => tx.encode_as_any(signer, json),
+            Self::Client(tx) => tx.encode_as_any(signer, json),
+            Self::Connection(tx) => tx.encode_as_any(signer, json),
+            Self::Packet(tx) => tx.encode_as_any(signer, json),
+            Self::Admin(_) | Self::Port(_) => Err(anyhow!(
+                "Only channel, client, connection and packet messages are read as JSON"
+            )),
+        }
     }
 
-    fn accounts(&self, payer_key: Pubkey) -> Vec<AccountMeta> {
+    /// Most transaction kinds encode to a single IBC instruction, but admin batches
+    /// can span several, which must be submitted in order. Messages given as JSON
+    /// are read from `input`, or from stdin if it is not given.
+    async fn encode_as_anys(
+        &self,
+        signer: ibc::Signer,
+        rpc_client: &RpcClient,
+        input: Option<&Path>,
+    ) -> anyhow::Result<Vec<protobuf::Any>> {
         match self {
-            Self::Admin(_) => vec![
-                AccountMeta::new_readonly(payer_key, true),
-                AccountMeta::new(eclipse_ibc_program::STORAGE_KEY, false),
-                AccountMeta::new_readonly(rent::id(), false),
-                AccountMeta::new_readonly(clock::id(), false),
-                AccountMeta::new_readonly(system_program::id(), false),
-            ],
-            Self::Channel(_) | Self::Client(_) | Self::Connection(_) | Self::Port(_) => {
-                vec![
-                    AccountMeta::new_readonly(payer_key, true),
-                    AccountMeta::new(eclipse_ibc_program::STORAGE_KEY, false),
-                    AccountMeta::new_readonly(clock::id(), false),
-                ]
+            Self::Admin(tx) => tx.encode_as_anys(rpc_client).await,
+            Self::Channel(_) | Self::Client(_) | Self::Connection(_) | Self::Packet(_) => {
+                Ok(vec![self.encode_json_as_any(signer, read_msg_json(input)?)?])
             }
+            Self::Port(tx) => Ok(vec![tx.encode_as_any()?]),
         }
     }
+
+    /// Plans the transactions running `msg`, a message of this kind, splitting it
+    /// across a new tx buffer if it does not fit in one instruction.
+    fn plan_txs(&self, msg: protobuf::Any, payer: &Arc<Keypair>) -> anyhow::Result<Vec<PlannedTx>> {
+        let payer_key = payer.pubkey();
+        let buffer_keypair = Arc::new(Keypair::new());
+        let buffer_key = buffer_keypair.pubkey();
+        let planned_instructions = match self {
+            Self::Admin(AdminTx::CloseTxBuffer { buffer }) => split_large_instruction(
+                msg.encode(),
+                payer_key,
+                buffer_key,
+                InstructionAccounts::CloseTxBuffer { buffer: *buffer },
+            ),
+            Self::Admin(_) => build_admin_instruction(msg, payer_key, buffer_key),
+            Self::Port(_) => build_port_instruction(msg, payer_key, buffer_key),
+            Self::Channel(_) | Self::Client(_) | Self::Connection(_) | Self::Packet(_) => {
+                build_router_instruction(msg, payer_key, buffer_key)
+            }
+        }?;
+        Ok(planned_txs(planned_instructions, payer, &buffer_keypair))
+    }
 }
 
-const MAX_SINGLE_INSTRUCTION_SIZE: usize = 825;
+/// A transaction to submit, along with the accounts the program expects in its
+/// single instruction.
+struct PlannedTx {
+    message: Message,
+    keypairs: Vec<Arc<Keypair>>,
+    account_spec: &'static [ExpectedAccount],
+    account_offset: usize,
+}
 
-async fn split_ibc_instruction_across_txs(
-    mut ibc_instruction_data: Vec<u8>,
-    payer: &Arc<Keypair>,
-    kind: &TxKind,
-) -> anyhow::Result<Vec<(Message, Vec<Arc<Keypair>>)>> {
-    let payer_key = payer.pubkey();
+impl PlannedTx {
+    /// Lists the accounts of the instruction next to the accounts the program
+    /// expects at each position.
+    fn annotated_accounts(&self) -> String {
+        let instruction = &self.message.instructions[0];
+        let keys = instruction
+            .accounts
+            .iter()
+            .map(|&index| self.message.account_keys[usize::from(index)])
+            .collect::<Vec<_>>();
+        let mismatches = account_mismatches(self.account_spec, &keys, self.account_offset);
 
-    let mut messages = vec![];
-    let mut buffer_pubkeys = vec![];
-    while ibc_instruction_data.len() > MAX_SINGLE_INSTRUCTION_SIZE {
-        let new_ibc_instruction_data = ibc_instruction_data.split_off(MAX_SINGLE_INSTRUCTION_SIZE);
-        let split_instruction_data = ibc_instruction_data;
-        ibc_instruction_data = new_ibc_instruction_data;
-
-        let to_keypair = Keypair::new();
-        let to_pubkey = to_keypair.pubkey();
-
-        buffer_pubkeys.push(to_pubkey);
-
-        // TODO: Create a bigger buffer and write to it multiple times, instead of creating
-        // a new buffer for each chunk.
-        let ibc_instruction_data = MsgWriteTxBuffer {
-            mode: MsgWriteTxBufferMode::Create {
-                buffer_size: MAX_SINGLE_INSTRUCTION_SIZE.try_into()?,
-            },
-            data: split_instruction_data,
+        let mut annotated = String::new();
+        for (offset, (key, &index)) in keys.iter().zip(&instruction.accounts).enumerate() {
+            let index = usize::from(index);
+            let flags = match (
+                self.message.is_signer(index),
+                self.message.is_writable(index),
+            ) {
+                (true, true) => "signer, writable",
+                (true, false) => "signer",
+                (false, true) => "writable",
+                (false, false) => "readonly",
+            };
+            let expected = match offset.checked_sub(self.account_offset) {
+                None => "instruction buffer".to_owned(),
+                Some(position) => match self.account_spec.get(position) {
+                    Some(ExpectedAccount {
+                        name,
+                        key: Some(expected_key),
+                    }) => format!("{name} ({expected_key})"),
+                    Some(ExpectedAccount { name, key: None }) => (*name).to_owned(),
+                    None if self.account_spec == IBC_HANDLER_ACCOUNTS => {
+                        "module callback account".to_owned()
+                    }
+                    None => "unexpected".to_owned(),
+                },
+            };
+            let mismatch = if mismatches.iter().any(|mismatch| mismatch.offset == offset) {
+                "  <- MISMATCH"
+            } else {
+                ""
+            };
+            writeln!(
+                annotated,
+                "{offset:>3}: {key} [{flags}] expected {expected}{mismatch}"
+            )
+            .expect("Writing to a String should not fail");
         }
-        .encode_as_any()
-        .encode();
-
-        let instruction_data = BorshSerialize::try_to_vec(&IbcContractInstruction {
-            extra_accounts_for_instruction: 0,
-            last_instruction_part: ibc_instruction_data,
-        })?;
-
-        let instructions = [Instruction::new_with_bytes(
-            eclipse_ibc_program::id(),
-            &instruction_data,
-            vec![
-                AccountMeta::new_readonly(payer_key, true),
-                AccountMeta::new(to_pubkey, true),
-                AccountMeta::new_readonly(rent::id(), false),
-                AccountMeta::new_readonly(system_program::id(), false),
-            ],
-        )];
-
-        let message = Message::new(&instructions, Some(&payer_key));
-        messages.push((message, vec![Arc::clone(payer), Arc::new(to_keypair)]));
+        annotated
     }
+}
 
-    let buffer_accounts = buffer_pubkeys
+/// Turns planned instructions into the transactions submitting them, each signed
+/// by `payer` and, where the instruction creates it, by the tx buffer.
+fn planned_txs(
+    planned_instructions: Vec<PlannedInstruction>,
+    payer: &Arc<Keypair>,
+    buffer_keypair: &Arc<Keypair>,
+) -> Vec<PlannedTx> {
+    let payer_key = payer.pubkey();
+    planned_instructions
         .into_iter()
-        .map(|buffer_pubkey| AccountMeta::new_readonly(buffer_pubkey, false))
-        .collect();
-
-    let instruction_data = BorshSerialize::try_to_vec(&IbcContractInstruction {
-        extra_accounts_for_instruction: messages.len(),
-        last_instruction_part: ibc_instruction_data,
-    })?;
-
-    let main_instruction = Instruction::new_with_bytes(
-        eclipse_ibc_program::id(),
-        &instruction_data,
-        [buffer_accounts, kind.accounts(payer_key)].concat(),
-    );
-    let main_message = Message::new(&[main_instruction], Some(&payer_key));
-    messages.push((main_message, vec![Arc::clone(payer)]));
-
-    Ok(messages)
+        .map(
+            |PlannedInstruction {
+                 instruction,
+                 buffer_signs,
+                 account_spec,
+                 account_offset,
+             }| {
+                let mut keypairs = vec![Arc::clone(payer)];
+                if buffer_signs {
+                    keypairs.push(Arc::clone(buffer_keypair));
+                }
+                PlannedTx {
+                    message: Message::new(&[instruction], Some(&payer_key)),
+                    keypairs,
+                    account_spec,
+                    account_offset,
+                }
+            },
+        )
+        .collect()
 }
 
 #[derive(Debug, Parser)]
@@ -381,18 +999,82 @@ pub(crate) struct Args {
     #[arg(long)]
     payer: Option<PathBuf>,
 
+    /// Print the accounts of each transaction next to the accounts the program
+    /// expects, instead of submitting the transactions
+    #[arg(long)]
+    check_accounts: bool,
+
+    /// Validate each channel, client, connection or packet message against the
+    /// current IBC state the way the program would, instead of signing and
+    /// submitting it. Callbacks into the programs of IBC modules are skipped.
+    #[arg(long, conflicts_with_all = ["batch", "check_accounts"])]
+    dry_run: bool,
+
+    /// Read the JSON of a channel, client, connection or packet message from this
+    /// file instead of stdin
+    #[arg(long, conflicts_with = "batch")]
+    input: Option<PathBuf>,
+
+    /// Read a JSON array of channel, client, connection or packet messages from
+    /// this file and submit each in its own transaction, in order. The signatures of the
+    /// submitted messages are printed as JSON.
+    #[arg(long)]
+    batch: Option<PathBuf>,
+
+    /// Index of the message of `--batch` to start from, for resuming a batch
+    /// after a failed message
+    #[arg(long, requires = "batch", default_value_t = 0)]
+    start_index: usize,
+
+    /// How long to wait for each transaction to be confirmed before reporting
+    /// that it may or may not have landed
+    #[arg(long, default_value = "60s", value_parser = duration::parse_duration)]
+    confirm_timeout: Duration,
+
+    /// Provenance written by `generate --provenance`, as one object or an array
+    /// for `--batch`. Before submitting, checks that the clients on this chain still
+    /// have the consensus states the proofs of the messages verify against, and are
+    /// still active, and that the messages have not expired.
+    #[arg(long)]
+    verify_against: Option<PathBuf>,
+
+    /// Submit messages even if their provenance in `--verify-against` says they
+    /// have expired
+    #[arg(long, requires = "verify_against")]
+    ignore_expiry: bool,
+
     /// Transaction kind
     #[command(subcommand)]
     kind: TxKind,
 }
 
-pub(crate) async fn run(
-    Args {
-        endpoint,
-        payer,
-        kind,
-    }: Args,
-) -> anyhow::Result<()> {
+/// Set from the global `--readonly` flag before any subcommand runs.
+static READONLY: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_readonly(readonly: bool) {
+    READONLY.store(readonly, Ordering::Relaxed);
+}
+
+/// Fails if the CLI is in readonly mode. Checked when reading the payer and again
+/// before sending, so that every command that signs transactions is covered
+/// without checking for the flag itself.
+fn ensure_not_readonly() -> anyhow::Result<()> {
+    if READONLY.load(Ordering::Relaxed) {
+        return Err(anyhow!(
+            "Refusing to sign or send transactions in readonly mode (--readonly or ECLIPSE_IBC_READONLY)"
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the payer keypair, defaulting to the Solana CLI's default keypair.
+pub(crate) fn read_payer(payer: Option<PathBuf>) -> anyhow::Result<Arc<Keypair>> {
+    ensure_not_readonly()?;
+    read_keypair(payer)
+}
+
+/// Reads the keypair at `payer`, or the Solana CLI's default keypair.
+fn read_keypair(payer: Option<PathBuf>) -> anyhow::Result<Arc<Keypair>> {
     let payer = match payer {
         Some(payer) => payer,
         None => {
@@ -402,36 +1084,722 @@ pub(crate) async fn run(
             keypair_path
         }
     };
-    let payer = Arc::new(
-        read_keypair_file(&payer)
-            .map_err(|err| anyhow!("Error reading keypair file: {:?}", err))?,
-    );
-    let rpc_client = RpcClient::new(endpoint);
+    Ok(Arc::new(read_keypair_file(&payer).map_err(|err| {
+        anyhow!("Error reading keypair file: {:?}", err)
+    })?))
+}
 
-    let messages =
-        split_ibc_instruction_across_txs(kind.instruction_data(payer.pubkey())?, &payer, &kind)
-            .await?;
+/// A transaction sent by [`send_planned_txs`], along with what became of it,
+/// which is `None` if it could not be sent or waited for.
+#[derive(Clone, Debug)]
+pub(crate) struct SentTx {
+    pub(crate) signature: Signature,
+    pub(crate) outcome: Option<TxOutcome>,
+}
 
-    info!("Submitting IBC txs: {kind:?}");
-    for (message, keypairs) in messages {
+/// Sends each transaction and waits up to `confirm_timeout` for it to be
+/// confirmed. Transactions dropped before landing are sent again with a new
+/// blockhash, while those whose fate is unknown by the deadline are reported
+/// rather than sent again, since they may still land. Each transaction sent is
+/// pushed to `sent` before it is sent, so that callers can account for
+/// transactions that failed but may still have been charged for.
+async fn send_planned_txs(
+    rpc_client: &RpcClient,
+    planned_txs: Vec<PlannedTx>,
+    confirm_timeout: Duration,
+    sent: &Mutex<Vec<SentTx>>,
+) -> anyhow::Result<()> {
+    ensure_not_readonly()?;
+    for PlannedTx {
+        message, keypairs, ..
+    } in planned_txs
+    {
         info!("Submitting message: {message:?}");
-        let blockhash = rpc_client.get_latest_blockhash().await?;
-
         let signers = keypairs
             .iter()
             .map(|keypair| &**keypair)
             .collect::<Vec<&Keypair>>();
-        let tx = Transaction::new(&signers, message, blockhash);
-        let sig = rpc_client
-            .send_and_confirm_transaction_with_spinner_and_config(
-                &tx,
-                rpc_client.commitment(),
-                RPC_SEND_TRANSACTION_CONFIG,
+
+        let mut attempt = 0;
+        let mut dropped = 0;
+        let mut funded_growth = false;
+        let sig = loop {
+            let (blockhash, last_valid_block_height) = rpc_client
+                .get_latest_blockhash_with_commitment(rpc_client.commitment())
+                .await?;
+            let tx = Transaction::new(&signers, message.clone(), blockhash);
+            let sig = tx.signatures[0];
+            sent.lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(SentTx {
+                    signature: sig,
+                    outcome: None,
+                });
+            rpc_client
+                .send_transaction_with_config(&tx, RPC_SEND_TRANSACTION_CONFIG)
+                .await?;
+            let outcome = confirmation::confirm_transaction(
+                rpc_client,
+                &sig,
+                last_valid_block_height,
+                confirm_timeout,
             )
-            .await?;
+            .await;
+            if let Some(sent_tx) = sent
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .last_mut()
+            {
+                sent_tx.outcome = Some(outcome.clone());
+            }
+
+            let tx_err = match outcome {
+                TxOutcome::Confirmed => break sig,
+                TxOutcome::Failed(tx_err) => tx_err,
+                TxOutcome::Dropped if dropped < MAX_DROPPED_RETRIES => {
+                    warn!("Transaction {sig} was dropped, resending it with a new blockhash");
+                    dropped += 1;
+                    continue;
+                }
+                TxOutcome::Dropped => {
+                    return Err(anyhow!(
+                        "Transaction {sig} was dropped without landing, as were the {dropped} \
+                         before it"
+                    ));
+                }
+                TxOutcome::Unknown => {
+                    return Err(anyhow!(
+                        "Transaction {sig} was not confirmed within {confirm_timeout:?}, but may \
+                         still land; check it with `solana confirm {sig}` before submitting the \
+                         message again"
+                    ));
+                }
+            };
+            match decode_program_error(&tx.message, &tx_err) {
+                Some((_, IbcProgramError::MigrationInProgress))
+                    if attempt < MAX_MIGRATION_RETRIES =>
+                {
+                    let delay = backoff_delay(attempt);
+                    warn!("A store write batch is being applied, retrying in {delay:?}");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Some((_, IbcProgramError::AccountFull)) if !funded_growth => {
+                    warn!("The storage account is full, paying its rent to grow it in place");
+                    fund_storage_growth(rpc_client, signers[0]).await?;
+                    funded_growth = true;
+                }
+                Some((code, program_err)) => {
+                    return Err(anyhow::Error::new(tx_err)
+                        .context(format!("IBC program error {code:#x}: {program_err}")));
+                }
+                None => {
+                    return Err(
+                        anyhow::Error::new(tx_err).context(format!("Transaction {sig} failed"))
+                    );
+                }
+            }
+        };
 
         info!("Submitted IBC tx: {sig}");
     }
+    Ok(())
+}
+
+/// Transfers the payer's lamports to the storage account, so that it stays
+/// rent-exempt after the program grows it in place by up to
+/// `MAX_PERMITTED_DATA_INCREASE` bytes. Growing it further takes
+/// `tx admin realloc-storage-account`.
+async fn fund_storage_growth(rpc_client: &RpcClient, payer: &Keypair) -> anyhow::Result<()> {
+    let storage_account = rpc_client.get_account(&STORAGE_KEY).await?;
+    let new_size = storage_account.data.len() + MAX_PERMITTED_DATA_INCREASE;
+    let top_up = rpc_client
+        .get_minimum_balance_for_rent_exemption(new_size)
+        .await?
+        .saturating_sub(storage_account.lamports);
+    if top_up == 0 {
+        return Err(anyhow!(
+            "The storage account is full and its rent already covers growing it in place; \
+             grow it further with `tx admin realloc-storage-account`"
+        ));
+    }
+    let blockhash = rpc_client.get_latest_blockhash().await?;
+    let tx = Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(
+            &payer.pubkey(),
+            &STORAGE_KEY,
+            top_up,
+        )],
+        Some(&payer.pubkey()),
+        &[payer],
+        blockhash,
+    );
+    let sig = rpc_client.send_and_confirm_transaction(&tx).await?;
+    info!("Paid {top_up} lamports of storage account rent: {sig}");
+    Ok(())
+}
+
+/// Decodes the custom error code of a failed IBC program instruction of
+/// `message`, whether the transaction failed in simulation or on chain. Codes of
+/// other programs' instructions mean something else, so they are left alone.
+fn decode_program_error(
+    message: &Message,
+    tx_err: &TransactionError,
+) -> Option<(u32, IbcProgramError)> {
+    match tx_err {
+        TransactionError::InstructionError(index, InstructionError::Custom(code)) => {
+            let instruction = message.instructions.get(usize::from(*index))?;
+            if *instruction.program_id(&message.account_keys) != eclipse_ibc_program::id() {
+                return None;
+            }
+            Some((*code, errors::decode(*code)?))
+        }
+        _ => None,
+    }
+}
+
+/// Submits an IBC message that runs against the IBC state, such as a packet
+/// message, splitting it across transactions if needed. The transactions sent
+/// are pushed to `sent`.
+pub(crate) async fn submit_ibc_msg(
+    rpc_client: &RpcClient,
+    payer: &Arc<Keypair>,
+    msg: protobuf::Any,
+    sent: &Mutex<Vec<SentTx>>,
+) -> anyhow::Result<()> {
+    let buffer_keypair = Arc::new(Keypair::new());
+    let planned_instructions =
+        build_router_instruction(msg, payer.pubkey(), buffer_keypair.pubkey())?;
+    let planned_txs = planned_txs(planned_instructions, payer, &buffer_keypair);
+    send_planned_txs(
+        rpc_client,
+        planned_txs,
+        confirmation::DEFAULT_CONFIRM_TIMEOUT,
+        sent,
+    )
+    .await
+}
+
+/// Signatures of the transactions that submitted a message of a batch, along
+/// with what became of each, including those dropped and sent again.
+#[derive(Debug, Serialize)]
+struct SubmittedMsg {
+    index: usize,
+    signatures: Vec<String>,
+    outcomes: Vec<Option<TxOutcome>>,
+}
+
+impl SubmittedMsg {
+    fn new(index: usize, sent: Vec<SentTx>) -> Self {
+        let (signatures, outcomes) = sent
+            .into_iter()
+            .map(|SentTx { signature, outcome }| (signature.to_string(), outcome))
+            .unzip();
+        Self {
+            index,
+            signatures,
+            outcomes,
+        }
+    }
+}
+
+/// Plans the transactions of each message of a batch, so that a message that
+/// cannot be encoded is reported before anything is sent.
+fn plan_batch(
+    kind: &TxKind,
+    payer: &Arc<Keypair>,
+    msgs: Vec<serde_json::Value>,
+) -> anyhow::Result<Vec<Vec<PlannedTx>>> {
+    let payer_key = payer.pubkey();
+    msgs.into_iter()
+        .enumerate()
+        .map(|(index, json)| {
+            kind.encode_json_as_any(payer_key.to_string().into(), json)
+                .and_then(|msg| kind.plan_txs(msg, payer))
+                .map_err(|err| err.context(format!("Invalid message {index} of the batch")))
+        })
+        .collect()
+}
+
+/// Submits each message of a batch in order, stopping at the first one that
+/// fails. The signatures of the messages submitted are printed either way.
+async fn run_batch(
+    rpc_client: &RpcClient,
+    payer: &Arc<Keypair>,
+    kind: &TxKind,
+    batch: &Path,
+    start_index: usize,
+    check_accounts: bool,
+    confirm_timeout: Duration,
+) -> anyhow::Result<()> {
+    let batch_txs = plan_batch(kind, payer, read_msg_batch(batch)?)?;
+    if start_index >= batch_txs.len() {
+        return Err(anyhow!(
+            "start index {start_index} is out of range; the batch has {} messages",
+            batch_txs.len()
+        ));
+    }
+    if let Some(type_url) = kind.json_type_url() {
+        capabilities::check_store(
+            "the endpoint",
+            generate::get_ibc_store(rpc_client),
+            &[type_url],
+        )
+        .await;
+    }
+
+    if check_accounts {
+        for (index, planned_txs) in batch_txs.iter().enumerate() {
+            for (tx_index, planned_tx) in planned_txs.iter().enumerate() {
+                writeln!(io::stdout(), "Message {index}, transaction {tx_index}:")?;
+                write!(io::stdout(), "{}", planned_tx.annotated_accounts())?;
+            }
+        }
+        return Ok(());
+    }
+
+    info!(
+        "Submitting messages {start_index} to {} of a batch of IBC messages: {kind:?}",
+        batch_txs.len() - 1
+    );
+    let mut submitted = vec![];
+    let mut result = Ok(());
+    for (index, planned_txs) in batch_txs.into_iter().enumerate().skip(start_index) {
+        let sent = Mutex::default();
+        let send_result = send_planned_txs(rpc_client, planned_txs, confirm_timeout, &sent).await;
+        let sent = sent.into_inner().unwrap_or_else(PoisonError::into_inner);
+        if let Err(err) = send_result {
+            result = Err(err.context(format!(
+                "Failed to submit message {index} of the batch; {}",
+                resume_hint(index, &sent)
+            )));
+            break;
+        }
+        submitted.push(SubmittedMsg::new(index, sent));
+    }
+    writeln!(io::stdout(), "{}", serde_json::to_string(&submitted)?)?;
+    result
+}
+
+/// How to resume a batch whose message `index` failed after sending `sent`.
+fn resume_hint(index: usize, sent: &[SentTx]) -> String {
+    match sent.last() {
+        Some(SentTx {
+            signature,
+            outcome: Some(TxOutcome::Unknown),
+        }) => format!(
+            "it may still land, so once {signature} is confirmed resume with --start-index {}, \
+             or if its blockhash expires without it landing with --start-index {index}",
+            index + 1
+        ),
+        _ => format!("resume with --start-index {index}"),
+    }
+}
+
+/// Checks the provenance in `path` against the live state of the chain at
+/// `rpc_client`, which the messages are about to be submitted to.
+/// Expired messages are refused unless `ignore_expiry` is set.
+async fn verify_provenance(
+    rpc_client: &RpcClient,
+    path: &Path,
+    ignore_expiry: bool,
+) -> anyhow::Result<()> {
+    let provenances = parse_provenances(&fs::read(path)?)?;
+    let ibc_store = generate::get_ibc_store(rpc_client).await?;
+    let ibc_state = generate::get_ibc_state(&ibc_store)?;
+    let now = chain_state::get_host_time(rpc_client).await?;
+    let now_unix = i64::try_from(now.nanoseconds() / 1_000_000_000)?;
+    for (index, provenance) in provenances.iter().enumerate() {
+        if let Err(err) = provenance.check_not_expired(now_unix) {
+            if !ignore_expiry {
+                return Err(err.context(format!(
+                    "Refusing to submit message {index}; pass --ignore-expiry to submit it anyway"
+                )));
+            }
+            warn!("Submitting message {index} anyway: {err}");
+        }
+        provenance.check_still_valid(&ibc_state, now)?;
+    }
+    info!(
+        "Proofs of {} message(s) still verify against their consensus states",
+        provenances.len()
+    );
+    Ok(())
+}
+
+/// Validates each router message against the current IBC state and prints
+/// whether the program would accept it. Messages are not executed, so each is
+/// validated against the same state rather than the state the messages before it
+/// would leave. Other messages are only reported as skipped.
+async fn dry_run_msgs(rpc_client: &RpcClient, msgs: Vec<protobuf::Any>) -> anyhow::Result<()> {
+    let mut account_data = chain_state::get_ibc_account_data(rpc_client).await?;
+    let clock = chain_state::get_clock(rpc_client).await?;
+
+    let mut failed = 0;
+    for msg in msgs {
+        let type_url = msg.type_url.clone();
+        let envelope = match IbcInstruction::try_from(msg) {
+            Ok(IbcInstruction::Router(envelope)) => envelope,
+            Ok(_) => {
+                writeln!(
+                    io::stdout(),
+                    "{type_url}: skipped, only router messages can be dry run"
+                )?;
+                continue;
+            }
+            Err(err) => {
+                failed += 1;
+                writeln!(io::stdout(), "{type_url}: {err}")?;
+                continue;
+            }
+        };
 
+        let DryRun {
+            result,
+            skipped_callbacks,
+        } = dry_run::validate_router_msg(&mut account_data, &clock, &type_url, envelope)?;
+        for callback in skipped_callbacks {
+            warn!("{type_url}: skipped the {callback}");
+        }
+        match result {
+            Ok(()) => writeln!(io::stdout(), "{type_url}: ok")?,
+            Err(err) => {
+                failed += 1;
+                writeln!(io::stdout(), "{type_url}: {:#}", anyhow::Error::new(err))?;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!("{failed} message(s) would be rejected"));
+    }
     Ok(())
 }
+
+pub(crate) async fn run(
+    Args {
+        endpoint,
+        payer,
+        check_accounts,
+        dry_run,
+        input,
+        batch,
+        start_index,
+        confirm_timeout,
+        verify_against,
+        ignore_expiry,
+        kind,
+    }: Args,
+) -> anyhow::Result<()> {
+    // A dry run signs nothing, so it is allowed in readonly mode
+    let payer = if dry_run {
+        read_keypair(payer)?
+    } else {
+        read_payer(payer)?
+    };
+    let rpc_client = RpcClient::new(endpoint);
+
+    if let Some(verify_against) = verify_against {
+        verify_provenance(&rpc_client, &verify_against, ignore_expiry).await?;
+    }
+
+    if let Some(batch) = batch {
+        return run_batch(
+            &rpc_client,
+            &payer,
+            &kind,
+            &batch,
+            start_index,
+            check_accounts,
+            confirm_timeout,
+        )
+        .await;
+    }
+
+    let signer: ibc::Signer = payer.pubkey().to_string().into();
+    let msgs = kind
+        .encode_as_anys(signer, &rpc_client, input.as_deref())
+        .await?;
+    // There are no capabilities to check before the storage account exists
+    if !matches!(kind, TxKind::Admin(AdminTx::InitStorageAccount { .. })) {
+        let type_urls = msgs
+            .iter()
+            .map(|msg| msg.type_url.as_str())
+            .collect::<Vec<_>>();
+        capabilities::check_store(
+            "the endpoint",
+            generate::get_ibc_store(&rpc_client),
+            &type_urls,
+        )
+        .await;
+    }
+
+    if dry_run {
+        return dry_run_msgs(&rpc_client, msgs).await;
+    }
+
+    let mut messages = vec![];
+    for msg in msgs {
+        messages.extend(kind.plan_txs(msg, &payer)?);
+    }
+
+    if check_accounts {
+        for (index, planned_tx) in messages.iter().enumerate() {
+            writeln!(io::stdout(), "Transaction {index}:")?;
+            write!(io::stdout(), "{}", planned_tx.annotated_accounts())?;
+        }
+        return Ok(());
+    }
+
+    info!("Submitting IBC txs: {kind:?}");
+    send_planned_txs(&rpc_client, messages, confirm_timeout, &Mutex::default()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        eclipse_ibc_program::account_spec::{
+            CLOSE_TX_BUFFER_ACCOUNTS, CREATE_TX_BUFFER_ACCOUNTS, REUSE_TX_BUFFER_ACCOUNTS,
+        },
+        solana_sdk::{
+            instruction::Instruction,
+            system_instruction,
+            sysvar::{clock, rent},
+        },
+    };
+
+    #[test]
+    fn messages_are_planned_with_the_accounts_of_their_kind() {
+        let payer = Arc::new(Keypair::new());
+        let buffer = Pubkey::new_unique();
+        for (kind, msg, accounts) in [
+            (
+                TxKind::Admin(AdminTx::InitStorageAccount { idempotent: false }),
+                MsgInitStorageAccount::default().encode_as_any(),
+                InstructionAccounts::InitStorageAccount,
+            ),
+            (
+                TxKind::Admin(AdminTx::Freeze),
+                MsgSetIbcFrozen { frozen: true }.encode_as_any(),
+                InstructionAccounts::IbcHandler,
+            ),
+            (
+                TxKind::Admin(AdminTx::ReallocStorageAccount { new_size: 20480 }),
+                MsgReallocStorageAccount { new_size: 20480 }.encode_as_any(),
+                InstructionAccounts::ReallocStorageAccount,
+            ),
+            (
+                TxKind::Admin(AdminTx::CloseTxBuffer { buffer }),
+                MsgCloseTxBuffer.encode_as_any(),
+                InstructionAccounts::CloseTxBuffer { buffer },
+            ),
+        ] {
+            let planned_txs = kind.plan_txs(msg, &payer).unwrap();
+            assert_eq!(planned_txs.len(), 1, "{kind:?}");
+            assert_eq!(planned_txs[0].account_spec, accounts.spec(), "{kind:?}");
+            assert_eq!(
+                instruction_keys(&planned_txs[0]),
+                accounts
+                    .metas(payer.pubkey())
+                    .iter()
+                    .map(|account| account.pubkey)
+                    .collect::<Vec<_>>(),
+                "{kind:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn swapped_accounts_are_annotated() {
+        let payer_key = Pubkey::new_unique();
+        let mut accounts = InstructionAccounts::InitStorageAccount.metas(payer_key);
+        accounts.swap(2, 3);
+        let planned_tx = PlannedTx {
+            message: Message::new(
+                &[Instruction::new_with_bytes(
+                    eclipse_ibc_program::id(),
+                    &[],
+                    accounts,
+                )],
+                Some(&payer_key),
+            ),
+            keypairs: vec![],
+            account_spec: InstructionAccounts::InitStorageAccount.spec(),
+            account_offset: 0,
+        };
+
+        let annotated = planned_tx.annotated_accounts();
+        let lines = annotated.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5, "{annotated}");
+        assert!(
+            lines[0].contains("[signer, writable] expected payer"),
+            "{annotated}"
+        );
+        assert!(!lines[1].contains("MISMATCH"), "{annotated}");
+        assert!(
+            lines[2].starts_with(&format!("  2: {}", clock::id())),
+            "{annotated}"
+        );
+        assert!(lines[2].ends_with(&format!(
+            "expected rent sysvar ({})  <- MISMATCH",
+            rent::id()
+        )));
+        assert!(lines[3].ends_with(&format!(
+            "expected clock sysvar ({})  <- MISMATCH",
+            clock::id()
+        )));
+    }
+
+    fn instruction_keys(planned_tx: &PlannedTx) -> Vec<Pubkey> {
+        planned_tx.message.instructions[0]
+            .accounts
+            .iter()
+            .map(|&index| planned_tx.message.account_keys[usize::from(index)])
+            .collect()
+    }
+
+    #[test]
+    fn only_the_tx_creating_the_buffer_is_signed_by_it() {
+        let payer = Arc::new(Keypair::new());
+        let msg = protobuf::Any {
+            type_url: "/test.Msg".to_owned(),
+            value: vec![7; 2 * MAX_SINGLE_INSTRUCTION_SIZE],
+        };
+        let planned_txs = TxKind::Packet(PacketTx::Recv)
+            .plan_txs(msg, &payer)
+            .unwrap();
+        let signers = planned_txs
+            .iter()
+            .map(|planned_tx| planned_tx.keypairs.len())
+            .collect::<Vec<_>>();
+        assert_eq!(signers, [2, 1, 1, 1]);
+
+        let buffer_key = planned_txs[0].keypairs[1].pubkey();
+        assert_eq!(instruction_keys(&planned_txs[0])[1], buffer_key);
+        assert_eq!(instruction_keys(&planned_txs[2])[0], buffer_key);
+        for planned_tx in &planned_txs {
+            assert_eq!(planned_tx.message.account_keys[0], payer.pubkey());
+            assert_eq!(
+                account_mismatches(
+                    planned_tx.account_spec,
+                    &instruction_keys(planned_tx),
+                    planned_tx.account_offset
+                ),
+                []
+            );
+        }
+    }
+
+    #[test]
+    fn custom_codes_of_ibc_instructions_are_decoded() {
+        let payer_key = Pubkey::new_unique();
+        let message = Message::new(
+            &[
+                system_instruction::transfer(&payer_key, &Pubkey::new_unique(), 1),
+                Instruction::new_with_bytes(
+                    eclipse_ibc_program::id(),
+                    &[],
+                    InstructionAccounts::IbcHandler.metas(payer_key),
+                ),
+            ],
+            Some(&payer_key),
+        );
+        let failed =
+            |index, code| TransactionError::InstructionError(index, InstructionError::Custom(code));
+
+        for err in IbcProgramError::ALL {
+            assert_eq!(
+                decode_program_error(&message, &failed(1, err.code())),
+                Some((err.code(), err))
+            );
+        }
+        // Custom codes of other programs mean something else
+        assert_eq!(decode_program_error(&message, &failed(0, 0xa9)), None);
+        assert_eq!(decode_program_error(&message, &failed(1, 0xffff)), None);
+        assert_eq!(
+            decode_program_error(&message, &TransactionError::AccountNotFound),
+            None
+        );
+    }
+
+    #[test]
+    fn batches_are_planned_per_message() {
+        let payer = Arc::new(Keypair::new());
+        let kind = TxKind::Channel(ChannelTx::CloseInit);
+        let close_init = |channel_id: &str| {
+            serde_json::json!({
+                "port_id": "transfer",
+                "channel_id": channel_id,
+                "signer": "",
+            })
+        };
+        let oversized = "x".repeat(2 * MAX_SINGLE_INSTRUCTION_SIZE);
+
+        let batch_txs = plan_batch(
+            &kind,
+            &payer,
+            vec![close_init("channel-0"), close_init(&oversized)],
+        )
+        .unwrap();
+        assert_eq!(batch_txs.len(), 2);
+        assert_eq!(batch_txs[0].len(), 1);
+        assert_eq!(
+            batch_txs[1]
+                .iter()
+                .map(|planned_tx| planned_tx.account_spec)
+                .collect::<Vec<_>>(),
+            [
+                CREATE_TX_BUFFER_ACCOUNTS,
+                REUSE_TX_BUFFER_ACCOUNTS,
+                IBC_HANDLER_ACCOUNTS,
+                CLOSE_TX_BUFFER_ACCOUNTS,
+            ]
+        );
+
+        let err = plan_batch(
+            &kind,
+            &payer,
+            vec![close_init("channel-0"), serde_json::json!({ "port_id": 7 })],
+        )
+        .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("Invalid message 1 of the batch"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn batches_resume_past_messages_that_may_have_landed() {
+        let sent = |outcome| SentTx {
+            signature: Signature::new_unique(),
+            outcome,
+        };
+        assert_eq!(resume_hint(3, &[]), "resume with --start-index 3");
+        assert_eq!(
+            resume_hint(3, &[sent(Some(TxOutcome::Confirmed)), sent(None)]),
+            "resume with --start-index 3"
+        );
+        assert_eq!(
+            resume_hint(
+                3,
+                &[
+                    sent(Some(TxOutcome::Dropped)),
+                    sent(Some(TxOutcome::Dropped))
+                ]
+            ),
+            "resume with --start-index 3"
+        );
+
+        let unknown = sent(Some(TxOutcome::Unknown));
+        let hint = resume_hint(3, &[sent(Some(TxOutcome::Dropped)), unknown.clone()]);
+        assert!(
+            hint.contains(&format!(
+                "once {} is confirmed resume with --start-index 4",
+                unknown.signature
+            )),
+            "{hint}"
+        );
+        assert!(hint.ends_with("--start-index 3"), "{hint}");
+    }
+}
@@ -0,0 +1,237 @@
+use {
+    crate::duration,
+    clap::Parser,
+    core::time::Duration,
+    eclipse_ibc_light_client::eclipse_chain,
+    ibc::core::ics24_host::identifier::{ChannelId, ClientId, ConnectionId, PortId},
+    log::warn,
+    serde::Serialize,
+    solana_client::{
+        nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+        rpc_response::RpcConfirmedTransactionStatusWithSignature,
+    },
+    solana_sdk::signature::Signature,
+    solana_transaction_status::UiTransactionEncoding,
+    std::{
+        io::{self, Write as _},
+        str::FromStr,
+    },
+};
+
+/// Identifiers to filter IBC events and logs by. An entry matches if it mentions
+/// every identifier that was specified.
+#[derive(Clone, Debug, Default, clap::Args)]
+pub(crate) struct ObjectFilter {
+    /// Only show entries referencing this client ID
+    #[arg(long = "client")]
+    client_id: Option<ClientId>,
+
+    /// Only show entries referencing this connection ID
+    #[arg(long = "connection")]
+    connection_id: Option<ConnectionId>,
+
+    /// Only show entries referencing this channel ID
+    #[arg(long = "channel")]
+    channel_id: Option<ChannelId>,
+
+    /// Only show entries referencing this port ID
+    #[arg(long = "port")]
+    port_id: Option<PortId>,
+}
+
+impl ObjectFilter {
+    fn ids(&self) -> impl Iterator<Item = &str> {
+        [
+            self.client_id.as_ref().map(ClientId::as_str),
+            self.connection_id.as_ref().map(ConnectionId::as_str),
+            self.channel_id.as_ref().map(ChannelId::as_str),
+            self.port_id.as_ref().map(PortId::as_str),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    pub(crate) fn matches(&self, entry: &str) -> bool {
+        self.ids().all(|id| mentions_id(entry, id))
+    }
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+' | '#')
+}
+
+/// Checks that `id` appears in `entry` as a whole identifier, so that
+/// `channel-5` does not match `channel-50`.
+fn mentions_id(entry: &str, id: &str) -> bool {
+    entry.match_indices(id).any(|(start, _)| {
+        let before = entry[..start].chars().next_back();
+        let after = entry[start + id.len()..].chars().next();
+        !before.map_or(false, is_id_char) && !after.map_or(false, is_id_char)
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct WatchEntry<'a> {
+    timestamp: Option<String>,
+    slot: u64,
+    signature: &'a str,
+    log: &'a str,
+}
+
+pub(crate) async fn get_logs(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+) -> anyhow::Result<Vec<String>> {
+    let tx = rpc_client
+        .get_transaction(signature, UiTransactionEncoding::Json)
+        .await?;
+    Ok(tx
+        .transaction
+        .meta
+        .and_then(|meta| Option::<Vec<String>>::from(meta.log_messages))
+        .unwrap_or_default())
+}
+
+/// Most signatures `getSignaturesForAddress` returns per request.
+const SIGNATURES_PER_PAGE: usize = 1000;
+
+/// Fetches the signatures of every transaction touching the storage account
+/// after `until`, oldest first, paging back with `before` past the per-request
+/// limit.
+async fn signatures_since(
+    rpc_client: &RpcClient,
+    until: Option<Signature>,
+) -> anyhow::Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+    let mut statuses = Vec::new();
+    let mut before = None;
+    loop {
+        // Signatures are returned newest first
+        let page = rpc_client
+            .get_signatures_for_address_with_config(
+                &eclipse_ibc_program::STORAGE_KEY,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit: Some(SIGNATURES_PER_PAGE),
+                    ..GetConfirmedSignaturesForAddress2Config::default()
+                },
+            )
+            .await?;
+        let is_last_page = page.len() < SIGNATURES_PER_PAGE;
+        if let Some(oldest) = page.last() {
+            before = Some(Signature::from_str(&oldest.signature)?);
+        }
+        statuses.extend(page);
+        if is_last_page {
+            break;
+        }
+    }
+    statuses.reverse();
+    Ok(statuses)
+}
+
+async fn poll_once(
+    rpc_client: &RpcClient,
+    filter: &ObjectFilter,
+    last_signature: &mut Option<Signature>,
+) -> anyhow::Result<()> {
+    for status in signatures_since(rpc_client, *last_signature).await? {
+        let signature = Signature::from_str(&status.signature)?;
+        if status.err.is_none() {
+            let timestamp = status
+                .block_time
+                .and_then(eclipse_chain::slot_time)
+                .map(|time| time.to_rfc3339());
+            for log in get_logs(rpc_client, &signature).await? {
+                if !filter.matches(&log) {
+                    continue;
+                }
+                let entry = WatchEntry {
+                    timestamp: timestamp.clone(),
+                    slot: status.slot,
+                    signature: &status.signature,
+                    log: &log,
+                };
+                writeln!(io::stdout(), "{}", serde_json::to_string(&entry)?)?;
+            }
+        }
+        // Only advance once the transaction's logs are out, so that a failed
+        // fetch is retried by the next poll instead of being skipped
+        *last_signature = Some(signature);
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct Args {
+    /// Endpoint to send a request to
+    #[arg(long, default_value = "http://127.0.0.1:8899")]
+    endpoint: String,
+
+    /// Time to wait between polls, e.g. `2s` or `500ms`
+    #[arg(long, default_value = "2s", value_parser = duration::parse_duration)]
+    poll_interval: Duration,
+
+    #[command(flatten)]
+    filter: ObjectFilter,
+}
+
+/// Streams IBC program logs touching the filtered objects as NDJSON until interrupted.
+pub(crate) async fn run(
+    Args {
+        endpoint,
+        poll_interval,
+        filter,
+    }: Args,
+) -> anyhow::Result<()> {
+    let rpc_client = RpcClient::new(endpoint);
+
+    // Only show entries that land after the watch started
+    let mut last_signature = rpc_client
+        .get_signatures_for_address_with_config(
+            &eclipse_ibc_program::STORAGE_KEY,
+            GetConfirmedSignaturesForAddress2Config {
+                limit: Some(1),
+                ..GetConfirmedSignaturesForAddress2Config::default()
+            },
+        )
+        .await?
+        .first()
+        .map(|status| Signature::from_str(&status.signature))
+        .transpose()?;
+
+    loop {
+        if let Err(err) = poll_once(&rpc_client, &filter, &mut last_signature).await {
+            warn!("Failed to poll IBC logs: {err}");
+        }
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+            _ = tokio::time::sleep(poll_interval) => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_matches_whole_ids() {
+        let filter = ObjectFilter {
+            channel_id: Some("channel-5".parse().unwrap()),
+            port_id: Some("transfer".parse().unwrap()),
+            ..ObjectFilter::default()
+        };
+
+        assert!(filter.matches(
+            r#"Program log: SendPacket { port_id_on_a: PortId("transfer"), chan_id_on_a: ChannelId("channel-5") }"#
+        ));
+        assert!(!filter.matches(
+            r#"Program log: SendPacket { port_id_on_a: PortId("transfer"), chan_id_on_a: ChannelId("channel-50") }"#
+        ));
+        assert!(!filter.matches(r#"Program log: ChannelId("channel-5")"#));
+        assert!(ObjectFilter::default().matches("Program log: anything"));
+    }
+}
@@ -0,0 +1,155 @@
+use {
+    eclipse_ibc_program::{
+        account_spec::{
+            ExpectedAccount, CLAIM_ADMIN_ACCOUNTS, CLOSE_TX_BUFFER_ACCOUNTS,
+            CREATE_TX_BUFFER_ACCOUNTS, IBC_HANDLER_ACCOUNTS, INIT_STORAGE_ACCOUNT_ACCOUNTS,
+            REALLOC_STORAGE_ACCOUNT_ACCOUNTS, REUSE_TX_BUFFER_ACCOUNTS,
+        },
+        STORAGE_KEY,
+    },
+    solana_sdk::{
+        bpf_loader_upgradeable,
+        instruction::AccountMeta,
+        pubkey::Pubkey,
+        system_program,
+        sysvar::{clock, rent},
+    },
+};
+
+/// Accounts an IBC instruction takes after any tx buffers holding the leading
+/// parts of its data, which depend on the message it runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InstructionAccounts {
+    /// Messages that run against the IBC state: router, port, ICS-20 transfer
+    /// and most admin messages
+    IbcHandler,
+    /// `MsgClaimAdmin`, where the payer is the upgrade authority recorded in the
+    /// program data account of the IBC program
+    ClaimAdmin,
+    /// `MsgInitStorageAccount`, which creates the storage account
+    InitStorageAccount,
+    /// `MsgReallocStorageAccount`, where the payer tops up the storage account's
+    /// rent
+    ReallocStorageAccount,
+    /// `MsgCloseTxBuffer` closing `buffer`
+    CloseTxBuffer { buffer: Pubkey },
+    /// `MsgWriteTxBuffer` creating `buffer`, which signs along with the payer
+    CreateTxBuffer { buffer: Pubkey },
+    /// `MsgWriteTxBuffer` writing to the existing `buffer`
+    ReuseTxBuffer { buffer: Pubkey },
+}
+
+impl InstructionAccounts {
+    /// Accounts of the instruction, in the order the program expects them.
+    #[must_use]
+    pub fn metas(&self, payer_key: Pubkey) -> Vec<AccountMeta> {
+        match self {
+            Self::IbcHandler => vec![
+                AccountMeta::new_readonly(payer_key, true),
+                AccountMeta::new(STORAGE_KEY, false),
+                AccountMeta::new_readonly(clock::id(), false),
+            ],
+            Self::ClaimAdmin => vec![
+                AccountMeta::new_readonly(payer_key, true),
+                AccountMeta::new(STORAGE_KEY, false),
+                AccountMeta::new_readonly(clock::id(), false),
+                AccountMeta::new_readonly(
+                    bpf_loader_upgradeable::get_program_data_address(&eclipse_ibc_program::id()),
+                    false,
+                ),
+            ],
+            Self::InitStorageAccount => vec![
+                AccountMeta::new_readonly(payer_key, true),
+                AccountMeta::new(STORAGE_KEY, false),
+                AccountMeta::new_readonly(rent::id(), false),
+                AccountMeta::new_readonly(clock::id(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            Self::ReallocStorageAccount => vec![
+                AccountMeta::new(payer_key, true),
+                AccountMeta::new(STORAGE_KEY, false),
+                AccountMeta::new_readonly(rent::id(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            Self::CloseTxBuffer { buffer } => vec![
+                AccountMeta::new(payer_key, true),
+                AccountMeta::new(*buffer, false),
+                AccountMeta::new(STORAGE_KEY, false),
+                AccountMeta::new_readonly(clock::id(), false),
+            ],
+            Self::CreateTxBuffer { buffer } => vec![
+                AccountMeta::new_readonly(payer_key, true),
+                AccountMeta::new(*buffer, true),
+                AccountMeta::new_readonly(rent::id(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(STORAGE_KEY, false),
+                AccountMeta::new_readonly(clock::id(), false),
+            ],
+            Self::ReuseTxBuffer { buffer } => vec![
+                AccountMeta::new_readonly(payer_key, true),
+                AccountMeta::new(*buffer, false),
+                AccountMeta::new_readonly(STORAGE_KEY, false),
+            ],
+        }
+    }
+
+    /// The accounts the program checks [`InstructionAccounts::metas`] against.
+    #[must_use]
+    pub fn spec(&self) -> &'static [ExpectedAccount] {
+        match self {
+            Self::IbcHandler => IBC_HANDLER_ACCOUNTS,
+            Self::ClaimAdmin => CLAIM_ADMIN_ACCOUNTS,
+            Self::InitStorageAccount => INIT_STORAGE_ACCOUNT_ACCOUNTS,
+            Self::ReallocStorageAccount => REALLOC_STORAGE_ACCOUNT_ACCOUNTS,
+            Self::CloseTxBuffer { .. } => CLOSE_TX_BUFFER_ACCOUNTS,
+            Self::CreateTxBuffer { .. } => CREATE_TX_BUFFER_ACCOUNTS,
+            Self::ReuseTxBuffer { .. } => REUSE_TX_BUFFER_ACCOUNTS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, eclipse_ibc_program::account_spec::account_mismatches};
+
+    #[test]
+    fn accounts_follow_the_program_spec() {
+        let payer_key = Pubkey::new_unique();
+        let buffer = Pubkey::new_unique();
+        for accounts in [
+            InstructionAccounts::IbcHandler,
+            InstructionAccounts::ClaimAdmin,
+            InstructionAccounts::InitStorageAccount,
+            InstructionAccounts::ReallocStorageAccount,
+            InstructionAccounts::CloseTxBuffer { buffer },
+            InstructionAccounts::CreateTxBuffer { buffer },
+            InstructionAccounts::ReuseTxBuffer { buffer },
+        ] {
+            let keys = accounts
+                .metas(payer_key)
+                .iter()
+                .map(|account| account.pubkey)
+                .collect::<Vec<_>>();
+            assert_eq!(keys.len(), accounts.spec().len(), "{accounts:?}");
+            assert_eq!(
+                account_mismatches(accounts.spec(), &keys, 0),
+                [],
+                "{accounts:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn ibc_handler_accounts_are_stable() {
+        let payer_key = Pubkey::new_unique();
+        // Relayers built against this crate depend on the order and the flags
+        assert_eq!(
+            InstructionAccounts::IbcHandler.metas(payer_key),
+            [
+                AccountMeta::new_readonly(payer_key, true),
+                AccountMeta::new(STORAGE_KEY, false),
+                AccountMeta::new_readonly(clock::id(), false),
+            ]
+        );
+    }
+}
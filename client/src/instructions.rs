@@ -0,0 +1,388 @@
+use {
+    crate::InstructionAccounts,
+    anyhow::{anyhow, bail},
+    borsh::BorshSerialize,
+    eclipse_ibc_known_proto::{KnownAnyProto, KnownProto},
+    eclipse_ibc_program::{
+        account_spec::{check_buffer_keys, ExpectedAccount},
+        ibc_contract_instruction::IbcContractInstruction,
+        ibc_instruction::msgs::{
+            MsgClaimAdmin, MsgCloseTxBuffer, MsgInitStorageAccount, MsgReallocStorageAccount,
+            MsgWriteTxBuffer, MsgWriteTxBufferMode,
+        },
+    },
+    ibc_proto::google::protobuf,
+    solana_sdk::{
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+    },
+};
+
+/// Largest part of the IBC instruction data sent in a single instruction. The
+/// leading parts of longer data are written to a tx buffer first.
+pub const MAX_SINGLE_INSTRUCTION_SIZE: usize = 825;
+
+const ECLIPSE_TYPE_URL_PREFIX: &str = "/eclipse.ibc.";
+const ADMIN_TYPE_URL_PREFIX: &str = "/eclipse.ibc.admin.";
+const PORT_TYPE_URL_PREFIX: &str = "/eclipse.ibc.port.";
+
+/// An IBC instruction to submit in its own transaction, paid for by the payer it
+/// was planned for, along with the accounts the program expects in it.
+#[derive(Clone, Debug)]
+pub struct PlannedInstruction {
+    pub instruction: Instruction,
+    /// Whether the tx buffer signs along with the payer, as it does when the
+    /// instruction creates it
+    pub buffer_signs: bool,
+    pub account_spec: &'static [ExpectedAccount],
+    /// Position of the first account of `account_spec`, after any tx buffers
+    pub account_offset: usize,
+}
+
+fn ibc_program_instruction(
+    extra_accounts_for_instruction: usize,
+    last_instruction_part: Vec<u8>,
+    accounts: Vec<AccountMeta>,
+) -> anyhow::Result<Instruction> {
+    let instruction_data = BorshSerialize::try_to_vec(&IbcContractInstruction {
+        extra_accounts_for_instruction,
+        last_instruction_part,
+    })?;
+    Ok(Instruction::new_with_bytes(
+        eclipse_ibc_program::id(),
+        &instruction_data,
+        accounts,
+    ))
+}
+
+/// Splits an IBC instruction whose main part takes `accounts` into instructions
+/// writing the leading parts to the tx buffer `buffer_key`, the instruction
+/// running the message, and an instruction closing the buffer so that it does
+/// not count against the payer's allowance. The first write creates the buffer
+/// at its full size, so `buffer_key` must be a new account, and later writes
+/// reuse it. Data that fits in a single instruction is not buffered.
+pub fn split_large_instruction(
+    mut ibc_instruction_data: Vec<u8>,
+    payer_key: Pubkey,
+    buffer_key: Pubkey,
+    accounts: InstructionAccounts,
+) -> anyhow::Result<Vec<PlannedInstruction>> {
+    // The last part, of up to `MAX_SINGLE_INSTRUCTION_SIZE` bytes, stays in the
+    // instruction running the message
+    let buffered_len = ibc_instruction_data.len().saturating_sub(1) / MAX_SINGLE_INSTRUCTION_SIZE
+        * MAX_SINGLE_INSTRUCTION_SIZE;
+    let last_instruction_part = ibc_instruction_data.split_off(buffered_len);
+    let buffered_data = ibc_instruction_data;
+
+    let mut planned = vec![];
+    for (index, chunk) in buffered_data
+        .chunks(MAX_SINGLE_INSTRUCTION_SIZE)
+        .enumerate()
+    {
+        let offset = index * MAX_SINGLE_INSTRUCTION_SIZE;
+        let (mode, write_accounts) = if offset == 0 {
+            (
+                MsgWriteTxBufferMode::Create {
+                    buffer_size: buffered_data.len().try_into()?,
+                },
+                InstructionAccounts::CreateTxBuffer { buffer: buffer_key },
+            )
+        } else {
+            (
+                MsgWriteTxBufferMode::Reuse {
+                    offset: offset.try_into()?,
+                },
+                InstructionAccounts::ReuseTxBuffer { buffer: buffer_key },
+            )
+        };
+        let write = MsgWriteTxBuffer {
+            mode,
+            data: chunk.to_vec(),
+        }
+        .encode_as_any()
+        .encode();
+        planned.push(PlannedInstruction {
+            instruction: ibc_program_instruction(0, write, write_accounts.metas(payer_key))?,
+            buffer_signs: offset == 0,
+            account_spec: write_accounts.spec(),
+            account_offset: 0,
+        });
+    }
+
+    let buffer_accounts = if buffered_data.is_empty() {
+        vec![]
+    } else {
+        vec![AccountMeta::new_readonly(buffer_key, false)]
+    };
+
+    // The program rejects repeated buffers and buffers that are the storage
+    // account or a sysvar, so never plan one
+    let buffer_keys = buffer_accounts
+        .iter()
+        .map(|account| account.pubkey)
+        .collect::<Vec<_>>();
+    check_buffer_keys(&buffer_keys)
+        .map_err(|rejection| anyhow!("Planned an invalid tx buffer layout: {rejection}"))?;
+
+    let extra_accounts_for_instruction = buffer_accounts.len();
+    planned.push(PlannedInstruction {
+        instruction: ibc_program_instruction(
+            extra_accounts_for_instruction,
+            last_instruction_part,
+            [buffer_accounts, accounts.metas(payer_key)].concat(),
+        )?,
+        buffer_signs: false,
+        account_spec: accounts.spec(),
+        account_offset: extra_accounts_for_instruction,
+    });
+
+    if !buffered_data.is_empty() {
+        let close_accounts = InstructionAccounts::CloseTxBuffer { buffer: buffer_key };
+        planned.push(PlannedInstruction {
+            instruction: ibc_program_instruction(
+                0,
+                MsgCloseTxBuffer.encode_as_any().encode(),
+                close_accounts.metas(payer_key),
+            )?,
+            buffer_signs: false,
+            account_spec: close_accounts.spec(),
+            account_offset: 0,
+        });
+    }
+
+    Ok(planned)
+}
+
+/// Instructions running a router message, such as a client, connection, channel
+/// or packet message, or an ICS-20 transfer, against the IBC state. Messages too
+/// large for one instruction are split across the new tx buffer `buffer_key`, as
+/// in [`split_large_instruction`].
+pub fn build_router_instruction(
+    msg: protobuf::Any,
+    payer_key: Pubkey,
+    buffer_key: Pubkey,
+) -> anyhow::Result<Vec<PlannedInstruction>> {
+    if msg.type_url.starts_with(ECLIPSE_TYPE_URL_PREFIX) {
+        bail!("{} is not a router message", msg.type_url);
+    }
+    split_large_instruction(
+        msg.encode(),
+        payer_key,
+        buffer_key,
+        InstructionAccounts::IbcHandler,
+    )
+}
+
+/// Instructions running a port message, such as `MsgBindPort`, signed by the
+/// payer as the module owning the port.
+pub fn build_port_instruction(
+    msg: protobuf::Any,
+    payer_key: Pubkey,
+    buffer_key: Pubkey,
+) -> anyhow::Result<Vec<PlannedInstruction>> {
+    if !msg.type_url.starts_with(PORT_TYPE_URL_PREFIX) {
+        bail!("{} is not a port message", msg.type_url);
+    }
+    split_large_instruction(
+        msg.encode(),
+        payer_key,
+        buffer_key,
+        InstructionAccounts::IbcHandler,
+    )
+}
+
+/// Instructions running an admin message, with the accounts that message takes.
+/// Tx buffers are written and closed by [`split_large_instruction`], so
+/// `MsgWriteTxBuffer` and `MsgCloseTxBuffer` are rejected here; a buffer left
+/// open can be closed by splitting `MsgCloseTxBuffer` with
+/// [`InstructionAccounts::CloseTxBuffer`].
+pub fn build_admin_instruction(
+    msg: protobuf::Any,
+    payer_key: Pubkey,
+    buffer_key: Pubkey,
+) -> anyhow::Result<Vec<PlannedInstruction>> {
+    let accounts = match &*msg.type_url {
+        MsgInitStorageAccount::TYPE_URL => InstructionAccounts::InitStorageAccount,
+        MsgReallocStorageAccount::TYPE_URL => InstructionAccounts::ReallocStorageAccount,
+        MsgClaimAdmin::TYPE_URL => InstructionAccounts::ClaimAdmin,
+        MsgWriteTxBuffer::TYPE_URL | MsgCloseTxBuffer::TYPE_URL => {
+            bail!("{} takes the tx buffer it acts on", msg.type_url)
+        }
+        type_url if type_url.starts_with(ADMIN_TYPE_URL_PREFIX) => InstructionAccounts::IbcHandler,
+        type_url => bail!("{type_url} is not an admin message"),
+    };
+    split_large_instruction(msg.encode(), payer_key, buffer_key, accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        borsh::BorshDeserialize,
+        eclipse_ibc_program::{
+            account_spec::{
+                account_mismatches, CLOSE_TX_BUFFER_ACCOUNTS, CREATE_TX_BUFFER_ACCOUNTS,
+                IBC_HANDLER_ACCOUNTS, INIT_STORAGE_ACCOUNT_ACCOUNTS, REUSE_TX_BUFFER_ACCOUNTS,
+            },
+            ibc_instruction::msgs::MsgSetIbcFrozen,
+        },
+    };
+
+    fn instruction_keys(planned: &PlannedInstruction) -> Vec<Pubkey> {
+        planned
+            .instruction
+            .accounts
+            .iter()
+            .map(|account| account.pubkey)
+            .collect()
+    }
+
+    fn contract_instruction(planned: &PlannedInstruction) -> IbcContractInstruction {
+        BorshDeserialize::try_from_slice(&planned.instruction.data).unwrap()
+    }
+
+    #[test]
+    fn instruction_data_layout_is_stable() {
+        let payer_key = Pubkey::new_unique();
+        let msg = MsgSetIbcFrozen { frozen: true }.encode_as_any();
+        let planned =
+            build_admin_instruction(msg.clone(), payer_key, Pubkey::new_unique()).unwrap();
+        assert_eq!(planned.len(), 1);
+        assert_eq!(planned[0].instruction.program_id, eclipse_ibc_program::id());
+        assert!(!planned[0].buffer_signs);
+
+        // Borsh encodes the count of buffers as a `u64` and the last part as a
+        // `u32` length followed by the `Any`
+        let ibc_instruction_data = msg.encode();
+        let data = &planned[0].instruction.data;
+        assert_eq!(data[..8], 0_u64.to_le_bytes());
+        assert_eq!(
+            data[8..12],
+            u32::try_from(ibc_instruction_data.len())
+                .unwrap()
+                .to_le_bytes()
+        );
+        assert_eq!(data[12..], ibc_instruction_data);
+    }
+
+    #[test]
+    fn large_instructions_are_written_to_one_buffer() {
+        let payer_key = Pubkey::new_unique();
+        let buffer_key = Pubkey::new_unique();
+        let ibc_instruction_data = protobuf::Any {
+            type_url: "/test.Msg".to_owned(),
+            value: vec![7; 3 * MAX_SINGLE_INSTRUCTION_SIZE],
+        }
+        .encode();
+        assert!(ibc_instruction_data.len() > 3 * MAX_SINGLE_INSTRUCTION_SIZE);
+
+        let planned = split_large_instruction(
+            ibc_instruction_data.clone(),
+            payer_key,
+            buffer_key,
+            InstructionAccounts::IbcHandler,
+        )
+        .unwrap();
+        let account_specs = planned
+            .iter()
+            .map(|planned| planned.account_spec)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            account_specs,
+            [
+                CREATE_TX_BUFFER_ACCOUNTS,
+                REUSE_TX_BUFFER_ACCOUNTS,
+                REUSE_TX_BUFFER_ACCOUNTS,
+                IBC_HANDLER_ACCOUNTS,
+                CLOSE_TX_BUFFER_ACCOUNTS,
+            ]
+        );
+        assert_eq!(
+            planned
+                .iter()
+                .map(|planned| planned.buffer_signs)
+                .collect::<Vec<_>>(),
+            [true, false, false, false, false]
+        );
+        for planned in &planned {
+            let keys = instruction_keys(planned);
+            assert_eq!(
+                account_mismatches(planned.account_spec, &keys, planned.account_offset),
+                []
+            );
+        }
+
+        // Replay the writes the way the program applies them
+        let mut buffer = vec![];
+        for planned in &planned[..3] {
+            assert_eq!(instruction_keys(planned)[1], buffer_key);
+            let any = protobuf::Any::decode(&*contract_instruction(planned).last_instruction_part)
+                .unwrap();
+            let MsgWriteTxBuffer { mode, data } = MsgWriteTxBuffer::decode(&*any.value).unwrap();
+            let offset = match mode {
+                MsgWriteTxBufferMode::Create { buffer_size } => {
+                    buffer = vec![0; usize::try_from(buffer_size).unwrap()];
+                    0
+                }
+                MsgWriteTxBufferMode::Reuse { offset } => usize::try_from(offset).unwrap(),
+            };
+            buffer[offset..offset + data.len()].copy_from_slice(&data);
+        }
+
+        let main = &planned[3];
+        assert_eq!(instruction_keys(main)[0], buffer_key);
+        let IbcContractInstruction {
+            extra_accounts_for_instruction,
+            last_instruction_part,
+        } = contract_instruction(main);
+        assert_eq!(extra_accounts_for_instruction, 1);
+        assert_eq!(
+            [buffer, last_instruction_part].concat(),
+            ibc_instruction_data
+        );
+
+        assert_eq!(instruction_keys(&planned[4])[1], buffer_key);
+    }
+
+    #[test]
+    fn small_instructions_are_not_buffered() {
+        let planned = split_large_instruction(
+            vec![0; MAX_SINGLE_INSTRUCTION_SIZE],
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            InstructionAccounts::IbcHandler,
+        )
+        .unwrap();
+        assert_eq!(planned.len(), 1);
+        assert_eq!(
+            contract_instruction(&planned[0]).extra_accounts_for_instruction,
+            0
+        );
+    }
+
+    #[test]
+    fn messages_are_built_with_the_accounts_of_their_kind() {
+        let payer_key = Pubkey::new_unique();
+        let buffer_key = Pubkey::new_unique();
+        let init = MsgInitStorageAccount {
+            genesis_hash: None,
+            idempotent: false,
+        }
+        .encode_as_any();
+        let planned = build_admin_instruction(init.clone(), payer_key, buffer_key).unwrap();
+        assert_eq!(planned[0].account_spec, INIT_STORAGE_ACCOUNT_ACCOUNTS);
+
+        let close = MsgCloseTxBuffer.encode_as_any();
+        build_admin_instruction(close, payer_key, buffer_key).unwrap_err();
+        build_port_instruction(init.clone(), payer_key, buffer_key).unwrap_err();
+        build_router_instruction(init, payer_key, buffer_key).unwrap_err();
+
+        let router_msg = protobuf::Any {
+            type_url: "/ibc.core.channel.v1.MsgRecvPacket".to_owned(),
+            value: vec![],
+        };
+        build_admin_instruction(router_msg.clone(), payer_key, buffer_key).unwrap_err();
+        let planned = build_router_instruction(router_msg, payer_key, buffer_key).unwrap();
+        assert_eq!(planned[0].account_spec, IBC_HANDLER_ACCOUNTS);
+    }
+}
@@ -0,0 +1,15 @@
+//! Builds the instructions of the Eclipse IBC program, so that the CLI and other
+//! Rust services, such as relayers, submit messages the same way. Messages too
+//! large for a single instruction are split across a tx buffer the way the
+//! program reassembles them.
+
+mod accounts;
+mod instructions;
+
+pub use {
+    accounts::InstructionAccounts,
+    instructions::{
+        build_admin_instruction, build_port_instruction, build_router_instruction,
+        split_large_instruction, PlannedInstruction, MAX_SINGLE_INSTRUCTION_SIZE,
+    },
+};
@@ -0,0 +1,38 @@
+use {
+    eclipse_ibc_known_proto::KnownProtoWithFrom,
+    eclipse_ibc_proto::eclipse::ibc::port::v1::CallbackVersions as RawCallbackVersions,
+};
+
+/// Versions of the callback encoding a module can decode, as it advertised them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallbackVersions {
+    pub versions: Vec<u32>,
+}
+
+impl CallbackVersions {
+    /// The highest of `supported` that the module also supports, if any.
+    #[must_use]
+    pub fn highest_common(&self, supported: &[u32]) -> Option<u32> {
+        supported
+            .iter()
+            .copied()
+            .filter(|version| self.versions.contains(version))
+            .max()
+    }
+}
+
+impl From<CallbackVersions> for RawCallbackVersions {
+    fn from(CallbackVersions { versions }: CallbackVersions) -> Self {
+        Self { versions }
+    }
+}
+
+impl From<RawCallbackVersions> for CallbackVersions {
+    fn from(RawCallbackVersions { versions }: RawCallbackVersions) -> Self {
+        Self { versions }
+    }
+}
+
+impl KnownProtoWithFrom for CallbackVersions {
+    type RawWithFrom = RawCallbackVersions;
+}
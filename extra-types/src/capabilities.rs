@@ -0,0 +1,63 @@
+use {
+    eclipse_ibc_known_proto::KnownProtoWithFrom,
+    eclipse_ibc_proto::eclipse::ibc::admin::v1::Capabilities as RawCapabilities,
+};
+
+/// Message families the deployed program handles, as advertised by the program
+/// itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Type URL prefixes of the supported messages
+    pub type_url_prefixes: Vec<String>,
+    /// Version of the program that wrote the capabilities
+    pub program_version: String,
+    /// Git hash of the program build that wrote the capabilities, empty for
+    /// programs that predate build IDs
+    pub build_id: String,
+}
+
+impl Capabilities {
+    /// Whether a message of type `type_url` falls under an advertised prefix.
+    #[must_use]
+    pub fn supports(&self, type_url: &str) -> bool {
+        self.type_url_prefixes
+            .iter()
+            .any(|prefix| type_url.starts_with(prefix.as_str()))
+    }
+}
+
+impl From<Capabilities> for RawCapabilities {
+    fn from(
+        Capabilities {
+            type_url_prefixes,
+            program_version,
+            build_id,
+        }: Capabilities,
+    ) -> Self {
+        Self {
+            type_url_prefixes,
+            program_version,
+            build_id,
+        }
+    }
+}
+
+impl From<RawCapabilities> for Capabilities {
+    fn from(
+        RawCapabilities {
+            type_url_prefixes,
+            program_version,
+            build_id,
+        }: RawCapabilities,
+    ) -> Self {
+        Self {
+            type_url_prefixes,
+            program_version,
+            build_id,
+        }
+    }
+}
+
+impl KnownProtoWithFrom for Capabilities {
+    type RawWithFrom = RawCapabilities;
+}
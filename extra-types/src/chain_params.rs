@@ -0,0 +1,313 @@
+use {
+    anyhow::anyhow,
+    eclipse_ibc_known_proto::KnownProtoWithFrom,
+    eclipse_ibc_proto::eclipse::ibc::admin::v1::{
+        ChainParams as RawChainParams, ValueSizeCap as RawValueSizeCap,
+    },
+};
+
+/// Largest tx buffer the program creates when `ChainParams::max_tx_buffer_size`
+/// is unset. Comfortably fits any IBC message the program accepts.
+pub const DEFAULT_MAX_TX_BUFFER_SIZE: u64 = 64 * 1024;
+
+/// Most tx buffer bytes a payer may have open at once when
+/// `ChainParams::max_tx_buffer_bytes_per_payer` is unset.
+pub const DEFAULT_MAX_TX_BUFFER_BYTES_PER_PAYER: u64 = 1024 * 1024;
+
+/// Most IBC events the event journal keeps when
+/// `ChainParams::event_journal_capacity` is unset.
+pub const DEFAULT_EVENT_JOURNAL_CAPACITY: u64 = 128;
+
+/// Most consensus states kept per client when
+/// `ChainParams::max_consensus_states_per_client` is unset.
+pub const DEFAULT_MAX_CONSENSUS_STATES_PER_CLIENT: u64 = 100;
+
+/// Operator-controlled parameters of the IBC program.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChainParams {
+    /// Type URL prefixes of messages that are rejected.
+    pub disabled_messages: Vec<String>,
+    /// Genesis hash of this cluster, if known.
+    pub genesis_hash: Option<[u8; 32]>,
+    /// Maximum number of in-flight packets per channel, if limited.
+    pub max_inflight_packets_per_channel: Option<u64>,
+    /// Whether all IBC activity is paused.
+    pub frozen: bool,
+    /// Whether the program skips checking the latest root of the IBC state when
+    /// loading it.
+    pub skip_root_check_on_load: bool,
+    /// Largest tx buffer the program creates, if not the default.
+    pub max_tx_buffer_size: Option<u64>,
+    /// Most tx buffer bytes a payer may have open at once, if not the default.
+    pub max_tx_buffer_bytes_per_payer: Option<u64>,
+    /// Name of this chain in the chain IDs of its counterparty clients, if known.
+    pub chain_name: Option<String>,
+    /// Whether the program logs error details and the messages ibc-rs logs.
+    pub verbose_logs: bool,
+    /// Most IBC events the event journal keeps, if not the default.
+    pub event_journal_capacity: Option<u64>,
+    /// Most consensus states kept per client, if not the default.
+    pub max_consensus_states_per_client: Option<u64>,
+    /// Whether client messages with legacy Eclipse type URLs are rejected
+    /// instead of rewritten.
+    pub reject_legacy_type_urls: bool,
+    /// Size in bytes above which values at internal paths are stored compressed,
+    /// if they are.
+    pub compress_values_above: Option<u64>,
+    /// Largest values an instruction may store under path prefixes.
+    pub value_size_caps: Vec<ValueSizeCap>,
+    /// Most bytes of values an instruction may store in total, if limited.
+    pub max_commit_bytes: Option<u64>,
+    /// Path prefixes no instruction may write to.
+    pub forbidden_write_prefixes: Vec<String>,
+}
+
+/// Largest value an instruction may store at paths under `path_prefix`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueSizeCap {
+    pub path_prefix: String,
+    pub max_size: u64,
+}
+
+impl From<ValueSizeCap> for RawValueSizeCap {
+    fn from(
+        ValueSizeCap {
+            path_prefix,
+            max_size,
+        }: ValueSizeCap,
+    ) -> Self {
+        Self {
+            path_prefix,
+            max_size,
+        }
+    }
+}
+
+impl From<RawValueSizeCap> for ValueSizeCap {
+    fn from(
+        RawValueSizeCap {
+            path_prefix,
+            max_size,
+        }: RawValueSizeCap,
+    ) -> Self {
+        Self {
+            path_prefix,
+            max_size,
+        }
+    }
+}
+
+impl ChainParams {
+    /// Returns the disabled prefix that `type_url` falls under, if any.
+    #[must_use]
+    pub fn disabled_prefix(&self, type_url: &str) -> Option<&str> {
+        self.disabled_messages
+            .iter()
+            .map(String::as_str)
+            .find(|prefix| type_url.starts_with(prefix))
+    }
+
+    /// Largest tx buffer the program creates when none is set.
+    #[must_use]
+    pub const fn default_max_tx_buffer_size() -> u64 {
+        DEFAULT_MAX_TX_BUFFER_SIZE
+    }
+
+    /// Most tx buffer bytes a payer may have open at once when none is set.
+    #[must_use]
+    pub const fn default_max_tx_buffer_bytes_per_payer() -> u64 {
+        DEFAULT_MAX_TX_BUFFER_BYTES_PER_PAYER
+    }
+
+    /// Most IBC events the event journal keeps when none is set.
+    #[must_use]
+    pub const fn default_event_journal_capacity() -> u64 {
+        DEFAULT_EVENT_JOURNAL_CAPACITY
+    }
+
+    /// Most consensus states kept per client when none is set.
+    #[must_use]
+    pub const fn default_max_consensus_states_per_client() -> u64 {
+        DEFAULT_MAX_CONSENSUS_STATES_PER_CLIENT
+    }
+
+    /// Returns the largest tx buffer the program creates.
+    #[must_use]
+    pub fn max_tx_buffer_size(&self) -> u64 {
+        self.max_tx_buffer_size
+            .unwrap_or(Self::default_max_tx_buffer_size())
+    }
+
+    /// Returns the most tx buffer bytes a payer may have open at once.
+    #[must_use]
+    pub fn max_tx_buffer_bytes_per_payer(&self) -> u64 {
+        self.max_tx_buffer_bytes_per_payer
+            .unwrap_or(Self::default_max_tx_buffer_bytes_per_payer())
+    }
+
+    /// Returns the most IBC events the event journal keeps.
+    #[must_use]
+    pub fn event_journal_capacity(&self) -> u64 {
+        self.event_journal_capacity
+            .unwrap_or(Self::default_event_journal_capacity())
+    }
+
+    /// Returns the most consensus states kept per client.
+    #[must_use]
+    pub fn max_consensus_states_per_client(&self) -> u64 {
+        self.max_consensus_states_per_client
+            .unwrap_or(Self::default_max_consensus_states_per_client())
+    }
+}
+
+impl From<ChainParams> for RawChainParams {
+    fn from(
+        ChainParams {
+            disabled_messages,
+            genesis_hash,
+            max_inflight_packets_per_channel,
+            frozen,
+            skip_root_check_on_load,
+            max_tx_buffer_size,
+            max_tx_buffer_bytes_per_payer,
+            chain_name,
+            verbose_logs,
+            event_journal_capacity,
+            max_consensus_states_per_client,
+            reject_legacy_type_urls,
+            compress_values_above,
+            value_size_caps,
+            max_commit_bytes,
+            forbidden_write_prefixes,
+        }: ChainParams,
+    ) -> Self {
+        Self {
+            disabled_messages,
+            genesis_hash: genesis_hash.map(Vec::from).unwrap_or_default(),
+            max_inflight_packets_per_channel: max_inflight_packets_per_channel.unwrap_or_default(),
+            frozen,
+            skip_root_check_on_load,
+            max_tx_buffer_size: max_tx_buffer_size.unwrap_or_default(),
+            max_tx_buffer_bytes_per_payer: max_tx_buffer_bytes_per_payer.unwrap_or_default(),
+            chain_name: chain_name.unwrap_or_default(),
+            verbose_logs,
+            event_journal_capacity: event_journal_capacity.unwrap_or_default(),
+            max_consensus_states_per_client: max_consensus_states_per_client.unwrap_or_default(),
+            reject_legacy_type_urls,
+            compress_values_above: compress_values_above.unwrap_or_default(),
+            value_size_caps: value_size_caps.into_iter().map(Into::into).collect(),
+            max_commit_bytes: max_commit_bytes.unwrap_or_default(),
+            forbidden_write_prefixes,
+        }
+    }
+}
+
+impl TryFrom<RawChainParams> for ChainParams {
+    type Error = anyhow::Error;
+
+    fn try_from(
+        RawChainParams {
+            disabled_messages,
+            genesis_hash,
+            max_inflight_packets_per_channel,
+            frozen,
+            skip_root_check_on_load,
+            max_tx_buffer_size,
+            max_tx_buffer_bytes_per_payer,
+            chain_name,
+            verbose_logs,
+            event_journal_capacity,
+            max_consensus_states_per_client,
+            reject_legacy_type_urls,
+            compress_values_above,
+            value_size_caps,
+            max_commit_bytes,
+            forbidden_write_prefixes,
+        }: RawChainParams,
+    ) -> Result<Self, Self::Error> {
+        let genesis_hash = if genesis_hash.is_empty() {
+            None
+        } else {
+            Some(genesis_hash.try_into().map_err(|hash: Vec<u8>| {
+                anyhow!("genesis hash must be 32 bytes, got {}", hash.len())
+            })?)
+        };
+        Ok(Self {
+            disabled_messages,
+            genesis_hash,
+            max_inflight_packets_per_channel: (max_inflight_packets_per_channel != 0)
+                .then_some(max_inflight_packets_per_channel),
+            frozen,
+            skip_root_check_on_load,
+            max_tx_buffer_size: (max_tx_buffer_size != 0).then_some(max_tx_buffer_size),
+            max_tx_buffer_bytes_per_payer: (max_tx_buffer_bytes_per_payer != 0)
+                .then_some(max_tx_buffer_bytes_per_payer),
+            chain_name: (!chain_name.is_empty()).then_some(chain_name),
+            verbose_logs,
+            event_journal_capacity: (event_journal_capacity != 0).then_some(event_journal_capacity),
+            max_consensus_states_per_client: (max_consensus_states_per_client != 0)
+                .then_some(max_consensus_states_per_client),
+            reject_legacy_type_urls,
+            compress_values_above: (compress_values_above != 0).then_some(compress_values_above),
+            value_size_caps: value_size_caps.into_iter().map(Into::into).collect(),
+            max_commit_bytes: (max_commit_bytes != 0).then_some(max_commit_bytes),
+            forbidden_write_prefixes,
+        })
+    }
+}
+
+impl KnownProtoWithFrom for ChainParams {
+    type RawWithFrom = RawChainParams;
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, eclipse_ibc_known_proto::KnownProto};
+
+    #[test]
+    fn unset_params_use_the_defaults() {
+        let chain_params = ChainParams::default();
+        assert_eq!(chain_params.max_tx_buffer_size(), 64 * 1024);
+        assert_eq!(chain_params.max_tx_buffer_bytes_per_payer(), 1024 * 1024);
+        assert_eq!(chain_params.max_inflight_packets_per_channel, None);
+        assert!(!chain_params.frozen);
+        assert!(!chain_params.skip_root_check_on_load);
+        assert_eq!(chain_params.disabled_prefix("/ibc.core.client"), None);
+        assert_eq!(chain_params.chain_name, None);
+        assert!(!chain_params.verbose_logs);
+        assert_eq!(chain_params.event_journal_capacity(), 128);
+        assert_eq!(chain_params.max_consensus_states_per_client(), 100);
+        assert!(!chain_params.reject_legacy_type_urls);
+        assert_eq!(chain_params.compress_values_above, None);
+        assert!(chain_params.value_size_caps.is_empty());
+        assert_eq!(chain_params.max_commit_bytes, None);
+        assert!(chain_params.forbidden_write_prefixes.is_empty());
+    }
+
+    #[test]
+    fn params_stored_before_newer_fields_get_their_defaults() {
+        // Fields that a stored message predates decode as zero values
+        let stored = RawChainParams {
+            disabled_messages: vec!["/ibc.core.channel".to_owned()],
+            frozen: true,
+            ..RawChainParams::default()
+        };
+        let chain_params = ChainParams::from_raw(stored).unwrap();
+        assert_eq!(
+            chain_params,
+            ChainParams {
+                disabled_messages: vec!["/ibc.core.channel".to_owned()],
+                frozen: true,
+                ..ChainParams::default()
+            }
+        );
+        assert_eq!(
+            chain_params.max_tx_buffer_size(),
+            ChainParams::default_max_tx_buffer_size()
+        );
+        assert_eq!(
+            chain_params.max_tx_buffer_bytes_per_payer(),
+            ChainParams::default_max_tx_buffer_bytes_per_payer()
+        );
+    }
+}
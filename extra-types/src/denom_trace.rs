@@ -0,0 +1,101 @@
+use {
+    eclipse_ibc_known_proto::KnownProtoWithFrom,
+    eclipse_ibc_proto::eclipse::ibc::transfer::v1::DenomTrace as RawDenomTrace,
+    sha2::{Digest, Sha256},
+};
+
+/// Prefix of the denoms ICS-20 vouchers are known by outside the chain that
+/// minted them, which is followed by the hash of their trace.
+pub const IBC_DENOM_PREFIX: &str = "ibc/";
+
+/// Where an ICS-20 voucher comes from: the port and channel hops its tokens took
+/// and their denom on the source chain. Vouchers are recorded by full path, such
+/// as `transfer/channel-0/uatom`, but ibc-go chains and wallets refer to them by
+/// the hash of that path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DenomTrace {
+    /// `{port}/{channel}` hops joined with `/`, most recent first. Empty for
+    /// tokens native to this chain.
+    pub path: String,
+    pub base_denom: String,
+}
+
+impl DenomTrace {
+    /// The denom with its trace, such as `transfer/channel-0/uatom`.
+    #[must_use]
+    pub fn full_path(&self) -> String {
+        if self.path.is_empty() {
+            self.base_denom.clone()
+        } else {
+            format!("{}/{}", self.path, self.base_denom)
+        }
+    }
+
+    /// Uppercase hex SHA-256 of the full path, as ibc-go hashes denom traces.
+    #[must_use]
+    pub fn hash(&self) -> String {
+        hex::encode_upper(Sha256::digest(self.full_path()))
+    }
+
+    /// `ibc/{hash}` for vouchers, and the base denom for native tokens.
+    #[must_use]
+    pub fn ibc_denom(&self) -> String {
+        if self.path.is_empty() {
+            self.base_denom.clone()
+        } else {
+            format!("{IBC_DENOM_PREFIX}{}", self.hash())
+        }
+    }
+}
+
+impl From<DenomTrace> for RawDenomTrace {
+    fn from(DenomTrace { path, base_denom }: DenomTrace) -> Self {
+        Self { path, base_denom }
+    }
+}
+
+impl From<RawDenomTrace> for DenomTrace {
+    fn from(RawDenomTrace { path, base_denom }: RawDenomTrace) -> Self {
+        Self { path, base_denom }
+    }
+}
+
+impl KnownProtoWithFrom for DenomTrace {
+    type RawWithFrom = RawDenomTrace;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(path: &str, base_denom: &str) -> DenomTrace {
+        DenomTrace {
+            path: path.to_owned(),
+            base_denom: base_denom.to_owned(),
+        }
+    }
+
+    #[test]
+    fn hashes_match_ibc_go() {
+        // ATOM on Osmosis and OSMO on the Cosmos Hub
+        assert_eq!(
+            trace("transfer/channel-0", "uatom").ibc_denom(),
+            "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2"
+        );
+        assert_eq!(
+            trace("transfer/channel-141", "uosmo").ibc_denom(),
+            "ibc/14F9BC3E44B8A9C1BE1FB08980FAB87034C9905EF17CF2F5008FC085218811CC"
+        );
+        assert_eq!(
+            trace("transfer/channel-0/transfer/channel-1", "uatom").hash(),
+            "D219F3A490310B65BDC312B5A644B0D56FFF1789D894B902A49FBF9D2F560B32"
+        );
+    }
+
+    #[test]
+    fn native_denoms_are_not_hashed() {
+        let native = trace("", "lamports");
+        assert_eq!(native.full_path(), "lamports");
+        assert_eq!(native.ibc_denom(), "lamports");
+    }
+}
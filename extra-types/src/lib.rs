@@ -1,8 +1,30 @@
 mod all_module_ids;
+mod callback_versions;
+mod capabilities;
+mod chain_params;
 mod client_connections;
 mod consensus_heights;
+mod denom_trace;
+mod module_account_templates;
+mod pending_admin_change;
+mod store_write_batch_cursor;
 
 pub use {
-    all_module_ids::AllModuleIds, client_connections::ClientConnections,
+    all_module_ids::AllModuleIds,
+    callback_versions::CallbackVersions,
+    capabilities::Capabilities,
+    chain_params::{
+        ChainParams, ValueSizeCap, DEFAULT_EVENT_JOURNAL_CAPACITY,
+        DEFAULT_MAX_CONSENSUS_STATES_PER_CLIENT, DEFAULT_MAX_TX_BUFFER_BYTES_PER_PAYER,
+        DEFAULT_MAX_TX_BUFFER_SIZE,
+    },
+    client_connections::ClientConnections,
     consensus_heights::ConsensusHeights,
+    denom_trace::{DenomTrace, IBC_DENOM_PREFIX},
+    module_account_templates::{
+        ModuleAccountTemplates, PdaSeedTemplate, SeedPart, MAX_ACCOUNT_TEMPLATES, MAX_SEED_LEN,
+        MAX_TEMPLATE_SEEDS,
+    },
+    pending_admin_change::PendingAdminChange,
+    store_write_batch_cursor::StoreWriteBatchCursor,
 };
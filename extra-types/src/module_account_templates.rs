@@ -0,0 +1,232 @@
+use {
+    anyhow::{anyhow, bail},
+    eclipse_ibc_known_proto::KnownProtoWithFrom,
+    eclipse_ibc_proto::eclipse::ibc::port::v1::{
+        seed_part::Part as RawSeedPartKind, ModuleAccountTemplates as RawModuleAccountTemplates,
+        PdaSeedTemplate as RawPdaSeedTemplate, SeedPart as RawSeedPart,
+    },
+    ibc::core::{
+        ics04_channel::packet::Sequence,
+        ics24_host::identifier::{ChannelId, PortId},
+    },
+};
+
+/// Maximum number of account templates a module can register.
+pub const MAX_ACCOUNT_TEMPLATES: usize = 4;
+
+/// Solana allows 16 seeds per PDA, one of which is taken by the bump seed.
+pub const MAX_TEMPLATE_SEEDS: usize = 15;
+
+/// Maximum length of a single seed, as enforced by Solana.
+pub const MAX_SEED_LEN: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SeedPart {
+    Literal(Vec<u8>),
+    PortId,
+    ChannelId,
+    Sequence,
+}
+
+/// Seeds of a PDA of a module's own program, with placeholders that are filled in
+/// from the callback being invoked.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PdaSeedTemplate {
+    pub seeds: Vec<SeedPart>,
+}
+
+impl PdaSeedTemplate {
+    /// Fills in the placeholders of the template, or returns `None` if it needs a
+    /// sequence and the callback is not for a packet.
+    #[must_use]
+    pub fn resolve(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        sequence: Option<Sequence>,
+    ) -> Option<Vec<Vec<u8>>> {
+        self.seeds
+            .iter()
+            .map(|seed| match seed {
+                SeedPart::Literal(literal) => Some(literal.clone()),
+                SeedPart::PortId => Some(port_id.as_str().as_bytes().to_vec()),
+                SeedPart::ChannelId => Some(channel_id.as_str().as_bytes().to_vec()),
+                SeedPart::Sequence => {
+                    sequence.map(|sequence| u64::from(sequence).to_be_bytes().to_vec())
+                }
+            })
+            .collect()
+    }
+}
+
+impl From<SeedPart> for RawSeedPart {
+    fn from(seed_part: SeedPart) -> Self {
+        let part = match seed_part {
+            SeedPart::Literal(literal) => RawSeedPartKind::Literal(literal),
+            SeedPart::PortId => RawSeedPartKind::PortId(true),
+            SeedPart::ChannelId => RawSeedPartKind::ChannelId(true),
+            SeedPart::Sequence => RawSeedPartKind::Sequence(true),
+        };
+        Self { part: Some(part) }
+    }
+}
+
+impl TryFrom<RawSeedPart> for SeedPart {
+    type Error = anyhow::Error;
+
+    fn try_from(RawSeedPart { part }: RawSeedPart) -> Result<Self, Self::Error> {
+        match part.ok_or_else(|| anyhow!("seed part cannot be empty"))? {
+            RawSeedPartKind::Literal(literal) => {
+                if literal.len() > MAX_SEED_LEN {
+                    bail!(
+                        "literal seed is {} bytes long, more than the maximum of {MAX_SEED_LEN}",
+                        literal.len()
+                    );
+                }
+                Ok(Self::Literal(literal))
+            }
+            RawSeedPartKind::PortId(_) => Ok(Self::PortId),
+            RawSeedPartKind::ChannelId(_) => Ok(Self::ChannelId),
+            RawSeedPartKind::Sequence(_) => Ok(Self::Sequence),
+        }
+    }
+}
+
+impl From<PdaSeedTemplate> for RawPdaSeedTemplate {
+    fn from(PdaSeedTemplate { seeds }: PdaSeedTemplate) -> Self {
+        Self {
+            seeds: seeds.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<RawPdaSeedTemplate> for PdaSeedTemplate {
+    type Error = anyhow::Error;
+
+    fn try_from(RawPdaSeedTemplate { seeds }: RawPdaSeedTemplate) -> Result<Self, Self::Error> {
+        if seeds.is_empty() || seeds.len() > MAX_TEMPLATE_SEEDS {
+            bail!(
+                "PDA seed template has {} seeds; it must have between 1 and {MAX_TEMPLATE_SEEDS}",
+                seeds.len()
+            );
+        }
+        Ok(Self {
+            seeds: seeds
+                .into_iter()
+                .map(SeedPart::try_from)
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+impl KnownProtoWithFrom for PdaSeedTemplate {
+    type RawWithFrom = RawPdaSeedTemplate;
+}
+
+/// PDAs of a module's program that are passed to its callbacks. Since the
+/// addresses are always derived from the module's own program ID, a module can
+/// only ever be handed its own PDAs, which it then signs for itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleAccountTemplates {
+    pub templates: Vec<PdaSeedTemplate>,
+}
+
+impl ModuleAccountTemplates {
+    /// # Errors
+    /// Returns an error if there are more than [`MAX_ACCOUNT_TEMPLATES`] templates.
+    pub fn new(templates: Vec<PdaSeedTemplate>) -> anyhow::Result<Self> {
+        if templates.len() > MAX_ACCOUNT_TEMPLATES {
+            bail!(
+                "{} account templates were given, more than the maximum of \
+                 {MAX_ACCOUNT_TEMPLATES}",
+                templates.len()
+            );
+        }
+        Ok(Self { templates })
+    }
+}
+
+impl From<ModuleAccountTemplates> for RawModuleAccountTemplates {
+    fn from(ModuleAccountTemplates { templates }: ModuleAccountTemplates) -> Self {
+        Self {
+            templates: templates.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<RawModuleAccountTemplates> for ModuleAccountTemplates {
+    type Error = anyhow::Error;
+
+    fn try_from(
+        RawModuleAccountTemplates { templates }: RawModuleAccountTemplates,
+    ) -> Result<Self, Self::Error> {
+        Self::new(
+            templates
+                .into_iter()
+                .map(PdaSeedTemplate::try_from)
+                .collect::<anyhow::Result<_>>()?,
+        )
+    }
+}
+
+impl KnownProtoWithFrom for ModuleAccountTemplates {
+    type RawWithFrom = RawModuleAccountTemplates;
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, eclipse_ibc_known_proto::KnownProto};
+
+    fn literal_part(len: usize) -> RawSeedPart {
+        SeedPart::Literal(vec![7; len]).into()
+    }
+
+    #[test]
+    fn templates_are_validated() {
+        let template = |seeds: Vec<RawSeedPart>| RawPdaSeedTemplate { seeds };
+
+        PdaSeedTemplate::try_from(template(vec![literal_part(MAX_SEED_LEN)])).unwrap();
+        PdaSeedTemplate::try_from(template(vec![literal_part(MAX_SEED_LEN + 1)])).unwrap_err();
+        PdaSeedTemplate::try_from(template(vec![])).unwrap_err();
+        PdaSeedTemplate::try_from(template(vec![literal_part(1); MAX_TEMPLATE_SEEDS])).unwrap();
+        PdaSeedTemplate::try_from(template(vec![literal_part(1); MAX_TEMPLATE_SEEDS + 1]))
+            .unwrap_err();
+        PdaSeedTemplate::try_from(template(vec![RawSeedPart { part: None }])).unwrap_err();
+
+        let templates = vec![PdaSeedTemplate::default(); MAX_ACCOUNT_TEMPLATES + 1];
+        ModuleAccountTemplates::new(templates).unwrap_err();
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        let template = PdaSeedTemplate {
+            seeds: vec![
+                SeedPart::Literal(b"escrow".to_vec()),
+                SeedPart::PortId,
+                SeedPart::ChannelId,
+                SeedPart::Sequence,
+            ],
+        };
+        let port_id = PortId::transfer();
+        let channel_id = ChannelId::new(3);
+
+        assert_eq!(
+            template.resolve(&port_id, &channel_id, Some(Sequence::from(258))),
+            Some(vec![
+                b"escrow".to_vec(),
+                b"transfer".to_vec(),
+                b"channel-3".to_vec(),
+                vec![0, 0, 0, 0, 0, 0, 1, 2],
+            ]),
+        );
+        assert_eq!(template.resolve(&port_id, &channel_id, None), None);
+
+        let templates = ModuleAccountTemplates {
+            templates: vec![template],
+        };
+        assert_eq!(
+            ModuleAccountTemplates::decode(&*templates.clone().encode()).unwrap(),
+            templates,
+        );
+    }
+}
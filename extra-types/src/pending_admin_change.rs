@@ -0,0 +1,45 @@
+use {
+    eclipse_ibc_known_proto::KnownProtoWithFrom,
+    eclipse_ibc_proto::eclipse::ibc::admin::v1::PendingAdminChange as RawPendingAdminChange,
+};
+
+/// An admin change proposed by the current admin, which `new_admin` can accept
+/// once the slot is past `effective_after_slot`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingAdminChange {
+    /// Base58 public key of the new admin
+    pub new_admin: String,
+    pub effective_after_slot: u64,
+}
+
+impl From<PendingAdminChange> for RawPendingAdminChange {
+    fn from(
+        PendingAdminChange {
+            new_admin,
+            effective_after_slot,
+        }: PendingAdminChange,
+    ) -> Self {
+        Self {
+            new_admin,
+            effective_after_slot,
+        }
+    }
+}
+
+impl From<RawPendingAdminChange> for PendingAdminChange {
+    fn from(
+        RawPendingAdminChange {
+            new_admin,
+            effective_after_slot,
+        }: RawPendingAdminChange,
+    ) -> Self {
+        Self {
+            new_admin,
+            effective_after_slot,
+        }
+    }
+}
+
+impl KnownProtoWithFrom for PendingAdminChange {
+    type RawWithFrom = RawPendingAdminChange;
+}
@@ -0,0 +1,48 @@
+use {
+    anyhow::anyhow, eclipse_ibc_known_proto::KnownProtoWithFrom,
+    eclipse_ibc_proto::eclipse::ibc::admin::v1::StoreWriteBatchCursor as RawStoreWriteBatchCursor,
+};
+
+/// Position of the store write batch that is currently being applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoreWriteBatchCursor {
+    pub next_chunk_index: u64,
+    pub next_chunk_hash: [u8; 32],
+}
+
+impl From<StoreWriteBatchCursor> for RawStoreWriteBatchCursor {
+    fn from(
+        StoreWriteBatchCursor {
+            next_chunk_index,
+            next_chunk_hash,
+        }: StoreWriteBatchCursor,
+    ) -> Self {
+        Self {
+            next_chunk_index,
+            next_chunk_hash: next_chunk_hash.to_vec(),
+        }
+    }
+}
+
+impl TryFrom<RawStoreWriteBatchCursor> for StoreWriteBatchCursor {
+    type Error = anyhow::Error;
+
+    fn try_from(
+        RawStoreWriteBatchCursor {
+            next_chunk_index,
+            next_chunk_hash,
+        }: RawStoreWriteBatchCursor,
+    ) -> Result<Self, Self::Error> {
+        let next_chunk_hash = next_chunk_hash.try_into().map_err(|hash: Vec<u8>| {
+            anyhow!("next chunk hash must be 32 bytes, got {}", hash.len())
+        })?;
+        Ok(Self {
+            next_chunk_index,
+            next_chunk_hash,
+        })
+    }
+}
+
+impl KnownProtoWithFrom for StoreWriteBatchCursor {
+    type RawWithFrom = RawStoreWriteBatchCursor;
+}
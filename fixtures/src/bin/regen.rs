@@ -0,0 +1,9 @@
+//! Rewrites the goldens under `fixtures/goldens` from their builders.
+
+fn main() -> anyhow::Result<()> {
+    eclipse_ibc_fixtures::goldens::regenerate()?;
+    for golden in eclipse_ibc_fixtures::goldens::GOLDENS {
+        println!("Wrote {}", golden.name);
+    }
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use {
+    eclipse_ibc_light_client::{eclipse_chain, EclipseClientState, EclipseHeader},
+    ibc::core::{
+        ics02_client::height::Height,
+        ics23_commitment::{commitment::CommitmentRoot, specs::ProofSpecs},
+    },
+    ibc_proto::{
+        google::protobuf,
+        ibc::{
+            core::commitment::v1::MerkleRoot,
+            lightclients::tendermint::v1::{
+                ClientState as RawTmClientState, ConsensusState as RawTmConsensusState, Fraction,
+            },
+        },
+    },
+    prost::Message as _,
+    solana_sdk::hash::hashv,
+    tendermint::time::Time as TendermintTime,
+};
+
+/// Chain name of the Eclipse chain that fixture clients track.
+pub const CHAIN_NAME: &str = "test";
+
+/// Chain ID of the Tendermint chain that fixture clients track.
+pub const TENDERMINT_CHAIN_ID: &str = "cosmoshub-4";
+
+pub const TENDERMINT_CLIENT_STATE_TYPE_URL: &str = "/ibc.lightclients.tendermint.v1.ClientState";
+
+pub const TENDERMINT_CONSENSUS_STATE_TYPE_URL: &str =
+    "/ibc.lightclients.tendermint.v1.ConsensusState";
+
+/// Header of the fixture Eclipse chain at `revision_height`, with a fixed root and
+/// timestamp so that encodings do not change between runs.
+#[must_use]
+pub fn eclipse_header(revision_height: u64) -> EclipseHeader {
+    EclipseHeader {
+        height: Height::new(0, revision_height).unwrap(),
+        commitment_root: CommitmentRoot::from_bytes(&[1; 32]),
+        timestamp: TendermintTime::unix_epoch(),
+    }
+}
+
+/// Unfrozen client of the fixture Eclipse chain, at `revision_height`.
+#[must_use]
+pub fn eclipse_client_state(revision_height: u64) -> EclipseClientState {
+    EclipseClientState {
+        chain_id: eclipse_chain::chain_id(CHAIN_NAME),
+        latest_header: eclipse_header(revision_height),
+        frozen_height: None,
+        genesis_hash: None,
+        revision_number: eclipse_chain::DEFAULT_REVISION_NUMBER,
+        valid_duration: None,
+    }
+}
+
+/// Client of the fixture Tendermint chain at `revision_height`, with the
+/// parameters a Cosmos Hub client is created with.
+#[must_use]
+pub fn raw_tendermint_client_state(revision_height: u64) -> RawTmClientState {
+    RawTmClientState {
+        chain_id: TENDERMINT_CHAIN_ID.to_owned(),
+        trust_level: Some(Fraction {
+            numerator: 1,
+            denominator: 3,
+        }),
+        trusting_period: Some(protobuf::Duration {
+            seconds: 14 * 24 * 60 * 60,
+            nanos: 0,
+        }),
+        unbonding_period: Some(protobuf::Duration {
+            seconds: 21 * 24 * 60 * 60,
+            nanos: 0,
+        }),
+        max_clock_drift: Some(protobuf::Duration {
+            seconds: 10,
+            nanos: 0,
+        }),
+        frozen_height: None,
+        latest_height: Some(Height::new(4, revision_height).unwrap().into()),
+        proof_specs: ProofSpecs::default().into(),
+        upgrade_path: vec!["upgrade".to_owned(), "upgradedIBCState".to_owned()],
+        allow_update_after_expiry: false,
+        allow_update_after_misbehaviour: false,
+    }
+}
+
+/// [`raw_tendermint_client_state`] as stored by the IBC program.
+#[must_use]
+pub fn tendermint_client_state_any(revision_height: u64) -> protobuf::Any {
+    protobuf::Any {
+        type_url: TENDERMINT_CLIENT_STATE_TYPE_URL.to_owned(),
+        value: raw_tendermint_client_state(revision_height).encode_to_vec(),
+    }
+}
+
+/// Consensus state of the fixture Tendermint chain at `revision_height`, with a
+/// block every 6 seconds and a root and validator set hash that differ at every
+/// height, like those of a live chain.
+#[must_use]
+pub fn raw_tendermint_consensus_state(revision_height: u64) -> RawTmConsensusState {
+    let height = revision_height.to_le_bytes();
+    RawTmConsensusState {
+        timestamp: Some(protobuf::Timestamp {
+            seconds: 1_700_000_000 + i64::try_from(revision_height).unwrap() * 6,
+            nanos: 0,
+        }),
+        root: Some(MerkleRoot {
+            hash: hashv(&[b"root", &height]).to_bytes().to_vec(),
+        }),
+        next_validators_hash: hashv(&[b"validators", &height]).to_bytes().to_vec(),
+    }
+}
+
+/// [`raw_tendermint_consensus_state`] as stored by the IBC program.
+#[must_use]
+pub fn tendermint_consensus_state_any(revision_height: u64) -> protobuf::Any {
+    protobuf::Any {
+        type_url: TENDERMINT_CONSENSUS_STATE_TYPE_URL.to_owned(),
+        value: raw_tendermint_consensus_state(revision_height).encode_to_vec(),
+    }
+}
@@ -0,0 +1,267 @@
+use {
+    crate::clients,
+    anyhow::{bail, ensure},
+    eclipse_ibc_extra_types::{ChainParams, ClientConnections},
+    eclipse_ibc_known_proto::KnownAnyProto,
+    eclipse_ibc_light_client::EclipseConsensusState,
+    eclipse_ibc_state::{
+        internal_path::{
+            ChainParamsPath, ClientTypeUrlPath, ConsensusHeightsPath, StateInitializedPath,
+        },
+        IbcAccountData, IbcMetadata, IbcMetadataV4, IbcState, LegacyIbcMetadata,
+    },
+    ibc::core::{
+        ics03_connection::connection::ConnectionEnd,
+        ics04_channel::{channel::ChannelEnd, commitment::PacketCommitment, packet::Sequence},
+        ics24_host::{
+            identifier::{ChannelId, ClientId, ConnectionId, PortId},
+            path::{
+                ChannelEndPath, ClientConnectionPath, ClientConsensusStatePath, ClientStatePath,
+                CommitmentPath, ConnectionPath, SeqAckPath, SeqRecvPath, SeqSendPath,
+            },
+        },
+    },
+    ibc_proto::ibc::core::{
+        channel::v1::{
+            Channel as RawChannel, Counterparty as RawChannelCounterparty, Order as RawOrder,
+            State as RawChannelState,
+        },
+        commitment::v1::MerklePrefix as RawMerklePrefix,
+        connection::v1::{
+            ConnectionEnd as RawConnectionEnd, Counterparty as RawConnectionCounterparty,
+            State as RawConnectionState, Version as RawConnectionVersion,
+        },
+    },
+    solana_sdk::clock::Slot,
+};
+
+/// Account data format versions that [`FixtureStore::account_dump`] can produce.
+/// Version 0 is the legacy layout, the bincode-encoded store followed by the
+/// metadata.
+pub const ACCOUNT_FORMAT_VERSIONS: [u8; 7] = [0, 1, 2, 3, 4, 5, 6];
+
+/// Same as the header magic in `eclipse_ibc_state`, which the dumps spell out so
+/// that older format versions can still be produced.
+const ACCOUNT_DATA_MAGIC: [u8; 4] = *b"EIBC";
+
+/// Slot the fixture state is initialized at.
+const INIT_SLOT: Slot = 1;
+/// Slot the fixture clients, connection and channels are written at.
+const SLOT: Slot = 2;
+
+/// Builds IBC stores holding a chosen number of Eclipse clients, channels and
+/// packets, written the way the IBC program writes them.
+///
+/// Clients are named `xx-eclipse-{i}` and track the chain in [`clients`]. Channels
+/// are open, unordered transfer channels on `connection-0`, which uses the first
+/// client, and each has the same number of outstanding packet commitments.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FixtureStore {
+    clients: u64,
+    channels: u64,
+    packets: u64,
+}
+
+impl FixtureStore {
+    #[must_use]
+    pub fn with_clients(clients: u64) -> Self {
+        Self {
+            clients,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn with_channels(self, channels: u64) -> Self {
+        Self { channels, ..self }
+    }
+
+    /// Sets the number of packet commitments on each channel.
+    #[must_use]
+    pub fn with_packets(self, packets: u64) -> Self {
+        Self { packets, ..self }
+    }
+
+    /// The canonical store behind the account dump goldens.
+    #[must_use]
+    pub fn canonical() -> Self {
+        Self::with_clients(2).with_channels(2).with_packets(3)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if channels are requested without any clients for their connection.
+    #[must_use]
+    pub fn build(&self) -> IbcAccountData {
+        assert!(
+            self.channels == 0 || self.clients > 0,
+            "channels need a client for their connection"
+        );
+
+        let mut account_data = IbcAccountData {
+            metadata: IbcMetadata {
+                client_id_counter: self.clients,
+                connection_id_counter: u64::from(self.channels > 0),
+                channel_id_counter: self.channels,
+                ..IbcMetadata::default()
+            },
+            ..IbcAccountData::default()
+        };
+
+        let mut state = IbcState::new(&account_data.store, INIT_SLOT);
+        state.set(&StateInitializedPath, ());
+        state.set(&ChainParamsPath, ChainParams::default());
+        let root_hash = state.commit().unwrap();
+        account_data.metadata.record_commit(root_hash);
+
+        let mut state = IbcState::new(&account_data.store, SLOT);
+        for index in 0..self.clients {
+            write_client(&mut state, &client_id(index));
+        }
+        if self.channels > 0 {
+            write_connection(&mut state);
+        }
+        for index in 0..self.channels {
+            write_channel(&mut state, &ChannelId::new(index), self.packets);
+        }
+        let root_hash = state.commit().unwrap();
+        account_data.metadata.record_commit(root_hash);
+
+        account_data
+    }
+
+    /// Encodes [`Self::build`] at one of [`ACCOUNT_FORMAT_VERSIONS`].
+    pub fn account_dump(&self, version: u8) -> anyhow::Result<Vec<u8>> {
+        let account_data = self.build();
+        // Metadata before version 4 has no commit checksum, and before version 5
+        // no revision number
+        let legacy_metadata = LegacyIbcMetadata::from(&account_data.metadata);
+        match version {
+            0 => Ok(bincode::serialize(&(&account_data.store, legacy_metadata))?),
+            // Version 1 is the header layout without the preimages segment,
+            // version 2 adds it, version 3 the event journal segment and version 6
+            // the stale node index segment
+            1..=5 => {
+                let metadata = if version >= 5 {
+                    bincode::serialize(&account_data.metadata)?
+                } else if version >= 4 {
+                    bincode::serialize(&IbcMetadataV4::from(&account_data.metadata))?
+                } else {
+                    bincode::serialize(&legacy_metadata)?
+                };
+                let mut data = ACCOUNT_DATA_MAGIC.to_vec();
+                data.push(version);
+                data.extend_from_slice(&u32::try_from(metadata.len())?.to_le_bytes());
+                data.extend_from_slice(&metadata);
+                bincode::serialize_into(&mut data, &account_data.store)?;
+                if version >= 2 {
+                    bincode::serialize_into(&mut data, &account_data.store.preimages()?)?;
+                }
+                if version >= 3 {
+                    bincode::serialize_into(&mut data, &account_data.event_journal)?;
+                }
+                Ok(data)
+            }
+            6 => {
+                let data = account_data.to_bytes()?;
+                ensure!(
+                    data[ACCOUNT_DATA_MAGIC.len()] == version,
+                    "IBC account data is no longer written at format version {version}"
+                );
+                Ok(data)
+            }
+            _ => bail!("no fixture for IBC account data format version {version}"),
+        }
+    }
+}
+
+fn client_id(index: u64) -> ClientId {
+    format!("xx-eclipse-{index}").parse().unwrap()
+}
+
+fn write_client(state: &mut IbcState, client_id: &ClientId) {
+    let client_state = clients::eclipse_client_state(1);
+    let height = client_state.latest_header.height;
+    let consensus_state = EclipseConsensusState::from(client_state.latest_header.clone());
+
+    let client_state = client_state.encode_as_any();
+    state.set(
+        &ClientTypeUrlPath(client_id.clone()),
+        client_state.type_url.clone(),
+    );
+    state
+        .set_any(&ClientStatePath::new(client_id), client_state)
+        .unwrap();
+    state
+        .set_any(
+            &ClientConsensusStatePath::new(client_id, &height),
+            consensus_state.encode_as_any(),
+        )
+        .unwrap();
+    state
+        .update(
+            &ConsensusHeightsPath(client_id.clone()),
+            |consensus_heights| {
+                consensus_heights.heights.insert(height);
+            },
+        )
+        .unwrap();
+}
+
+fn write_connection(state: &mut IbcState) {
+    let client_id = client_id(0);
+    let connection_id = ConnectionId::new(0);
+    let connection_end = ConnectionEnd::try_from(RawConnectionEnd {
+        client_id: client_id.to_string(),
+        versions: vec![RawConnectionVersion {
+            identifier: "1".to_owned(),
+            features: vec!["ORDER_ORDERED".to_owned(), "ORDER_UNORDERED".to_owned()],
+        }],
+        state: RawConnectionState::Open as i32,
+        counterparty: Some(RawConnectionCounterparty {
+            client_id: client_id.to_string(),
+            connection_id: connection_id.to_string(),
+            prefix: Some(RawMerklePrefix {
+                key_prefix: b"ibc".to_vec(),
+            }),
+        }),
+        delay_period: 0,
+    })
+    .unwrap();
+    state.set(&ConnectionPath::new(&connection_id), connection_end);
+    state.set(
+        &ClientConnectionPath::new(&client_id),
+        ClientConnections {
+            connections: [connection_id].into_iter().collect(),
+        },
+    );
+}
+
+fn write_channel(state: &mut IbcState, channel_id: &ChannelId, packets: u64) {
+    let port_id = PortId::transfer();
+    let channel_end = ChannelEnd::try_from(RawChannel {
+        state: RawChannelState::Open as i32,
+        ordering: RawOrder::Unordered as i32,
+        counterparty: Some(RawChannelCounterparty {
+            port_id: port_id.to_string(),
+            channel_id: channel_id.to_string(),
+        }),
+        connection_hops: vec![ConnectionId::new(0).to_string()],
+        version: "ics20-1".to_owned(),
+    })
+    .unwrap();
+    state.set(&ChannelEndPath::new(&port_id, channel_id), channel_end);
+
+    state.set(
+        &SeqSendPath::new(&port_id, channel_id),
+        Sequence::from(packets + 1),
+    );
+    state.set(&SeqRecvPath::new(&port_id, channel_id), Sequence::from(1));
+    state.set(&SeqAckPath::new(&port_id, channel_id), Sequence::from(1));
+    for sequence in 1..=packets {
+        state.set(
+            &CommitmentPath::new(&port_id, channel_id, Sequence::from(sequence)),
+            PacketCommitment::from(vec![sequence as u8; 32]),
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use {
+    crate::{clients, proof_bundle::ProofBundle, FixtureStore},
+    anyhow::Context as _,
+    eclipse_ibc_known_proto::KnownAnyProto,
+    prost::Message as _,
+    std::{fs, path::PathBuf},
+};
+
+/// Height of the client state goldens.
+pub const CLIENT_STATE_HEIGHT: u64 = 5;
+
+/// A file under `goldens/` and the builder that produces it.
+pub struct Golden {
+    pub name: &'static str,
+    pub build: fn() -> anyhow::Result<Vec<u8>>,
+}
+
+/// Every golden, in the order `regen` writes them.
+pub const GOLDENS: &[Golden] = &[
+    Golden {
+        name: "account_v0.bin",
+        build: || FixtureStore::canonical().account_dump(0),
+    },
+    Golden {
+        name: "account_v1.bin",
+        build: || FixtureStore::canonical().account_dump(1),
+    },
+    Golden {
+        name: "account_v2.bin",
+        build: || FixtureStore::canonical().account_dump(2),
+    },
+    Golden {
+        name: "account_v3.bin",
+        build: || FixtureStore::canonical().account_dump(3),
+    },
+    Golden {
+        name: "account_v4.bin",
+        build: || FixtureStore::canonical().account_dump(4),
+    },
+    Golden {
+        name: "account_v5.bin",
+        build: || FixtureStore::canonical().account_dump(5),
+    },
+    Golden {
+        name: "account_v6.bin",
+        build: || FixtureStore::canonical().account_dump(6),
+    },
+    Golden {
+        name: "eclipse_client_state.bin",
+        build: || {
+            Ok(clients::eclipse_client_state(CLIENT_STATE_HEIGHT)
+                .encode_as_any()
+                .encode_to_vec())
+        },
+    },
+    Golden {
+        name: "tendermint_client_state.bin",
+        build: || Ok(clients::tendermint_client_state_any(CLIENT_STATE_HEIGHT).encode_to_vec()),
+    },
+    Golden {
+        name: "packet_commitment_proof.json",
+        build: || {
+            let json_str =
+                serde_json::to_string_pretty(&ProofBundle::packet_commitment().to_json())?;
+            Ok(format!("{json_str}\n").into_bytes())
+        },
+    },
+];
+
+/// Name of the account dump golden at a format version.
+#[must_use]
+pub fn account_dump_name(version: u8) -> String {
+    format!("account_v{version}.bin")
+}
+
+#[must_use]
+pub fn goldens_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("goldens")
+}
+
+/// Reads a golden by name.
+pub fn load(name: &str) -> anyhow::Result<Vec<u8>> {
+    let path = goldens_dir().join(name);
+    fs::read(&path).with_context(|| {
+        format!(
+            "Failed to read golden {}; run `cargo run -p eclipse-ibc-fixtures --bin regen`",
+            path.display()
+        )
+    })
+}
+
+/// Rewrites every golden from its builder.
+pub fn regenerate() -> anyhow::Result<()> {
+    let dir = goldens_dir();
+    fs::create_dir_all(&dir)?;
+    for Golden { name, build } in GOLDENS {
+        let data = build().with_context(|| format!("Failed to build golden {name}"))?;
+        fs::write(dir.join(name), data)
+            .with_context(|| format!("Failed to write golden {name}"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::ACCOUNT_FORMAT_VERSIONS};
+
+    #[test]
+    #[ignore = "binary goldens are not checked in yet; run `cargo run -p eclipse-ibc-fixtures --bin regen`"]
+    fn goldens_match_their_builders() {
+        for Golden { name, build } in GOLDENS {
+            assert_eq!(
+                load(name).unwrap(),
+                build().unwrap(),
+                "golden {name} is stale; run `cargo run -p eclipse-ibc-fixtures --bin regen` \
+                 after an intentional change"
+            );
+        }
+
+        for version in ACCOUNT_FORMAT_VERSIONS {
+            let name = account_dump_name(version);
+            assert!(GOLDENS.iter().any(|golden| golden.name == name), "{name}");
+        }
+    }
+}
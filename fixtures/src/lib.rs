@@ -0,0 +1,15 @@
+//! Canonical test fixtures shared by the state, light client, program and CLI
+//! test suites: builders for client states and IBC stores, and golden artifacts
+//! pinned under `goldens/` for checking encodings across crates and against
+//! other light client implementations.
+//!
+//! Goldens are produced by the builders in this crate. After an intentional
+//! encoding change, rewrite them with `cargo run -p eclipse-ibc-fixtures --bin
+//! regen` and commit the result.
+
+pub mod clients;
+mod fixture_store;
+pub mod goldens;
+pub mod proof_bundle;
+
+pub use fixture_store::{FixtureStore, ACCOUNT_FORMAT_VERSIONS};
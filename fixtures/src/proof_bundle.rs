@@ -0,0 +1,85 @@
+use {
+    eclipse_ibc_known_proto::KnownProto,
+    eclipse_ibc_light_client::{
+        eclipse_chain::{self, EclipseHeight},
+        EclipseHeader,
+    },
+    eclipse_ibc_state::{IbcState, IbcStore},
+    ibc::core::{
+        ics02_client::height::Height,
+        ics04_channel::{commitment::PacketCommitment, packet::Sequence},
+        ics23_commitment::commitment::CommitmentRoot,
+        ics24_host::{
+            identifier::{ChannelId, PortId},
+            path::CommitmentPath,
+        },
+    },
+    prost::Message as _,
+    serde_json::{json, Value},
+    solana_sdk::clock::Slot,
+    tendermint::time::Time as TendermintTime,
+};
+
+/// Slot the IBC program writes the packet commitment in.
+pub const SLOT: Slot = 41;
+const TIMESTAMP_SECS: i64 = 1_685_577_600;
+
+/// A packet commitment stored by the IBC program, with the header and proof a
+/// relayer gives a counterparty to verify it.
+#[derive(Clone, Debug)]
+pub struct ProofBundle {
+    pub path: CommitmentPath,
+    pub commitment: PacketCommitment,
+    /// Root of the IBC store, which the header commits to under the prefix.
+    pub state_root: CommitmentRoot,
+    pub header: EclipseHeader,
+    /// Encoded `MerkleProof`, as attached to IBC messages.
+    pub proof: Vec<u8>,
+}
+
+impl ProofBundle {
+    /// Stores a commitment in a fresh store and proves it, in the same form as the
+    /// CLI.
+    #[must_use]
+    pub fn packet_commitment() -> Self {
+        let path = CommitmentPath::new(&PortId::transfer(), &ChannelId::new(0), Sequence::from(1));
+        let commitment = PacketCommitment::from((0..32).collect::<Vec<u8>>());
+
+        let store = IbcStore::default();
+        let mut state = IbcState::new(&store, SLOT);
+        state.set(&path, commitment.clone());
+        state.commit().unwrap();
+
+        // The CLI builds the header for that slot's root, at height `SLOT + 1`
+        let state_root = state.get_root_option(SLOT).unwrap().unwrap();
+        let header = EclipseHeader {
+            height: Height::of_slot(SLOT).unwrap(),
+            commitment_root: state.get_commitment_root_option(SLOT).unwrap().unwrap(),
+            timestamp: TendermintTime::from_unix_timestamp(TIMESTAMP_SECS, 0).unwrap(),
+        };
+
+        let merkle_proof = eclipse_chain::merkle_proof(state.get_proof(&path).unwrap());
+
+        Self {
+            path,
+            commitment,
+            state_root,
+            header,
+            proof: merkle_proof.encode_to_vec(),
+        }
+    }
+
+    /// The intermediate bytes, hex-encoded, as pinned in the golden.
+    #[must_use]
+    pub fn to_json(&self) -> Value {
+        json!({
+            "path": self.path.to_string(),
+            "commitment": hex::encode(self.commitment.clone().into_vec()),
+            "stored_value": hex::encode(KnownProto::encode(self.commitment.clone())),
+            "state_root": hex::encode(self.state_root.as_bytes()),
+            "commitment_root": hex::encode(self.header.commitment_root.as_bytes()),
+            "header": hex::encode(KnownProto::encode(self.header.clone())),
+            "proof": hex::encode(&self.proof),
+        })
+    }
+}
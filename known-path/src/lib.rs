@@ -12,7 +12,7 @@ use {
         ics24_host::path::{
             AckPath, ChannelEndPath, ClientConnectionPath, ClientConsensusStatePath,
             ClientStatePath, CommitmentPath, ConnectionPath, PortPath, ReceiptPath, SeqAckPath,
-            SeqRecvPath, SeqSendPath,
+            SeqRecvPath, SeqSendPath, UpgradeClientPath,
         },
         router::ModuleId,
     },
@@ -72,3 +72,7 @@ impl KnownPath for SeqRecvPath {
 impl KnownPath for SeqSendPath {
     type Value = Sequence;
 }
+
+impl KnownPath for UpgradeClientPath {
+    type Value = protobuf::Any;
+}
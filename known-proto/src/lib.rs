@@ -111,6 +111,11 @@ impl KnownAnyProto for TendermintClientState {
     fn type_url() -> String {
         TENDERMINT_CLIENT_STATE_TYPE_URL.to_owned()
     }
+
+    // The raw message is already an `Any`, which must not be wrapped again
+    fn encode_as_any(self) -> protobuf::Any {
+        self.into_raw()
+    }
 }
 
 impl KnownProtoWithFrom for TendermintConsensusState {
@@ -121,6 +126,11 @@ impl KnownAnyProto for TendermintConsensusState {
     fn type_url() -> String {
         TENDERMINT_CONSENSUS_STATE_TYPE_URL.to_owned()
     }
+
+    // The raw message is already an `Any`, which must not be wrapped again
+    fn encode_as_any(self) -> protobuf::Any {
+        self.into_raw()
+    }
 }
 
 impl KnownProtoWithFrom for ConnectionEnd {
@@ -188,6 +198,14 @@ impl KnownProtoWithFrom for Sequence {
     type RawWithFrom = u64;
 }
 
+impl KnownProtoWithFrom for u64 {
+    type RawWithFrom = Self;
+}
+
+impl KnownProtoWithFrom for String {
+    type RawWithFrom = Self;
+}
+
 impl KnownProtoWithFrom for () {
     type RawWithFrom = ();
 }
@@ -200,6 +218,16 @@ impl KnownProtoWithFrom for tendermint::time::Time {
     type RawWithFrom = tendermint_proto::google::protobuf::Timestamp;
 }
 
+/// Latest timestamp that is stored or accepted, 2200-01-01T00:00:00Z in
+/// nanoseconds since the Unix epoch. Timestamps are `u64` nanoseconds, which run
+/// out in 2554, and converting them to `tendermint::Time` fails even sooner, in
+/// 2262. Bounding them well before that keeps timeout arithmetic clear of both.
+pub const MAX_TIMESTAMP_NANOS: u64 = 7_258_118_400_000_000_000;
+
+/// Timestamps are stored as their nanoseconds since the Unix epoch. Zero is how
+/// IBC spells "no timestamp", and decodes as `Timestamp::none()`; the IBC
+/// handler never stores it as a time anything happened at. Values after
+/// [`MAX_TIMESTAMP_NANOS`] are rejected rather than decoded.
 impl KnownProto for ibc::core::timestamp::Timestamp {
     type Raw = u64;
 
@@ -208,6 +236,12 @@ impl KnownProto for ibc::core::timestamp::Timestamp {
     }
 
     fn from_raw(raw: Self::Raw) -> anyhow::Result<Self> {
+        if raw > MAX_TIMESTAMP_NANOS {
+            bail!(
+                "timestamp of {raw}ns is after the latest supported timestamp, \
+                 {MAX_TIMESTAMP_NANOS}ns (2200-01-01T00:00:00Z)"
+            );
+        }
         Ok(ibc::core::timestamp::Timestamp::from_nanoseconds(raw)?)
     }
 }
@@ -2,53 +2,227 @@ use {
     core::time::Duration,
     ibc::core::{
         ics02_client::{error::ClientError, height::Height},
-        ics23_commitment::specs::ProofSpecs,
-        ics24_host::identifier::ChainId,
+        ics23_commitment::{
+            commitment::{CommitmentPrefix, CommitmentRoot},
+            specs::ProofSpecs,
+        },
+        ics24_host::{identifier::ChainId, path::Path},
+    },
+    ibc_proto::{
+        ibc::core::commitment::v1::MerkleProof as RawMerkleProof,
+        ics23::CommitmentProof as IbcRawCommitmentProof,
+    },
+    ics23::{
+        calculate_existence_root, commitment_proof, CommitmentProof, ExistenceProof,
+        HostFunctionsManager,
+    },
+    prost::Message as _,
+    solana_sdk::{
+        clock::{Slot, UnixTimestamp},
+        sysvar::clock::Clock,
     },
-    solana_sdk::{clock::Slot, sysvar::clock::Clock},
     tendermint::time::Time as TendermintTime,
 };
 
+/// Target slot time, used to estimate the time of past slots
+pub const EXPECTED_SLOT_TIME: Duration = Duration::from_millis(400);
 /// Target slot time is 400ms but in practice Solana goes up to 600ms
 pub const MAX_EXPECTED_SLOT_TIME: Duration = Duration::from_millis(600);
 pub const IBC_MESSAGE_VALID_DURATION: Duration = Duration::from_secs(3600);
 pub const CHAIN_NAME_PREFIX: &str = "eclipse";
 pub const UPGRADE_PREFIX: &str = "eclipse-upgrade";
 pub const COMMITMENT_PREFIX: &[u8] = b"ibc";
-const REVISION_NUMBER: u64 = 0;
+/// Revision number of this chain until an admin bumps it with
+/// `MsgSetRevisionNumber`, as is done for upgrades that restart IBC heights from
+/// the point of view of counterparties.
+pub const DEFAULT_REVISION_NUMBER: u64 = 0;
 
 pub fn chain_id(chain_name: &str) -> ChainId {
-    ChainId::new(format!("{CHAIN_NAME_PREFIX}-{chain_name}"), REVISION_NUMBER)
+    chain_id_with_revision(chain_name, DEFAULT_REVISION_NUMBER)
+}
+
+/// The chain ID of this chain in revision `revision_number`, which ibc-rs
+/// expects as the suffix of the chain ID.
+pub fn chain_id_with_revision(chain_name: &str, revision_number: u64) -> ChainId {
+    ChainId::new(format!("{CHAIN_NAME_PREFIX}-{chain_name}"), revision_number)
+}
+
+/// `chain_id` with its revision number replaced by `revision_number`.
+pub fn chain_id_in_revision(chain_id: &ChainId, revision_number: u64) -> ChainId {
+    let suffix = format!("-{}", chain_id.version());
+    let name = chain_id
+        .as_str()
+        .strip_suffix(&suffix)
+        .unwrap_or_else(|| chain_id.as_str());
+    ChainId::new(name.to_owned(), revision_number)
 }
 
-pub fn height_of_slot(slot: Slot) -> Result<Height, ClientError> {
-    // clock.slot starts at 0, so we add 1 for the height
+/// The height of `slot` in revision `revision_number`. Slots keep counting across
+/// revisions, so the revision height of a slot is the same in every revision.
+pub fn height_of_slot_with_revision(
+    slot: Slot,
+    revision_number: u64,
+) -> Result<Height, ClientError> {
     let revision_height = slot
         .checked_add(1)
         .ok_or_else(|| ClientError::InvalidHeight)?;
-    Height::new(REVISION_NUMBER, revision_height)
+    Height::new(revision_number, revision_height)
 }
 
-pub fn slot_of_height(height: Height) -> Result<Slot, ClientError> {
-    if height.revision_number() != REVISION_NUMBER {
+/// The slot of `height` while `active_revision` is the revision of this chain.
+/// Heights of previous revisions are accepted, since historical proofs refer to
+/// them, but heights of later revisions do not exist yet.
+pub fn slot_of_height(height: &Height, active_revision: u64) -> Result<Slot, ClientError> {
+    if height.revision_number() > active_revision {
         return Err(ClientError::InvalidHeight);
     }
+    height.to_slot()
+}
+
+/// Conversions between Solana slots and IBC heights of this chain. `clock.slot`
+/// starts at 0 while IBC heights start at 1, so the state committed in slot `n` is
+/// at revision height `n + 1`. Slots and revision heights are both `u64`, so these
+/// are the only places that offset should be applied.
+pub trait EclipseHeight: Sized {
+    /// The height of `slot` in `DEFAULT_REVISION_NUMBER`. The host uses
+    /// [`height_of_slot_with_revision`] with its active revision instead.
+    fn of_slot(slot: Slot) -> Result<Self, ClientError>;
+
+    /// The slot of this height, whatever its revision. Use [`slot_of_height`] to
+    /// also reject revisions after the active one.
+    fn to_slot(&self) -> Result<Slot, ClientError>;
+
+    /// The next height in the revision of this height.
+    fn succ(&self) -> Result<Self, ClientError>;
+
+    /// The previous height in the revision of this height, which does not exist
+    /// for the height of slot 0.
+    fn pred(&self) -> Result<Self, ClientError>;
+}
+
+impl EclipseHeight for Height {
+    fn of_slot(slot: Slot) -> Result<Self, ClientError> {
+        height_of_slot_with_revision(slot, DEFAULT_REVISION_NUMBER)
+    }
+
+    fn to_slot(&self) -> Result<Slot, ClientError> {
+        self.revision_height()
+            .checked_sub(1)
+            .ok_or_else(|| ClientError::InvalidHeight)
+    }
 
-    // clock.slot starts at 0, so we subtract 1 from the height
-    height
-        .revision_height()
-        .checked_sub(1)
-        .ok_or_else(|| ClientError::InvalidHeight)
+    fn succ(&self) -> Result<Self, ClientError> {
+        let revision_height = self
+            .revision_height()
+            .checked_add(1)
+            .ok_or_else(|| ClientError::InvalidHeight)?;
+        Height::new(self.revision_number(), revision_height)
+    }
+
+    fn pred(&self) -> Result<Self, ClientError> {
+        let revision_height = self
+            .revision_height()
+            .checked_sub(1)
+            .ok_or_else(|| ClientError::InvalidHeight)?;
+        Height::new(self.revision_number(), revision_height)
+    }
 }
 
+/// The canonical time of a slot: the `unix_timestamp` of the Clock sysvar while
+/// the slot's block is produced, which is also the `block_time` RPC nodes report
+/// for that block. Both are whole seconds, so times of this chain never carry
+/// nanoseconds. `None` if the timestamp is outside the range of Tendermint times.
+#[must_use]
+pub fn slot_time(unix_timestamp: UnixTimestamp) -> Option<TendermintTime> {
+    TendermintTime::from_unix_timestamp(unix_timestamp, 0).ok()
+}
+
+/// The canonical time of the current slot, see [`slot_time`].
 pub fn tendermint_time_from_clock(clock: &Clock) -> TendermintTime {
-    TendermintTime::from_unix_timestamp(clock.unix_timestamp, 0)
-        .expect("Unix timestamp from Clock should be valid")
+    slot_time(clock.unix_timestamp).expect("Unix timestamp from Clock should be valid")
+}
+
+/// Estimates the time of `slot` by counting back `EXPECTED_SLOT_TIME` per slot
+/// from `current_time`, the time of `current_slot`. Slots after `current_slot`
+/// get `current_time`, and estimates before the Unix epoch are clamped to it.
+pub fn estimate_slot_time(
+    current_time: TendermintTime,
+    current_slot: Slot,
+    slot: Slot,
+) -> TendermintTime {
+    const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+    let elapsed_slots = current_slot.saturating_sub(slot);
+    let elapsed_nanos = i128::from(elapsed_slots) * EXPECTED_SLOT_TIME.as_nanos() as i128;
+    let estimated_nanos = (current_time.unix_timestamp_nanos() - elapsed_nanos).max(0);
+    TendermintTime::from_unix_timestamp(
+        (estimated_nanos / NANOS_PER_SEC) as i64,
+        (estimated_nanos % NANOS_PER_SEC) as u32,
+    )
+    .expect("Estimated time is between the Unix epoch and the current time")
+}
+
+pub fn commitment_prefix() -> CommitmentPrefix {
+    COMMITMENT_PREFIX
+        .to_vec()
+        .try_into()
+        .expect("Prefix is not empty")
 }
 
+/// Specs of the two layers of every proof of this chain's state, innermost first.
+/// The IBC store is a JMT, and its root is proven under `COMMITMENT_PREFIX` in a
+/// single-leaf tree hashed like the Cosmos SDK multistore, so the key path of a
+/// proof is the prefix followed by the IBC path, as `apply_prefix` builds it.
 pub fn proof_specs() -> ProofSpecs {
-    // TODO: Figure out top-level proof spec to use
-    vec![jmt::ics23_spec()].into()
+    vec![jmt::ics23_spec(), ics23::tendermint_spec()].into()
+}
+
+/// Outer layer of every proof of this chain's state, proving the IBC store root
+/// `state_root` under `COMMITMENT_PREFIX`.
+pub fn prefix_proof(state_root: &[u8]) -> ExistenceProof {
+    ExistenceProof {
+        key: COMMITMENT_PREFIX.to_vec(),
+        value: state_root.to_vec(),
+        leaf: ics23::tendermint_spec().leaf_spec,
+        path: vec![],
+    }
+}
+
+/// Root that headers and consensus states of this chain commit to when the IBC
+/// store root is `state_root`.
+pub fn commitment_root(state_root: &CommitmentRoot) -> CommitmentRoot {
+    let root =
+        calculate_existence_root::<HostFunctionsManager>(&prefix_proof(state_root.as_bytes()))
+            .expect("Prefix proof has a leaf");
+    CommitmentRoot::from_bytes(&root)
+}
+
+/// Wraps a proof from the IBC store in the `MerkleProof` that counterparties
+/// verify against `commitment_root`, adding the layer for `COMMITMENT_PREFIX`.
+pub fn merkle_proof(existence_proof: ExistenceProof) -> RawMerkleProof {
+    let state_root = calculate_existence_root::<HostFunctionsManager>(&existence_proof)
+        .expect("Proofs from the IBC store have a leaf");
+    let proofs = [existence_proof, prefix_proof(&state_root)]
+        .into_iter()
+        .map(|existence_proof| {
+            let commitment_proof = CommitmentProof {
+                proof: Some(commitment_proof::Proof::Exist(existence_proof)),
+            };
+            IbcRawCommitmentProof::decode(&*commitment_proof.encode_to_vec())
+                .expect("CommitmentProof should be the same between ics23 and ibc-proto")
+        })
+        .collect();
+    RawMerkleProof { proofs }
+}
+
+/// Converts a value passed to `verify_membership` into the bytes that the IBC program
+/// stores under `path`. ibc-rs passes packet and acknowledgement commitments as the
+/// bare hash, whereas the program stores them as protobuf `bytes` values.
+pub fn stored_value(path: &Path, value: Vec<u8>) -> Vec<u8> {
+    match path {
+        Path::Commitment(_) | Path::Ack(_) => prost::Message::encode_to_vec(&value),
+        _ => value,
+    }
 }
 
 #[cfg(test)]
@@ -61,17 +235,63 @@ mod tests {
     }
 
     #[test]
-    fn height_of_slot_to_string() {
-        assert_eq!(height_of_slot(0).unwrap().to_string(), "0-1");
-        assert_eq!(height_of_slot(5).unwrap().to_string(), "0-6");
+    fn slot_zero_is_height_one() {
+        let first_height = Height::new(DEFAULT_REVISION_NUMBER, 1).unwrap();
+        assert_eq!(Height::of_slot(0).unwrap(), first_height);
+        assert_eq!(first_height.to_slot().unwrap(), 0);
+        first_height.pred().unwrap_err();
+        assert_eq!(first_height.succ().unwrap(), Height::of_slot(1).unwrap());
+        assert_eq!(Height::of_slot(1).unwrap().pred().unwrap(), first_height);
+
+        Height::of_slot(Slot::MAX).unwrap_err();
+        Height::new(DEFAULT_REVISION_NUMBER, u64::MAX)
+            .unwrap()
+            .succ()
+            .unwrap_err();
+    }
+
+    #[test]
+    fn heights_keep_their_revision() {
+        let height = height_of_slot_with_revision(4, 2).unwrap();
+        assert_eq!(height, Height::new(2, 5).unwrap());
+        assert_eq!(height.succ().unwrap(), Height::new(2, 6).unwrap());
+        assert_eq!(height.pred().unwrap(), Height::new(2, 4).unwrap());
+        assert_eq!(
+            chain_id_with_revision("apricot", 2).to_string(),
+            "eclipse-apricot-2"
+        );
+        assert_eq!(
+            chain_id_in_revision(&chain_id("apricot"), 3),
+            chain_id_with_revision("apricot", 3)
+        );
     }
 
     #[test]
-    fn check_slot_of_height() {
+    fn later_revisions_have_no_slot() {
+        let previous = Height::new(1, 5).unwrap();
+        let active = Height::new(2, 5).unwrap();
+        let next = Height::new(3, 5).unwrap();
+        assert_eq!(slot_of_height(&previous, 2).unwrap(), 4);
+        assert_eq!(slot_of_height(&active, 2).unwrap(), 4);
+        slot_of_height(&next, 2).unwrap_err();
+    }
+
+    #[test]
+    fn slot_times_count_back_from_the_current_slot() {
+        let current_time = TendermintTime::from_unix_timestamp(1_000, 0).unwrap();
+        assert_eq!(estimate_slot_time(current_time, 100, 100), current_time);
+        assert_eq!(estimate_slot_time(current_time, 100, 101), current_time);
+        assert_eq!(
+            estimate_slot_time(current_time, 100, 95),
+            TendermintTime::from_unix_timestamp(998, 0).unwrap(),
+        );
+        assert_eq!(
+            estimate_slot_time(current_time, 100, 99),
+            TendermintTime::from_unix_timestamp(999, 600_000_000).unwrap(),
+        );
         assert_eq!(
-            slot_of_height(Height::new(REVISION_NUMBER, 1).unwrap()).unwrap(),
-            0,
+            estimate_slot_time(current_time, Slot::MAX, 0),
+            TendermintTime::unix_epoch(),
         );
-        assert!(slot_of_height(Height::new(REVISION_NUMBER + 1, 1).unwrap()).is_err());
     }
 }
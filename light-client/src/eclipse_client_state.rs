@@ -1,5 +1,8 @@
 use {
-    crate::{eclipse_chain, error::Error, EclipseConsensusState, EclipseHeader},
+    crate::{
+        eclipse_chain, error::Error, EclipseConsensusState, EclipseHeader, EclipseHeaderBatch,
+        EclipseMisbehaviour, ECLIPSE_HEADER_BATCH_TYPE_URL,
+    },
     core::time::Duration,
     eclipse_ibc_known_proto::{KnownAnyProto, KnownProto, KnownProtoWithFrom},
     eclipse_ibc_proto::eclipse::ibc::chain::v1::ClientState as RawEclipseClientState,
@@ -13,7 +16,7 @@ use {
         },
         ics23_commitment::{
             commitment::{CommitmentPrefix, CommitmentProofBytes, CommitmentRoot},
-            merkle::MerkleProof,
+            merkle::{apply_prefix, MerkleProof},
         },
         ics24_host::{
             identifier::{ChainId, ClientId},
@@ -23,14 +26,16 @@ use {
     },
     ibc_proto::{
         google::protobuf,
-        ibc::core::commitment::v1::{MerklePath, MerkleProof as RawMerkleProof, MerkleRoot},
+        ibc::core::commitment::v1::{MerkleProof as RawMerkleProof, MerkleRoot},
         protobuf::Protobuf,
     },
+    prost::Message as _,
     serde::Serialize,
+    solana_sdk::hash::Hash,
 };
 
 const CLIENT_TYPE: &str = "xx-eclipse";
-pub const ECLIPSE_CLIENT_STATE_TYPE_URL: &str = "/eclipse.ibc.v1.chain.ClientState";
+pub const ECLIPSE_CLIENT_STATE_TYPE_URL: &str = "/eclipse.ibc.chain.v1.ClientState";
 
 fn client_type() -> ClientType {
     ClientType::new(CLIENT_TYPE.to_owned()).unwrap()
@@ -51,6 +56,93 @@ pub struct EclipseClientState {
     pub chain_id: ChainId,
     pub latest_header: EclipseHeader,
     pub frozen_height: Option<Height>,
+    /// Identifies the Solana cluster the client tracks, so that a client created
+    /// from one cluster's data is not mistaken for a client of another.
+    pub genesis_hash: Option<Hash>,
+    /// Revision of the tracked chain as of the latest header. Headers either stay
+    /// in it or move to the next one, when the chain bumps its revision.
+    pub revision_number: u64,
+    /// How long the client stays usable after its latest update, in whole
+    /// seconds. `eclipse_chain::IBC_MESSAGE_VALID_DURATION` if not set.
+    pub valid_duration: Option<Duration>,
+}
+
+impl EclipseClientState {
+    /// Checks that a header at `header_height` stays in the client's revision or
+    /// moves to the next one.
+    fn check_revision_continuity(&self, header_height: Height) -> Result<(), ClientError> {
+        let header_revision_number = header_height.revision_number();
+        if header_revision_number == self.revision_number
+            || Some(header_revision_number) == self.revision_number.checked_add(1)
+        {
+            return Ok(());
+        }
+        Err(ClientError::ClientSpecific {
+            description: Error::RevisionDiscontinuity {
+                revision_number: self.revision_number,
+                header_revision_number,
+            }
+            .to_string(),
+        })
+    }
+
+    /// The client state once updated with `header`, after checking that the
+    /// header continues it.
+    fn updated_with(&self, header: EclipseHeader) -> Result<Self, ClientError> {
+        self.check_revision_continuity(header.height)?;
+        // Slots keep counting across revisions, so a header of the next revision
+        // must still be past the latest header's revision height
+        if self.latest_height().revision_height() >= header.height.revision_height() {
+            return Err(ClientError::LowHeaderHeight {
+                header_height: header.height,
+                latest_height: self.latest_height(),
+            });
+        }
+
+        let revision_number = header.height.revision_number();
+        let chain_id = if revision_number == self.revision_number {
+            self.chain_id.clone()
+        } else {
+            eclipse_chain::chain_id_in_revision(&self.chain_id, revision_number)
+        };
+        Ok(Self {
+            chain_id,
+            latest_header: header,
+            frozen_height: self.frozen_height,
+            genesis_hash: self.genesis_hash,
+            revision_number,
+            valid_duration: self.valid_duration,
+        })
+    }
+}
+
+/// Rejects submitted misbehaviour evidence. Eclipse headers are not signed, so
+/// anyone can make up a header conflicting with any stored consensus state, and
+/// freezing a client on such evidence would let anyone freeze every client.
+/// Evidence is still decoded, so that malformed messages get a decoding error.
+fn reject_misbehaviour(client_message: protobuf::Any) -> ClientError {
+    match EclipseMisbehaviour::try_from(client_message) {
+        Ok(misbehaviour) => ClientError::MisbehaviourHandlingFailure {
+            reason: format!(
+                "misbehaviour at height {} cannot be verified: Eclipse headers are not \
+                 signed, so clients are not frozen on submitted evidence",
+                misbehaviour.height()
+            ),
+        },
+        Err(err) => err,
+    }
+}
+
+/// Headers of a client update message, which is either a single header or a
+/// batch of them.
+fn headers_of_client_message(
+    client_message: protobuf::Any,
+) -> Result<Vec<EclipseHeader>, ClientError> {
+    if client_message.type_url == ECLIPSE_HEADER_BATCH_TYPE_URL {
+        Ok(EclipseHeaderBatch::try_from(client_message)?.headers)
+    } else {
+        Ok(vec![EclipseHeader::try_from(client_message)?])
+    }
 }
 
 impl From<EclipseClientState> for RawEclipseClientState {
@@ -59,12 +151,21 @@ impl From<EclipseClientState> for RawEclipseClientState {
             chain_id,
             latest_header,
             frozen_height,
+            genesis_hash,
+            revision_number,
+            valid_duration,
         }: EclipseClientState,
     ) -> Self {
         Self {
             chain_id: chain_id.to_string(),
             latest_header: Some(latest_header.into()),
             frozen_height: frozen_height.map(Height::into),
+            genesis_hash: genesis_hash
+                .map(|genesis_hash| genesis_hash.to_bytes().to_vec())
+                .unwrap_or_default(),
+            revision_number,
+            valid_duration_secs: valid_duration
+                .map_or(0, |valid_duration| valid_duration.as_secs()),
         }
     }
 }
@@ -77,8 +178,22 @@ impl TryFrom<RawEclipseClientState> for EclipseClientState {
             chain_id,
             latest_header,
             frozen_height,
+            genesis_hash,
+            revision_number,
+            valid_duration_secs,
         }: RawEclipseClientState,
     ) -> Result<Self, Self::Error> {
+        let genesis_hash = if genesis_hash.is_empty() {
+            None
+        } else {
+            let genesis_hash = <[u8; 32]>::try_from(&*genesis_hash).map_err(|_| {
+                Error::InvalidGenesisHashLength {
+                    len: genesis_hash.len(),
+                }
+            })?;
+            Some(Hash::new_from_array(genesis_hash))
+        };
+
         Ok(Self {
             chain_id: ChainId::from_string(&chain_id),
             latest_header: latest_header
@@ -89,6 +204,10 @@ impl TryFrom<RawEclipseClientState> for EclipseClientState {
             frozen_height: frozen_height
                 .map(|frozen_height| frozen_height.try_into().map_err(Error::Client))
                 .transpose()?,
+            genesis_hash,
+            revision_number,
+            valid_duration: (valid_duration_secs != 0)
+                .then(|| Duration::from_secs(valid_duration_secs)),
         })
     }
 }
@@ -167,7 +286,10 @@ impl ClientState for EclipseClientState {
     }
 
     fn expired(&self, elapsed: Duration) -> bool {
-        elapsed > eclipse_chain::IBC_MESSAGE_VALID_DURATION
+        elapsed
+            > self
+                .valid_duration
+                .unwrap_or(eclipse_chain::IBC_MESSAGE_VALID_DURATION)
     }
 
     fn initialise(
@@ -186,21 +308,14 @@ impl ClientState for EclipseClientState {
     ) -> Result<(), ClientError> {
         match update_kind {
             UpdateKind::UpdateClient => (),
-            UpdateKind::SubmitMisbehaviour => {
-                return Err(ClientError::MisbehaviourHandlingFailure {
-                    reason: "Misbehaviour checks are not yet supported".to_owned(),
-                });
-            }
+            UpdateKind::SubmitMisbehaviour => return Err(reject_misbehaviour(client_message)),
         }
 
-        let header = EclipseHeader::try_from(client_message)?;
-
-        if self.latest_height() >= header.height {
-            return Err(ClientError::LowHeaderHeight {
-                header_height: header.height,
-                latest_height: self.latest_height(),
-            });
-        }
+        headers_of_client_message(client_message)?
+            .into_iter()
+            .try_fold(self.clone(), |client_state, header| {
+                client_state.updated_with(header)
+            })?;
 
         let _client_state = ctx
             .client_state(client_id)
@@ -215,7 +330,9 @@ impl ClientState for EclipseClientState {
         Ok(())
     }
 
-    // TODO: Support misbehaviour checks
+    /// Updates only add headers past the latest one, where the client has no
+    /// consensus state for them to conflict with, and submitted evidence is
+    /// rejected, so nothing is ever misbehaviour.
     fn check_for_misbehaviour(
         &self,
         _ctx: &dyn ValidationContext,
@@ -232,10 +349,9 @@ impl ClientState for EclipseClientState {
         client_id: &ClientId,
         client_message: protobuf::Any,
     ) -> Result<Vec<Height>, ClientError> {
-        let header = EclipseHeader::try_from(client_message)?;
-        let new_height = header.height;
+        let headers = headers_of_client_message(client_message)?;
 
-        let client_state = ctx
+        let mut new_client_state = ctx
             .client_state(client_id)
             .map_err(client_err_from_context)?
             .as_any()
@@ -245,45 +361,38 @@ impl ClientState for EclipseClientState {
                     .to_owned(),
             })?
             .clone();
-
-        let new_client_state = Self {
-            chain_id: client_state.chain_id,
-            latest_header: header.clone(),
-            frozen_height: client_state.frozen_height,
-        };
-
-        let new_consensus_state = EclipseConsensusState::from(header);
-
-        ctx.store_update_time(
-            client_id.clone(),
-            new_client_state.latest_height(),
-            ctx.host_timestamp()?,
-        )?;
-        ctx.store_update_height(
-            client_id.clone(),
-            new_client_state.latest_height(),
-            ctx.host_height()?,
-        )?;
-
-        ctx.store_consensus_state(
-            ClientConsensusStatePath::new(client_id, &new_client_state.latest_height()),
-            Box::new(new_consensus_state),
-        )?;
+        let host_timestamp = ctx.host_timestamp()?;
+        let host_height = ctx.host_height()?;
+
+        let mut new_heights = Vec::with_capacity(headers.len());
+        for header in headers {
+            new_client_state = new_client_state.updated_with(header.clone())?;
+            let new_height = header.height;
+
+            ctx.store_update_time(client_id.clone(), new_height, host_timestamp)?;
+            ctx.store_update_height(client_id.clone(), new_height, host_height)?;
+            ctx.store_consensus_state(
+                ClientConsensusStatePath::new(client_id, &new_height),
+                Box::new(EclipseConsensusState::from(header)),
+            )?;
+            new_heights.push(new_height);
+        }
         ctx.store_client_state(ClientStatePath::new(client_id), Box::new(new_client_state))?;
 
-        Ok(vec![new_height])
+        // The update event reports the first height as the one the client was
+        // updated to, which for a batch is the latest
+        new_heights.reverse();
+        Ok(new_heights)
     }
 
     fn update_state_on_misbehaviour(
         &self,
         _ctx: &mut dyn ExecutionContext,
         _client_id: &ClientId,
-        _client_message: protobuf::Any,
+        client_message: protobuf::Any,
         _update_kind: &UpdateKind,
     ) -> Result<(), ClientError> {
-        Err(ClientError::MisbehaviourHandlingFailure {
-            reason: "Misbehaviour checks are not yet supported".to_owned(),
-        })
+        Err(reject_misbehaviour(client_message))
     }
 
     fn verify_upgrade_client(
@@ -294,8 +403,12 @@ impl ClientState for EclipseClientState {
         proof_upgrade_consensus_state: RawMerkleProof,
         root: &CommitmentRoot,
     ) -> Result<(), ClientError> {
+        // Upgrades are stored as the `Any`s they are submitted as, so those are
+        // what the proofs are checked against
+        let client_state_value = upgraded_client_state.encode_to_vec();
+        let consensus_state_value = upgraded_consensus_state.encode_to_vec();
         let upgraded_client_state = EclipseClientState::try_from(upgraded_client_state)?;
-        let upgraded_consensus_state = EclipseConsensusState::try_from(upgraded_consensus_state)?;
+        EclipseConsensusState::try_from(upgraded_consensus_state)?;
 
         let merkle_proof_upgrade_client = MerkleProof::from(proof_upgrade_client);
         let merkle_proof_upgrade_consensus_state = MerkleProof::from(proof_upgrade_consensus_state);
@@ -309,16 +422,14 @@ impl ClientState for EclipseClientState {
         }
 
         let last_height = self.latest_height().revision_height();
+        // Upgrades are stored in the IBC store like all other state, so they are
+        // proven under the same prefix
+        let upgrade_prefix = eclipse_chain::commitment_prefix();
 
-        let client_upgrade_path = vec![
-            //eclipse_chain::UPGRADE_PREFIX.to_owned(),
-            UpgradeClientPath::UpgradedClientState(last_height).to_string(),
-        ];
-        let client_upgrade_merkle_path = MerklePath {
-            key_path: client_upgrade_path,
-        };
-
-        let client_state_value = KnownProto::encode(upgraded_client_state);
+        let client_upgrade_merkle_path = apply_prefix(
+            &upgrade_prefix,
+            vec![UpgradeClientPath::UpgradedClientState(last_height).to_string()],
+        );
 
         merkle_proof_upgrade_client
             .verify_membership(
@@ -330,15 +441,10 @@ impl ClientState for EclipseClientState {
             )
             .map_err(ClientError::Ics23Verification)?;
 
-        let consensus_upgrade_path = vec![
-            //eclipse_chain::UPGRADE_PREFIX.to_owned(),
-            UpgradeClientPath::UpgradedClientConsensusState(last_height).to_string(),
-        ];
-        let consensus_upgrade_merkle_path = MerklePath {
-            key_path: consensus_upgrade_path,
-        };
-
-        let consensus_state_value = KnownProto::encode(upgraded_consensus_state);
+        let consensus_upgrade_merkle_path = apply_prefix(
+            &upgrade_prefix,
+            vec![UpgradeClientPath::UpgradedClientConsensusState(last_height).to_string()],
+        );
 
         merkle_proof_upgrade_consensus_state
             .verify_membership(
@@ -366,7 +472,7 @@ impl ClientState for EclipseClientState {
 
     fn verify_membership(
         &self,
-        _prefix: &CommitmentPrefix,
+        prefix: &CommitmentPrefix,
         proof: &CommitmentProofBytes,
         root: &CommitmentRoot,
         path: Path,
@@ -374,10 +480,8 @@ impl ClientState for EclipseClientState {
     ) -> Result<(), ClientError> {
         let proof_specs = eclipse_chain::proof_specs();
         let merkle_root: MerkleRoot = root.clone().into();
-        // TODO: Use `ics23_commitment::merkle::apply_prefix`
-        let merkle_path = MerklePath {
-            key_path: vec![path.to_string()],
-        };
+        let value = eclipse_chain::stored_value(&path, value);
+        let merkle_path = apply_prefix(prefix, vec![path.to_string()]);
         let merkle_proof: MerkleProof = RawMerkleProof::try_from(proof.clone())
             .map_err(ClientError::Ics23Verification)?
             .into();
@@ -390,17 +494,14 @@ impl ClientState for EclipseClientState {
 
     fn verify_non_membership(
         &self,
-        _prefix: &CommitmentPrefix,
+        prefix: &CommitmentPrefix,
         proof: &CommitmentProofBytes,
         root: &CommitmentRoot,
         path: Path,
     ) -> Result<(), ClientError> {
         let proof_specs = eclipse_chain::proof_specs();
         let merkle_root: MerkleRoot = root.clone().into();
-        // TODO: Use `ics23_commitment::merkle::apply_prefix`
-        let merkle_path = MerklePath {
-            key_path: vec![path.to_string()],
-        };
+        let merkle_path = apply_prefix(prefix, vec![path.to_string()]);
         let merkle_proof: MerkleProof = RawMerkleProof::try_from(proof.clone())
             .map_err(ClientError::Ics23Verification)?
             .into();
@@ -420,4 +521,39 @@ mod tests {
     fn print_client_type() {
         assert_eq!(CLIENT_TYPE, client_type().as_str());
     }
+
+    #[test]
+    fn revisions_advance_one_at_a_time() {
+        let client_state = |revision_number| EclipseClientState {
+            chain_id: eclipse_chain::chain_id_with_revision("test", revision_number),
+            latest_header: EclipseHeader {
+                height: Height::new(revision_number, 10).unwrap(),
+                commitment_root: CommitmentRoot::from_bytes(&[1; 32]),
+                timestamp: tendermint::time::Time::unix_epoch(),
+            },
+            frozen_height: None,
+            genesis_hash: None,
+            revision_number,
+            valid_duration: None,
+        };
+        let header_height = |revision_number| Height::new(revision_number, 20).unwrap();
+
+        let client_state = client_state(1);
+        client_state
+            .check_revision_continuity(header_height(1))
+            .unwrap();
+        client_state
+            .check_revision_continuity(header_height(2))
+            .unwrap();
+        client_state
+            .check_revision_continuity(header_height(0))
+            .unwrap_err();
+        client_state
+            .check_revision_continuity(header_height(3))
+            .unwrap_err();
+
+        let raw = RawEclipseClientState::from(client_state.clone());
+        assert_eq!(raw.revision_number, 1);
+        assert_eq!(EclipseClientState::try_from(raw).unwrap(), client_state);
+    }
 }
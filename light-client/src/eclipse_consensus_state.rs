@@ -12,7 +12,7 @@ use {
     tendermint::time::Time as TendermintTime,
 };
 
-pub const ECLIPSE_CONSENSUS_STATE_TYPE_URL: &str = "/eclipse.ibc.v1.chain.ConsensusState";
+pub const ECLIPSE_CONSENSUS_STATE_TYPE_URL: &str = "/eclipse.ibc.chain.v1.ConsensusState";
 
 // TODO: Store state in a sysvar
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
@@ -12,7 +12,7 @@ use {
     tendermint::time::Time as TendermintTime,
 };
 
-pub const ECLIPSE_HEADER_TYPE_URL: &str = "/eclipse.ibc.v1.chain.Header";
+pub const ECLIPSE_HEADER_TYPE_URL: &str = "/eclipse.ibc.chain.v1.Header";
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct EclipseHeader {
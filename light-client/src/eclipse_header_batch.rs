@@ -0,0 +1,173 @@
+use {
+    crate::{error::Error, EclipseHeader},
+    eclipse_ibc_known_proto::{KnownAnyProto, KnownProto, KnownProtoWithFrom},
+    eclipse_ibc_proto::eclipse::ibc::chain::v1::HeaderBatch as RawEclipseHeaderBatch,
+    ibc::core::ics02_client::{error::ClientError, height::Height},
+    ibc_proto::{google::protobuf, protobuf::Protobuf},
+    serde::Serialize,
+};
+
+pub const ECLIPSE_HEADER_BATCH_TYPE_URL: &str = "/eclipse.ibc.chain.v1.HeaderBatch";
+
+/// Most headers a batch may hold. Each header stores a consensus state and the
+/// time and height of its update, so the compute a batch takes to handle grows
+/// with it, and this keeps a full batch well within a transaction's budget.
+pub const MAX_HEADERS_PER_BATCH: usize = 16;
+
+/// Headers at strictly increasing heights that update a client in one message,
+/// for catching it up while keeping consensus states at heights in between.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct EclipseHeaderBatch {
+    pub headers: Vec<EclipseHeader>,
+}
+
+impl EclipseHeaderBatch {
+    /// Batches `headers`, checking that there are some, no more than
+    /// [`MAX_HEADERS_PER_BATCH`], and that their heights strictly increase.
+    pub fn new(headers: Vec<EclipseHeader>) -> Result<Self, Error> {
+        let batch = Self { headers };
+        batch.check_headers()?;
+        Ok(batch)
+    }
+
+    /// Height of the last header, which the client is at once updated.
+    #[must_use]
+    pub fn latest_height(&self) -> Option<Height> {
+        self.headers.last().map(|header| header.height)
+    }
+
+    /// Checks that the batch holds between one and [`MAX_HEADERS_PER_BATCH`]
+    /// headers, at strictly increasing heights.
+    pub fn check_headers(&self) -> Result<(), Error> {
+        if self.headers.is_empty() {
+            return Err(Error::EmptyHeaderBatch);
+        }
+        if self.headers.len() > MAX_HEADERS_PER_BATCH {
+            return Err(Error::HeaderBatchTooLarge {
+                len: self.headers.len(),
+                max_len: MAX_HEADERS_PER_BATCH,
+            });
+        }
+        for pair in self.headers.windows(2) {
+            if pair[0].height >= pair[1].height {
+                return Err(Error::HeaderBatchNotIncreasing {
+                    previous_height: pair[0].height,
+                    height: pair[1].height,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<EclipseHeaderBatch> for RawEclipseHeaderBatch {
+    fn from(EclipseHeaderBatch { headers }: EclipseHeaderBatch) -> Self {
+        Self {
+            headers: headers.into_iter().map(EclipseHeader::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<RawEclipseHeaderBatch> for EclipseHeaderBatch {
+    type Error = Error;
+
+    fn try_from(
+        RawEclipseHeaderBatch { headers }: RawEclipseHeaderBatch,
+    ) -> Result<Self, Self::Error> {
+        Self::new(
+            headers
+                .into_iter()
+                .map(EclipseHeader::try_from)
+                .collect::<Result<_, _>>()?,
+        )
+    }
+}
+
+impl Protobuf<RawEclipseHeaderBatch> for EclipseHeaderBatch {}
+
+impl KnownProtoWithFrom for EclipseHeaderBatch {
+    type RawWithFrom = RawEclipseHeaderBatch;
+}
+
+impl KnownAnyProto for EclipseHeaderBatch {
+    fn type_url() -> String {
+        ECLIPSE_HEADER_BATCH_TYPE_URL.to_owned()
+    }
+}
+
+impl From<EclipseHeaderBatch> for protobuf::Any {
+    fn from(batch: EclipseHeaderBatch) -> Self {
+        Self {
+            type_url: ECLIPSE_HEADER_BATCH_TYPE_URL.to_owned(),
+            value: KnownProto::encode(batch),
+        }
+    }
+}
+
+impl TryFrom<protobuf::Any> for EclipseHeaderBatch {
+    type Error = ClientError;
+
+    fn try_from(raw: protobuf::Any) -> Result<Self, Self::Error> {
+        use prost::Message;
+
+        if &*raw.type_url == ECLIPSE_HEADER_BATCH_TYPE_URL {
+            RawEclipseHeaderBatch::decode(&*raw.value)
+                .map_err(ClientError::Decode)?
+                .try_into()
+                .map_err(|err: Error| ClientError::ClientSpecific {
+                    description: err.to_string(),
+                })
+        } else {
+            Err(ClientError::UnknownHeaderType {
+                header_type: raw.type_url,
+            })
+        }
+    }
+}
+
+impl Protobuf<protobuf::Any> for EclipseHeaderBatch {}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, eclipse_ibc_fixtures::clients};
+
+    #[test]
+    fn batches_hold_headers_at_increasing_heights() {
+        let batch =
+            EclipseHeaderBatch::new((1..=3).map(clients::eclipse_header).collect()).unwrap();
+        assert_eq!(
+            batch.latest_height(),
+            Some(clients::eclipse_header(3).height)
+        );
+        assert_eq!(
+            EclipseHeaderBatch::try_from(protobuf::Any::from(batch.clone())).unwrap(),
+            batch
+        );
+
+        let err = EclipseHeaderBatch::new(vec![]).unwrap_err();
+        assert!(matches!(err, Error::EmptyHeaderBatch), "{err}");
+        let err = EclipseHeaderBatch::new(
+            (1..=MAX_HEADERS_PER_BATCH as u64 + 1)
+                .map(clients::eclipse_header)
+                .collect(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::HeaderBatchTooLarge { .. }), "{err}");
+        let err =
+            EclipseHeaderBatch::new(vec![clients::eclipse_header(2), clients::eclipse_header(2)])
+                .unwrap_err();
+        assert!(
+            matches!(err, Error::HeaderBatchNotIncreasing { .. }),
+            "{err}"
+        );
+
+        // Decoding checks the headers too
+        let unordered = RawEclipseHeaderBatch {
+            headers: vec![
+                clients::eclipse_header(3).into(),
+                clients::eclipse_header(1).into(),
+            ],
+        };
+        assert!(EclipseHeaderBatch::try_from(unordered).is_err());
+    }
+}
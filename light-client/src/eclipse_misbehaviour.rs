@@ -0,0 +1,190 @@
+use {
+    crate::{error::Error, EclipseHeader},
+    eclipse_ibc_known_proto::{KnownAnyProto, KnownProtoWithFrom},
+    eclipse_ibc_proto::eclipse::ibc::chain::v1::Misbehaviour as RawEclipseMisbehaviour,
+    ibc::core::ics02_client::{error::ClientError, height::Height},
+    ibc_proto::{google::protobuf, protobuf::Protobuf},
+    serde::Serialize,
+};
+
+pub const ECLIPSE_MISBEHAVIOUR_TYPE_URL: &str = "/eclipse.ibc.chain.v1.Misbehaviour";
+
+/// Two headers of the chain tracked by the client `client_id` that commit to
+/// different roots at the same height.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct EclipseMisbehaviour {
+    pub client_id: String,
+    pub header_1: EclipseHeader,
+    pub header_2: EclipseHeader,
+}
+
+impl EclipseMisbehaviour {
+    /// Pairs two headers as misbehaviour evidence, checking that they actually
+    /// conflict: same height, different commitment roots.
+    pub fn new(
+        client_id: String,
+        header_1: EclipseHeader,
+        header_2: EclipseHeader,
+    ) -> Result<Self, Error> {
+        let misbehaviour = Self {
+            client_id,
+            header_1,
+            header_2,
+        };
+        misbehaviour.check_conflict()?;
+        Ok(misbehaviour)
+    }
+
+    /// Height both headers are at.
+    #[must_use]
+    pub fn height(&self) -> Height {
+        self.header_1.height
+    }
+
+    /// Checks that the headers are at the same height and commit to different
+    /// roots there.
+    pub fn check_conflict(&self) -> Result<(), Error> {
+        if self.header_1.height != self.header_2.height {
+            return Err(Error::MisbehaviourHeightMismatch {
+                height_1: self.header_1.height,
+                height_2: self.header_2.height,
+            });
+        }
+        if self.header_1.commitment_root == self.header_2.commitment_root {
+            return Err(Error::MisbehaviourHeadersAgree {
+                height: self.header_1.height,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl From<EclipseMisbehaviour> for RawEclipseMisbehaviour {
+    fn from(
+        EclipseMisbehaviour {
+            client_id,
+            header_1,
+            header_2,
+        }: EclipseMisbehaviour,
+    ) -> Self {
+        Self {
+            client_id,
+            header_1: Some(header_1.into()),
+            header_2: Some(header_2.into()),
+        }
+    }
+}
+
+impl TryFrom<RawEclipseMisbehaviour> for EclipseMisbehaviour {
+    type Error = Error;
+
+    fn try_from(
+        RawEclipseMisbehaviour {
+            client_id,
+            header_1,
+            header_2,
+        }: RawEclipseMisbehaviour,
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            client_id,
+            header_1: header_1
+                .ok_or(Error::MissingFieldInRawMisbehaviour {
+                    missing_field: "header_1",
+                })?
+                .try_into()?,
+            header_2: header_2
+                .ok_or(Error::MissingFieldInRawMisbehaviour {
+                    missing_field: "header_2",
+                })?
+                .try_into()?,
+        })
+    }
+}
+
+impl Protobuf<RawEclipseMisbehaviour> for EclipseMisbehaviour {}
+
+impl KnownProtoWithFrom for EclipseMisbehaviour {
+    type RawWithFrom = RawEclipseMisbehaviour;
+}
+
+impl KnownAnyProto for EclipseMisbehaviour {
+    fn type_url() -> String {
+        ECLIPSE_MISBEHAVIOUR_TYPE_URL.to_owned()
+    }
+}
+
+impl TryFrom<protobuf::Any> for EclipseMisbehaviour {
+    type Error = ClientError;
+
+    fn try_from(raw: protobuf::Any) -> Result<Self, Self::Error> {
+        use prost::Message;
+
+        if &*raw.type_url == ECLIPSE_MISBEHAVIOUR_TYPE_URL {
+            RawEclipseMisbehaviour::decode(&*raw.value)
+                .map_err(ClientError::Decode)?
+                .try_into()
+                .map_err(|err: Error| ClientError::ClientSpecific {
+                    description: err.to_string(),
+                })
+        } else {
+            Err(ClientError::UnknownMisbehaviourType {
+                misbehaviour_type: raw.type_url,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, eclipse_ibc_fixtures::clients, eclipse_ibc_known_proto::KnownProto,
+        ibc::core::ics23_commitment::commitment::CommitmentRoot,
+    };
+
+    fn forked_header(revision_height: u64) -> EclipseHeader {
+        EclipseHeader {
+            commitment_root: CommitmentRoot::from_bytes(&[2; 32]),
+            ..clients::eclipse_header(revision_height)
+        }
+    }
+
+    #[test]
+    fn conflicting_headers_round_trip() {
+        let misbehaviour = EclipseMisbehaviour::new(
+            "xx-eclipse-0".to_owned(),
+            clients::eclipse_header(10),
+            forked_header(10),
+        )
+        .unwrap();
+        assert_eq!(misbehaviour.height(), clients::eclipse_header(10).height);
+        assert_eq!(
+            EclipseMisbehaviour::decode(&*misbehaviour.clone().encode()).unwrap(),
+            misbehaviour
+        );
+    }
+
+    #[test]
+    fn headers_must_conflict() {
+        let err = EclipseMisbehaviour::new(
+            "xx-eclipse-0".to_owned(),
+            clients::eclipse_header(10),
+            forked_header(11),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err, Error::MisbehaviourHeightMismatch { .. }),
+            "{err}"
+        );
+
+        let err = EclipseMisbehaviour::new(
+            "xx-eclipse-0".to_owned(),
+            clients::eclipse_header(10),
+            clients::eclipse_header(10),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err, Error::MisbehaviourHeadersAgree { .. }),
+            "{err}"
+        );
+    }
+}
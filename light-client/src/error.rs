@@ -1,5 +1,6 @@
 use {
-    ibc::core::ics02_client::error::ClientError, tendermint::error::Error as TendermintError,
+    ibc::core::ics02_client::{error::ClientError, height::Height},
+    tendermint::error::Error as TendermintError,
     thiserror::Error,
 };
 
@@ -11,6 +12,37 @@ pub enum Error {
     MissingFieldInRawHeader { missing_field: &'static str },
     #[error("invalid raw client state due to a missing field: {missing_field}")]
     MissingFieldInRawClientState { missing_field: &'static str },
+    #[error("invalid genesis hash length: {len}")]
+    InvalidGenesisHashLength { len: usize },
+    #[error(
+        "header of revision {header_revision_number} does not continue revision \
+         {revision_number}, which may only be followed by the next revision"
+    )]
+    RevisionDiscontinuity {
+        revision_number: u64,
+        header_revision_number: u64,
+    },
+    #[error("invalid raw misbehaviour due to a missing field: {missing_field}")]
+    MissingFieldInRawMisbehaviour { missing_field: &'static str },
+    #[error(
+        "misbehaviour headers are at different heights {height_1} and {height_2}, but must \
+         be at the same one"
+    )]
+    MisbehaviourHeightMismatch { height_1: Height, height_2: Height },
+    #[error("misbehaviour headers at {height} commit to the same root, so they do not conflict")]
+    MisbehaviourHeadersAgree { height: Height },
+    #[error("header batch holds no headers")]
+    EmptyHeaderBatch,
+    #[error("header batch holds {len} headers, more than the cap of {max_len}")]
+    HeaderBatchTooLarge { len: usize, max_len: usize },
+    #[error(
+        "header at {height} follows one at {previous_height} in the batch, but heights must \
+         strictly increase"
+    )]
+    HeaderBatchNotIncreasing {
+        previous_height: Height,
+        height: Height,
+    },
     #[error("Tendermint error: {0}")]
     Tendermint(TendermintError),
     #[error("IBC client error: {0}")]
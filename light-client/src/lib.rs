@@ -2,10 +2,40 @@ pub mod eclipse_chain;
 mod eclipse_client_state;
 mod eclipse_consensus_state;
 mod eclipse_header;
+mod eclipse_header_batch;
+mod eclipse_misbehaviour;
 mod error;
 
 pub use {
     eclipse_client_state::{EclipseClientState, ECLIPSE_CLIENT_STATE_TYPE_URL},
     eclipse_consensus_state::{EclipseConsensusState, ECLIPSE_CONSENSUS_STATE_TYPE_URL},
-    eclipse_header::EclipseHeader,
+    eclipse_header::{EclipseHeader, ECLIPSE_HEADER_TYPE_URL},
+    eclipse_header_batch::{
+        EclipseHeaderBatch, ECLIPSE_HEADER_BATCH_TYPE_URL, MAX_HEADERS_PER_BATCH,
+    },
+    eclipse_misbehaviour::{EclipseMisbehaviour, ECLIPSE_MISBEHAVIOUR_TYPE_URL},
 };
+
+/// Type URLs the Eclipse light client types had before they were fixed to name
+/// their proto package, `eclipse.ibc.chain.v1`, paired with the fixed ones.
+/// Client states stored and messages generated before the fix still carry them.
+pub const LEGACY_TYPE_URLS: [(&str, &str); 3] = [
+    (
+        "/eclipse.ibc.v1.chain.ClientState",
+        ECLIPSE_CLIENT_STATE_TYPE_URL,
+    ),
+    (
+        "/eclipse.ibc.v1.chain.ConsensusState",
+        ECLIPSE_CONSENSUS_STATE_TYPE_URL,
+    ),
+    ("/eclipse.ibc.v1.chain.Header", ECLIPSE_HEADER_TYPE_URL),
+];
+
+/// The fixed type URL of `type_url`, if it is one of [`LEGACY_TYPE_URLS`].
+#[must_use]
+pub fn canonical_type_url(type_url: &str) -> Option<&'static str> {
+    LEGACY_TYPE_URLS
+        .iter()
+        .find(|(legacy, _)| *legacy == type_url)
+        .map(|(_, canonical)| *canonical)
+}
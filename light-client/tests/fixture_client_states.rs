@@ -0,0 +1,54 @@
+//! Checks the client state goldens in the fixtures crate against the light client
+//! types that counterparties and the IBC program decode them with.
+
+use {
+    eclipse_ibc_fixtures::{clients, goldens},
+    eclipse_ibc_known_proto::{KnownAnyProto, KnownProto},
+    eclipse_ibc_light_client::{EclipseClientState, ECLIPSE_CLIENT_STATE_TYPE_URL},
+    ibc::{
+        clients::ics07_tendermint::client_state::ClientState as TendermintClientState,
+        core::ics02_client::{client_state::ClientState, height::Height},
+    },
+    ibc_proto::google::protobuf,
+    prost::Message as _,
+};
+
+#[test]
+#[ignore = "binary goldens are not checked in yet; run `cargo run -p eclipse-ibc-fixtures --bin regen`"]
+fn eclipse_client_state_golden_decodes_to_its_builder() {
+    let any = protobuf::Any::decode(&*goldens::load("eclipse_client_state.bin").unwrap()).unwrap();
+    assert_eq!(any.type_url, ECLIPSE_CLIENT_STATE_TYPE_URL);
+
+    let client_state = EclipseClientState::decode(&*any.value).unwrap();
+    let expected = clients::eclipse_client_state(goldens::CLIENT_STATE_HEIGHT);
+    assert_eq!(client_state, expected);
+    assert_eq!(
+        client_state.latest_height(),
+        Height::new(0, goldens::CLIENT_STATE_HEIGHT).unwrap()
+    );
+    assert!(!client_state.is_frozen());
+
+    // Re-encoding reproduces the golden bytes
+    assert_eq!(client_state.encode_as_any(), any);
+}
+
+#[test]
+#[ignore = "binary goldens are not checked in yet; run `cargo run -p eclipse-ibc-fixtures --bin regen`"]
+fn tendermint_client_state_golden_decodes_to_its_builder() {
+    let data = goldens::load("tendermint_client_state.bin").unwrap();
+    assert_eq!(
+        protobuf::Any::decode(&*data).unwrap(),
+        clients::tendermint_client_state_any(goldens::CLIENT_STATE_HEIGHT)
+    );
+
+    let client_state = TendermintClientState::decode(&*data).unwrap();
+    assert_eq!(
+        client_state.chain_id().to_string(),
+        clients::TENDERMINT_CHAIN_ID
+    );
+    assert_eq!(
+        client_state.latest_height(),
+        Height::new(4, goldens::CLIENT_STATE_HEIGHT).unwrap()
+    );
+    assert!(!client_state.is_frozen());
+}
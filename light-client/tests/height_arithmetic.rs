@@ -0,0 +1,78 @@
+//! Slots and revision heights are both `u64`, so offsetting one into the other by
+//! hand type-checks even when it is wrong. All such conversions go through
+//! `eclipse_chain::EclipseHeight`; this test fails if one is hand-rolled elsewhere.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const HAND_ROLLED_PATTERNS: &[&str] = &[
+    "revision_height() + 1",
+    "revision_height() - 1",
+    "revision_height().checked_add(1)",
+    "revision_height().checked_sub(1)",
+    "slot + 1",
+    "slot - 1",
+    "slot.checked_add(1)",
+    "slot.checked_sub(1)",
+];
+
+/// The one file that is allowed to convert between slots and heights.
+const CONVERSIONS_FILE: &str = "light-client/src/eclipse_chain.rs";
+
+const CRATE_SOURCE_DIRS: &[&str] = &[
+    "cli/src",
+    "fixtures/src",
+    "light-client/src",
+    "program/src",
+    "state/src",
+];
+
+fn rust_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            rust_files(&path, files);
+        } else if path
+            .extension()
+            .map_or(false, |extension| extension == "rs")
+        {
+            files.push(path);
+        }
+    }
+}
+
+#[test]
+fn slot_height_conversions_are_not_hand_rolled() {
+    let workspace_dir = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+
+    let mut files = Vec::new();
+    for dir in CRATE_SOURCE_DIRS {
+        rust_files(&workspace_dir.join(dir), &mut files);
+    }
+    assert!(!files.is_empty());
+
+    let violations = files
+        .iter()
+        .filter(|path| !path.ends_with(CONVERSIONS_FILE))
+        .flat_map(|path| {
+            let source = fs::read_to_string(path).unwrap();
+            source
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| {
+                    HAND_ROLLED_PATTERNS
+                        .iter()
+                        .any(|pattern| line.contains(pattern))
+                })
+                .map(|(index, line)| format!("{}:{}: {}", path.display(), index + 1, line.trim()))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert!(
+        violations.is_empty(),
+        "use `EclipseHeight` instead of offsetting slots and heights by hand:\n{}",
+        violations.join("\n")
+    );
+}
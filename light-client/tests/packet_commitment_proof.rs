@@ -0,0 +1,82 @@
+//! End-to-end check that a packet commitment stored by the IBC program can be
+//! verified by a counterparty tracking Eclipse with `EclipseClientState`, using the
+//! same root, proof and value that the counterparty would be given.
+//!
+//! The intermediate bytes are pinned in the fixtures crate's
+//! `goldens/packet_commitment_proof.json` as golden vectors for other light client
+//! implementations. Run `cargo run -p eclipse-ibc-fixtures --bin regen` to rewrite
+//! them after an intentional change.
+
+use {
+    eclipse_ibc_fixtures::{goldens, proof_bundle::ProofBundle},
+    eclipse_ibc_light_client::{eclipse_chain, EclipseClientState},
+    ibc::core::{
+        ics02_client::client_state::ClientState,
+        ics23_commitment::commitment::{CommitmentPrefix, CommitmentProofBytes},
+        ics24_host::path::Path,
+    },
+    serde_json::Value,
+};
+
+fn check_golden(actual: &Value) {
+    let expected: Value =
+        serde_json::from_slice(&goldens::load("packet_commitment_proof.json").unwrap()).unwrap();
+    for (field, expected_value) in expected.as_object().unwrap() {
+        assert_eq!(
+            &actual[field], expected_value,
+            "golden field `{field}` changed"
+        );
+    }
+    assert_eq!(actual, &expected);
+}
+
+#[test]
+fn counterparty_verifies_stored_packet_commitment() {
+    let bundle = ProofBundle::packet_commitment();
+    check_golden(&bundle.to_json());
+
+    // The counterparty verifies the bare commitment hash, as ibc-rs does when
+    // handling an acknowledgement or timeout
+    let client_state = EclipseClientState {
+        chain_id: eclipse_chain::chain_id("fixture"),
+        latest_header: bundle.header.clone(),
+        frozen_height: None,
+        genesis_hash: None,
+        revision_number: eclipse_chain::DEFAULT_REVISION_NUMBER,
+        valid_duration: None,
+    };
+    let prefix = eclipse_chain::commitment_prefix();
+    let proof = CommitmentProofBytes::try_from(bundle.proof.clone()).unwrap();
+    client_state
+        .verify_membership(
+            &prefix,
+            &proof,
+            &bundle.header.commitment_root,
+            Path::Commitment(bundle.path.clone()),
+            bundle.commitment.clone().into_vec(),
+        )
+        .unwrap();
+
+    // Any other commitment must not verify against the same proof
+    client_state
+        .verify_membership(
+            &prefix,
+            &proof,
+            &bundle.header.commitment_root,
+            Path::Commitment(bundle.path.clone()),
+            vec![0; 32],
+        )
+        .unwrap_err();
+
+    // Nor may the commitment be claimed under another prefix
+    let other_prefix = CommitmentPrefix::try_from(b"other".to_vec()).unwrap();
+    client_state
+        .verify_membership(
+            &other_prefix,
+            &proof,
+            &bundle.header.commitment_root,
+            Path::Commitment(bundle.path.clone()),
+            bundle.commitment.clone().into_vec(),
+        )
+        .unwrap_err();
+}
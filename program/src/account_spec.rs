@@ -0,0 +1,356 @@
+use {
+    crate::{id, STORAGE_KEY},
+    solana_program_runtime::{ic_msg, invoke_context::InvokeContext},
+    solana_sdk::{
+        instruction::InstructionError,
+        pubkey::Pubkey,
+        system_program,
+        sysvar::{self, clock, rent},
+        transaction_context::{InstructionContext, TransactionContext},
+    },
+    std::fmt,
+};
+
+/// An account the program expects at a fixed position, after any accounts
+/// holding the leading parts of the instruction data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExpectedAccount {
+    pub name: &'static str,
+    /// Key the account must have, if it is fixed.
+    pub key: Option<Pubkey>,
+}
+
+const PAYER: ExpectedAccount = ExpectedAccount {
+    name: "payer",
+    key: None,
+};
+const STORAGE: ExpectedAccount = ExpectedAccount {
+    name: "storage",
+    key: Some(STORAGE_KEY),
+};
+const CLOCK: ExpectedAccount = ExpectedAccount {
+    name: "clock sysvar",
+    key: Some(clock::ID),
+};
+const RENT: ExpectedAccount = ExpectedAccount {
+    name: "rent sysvar",
+    key: Some(rent::ID),
+};
+const SYSTEM_PROGRAM: ExpectedAccount = ExpectedAccount {
+    name: "system program",
+    key: Some(system_program::ID),
+};
+const TX_BUFFER: ExpectedAccount = ExpectedAccount {
+    name: "tx buffer",
+    key: None,
+};
+const PROGRAM_DATA: ExpectedAccount = ExpectedAccount {
+    name: "program data",
+    key: None,
+};
+
+/// Accounts of instructions that run against the IBC state. Any accounts after
+/// these are passed to module callbacks.
+pub const IBC_HANDLER_ACCOUNTS: &[ExpectedAccount] = &[PAYER, STORAGE, CLOCK];
+
+/// Accounts of `MsgClaimAdmin`. The program data account of this program
+/// records the upgrade authority allowed to claim the admin.
+pub const CLAIM_ADMIN_ACCOUNTS: &[ExpectedAccount] = &[PAYER, STORAGE, CLOCK, PROGRAM_DATA];
+
+/// Accounts of `MsgInitStorageAccount`.
+pub const INIT_STORAGE_ACCOUNT_ACCOUNTS: &[ExpectedAccount] =
+    &[PAYER, STORAGE, RENT, CLOCK, SYSTEM_PROGRAM];
+
+/// Accounts of `MsgReallocStorageAccount`. The payer tops up the storage
+/// account's rent.
+pub const REALLOC_STORAGE_ACCOUNT_ACCOUNTS: &[ExpectedAccount] =
+    &[PAYER, STORAGE, RENT, SYSTEM_PROGRAM];
+
+/// Accounts of `MsgWriteTxBuffer` in `Create` mode. The storage account records
+/// the new buffer against the payer's allowance.
+pub const CREATE_TX_BUFFER_ACCOUNTS: &[ExpectedAccount] =
+    &[PAYER, TX_BUFFER, RENT, SYSTEM_PROGRAM, STORAGE, CLOCK];
+
+/// Accounts of `MsgWriteTxBuffer` in `Reuse` mode. The storage account is only
+/// read, to check that the payer created the buffer.
+pub const REUSE_TX_BUFFER_ACCOUNTS: &[ExpectedAccount] = &[PAYER, TX_BUFFER, STORAGE];
+
+/// Accounts of `MsgCloseTxBuffer`. The payer receives the buffer's lamports.
+pub const CLOSE_TX_BUFFER_ACCOUNTS: &[ExpectedAccount] = &[PAYER, TX_BUFFER, STORAGE, CLOCK];
+
+/// An account whose key differs from the one expected at its position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountMismatch {
+    /// Position of the account in the instruction, counting the accounts holding
+    /// leading parts of the instruction data.
+    pub offset: usize,
+    pub name: &'static str,
+    pub expected: Pubkey,
+    pub found: Pubkey,
+}
+
+impl fmt::Display for AccountMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AccountMismatch {{ offset: {}, name: {}, expected: {}, found: {} }}",
+            self.offset, self.name, self.expected, self.found
+        )
+    }
+}
+
+/// Compares the keys of an instruction's accounts against `spec`, which starts at
+/// `account_offset`. Accounts missing from the end of `keys` are not reported.
+#[must_use]
+pub fn account_mismatches(
+    spec: &[ExpectedAccount],
+    keys: &[Pubkey],
+    account_offset: usize,
+) -> Vec<AccountMismatch> {
+    spec.iter()
+        .zip(keys.iter().skip(account_offset))
+        .enumerate()
+        .filter_map(|(index, (expected_account, found))| {
+            let expected = expected_account.key?;
+            (expected != *found).then_some(AccountMismatch {
+                offset: account_offset + index,
+                name: expected_account.name,
+                expected,
+                found: *found,
+            })
+        })
+        .collect()
+}
+
+/// Checks the instruction's accounts against `spec` before the sysvar and storage
+/// checks, which fail without saying which account was wrong. Each mismatch is
+/// logged before returning `InvalidArgument`.
+pub(crate) fn check_accounts(
+    invoke_context: &InvokeContext,
+    transaction_context: &TransactionContext,
+    instruction_context: &InstructionContext,
+    account_offset: usize,
+    spec: &[ExpectedAccount],
+) -> Result<(), InstructionError> {
+    let keys = (0..instruction_context.get_number_of_instruction_accounts())
+        .map(|index_in_instruction| {
+            let index_in_transaction = instruction_context
+                .get_index_of_instruction_account_in_transaction(index_in_instruction)?;
+            transaction_context
+                .get_key_of_account_at_index(index_in_transaction)
+                .copied()
+        })
+        .collect::<Result<Vec<_>, InstructionError>>()?;
+
+    let mismatches = account_mismatches(spec, &keys, account_offset);
+    for mismatch in &mismatches {
+        ic_msg!(invoke_context, "{}", mismatch);
+    }
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(InstructionError::InvalidArgument)
+    }
+}
+
+/// Error code of instructions that list an account which cannot hold a leading
+/// part of the instruction data.
+pub const BUFFER_ACCOUNT_ERR_CODE: u32 = 0xa6;
+
+/// Why an account listed as holding a leading part of the instruction data is
+/// rejected. Those accounts are concatenated in order, so a repeated or unrelated
+/// account would feed the program data that the payer never wrote.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferAccountViolation {
+    /// The account is also listed at `first_index`.
+    Duplicate {
+        first_index: usize,
+    },
+    StorageAccount,
+    Sysvar,
+    NotOwnedByProgram {
+        owner: Pubkey,
+    },
+}
+
+/// An account holding a leading part of the instruction data that was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferAccountRejection {
+    /// Position of the account in the instruction
+    pub index: usize,
+    pub key: Pubkey,
+    pub violation: BufferAccountViolation,
+}
+
+impl fmt::Display for BufferAccountRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "BufferAccountRejected {{ index: {}, key: {}, reason: ",
+            self.index, self.key
+        )?;
+        match self.violation {
+            BufferAccountViolation::Duplicate { first_index } => {
+                write!(f, "duplicate of index {first_index}")?;
+            }
+            BufferAccountViolation::StorageAccount => write!(f, "storage account")?,
+            BufferAccountViolation::Sysvar => write!(f, "sysvar")?,
+            BufferAccountViolation::NotOwnedByProgram { owner } => {
+                write!(f, "owned by {owner}")?;
+            }
+        }
+        write!(f, " }}")
+    }
+}
+
+/// Checks the keys of the accounts holding the leading parts of the instruction
+/// data, in order. Each must be listed once, and be neither the storage account
+/// nor a sysvar. Returns the first account rejected.
+pub fn check_buffer_keys(keys: &[Pubkey]) -> Result<(), BufferAccountRejection> {
+    for (index, key) in keys.iter().enumerate() {
+        let violation = if let Some(first_index) = keys[..index].iter().position(|k| k == key) {
+            BufferAccountViolation::Duplicate { first_index }
+        } else if *key == STORAGE_KEY {
+            BufferAccountViolation::StorageAccount
+        } else if sysvar::is_sysvar_id(key) {
+            BufferAccountViolation::Sysvar
+        } else {
+            continue;
+        };
+        return Err(BufferAccountRejection {
+            index,
+            key: *key,
+            violation,
+        });
+    }
+    Ok(())
+}
+
+/// Checks the keys and owners of the accounts holding the leading parts of the
+/// instruction data, as [`check_buffer_keys`] does and also requiring each to be
+/// owned by the program, as tx buffers are.
+pub fn check_buffer_accounts(accounts: &[(Pubkey, Pubkey)]) -> Result<(), BufferAccountRejection> {
+    let keys = accounts.iter().map(|&(key, _)| key).collect::<Vec<_>>();
+    check_buffer_keys(&keys)?;
+    match accounts
+        .iter()
+        .enumerate()
+        .find(|(_, (_, owner))| *owner != id())
+    {
+        Some((index, &(key, owner))) => Err(BufferAccountRejection {
+            index,
+            key,
+            violation: BufferAccountViolation::NotOwnedByProgram { owner },
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys_of(spec: &[ExpectedAccount]) -> Vec<Pubkey> {
+        spec.iter()
+            .map(|account| account.key.unwrap_or_else(Pubkey::new_unique))
+            .collect()
+    }
+
+    #[test]
+    fn matching_accounts_have_no_mismatches() {
+        for spec in [
+            IBC_HANDLER_ACCOUNTS,
+            INIT_STORAGE_ACCOUNT_ACCOUNTS,
+            REALLOC_STORAGE_ACCOUNT_ACCOUNTS,
+            CREATE_TX_BUFFER_ACCOUNTS,
+            REUSE_TX_BUFFER_ACCOUNTS,
+        ] {
+            assert_eq!(account_mismatches(spec, &keys_of(spec), 0), []);
+        }
+    }
+
+    #[test]
+    fn swapped_sysvars_are_reported_with_their_offsets() {
+        let buffer_key = Pubkey::new_unique();
+        let mut keys = [vec![buffer_key], keys_of(INIT_STORAGE_ACCOUNT_ACCOUNTS)].concat();
+        keys.swap(3, 4);
+
+        let messages = account_mismatches(INIT_STORAGE_ACCOUNT_ACCOUNTS, &keys, 1)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                format!(
+                    "AccountMismatch {{ offset: 3, name: rent sysvar, expected: {}, found: {} }}",
+                    rent::ID,
+                    clock::ID
+                ),
+                format!(
+                    "AccountMismatch {{ offset: 4, name: clock sysvar, expected: {}, found: {} }}",
+                    clock::ID,
+                    rent::ID
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn wrong_storage_account_is_reported() {
+        let mut keys = keys_of(IBC_HANDLER_ACCOUNTS);
+        let found = Pubkey::new_unique();
+        keys[1] = found;
+
+        assert_eq!(
+            account_mismatches(IBC_HANDLER_ACCOUNTS, &keys, 0),
+            [AccountMismatch {
+                offset: 1,
+                name: "storage",
+                expected: STORAGE_KEY,
+                found,
+            }]
+        );
+        // Missing accounts are left to the account count checks
+        assert_eq!(account_mismatches(IBC_HANDLER_ACCOUNTS, &keys[..1], 0), []);
+    }
+
+    #[test]
+    fn buffer_accounts_must_be_distinct_program_owned_buffers() {
+        let [first, second] = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let buffers = |keys: &[Pubkey]| keys.iter().map(|&key| (key, id())).collect::<Vec<_>>();
+        assert_eq!(check_buffer_accounts(&buffers(&[first, second])), Ok(()));
+        assert_eq!(check_buffer_accounts(&[]), Ok(()));
+
+        let rejection = |keys: &[Pubkey]| check_buffer_accounts(&buffers(keys)).unwrap_err();
+        assert_eq!(
+            rejection(&[first, second, first]),
+            BufferAccountRejection {
+                index: 2,
+                key: first,
+                violation: BufferAccountViolation::Duplicate { first_index: 0 },
+            }
+        );
+        assert_eq!(
+            rejection(&[first, STORAGE_KEY]).violation,
+            BufferAccountViolation::StorageAccount
+        );
+        assert_eq!(
+            rejection(&[clock::ID]).violation,
+            BufferAccountViolation::Sysvar
+        );
+
+        let owner = system_program::ID;
+        let rejection = check_buffer_accounts(&[(first, id()), (second, owner)]).unwrap_err();
+        assert_eq!(
+            rejection.violation,
+            BufferAccountViolation::NotOwnedByProgram { owner }
+        );
+        assert_eq!(
+            rejection.to_string(),
+            format!(
+                "BufferAccountRejected {{ index: 1, key: {second}, reason: owned by {owner} }}"
+            )
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! Logging of content the program does not control, such as type URLs and errors
+//! that embed message fields. Such content is cut to a fixed number of bytes
+//! before it is logged, so that the log lines and the compute spent formatting
+//! them stay bounded however large the message is.
+
+use {
+    solana_program_runtime::{ic_msg, invoke_context::InvokeContext},
+    solana_sdk::msg,
+    std::fmt::{self, Write},
+};
+
+/// Most bytes of untrusted content logged on one line, unless verbose logs are
+/// on.
+pub(crate) const MAX_TERSE_LOG_LEN: usize = 256;
+
+/// Most bytes of untrusted content logged on one line when verbose logs are on.
+pub(crate) const MAX_VERBOSE_LOG_LEN: usize = 2048;
+
+/// Start of the marker that ends truncated content. The full marker is
+/// `...[truncated <number of bytes left out> bytes]`.
+pub(crate) const TRUNCATION_MARKER: &str = "...[truncated ";
+
+/// Collects at most `max_len` bytes of formatted content, counting the bytes
+/// left out instead of storing them.
+struct BoundedWriter {
+    out: String,
+    max_len: usize,
+    left_out: usize,
+}
+
+impl Write for BoundedWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.left_out != 0 {
+            self.left_out += s.len();
+            return Ok(());
+        }
+        let room = self.max_len - self.out.len();
+        if s.len() <= room {
+            self.out.push_str(s);
+            return Ok(());
+        }
+        let mut end = room;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.out.push_str(&s[..end]);
+        self.left_out = s.len() - end;
+        Ok(())
+    }
+}
+
+/// Formats `content`, keeping at most `max_len` bytes of it. Truncated content
+/// ends in a marker saying how many bytes were left out.
+pub(crate) fn truncate(content: impl fmt::Display, max_len: usize) -> String {
+    let mut writer = BoundedWriter {
+        out: String::new(),
+        max_len,
+        left_out: 0,
+    };
+    // Writing to the buffer cannot fail, though `Display` impls may
+    let _ = write!(writer, "{content}");
+    if writer.left_out != 0 {
+        let _ = write!(writer.out, "{TRUNCATION_MARKER}{} bytes]", writer.left_out);
+    }
+    writer.out
+}
+
+/// Logs `content` after `label`, keeping at most `max_len` bytes of it.
+pub(crate) fn log_trunc(
+    invoke_context: &InvokeContext,
+    label: &str,
+    content: impl fmt::Display,
+    max_len: usize,
+) {
+    ic_msg!(invoke_context, "{}: {}", label, truncate(content, max_len));
+}
+
+/// Logs `content` after `label` from code without an `InvokeContext`, keeping at
+/// most `max_len` bytes of it.
+pub(crate) fn msg_trunc(label: &str, content: impl fmt::Display, max_len: usize) {
+    msg!("{}: {}", label, truncate(content, max_len));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_content_ends_in_a_truncation_marker() {
+        assert_eq!(truncate("short", 8), "short");
+        assert_eq!(truncate("exactly8", 8), "exactly8");
+        assert_eq!(
+            truncate("a".repeat(8 * 1024), 16),
+            format!("{}...[truncated {} bytes]", "a".repeat(16), 8 * 1024 - 16)
+        );
+
+        // Content split across several writes is cut the same way
+        let debug = truncate(format_args!("{:?}", vec!["x".repeat(10); 4]), 20);
+        assert_eq!(debug, r#"["xxxxxxxxxx", "xxxx...[truncated 36 bytes]"#);
+    }
+
+    #[test]
+    fn truncation_keeps_whole_chars() {
+        // Each 'é' is two bytes, so only two of them fit in five bytes
+        assert_eq!(truncate("ééé", 5), "éé...[truncated 2 bytes]");
+    }
+}
@@ -0,0 +1,4 @@
+//! What this build of the program was built from, as generated by
+//! `scripts/build_info.rs`.
+
+include!(concat!(env!("OUT_DIR"), "/build_info.rs"));
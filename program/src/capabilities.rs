@@ -0,0 +1,76 @@
+//! Message families this program handles, which it advertises at
+//! `CapabilitiesPath` so that clients built against a newer proto set can tell
+//! which of their messages it cannot decode before sending them.
+
+use {
+    crate::{
+        build_info,
+        ibc_instruction::msgs::{
+            MsgAbortStoreWriteBatch, MsgAcceptAdminChange, MsgBindPort, MsgCancelAdminChange,
+            MsgClaimAdmin, MsgCloseTxBuffer, MsgForceReleasePort, MsgInitStorageAccount,
+            MsgProposeAdminChange, MsgPruneState, MsgReallocStorageAccount, MsgRefreshCapabilities,
+            MsgReleasePort, MsgRepairIndices, MsgSetIbcFrozen, MsgSetRevisionNumber,
+            MsgStoreWriteBatch, MsgTransferPort, MsgUpdateChainParams, MsgWriteTxBuffer,
+            MsgWriteUpgrade,
+        },
+    },
+    eclipse_ibc_extra_types::Capabilities,
+    ibc::applications::transfer::msgs::transfer::TYPE_URL as TRANSFER_TYPE_URL,
+};
+
+/// Version of this program, as advertised in its capabilities.
+pub const PROGRAM_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Type URLs of every message `IbcInstruction` decodes.
+pub const SUPPORTED_TYPE_URLS: [&str; 40] = [
+    "/ibc.core.client.v1.MsgCreateClient",
+    "/ibc.core.client.v1.MsgUpdateClient",
+    "/ibc.core.client.v1.MsgSubmitMisbehaviour",
+    "/ibc.core.client.v1.MsgUpgradeClient",
+    "/ibc.core.connection.v1.MsgConnectionOpenInit",
+    "/ibc.core.connection.v1.MsgConnectionOpenTry",
+    "/ibc.core.connection.v1.MsgConnectionOpenAck",
+    "/ibc.core.connection.v1.MsgConnectionOpenConfirm",
+    "/ibc.core.channel.v1.MsgChannelOpenInit",
+    "/ibc.core.channel.v1.MsgChannelOpenTry",
+    "/ibc.core.channel.v1.MsgChannelOpenAck",
+    "/ibc.core.channel.v1.MsgChannelOpenConfirm",
+    "/ibc.core.channel.v1.MsgChannelCloseInit",
+    "/ibc.core.channel.v1.MsgChannelCloseConfirm",
+    "/ibc.core.channel.v1.MsgRecvPacket",
+    "/ibc.core.channel.v1.MsgAcknowledgement",
+    "/ibc.core.channel.v1.MsgTimeout",
+    "/ibc.core.channel.v1.MsgTimeoutOnClose",
+    TRANSFER_TYPE_URL,
+    MsgBindPort::TYPE_URL,
+    MsgReleasePort::TYPE_URL,
+    MsgTransferPort::TYPE_URL,
+    MsgInitStorageAccount::TYPE_URL,
+    MsgWriteTxBuffer::TYPE_URL,
+    MsgCloseTxBuffer::TYPE_URL,
+    MsgStoreWriteBatch::TYPE_URL,
+    MsgAbortStoreWriteBatch::TYPE_URL,
+    MsgUpdateChainParams::TYPE_URL,
+    MsgSetIbcFrozen::TYPE_URL,
+    MsgPruneState::TYPE_URL,
+    MsgReallocStorageAccount::TYPE_URL,
+    MsgProposeAdminChange::TYPE_URL,
+    MsgAcceptAdminChange::TYPE_URL,
+    MsgCancelAdminChange::TYPE_URL,
+    MsgClaimAdmin::TYPE_URL,
+    MsgWriteUpgrade::TYPE_URL,
+    MsgRefreshCapabilities::TYPE_URL,
+    MsgForceReleasePort::TYPE_URL,
+    MsgSetRevisionNumber::TYPE_URL,
+    MsgRepairIndices::TYPE_URL,
+];
+
+/// Capabilities of this build of the program.
+#[must_use]
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        type_url_prefixes: SUPPORTED_TYPE_URLS.map(str::to_owned).to_vec(),
+        program_version: PROGRAM_VERSION.to_owned(),
+        build_id: build_info::GIT_HASH.to_owned(),
+    }
+}
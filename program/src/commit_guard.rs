@@ -0,0 +1,235 @@
+//! Checks of what an instruction is about to commit to the IBC state. Before the
+//! handler commits, each registered [`PreCommitValidator`] looks over the summary
+//! of the pending changes, and any violation it finds aborts the commit. The
+//! built-in validators enforce the limits on writes in the chain params.
+
+use {
+    core::fmt::{self, Debug},
+    eclipse_ibc_extra_types::{ChainParams, ValueSizeCap},
+    eclipse_ibc_state::{internal_path::ChainParamsPath, ChangeKind},
+    thiserror::Error,
+};
+
+/// A pending change, as given by `IbcState::pending_summary`: the changed path,
+/// whether its value is set or removed, and the length of the stored value.
+pub(crate) type PendingChange = (String, ChangeKind, usize);
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub(crate) enum CommitViolation {
+    #[error("value of {len} bytes at {path} is larger than the cap of {max_size} bytes under {path_prefix}")]
+    ValueTooLarge {
+        path: String,
+        path_prefix: String,
+        len: usize,
+        max_size: u64,
+    },
+    #[error("values of {total} bytes are more than the cap of {max_total} bytes per commit")]
+    CommitTooLarge { total: u64, max_total: u64 },
+    #[error("write to {path} is forbidden under {path_prefix}")]
+    ForbiddenWrite { path: String, path_prefix: String },
+}
+
+/// Every violation found in the pending changes of a commit, which was aborted.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub(crate) struct CommitRejected {
+    pub(crate) violations: Vec<CommitViolation>,
+}
+
+impl fmt::Display for CommitRejected {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "commit rejected with {} violations",
+            self.violations.len()
+        )?;
+        for violation in &self.violations {
+            write!(fmt, "; {violation}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Looks over the changes an instruction is about to commit.
+pub(crate) trait PreCommitValidator: Debug {
+    /// Returns what is wrong with `pending_changes`, if anything.
+    fn validate(&self, pending_changes: &[PendingChange]) -> Vec<CommitViolation>;
+}
+
+/// Writes to the chain params are left to the admin, so that the limits can
+/// always be changed however tight they are.
+fn is_exempt(path: &str) -> bool {
+    path == ChainParamsPath.to_string()
+}
+
+/// Caps the size of each value stored under a path prefix.
+#[derive(Debug)]
+struct ValueSizeCaps(Vec<ValueSizeCap>);
+
+impl PreCommitValidator for ValueSizeCaps {
+    fn validate(&self, pending_changes: &[PendingChange]) -> Vec<CommitViolation> {
+        let mut violations = vec![];
+        for (path, _, len) in pending_changes {
+            if is_exempt(path) {
+                continue;
+            }
+            for ValueSizeCap {
+                path_prefix,
+                max_size,
+            } in &self.0
+            {
+                if path.starts_with(path_prefix.as_str()) && *len as u64 > *max_size {
+                    violations.push(CommitViolation::ValueTooLarge {
+                        path: path.clone(),
+                        path_prefix: path_prefix.clone(),
+                        len: *len,
+                        max_size: *max_size,
+                    });
+                }
+            }
+        }
+        violations
+    }
+}
+
+/// Caps the total size of the values stored by one commit.
+#[derive(Debug)]
+struct CommitBytesCap(u64);
+
+impl PreCommitValidator for CommitBytesCap {
+    fn validate(&self, pending_changes: &[PendingChange]) -> Vec<CommitViolation> {
+        let total = pending_changes
+            .iter()
+            .filter(|(path, _, _)| !is_exempt(path))
+            .map(|(_, _, len)| *len as u64)
+            .sum::<u64>();
+        if total > self.0 {
+            vec![CommitViolation::CommitTooLarge {
+                total,
+                max_total: self.0,
+            }]
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// Forbids sets and removals of paths under any of the prefixes.
+#[derive(Debug)]
+struct ForbiddenWrites(Vec<String>);
+
+impl PreCommitValidator for ForbiddenWrites {
+    fn validate(&self, pending_changes: &[PendingChange]) -> Vec<CommitViolation> {
+        pending_changes
+            .iter()
+            .filter(|(path, _, _)| !is_exempt(path))
+            .filter_map(|(path, _, _)| {
+                let path_prefix = self
+                    .0
+                    .iter()
+                    .find(|path_prefix| path.starts_with(path_prefix.as_str()))?;
+                Some(CommitViolation::ForbiddenWrite {
+                    path: path.clone(),
+                    path_prefix: path_prefix.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Validators of the limits on writes set in `chain_params`. Limits that are
+/// unset get no validator.
+pub(crate) fn chain_params_validators(
+    chain_params: &ChainParams,
+) -> Vec<Box<dyn PreCommitValidator>> {
+    let mut validators = Vec::<Box<dyn PreCommitValidator>>::new();
+    if !chain_params.value_size_caps.is_empty() {
+        validators.push(Box::new(ValueSizeCaps(
+            chain_params.value_size_caps.clone(),
+        )));
+    }
+    if let Some(max_commit_bytes) = chain_params.max_commit_bytes {
+        validators.push(Box::new(CommitBytesCap(max_commit_bytes)));
+    }
+    if !chain_params.forbidden_write_prefixes.is_empty() {
+        validators.push(Box::new(ForbiddenWrites(
+            chain_params.forbidden_write_prefixes.clone(),
+        )));
+    }
+    validators
+}
+
+/// Runs every validator over `pending_changes`, failing with all the violations
+/// they find.
+pub(crate) fn check(
+    validators: &[Box<dyn PreCommitValidator>],
+    pending_changes: &[PendingChange],
+) -> Result<(), CommitRejected> {
+    let violations = validators
+        .iter()
+        .flat_map(|validator| validator.validate(pending_changes))
+        .collect::<Vec<_>>();
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(CommitRejected { violations })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(path: &str, len: usize) -> PendingChange {
+        (path.to_owned(), ChangeKind::Set, len)
+    }
+
+    #[test]
+    fn chain_params_limits_report_every_violation() {
+        let validators = chain_params_validators(&ChainParams {
+            value_size_caps: vec![ValueSizeCap {
+                path_prefix: "clients/".to_owned(),
+                max_size: 100,
+            }],
+            max_commit_bytes: Some(150),
+            forbidden_write_prefixes: vec!["internal/ics20/".to_owned()],
+            ..ChainParams::default()
+        });
+        let pending_changes = [
+            set("clients/07-tendermint-0/clientState", 120),
+            set("connections/connection-0", 40),
+            (
+                "internal/ics20/denomTraces/abc".to_owned(),
+                ChangeKind::Remove,
+                0,
+            ),
+        ];
+        let CommitRejected { violations } = check(&validators, &pending_changes).unwrap_err();
+        assert_eq!(
+            violations,
+            [
+                CommitViolation::ValueTooLarge {
+                    path: "clients/07-tendermint-0/clientState".to_owned(),
+                    path_prefix: "clients/".to_owned(),
+                    len: 120,
+                    max_size: 100,
+                },
+                CommitViolation::CommitTooLarge {
+                    total: 160,
+                    max_total: 150,
+                },
+                CommitViolation::ForbiddenWrite {
+                    path: "internal/ics20/denomTraces/abc".to_owned(),
+                    path_prefix: "internal/ics20/".to_owned(),
+                },
+            ]
+        );
+
+        // Within the limits, and chain params are never held to them
+        let pending_changes = [
+            set("clients/07-tendermint-0/clientState", 100),
+            set(&ChainParamsPath.to_string(), 1000),
+        ];
+        check(&validators, &pending_changes).unwrap();
+        assert!(chain_params_validators(&ChainParams::default()).is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+use {
+    crate::ibc_handler::{multihop_channel_rejection, IbcHandler},
+    eclipse_ibc_state::IbcAccountData,
+    ibc::core::{validate, MsgEnvelope, RouterError},
+    solana_sdk::sysvar::clock::Clock,
+    std::{mem, sync::PoisonError},
+    thiserror::Error,
+};
+
+/// Why a dry run rejected a message.
+#[derive(Debug, Error)]
+pub enum DryRunError {
+    /// The program refuses the message before handing it to ibc-rs, e.g. because
+    /// IBC is frozen or the message is disabled
+    #[error("{0}")]
+    Rejected(String),
+    #[error(transparent)]
+    Router(#[from] RouterError),
+}
+
+/// What a dry run of a message found.
+#[derive(Debug)]
+pub struct DryRun {
+    pub result: Result<(), DryRunError>,
+    /// Validation callbacks of modules that were skipped rather than run, since
+    /// module programs can only be invoked on chain
+    pub skipped_callbacks: Vec<String>,
+}
+
+/// Validates the router message `envelope`, whose type URL is `type_url`, against
+/// the latest IBC state in `account_data` at the slot and time of `clock`. The
+/// program's own checks run first, in the order it runs them, then
+/// `ibc::core::validate`. Nothing is executed or committed, so a dry run only
+/// says whether the message would be rejected before anything is written.
+pub fn validate_router_msg(
+    account_data: &mut IbcAccountData,
+    clock: &Clock,
+    type_url: &str,
+    mut envelope: MsgEnvelope,
+) -> anyhow::Result<DryRun> {
+    let (mut ibc_handler, skipped_callbacks) =
+        IbcHandler::new_off_chain(&account_data.store, &mut account_data.metadata, clock)?;
+
+    let result = match rejection(&mut ibc_handler, type_url, &mut envelope)? {
+        Some(rejection) => Err(DryRunError::Rejected(rejection)),
+        None => validate(&ibc_handler, envelope).map_err(DryRunError::from),
+    };
+    drop(ibc_handler);
+
+    let skipped_callbacks = mem::take(
+        &mut *skipped_callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner),
+    );
+    Ok(DryRun {
+        result,
+        skipped_callbacks,
+    })
+}
+
+/// Why the program would refuse `envelope` before dispatching it, following
+/// `with_ibc_handler` and the router arm of `process_ibc_instruction`.
+fn rejection(
+    ibc_handler: &mut IbcHandler<'_>,
+    type_url: &str,
+    envelope: &mut MsgEnvelope,
+) -> anyhow::Result<Option<String>> {
+    if ibc_handler.rejects_during_migration(type_url)? {
+        return Ok(Some(format!(
+            "MigrationInProgress {{ type_url: {type_url} }}"
+        )));
+    }
+    if ibc_handler.rejects_while_frozen(type_url) {
+        return Ok(Some(format!("IbcFrozen {{ type_url: {type_url} }}")));
+    }
+    if let Some(prefix) = ibc_handler.disabled_message_prefix(type_url) {
+        return Ok(Some(format!(
+            "MessageDisabled {{ type_url: {type_url}, prefix: {prefix} }}"
+        )));
+    }
+
+    if let Some(rejection) = ibc_handler.canonicalize_type_urls(envelope)? {
+        return Ok(Some(rejection));
+    }
+    if let Some(rejection) = multihop_channel_rejection(envelope) {
+        return Ok(Some(rejection));
+    }
+    if let Some(mismatch) = ibc_handler.client_type_mismatch(envelope)? {
+        return Ok(Some(mismatch));
+    }
+    if let Some(mismatch) = ibc_handler.handshake_chain_mismatch(envelope)? {
+        return Ok(Some(mismatch));
+    }
+    ibc_handler.route_to_channel_owner(envelope)?;
+    ibc_handler.callback_version_mismatch(envelope)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        eclipse_ibc_extra_types::ChainParams,
+        eclipse_ibc_fixtures::clients,
+        eclipse_ibc_known_proto::KnownAnyProto,
+        eclipse_ibc_light_client::EclipseConsensusState,
+        eclipse_ibc_state::{internal_path::ChainParamsPath, IbcState},
+        ibc::core::{ics02_client::msgs::ClientMsg, ContextError},
+        ibc_proto::ibc::core::client::v1::{
+            MsgCreateClient as RawMsgCreateClient, MsgUpdateClient as RawMsgUpdateClient,
+        },
+    };
+
+    const CREATE_CLIENT_TYPE_URL: &str = "/ibc.core.client.v1.MsgCreateClient";
+    const UPDATE_CLIENT_TYPE_URL: &str = "/ibc.core.client.v1.MsgUpdateClient";
+
+    fn account_data(chain_params: ChainParams) -> IbcAccountData {
+        let account_data = IbcAccountData::default();
+        let mut state = IbcState::new(&account_data.store, 0);
+        state.set(&ChainParamsPath, chain_params);
+        state.commit().unwrap();
+        account_data
+    }
+
+    fn create_client() -> MsgEnvelope {
+        MsgEnvelope::Client(ClientMsg::CreateClient(
+            RawMsgCreateClient {
+                client_state: Some(clients::eclipse_client_state(1).encode_as_any()),
+                consensus_state: Some(
+                    EclipseConsensusState::from(clients::eclipse_header(1)).encode_as_any(),
+                ),
+                signer: "signer".to_owned(),
+            }
+            .try_into()
+            .unwrap(),
+        ))
+    }
+
+    #[test]
+    fn messages_are_validated_without_being_executed() {
+        let mut account_data = account_data(ChainParams::default());
+        let clock = Clock::default();
+        let dry_run = validate_router_msg(
+            &mut account_data,
+            &clock,
+            CREATE_CLIENT_TYPE_URL,
+            create_client(),
+        )
+        .unwrap();
+        dry_run.result.unwrap();
+        assert!(dry_run.skipped_callbacks.is_empty());
+        assert_eq!(account_data.metadata.client_id_counter, 0);
+        assert_eq!(account_data.store.read().versions(), [0]);
+
+        // Updating a client that does not exist fails in ibc-rs
+        let update_client = MsgEnvelope::Client(ClientMsg::UpdateClient(
+            RawMsgUpdateClient {
+                client_id: "xx-eclipse-0".to_owned(),
+                header: Some(clients::eclipse_header(2).encode_as_any()),
+                signer: "signer".to_owned(),
+            }
+            .try_into()
+            .unwrap(),
+        ));
+        let dry_run = validate_router_msg(
+            &mut account_data,
+            &clock,
+            UPDATE_CLIENT_TYPE_URL,
+            update_client,
+        )
+        .unwrap();
+        assert!(
+            matches!(
+                dry_run.result,
+                Err(DryRunError::Router(RouterError::ContextError(
+                    ContextError::ClientError(_)
+                )))
+            ),
+            "{:?}",
+            dry_run.result
+        );
+    }
+
+    #[test]
+    fn program_checks_run_before_ibc_rs() {
+        let mut account_data = account_data(ChainParams {
+            frozen: true,
+            ..ChainParams::default()
+        });
+        let dry_run = validate_router_msg(
+            &mut account_data,
+            &Clock::default(),
+            CREATE_CLIENT_TYPE_URL,
+            create_client(),
+        )
+        .unwrap();
+        match dry_run.result {
+            Err(DryRunError::Rejected(rejection)) => {
+                assert!(rejection.starts_with("IbcFrozen"), "{rejection}");
+            }
+            result => panic!("expected a rejection, got {result:?}"),
+        }
+    }
+}
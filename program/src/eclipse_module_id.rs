@@ -0,0 +1,173 @@
+use {
+    core::{fmt, str::FromStr},
+    ibc::core::router::ModuleId,
+    solana_sdk::pubkey::Pubkey,
+    thiserror::Error,
+};
+
+/// Prefix of module IDs in the `solana-<base58 program ID>` format.
+const SOLANA_PREFIX: &str = "solana-";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub(crate) enum EclipseModuleIdError {
+    #[error("module ID {0:?} is not the lowercase hex encoding of a 32-byte program ID")]
+    InvalidLegacyHex(String),
+    #[error("module ID {0:?} does not hold a base58 program ID")]
+    InvalidBase58(String),
+}
+
+/// The ID of a module implemented by a Solana program. Every `ModuleId` the
+/// program stores comes from one of these, so that it can always be turned back
+/// into the program ID when routing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct EclipseModuleId(Pubkey);
+
+impl EclipseModuleId {
+    pub(crate) fn from_pubkey(program_id: Pubkey) -> Self {
+        Self(program_id)
+    }
+
+    pub(crate) fn program_id(self) -> Pubkey {
+        self.0
+    }
+
+    /// Parses the lowercase hex encoding of the program ID, which is the format
+    /// stored so far. Other spellings of the same bytes are rejected, since they
+    /// would be a different `ModuleId` for the same program.
+    pub(crate) fn parse_legacy_hex(module_id: &str) -> Result<Self, EclipseModuleIdError> {
+        let invalid = || EclipseModuleIdError::InvalidLegacyHex(module_id.to_owned());
+        let bytes = hex::decode(module_id).map_err(|_err| invalid())?;
+        let program_id = Pubkey::try_from(bytes).map_err(|_bytes| invalid())?;
+        if hex::encode(program_id) != module_id {
+            return Err(invalid());
+        }
+        Ok(Self(program_id))
+    }
+
+    /// Parses a module ID in any supported format: legacy hex or
+    /// `solana-<base58 program ID>`.
+    pub(crate) fn parse(module_id: &str) -> Result<Self, EclipseModuleIdError> {
+        match module_id.strip_prefix(SOLANA_PREFIX) {
+            Some(base58) => {
+                let invalid = || EclipseModuleIdError::InvalidBase58(module_id.to_owned());
+                let program_id = Pubkey::from_str(base58).map_err(|_err| invalid())?;
+                if program_id.to_string() != base58 {
+                    return Err(invalid());
+                }
+                Ok(Self(program_id))
+            }
+            None => Self::parse_legacy_hex(module_id),
+        }
+    }
+
+    /// The `ModuleId` stored for this module, which is still in the legacy hex
+    /// format.
+    pub(crate) fn to_module_id(self) -> ModuleId {
+        ModuleId::new(hex::encode(self.0))
+    }
+}
+
+impl TryFrom<&ModuleId> for EclipseModuleId {
+    type Error = EclipseModuleIdError;
+
+    fn try_from(module_id: &ModuleId) -> Result<Self, Self::Error> {
+        Self::parse(&module_id.to_string())
+    }
+}
+
+impl fmt::Display for EclipseModuleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{SOLANA_PREFIX}{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::signer::{keypair::Keypair, Signer as _},
+    };
+
+    fn random_program_ids() -> impl Iterator<Item = Pubkey> {
+        (0..256)
+            .map(|_| Keypair::new().pubkey())
+            .chain([Pubkey::default(), Pubkey::new_from_array([0xff; 32])])
+    }
+
+    #[test]
+    fn stored_module_ids_match_the_legacy_format() {
+        let program_id = Pubkey::new_unique();
+        assert_eq!(
+            EclipseModuleId::from_pubkey(program_id)
+                .to_module_id()
+                .to_string(),
+            "0000000000000001000000000000000000000000000000000000000000000000",
+        );
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        for program_id in random_program_ids() {
+            let module_id = EclipseModuleId::from_pubkey(program_id);
+            let stored = module_id.to_module_id();
+            assert_eq!(EclipseModuleId::try_from(&stored), Ok(module_id));
+            assert_eq!(
+                EclipseModuleId::parse_legacy_hex(&stored.to_string()),
+                Ok(module_id)
+            );
+            assert_eq!(
+                EclipseModuleId::parse(&module_id.to_string()),
+                Ok(module_id)
+            );
+            assert_eq!(module_id.program_id(), program_id);
+        }
+    }
+
+    #[test]
+    fn malformed_module_ids_are_rejected() {
+        let program_id = Keypair::new().pubkey();
+        let hex = hex::encode(program_id);
+        let base58 = program_id.to_string();
+        for module_id in [
+            String::new(),
+            "transfer".to_owned(),
+            hex[1..].to_owned(),
+            hex[2..].to_owned(),
+            format!("{hex}00"),
+            hex.to_uppercase(),
+            format!("{}g", &hex[1..]),
+            format!(" {hex}"),
+            "é".repeat(32),
+            SOLANA_PREFIX.to_owned(),
+            format!("{SOLANA_PREFIX}{hex}"),
+            format!("{SOLANA_PREFIX}{base58}{base58}"),
+            format!("{SOLANA_PREFIX}{base58}0"),
+            format!("{SOLANA_PREFIX}1{base58}"),
+            format!("solana:{base58}"),
+            base58.clone(),
+        ] {
+            EclipseModuleId::parse(&module_id).unwrap_err();
+        }
+        EclipseModuleId::parse_legacy_hex(&format!("{SOLANA_PREFIX}{base58}")).unwrap_err();
+    }
+
+    #[test]
+    fn arbitrary_strings_do_not_panic() {
+        for program_id in random_program_ids() {
+            let bytes = program_id.to_bytes();
+            // Mixes of hex digits, base58 digits and arbitrary characters of
+            // varying lengths
+            for len in [0, 1, 31, 32, 63, 64, 65, 128] {
+                let module_id = bytes
+                    .iter()
+                    .cycle()
+                    .take(len)
+                    .map(|&byte| char::from(byte % 96 + 32))
+                    .collect::<String>();
+                let _ = EclipseModuleId::parse(&module_id);
+                let _ = EclipseModuleId::parse(&format!("{SOLANA_PREFIX}{module_id}"));
+                let _ = EclipseModuleId::parse_legacy_hex(&module_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Custom error codes the program fails instructions with. Each code names the
+//! step or IBC message family that rejected the instruction, and the logs of the
+//! transaction carry the details. Codes are never reused or renumbered, so that
+//! clients built against an older program still decode them.
+
+use {
+    crate::account_spec::BUFFER_ACCOUNT_ERR_CODE,
+    eclipse_ibc_state::{ACCOUNT_FULL_ERR_CODE, CORRUPTED_STATE_ERR_CODE},
+    ibc::core::{ContextError, RouterError},
+    solana_sdk::instruction::InstructionError,
+    thiserror::Error,
+};
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum IbcProgramError {
+    /// Messages ibc-rs cannot route, and messages rejected before they are
+    /// routed, such as those for a client of another type
+    #[error("the message could not be routed to an IBC handler")]
+    Router,
+    #[error("the port could not be bound or released, or is not bound")]
+    Port,
+    #[error("the IBC state could not be read, written or committed")]
+    Storage,
+    #[error("the message family is disabled in the chain params")]
+    Disabled,
+    #[error("IBC is frozen")]
+    Frozen,
+    #[error("the IBC state is corrupted and could not be loaded")]
+    CorruptedState,
+    #[error("the tx buffer is larger than the chain params allow")]
+    TxBufferTooLarge,
+    #[error("the data does not fit in the tx buffer")]
+    TxBufferDataTooLarge,
+    #[error("the payer has too many tx buffer bytes open")]
+    TxBufferPayerCapExceeded,
+    #[error("the tx buffer is not owned by the payer or the program")]
+    TxBufferNotOwned,
+    #[error("the ICS-20 transfer failed")]
+    Transfer,
+    #[error("the write falls outside the tx buffer")]
+    TxBufferWriteOutOfBounds,
+    #[error("the IBC storage account is full and must be reallocated")]
+    AccountFull,
+    #[error("the storage account cannot be reallocated to that size")]
+    StorageRealloc,
+    #[error("the admin message or admin change was rejected")]
+    Admin,
+    #[error("the tx buffer accounts of the instruction are invalid")]
+    BufferAccount,
+    #[error("the client message was rejected")]
+    Client,
+    #[error("the connection handshake message was rejected")]
+    Connection,
+    #[error("the channel handshake message was rejected")]
+    Channel,
+    #[error("the packet message was rejected")]
+    Packet,
+    #[error("a store write batch is being applied; retry once it completes")]
+    MigrationInProgress,
+    #[error("the admin message was not signed by the admin")]
+    NotAdmin,
+    #[error("the changes of the instruction break the chain params limits on writes")]
+    CommitRejected,
+}
+
+impl IbcProgramError {
+    pub const ALL: [Self; 23] = [
+        Self::Router,
+        Self::Port,
+        Self::Storage,
+        Self::Disabled,
+        Self::Frozen,
+        Self::CorruptedState,
+        Self::TxBufferTooLarge,
+        Self::TxBufferDataTooLarge,
+        Self::TxBufferPayerCapExceeded,
+        Self::TxBufferNotOwned,
+        Self::Transfer,
+        Self::TxBufferWriteOutOfBounds,
+        Self::AccountFull,
+        Self::StorageRealloc,
+        Self::Admin,
+        Self::BufferAccount,
+        Self::Client,
+        Self::Connection,
+        Self::Channel,
+        Self::Packet,
+        Self::MigrationInProgress,
+        Self::NotAdmin,
+        Self::CommitRejected,
+    ];
+
+    /// Code of `InstructionError::Custom` this error fails the instruction with.
+    #[must_use]
+    pub const fn code(self) -> u32 {
+        match self {
+            Self::Router => 0x97,
+            Self::Port => 0x98,
+            Self::Storage => 0x99,
+            Self::Disabled => 0x9a,
+            Self::Frozen => 0x9b,
+            Self::CorruptedState => CORRUPTED_STATE_ERR_CODE,
+            Self::TxBufferTooLarge => 0x9d,
+            Self::TxBufferDataTooLarge => 0x9e,
+            Self::TxBufferPayerCapExceeded => 0x9f,
+            Self::TxBufferNotOwned => 0xa0,
+            Self::Transfer => 0xa1,
+            Self::TxBufferWriteOutOfBounds => 0xa2,
+            Self::AccountFull => ACCOUNT_FULL_ERR_CODE,
+            Self::StorageRealloc => 0xa4,
+            Self::Admin => 0xa5,
+            Self::BufferAccount => BUFFER_ACCOUNT_ERR_CODE,
+            // Router errors by the message family that failed
+            Self::Client => 0xa7,
+            Self::Connection => 0xa8,
+            Self::Channel => 0xa9,
+            Self::Packet => 0xaa,
+            Self::MigrationInProgress => 0xab,
+            Self::NotAdmin => 0xac,
+            Self::CommitRejected => 0xad,
+        }
+    }
+}
+
+impl From<IbcProgramError> for InstructionError {
+    fn from(err: IbcProgramError) -> Self {
+        Self::Custom(err.code())
+    }
+}
+
+impl From<&RouterError> for IbcProgramError {
+    fn from(err: &RouterError) -> Self {
+        match err {
+            RouterError::ContextError(ContextError::ClientError(_)) => Self::Client,
+            RouterError::ContextError(ContextError::ConnectionError(_)) => Self::Connection,
+            RouterError::ContextError(ContextError::ChannelError(_)) => Self::Channel,
+            RouterError::ContextError(ContextError::PacketError(_)) => Self::Packet,
+            RouterError::UnknownPort { .. } | RouterError::ModuleNotFound => Self::Port,
+            _ => Self::Router,
+        }
+    }
+}
+
+/// The error a custom error code of the program stands for, if any.
+#[must_use]
+pub fn decode(code: u32) -> Option<IbcProgramError> {
+    IbcProgramError::ALL
+        .into_iter()
+        .find(|err| err.code() == code)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        ibc::core::{
+            ics02_client::error::ClientError, ics04_channel::error::PacketError,
+            ics24_host::identifier::PortId,
+        },
+        std::collections::HashSet,
+    };
+
+    #[test]
+    fn every_error_round_trips_through_its_code() {
+        for err in IbcProgramError::ALL {
+            assert_eq!(decode(err.code()), Some(err), "{err:?}");
+            assert_eq!(
+                InstructionError::from(err),
+                InstructionError::Custom(err.code())
+            );
+        }
+        let codes = IbcProgramError::ALL
+            .iter()
+            .map(|err| err.code())
+            .collect::<HashSet<_>>();
+        assert_eq!(codes.len(), IbcProgramError::ALL.len());
+        assert_eq!(decode(0), None);
+        assert_eq!(decode(0xae), None);
+    }
+
+    #[test]
+    fn router_errors_map_to_their_message_family() {
+        let client_err = RouterError::ContextError(ContextError::ClientError(ClientError::Other {
+            description: "test".to_owned(),
+        }));
+        assert_eq!(IbcProgramError::from(&client_err), IbcProgramError::Client);
+        let packet_err =
+            RouterError::ContextError(ContextError::PacketError(PacketError::MissingNextRecvSeq {
+                port_id: PortId::transfer(),
+                channel_id: "channel-0".parse().unwrap(),
+            }));
+        assert_eq!(IbcProgramError::from(&packet_err), IbcProgramError::Packet);
+        assert_eq!(
+            IbcProgramError::from(&RouterError::ModuleNotFound),
+            IbcProgramError::Port
+        );
+        assert_eq!(
+            IbcProgramError::from(&RouterError::UnknownMessageTypeUrl {
+                url: "/unknown".to_owned(),
+            }),
+            IbcProgramError::Router
+        );
+    }
+}
@@ -1,5 +1,10 @@
 use {
-    crate::ibc_instruction::IbcInstruction,
+    crate::{
+        account_spec::{self, BUFFER_ACCOUNT_ERR_CODE},
+        bounded_log::{self, MAX_TERSE_LOG_LEN},
+        ibc_instruction::IbcInstruction,
+    },
+    anyhow::bail,
     borsh::{BorshDeserialize, BorshSerialize},
     eclipse_ibc_known_proto::KnownProto,
     ibc_proto::google::protobuf,
@@ -16,24 +21,56 @@ pub struct IbcContractInstruction {
     pub last_instruction_part: Vec<u8>,
 }
 
+/// Decodes the `Any` from the reassembled instruction data, rejecting the data if
+/// the decoded message does not account for every byte. Prost otherwise silently
+/// skips trailing unknown fields, so a miscounted `extra_accounts_for_instruction`
+/// could produce a different message than the one intended.
+fn decode_any_exact(ibc_instruction_data: &[u8]) -> anyhow::Result<protobuf::Any> {
+    let any_msg = protobuf::Any::decode(ibc_instruction_data)?;
+    let encoded_len = prost::Message::encoded_len(&any_msg);
+    if encoded_len != ibc_instruction_data.len() {
+        bail!(
+            "decoded Any does not consume the entire instruction; decoded length: {}, instruction length: {}",
+            encoded_len,
+            ibc_instruction_data.len(),
+        );
+    }
+    Ok(any_msg)
+}
+
+/// Returns the parsed instruction along with its type URL and the number of
+/// accounts holding the leading parts of the instruction data.
 pub fn parse_instruction(
     invoke_context: &InvokeContext,
     transaction_context: &TransactionContext,
     instruction_context: &InstructionContext,
-) -> Result<(IbcInstruction, usize), InstructionError> {
+) -> Result<(IbcInstruction, String, usize), InstructionError> {
     let instruction_data = instruction_context.get_instruction_data();
     let IbcContractInstruction {
         extra_accounts_for_instruction,
         mut last_instruction_part,
     } = BorshDeserialize::try_from_slice(instruction_data).map_err(|err| {
-        ic_msg!(
+        bounded_log::log_trunc(
             invoke_context,
-            "could not parse instruction as IbcContractInstruction: {:?}",
-            err
+            "could not parse instruction as IbcContractInstruction",
+            format_args!("{err:?}"),
+            MAX_TERSE_LOG_LEN,
         );
         InstructionError::InvalidInstructionData
     })?;
 
+    let buffer_accounts = (0..extra_accounts_for_instruction)
+        .map(|account_index| {
+            let extra_account = instruction_context
+                .try_borrow_instruction_account(transaction_context, account_index)?;
+            Ok((*extra_account.get_key(), *extra_account.get_owner()))
+        })
+        .collect::<Result<Vec<_>, InstructionError>>()?;
+    account_spec::check_buffer_accounts(&buffer_accounts).map_err(|rejection| {
+        ic_msg!(invoke_context, "{}", rejection);
+        InstructionError::Custom(BUFFER_ACCOUNT_ERR_CODE)
+    })?;
+
     let mut ibc_instruction_data: Vec<u8> = vec![];
     for account_index in 0..extra_accounts_for_instruction {
         let extra_account = instruction_context
@@ -43,26 +80,63 @@ pub fn parse_instruction(
 
     ibc_instruction_data.append(&mut last_instruction_part);
 
-    let any_msg = protobuf::Any::decode(&*ibc_instruction_data).map_err(|err| {
-        ic_msg!(
+    let any_msg = decode_any_exact(&ibc_instruction_data).map_err(|err| {
+        bounded_log::log_trunc(
             invoke_context,
-            "could not parse instruction as Any Protobuf: {:?}",
-            err
+            "could not parse instruction as Any Protobuf",
+            format_args!("{err:?}"),
+            MAX_TERSE_LOG_LEN,
         );
         InstructionError::InvalidInstructionData
     })?;
 
     let type_url = any_msg.type_url.clone();
-    ic_msg!(invoke_context, "IBC instruction type: {}", type_url);
+    bounded_log::log_trunc(
+        invoke_context,
+        "IBC instruction type",
+        &type_url,
+        MAX_TERSE_LOG_LEN,
+    );
 
     let ibc_instruction: IbcInstruction = any_msg.try_into().map_err(|err| {
-        ic_msg!(
+        bounded_log::log_trunc(
             invoke_context,
-            "could not parse Any Protobuf into a specific instruction: {:?}",
-            err
+            "could not parse Any Protobuf into a specific instruction",
+            format_args!("{err:?}"),
+            MAX_TERSE_LOG_LEN,
         );
         InstructionError::InvalidInstructionData
     })?;
 
-    Ok((ibc_instruction, extra_accounts_for_instruction))
+    Ok((ibc_instruction, type_url, extra_accounts_for_instruction))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::ibc_instruction::msgs::{MsgBindPort, MsgInitStorageAccount},
+        eclipse_ibc_known_proto::KnownAnyProto,
+    };
+
+    #[test]
+    fn decode_any_exact_accepts_whole_message() {
+        let any_msg = MsgBindPort {
+            port_id: "transfer".parse().unwrap(),
+            account_templates: Default::default(),
+        }
+        .encode_as_any();
+        let decoded = decode_any_exact(&any_msg.clone().encode()).unwrap();
+        assert_eq!(decoded, any_msg);
+    }
+
+    #[test]
+    fn decode_any_exact_rejects_trailing_bytes() {
+        let mut ibc_instruction_data = MsgInitStorageAccount::default().encode_as_any().encode();
+        // An unknown length-delimited field 3 that prost would otherwise skip
+        ibc_instruction_data.extend_from_slice(&[0x1a, 0x02, 0xde, 0xad]);
+
+        let err = decode_any_exact(&ibc_instruction_data).unwrap_err();
+        assert!(err.to_string().contains("decoded length"));
+    }
 }
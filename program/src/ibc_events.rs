@@ -0,0 +1,380 @@
+use {
+    crate::bounded_log,
+    anyhow::{anyhow, bail},
+    ibc::core::{events::IbcEvent, ics02_client::height::Height},
+    serde::{Deserialize, Serialize},
+    solana_sdk::program::MAX_RETURN_DATA,
+    tendermint::abci,
+};
+
+/// Prefix of the program log line of each IBC event. The full line is
+/// `ibc-event:<event type>:<JSON array of attributes>`, where the event type and
+/// attributes are those of the event's ABCI form, as relayers see on Cosmos chains.
+pub const EVENT_LOG_PREFIX: &str = "ibc-event:";
+
+/// Prefix Solana adds to lines logged by a program.
+const PROGRAM_LOG_PREFIX: &str = "Program log: ";
+
+#[derive(Debug, Deserialize, Serialize)]
+struct LoggedAttribute {
+    key: String,
+    value: String,
+    index: bool,
+}
+
+/// Key of the attribute added to `update_client` events holding the latest height
+/// of the client before the update. Eclipse clients can skip heights, so relayers
+/// cannot derive it from the new consensus height.
+pub const PREVIOUS_LATEST_HEIGHT_KEY: &str = "previous_latest_height";
+
+/// Most bytes of an attribute key logged with an IBC event. Longer keys are
+/// truncated, ending in a truncation marker.
+pub const MAX_EVENT_ATTRIBUTE_KEY_LEN: usize = bounded_log::MAX_TERSE_LOG_LEN;
+
+/// Most bytes of an attribute value logged with an IBC event. Longer values, such
+/// as the data of large packets, are truncated and end in a truncation marker.
+/// Solana keeps at most 10 KB of logs per transaction, so such values would not
+/// survive intact anyway.
+pub const MAX_EVENT_ATTRIBUTE_VALUE_LEN: usize = 4096;
+
+/// Whether a logged attribute key or value was cut short by the program.
+#[must_use]
+pub fn is_truncated(logged: &str) -> bool {
+    logged.ends_with(" bytes]") && logged.contains(bounded_log::TRUNCATION_MARKER)
+}
+
+/// Formats an IBC event as a log line.
+pub fn event_log(event: IbcEvent) -> anyhow::Result<String> {
+    event_log_with_attributes(event, vec![])
+}
+
+/// Formats an IBC event as a log line, with `extra_attributes` appended to the
+/// ones ibc-rs gives the event. Attribute keys and values are truncated to
+/// [`MAX_EVENT_ATTRIBUTE_KEY_LEN`] and [`MAX_EVENT_ATTRIBUTE_VALUE_LEN`] bytes.
+pub fn event_log_with_attributes(
+    event: IbcEvent,
+    extra_attributes: Vec<(&str, String)>,
+) -> anyhow::Result<String> {
+    let abci::Event { kind, attributes } =
+        abci::Event::try_from(event).map_err(|err| anyhow!("IBC event has no ABCI form: {err}"))?;
+    let extra_attributes = extra_attributes
+        .into_iter()
+        .map(|(key, value)| abci::EventAttribute {
+            key: key.to_owned(),
+            value,
+            index: true,
+        });
+    let attributes = attributes
+        .into_iter()
+        .chain(extra_attributes)
+        .map(|attribute| LoggedAttribute {
+            key: truncate_attribute(attribute.key, MAX_EVENT_ATTRIBUTE_KEY_LEN),
+            value: truncate_attribute(attribute.value, MAX_EVENT_ATTRIBUTE_VALUE_LEN),
+            index: attribute.index,
+        })
+        .collect::<Vec<_>>();
+    Ok(format!(
+        "{EVENT_LOG_PREFIX}{kind}:{}",
+        serde_json::to_string(&attributes)?
+    ))
+}
+
+fn truncate_attribute(logged: String, max_len: usize) -> String {
+    if logged.len() <= max_len {
+        logged
+    } else {
+        bounded_log::truncate(logged, max_len)
+    }
+}
+
+/// Parses a log line written by [`event_log`], with or without Solana's program
+/// log prefix. Returns `None` for lines that are not IBC events.
+pub fn parse_event_log(log: &str) -> anyhow::Result<Option<abci::Event>> {
+    let log = log.strip_prefix(PROGRAM_LOG_PREFIX).unwrap_or(log);
+    let event = match log.strip_prefix(EVENT_LOG_PREFIX) {
+        Some(event) => event,
+        None => return Ok(None),
+    };
+    let (kind, attributes) = event
+        .split_once(':')
+        .ok_or_else(|| anyhow!("IBC event log is missing its attributes: {log}"))?;
+    if kind.is_empty() {
+        bail!("IBC event log is missing its event type: {log}");
+    }
+
+    let attributes = serde_json::from_str::<Vec<LoggedAttribute>>(attributes)?
+        .into_iter()
+        .map(|LoggedAttribute { key, value, index }| abci::EventAttribute { key, value, index })
+        .collect();
+    Ok(Some(abci::Event {
+        kind: kind.to_owned(),
+        attributes,
+    }))
+}
+
+/// What relayers need from an `update_client` event to chain client updates.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct UpdateClientPayload {
+    pub client_id: String,
+    pub client_type: String,
+    /// Height of the consensus state the update added, the latest one for a
+    /// batch of headers
+    pub consensus_height: String,
+    /// Latest height of the client before the update, if it was logged
+    pub previous_latest_height: Option<String>,
+}
+
+impl UpdateClientPayload {
+    /// Reads the payload of an `update_client` event, or returns `None` for other
+    /// events.
+    pub fn from_event(event: &abci::Event) -> anyhow::Result<Option<Self>> {
+        if event.kind != "update_client" {
+            return Ok(None);
+        }
+        let attribute = |key: &str| {
+            event
+                .attributes
+                .iter()
+                .find(|attribute| attribute.key == key)
+                .map(|attribute| attribute.value.clone())
+        };
+        let required = |key: &str| {
+            attribute(key).ok_or_else(|| anyhow!("update_client event is missing {key}"))
+        };
+        let height = |height: String| -> anyhow::Result<String> {
+            height.parse::<Height>()?;
+            Ok(height)
+        };
+        Ok(Some(Self {
+            client_id: required("client_id")?,
+            client_type: required("client_type")?,
+            consensus_height: height(required("consensus_height")?)?,
+            previous_latest_height: attribute(PREVIOUS_LATEST_HEIGHT_KEY)
+                .map(height)
+                .transpose()?,
+        }))
+    }
+}
+
+/// Joins the event logs of an instruction into its return data, one per line.
+/// Return data is limited to `MAX_RETURN_DATA` bytes, so only the leading events
+/// that fit are included; the number included is returned alongside.
+#[must_use]
+pub fn encode_return_data(event_logs: &[String]) -> (Vec<u8>, usize) {
+    let mut return_data = Vec::new();
+    let mut included = 0;
+    for event_log in event_logs {
+        let separator_len = usize::from(!return_data.is_empty());
+        if return_data.len() + separator_len + event_log.len() > MAX_RETURN_DATA {
+            break;
+        }
+        if separator_len != 0 {
+            return_data.push(b'\n');
+        }
+        return_data.extend_from_slice(event_log.as_bytes());
+        included += 1;
+    }
+    (return_data, included)
+}
+
+/// Parses the return data of an IBC instruction into its events.
+pub fn parse_return_data(return_data: &[u8]) -> anyhow::Result<Vec<abci::Event>> {
+    std::str::from_utf8(return_data)?
+        .lines()
+        .map(|log| parse_event_log(log)?.ok_or_else(|| anyhow!("Not an IBC event log: {log}")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        ibc::core::{
+            events::{MessageEvent, ModuleEvent, ModuleEventAttribute},
+            ics02_client::{
+                client_type::ClientType,
+                events::{CreateClient, UpdateClient},
+                height::Height,
+            },
+            ics04_channel::{
+                channel::Order,
+                events::{SendPacket, WriteAcknowledgement},
+                packet::Packet,
+            },
+            ics24_host::identifier::{ClientId, ConnectionId},
+        },
+        ibc_proto::{
+            google::protobuf,
+            ibc::core::{channel::v1::Packet as RawPacket, client::v1::Height as RawHeight},
+        },
+    };
+
+    fn packet() -> Packet {
+        packet_with_data(br#"{"amount":"1"}"#.to_vec())
+    }
+
+    fn packet_with_data(data: Vec<u8>) -> Packet {
+        RawPacket {
+            sequence: 1,
+            source_port: "transfer".to_owned(),
+            source_channel: "channel-0".to_owned(),
+            destination_port: "transfer".to_owned(),
+            destination_channel: "channel-1".to_owned(),
+            data,
+            timeout_height: Some(RawHeight {
+                revision_number: 0,
+                revision_height: 100,
+            }),
+            timeout_timestamp: 0,
+        }
+        .try_into()
+        .unwrap()
+    }
+
+    fn events() -> Vec<IbcEvent> {
+        vec![
+            IbcEvent::CreateClient(CreateClient::new(
+                ClientId::default(),
+                ClientType::new("07-tendermint".to_owned()).unwrap(),
+                Height::new(0, 10).unwrap(),
+            )),
+            IbcEvent::SendPacket(SendPacket::new(
+                packet(),
+                Order::Unordered,
+                ConnectionId::default(),
+            )),
+            IbcEvent::WriteAcknowledgement(WriteAcknowledgement::new(
+                packet(),
+                br#"{"result":"AQ=="}"#.to_vec().try_into().unwrap(),
+                ConnectionId::default(),
+            )),
+            IbcEvent::Message(MessageEvent::Channel),
+            IbcEvent::Module(ModuleEvent {
+                kind: "fungible_token_packet".to_owned(),
+                attributes: vec![ModuleEventAttribute {
+                    key: "success".to_owned(),
+                    value: "true".to_owned(),
+                }],
+            }),
+        ]
+    }
+
+    #[test]
+    fn event_logs_round_trip() {
+        for event in events() {
+            let expected = abci::Event::try_from(event.clone()).unwrap();
+            let log = event_log(event).unwrap();
+            assert!(
+                log.starts_with(&format!("{EVENT_LOG_PREFIX}{}:", expected.kind)),
+                "{log}"
+            );
+
+            assert_eq!(parse_event_log(&log).unwrap(), Some(expected.clone()));
+            let program_log = format!("{PROGRAM_LOG_PREFIX}{log}");
+            assert_eq!(parse_event_log(&program_log).unwrap(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn oversized_attributes_are_truncated_with_a_marker() {
+        let data = vec![b'a'; 8 * 1024];
+        let event = IbcEvent::SendPacket(SendPacket::new(
+            packet_with_data(data),
+            Order::Unordered,
+            ConnectionId::default(),
+        ));
+        let log = event_log(event).unwrap();
+        let event = parse_event_log(&log).unwrap().unwrap();
+        let value = |key: &str| {
+            event
+                .attributes
+                .iter()
+                .find(|attribute| attribute.key == key)
+                .unwrap()
+                .value
+                .clone()
+        };
+
+        // The data is logged as text and as hex, twice its size
+        let packet_data_hex = value("packet_data_hex");
+        assert!(is_truncated(&packet_data_hex), "{packet_data_hex}");
+        assert!(packet_data_hex.ends_with("...[truncated 12288 bytes]"));
+        assert!(is_truncated(&value("packet_data")));
+        assert!(!is_truncated(&value("packet_sequence")));
+        assert!(log.len() < 3 * MAX_EVENT_ATTRIBUTE_VALUE_LEN);
+    }
+
+    #[test]
+    fn other_logs_are_not_events() {
+        assert_eq!(
+            parse_event_log("Program log: IBC instruction type: /x").unwrap(),
+            None
+        );
+        parse_event_log("ibc-event:send_packet").unwrap_err();
+        parse_event_log("ibc-event::[]").unwrap_err();
+        parse_event_log("ibc-event:send_packet:{").unwrap_err();
+    }
+
+    #[test]
+    fn return_data_keeps_the_events_that_fit() {
+        let event_logs = events()
+            .into_iter()
+            .map(|event| event_log(event).unwrap())
+            .collect::<Vec<_>>();
+
+        let (return_data, included) = encode_return_data(&event_logs[..2]);
+        assert_eq!(included, 2);
+        assert_eq!(
+            parse_return_data(&return_data).unwrap(),
+            event_logs[..2]
+                .iter()
+                .map(|log| parse_event_log(log).unwrap().unwrap())
+                .collect::<Vec<_>>()
+        );
+
+        let many_event_logs = vec![event_logs[1].clone(); 100];
+        let (return_data, included) = encode_return_data(&many_event_logs);
+        assert!(return_data.len() <= MAX_RETURN_DATA);
+        assert!(0 < included && included < many_event_logs.len());
+        assert_eq!(parse_return_data(&return_data).unwrap().len(), included);
+    }
+
+    #[test]
+    fn update_client_payload_names_the_previous_height() {
+        let event = IbcEvent::UpdateClient(UpdateClient::new(
+            "xx-eclipse-0".parse().unwrap(),
+            ClientType::new("xx-eclipse".to_owned()).unwrap(),
+            Height::new(0, 20).unwrap(),
+            vec![Height::new(0, 20).unwrap()],
+            protobuf::Any::default(),
+        ));
+        let log = event_log_with_attributes(
+            event.clone(),
+            vec![(PREVIOUS_LATEST_HEIGHT_KEY, "0-12".to_owned())],
+        )
+        .unwrap();
+
+        let payload = UpdateClientPayload::from_event(&parse_event_log(&log).unwrap().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            payload,
+            UpdateClientPayload {
+                client_id: "xx-eclipse-0".to_owned(),
+                client_type: "xx-eclipse".to_owned(),
+                consensus_height: "0-20".to_owned(),
+                previous_latest_height: Some("0-12".to_owned()),
+            }
+        );
+
+        // Logged without the extra attribute, the previous height is unknown
+        let log = event_log(event).unwrap();
+        let payload = UpdateClientPayload::from_event(&parse_event_log(&log).unwrap().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(payload.previous_latest_height, None);
+
+        let send_packet = abci::Event::try_from(events().swap_remove(1)).unwrap();
+        assert_eq!(UpdateClientPayload::from_event(&send_packet).unwrap(), None);
+    }
+}
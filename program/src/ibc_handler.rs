@@ -1,30 +1,65 @@
 use {
-    crate::module_instruction::*,
+    crate::{
+        bounded_log::{self, MAX_TERSE_LOG_LEN, MAX_VERBOSE_LOG_LEN},
+        capabilities,
+        commit_guard::{self, PreCommitValidator},
+        eclipse_module_id::EclipseModuleId,
+        ibc_events,
+        ibc_instruction::msgs::{
+            MsgAbortStoreWriteBatch, MsgAcceptAdminChange, MsgCancelAdminChange, MsgClaimAdmin,
+            MsgForceReleasePort, MsgProposeAdminChange, MsgPruneState, MsgReallocStorageAccount,
+            MsgRefreshCapabilities, MsgRepairIndices, MsgSetIbcFrozen, MsgSetRevisionNumber,
+            MsgStoreWriteBatch, MsgUpdateChainParams, MsgWriteUpgrade,
+        },
+        ics20_module::{ics20_module_id, Ics20Module},
+        module_instruction::*,
+        store_write_batch::{self, IndexRepair, StoreWriteBatchError},
+    },
     anyhow::anyhow,
     core::{
+        fmt::Display,
         ops::Bound::{Excluded, Unbounded},
         str::FromStr,
     },
-    eclipse_ibc_light_client::{eclipse_chain, EclipseConsensusState},
+    eclipse_ibc_extra_types::{
+        CallbackVersions, Capabilities, ChainParams, ConsensusHeights, ModuleAccountTemplates,
+        PendingAdminChange, StoreWriteBatchCursor,
+    },
+    eclipse_ibc_known_path::KnownPath,
+    eclipse_ibc_known_proto::MAX_TIMESTAMP_NANOS,
+    eclipse_ibc_light_client::{
+        canonical_type_url, eclipse_chain, EclipseClientState, EclipseConsensusState,
+        ECLIPSE_CLIENT_STATE_TYPE_URL,
+    },
     eclipse_ibc_state::{
         decode_client_state, decode_consensus_state, encode_client_state, encode_consensus_state,
+        header_batch_type_url_of_client, header_type_url_of_client,
         internal_path::{
-            AllModulesPath, ClientUpdateHeightPath, ClientUpdateTimePath, ConsensusHeightsPath,
+            AdminPath, AllModulesPath, CapabilitiesPath, ChainParamsPath, ChannelOwnerPath,
+            ClientTypeUrlPath, ClientUpdateHeightPath, ClientUpdateTimePath, ConsensusHeightsPath,
+            InflightPacketsPath, LegacyTypeUrlCountPath, ModuleAccountTemplatesPath,
+            ModuleCallbackVersionsPath, PendingAdminChangePath, ProcessedHeightsPath, SlotTimePath,
+            StoreWriteBatchCursorPath,
         },
-        IbcMetadata, IbcState, IbcStore,
+        tracked_chain_id, IbcMetadata, IbcState, IbcStore, LoadChainParams, StorePruning,
     },
     ibc::{
+        applications::transfer::msgs::transfer::MsgTransfer,
         core::{
-            events::IbcEvent,
+            events::{IbcEvent, ModuleEvent, ModuleEventAttribute},
             ics02_client::{
                 client_state::ClientState, consensus_state::ConsensusState, error::ClientError,
-                height::Height,
+                height::Height, msgs::ClientMsg,
+            },
+            ics03_connection::{
+                connection::ConnectionEnd, error::ConnectionError, msgs::ConnectionMsg,
             },
-            ics03_connection::{connection::ConnectionEnd, error::ConnectionError},
             ics04_channel::{
-                channel::{ChannelEnd, Counterparty, Order},
+                channel::{ChannelEnd, Counterparty, Order, State as ChannelState},
                 commitment::{AcknowledgementCommitment, PacketCommitment},
                 error::{ChannelError, PacketError, PortError},
+                handler::send_packet::send_packet,
+                msgs::{ChannelMsg, PacketMsg},
                 packet::{Acknowledgement, Packet, Receipt, Sequence},
                 Version,
             },
@@ -34,28 +69,171 @@ use {
                 path::{
                     AckPath, ChannelEndPath, ClientConnectionPath, ClientConsensusStatePath,
                     ClientStatePath, CommitmentPath, ConnectionPath, PortPath, ReceiptPath,
-                    SeqAckPath, SeqRecvPath, SeqSendPath,
+                    SeqAckPath, SeqRecvPath, SeqSendPath, UpgradeClientPath,
                 },
             },
             router::{Module, ModuleExtras, ModuleId, Router},
             timestamp::Timestamp,
-            ContextError, ExecutionContext, ValidationContext,
+            ContextError, ExecutionContext, MsgEnvelope, ValidationContext,
         },
         Signer,
     },
-    ibc_proto::google::protobuf,
+    ibc_proto::{
+        google::protobuf,
+        ibc::core::{
+            channel::v1::{
+                MsgChannelCloseConfirm as RawMsgChannelCloseConfirm,
+                MsgChannelCloseInit as RawMsgChannelCloseInit,
+                MsgChannelOpenAck as RawMsgChannelOpenAck,
+                MsgChannelOpenConfirm as RawMsgChannelOpenConfirm,
+                MsgChannelOpenInit as RawMsgChannelOpenInit,
+                MsgChannelOpenTry as RawMsgChannelOpenTry, State as RawChannelState,
+            },
+            client::v1::{
+                MsgCreateClient as RawMsgCreateClient,
+                MsgSubmitMisbehaviour as RawMsgSubmitMisbehaviour,
+                MsgUpdateClient as RawMsgUpdateClient, MsgUpgradeClient as RawMsgUpgradeClient,
+            },
+            connection::v1::{
+                MsgConnectionOpenAck as RawMsgConnectionOpenAck,
+                MsgConnectionOpenConfirm as RawMsgConnectionOpenConfirm,
+                State as RawConnectionState,
+            },
+        },
+    },
     solana_sdk::{
         clock::Slot,
-        instruction::Instruction,
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
         msg,
         program::{get_return_data, invoke},
         pubkey::Pubkey,
         sysvar::clock::Clock,
     },
-    std::{collections::BTreeMap, time::Duration},
+    std::{
+        collections::BTreeMap,
+        mem,
+        sync::{Arc, Mutex, PoisonError},
+        time::Duration,
+    },
     tendermint::time::Time as TendermintTime,
+    thiserror::Error,
 };
 
+/// Descriptions of the module callbacks an off-chain handler skipped, shared with
+/// its modules.
+pub(super) type SkippedCallbacks = Arc<Mutex<Vec<String>>>;
+
+/// Kind of the event reporting that a client message carried a legacy Eclipse type
+/// URL, which older CLIs generate. Deprecated and rewritten for now.
+const LEGACY_TYPE_URL_EVENT: &str = "legacy_type_url";
+
+/// Prefix of the admin messages, which are accepted while a store write batch is
+/// being applied so that the batch can be completed or the endpoint recovered.
+const ADMIN_TYPE_URL_PREFIX: &str = "/eclipse.ibc.admin.";
+
+/// Latest recorded update heights of each client whose update time and height are
+/// kept by [`IbcHandler::prune_client_updates`] even once their delay windows
+/// have passed, so that proofs at recent heights of the client stay verifiable.
+const RETAINED_PROCESSED_HEIGHTS: usize = 16;
+
+/// Admin messages that only the admin may send. Without an admin, nobody may send
+/// them until one is claimed with `MsgClaimAdmin`.
+const ADMIN_ONLY_TYPE_URLS: [&str; 13] = [
+    MsgStoreWriteBatch::TYPE_URL,
+    MsgAbortStoreWriteBatch::TYPE_URL,
+    MsgReallocStorageAccount::TYPE_URL,
+    MsgUpdateChainParams::TYPE_URL,
+    MsgSetIbcFrozen::TYPE_URL,
+    MsgPruneState::TYPE_URL,
+    MsgProposeAdminChange::TYPE_URL,
+    MsgCancelAdminChange::TYPE_URL,
+    MsgWriteUpgrade::TYPE_URL,
+    MsgRefreshCapabilities::TYPE_URL,
+    MsgForceReleasePort::TYPE_URL,
+    MsgSetRevisionNumber::TYPE_URL,
+    MsgRepairIndices::TYPE_URL,
+];
+
+/// Why an admin message or admin change was rejected.
+#[derive(Debug, Error)]
+pub(super) enum AdminError {
+    #[error("NotAdmin {{ signer: {signer}, admin: {admin} }}")]
+    NotAdmin { signer: Pubkey, admin: Pubkey },
+    #[error("NoAdmin {{ signer: {signer} }}")]
+    NoAdmin { signer: Pubkey },
+    #[error("AdminAlreadySet {{ admin: {admin} }}")]
+    AlreadySet { admin: Pubkey },
+    #[error("NotBootstrapAuthority {{ signer: {signer} }}")]
+    NotBootstrapAuthority { signer: Pubkey },
+    #[error(
+        "AdminChangeInPast {{ effective_after_slot: {effective_after_slot}, current_slot: {current_slot} }}"
+    )]
+    ChangeInPast {
+        effective_after_slot: Slot,
+        current_slot: Slot,
+    },
+    #[error("NoPendingAdminChange")]
+    NoPendingChange,
+    #[error("NotProposedAdmin {{ signer: {signer}, new_admin: {new_admin} }}")]
+    NotProposedAdmin { signer: Pubkey, new_admin: String },
+    #[error(
+        "AdminChangeNotYetEffective {{ effective_after_slot: {effective_after_slot}, current_slot: {current_slot} }}"
+    )]
+    NotYetEffective {
+        effective_after_slot: Slot,
+        current_slot: Slot,
+    },
+    #[error("UpgradeInPast {{ upgrade_height: {upgrade_height}, host_height: {host_height} }}")]
+    UpgradeInPast {
+        upgrade_height: u64,
+        host_height: u64,
+    },
+    #[error("InvalidUpgrade {{ reason: {reason} }}")]
+    InvalidUpgrade { reason: String },
+    #[error(
+        "RevisionNotIncreasing {{ revision_number: {revision_number}, current_revision_number: {current_revision_number} }}"
+    )]
+    RevisionNotIncreasing {
+        revision_number: u64,
+        current_revision_number: u64,
+    },
+    #[error("failed to read the admin: {0:#}")]
+    State(#[from] anyhow::Error),
+}
+
+/// The admin recorded in `state`, if any.
+pub(super) fn admin_of(state: &IbcState<'_>) -> anyhow::Result<Option<Pubkey>> {
+    state
+        .get(&AdminPath)?
+        .map(|admin| {
+            Pubkey::from_str(&admin).map_err(|err| anyhow!("malformed admin {admin}: {err}"))
+        })
+        .transpose()
+}
+
+/// Rejects `signer` sending a message of type `type_url` that only the admin of
+/// `state` may send. The storage account records its payer as the admin when it
+/// is initialized, but endpoints initialized before that have no admin and reject
+/// these messages from every signer until an admin is claimed.
+pub(super) fn check_admin_of(
+    state: &IbcState<'_>,
+    type_url: &str,
+    signer: &Pubkey,
+) -> Result<(), AdminError> {
+    if !ADMIN_ONLY_TYPE_URLS.contains(&type_url) {
+        return Ok(());
+    }
+    match admin_of(state)? {
+        Some(admin) if admin == *signer => Ok(()),
+        Some(admin) => Err(AdminError::NotAdmin {
+            signer: *signer,
+            admin,
+        }),
+        None => Err(AdminError::NoAdmin { signer: *signer }),
+    }
+}
+
 #[derive(Debug)]
 pub(super) struct IbcHandler<'a> {
     state: IbcState<'a>,
@@ -65,22 +243,82 @@ pub(super) struct IbcHandler<'a> {
     current_time: TendermintTime,
     max_expected_time_per_block: Duration,
     module_by_id: BTreeMap<ModuleId, Box<dyn Module>>,
+    ics20_module: Ics20Module<'a>,
+    /// Chain params as of the start of the instruction. Changes to them take
+    /// effect from the next instruction.
+    chain_params: ChainParams,
+    /// Log lines of the IBC events emitted so far, in order
+    event_logs: Vec<String>,
+    /// Latest heights of clients whose state was replaced, until their
+    /// `update_client` event is emitted
+    previous_latest_heights: BTreeMap<ClientId, Height>,
+    /// Port of the channel the message being handled is for, and the module
+    /// that opened that channel, which is routed to instead of the module now
+    /// bound to the port
+    channel_owner_route: Option<(PortId, ModuleId)>,
+    /// What modules were loaded with, kept for modules reloaded once their
+    /// callback versions are known
+    instruction_accounts: Vec<AccountMeta>,
+    skipped_callbacks: Option<SkippedCallbacks>,
+    /// Checks of the pending changes, any of which can abort the commit
+    pre_commit_validators: Vec<Box<dyn PreCommitValidator>>,
 }
 
 impl<'a> IbcHandler<'a> {
+    /// `instruction_accounts` are the accounts passed to the IBC instruction after
+    /// the fixed ones. Module callbacks are given those that match the module's
+    /// account templates.
     pub(super) fn new(
         store: &'a IbcStore,
         metadata: &'a mut IbcMetadata,
         clock: &Clock,
+        instruction_accounts: &[AccountMeta],
+    ) -> anyhow::Result<Self> {
+        Self::with_skipped_callbacks(store, metadata, clock, instruction_accounts, None)
+    }
+
+    /// A handler that runs off-chain, such as for a dry run of a message. Module
+    /// programs cannot be invoked there, so their validation callbacks are
+    /// skipped and recorded in the returned list rather than run.
+    #[cfg(feature = "dry-run")]
+    pub(super) fn new_off_chain(
+        store: &'a IbcStore,
+        metadata: &'a mut IbcMetadata,
+        clock: &Clock,
+    ) -> anyhow::Result<(Self, SkippedCallbacks)> {
+        let skipped_callbacks = SkippedCallbacks::default();
+        let ibc_handler = Self::with_skipped_callbacks(
+            store,
+            metadata,
+            clock,
+            &[],
+            Some(skipped_callbacks.clone()),
+        )?;
+        Ok((ibc_handler, skipped_callbacks))
+    }
+
+    fn with_skipped_callbacks(
+        store: &'a IbcStore,
+        metadata: &'a mut IbcMetadata,
+        clock: &Clock,
+        instruction_accounts: &[AccountMeta],
+        skipped_callbacks: Option<SkippedCallbacks>,
     ) -> anyhow::Result<Self> {
-        let state = IbcState::new(store, clock.slot);
-        let all_module_ids = state.get(&AllModulesPath)?.unwrap_or_default();
+        let mut state = IbcState::new(store, clock.slot);
+        let chain_params = ChainParams::load_or_default(&state)?;
+        state.set_compress_values_above(chain_params.compress_values_above);
+        let all_module_ids = state.get_or_default(&AllModulesPath)?;
         let module_by_id = all_module_ids
             .modules
             .into_iter()
             .map(|module_id| {
-                let program_id = pubkey_of_module_id(&module_id)?;
-                Ok((module_id, SolanaModule { program_id }.into_box()))
+                let module = SolanaModule::load(
+                    &state,
+                    &module_id,
+                    instruction_accounts,
+                    skipped_callbacks.clone(),
+                )?;
+                Ok((module_id, module.into_box()))
             })
             .collect::<anyhow::Result<_>>()?;
 
@@ -92,1125 +330,6088 @@ impl<'a> IbcHandler<'a> {
             current_time: eclipse_chain::tendermint_time_from_clock(clock),
             max_expected_time_per_block: eclipse_chain::MAX_EXPECTED_SLOT_TIME,
             module_by_id,
+            ics20_module: Ics20Module::new(store, clock.slot),
+            pre_commit_validators: commit_guard::chain_params_validators(&chain_params),
+            chain_params,
+            event_logs: vec![],
+            previous_latest_heights: BTreeMap::new(),
+            channel_owner_route: None,
+            instruction_accounts: instruction_accounts.to_vec(),
+            skipped_callbacks,
         })
     }
 
+    /// The host consensus state at `slot`. Its timestamp is the Clock time of the
+    /// slot whose commit produced the state version in effect at `slot`, the same
+    /// block time relayers query for it (see `eclipse_chain::slot_time`). Versions
+    /// committed before slot times were recorded get an estimate counted back from
+    /// the current time instead.
     fn consensus_state(&self, slot: Slot) -> anyhow::Result<Option<Box<dyn ConsensusState>>> {
         let version = self
             .store
-            .read()?
+            .read()
             .find_version(slot)
             .ok_or_else(|| anyhow!("No IBC state versions found"))?;
 
-        match self.state.get_root_option(version)? {
+        match self.state.get_commitment_root_option(version)? {
             None => Ok(None),
             Some(commitment_root) => Ok(Some(Box::new(EclipseConsensusState {
                 commitment_root,
-                // TODO: Adjust the time based on the slot
-                timestamp: self.current_time,
+                timestamp: self.slot_time(slot, version)?,
             }))),
         }
     }
 
-    pub(super) fn commit(&mut self) -> anyhow::Result<()> {
-        self.state.commit()
+    /// The time of `slot`, whose state version is `version`: the current time for
+    /// the current slot, and otherwise the time recorded when `version` was
+    /// committed.
+    fn slot_time(&self, slot: Slot, version: Slot) -> anyhow::Result<TendermintTime> {
+        if slot >= self.current_slot {
+            return Ok(self.current_time);
+        }
+        let recorded_time = IbcState::new(self.store, version)
+            .get(&SlotTimePath)?
+            .and_then(Timestamp::into_tm_time);
+        Ok(recorded_time.unwrap_or_else(|| {
+            eclipse_chain::estimate_slot_time(self.current_time, self.current_slot, slot)
+        }))
     }
-}
 
-impl<'a> ExecutionContext for IbcHandler<'a> {
-    fn store_client_state(
-        &mut self,
-        client_state_path: ClientStatePath,
-        client_state: Box<dyn ClientState>,
-    ) -> Result<(), ContextError> {
+    /// Registers a check of the pending changes, run along with the built-in
+    /// ones before every commit.
+    #[cfg(test)]
+    pub(super) fn add_pre_commit_validator(&mut self, validator: Box<dyn PreCommitValidator>) {
+        self.pre_commit_validators.push(validator);
+    }
+
+    /// Commits the pending changes, unless a pre-commit validator finds a
+    /// violation in them, in which case nothing is written and the error is a
+    /// [`commit_guard::CommitRejected`] listing every violation found.
+    pub(super) fn commit(&mut self) -> anyhow::Result<()> {
+        self.state
+            .absorb_pending_changes(self.ics20_module.state_mut());
+        commit_guard::check(&self.pre_commit_validators, &self.state.pending_summary())?;
+        // Bookkeeping of the handler rather than a change of the message, so the
+        // validators never see it
         self.state
-            .set(&client_state_path, encode_client_state(client_state)?);
+            .set(&SlotTimePath, Timestamp::from(self.current_time));
+        let root_hash = self.state.commit()?;
+        self.metadata.record_commit(root_hash);
         Ok(())
     }
 
-    fn store_consensus_state(
-        &mut self,
-        consensus_state_path: ClientConsensusStatePath,
-        consensus_state: Box<dyn ConsensusState>,
-    ) -> Result<(), ContextError> {
-        let ClientConsensusStatePath {
-            client_id,
-            epoch: revision_number,
-            height: revision_height,
-        } = &consensus_state_path;
-        let height = Height::new(*revision_number, *revision_height)?;
+    /// Whether error details and the messages ibc-rs logs are logged, as of the
+    /// start of the instruction.
+    pub(super) fn verbose_logs(&self) -> bool {
+        self.chain_params.verbose_logs
+    }
 
-        let consensus_heights_path = ConsensusHeightsPath(client_id.clone());
-        self.state
-            .update(&consensus_heights_path, |consensus_heights| {
-                consensus_heights.heights.insert(height);
-            })
-            .map_err(|err| ClientError::Other {
-                description: err.to_string(),
-            })?;
+    /// Most IBC events the event journal keeps, as of the start of the
+    /// instruction.
+    pub(super) fn event_journal_capacity(&self) -> usize {
+        usize::try_from(self.chain_params.event_journal_capacity()).unwrap_or(usize::MAX)
+    }
 
-        self.state.set(
-            &consensus_state_path,
-            encode_consensus_state(consensus_state)?,
-        );
-        Ok(())
+    /// Takes the log lines of the IBC events emitted so far.
+    pub(super) fn take_event_logs(&mut self) -> Vec<String> {
+        mem::take(&mut self.event_logs)
     }
 
-    fn increase_client_counter(&mut self) {
-        self.metadata.client_id_counter += 1;
+    pub(super) fn admin(&self) -> anyhow::Result<Option<Pubkey>> {
+        admin_of(&self.state)
     }
 
-    fn store_update_time(
+    /// Applies a store write batch chunk sent by `signer`, which must be the admin.
+    /// Storage accounts initialized before the admin was recorded have none, and
+    /// reject every chunk until one is claimed.
+    pub(super) fn apply_store_write_batch(
         &mut self,
-        client_id: ClientId,
-        height: Height,
-        timestamp: Timestamp,
-    ) -> Result<(), ContextError> {
-        let client_update_time_path = ClientUpdateTimePath(client_id, height);
-        self.state.set(&client_update_time_path, timestamp);
-        Ok(())
+        signer: &Pubkey,
+        chunk: MsgStoreWriteBatch,
+    ) -> Result<(), StoreWriteBatchError> {
+        let admin = self.admin().map_err(StoreWriteBatchError::ReadAdmin)?;
+        if admin != Some(*signer) {
+            return Err(StoreWriteBatchError::NotAdmin { signer: *signer });
+        }
+        store_write_batch::apply_chunk(&mut self.state, chunk)
     }
 
-    fn store_update_height(
+    /// Drops the store write batch in progress, returning its cursor. The admin
+    /// is checked up front.
+    pub(super) fn abort_store_write_batch(
         &mut self,
-        client_id: ClientId,
-        height: Height,
-        host_height: Height,
-    ) -> Result<(), ContextError> {
-        let client_update_height_path = ClientUpdateHeightPath(client_id, height);
-        self.state.set(&client_update_height_path, host_height);
-        Ok(())
+    ) -> Result<StoreWriteBatchCursor, StoreWriteBatchError> {
+        store_write_batch::abort_batch(&mut self.state)
     }
 
-    fn store_connection(
+    pub(super) fn repair_indices(
         &mut self,
-        connection_path: &ConnectionPath,
-        connection_end: ConnectionEnd,
-    ) -> Result<(), ContextError> {
-        self.state.set(connection_path, connection_end);
-        Ok(())
+        msg: MsgRepairIndices,
+    ) -> Result<IndexRepair, StoreWriteBatchError> {
+        store_write_batch::repair_index(&mut self.state, msg)
     }
 
-    fn store_connection_to_client(
+    /// Returns the disabled message prefix that `type_url` falls under, if any.
+    /// Chain param updates and freezing are never disabled so that the list can
+    /// always be cleared and IBC can always be unfrozen.
+    pub(super) fn disabled_message_prefix(&self, type_url: &str) -> Option<&str> {
+        if type_url == MsgUpdateChainParams::TYPE_URL || type_url == MsgSetIbcFrozen::TYPE_URL {
+            return None;
+        }
+        self.chain_params.disabled_prefix(type_url)
+    }
+
+    /// Drops the state versions from before the last `retain_slots` slots, which
+    /// bounds the size of the storage account but leaves host consensus states of
+    /// those slots unavailable. Consensus states of the clients on this chain, as
+    /// indexed by `ConsensusHeightsPath`, are values of the latest version and so
+    /// are not dropped; they are only evicted beyond the per-client cap as new ones
+    /// are stored. At most `max_nodes` of the tree nodes that only the dropped
+    /// versions use are dropped, and the rest by later calls.
+    pub(super) fn prune_state(
         &mut self,
-        client_connection_path: &ClientConnectionPath,
-        connection_id: ConnectionId,
-    ) -> Result<(), ContextError> {
-        self.state
-            .update(client_connection_path, |client_connections| {
-                client_connections.connections.insert(connection_id);
-            })
-            .map_err(|err| ConnectionError::Other {
-                description: err.to_string(),
-            })?;
-        Ok(())
+        retain_slots: Slot,
+        max_nodes: usize,
+    ) -> anyhow::Result<StorePruning> {
+        self.store
+            .prune_versions_older_than(self.current_slot.saturating_sub(retain_slots), max_nodes)
     }
 
-    fn increase_connection_counter(&mut self) {
-        self.metadata.connection_id_counter += 1;
+    /// Drops the update times and heights recorded for client heights whose
+    /// connection delay windows have passed, as indexed by `ProcessedHeightsPath`,
+    /// except for the latest [`RETAINED_PROCESSED_HEIGHTS`] of each client. A
+    /// window is the longest delay period of the client's connections, both in
+    /// time and in blocks of `max_expected_time_per_block`. Proofs at the dropped
+    /// heights can no longer be verified. Returns the number of heights dropped.
+    pub(super) fn prune_client_updates(&mut self) -> anyhow::Result<usize> {
+        const PREFIX: &str = "internal/clients/";
+        const SUFFIX: &str = "/processedHeights";
+
+        let mut pruned_count = 0;
+        for path in self.state.paths_with_prefix(PREFIX)? {
+            let client_id = match path
+                .strip_prefix(PREFIX)
+                .and_then(|path| path.strip_suffix(SUFFIX))
+            {
+                None => continue,
+                Some(client_id) => client_id.parse::<ClientId>()?,
+            };
+            pruned_count += self.prune_processed_heights(&client_id)?;
+        }
+        Ok(pruned_count)
     }
 
-    fn store_packet_commitment(
-        &mut self,
-        commitment_path: &CommitmentPath,
-        commitment: PacketCommitment,
-    ) -> Result<(), ContextError> {
-        self.state.set(commitment_path, commitment);
-        Ok(())
+    fn prune_processed_heights(&mut self, client_id: &ClientId) -> anyhow::Result<usize> {
+        let processed_heights_path = ProcessedHeightsPath(client_id.clone());
+        let mut processed_heights = match self.state.get(&processed_heights_path)? {
+            None => return Ok(0),
+            Some(processed_heights) => processed_heights,
+        };
+
+        let delay_period = self.longest_delay_period(client_id)?;
+        let prunable_count = processed_heights
+            .heights
+            .len()
+            .saturating_sub(RETAINED_PROCESSED_HEIGHTS);
+        let mut stale_heights = vec![];
+        for height in processed_heights.heights.iter().take(prunable_count) {
+            if self.delay_window_passed(client_id, *height, delay_period)? {
+                stale_heights.push(*height);
+            }
+        }
+
+        for height in &stale_heights {
+            self.state
+                .remove(&ClientUpdateTimePath(client_id.clone(), *height));
+            self.state
+                .remove(&ClientUpdateHeightPath(client_id.clone(), *height));
+            processed_heights.heights.remove(height);
+        }
+        if !stale_heights.is_empty() {
+            self.state.set(&processed_heights_path, processed_heights);
+        }
+        Ok(stale_heights.len())
     }
 
-    fn delete_packet_commitment(
-        &mut self,
-        commitment_path: &CommitmentPath,
-    ) -> Result<(), ContextError> {
-        self.state.remove(commitment_path);
-        Ok(())
+    /// Whether the delay window of `delay_period` that started when the client was
+    /// updated to `height` has passed, both in time and in blocks of
+    /// `max_expected_time_per_block`. Windows of updates that were not recorded
+    /// have always passed.
+    fn delay_window_passed(
+        &self,
+        client_id: &ClientId,
+        height: Height,
+        delay_period: Duration,
+    ) -> anyhow::Result<bool> {
+        let delay_nanos = u64::try_from(delay_period.as_nanos()).unwrap_or(u64::MAX);
+        let block_time_nanos = self.max_expected_time_per_block.as_nanos();
+        let block_delay = if block_time_nanos == 0 {
+            0
+        } else {
+            u64::try_from((delay_period.as_nanos() + block_time_nanos - 1) / block_time_nanos)
+                .unwrap_or(u64::MAX)
+        };
+        let host_nanos = Timestamp::from(self.current_time).nanoseconds();
+        let host_height = self.host_height().map_err(|err| anyhow!(err))?;
+
+        let update_time = self
+            .state
+            .get(&ClientUpdateTimePath(client_id.clone(), height))?;
+        let update_height = self
+            .state
+            .get(&ClientUpdateHeightPath(client_id.clone(), height))?;
+        let time_passed = update_time.map_or(true, |update_time| {
+            update_time.nanoseconds().saturating_add(delay_nanos) <= host_nanos
+        });
+        // Slots keep counting across revision bumps, so blocks are counted by
+        // revision height, whatever revision the update was recorded in
+        let height_passed = update_height.map_or(true, |update_height| {
+            update_height.revision_height().saturating_add(block_delay)
+                <= host_height.revision_height()
+        });
+        Ok(time_passed && height_passed)
     }
 
-    fn store_packet_receipt(
+    /// Evicts the oldest consensus states of the client beyond the
+    /// `max_consensus_states_per_client` chain param from `consensus_heights`, the
+    /// client's heights, along with the update time and height recorded for them.
+    /// The latest height, `stored_height` and heights whose connection delay
+    /// windows have not passed are never evicted, so the client may keep more
+    /// states than the cap.
+    fn evict_consensus_states(
         &mut self,
-        receipt_path: &ReceiptPath,
-        receipt: Receipt,
-    ) -> Result<(), ContextError> {
-        self.state.set(receipt_path, receipt);
+        client_id: &ClientId,
+        consensus_heights: &mut ConsensusHeights,
+        stored_height: Height,
+    ) -> anyhow::Result<()> {
+        let max_consensus_states =
+            usize::try_from(self.chain_params.max_consensus_states_per_client())
+                .unwrap_or(usize::MAX);
+        let excess_count = consensus_heights
+            .heights
+            .len()
+            .saturating_sub(max_consensus_states);
+        let latest_height = match consensus_heights.heights.iter().next_back() {
+            Some(latest_height) if excess_count > 0 => *latest_height,
+            _ => return Ok(()),
+        };
+
+        let delay_period = self.longest_delay_period(client_id)?;
+        let mut evicted_heights = vec![];
+        for height in &consensus_heights.heights {
+            if evicted_heights.len() == excess_count || *height == latest_height {
+                break;
+            }
+            if *height != stored_height
+                && self.delay_window_passed(client_id, *height, delay_period)?
+            {
+                evicted_heights.push(*height);
+            }
+        }
+
+        let processed_heights_path = ProcessedHeightsPath(client_id.clone());
+        let mut processed_heights = self.state.get(&processed_heights_path)?;
+        for height in &evicted_heights {
+            consensus_heights.heights.remove(height);
+            self.state
+                .remove(&ClientConsensusStatePath::new(client_id, height));
+            self.state
+                .remove(&ClientUpdateTimePath(client_id.clone(), *height));
+            self.state
+                .remove(&ClientUpdateHeightPath(client_id.clone(), *height));
+            if let Some(processed_heights) = &mut processed_heights {
+                processed_heights.heights.remove(height);
+            }
+        }
+        if let Some(processed_heights) = processed_heights {
+            if !evicted_heights.is_empty() {
+                self.state.set(&processed_heights_path, processed_heights);
+            }
+        }
         Ok(())
     }
 
-    fn store_packet_acknowledgement(
-        &mut self,
-        ack_path: &AckPath,
-        ack_commitment: AcknowledgementCommitment,
-    ) -> Result<(), ContextError> {
-        self.state.set(ack_path, ack_commitment);
-        Ok(())
+    /// Longest delay period of the connections on the client, or zero if it has
+    /// none.
+    fn longest_delay_period(&self, client_id: &ClientId) -> anyhow::Result<Duration> {
+        let client_connections = self
+            .state
+            .get_or_default(&ClientConnectionPath::new(client_id))?;
+        let mut longest_delay_period = Duration::ZERO;
+        for connection_id in &client_connections.connections {
+            if let Some(connection_end) = self.state.get(&ConnectionPath::new(connection_id))? {
+                longest_delay_period = longest_delay_period.max(connection_end.delay_period());
+            }
+        }
+        Ok(longest_delay_period)
     }
 
-    fn delete_packet_acknowledgement(&mut self, ack_path: &AckPath) -> Result<(), ContextError> {
-        self.state.remove(ack_path);
-        Ok(())
+    /// Rewrites the advertised capabilities with those of the running program,
+    /// returning the ones they replace.
+    pub(super) fn refresh_capabilities(&mut self) -> anyhow::Result<Option<Capabilities>> {
+        let previous = self.state.get(&CapabilitiesPath)?;
+        self.state
+            .set(&CapabilitiesPath, capabilities::capabilities());
+        Ok(previous)
     }
 
-    fn store_channel(
-        &mut self,
-        channel_end_path: &ChannelEndPath,
-        channel_end: ChannelEnd,
-    ) -> Result<(), ContextError> {
-        self.state.set(channel_end_path, channel_end);
-        Ok(())
+    pub(super) fn update_chain_params(&mut self, params: ChainParams) {
+        self.state.set(&ChainParamsPath, params);
     }
 
-    fn store_next_sequence_send(
-        &mut self,
-        seq_send_path: &SeqSendPath,
-        seq: Sequence,
-    ) -> Result<(), ContextError> {
-        self.state.set(seq_send_path, seq);
+    pub(super) fn is_frozen(&self) -> bool {
+        self.chain_params.frozen
+    }
+
+    /// Whether a message of type `type_url` is rejected because a store write
+    /// batch, such as a genesis import, spans several transactions and has not
+    /// been applied in full. Other messages would observe half-written state, so
+    /// only admin messages are accepted until the final chunk clears the batch
+    /// cursor, or the admin aborts the batch.
+    pub(super) fn rejects_during_migration(&self, type_url: &str) -> anyhow::Result<bool> {
+        if type_url.starts_with(ADMIN_TYPE_URL_PREFIX) {
+            return Ok(false);
+        }
+        self.state.contains(&StoreWriteBatchCursorPath)
+    }
+
+    /// Whether a message of type `type_url` is rejected because IBC is frozen.
+    /// Chain param updates and freezing itself are always accepted so that IBC
+    /// can be unfrozen, and so are admin changes so that a compromised admin can
+    /// be replaced while IBC is paused, and claiming an admin so that an endpoint
+    /// without one can be unfrozen at all.
+    pub(super) fn rejects_while_frozen(&self, type_url: &str) -> bool {
+        if [
+            MsgUpdateChainParams::TYPE_URL,
+            MsgSetIbcFrozen::TYPE_URL,
+            MsgProposeAdminChange::TYPE_URL,
+            MsgAcceptAdminChange::TYPE_URL,
+            MsgCancelAdminChange::TYPE_URL,
+            MsgClaimAdmin::TYPE_URL,
+        ]
+        .contains(&type_url)
+        {
+            return false;
+        }
+        self.is_frozen()
+    }
+
+    pub(super) fn set_frozen(&mut self, frozen: bool) {
+        self.update_chain_params(ChainParams {
+            frozen,
+            ..self.chain_params.clone()
+        });
+    }
+
+    /// Rejects `signer` sending a message of type `type_url` that only the admin
+    /// may send, as [`check_admin_of`] does.
+    pub(super) fn check_admin(&self, type_url: &str, signer: &Pubkey) -> Result<(), AdminError> {
+        check_admin_of(&self.state, type_url, signer)
+    }
+
+    /// Makes `signer` the admin of an endpoint that has none, such as one
+    /// initialized before the admin was recorded. Whether `signer` is allowed to
+    /// claim it is checked up front.
+    pub(super) fn claim_admin(&mut self, signer: &Pubkey) -> Result<(), AdminError> {
+        if let Some(admin) = self.admin()? {
+            return Err(AdminError::AlreadySet { admin });
+        }
+        self.state.set(&AdminPath, signer.to_string());
         Ok(())
     }
 
-    fn store_next_sequence_recv(
+    /// Records `new_admin` as the admin to hand over to once the slot is past
+    /// `effective_after_slot`, replacing any pending change. Only the admin may
+    /// propose, so endpoints without one reject every proposal.
+    pub(super) fn propose_admin_change(
         &mut self,
-        seq_recv_path: &SeqRecvPath,
-        seq: Sequence,
-    ) -> Result<(), ContextError> {
-        self.state.set(seq_recv_path, seq);
+        proposer: &Pubkey,
+        new_admin: &Pubkey,
+        effective_after_slot: Slot,
+    ) -> Result<(), AdminError> {
+        self.check_admin(MsgProposeAdminChange::TYPE_URL, proposer)?;
+        if effective_after_slot < self.current_slot {
+            return Err(AdminError::ChangeInPast {
+                effective_after_slot,
+                current_slot: self.current_slot,
+            });
+        }
+        self.state.set(
+            &PendingAdminChangePath,
+            PendingAdminChange {
+                new_admin: new_admin.to_string(),
+                effective_after_slot,
+            },
+        );
         Ok(())
     }
 
-    fn store_next_sequence_ack(
+    /// Makes `signer` the admin if it is the new admin of the pending change and
+    /// the change is in effect. Returns the previous admin, if any.
+    pub(super) fn accept_admin_change(
         &mut self,
-        seq_ack_path: &SeqAckPath,
-        seq: Sequence,
-    ) -> Result<(), ContextError> {
-        self.state.set(seq_ack_path, seq);
-        Ok(())
+        signer: &Pubkey,
+    ) -> Result<Option<Pubkey>, AdminError> {
+        let PendingAdminChange {
+            new_admin,
+            effective_after_slot,
+        } = self
+            .state
+            .get(&PendingAdminChangePath)?
+            .ok_or(AdminError::NoPendingChange)?;
+        if new_admin != signer.to_string() {
+            return Err(AdminError::NotProposedAdmin {
+                signer: *signer,
+                new_admin,
+            });
+        }
+        if self.current_slot <= effective_after_slot {
+            return Err(AdminError::NotYetEffective {
+                effective_after_slot,
+                current_slot: self.current_slot,
+            });
+        }
+
+        let previous_admin = self.admin()?;
+        self.state.set(&AdminPath, new_admin);
+        self.state.remove(&PendingAdminChangePath);
+        Ok(previous_admin)
     }
 
-    fn increase_channel_counter(&mut self) {
-        self.metadata.channel_id_counter += 1;
+    /// Drops the pending admin change, returning it. The canceller is checked
+    /// against the admin up front.
+    pub(super) fn cancel_admin_change(&mut self) -> Result<PendingAdminChange, AdminError> {
+        let pending = self
+            .state
+            .get(&PendingAdminChangePath)?
+            .ok_or(AdminError::NoPendingChange)?;
+        self.state.remove(&PendingAdminChangePath);
+        Ok(pending)
     }
 
-    // TODO: Figure out where to emit IBC events
-    fn emit_ibc_event(&mut self, event: IbcEvent) {
-        msg!("{:?}", event);
+    /// Moves the host height to `revision_number` from the next instruction on,
+    /// returning the previous revision. Counterparty clients follow with headers
+    /// of the new revision, and proofs at heights of earlier revisions stay valid.
+    pub(super) fn set_revision_number(&mut self, revision_number: u64) -> Result<u64, AdminError> {
+        let current_revision_number = self.metadata.revision_number;
+        if revision_number <= current_revision_number {
+            return Err(AdminError::RevisionNotIncreasing {
+                revision_number,
+                current_revision_number,
+            });
+        }
+        self.metadata.revision_number = revision_number;
+        Ok(current_revision_number)
     }
 
-    // TODO: Figure out where to log IBC messages
-    fn log_message(&mut self, message: String) {
-        msg!(&message);
+    /// Stores the upgraded client and consensus state of this chain at the upgrade
+    /// paths of `upgrade_height`. Counterparty clients of this chain look the
+    /// upgrade up at their latest height, so it must be written no later than the
+    /// slot of that height to be in the state version they prove against.
+    pub(super) fn write_upgrade(
+        &mut self,
+        MsgWriteUpgrade {
+            upgrade_height,
+            upgraded_client_state,
+            upgraded_consensus_state,
+        }: MsgWriteUpgrade,
+    ) -> Result<(), AdminError> {
+        let host_height = self
+            .host_height()
+            .map_err(|err| anyhow!(err))?
+            .revision_height();
+        if upgrade_height < host_height {
+            return Err(AdminError::UpgradeInPast {
+                upgrade_height,
+                host_height,
+            });
+        }
+
+        let invalid_upgrade = |reason: String| AdminError::InvalidUpgrade { reason };
+        let client_state = EclipseClientState::try_from(upgraded_client_state.clone())
+            .map_err(|err| invalid_upgrade(err.to_string()))?;
+        EclipseConsensusState::try_from(upgraded_consensus_state.clone())
+            .map_err(|err| invalid_upgrade(err.to_string()))?;
+        let upgraded_height = client_state.latest_height().revision_height();
+        if upgraded_height <= upgrade_height {
+            return Err(invalid_upgrade(format!(
+                "upgraded client height {upgraded_height} is not past the upgrade height"
+            )));
+        }
+
+        self.state.set_any(
+            &UpgradeClientPath::UpgradedClientState(upgrade_height),
+            upgraded_client_state,
+        )?;
+        self.state.set_any(
+            &UpgradeClientPath::UpgradedClientConsensusState(upgrade_height),
+            upgraded_consensus_state,
+        )?;
+        Ok(())
     }
-}
 
-impl<'a> ValidationContext for IbcHandler<'a> {
-    fn client_state(&self, client_id: &ClientId) -> Result<Box<dyn ClientState>, ContextError> {
-        let client_state_path = ClientStatePath::new(client_id);
-        self.decode_client_state(
+    /// Type URL of the client state stored for `client_id`, read from the client
+    /// type index without decoding the client state. Clients stored before the
+    /// index existed get their index entry written here.
+    pub(super) fn client_type_of(
+        &mut self,
+        client_id: &ClientId,
+    ) -> anyhow::Result<Option<String>> {
+        let client_type_url_path = ClientTypeUrlPath(client_id.clone());
+        if let Some(type_url) = self.state.get(&client_type_url_path)? {
+            return Ok(Some(type_url));
+        }
+
+        let client_state = self.state.get_any(&ClientStatePath::new(client_id))?;
+        Ok(client_state.map(|client_state| {
             self.state
-                .get(&client_state_path)
-                .map_err(|err| ClientError::Other {
-                    description: err.to_string(),
-                })?
-                .ok_or_else(|| ClientError::ClientStateNotFound {
-                    client_id: client_id.clone(),
-                })?,
-        )
-    }
-
-    fn decode_client_state(
-        &self,
-        client_state: protobuf::Any,
-    ) -> Result<Box<dyn ClientState>, ContextError> {
-        decode_client_state(client_state)
-    }
-
-    fn consensus_state(
-        &self,
-        client_consensus_path: &ClientConsensusStatePath,
-    ) -> Result<Box<dyn ConsensusState>, ContextError> {
-        let ClientConsensusStatePath {
-            client_id,
-            epoch: revision_number,
-            height: revision_height,
-        } = client_consensus_path;
-        let height = Height::new(*revision_number, *revision_height)?;
-
-        decode_consensus_state(
-            self.state
-                .get(client_consensus_path)
-                .map_err(|err| ClientError::Other {
-                    description: err.to_string(),
-                })?
-                .ok_or_else(|| ClientError::ConsensusStateNotFound {
-                    client_id: client_id.clone(),
-                    height,
-                })?,
-        )
+                .set(&client_type_url_path, client_state.type_url.clone());
+            client_state.type_url
+        }))
     }
 
-    fn next_consensus_state(
-        &self,
-        client_id: &ClientId,
-        height: &Height,
-    ) -> Result<Option<Box<dyn ConsensusState>>, ContextError> {
-        let consensus_heights_path = ConsensusHeightsPath(client_id.clone());
-
-        let consensus_heights =
-            self.state
-                .get(&consensus_heights_path)
-                .map_err(|err| ClientError::Other {
-                    description: err.to_string(),
-                })?;
-
-        let consensus_heights = match consensus_heights {
+    /// Describes why `envelope` cannot apply to its client, if it updates a client
+    /// with a header meant for another client type. This is checked before
+    /// dispatch so that the client state does not have to be decoded to reject it.
+    pub(super) fn client_type_mismatch(
+        &mut self,
+        envelope: &MsgEnvelope,
+    ) -> anyhow::Result<Option<String>> {
+        let msg = match envelope {
+            MsgEnvelope::Client(ClientMsg::UpdateClient(msg)) => msg,
+            _ => return Ok(None),
+        };
+        let RawMsgUpdateClient {
+            client_id, header, ..
+        } = msg.clone().into();
+        let header_type_url = match header {
+            Some(header) => header.type_url,
             None => return Ok(None),
-            Some(consensus_heights) => consensus_heights,
         };
 
-        let next_consensus_height = match consensus_heights
-            .heights
-            .range((Excluded(*height), Unbounded))
-            .next()
-        {
+        let client_type_url = match self.client_type_of(&client_id.parse()?)? {
+            Some(client_type_url) => client_type_url,
             None => return Ok(None),
-            Some(next_consensus_height) => *next_consensus_height,
         };
+        if header_batch_type_url_of_client(&client_type_url) == Some(&*header_type_url) {
+            return Ok(None);
+        }
+        Ok(match header_type_url_of_client(&client_type_url) {
+            Some(expected) if expected != header_type_url => Some(format!(
+                "client {client_id} has type {client_type_url}, which is updated with \
+                 {expected} headers, not {header_type_url}"
+            )),
+            _ => None,
+        })
+    }
 
-        let client_consensus_path = ClientConsensusStatePath {
-            client_id: client_id.clone(),
-            epoch: next_consensus_height.revision_number(),
-            height: next_consensus_height.revision_height(),
-        };
+    /// Rewrites the legacy Eclipse type URLs of the states and headers a client
+    /// message carries to the fixed ones (see
+    /// `eclipse_ibc_light_client::LEGACY_TYPE_URLS`), which messages generated by
+    /// older CLIs still have. Each rewrite is counted under `LegacyTypeUrlCountPath`
+    /// and reported in a `legacy_type_url` event. Returns why the message is
+    /// rejected instead if the `reject_legacy_type_urls` chain param is set.
+    ///
+    /// Connection handshake messages are left as they are: the client state they
+    /// carry is proven as the bytes the counterparty stored, so
+    /// `validate_self_client` accepts legacy type URLs there instead.
+    pub(super) fn canonicalize_type_urls(
+        &mut self,
+        envelope: &mut MsgEnvelope,
+    ) -> anyhow::Result<Option<String>> {
+        let mut rewritten = vec![];
+        match envelope {
+            MsgEnvelope::Client(ClientMsg::CreateClient(msg)) => {
+                let mut raw: RawMsgCreateClient = msg.clone().into();
+                canonicalize_any(&mut raw.client_state, &mut rewritten);
+                canonicalize_any(&mut raw.consensus_state, &mut rewritten);
+                if !rewritten.is_empty() {
+                    *msg = raw.try_into()?;
+                }
+            }
+            MsgEnvelope::Client(ClientMsg::UpdateClient(msg)) => {
+                let mut raw: RawMsgUpdateClient = msg.clone().into();
+                canonicalize_any(&mut raw.header, &mut rewritten);
+                if !rewritten.is_empty() {
+                    *msg = raw.try_into()?;
+                }
+            }
+            MsgEnvelope::Client(ClientMsg::Misbehaviour(msg)) => {
+                let mut raw: RawMsgSubmitMisbehaviour = msg.clone().into();
+                canonicalize_any(&mut raw.misbehaviour, &mut rewritten);
+                if !rewritten.is_empty() {
+                    *msg = raw.try_into()?;
+                }
+            }
+            MsgEnvelope::Client(ClientMsg::UpgradeClient(msg)) => {
+                let mut raw: RawMsgUpgradeClient = msg.clone().into();
+                canonicalize_any(&mut raw.client_state, &mut rewritten);
+                canonicalize_any(&mut raw.consensus_state, &mut rewritten);
+                if !rewritten.is_empty() {
+                    *msg = raw.try_into()?;
+                }
+            }
+            _ => {}
+        }
+        if rewritten.is_empty() {
+            return Ok(None);
+        }
+        if self.chain_params.reject_legacy_type_urls {
+            let (legacy, canonical) = &rewritten[0];
+            return Ok(Some(format!(
+                "type URL {legacy} is no longer accepted; messages must use {canonical}"
+            )));
+        }
 
-        Ok(Some(decode_consensus_state(
-            self.state
-                .get(&client_consensus_path)
-                .map_err(|err| ClientError::Other {
-                    description: err.to_string(),
-                })?
-                .ok_or_else(|| ClientError::ConsensusStateNotFound {
-                    client_id: client_id.clone(),
-                    height: next_consensus_height,
-                })?,
-        )?))
+        let count = self.state.get_or_default(&LegacyTypeUrlCountPath)?;
+        self.state.set(
+            &LegacyTypeUrlCountPath,
+            count.saturating_add(rewritten.len() as u64),
+        );
+        for (legacy, canonical) in rewritten {
+            self.emit_ibc_event(IbcEvent::Module(ModuleEvent {
+                kind: LEGACY_TYPE_URL_EVENT.to_owned(),
+                attributes: vec![
+                    ModuleEventAttribute {
+                        key: "type_url".to_owned(),
+                        value: legacy,
+                    },
+                    ModuleEventAttribute {
+                        key: "canonical_type_url".to_owned(),
+                        value: canonical.to_owned(),
+                    },
+                ],
+            }));
+        }
+        Ok(None)
     }
 
-    fn prev_consensus_state(
+    /// Describes why a connection or channel handshake step cannot be meant for
+    /// this chain, if it names a local connection or channel that is not at the
+    /// step it completes, or carries a proof height the connection's client cannot
+    /// have a consensus state for. Identifiers such as `connection-0` exist on every
+    /// chain, so a message submitted to the wrong chain would otherwise only fail
+    /// proof verification, or apply to an unrelated handshake.
+    pub(super) fn handshake_chain_mismatch(
         &self,
-        client_id: &ClientId,
-        height: &Height,
-    ) -> Result<Option<Box<dyn ConsensusState>>, ContextError> {
-        let consensus_heights_path = ConsensusHeightsPath(client_id.clone());
+        envelope: &MsgEnvelope,
+    ) -> anyhow::Result<Option<String>> {
+        let (msg_name, target, proof_height) = match envelope {
+            MsgEnvelope::Connection(ConnectionMsg::OpenAck(msg)) => {
+                let RawMsgConnectionOpenAck {
+                    connection_id,
+                    proof_height,
+                    ..
+                } = msg.clone().into();
+                (
+                    "MsgConnectionOpenAck",
+                    HandshakeTarget::Connection(connection_id, RawConnectionState::Init),
+                    proof_height,
+                )
+            }
+            MsgEnvelope::Connection(ConnectionMsg::OpenConfirm(msg)) => {
+                let RawMsgConnectionOpenConfirm {
+                    connection_id,
+                    proof_height,
+                    ..
+                } = msg.clone().into();
+                (
+                    "MsgConnectionOpenConfirm",
+                    HandshakeTarget::Connection(connection_id, RawConnectionState::Tryopen),
+                    proof_height,
+                )
+            }
+            MsgEnvelope::Channel(ChannelMsg::OpenAck(msg)) => {
+                let RawMsgChannelOpenAck {
+                    port_id,
+                    channel_id,
+                    proof_height,
+                    ..
+                } = msg.clone().into();
+                (
+                    "MsgChannelOpenAck",
+                    HandshakeTarget::Channel(port_id, channel_id, RawChannelState::Init),
+                    proof_height,
+                )
+            }
+            MsgEnvelope::Channel(ChannelMsg::OpenConfirm(msg)) => {
+                let RawMsgChannelOpenConfirm {
+                    port_id,
+                    channel_id,
+                    proof_height,
+                    ..
+                } = msg.clone().into();
+                (
+                    "MsgChannelOpenConfirm",
+                    HandshakeTarget::Channel(port_id, channel_id, RawChannelState::Tryopen),
+                    proof_height,
+                )
+            }
+            _ => return Ok(None),
+        };
+        let proof_height = match proof_height {
+            Some(proof_height) => Height::try_from(proof_height)?,
+            None => return Ok(None),
+        };
 
-        let consensus_heights =
-            self.state
-                .get(&consensus_heights_path)
-                .map_err(|err| ClientError::Other {
-                    description: err.to_string(),
-                })?;
+        // Missing identifiers are left for the handler to report as not found.
+        let (connection_id, expected_connection_state) = match target {
+            HandshakeTarget::Connection(connection_id, expected_state) => {
+                (connection_id, expected_state)
+            }
+            HandshakeTarget::Channel(port_id, channel_id, expected_state) => {
+                let channel_end_path = ChannelEndPath::new(&port_id.parse()?, &channel_id.parse()?);
+                let channel = match self.state.get_raw(&channel_end_path)? {
+                    Some(channel) => channel,
+                    None => return Ok(None),
+                };
+                if channel.state != expected_state as i32 {
+                    return Ok(Some(format!(
+                        "channel {channel_id} on port {port_id} is {} here, not {} as \
+                         {msg_name} expects; the message may be meant for another chain or \
+                         already applied",
+                        raw_channel_state_name(channel.state),
+                        expected_state.as_str_name(),
+                    )));
+                }
+                match channel.connection_hops.into_iter().next() {
+                    Some(connection_id) => (connection_id, RawConnectionState::Open),
+                    None => return Ok(None),
+                }
+            }
+        };
 
-        let consensus_heights = match consensus_heights {
+        let connection = match self
+            .state
+            .get_raw(&ConnectionPath::new(&connection_id.parse()?))?
+        {
+            Some(connection) => connection,
             None => return Ok(None),
-            Some(consensus_heights) => consensus_heights,
         };
+        if connection.state != expected_connection_state as i32 {
+            return Ok(Some(format!(
+                "connection {connection_id} is {} here, not {} as {msg_name} expects; the \
+                 message may be meant for another chain or already applied",
+                raw_connection_state_name(connection.state),
+                expected_connection_state.as_str_name(),
+            )));
+        }
 
-        let next_consensus_height = match consensus_heights.heights.range(..*height).next_back() {
+        let client_id = connection.client_id.parse::<ClientId>()?;
+        let client_state = match self.state.get_any(&ClientStatePath::new(&client_id))? {
+            Some(client_state) => decode_client_state(client_state)?,
             None => return Ok(None),
-            Some(next_consensus_height) => *next_consensus_height,
         };
-
-        let client_consensus_path = ClientConsensusStatePath {
-            client_id: client_id.clone(),
-            epoch: next_consensus_height.revision_number(),
-            height: next_consensus_height.revision_height(),
+        let tracked_chain_id = match tracked_chain_id(&*client_state) {
+            Some(tracked_chain_id) => tracked_chain_id,
+            None => return Ok(None),
         };
+        if proof_height.revision_number() != tracked_chain_id.version() {
+            return Ok(Some(format!(
+                "{msg_name} proves state at {proof_height}, but connection {connection_id} is \
+                 bound by client {client_id} to {tracked_chain_id}, whose heights are in \
+                 revision {}; the message may have been generated for a different chain",
+                tracked_chain_id.version(),
+            )));
+        }
+        let consensus_state_path = ClientConsensusStatePath::new(&client_id, &proof_height);
+        if !self.state.contains(&consensus_state_path)? {
+            return Ok(Some(format!(
+                "{msg_name} proves state at {proof_height}, but client {client_id} of connection \
+                 {connection_id}, which tracks {tracked_chain_id}, has no consensus state at that \
+                 height; the message may have been generated for a different chain, or the \
+                 client needs an update first",
+            )));
+        }
+        Ok(None)
+    }
 
-        Ok(Some(decode_consensus_state(
-            self.state
-                .get(&client_consensus_path)
-                .map_err(|err| ClientError::Other {
-                    description: err.to_string(),
-                })?
-                .ok_or_else(|| ClientError::ConsensusStateNotFound {
-                    client_id: client_id.clone(),
-                    height: next_consensus_height,
-                })?,
-        )?))
+    /// Latest height of the client state stored for `client_id`, if there is one
+    /// that can be decoded.
+    fn stored_latest_height(&self, client_id: &ClientId) -> Option<Height> {
+        let client_state = self
+            .state
+            .get_any(&ClientStatePath::new(client_id))
+            .ok()??;
+        decode_client_state(client_state)
+            .ok()
+            .map(|client_state| client_state.latest_height())
     }
 
-    fn host_height(&self) -> Result<Height, ContextError> {
-        Ok(eclipse_chain::height_of_slot(self.current_slot)?)
+    /// Number of packets sent on the channel whose commitments have not been
+    /// deleted. Channels that sent packets before they were counted have no count
+    /// yet, so it is counted from the channel's packet commitments the first time
+    /// it is needed.
+    fn inflight_packets(
+        &self,
+        inflight_packets_path: &InflightPacketsPath,
+    ) -> Result<u64, ChannelError> {
+        let read_err = |err: anyhow::Error| ChannelError::Other {
+            description: read_err_description(inflight_packets_path, &err),
+        };
+        if let Some(inflight_packets) = self.state.get(inflight_packets_path).map_err(read_err)? {
+            return Ok(inflight_packets);
+        }
+        let InflightPacketsPath(port_id, channel_id) = inflight_packets_path;
+        let commitments = self
+            .state
+            .paths_with_prefix(&format!(
+                "commitments/ports/{port_id}/channels/{channel_id}/sequences/"
+            ))
+            .map_err(read_err)?;
+        Ok(commitments.len() as u64)
     }
+}
 
-    fn host_timestamp(&self) -> Result<Timestamp, ContextError> {
-        Ok(self.current_time.into())
+impl<'a> IbcHandler<'a> {
+    /// The next sequence stored at `path`. ICS-4 sequences start at 1, so a
+    /// stored zero can only have been written by a bug and is reported like a
+    /// value that cannot be decoded rather than used.
+    fn next_sequence<K>(&self, path: &K) -> Result<Option<Sequence>, ChannelError>
+    where
+        K: KnownPath<Value = Sequence>,
+    {
+        let read_err = |err| ChannelError::Other {
+            description: read_err_description(path, &err),
+        };
+        match self.state.get(path).map_err(read_err)? {
+            Some(seq) if u64::from(seq) == 0 => Err(read_err(anyhow!(
+                "sequence 0 is invalid since sequences start at 1"
+            ))),
+            seq => Ok(seq),
+        }
     }
 
-    fn host_consensus_state(
-        &self,
-        height: &Height,
-    ) -> Result<Box<dyn ConsensusState>, ContextError> {
-        let slot = eclipse_chain::slot_of_height(*height)?;
-        Ok(self
-            .consensus_state(slot)
-            .map_err(|err| ClientError::ClientSpecific {
-                description: err.to_string(),
-            })?
-            .ok_or(ClientError::MissingLocalConsensusState { height: *height })?)
+    fn store_next_sequence<K>(&mut self, path: &K, seq: Sequence) -> Result<(), ChannelError>
+    where
+        K: KnownPath<Value = Sequence>,
+    {
+        if u64::from(seq) == 0 {
+            return Err(ChannelError::Other {
+                description: format!(
+                    "cannot store sequence 0 at path {path} since sequences start at 1"
+                ),
+            });
+        }
+        self.state.set(path, seq);
+        Ok(())
     }
+}
 
-    fn client_counter(&self) -> Result<u64, ContextError> {
-        Ok(self.metadata.client_id_counter)
+/// The local end of a handshake that a message completes a step of, along with
+/// the state it must be in for that step.
+enum HandshakeTarget {
+    Connection(String, RawConnectionState),
+    Channel(String, String, RawChannelState),
+}
+
+/// The port and channel of the existing channel that `envelope` is for, if it is
+/// a channel message past `OpenTry` or a packet message.
+fn existing_channel(envelope: &MsgEnvelope) -> anyhow::Result<Option<(PortId, ChannelId)>> {
+    let channel = match envelope {
+        MsgEnvelope::Channel(ChannelMsg::OpenAck(msg)) => {
+            let RawMsgChannelOpenAck {
+                port_id,
+                channel_id,
+                ..
+            } = msg.clone().into();
+            (port_id.parse()?, channel_id.parse()?)
+        }
+        MsgEnvelope::Channel(ChannelMsg::OpenConfirm(msg)) => {
+            let RawMsgChannelOpenConfirm {
+                port_id,
+                channel_id,
+                ..
+            } = msg.clone().into();
+            (port_id.parse()?, channel_id.parse()?)
+        }
+        MsgEnvelope::Channel(ChannelMsg::CloseInit(msg)) => {
+            let RawMsgChannelCloseInit {
+                port_id,
+                channel_id,
+                ..
+            } = msg.clone().into();
+            (port_id.parse()?, channel_id.parse()?)
+        }
+        MsgEnvelope::Channel(ChannelMsg::CloseConfirm(msg)) => {
+            let RawMsgChannelCloseConfirm {
+                port_id,
+                channel_id,
+                ..
+            } = msg.clone().into();
+            (port_id.parse()?, channel_id.parse()?)
+        }
+        MsgEnvelope::Packet(PacketMsg::Recv(msg)) => (
+            msg.packet.port_id_on_b.clone(),
+            msg.packet.chan_id_on_b.clone(),
+        ),
+        MsgEnvelope::Packet(PacketMsg::Ack(msg)) => (
+            msg.packet.port_id_on_a.clone(),
+            msg.packet.chan_id_on_a.clone(),
+        ),
+        MsgEnvelope::Packet(PacketMsg::Timeout(msg)) => (
+            msg.packet.port_id_on_a.clone(),
+            msg.packet.chan_id_on_a.clone(),
+        ),
+        MsgEnvelope::Packet(PacketMsg::TimeoutOnClose(msg)) => (
+            msg.packet.port_id_on_a.clone(),
+            msg.packet.chan_id_on_a.clone(),
+        ),
+        _ => return Ok(None),
+    };
+    Ok(Some(channel))
+}
+
+/// Describes why `envelope` is rejected, if it opens a channel over more than
+/// one connection hop. Multihop channels are not supported yet, so they are
+/// rejected before dispatch instead of being left to ibc-rs.
+pub(super) fn multihop_channel_rejection(envelope: &MsgEnvelope) -> Option<String> {
+    let (msg_name, channel) = match envelope {
+        MsgEnvelope::Channel(ChannelMsg::OpenInit(msg)) => {
+            let RawMsgChannelOpenInit { channel, .. } = msg.clone().into();
+            ("MsgChannelOpenInit", channel)
+        }
+        MsgEnvelope::Channel(ChannelMsg::OpenTry(msg)) => {
+            let RawMsgChannelOpenTry { channel, .. } = msg.clone().into();
+            ("MsgChannelOpenTry", channel)
+        }
+        _ => return None,
+    };
+    let connection_hops = channel?.connection_hops;
+    (connection_hops.len() > 1).then(|| {
+        format!(
+            "{msg_name} has connection hops {}, but multihop channels are not yet supported",
+            connection_hops.join(",")
+        )
+    })
+}
+
+/// Rewrites `any` to its fixed type URL if it has a legacy Eclipse one, recording
+/// the legacy type URL and its replacement in `rewritten`.
+fn canonicalize_any(any: &mut Option<protobuf::Any>, rewritten: &mut Vec<(String, &'static str)>) {
+    if let Some(any) = any {
+        if let Some(canonical) = canonical_type_url(&any.type_url) {
+            let legacy = mem::replace(&mut any.type_url, canonical.to_owned());
+            rewritten.push((legacy, canonical));
+        }
     }
+}
 
-    fn connection_end(&self, connection_id: &ConnectionId) -> Result<ConnectionEnd, ContextError> {
-        let connection_path = ConnectionPath(connection_id.clone());
-        Ok(self
-            .state
-            .get(&connection_path)
-            .map_err(|err| ConnectionError::Other {
+fn raw_connection_state_name(state: i32) -> &'static str {
+    RawConnectionState::from_i32(state).map_or("UNKNOWN", |state| state.as_str_name())
+}
+
+fn raw_channel_state_name(state: i32) -> &'static str {
+    RawChannelState::from_i32(state).map_or("UNKNOWN", |state| state.as_str_name())
+}
+
+/// Describes a failure to read or decode the value stored at `path`. This is kept
+/// distinct from the not-found errors so that a corrupt value is never mistaken for
+/// a missing one.
+fn read_err_description(path: &impl Display, err: &anyhow::Error) -> String {
+    format!("failed to read value at path {path}: {err:#}")
+}
+
+impl<'a> ExecutionContext for IbcHandler<'a> {
+    fn store_client_state(
+        &mut self,
+        client_state_path: ClientStatePath,
+        client_state: Box<dyn ClientState>,
+    ) -> Result<(), ContextError> {
+        // Client types update their state in ibc-rs or the light client, so the
+        // height an update moves from is read here, before it is overwritten
+        if let Some(previous_latest_height) = self.stored_latest_height(&client_state_path.0) {
+            self.previous_latest_heights
+                .insert(client_state_path.0.clone(), previous_latest_height);
+        }
+
+        let client_state = encode_client_state(client_state)?;
+        self.state.set(
+            &ClientTypeUrlPath(client_state_path.0.clone()),
+            client_state.type_url.clone(),
+        );
+        self.state
+            .set_any(&client_state_path, client_state)
+            .map_err(|err| ClientError::Other {
                 description: err.to_string(),
-            })?
-            .ok_or_else(|| ConnectionError::ConnectionNotFound {
-                connection_id: connection_id.clone(),
-            })?)
+            })?;
+        Ok(())
     }
 
-    fn validate_self_client(
-        &self,
-        _counterparty_client_state: protobuf::Any,
+    fn store_consensus_state(
+        &mut self,
+        consensus_state_path: ClientConsensusStatePath,
+        consensus_state: Box<dyn ConsensusState>,
     ) -> Result<(), ContextError> {
-        // TODO: Figure out how to actually validate `counterparty_client_state`
+        let ClientConsensusStatePath {
+            client_id,
+            epoch: revision_number,
+            height: revision_height,
+        } = &consensus_state_path;
+        let height = Height::new(*revision_number, *revision_height)?;
+
+        let consensus_heights_path = ConsensusHeightsPath(client_id.clone());
+        let mut consensus_heights =
+            self.state
+                .get_or_default(&consensus_heights_path)
+                .map_err(|err| ClientError::Other {
+                    description: read_err_description(&consensus_heights_path, &err),
+                })?;
+        consensus_heights.heights.insert(height);
+        self.evict_consensus_states(client_id, &mut consensus_heights, height)
+            .map_err(|err| ClientError::Other {
+                description: format!(
+                    "failed to evict consensus states of client {client_id}: {err:#}"
+                ),
+            })?;
+        self.state.set(&consensus_heights_path, consensus_heights);
+
+        self.state
+            .set_any(
+                &consensus_state_path,
+                encode_consensus_state(consensus_state)?,
+            )
+            .map_err(|err| ClientError::Other {
+                description: err.to_string(),
+            })?;
         Ok(())
     }
 
-    fn commitment_prefix(&self) -> CommitmentPrefix {
-        eclipse_chain::COMMITMENT_PREFIX
-            .to_vec()
-            .try_into()
-            .expect("Prefix is not empty")
+    fn increase_client_counter(&mut self) {
+        self.metadata.client_id_counter += 1;
     }
 
-    fn connection_counter(&self) -> Result<u64, ContextError> {
-        Ok(self.metadata.connection_id_counter)
+    fn store_update_time(
+        &mut self,
+        client_id: ClientId,
+        height: Height,
+        timestamp: Timestamp,
+    ) -> Result<(), ContextError> {
+        // The zero timestamp means "no timestamp", which a client was never
+        // updated at, and a time past the bound could not be read back
+        if timestamp == Timestamp::none() || timestamp.nanoseconds() > MAX_TIMESTAMP_NANOS {
+            return Err(ClientError::Other {
+                description: format!(
+                    "refusing to record {timestamp} as the update time of client {client_id} \
+                     at {height}"
+                ),
+            }
+            .into());
+        }
+        let client_update_time_path = ClientUpdateTimePath(client_id, height);
+        self.state.set(&client_update_time_path, timestamp);
+        Ok(())
     }
 
-    fn channel_end(&self, channel_end_path: &ChannelEndPath) -> Result<ChannelEnd, ContextError> {
-        Ok(self
-            .state
-            .get(channel_end_path)
-            .map_err(|err| ChannelError::Other {
-                description: err.to_string(),
-            })?
-            .ok_or_else(|| {
-                let ChannelEndPath(port_id, channel_id) = channel_end_path;
-                ChannelError::ChannelNotFound {
-                    port_id: port_id.clone(),
-                    channel_id: channel_id.clone(),
-                }
-            })?)
+    fn store_update_height(
+        &mut self,
+        client_id: ClientId,
+        height: Height,
+        host_height: Height,
+    ) -> Result<(), ContextError> {
+        let processed_heights_path = ProcessedHeightsPath(client_id.clone());
+        self.state
+            .update(&processed_heights_path, |processed_heights| {
+                processed_heights.heights.insert(height);
+            })
+            .map_err(|err| ClientError::Other {
+                description: read_err_description(&processed_heights_path, &err),
+            })?;
+
+        let client_update_height_path = ClientUpdateHeightPath(client_id, height);
+        self.state.set(&client_update_height_path, host_height);
+        Ok(())
     }
 
-    fn get_next_sequence_send(
-        &self,
-        seq_send_path: &SeqSendPath,
-    ) -> Result<Sequence, ContextError> {
-        Ok(self
-            .state
-            .get(seq_send_path)
-            // TODO: Fix the IBC library to include an error message
-            .map_err(|_err| PacketError::ImplementationSpecific)?
-            .ok_or_else(|| {
-                let SeqSendPath(port_id, channel_id) = seq_send_path;
-                PacketError::MissingNextSendSeq {
-                    port_id: port_id.clone(),
-                    channel_id: channel_id.clone(),
-                }
-            })?)
+    fn store_connection(
+        &mut self,
+        connection_path: &ConnectionPath,
+        connection_end: ConnectionEnd,
+    ) -> Result<(), ContextError> {
+        self.state.set(connection_path, connection_end);
+        Ok(())
     }
 
-    fn get_next_sequence_recv(
-        &self,
-        seq_recv_path: &SeqRecvPath,
-    ) -> Result<Sequence, ContextError> {
-        Ok(self
-            .state
-            .get(seq_recv_path)
-            // TODO: Fix the IBC library to include an error message
-            .map_err(|_err| PacketError::ImplementationSpecific)?
-            .ok_or_else(|| {
-                let SeqRecvPath(port_id, channel_id) = seq_recv_path;
-                PacketError::MissingNextRecvSeq {
-                    port_id: port_id.clone(),
-                    channel_id: channel_id.clone(),
-                }
-            })?)
+    fn store_connection_to_client(
+        &mut self,
+        client_connection_path: &ClientConnectionPath,
+        connection_id: ConnectionId,
+    ) -> Result<(), ContextError> {
+        self.state
+            .update(client_connection_path, |client_connections| {
+                client_connections.connections.insert(connection_id);
+            })
+            .map_err(|err| ConnectionError::Other {
+                description: read_err_description(client_connection_path, &err),
+            })?;
+        Ok(())
     }
 
-    fn get_next_sequence_ack(&self, seq_ack_path: &SeqAckPath) -> Result<Sequence, ContextError> {
-        Ok(self
-            .state
-            .get(seq_ack_path)
-            // TODO: Fix the IBC library to include an error message
-            .map_err(|_err| PacketError::ImplementationSpecific)?
-            .ok_or_else(|| {
-                let SeqAckPath(port_id, channel_id) = seq_ack_path;
-                PacketError::MissingNextAckSeq {
-                    port_id: port_id.clone(),
-                    channel_id: channel_id.clone(),
-                }
-            })?)
+    fn increase_connection_counter(&mut self) {
+        self.metadata.connection_id_counter += 1;
     }
 
-    fn get_packet_commitment(
-        &self,
+    fn store_packet_commitment(
+        &mut self,
         commitment_path: &CommitmentPath,
-    ) -> Result<PacketCommitment, ContextError> {
-        Ok(self
-            .state
-            .get(commitment_path)
-            // TODO: Fix the IBC library to include an error message
-            .map_err(|_err| PacketError::ImplementationSpecific)?
-            .ok_or_else(|| {
-                let CommitmentPath {
-                    port_id: _,
-                    channel_id: _,
-                    sequence,
-                } = commitment_path;
-                PacketError::PacketCommitmentNotFound {
-                    sequence: *sequence,
+        commitment: PacketCommitment,
+    ) -> Result<(), ContextError> {
+        let inflight_packets_path = InflightPacketsPath(
+            commitment_path.port_id.clone(),
+            commitment_path.channel_id.clone(),
+        );
+        let inflight_packets = self.inflight_packets(&inflight_packets_path)?;
+
+        if let Some(max_inflight_packets) = self.chain_params.max_inflight_packets_per_channel {
+            if inflight_packets >= max_inflight_packets {
+                return Err(ChannelError::Other {
+                    description: format!(
+                        "channel {}/{} already has {inflight_packets} in-flight packets, \
+                         the most allowed by max_inflight_packets_per_channel",
+                        commitment_path.port_id, commitment_path.channel_id,
+                    ),
                 }
-            })?)
-    }
+                .into());
+            }
+        }
 
-    fn get_packet_receipt(&self, receipt_path: &ReceiptPath) -> Result<Receipt, ContextError> {
-        Ok(self
-            .state
-            .get(receipt_path)
-            // TODO: Fix the IBC library to include an error message
-            .map_err(|_err| PacketError::ImplementationSpecific)?
-            .ok_or_else(|| {
-                let ReceiptPath {
-                    port_id: _,
-                    channel_id: _,
-                    sequence,
-                } = receipt_path;
-                PacketError::PacketReceiptNotFound {
-                    sequence: *sequence,
-                }
-            })?)
+        self.state.set(&inflight_packets_path, inflight_packets + 1);
+        self.state.set(commitment_path, commitment);
+        Ok(())
     }
 
-    fn get_packet_acknowledgement(
-        &self,
-        ack_path: &AckPath,
-    ) -> Result<AcknowledgementCommitment, ContextError> {
-        Ok(self
-            .state
-            .get(ack_path)
-            // TODO: Fix the IBC library to include an error message
-            .map_err(|_err| PacketError::ImplementationSpecific)?
-            .ok_or_else(|| {
-                let AckPath {
-                    port_id: _,
-                    channel_id: _,
-                    sequence,
-                } = ack_path;
-                PacketError::PacketAcknowledgementNotFound {
-                    sequence: *sequence,
-                }
-            })?)
+    fn delete_packet_commitment(
+        &mut self,
+        commitment_path: &CommitmentPath,
+    ) -> Result<(), ContextError> {
+        let inflight_packets_path = InflightPacketsPath(
+            commitment_path.port_id.clone(),
+            commitment_path.channel_id.clone(),
+        );
+        // Kept at zero, so that the channel's commitments are only counted once
+        let inflight_packets = self.inflight_packets(&inflight_packets_path)?;
+        self.state
+            .set(&inflight_packets_path, inflight_packets.saturating_sub(1));
+        self.state.remove(commitment_path);
+        Ok(())
     }
 
-    fn client_update_time(
-        &self,
-        client_id: &ClientId,
-        height: &Height,
-    ) -> Result<Timestamp, ContextError> {
-        let client_update_time_path = ClientUpdateTimePath(client_id.clone(), *height);
-        Ok(self
-            .state
-            .get(&client_update_time_path)
-            .map_err(|err| ChannelError::Other {
-                description: err.to_string(),
-            })?
-            .ok_or_else(|| ChannelError::ProcessedTimeNotFound {
-                client_id: client_id.clone(),
-                height: *height,
-            })?)
+    fn store_packet_receipt(
+        &mut self,
+        receipt_path: &ReceiptPath,
+        receipt: Receipt,
+    ) -> Result<(), ContextError> {
+        self.state.set(receipt_path, receipt);
+        Ok(())
     }
 
-    fn client_update_height(
-        &self,
-        client_id: &ClientId,
-        height: &Height,
-    ) -> Result<Height, ContextError> {
-        let client_update_height_path = ClientUpdateHeightPath(client_id.clone(), *height);
-        Ok(self
-            .state
-            .get(&client_update_height_path)
-            .map_err(|err| ChannelError::Other {
-                description: err.to_string(),
-            })?
-            .ok_or_else(|| ChannelError::ProcessedHeightNotFound {
-                client_id: client_id.clone(),
-                height: *height,
-            })?)
+    fn store_packet_acknowledgement(
+        &mut self,
+        ack_path: &AckPath,
+        ack_commitment: AcknowledgementCommitment,
+    ) -> Result<(), ContextError> {
+        self.state.set(ack_path, ack_commitment);
+        Ok(())
     }
 
-    fn channel_counter(&self) -> Result<u64, ContextError> {
-        Ok(self.metadata.channel_id_counter)
+    fn delete_packet_acknowledgement(&mut self, ack_path: &AckPath) -> Result<(), ContextError> {
+        self.state.remove(ack_path);
+        Ok(())
     }
 
-    fn max_expected_time_per_block(&self) -> Duration {
-        self.max_expected_time_per_block
+    fn store_channel(
+        &mut self,
+        channel_end_path: &ChannelEndPath,
+        channel_end: ChannelEnd,
+    ) -> Result<(), ContextError> {
+        if matches!(
+            channel_end.state(),
+            ChannelState::Init | ChannelState::TryOpen
+        ) {
+            let ChannelEndPath(port_id, channel_id) = channel_end_path;
+            self.record_channel_owner(ChannelOwnerPath(port_id.clone(), channel_id.clone()))?;
+        }
+        self.state.set(channel_end_path, channel_end);
+        Ok(())
     }
 
-    fn validate_message_signer(&self, signer: &Signer) -> Result<(), ContextError> {
-        Pubkey::from_str(signer.as_ref()).map_err(|err| ClientError::InvalidSigner {
-            reason: err.to_string(),
-        })?;
+    fn store_next_sequence_send(
+        &mut self,
+        seq_send_path: &SeqSendPath,
+        seq: Sequence,
+    ) -> Result<(), ContextError> {
+        self.store_next_sequence(seq_send_path, seq)?;
         Ok(())
     }
-}
-
-fn module_id_of_pubkey(pubkey: &Pubkey) -> ModuleId {
-    ModuleId::new(hex::encode(pubkey.as_ref()))
-}
 
-fn pubkey_of_module_id(module_id: &ModuleId) -> anyhow::Result<Pubkey> {
-    Pubkey::try_from(hex::decode(module_id.to_string())?)
-        .map_err(|bytes| anyhow!("Failed to decode pubkey from bytes: {bytes:?}"))
-}
+    fn store_next_sequence_recv(
+        &mut self,
+        seq_recv_path: &SeqRecvPath,
+        seq: Sequence,
+    ) -> Result<(), ContextError> {
+        self.store_next_sequence(seq_recv_path, seq)?;
+        Ok(())
+    }
 
-impl<'a> Router for IbcHandler<'a> {
-    fn get_route(&self, module_id: &ModuleId) -> Option<&dyn Module> {
-        self.module_by_id.get(module_id).map(|m| &**m)
+    fn store_next_sequence_ack(
+        &mut self,
+        seq_ack_path: &SeqAckPath,
+        seq: Sequence,
+    ) -> Result<(), ContextError> {
+        self.store_next_sequence(seq_ack_path, seq)?;
+        Ok(())
     }
 
-    fn get_route_mut(&mut self, module_id: &ModuleId) -> Option<&mut dyn Module> {
-        // Must be manually expanded due to a compiler bug
-        match self.module_by_id.get_mut(module_id) {
-            Some(m) => Some(&mut **m),
-            None => None,
-        }
+    fn increase_channel_counter(&mut self) {
+        self.metadata.channel_id_counter += 1;
     }
 
-    fn has_route(&self, module_id: &ModuleId) -> bool {
-        self.module_by_id.contains_key(module_id)
+    fn emit_ibc_event(&mut self, event: IbcEvent) {
+        let extra_attributes = match &event {
+            IbcEvent::UpdateClient(update_client) => self
+                .previous_latest_heights
+                .remove(update_client.client_id())
+                .map(|height| vec![(ibc_events::PREVIOUS_LATEST_HEIGHT_KEY, height.to_string())])
+                .unwrap_or_default(),
+            _ => vec![],
+        };
+        match ibc_events::event_log_with_attributes(event, extra_attributes) {
+            Ok(event_log) => {
+                msg!(&event_log);
+                self.event_logs.push(event_log);
+            }
+            Err(err) => {
+                bounded_log::msg_trunc("could not encode IBC event", err, MAX_TERSE_LOG_LEN)
+            }
+        }
     }
 
-    fn lookup_module_by_port(&self, port_id: &PortId) -> Option<ModuleId> {
-        let port_path = PortPath(port_id.clone());
-        self.state.get(&port_path).ok().flatten()
+    // TODO: Figure out where to log IBC messages
+    fn log_message(&mut self, message: String) {
+        // These describe the handled message, including fields of arbitrary size,
+        // so they are only logged when asked for
+        if self.chain_params.verbose_logs {
+            bounded_log::msg_trunc("ibc", message, MAX_VERBOSE_LOG_LEN);
+        }
     }
 }
 
-impl<'a> IbcHandler<'a> {
-    pub(super) fn bind_port(&mut self, port_id: &PortId, pubkey: &Pubkey) -> Result<(), PortError> {
-        let port_path = PortPath(port_id.clone());
-        let module_id = module_id_of_pubkey(pubkey);
-        if self.lookup_module_by_port(port_id).is_none() {
-            self.state.set(&port_path, module_id.clone());
-            self.state
-                .update(&AllModulesPath, |all_module_ids| {
-                    all_module_ids.modules.insert(module_id);
-                })
-                .map_err(|_err| PortError::ImplementationSpecific)?;
+impl<'a> ValidationContext for IbcHandler<'a> {
+    fn client_state(&self, client_id: &ClientId) -> Result<Box<dyn ClientState>, ContextError> {
+        let client_state_path = ClientStatePath::new(client_id);
+        self.decode_client_state(
+            self.state
+                .get_any(&client_state_path)
+                .map_err(|err| ClientError::Other {
+                    description: read_err_description(&client_state_path, &err),
+                })?
+                .ok_or_else(|| ClientError::ClientStateNotFound {
+                    client_id: client_id.clone(),
+                })?,
+        )
+    }
 
-            Ok(())
-        } else {
-            Err(PortError::ImplementationSpecific)
-        }
+    fn decode_client_state(
+        &self,
+        client_state: protobuf::Any,
+    ) -> Result<Box<dyn ClientState>, ContextError> {
+        decode_client_state(client_state)
     }
 
-    pub(super) fn release_port(
-        &mut self,
-        port_id: &PortId,
-        pubkey: &Pubkey,
-    ) -> Result<(), PortError> {
-        let port_path = PortPath(port_id.clone());
-        let module_id = module_id_of_pubkey(pubkey);
-        match self.lookup_module_by_port(port_id) {
-            Some(curr_module_id) => {
-                if module_id == curr_module_id {
-                    self.state.remove(&port_path);
-                    self.state
-                        .update(&AllModulesPath, |all_module_ids| {
-                            all_module_ids.modules.remove(&module_id);
-                        })
-                        .map_err(|_err| PortError::ImplementationSpecific)?;
+    fn consensus_state(
+        &self,
+        client_consensus_path: &ClientConsensusStatePath,
+    ) -> Result<Box<dyn ConsensusState>, ContextError> {
+        let ClientConsensusStatePath {
+            client_id,
+            epoch: revision_number,
+            height: revision_height,
+        } = client_consensus_path;
+        let height = Height::new(*revision_number, *revision_height)?;
 
-                    Ok(())
-                } else {
-                    Err(PortError::ImplementationSpecific)
+        decode_consensus_state(
+            self.state
+                .get_any(client_consensus_path)
+                .map_err(|err| ClientError::Other {
+                    description: read_err_description(client_consensus_path, &err),
+                })?
+                .ok_or_else(|| ClientError::ConsensusStateNotFound {
+                    client_id: client_id.clone(),
+                    height,
+                })?,
+        )
+    }
+
+    fn next_consensus_state(
+        &self,
+        client_id: &ClientId,
+        height: &Height,
+    ) -> Result<Option<Box<dyn ConsensusState>>, ContextError> {
+        let consensus_heights_path = ConsensusHeightsPath(client_id.clone());
+
+        let consensus_heights =
+            self.state
+                .get(&consensus_heights_path)
+                .map_err(|err| ClientError::Other {
+                    description: read_err_description(&consensus_heights_path, &err),
+                })?;
+
+        let consensus_heights = match consensus_heights {
+            None => return Ok(None),
+            Some(consensus_heights) => consensus_heights,
+        };
+
+        let next_consensus_height = match consensus_heights
+            .heights
+            .range((Excluded(*height), Unbounded))
+            .next()
+        {
+            None => return Ok(None),
+            Some(next_consensus_height) => *next_consensus_height,
+        };
+
+        let client_consensus_path = ClientConsensusStatePath {
+            client_id: client_id.clone(),
+            epoch: next_consensus_height.revision_number(),
+            height: next_consensus_height.revision_height(),
+        };
+
+        Ok(Some(decode_consensus_state(
+            self.state
+                .get_any(&client_consensus_path)
+                .map_err(|err| ClientError::Other {
+                    description: read_err_description(&client_consensus_path, &err),
+                })?
+                .ok_or_else(|| ClientError::ConsensusStateNotFound {
+                    client_id: client_id.clone(),
+                    height: next_consensus_height,
+                })?,
+        )?))
+    }
+
+    fn prev_consensus_state(
+        &self,
+        client_id: &ClientId,
+        height: &Height,
+    ) -> Result<Option<Box<dyn ConsensusState>>, ContextError> {
+        let consensus_heights_path = ConsensusHeightsPath(client_id.clone());
+
+        let consensus_heights =
+            self.state
+                .get(&consensus_heights_path)
+                .map_err(|err| ClientError::Other {
+                    description: read_err_description(&consensus_heights_path, &err),
+                })?;
+
+        let consensus_heights = match consensus_heights {
+            None => return Ok(None),
+            Some(consensus_heights) => consensus_heights,
+        };
+
+        let next_consensus_height = match consensus_heights.heights.range(..*height).next_back() {
+            None => return Ok(None),
+            Some(next_consensus_height) => *next_consensus_height,
+        };
+
+        let client_consensus_path = ClientConsensusStatePath {
+            client_id: client_id.clone(),
+            epoch: next_consensus_height.revision_number(),
+            height: next_consensus_height.revision_height(),
+        };
+
+        Ok(Some(decode_consensus_state(
+            self.state
+                .get_any(&client_consensus_path)
+                .map_err(|err| ClientError::Other {
+                    description: read_err_description(&client_consensus_path, &err),
+                })?
+                .ok_or_else(|| ClientError::ConsensusStateNotFound {
+                    client_id: client_id.clone(),
+                    height: next_consensus_height,
+                })?,
+        )?))
+    }
+
+    fn host_height(&self) -> Result<Height, ContextError> {
+        Ok(eclipse_chain::height_of_slot_with_revision(
+            self.current_slot,
+            self.metadata.revision_number,
+        )?)
+    }
+
+    fn host_timestamp(&self) -> Result<Timestamp, ContextError> {
+        Ok(self.current_time.into())
+    }
+
+    fn host_consensus_state(
+        &self,
+        height: &Height,
+    ) -> Result<Box<dyn ConsensusState>, ContextError> {
+        let slot = eclipse_chain::slot_of_height(height, self.metadata.revision_number)?;
+        Ok(self
+            .consensus_state(slot)
+            .map_err(|err| ClientError::ClientSpecific {
+                description: err.to_string(),
+            })?
+            .ok_or(ClientError::MissingLocalConsensusState { height: *height })?)
+    }
+
+    fn client_counter(&self) -> Result<u64, ContextError> {
+        Ok(self.metadata.client_id_counter)
+    }
+
+    fn connection_end(&self, connection_id: &ConnectionId) -> Result<ConnectionEnd, ContextError> {
+        let connection_path = ConnectionPath(connection_id.clone());
+        Ok(self
+            .state
+            .get(&connection_path)
+            .map_err(|err| ConnectionError::Other {
+                description: read_err_description(&connection_path, &err),
+            })?
+            .ok_or_else(|| ConnectionError::ConnectionNotFound {
+                connection_id: connection_id.clone(),
+            })?)
+    }
+
+    fn validate_self_client(
+        &self,
+        mut counterparty_client_state: protobuf::Any,
+    ) -> Result<(), ContextError> {
+        // The counterparty may have stored its client before the Eclipse type URLs
+        // were fixed, and this is the `Any` it stored
+        if let Some(canonical) = canonical_type_url(&counterparty_client_state.type_url) {
+            counterparty_client_state.type_url = canonical.to_owned();
+        }
+        if counterparty_client_state.type_url != ECLIPSE_CLIENT_STATE_TYPE_URL {
+            return Err(ConnectionError::Other {
+                description: format!(
+                    "counterparty client state has type URL {}, but this chain can only be \
+                     tracked by clients of type {ECLIPSE_CLIENT_STATE_TYPE_URL}",
+                    counterparty_client_state.type_url,
+                ),
+            }
+            .into());
+        }
+        let client_state =
+            EclipseClientState::try_from(counterparty_client_state).map_err(|err| {
+                ConnectionError::Other {
+                    description: format!(
+                        "counterparty client state is not an Eclipse client: {err}"
+                    ),
+                }
+            })?;
+
+        let chain_id = &client_state.chain_id;
+        if !chain_id
+            .as_str()
+            .starts_with(&format!("{}-", eclipse_chain::CHAIN_NAME_PREFIX))
+        {
+            return Err(ConnectionError::Other {
+                description: format!(
+                    "counterparty client tracks chain {chain_id}, which is not an Eclipse chain"
+                ),
+            }
+            .into());
+        }
+        if let Some(chain_name) = &self.chain_params.chain_name {
+            let revision_number = self.metadata.revision_number;
+            let expected = eclipse_chain::chain_id_with_revision(chain_name, revision_number);
+            // Clients not yet updated past a revision bump still track this chain
+            if chain_id.version() > revision_number
+                || eclipse_chain::chain_id_in_revision(chain_id, revision_number) != expected
+            {
+                return Err(ConnectionError::Other {
+                    description: format!(
+                        "counterparty client tracks chain {chain_id}, but this chain is {expected}"
+                    ),
                 }
+                .into());
             }
-            None => Err(PortError::UnknownPort {
-                port_id: port_id.clone(),
-            }),
         }
+
+        // Either side may predate genesis hashes, in which case there is nothing to compare
+        if let (Some(expected), Some(actual)) =
+            (self.chain_params.genesis_hash, client_state.genesis_hash)
+        {
+            if actual.to_bytes() != expected {
+                return Err(ConnectionError::Other {
+                    description: format!(
+                        "counterparty client tracks the cluster with genesis hash {actual}, \
+                         but this cluster's genesis hash is {}",
+                        Hash::new_from_array(expected),
+                    ),
+                }
+                .into());
+            }
+        }
+
+        let host_height = self.host_height()?;
+        let latest_height = client_state.latest_height();
+        if latest_height > host_height {
+            return Err(ConnectionError::Other {
+                description: format!(
+                    "counterparty client is at height {latest_height}, past this chain's height \
+                     {host_height}"
+                ),
+            }
+            .into());
+        }
+        if let Some(frozen_height) = client_state.frozen_height {
+            return Err(ConnectionError::Other {
+                description: format!("counterparty client is frozen at height {frozen_height}"),
+            }
+            .into());
+        }
+
+        // Eclipse client states carry no proof specs or commitment prefix to compare:
+        // they always verify with `eclipse_chain::proof_specs()`, and the prefix the
+        // counterparty records for this chain is part of the connection end that
+        // ibc-rs proves against `commitment_prefix()`
+        Ok(())
     }
-}
 
-#[derive(Debug)]
-struct SolanaModule {
-    program_id: Pubkey,
-}
+    fn commitment_prefix(&self) -> CommitmentPrefix {
+        eclipse_chain::commitment_prefix()
+    }
 
-impl Module for SolanaModule {
-    fn on_chan_open_init_validate(
+    fn connection_counter(&self) -> Result<u64, ContextError> {
+        Ok(self.metadata.connection_id_counter)
+    }
+
+    fn channel_end(&self, channel_end_path: &ChannelEndPath) -> Result<ChannelEnd, ContextError> {
+        Ok(self
+            .state
+            .get(channel_end_path)
+            .map_err(|err| ChannelError::Other {
+                description: read_err_description(channel_end_path, &err),
+            })?
+            .ok_or_else(|| {
+                let ChannelEndPath(port_id, channel_id) = channel_end_path;
+                ChannelError::ChannelNotFound {
+                    port_id: port_id.clone(),
+                    channel_id: channel_id.clone(),
+                }
+            })?)
+    }
+
+    fn get_next_sequence_send(
         &self,
-        order: Order,
-        connection_hops: &[ConnectionId],
-        port_id: &PortId,
-        channel_id: &ChannelId,
-        counterparty: &Counterparty,
-        version: &Version,
-    ) -> Result<Version, ChannelError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnChanOpenInitValidate(OnChanOpenInitValidate {
-                order,
-                connection_hops: connection_hops.to_vec(),
+        seq_send_path: &SeqSendPath,
+    ) -> Result<Sequence, ContextError> {
+        Ok(self.next_sequence(seq_send_path)?.ok_or_else(|| {
+            let SeqSendPath(port_id, channel_id) = seq_send_path;
+            PacketError::MissingNextSendSeq {
                 port_id: port_id.clone(),
                 channel_id: channel_id.clone(),
-                counterparty: counterparty.clone(),
-                version: version.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
+            }
+        })?)
+    }
 
-        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })?;
+    fn get_next_sequence_recv(
+        &self,
+        seq_recv_path: &SeqRecvPath,
+    ) -> Result<Sequence, ContextError> {
+        Ok(self.next_sequence(seq_recv_path)?.ok_or_else(|| {
+            let SeqRecvPath(port_id, channel_id) = seq_recv_path;
+            PacketError::MissingNextRecvSeq {
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+            }
+        })?)
+    }
+
+    fn get_next_sequence_ack(&self, seq_ack_path: &SeqAckPath) -> Result<Sequence, ContextError> {
+        Ok(self.next_sequence(seq_ack_path)?.ok_or_else(|| {
+            let SeqAckPath(port_id, channel_id) = seq_ack_path;
+            PacketError::MissingNextAckSeq {
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+            }
+        })?)
+    }
+
+    fn get_packet_commitment(
+        &self,
+        commitment_path: &CommitmentPath,
+    ) -> Result<PacketCommitment, ContextError> {
+        Ok(self
+            .state
+            .get(commitment_path)
+            .map_err(|err| ChannelError::Other {
+                description: read_err_description(commitment_path, &err),
+            })?
+            .ok_or_else(|| {
+                let CommitmentPath {
+                    port_id: _,
+                    channel_id: _,
+                    sequence,
+                } = commitment_path;
+                PacketError::PacketCommitmentNotFound {
+                    sequence: *sequence,
+                }
+            })?)
+    }
+
+    fn get_packet_receipt(&self, receipt_path: &ReceiptPath) -> Result<Receipt, ContextError> {
+        Ok(self
+            .state
+            .get(receipt_path)
+            .map_err(|err| ChannelError::Other {
+                description: read_err_description(receipt_path, &err),
+            })?
+            .ok_or_else(|| {
+                let ReceiptPath {
+                    port_id: _,
+                    channel_id: _,
+                    sequence,
+                } = receipt_path;
+                PacketError::PacketReceiptNotFound {
+                    sequence: *sequence,
+                }
+            })?)
+    }
+
+    fn get_packet_acknowledgement(
+        &self,
+        ack_path: &AckPath,
+    ) -> Result<AcknowledgementCommitment, ContextError> {
+        Ok(self
+            .state
+            .get(ack_path)
+            .map_err(|err| ChannelError::Other {
+                description: read_err_description(ack_path, &err),
+            })?
+            .ok_or_else(|| {
+                let AckPath {
+                    port_id: _,
+                    channel_id: _,
+                    sequence,
+                } = ack_path;
+                PacketError::PacketAcknowledgementNotFound {
+                    sequence: *sequence,
+                }
+            })?)
+    }
+
+    fn client_update_time(
+        &self,
+        client_id: &ClientId,
+        height: &Height,
+    ) -> Result<Timestamp, ContextError> {
+        let client_update_time_path = ClientUpdateTimePath(client_id.clone(), *height);
+        Ok(self
+            .state
+            .get(&client_update_time_path)
+            .map_err(|err| ChannelError::Other {
+                description: read_err_description(&client_update_time_path, &err),
+            })?
+            .ok_or_else(|| ChannelError::ProcessedTimeNotFound {
+                client_id: client_id.clone(),
+                height: *height,
+            })?)
+    }
+
+    fn client_update_height(
+        &self,
+        client_id: &ClientId,
+        height: &Height,
+    ) -> Result<Height, ContextError> {
+        let client_update_height_path = ClientUpdateHeightPath(client_id.clone(), *height);
+        Ok(self
+            .state
+            .get(&client_update_height_path)
+            .map_err(|err| ChannelError::Other {
+                description: read_err_description(&client_update_height_path, &err),
+            })?
+            .ok_or_else(|| ChannelError::ProcessedHeightNotFound {
+                client_id: client_id.clone(),
+                height: *height,
+            })?)
+    }
+
+    fn channel_counter(&self) -> Result<u64, ContextError> {
+        Ok(self.metadata.channel_id_counter)
+    }
+
+    fn max_expected_time_per_block(&self) -> Duration {
+        self.max_expected_time_per_block
+    }
+
+    fn validate_message_signer(&self, signer: &Signer) -> Result<(), ContextError> {
+        Pubkey::from_str(signer.as_ref()).map_err(|err| ClientError::InvalidSigner {
+            reason: err.to_string(),
+        })?;
+        Ok(())
+    }
+}
+
+impl<'a> Router for IbcHandler<'a> {
+    fn get_route(&self, module_id: &ModuleId) -> Option<&dyn Module> {
+        if *module_id == ics20_module_id() {
+            return Some(&self.ics20_module);
+        }
+        self.module_by_id.get(module_id).map(|m| &**m)
+    }
+
+    fn get_route_mut(&mut self, module_id: &ModuleId) -> Option<&mut dyn Module> {
+        if *module_id == ics20_module_id() {
+            return Some(&mut self.ics20_module);
+        }
+        // Must be manually expanded due to a compiler bug
+        match self.module_by_id.get_mut(module_id) {
+            Some(m) => Some(&mut **m),
+            None => None,
+        }
+    }
+
+    fn has_route(&self, module_id: &ModuleId) -> bool {
+        *module_id == ics20_module_id() || self.module_by_id.contains_key(module_id)
+    }
+
+    fn lookup_module_by_port(&self, port_id: &PortId) -> Option<ModuleId> {
+        if let Some((owned_port_id, owner)) = &self.channel_owner_route {
+            if owned_port_id == port_id {
+                return Some(owner.clone());
+            }
+        }
+        self.module_by_port(port_id)
+            .map_err(|err| {
+                bounded_log::msg_trunc(
+                    "could not look up the module of the port",
+                    err,
+                    MAX_TERSE_LOG_LEN,
+                )
+            })
+            .ok()
+            .flatten()
+    }
+}
+
+impl<'a> IbcHandler<'a> {
+    /// The module that serves `port_id`. The `transfer` port is served by the
+    /// built-in ICS-20 module unless a program bound it before the port was
+    /// reserved; such a binding stands until it is released.
+    fn module_by_port(&self, port_id: &PortId) -> anyhow::Result<Option<ModuleId>> {
+        // Most lookups are for unbound ports, which need nothing decoded
+        if !self.state.contains(&PortPath(port_id.clone()))? {
+            return Ok((*port_id == PortId::transfer()).then(ics20_module_id));
+        }
+        self.bound_module(port_id)
+    }
+
+    /// The module bound to `port_id`, which is checked to name a program so that
+    /// a malformed stored value fails the lookup instead of a later dispatch.
+    fn bound_module(&self, port_id: &PortId) -> anyhow::Result<Option<ModuleId>> {
+        let port_path = PortPath(port_id.clone());
+        let module_id = self
+            .state
+            .get(&port_path)
+            .map_err(|err| anyhow!(read_err_description(&port_path, &err)))?;
+        if let Some(module_id) = &module_id {
+            EclipseModuleId::try_from(module_id)?;
+        }
+        Ok(module_id)
+    }
+
+    /// Records the module bound to the channel's port as the channel's owner,
+    /// unless one is already recorded.
+    fn record_channel_owner(&mut self, owner_path: ChannelOwnerPath) -> Result<(), ChannelError> {
+        let has_owner = self
+            .state
+            .contains(&owner_path)
+            .map_err(|err| ChannelError::Other {
+                description: read_err_description(&owner_path, &err),
+            })?;
+        if has_owner {
+            return Ok(());
+        }
+        let module_id = self
+            .module_by_port(&owner_path.0)
+            .map_err(|err| ChannelError::Other {
+                description: err.to_string(),
+            })?;
+        if let Some(module_id) = module_id {
+            self.state.set(&owner_path, module_id);
+        }
+        Ok(())
+    }
+
+    /// Routes the callbacks of the existing channel that `envelope` is for to
+    /// the module that opened it rather than to the module now bound to its
+    /// port. Channels opened before owners were recorded are left to the port
+    /// binding.
+    pub(super) fn route_to_channel_owner(&mut self, envelope: &MsgEnvelope) -> anyhow::Result<()> {
+        let (port_id, channel_id) = match existing_channel(envelope)? {
+            Some(channel) => channel,
+            None => {
+                self.channel_owner_route = None;
+                return Ok(());
+            }
+        };
+        let owner = self
+            .state
+            .get(&ChannelOwnerPath(port_id.clone(), channel_id))?;
+        self.channel_owner_route = owner.map(|owner| (port_id, owner));
+        Ok(())
+    }
+
+    /// Why the module whose callbacks a channel or packet message would invoke
+    /// cannot be sent them, if it shares no callback version with the program.
+    /// Must run after [`Self::route_to_channel_owner`]. The first time a module
+    /// is routed to, it is asked which versions it supports and the answer is
+    /// recorded, so that its callbacks are sent in the highest version both
+    /// support from then on.
+    pub(super) fn callback_version_mismatch(
+        &mut self,
+        envelope: &MsgEnvelope,
+    ) -> anyhow::Result<Option<String>> {
+        let port_id = match envelope {
+            MsgEnvelope::Channel(ChannelMsg::OpenInit(msg)) => {
+                let RawMsgChannelOpenInit { port_id, .. } = msg.clone().into();
+                port_id.parse()?
+            }
+            MsgEnvelope::Channel(ChannelMsg::OpenTry(msg)) => {
+                let RawMsgChannelOpenTry { port_id, .. } = msg.clone().into();
+                port_id.parse()?
+            }
+            _ => match existing_channel(envelope)? {
+                Some((port_id, _)) => port_id,
+                None => return Ok(None),
+            },
+        };
+        // The ICS-20 module is built in, and unbound ports are left to ibc-rs
+        let module_id = match self.lookup_module_by_port(&port_id) {
+            Some(module_id) if self.module_by_id.contains_key(&module_id) => module_id,
+            _ => return Ok(None),
+        };
+
+        let versions_path = ModuleCallbackVersionsPath(module_id.clone());
+        let module_versions = match self.state.get(&versions_path)? {
+            Some(module_versions) => module_versions,
+            None => {
+                let module = SolanaModule::load(
+                    &self.state,
+                    &module_id,
+                    &self.instruction_accounts,
+                    self.skipped_callbacks.clone(),
+                )?;
+                let module_versions = module.query_callback_versions(&port_id);
+                // A skipped query says nothing about the module, so is not recorded
+                if self.skipped_callbacks.is_none() {
+                    self.state.set(&versions_path, module_versions.clone());
+                }
+                let module = SolanaModule {
+                    callback_version: module_versions
+                        .highest_common(&SUPPORTED_CALLBACK_VERSIONS)
+                        .unwrap_or(LEGACY_CALLBACK_VERSION),
+                    ..module
+                };
+                self.module_by_id
+                    .insert(module_id.clone(), module.into_box());
+                module_versions
+            }
+        };
+
+        if module_versions
+            .highest_common(&SUPPORTED_CALLBACK_VERSIONS)
+            .is_some()
+        {
+            return Ok(None);
+        }
+        Ok(Some(format!(
+            "NoCommonCallbackVersion {{ module_id: {module_id}, port_id: {port_id}, module_versions: {:?}, supported_versions: {:?} }}",
+            module_versions.versions, SUPPORTED_CALLBACK_VERSIONS,
+        )))
+    }
+
+    /// Channels on `port_id` opened by `module_id` that are not closed, which
+    /// still need the module's callbacks.
+    fn open_owned_channels(
+        &self,
+        port_id: &PortId,
+        module_id: &ModuleId,
+    ) -> anyhow::Result<Vec<ChannelId>> {
+        let mut channel_ids = vec![];
+        for channel_id in self.owned_channels(port_id, module_id)? {
+            let channel = self
+                .state
+                .get_raw(&ChannelEndPath::new(port_id, &channel_id))?;
+            if channel.map_or(false, |channel| {
+                channel.state != RawChannelState::Closed as i32
+            }) {
+                channel_ids.push(channel_id);
+            }
+        }
+        Ok(channel_ids)
+    }
+
+    /// Channels on `port_id` recorded as opened by `module_id`.
+    fn owned_channels(
+        &self,
+        port_id: &PortId,
+        module_id: &ModuleId,
+    ) -> anyhow::Result<Vec<ChannelId>> {
+        let prefix = format!("internal/channelOwners/ports/{port_id}/channels/");
+        let mut channel_ids = vec![];
+        for path in self.state.paths_with_prefix(&prefix)? {
+            let channel_id = match path.strip_prefix(&prefix) {
+                None => continue,
+                Some(channel_id) => channel_id.parse::<ChannelId>()?,
+            };
+            let owner = self
+                .state
+                .get(&ChannelOwnerPath(port_id.clone(), channel_id.clone()))?;
+            if owner.as_ref() == Some(module_id) {
+                channel_ids.push(channel_id);
+            }
+        }
+        Ok(channel_ids)
+    }
+
+    /// Drops the module from those that can be routed to, along with its
+    /// account templates and callback versions.
+    fn remove_module(&mut self, module_id: &ModuleId) -> Result<(), PortError> {
+        self.state
+            .remove(&ModuleAccountTemplatesPath(module_id.clone()));
+        self.state
+            .remove(&ModuleCallbackVersionsPath(module_id.clone()));
+        self.state
+            .update(&AllModulesPath, |all_module_ids| {
+                all_module_ids.modules.remove(module_id);
+            })
+            .map_err(|_err| PortError::ImplementationSpecific)
+    }
+
+    /// Binds `port_id` to the module with program ID `pubkey`, replacing the
+    /// module's account templates. The `transfer` port is reserved for the
+    /// built-in ICS-20 module, whose vouchers a program could otherwise mint.
+    pub(super) fn bind_port(
+        &mut self,
+        port_id: &PortId,
+        pubkey: &Pubkey,
+        account_templates: ModuleAccountTemplates,
+    ) -> Result<(), PortError> {
+        if *port_id == PortId::transfer() {
+            return Err(PortError::ImplementationSpecific);
+        }
+        let port_path = PortPath(port_id.clone());
+        let module_id = EclipseModuleId::from_pubkey(*pubkey).to_module_id();
+        // A port holding any value is bound, even one that fails to decode
+        let is_bound = self
+            .state
+            .contains(&port_path)
+            .map_err(|_err| PortError::ImplementationSpecific)?;
+        if !is_bound {
+            self.state.set(&port_path, module_id.clone());
+            let account_templates_path = ModuleAccountTemplatesPath(module_id.clone());
+            if account_templates.templates.is_empty() {
+                self.state.remove(&account_templates_path);
+            } else {
+                self.state.set(&account_templates_path, account_templates);
+            }
+            // The module may have been upgraded since it last bound a port
+            self.state
+                .remove(&ModuleCallbackVersionsPath(module_id.clone()));
+            self.state
+                .update(&AllModulesPath, |all_module_ids| {
+                    all_module_ids.modules.insert(module_id);
+                })
+                .map_err(|_err| PortError::ImplementationSpecific)?;
+
+            Ok(())
+        } else {
+            Err(PortError::ImplementationSpecific)
+        }
+    }
+
+    /// Unbinds `port_id` from the module with program ID `pubkey`, which must be
+    /// bound to it. Refused while channels the module opened on the port are
+    /// not closed, since their callbacks would have no module left to reach;
+    /// the admin can still force the release.
+    pub(super) fn release_port(
+        &mut self,
+        port_id: &PortId,
+        pubkey: &Pubkey,
+    ) -> Result<(), PortError> {
+        let port_path = PortPath(port_id.clone());
+        let module_id = EclipseModuleId::from_pubkey(*pubkey).to_module_id();
+        let curr_module_id = self
+            .bound_module(port_id)
+            .map_err(|_err| PortError::ImplementationSpecific)?;
+        match curr_module_id {
+            Some(curr_module_id) => {
+                if module_id == curr_module_id {
+                    let open_channel_ids = self
+                        .open_owned_channels(port_id, &module_id)
+                        .map_err(|_err| PortError::ImplementationSpecific)?;
+                    if !open_channel_ids.is_empty() {
+                        bounded_log::msg_trunc(
+                            "port release refused",
+                            format_args!(
+                                "{port_id} has open channels {} of module {module_id}",
+                                open_channel_ids
+                                    .iter()
+                                    .map(ToString::to_string)
+                                    .collect::<Vec<_>>()
+                                    .join(",")
+                            ),
+                            MAX_TERSE_LOG_LEN,
+                        );
+                        return Err(PortError::ImplementationSpecific);
+                    }
+                    self.state.remove(&port_path);
+                    self.remove_module(&module_id)
+                } else {
+                    Err(PortError::ImplementationSpecific)
+                }
+            }
+            None => Err(PortError::UnknownPort {
+                port_id: port_id.clone(),
+            }),
+        }
+    }
+
+    /// Unbinds `port_id` from whichever module is bound to it, even if the
+    /// module still has open channels on it. The module is kept routable for
+    /// the callbacks of those channels.
+    pub(super) fn force_release_port(&mut self, port_id: &PortId) -> Result<(), PortError> {
+        let module_id = self
+            .bound_module(port_id)
+            .map_err(|_err| PortError::ImplementationSpecific)?
+            .ok_or_else(|| PortError::UnknownPort {
+                port_id: port_id.clone(),
+            })?;
+        let open_channel_ids = self
+            .open_owned_channels(port_id, &module_id)
+            .map_err(|_err| PortError::ImplementationSpecific)?;
+        self.state.remove(&PortPath(port_id.clone()));
+        if open_channel_ids.is_empty() {
+            self.remove_module(&module_id)?;
+        }
+        Ok(())
+    }
+
+    /// Transfers `port_id` from the module with program ID `pubkey`, which must
+    /// be bound to it, to the module with program ID `new_module_pubkey`. The
+    /// module's account templates and the channels it opened on the port move
+    /// with the port, so the new module must not already be bound to a port.
+    pub(super) fn transfer_port(
+        &mut self,
+        port_id: &PortId,
+        pubkey: &Pubkey,
+        new_module_pubkey: &Pubkey,
+    ) -> Result<(), PortError> {
+        let port_path = PortPath(port_id.clone());
+        let module_id = EclipseModuleId::from_pubkey(*pubkey).to_module_id();
+        let new_module_id = EclipseModuleId::from_pubkey(*new_module_pubkey).to_module_id();
+        let curr_module_id = self
+            .bound_module(port_id)
+            .map_err(|_err| PortError::ImplementationSpecific)?
+            .ok_or_else(|| PortError::UnknownPort {
+                port_id: port_id.clone(),
+            })?;
+        if curr_module_id != module_id {
+            return Err(PortError::ImplementationSpecific);
+        }
+        let all_module_ids = self
+            .state
+            .get_or_default(&AllModulesPath)
+            .map_err(|_err| PortError::ImplementationSpecific)?;
+        if all_module_ids.modules.contains(&new_module_id) {
+            return Err(PortError::ImplementationSpecific);
+        }
+
+        self.state.set(&port_path, new_module_id.clone());
+        let account_templates = self
+            .state
+            .get(&ModuleAccountTemplatesPath(module_id.clone()))
+            .map_err(|_err| PortError::ImplementationSpecific)?;
+        if let Some(account_templates) = account_templates {
+            self.state
+                .remove(&ModuleAccountTemplatesPath(module_id.clone()));
+            self.state.set(
+                &ModuleAccountTemplatesPath(new_module_id.clone()),
+                account_templates,
+            );
+        }
+        // The new module is asked for its own callback versions
+        self.state
+            .remove(&ModuleCallbackVersionsPath(module_id.clone()));
+        let owned_channel_ids = self
+            .owned_channels(port_id, &module_id)
+            .map_err(|_err| PortError::ImplementationSpecific)?;
+        for channel_id in owned_channel_ids {
+            self.state.set(
+                &ChannelOwnerPath(port_id.clone(), channel_id),
+                new_module_id.clone(),
+            );
+        }
+        self.state
+            .update(&AllModulesPath, |all_module_ids| {
+                all_module_ids.modules.remove(&module_id);
+                all_module_ids.modules.insert(new_module_id);
+            })
+            .map_err(|_err| PortError::ImplementationSpecific)?;
+
+        Ok(())
+    }
+}
+
+impl<'a> IbcHandler<'a> {
+    /// Sends the tokens in `msg` over ICS-20 from `payer`, which must be the
+    /// sender. The tokens are taken from the sender's balance in the built-in
+    /// ICS-20 module before the packet is sent.
+    pub(super) fn transfer(&mut self, msg: MsgTransfer, payer: &Pubkey) -> anyhow::Result<()> {
+        let MsgTransfer {
+            port_id_on_a,
+            chan_id_on_a,
+            packet_data,
+            timeout_height_on_b,
+            timeout_timestamp_on_b,
+        } = msg;
+        if packet_data.sender.as_ref() != payer.to_string() {
+            return Err(anyhow!(
+                "transfer sender {} is not the payer {payer}",
+                packet_data.sender
+            ));
+        }
+        if timeout_timestamp_on_b.nanoseconds() > MAX_TIMESTAMP_NANOS {
+            return Err(anyhow!(
+                "transfer timeout {timeout_timestamp_on_b} is after the latest supported \
+                 timestamp"
+            ));
+        }
+        if self.module_by_port(&port_id_on_a)? != Some(ics20_module_id()) {
+            return Err(anyhow!(
+                "port {port_id_on_a} is not served by the ICS-20 module"
+            ));
+        }
+
+        let channel_end = self
+            .channel_end(&ChannelEndPath::new(&port_id_on_a, &chan_id_on_a))
+            .map_err(|err| anyhow!("{err}"))?;
+        let counterparty = channel_end.counterparty();
+        let chan_id_on_b = counterparty.channel_id().cloned().ok_or_else(|| {
+            anyhow!("channel {port_id_on_a}/{chan_id_on_a} has no counterparty channel")
+        })?;
+        let seq_on_a = self
+            .get_next_sequence_send(&SeqSendPath::new(&port_id_on_a, &chan_id_on_a))
+            .map_err(|err| anyhow!("{err}"))?;
+
+        self.ics20_module
+            .send_transfer(&port_id_on_a, &chan_id_on_a, &packet_data)?;
+
+        let packet = Packet {
+            seq_on_a,
+            port_id_on_a,
+            chan_id_on_a,
+            port_id_on_b: counterparty.port_id().clone(),
+            chan_id_on_b,
+            data: serde_json::to_vec(&packet_data)?,
+            timeout_height_on_b,
+            timeout_timestamp_on_b,
+        };
+        send_packet(self, packet).map_err(|err| anyhow!("{err}"))
+    }
+}
+
+/// ICS-4 does not allow empty acknowledgements, but modules return theirs as
+/// bincode, which does not enforce that. An empty one is replaced with an error
+/// acknowledgement so that the committed acknowledgement is always one that
+/// relayers can relay.
+fn validated_ack(acknowledgement: Acknowledgement) -> Acknowledgement {
+    if acknowledgement.as_ref().is_empty() {
+        msg!("module returned an empty acknowledgement, writing an error acknowledgement");
+        error_ack("empty acknowledgement")
+    } else {
+        acknowledgement
+    }
+}
+
+#[derive(Debug)]
+struct SolanaModule {
+    program_id: Pubkey,
+    account_templates: ModuleAccountTemplates,
+    instruction_accounts: Vec<AccountMeta>,
+    /// Set when the handler runs off-chain, where module programs cannot be
+    /// invoked, to record the validation callbacks skipped instead
+    skipped_callbacks: Option<SkippedCallbacks>,
+    /// Version of the encoding callbacks are sent in. Channel messages for a
+    /// module that shares no version with the program are refused before any
+    /// callback is sent (see `IbcHandler::callback_version_mismatch`).
+    callback_version: u32,
+}
+
+impl Module for SolanaModule {
+    fn on_chan_open_init_validate(
+        &self,
+        order: Order,
+        connection_hops: &[ConnectionId],
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        version: &Version,
+    ) -> Result<Version, ChannelError> {
+        if self.skips_callback("on_chan_open_init_validate", port_id) {
+            return Ok(version.clone());
+        }
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnChanOpenInitValidate(OnChanOpenInitValidate {
+                order,
+                connection_hops: connection_hops.to_vec(),
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+                counterparty: counterparty.clone(),
+                version: version.clone(),
+            });
+        let accounts = self.callback_accounts(port_id, channel_id, None);
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })?;
+
+        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
+            description: "Return data missing".to_owned(),
+        })?;
+
+        bincode::deserialize(&return_data).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })
+    }
+
+    fn on_chan_open_init_execute(
+        &mut self,
+        order: Order,
+        connection_hops: &[ConnectionId],
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        version: &Version,
+    ) -> Result<(ModuleExtras, Version), ChannelError> {
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnChanOpenInitExecute(OnChanOpenInitExecute {
+                order,
+                connection_hops: connection_hops.to_vec(),
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+                counterparty: counterparty.clone(),
+                version: version.clone(),
+            });
+        let accounts = self.callback_accounts(port_id, channel_id, None);
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })?;
+
+        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
+            description: "Return data missing".to_owned(),
+        })?;
+
+        bincode::deserialize::<(SerializableModuleExtras, Version)>(&return_data)
+            .map(|(extras, version)| (extras.into(), version))
+            .map_err(|err| ChannelError::Other {
+                description: err.to_string(),
+            })
+    }
+
+    fn on_chan_open_try_validate(
+        &self,
+        order: Order,
+        connection_hops: &[ConnectionId],
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        counterparty_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        if self.skips_callback("on_chan_open_try_validate", port_id) {
+            return Ok(counterparty_version.clone());
+        }
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnChanOpenTryValidate(OnChanOpenTryValidate {
+                order,
+                connection_hops: connection_hops.to_vec(),
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+                counterparty: counterparty.clone(),
+                counterparty_version: counterparty_version.clone(),
+            });
+        let accounts = self.callback_accounts(port_id, channel_id, None);
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })?;
+
+        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
+            description: "Return data missing".to_owned(),
+        })?;
+
+        bincode::deserialize(&return_data).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })
+    }
+
+    fn on_chan_open_try_execute(
+        &mut self,
+        order: Order,
+        connection_hops: &[ConnectionId],
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty: &Counterparty,
+        counterparty_version: &Version,
+    ) -> Result<(ModuleExtras, Version), ChannelError> {
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnChanOpenTryExecute(OnChanOpenTryExecute {
+                order,
+                connection_hops: connection_hops.to_vec(),
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+                counterparty: counterparty.clone(),
+                counterparty_version: counterparty_version.clone(),
+            });
+        let accounts = self.callback_accounts(port_id, channel_id, None);
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })?;
+
+        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
+            description: "Return data missing".to_owned(),
+        })?;
+
+        bincode::deserialize::<(SerializableModuleExtras, Version)>(&return_data)
+            .map(|(extras, version)| (extras.into(), version))
+            .map_err(|err| ChannelError::Other {
+                description: err.to_string(),
+            })
+    }
+
+    fn on_chan_open_ack_validate(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty_version: &Version,
+    ) -> Result<(), ChannelError> {
+        if self.skips_callback("on_chan_open_ack_validate", port_id) {
+            return Ok(());
+        }
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnChanOpenAckValidate(OnChanOpenAckValidate {
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+                counterparty_version: counterparty_version.clone(),
+            });
+        let accounts = self.callback_accounts(port_id, channel_id, None);
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })?;
+
+        Ok(())
+    }
+
+    fn on_chan_open_ack_execute(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        counterparty_version: &Version,
+    ) -> Result<ModuleExtras, ChannelError> {
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnChanOpenAckExecute(OnChanOpenAckExecute {
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+                counterparty_version: counterparty_version.clone(),
+            });
+        let accounts = self.callback_accounts(port_id, channel_id, None);
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })?;
+
+        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
+            description: "Return data missing".to_owned(),
+        })?;
+
+        bincode::deserialize::<SerializableModuleExtras>(&return_data)
+            .map(ModuleExtras::from)
+            .map_err(|err| ChannelError::Other {
+                description: err.to_string(),
+            })
+    }
+
+    fn on_chan_open_confirm_validate(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        if self.skips_callback("on_chan_open_confirm_validate", port_id) {
+            return Ok(());
+        }
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnChanOpenConfirmValidate(OnChanOpenConfirmValidate {
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+            });
+        let accounts = self.callback_accounts(port_id, channel_id, None);
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })?;
+
+        Ok(())
+    }
+
+    fn on_chan_open_confirm_execute(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<ModuleExtras, ChannelError> {
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnChanOpenConfirmExecute(OnChanOpenConfirmExecute {
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+            });
+        let accounts = self.callback_accounts(port_id, channel_id, None);
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })?;
+
+        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
+            description: "Return data missing".to_owned(),
+        })?;
+
+        bincode::deserialize::<SerializableModuleExtras>(&return_data)
+            .map(ModuleExtras::from)
+            .map_err(|err| ChannelError::Other {
+                description: err.to_string(),
+            })
+    }
+
+    fn on_chan_close_init_validate(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        if self.skips_callback("on_chan_close_init_validate", port_id) {
+            return Ok(());
+        }
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnChanCloseInitValidate(OnChanCloseInitValidate {
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+            });
+        let accounts = self.callback_accounts(port_id, channel_id, None);
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })?;
+
+        Ok(())
+    }
+
+    fn on_chan_close_init_execute(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<ModuleExtras, ChannelError> {
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnChanCloseInitExecute(OnChanCloseInitExecute {
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+            });
+        let accounts = self.callback_accounts(port_id, channel_id, None);
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })?;
+
+        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
+            description: "Return data missing".to_owned(),
+        })?;
+
+        bincode::deserialize::<SerializableModuleExtras>(&return_data)
+            .map(ModuleExtras::from)
+            .map_err(|err| ChannelError::Other {
+                description: err.to_string(),
+            })
+    }
+
+    fn on_chan_close_confirm_validate(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        if self.skips_callback("on_chan_close_confirm_validate", port_id) {
+            return Ok(());
+        }
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnChanCloseConfirmValidate(OnChanCloseConfirmValidate {
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+            });
+        let accounts = self.callback_accounts(port_id, channel_id, None);
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })?;
+
+        Ok(())
+    }
+
+    fn on_chan_close_confirm_execute(
+        &mut self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<ModuleExtras, ChannelError> {
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnChanCloseConfirmExecute(OnChanCloseConfirmExecute {
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+            });
+        let accounts = self.callback_accounts(port_id, channel_id, None);
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
+            description: err.to_string(),
+        })?;
+
+        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
+            description: "Return data missing".to_owned(),
+        })?;
+
+        bincode::deserialize::<SerializableModuleExtras>(&return_data)
+            .map(ModuleExtras::from)
+            .map_err(|err| ChannelError::Other {
+                description: err.to_string(),
+            })
+    }
+
+    fn on_recv_packet_execute(
+        &mut self,
+        packet: &Packet,
+        relayer: &Signer,
+    ) -> (ModuleExtras, Acknowledgement) {
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnRecvPacketExecute(OnRecvPacketExecute {
+                packet: packet.clone(),
+                relayer: relayer.clone(),
+            });
+        let accounts = self.callback_accounts(
+            &packet.port_id_on_b,
+            &packet.chan_id_on_b,
+            Some(packet.seq_on_a),
+        );
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        // A module that fails to process the packet must still acknowledge it, so
+        // that the sender can tell it was not delivered
+        let returned = invoke(&instruction, &[])
+            .map_err(|err| format!("module failed to receive the packet: {err}"))
+            .and_then(|()| {
+                let (_, return_data) = get_return_data()
+                    .ok_or_else(|| "module returned no acknowledgement".to_owned())?;
+                bincode::deserialize::<(SerializableModuleExtras, Acknowledgement)>(&return_data)
+                    .map_err(|err| format!("module returned an undecodable acknowledgement: {err}"))
+            });
+        match returned {
+            Ok((extras, acknowledgement)) => (extras.into(), validated_ack(acknowledgement)),
+            Err(description) => {
+                bounded_log::msg_trunc(
+                    "writing an error acknowledgement",
+                    &description,
+                    MAX_TERSE_LOG_LEN,
+                );
+                (ModuleExtras::empty(), error_ack(description))
+            }
+        }
+    }
+
+    fn on_acknowledgement_packet_validate(
+        &self,
+        packet: &Packet,
+        acknowledgement: &Acknowledgement,
+        relayer: &Signer,
+    ) -> Result<(), PacketError> {
+        if self.skips_callback("on_acknowledgement_packet_validate", &packet.port_id_on_a) {
+            return Ok(());
+        }
+        let ibc_module_instruction = IbcModuleInstruction::OnAcknowledgementPacketValidate(
+            OnAcknowledgementPacketValidate {
+                packet: packet.clone(),
+                acknowledgement: acknowledgement.clone(),
+                relayer: relayer.clone(),
+            },
+        );
+        let accounts = self.callback_accounts(
+            &packet.port_id_on_a,
+            &packet.chan_id_on_a,
+            Some(packet.seq_on_a),
+        );
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|_err| {
+            // TODO: Fix the IBC library to include an error message
+            PacketError::ImplementationSpecific
+        })?;
+
+        Ok(())
+    }
+
+    fn on_acknowledgement_packet_execute(
+        &mut self,
+        packet: &Packet,
+        acknowledgement: &Acknowledgement,
+        relayer: &Signer,
+    ) -> (ModuleExtras, Result<(), PacketError>) {
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnAcknowledgementPacketExecute(OnAcknowledgementPacketExecute {
+                packet: packet.clone(),
+                acknowledgement: acknowledgement.clone(),
+                relayer: relayer.clone(),
+            });
+        let accounts = self.callback_accounts(
+            &packet.port_id_on_a,
+            &packet.chan_id_on_a,
+            Some(packet.seq_on_a),
+        );
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        let result = invoke(&instruction, &[]).map_err(|_err| {
+            // TODO: Fix the IBC library to include an error message
+            PacketError::ImplementationSpecific
+        });
+
+        (self.returned_extras(), result)
+    }
+
+    fn on_timeout_packet_validate(
+        &self,
+        packet: &Packet,
+        relayer: &Signer,
+    ) -> Result<(), PacketError> {
+        if self.skips_callback("on_timeout_packet_validate", &packet.port_id_on_a) {
+            return Ok(());
+        }
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnTimeoutPacketValidate(OnTimeoutPacketValidate {
+                packet: packet.clone(),
+                relayer: relayer.clone(),
+            });
+        let accounts = self.callback_accounts(
+            &packet.port_id_on_a,
+            &packet.chan_id_on_a,
+            Some(packet.seq_on_a),
+        );
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        invoke(&instruction, &[]).map_err(|_err| {
+            // TODO: Fix the IBC library to include an error message
+            PacketError::ImplementationSpecific
+        })?;
+
+        Ok(())
+    }
+
+    fn on_timeout_packet_execute(
+        &mut self,
+        packet: &Packet,
+        relayer: &Signer,
+    ) -> (ModuleExtras, Result<(), PacketError>) {
+        let ibc_module_instruction =
+            IbcModuleInstruction::OnTimeoutPacketExecute(OnTimeoutPacketExecute {
+                packet: packet.clone(),
+                relayer: relayer.clone(),
+            });
+        let accounts = self.callback_accounts(
+            &packet.port_id_on_a,
+            &packet.chan_id_on_a,
+            Some(packet.seq_on_a),
+        );
+        let instruction = self.callback_instruction(&ibc_module_instruction, accounts);
+
+        let result = invoke(&instruction, &[]).map_err(|_err| {
+            // TODO: Fix the IBC library to include an error message
+            PacketError::ImplementationSpecific
+        });
+
+        (self.returned_extras(), result)
+    }
+}
+
+impl SolanaModule {
+    /// The module `module_id` as recorded in `state`. Modules whose callback
+    /// versions are not yet known are sent legacy callbacks.
+    fn load(
+        state: &IbcState<'_>,
+        module_id: &ModuleId,
+        instruction_accounts: &[AccountMeta],
+        skipped_callbacks: Option<SkippedCallbacks>,
+    ) -> anyhow::Result<Self> {
+        let program_id = EclipseModuleId::try_from(module_id)?.program_id();
+        let account_templates =
+            state.get_or_default(&ModuleAccountTemplatesPath(module_id.clone()))?;
+        let callback_version = state
+            .get(&ModuleCallbackVersionsPath(module_id.clone()))?
+            .and_then(|module_versions| {
+                module_versions.highest_common(&SUPPORTED_CALLBACK_VERSIONS)
+            })
+            .unwrap_or(LEGACY_CALLBACK_VERSION);
+        Ok(Self {
+            program_id,
+            account_templates,
+            instruction_accounts: instruction_accounts.to_vec(),
+            skipped_callbacks,
+            callback_version,
+        })
+    }
+
+    fn into_box(self) -> Box<dyn Module> {
+        Box::new(self)
+    }
+
+    /// The instruction invoking the module with `ibc_module_instruction`, encoded
+    /// in the module's callback version.
+    fn callback_instruction(
+        &self,
+        ibc_module_instruction: &IbcModuleInstruction,
+        accounts: Vec<AccountMeta>,
+    ) -> Instruction {
+        let data = encode_callback(self.callback_version, ibc_module_instruction)
+            .expect("module instructions always encode");
+        Instruction::new_with_bytes(self.program_id, &data, accounts)
+    }
+
+    /// Asks the module which callback versions it supports. Modules that fail the
+    /// query or answer with something other than a list of versions predate it,
+    /// and only support the legacy version. Off-chain the query is skipped and
+    /// the legacy version assumed.
+    fn query_callback_versions(&self, port_id: &PortId) -> CallbackVersions {
+        let legacy = CallbackVersions {
+            versions: vec![LEGACY_CALLBACK_VERSION],
+        };
+        if self.skips_callback("supported_callback_versions", port_id) {
+            return legacy;
+        }
+        let instruction = Instruction::new_with_bincode(
+            self.program_id,
+            &IbcModuleInstruction::SupportedCallbackVersions,
+            vec![],
+        );
+        if invoke(&instruction, &[]).is_err() {
+            return legacy;
+        }
+        match get_return_data() {
+            Some((program_id, return_data)) if program_id == self.program_id => {
+                bincode::deserialize::<Vec<u32>>(&return_data)
+                    .map_or(legacy, |versions| CallbackVersions { versions })
+            }
+            _ => legacy,
+        }
+    }
+
+    /// Whether `callback` is skipped because the handler runs off-chain, in which
+    /// case the skip is recorded.
+    fn skips_callback(&self, callback: &str, port_id: &PortId) -> bool {
+        match &self.skipped_callbacks {
+            Some(skipped_callbacks) => {
+                skipped_callbacks
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(format!(
+                        "{callback} of module {} bound to port {port_id}",
+                        self.program_id
+                    ));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The extras a packet callback returned, which ibc-rs then emits and logs
+    /// through the handler. Modules that set no return data of their own have
+    /// none, and extras that fail to decode are logged and dropped rather than
+    /// failing a callback that already ran.
+    fn returned_extras(&self) -> ModuleExtras {
+        let return_data = match get_return_data() {
+            Some((program_id, return_data))
+                if program_id == self.program_id && !return_data.is_empty() =>
+            {
+                return_data
+            }
+            _ => return ModuleExtras::empty(),
+        };
+        match bincode::deserialize::<SerializableModuleExtras>(&return_data) {
+            Ok(extras) => extras.into(),
+            Err(err) => {
+                bounded_log::msg_trunc("could not decode module extras", err, MAX_TERSE_LOG_LEN);
+                ModuleExtras::empty()
+            }
+        }
+    }
+
+    /// Derives the module's PDAs from its account templates and returns those that
+    /// were passed to the IBC instruction. They are never marked as signers, since
+    /// only the module's own program can sign for them.
+    fn callback_accounts(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+        sequence: Option<Sequence>,
+    ) -> Vec<AccountMeta> {
+        self.account_templates
+            .templates
+            .iter()
+            .filter_map(|template| template.resolve(port_id, channel_id, sequence))
+            .filter_map(|seeds| {
+                let seeds = seeds.iter().map(Vec::as_slice).collect::<Vec<_>>();
+                Pubkey::try_find_program_address(&seeds, &self.program_id)
+            })
+            .filter_map(|(pubkey, _bump_seed)| {
+                self.instruction_accounts
+                    .iter()
+                    .find(|account| account.pubkey == pubkey)
+            })
+            .map(|account| AccountMeta {
+                pubkey: account.pubkey,
+                is_signer: false,
+                is_writable: account.is_writable,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            ibc_events::UpdateClientPayload,
+            ibc_instruction::msgs::{MsgBindPort, StoreWrite},
+        },
+        core::{cell::RefCell, fmt, marker::PhantomData},
+        eclipse_ibc_extra_types::{PdaSeedTemplate, SeedPart},
+        eclipse_ibc_fixtures::clients,
+        eclipse_ibc_known_proto::{KnownAnyProto, KnownProto},
+        eclipse_ibc_light_client::{
+            eclipse_chain::EclipseHeight, EclipseHeader, EclipseMisbehaviour,
+            ECLIPSE_HEADER_BATCH_TYPE_URL, ECLIPSE_HEADER_TYPE_URL, LEGACY_TYPE_URLS,
+            MAX_HEADERS_PER_BATCH,
+        },
+        eclipse_ibc_proto::eclipse::ibc::chain::v1::{
+            ClientState as RawEclipseClientState, HeaderBatch as RawEclipseHeaderBatch,
+        },
+        eclipse_ibc_state::{
+            internal_path::{Ics20BalancePath, StateInitializedPath},
+            ChangeKind,
+        },
+        ibc::{
+            clients::ics07_tendermint::consensus_state::ConsensusState as TendermintConsensusState,
+            core::{
+                dispatch,
+                ics02_client::{client_type::ClientType, events::UpdateClient},
+                ics04_channel::timeout::TimeoutHeight,
+                ics23_commitment::commitment::CommitmentRoot,
+                ics24_host::identifier::ChainId,
+                timestamp::Expiry,
+            },
+        },
+        ibc_proto::{
+            cosmos::base::v1beta1::Coin as RawCoin,
+            ibc::{
+                applications::transfer::v1::MsgTransfer as RawMsgTransfer,
+                core::{
+                    channel::v1::{
+                        Channel as RawChannel, Counterparty as RawChannelCounterparty,
+                        MsgChannelOpenInit as RawMsgChannelOpenInit,
+                        MsgRecvPacket as RawMsgRecvPacket, Order as RawOrder, Packet as RawPacket,
+                        State as RawChannelState,
+                    },
+                    client::v1::Height as RawHeight,
+                    commitment::v1::MerklePrefix as RawMerklePrefix,
+                    connection::v1::{
+                        ConnectionEnd as RawConnectionEnd,
+                        Counterparty as RawConnectionCounterparty, State as RawConnectionState,
+                        Version as RawConnectionVersion,
+                    },
+                },
+                lightclients::tendermint::v1::ConsensusState as RawTmConsensusState,
+            },
+        },
+        prost::Message as _,
+        solana_sdk::{
+            account_info::AccountInfo,
+            entrypoint::ProgramResult,
+            program_error::ProgramError,
+            program_stubs::{set_syscall_stubs, SyscallStubs},
+        },
+        std::{rc::Rc, sync::Once},
+    };
+
+    /// Lets tests plant a value of type `V` under an arbitrary path string, which
+    /// is how corrupt bytes are stored under paths that expect a different type.
+    struct PlantedPath<V>(String, PhantomData<V>);
+
+    impl<V> PlantedPath<V> {
+        fn at(path: &(impl Display + ?Sized)) -> Self {
+            Self(path.to_string(), PhantomData)
+        }
+    }
+
+    impl<V> fmt::Display for PlantedPath<V> {
+        fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            self.0.fmt(fmt)
+        }
+    }
+
+    impl<V: KnownProto> KnownPath for PlantedPath<V> {
+        type Value = V;
+    }
+
+    fn assert_read_err<T>(result: Result<T, ContextError>, path: &impl Display) {
+        let err = result.err().expect("corrupt value should not be readable");
+        match &err {
+            ContextError::ClientError(ClientError::Other { description })
+            | ContextError::ConnectionError(ConnectionError::Other { description })
+            | ContextError::ChannelError(ChannelError::Other { description }) => {
+                assert!(description.contains(&path.to_string()), "{description}");
+            }
+            _ => panic!("expected a read error for {path}, got: {err:?}"),
+        }
+    }
+
+    // Sequences encode as a varint in field 1, and `Any` as length-delimited
+    // bytes in field 1, so each is a wire type mismatch for the other.
+    fn corrupt_sequence() -> Sequence {
+        Sequence::from(5)
+    }
+
+    fn corrupt_any() -> protobuf::Any {
+        protobuf::Any {
+            type_url: "corrupt".to_owned(),
+            value: vec![],
+        }
+    }
+
+    /// A store initialized at version 0, as `MsgInitStorageAccount` leaves it but
+    /// without an admin or chain params.
+    fn initialized_store() -> IbcStore {
+        let store = IbcStore::default();
+        let mut state = IbcState::new(&store, 0);
+        state.set(&StateInitializedPath, ());
+        state.commit().unwrap();
+        store
+    }
+
+    /// A handler at `clock` over `store`, with no accounts for module callbacks.
+    fn new_handler<'a>(
+        store: &'a IbcStore,
+        metadata: &'a mut IbcMetadata,
+        clock: &Clock,
+    ) -> IbcHandler<'a> {
+        IbcHandler::new(store, metadata, clock, &[]).unwrap()
+    }
+
+    /// An initialized store whose chain params were set by an earlier instruction.
+    fn store_with_chain_params(chain_params: ChainParams) -> IbcStore {
+        let store = IbcStore::default();
+        let mut state = IbcState::new(&store, 0);
+        state.set(&StateInitializedPath, ());
+        state.set(&ChainParamsPath, chain_params);
+        state.commit().unwrap();
+        store
+    }
+
+    /// Stores an open connection `connection-0` on `client_id` with a delay period
+    /// of `delay_period`.
+    fn store_delayed_connection(
+        ibc_handler: &mut IbcHandler,
+        client_id: &ClientId,
+        delay_period: Duration,
+    ) {
+        let connection_id = ConnectionId::default();
+        let connection_end = ConnectionEnd::try_from(RawConnectionEnd {
+            client_id: client_id.to_string(),
+            versions: vec![RawConnectionVersion {
+                identifier: "1".to_owned(),
+                features: vec!["ORDER_ORDERED".to_owned(), "ORDER_UNORDERED".to_owned()],
+            }],
+            state: RawConnectionState::Open as i32,
+            counterparty: Some(RawConnectionCounterparty {
+                client_id: client_id.to_string(),
+                connection_id: connection_id.to_string(),
+                prefix: Some(RawMerklePrefix {
+                    key_prefix: b"ibc".to_vec(),
+                }),
+            }),
+            delay_period: delay_period.as_nanos().try_into().unwrap(),
+        })
+        .unwrap();
+        ibc_handler
+            .store_connection(&ConnectionPath::new(&connection_id), connection_end)
+            .unwrap();
+        ibc_handler
+            .store_connection_to_client(&ClientConnectionPath::new(client_id), connection_id)
+            .unwrap();
+    }
+
+    #[test]
+    fn client_updates_are_pruned_once_their_delay_windows_pass() {
+        let store = store_with_chain_params(ChainParams::default());
+        let mut metadata = IbcMetadata::default();
+        let clock = Clock {
+            slot: 1_000,
+            unix_timestamp: 1_700_000_000,
+            ..Clock::default()
+        };
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock);
+
+        // The client's only connection has a delay period of 60s, or 100 slots
+        let client_id = ClientId::default();
+        store_delayed_connection(&mut ibc_handler, &client_id, Duration::from_secs(60));
+
+        // Heights 1 and 2 were processed long ago, the rest within the window
+        let host_nanos = ibc_handler.host_timestamp().unwrap().nanoseconds();
+        let heights = (1..=20)
+            .map(|height| Height::new(0, height).unwrap())
+            .collect::<Vec<_>>();
+        for (index, height) in heights.iter().enumerate() {
+            let (update_time, update_slot) = if index < 2 {
+                (host_nanos - 120_000_000_000, 10)
+            } else {
+                (host_nanos - 30_000_000_000, 990)
+            };
+            ibc_handler
+                .store_update_time(
+                    client_id.clone(),
+                    *height,
+                    Timestamp::from_nanoseconds(update_time).unwrap(),
+                )
+                .unwrap();
+            ibc_handler
+                .store_update_height(
+                    client_id.clone(),
+                    *height,
+                    Height::of_slot(update_slot).unwrap(),
+                )
+                .unwrap();
+        }
+        ibc_handler.commit().unwrap();
+
+        assert_eq!(ibc_handler.prune_client_updates().unwrap(), 2);
+        assert_eq!(ibc_handler.prune_client_updates().unwrap(), 0);
+        ibc_handler.commit().unwrap();
+
+        for height in &heights[..2] {
+            ibc_handler
+                .client_update_time(&client_id, height)
+                .unwrap_err();
+            ibc_handler
+                .client_update_height(&client_id, height)
+                .unwrap_err();
+        }
+        for height in &heights[2..] {
+            ibc_handler.client_update_time(&client_id, height).unwrap();
+            ibc_handler
+                .client_update_height(&client_id, height)
+                .unwrap();
+        }
+        let processed_heights = ibc_handler
+            .state
+            .get(&ProcessedHeightsPath(client_id.clone()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            processed_heights.heights.into_iter().collect::<Vec<_>>(),
+            heights[2..]
+        );
+
+        // Once every window has passed, the latest heights are still kept
+        drop(ibc_handler);
+        let clock = Clock {
+            slot: 2_000,
+            unix_timestamp: 1_700_001_000,
+            ..Clock::default()
+        };
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock);
+        assert_eq!(
+            ibc_handler.prune_client_updates().unwrap(),
+            18 - RETAINED_PROCESSED_HEIGHTS
+        );
+        ibc_handler
+            .client_update_time(&client_id, &heights[20 - RETAINED_PROCESSED_HEIGHTS])
+            .unwrap();
+    }
+
+    #[test]
+    fn oldest_consensus_states_are_evicted_beyond_the_cap() {
+        let store = store_with_chain_params(ChainParams {
+            max_consensus_states_per_client: Some(4),
+            ..ChainParams::default()
+        });
+        let mut metadata = IbcMetadata::default();
+        let clock = |slot| Clock {
+            slot,
+            unix_timestamp: 1_700_000_000,
+            ..Clock::default()
+        };
+        let client_id = ClientId::default();
+        let height = |slot| Height::of_slot(slot).unwrap();
+
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(20));
+        for slot in 1..=10 {
+            let consensus_state = ibc_handler.host_consensus_state(&height(slot)).unwrap();
+            ibc_handler
+                .store_consensus_state(
+                    ClientConsensusStatePath::new(&client_id, &height(slot)),
+                    consensus_state,
+                )
+                .unwrap();
+        }
+        ibc_handler.commit().unwrap();
+
+        let consensus_heights = ibc_handler
+            .state
+            .get(&ConsensusHeightsPath(client_id.clone()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            consensus_heights.heights.into_iter().collect::<Vec<_>>(),
+            (7..=10).map(height).collect::<Vec<_>>()
+        );
+        for slot in 1..=6 {
+            ValidationContext::consensus_state(
+                &ibc_handler,
+                &ClientConsensusStatePath::new(&client_id, &height(slot)),
+            )
+            .unwrap_err();
+        }
+
+        // Neighbours are found within the retained window only
+        let timestamp_at = |slot| {
+            ibc_handler
+                .host_consensus_state(&height(slot))
+                .unwrap()
+                .timestamp()
+        };
+        let next = ibc_handler
+            .next_consensus_state(&client_id, &height(3))
+            .unwrap()
+            .unwrap();
+        assert_eq!(next.timestamp(), timestamp_at(7));
+        let prev = ibc_handler
+            .prev_consensus_state(&client_id, &height(9))
+            .unwrap()
+            .unwrap();
+        assert_eq!(prev.timestamp(), timestamp_at(8));
+        assert!(ibc_handler
+            .prev_consensus_state(&client_id, &height(7))
+            .unwrap()
+            .is_none());
+        assert!(ibc_handler
+            .next_consensus_state(&client_id, &height(10))
+            .unwrap()
+            .is_none());
+
+        // A height whose connection delay window has not passed is kept, and the
+        // next oldest ones are evicted instead
+        store_delayed_connection(&mut ibc_handler, &client_id, Duration::from_secs(60));
+        let host_timestamp = ibc_handler.host_timestamp().unwrap();
+        let host_height = ibc_handler.host_height().unwrap();
+        ibc_handler
+            .store_update_time(client_id.clone(), height(7), host_timestamp)
+            .unwrap();
+        ibc_handler
+            .store_update_height(client_id.clone(), height(7), host_height)
+            .unwrap();
+        for slot in 11..=12 {
+            let consensus_state = ibc_handler.host_consensus_state(&height(slot)).unwrap();
+            ibc_handler
+                .store_consensus_state(
+                    ClientConsensusStatePath::new(&client_id, &height(slot)),
+                    consensus_state,
+                )
+                .unwrap();
+        }
+        let consensus_heights = ibc_handler
+            .state
+            .get(&ConsensusHeightsPath(client_id.clone()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            consensus_heights.heights.into_iter().collect::<Vec<_>>(),
+            [7, 10, 11, 12].map(height)
+        );
+        ibc_handler
+            .client_update_time(&client_id, &height(7))
+            .unwrap();
+    }
+
+    #[test]
+    fn host_height_of_slot_zero_is_one() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+
+        let first_height = Height::new(0, 1).unwrap();
+        assert_eq!(ibc_handler.host_height().unwrap(), first_height);
+        let consensus_state = ibc_handler.host_consensus_state(&first_height).unwrap();
+        assert_eq!(
+            Some(consensus_state.root()),
+            IbcState::new(&store, 0)
+                .get_commitment_root_option(0)
+                .unwrap()
+                .as_ref()
+        );
+        ibc_handler
+            .host_consensus_state(&Height::new(1, 1).unwrap())
+            .unwrap_err();
+    }
+
+    #[test]
+    fn host_height_follows_the_revision_number() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        assert!(matches!(
+            ibc_handler.set_revision_number(0),
+            Err(AdminError::RevisionNotIncreasing { .. })
+        ));
+        assert_eq!(ibc_handler.set_revision_number(2).unwrap(), 0);
+        assert!(matches!(
+            ibc_handler.set_revision_number(1),
+            Err(AdminError::RevisionNotIncreasing { .. })
+        ));
+
+        assert_eq!(
+            ibc_handler.host_height().unwrap(),
+            Height::new(2, 1).unwrap()
+        );
+        // Proofs at heights of earlier revisions are still checked against the
+        // state of their slot
+        for revision_number in 0..=2 {
+            ibc_handler
+                .host_consensus_state(&Height::new(revision_number, 1).unwrap())
+                .unwrap();
+        }
+        ibc_handler
+            .host_consensus_state(&Height::new(3, 1).unwrap())
+            .unwrap_err();
+        drop(ibc_handler);
+        assert_eq!(metadata.revision_number, 2);
+    }
+
+    #[test]
+    fn host_consensus_states_of_past_heights_are_earlier() {
+        let store = IbcStore::default();
+        for slot in [10, 20, 30] {
+            let mut state = IbcState::new(&store, slot);
+            state.set(&StateInitializedPath, ());
+            state.commit().unwrap();
+        }
+
+        let mut metadata = IbcMetadata::default();
+        let clock = Clock {
+            slot: 40,
+            unix_timestamp: 1_700_000_000,
+            ..Clock::default()
+        };
+        let ibc_handler = new_handler(&store, &mut metadata, &clock);
+        let current_time = ibc_handler.host_timestamp().unwrap().nanoseconds();
+
+        let timestamps = [10, 20, 30, 40]
+            .map(|slot| {
+                ibc_handler
+                    .host_consensus_state(&Height::of_slot(slot).unwrap())
+                    .unwrap()
+                    .timestamp()
+                    .nanoseconds()
+            })
+            .to_vec();
+        assert!(timestamps.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(timestamps[..3]
+            .iter()
+            .all(|timestamp| *timestamp < current_time));
+        assert_eq!(timestamps[3], current_time);
+    }
+
+    #[test]
+    fn host_consensus_states_carry_the_time_of_their_slot() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let clock = |slot, unix_timestamp| Clock {
+            slot,
+            unix_timestamp,
+            ..Clock::default()
+        };
+        // The block time RPC nodes report for slot 10, which relayers put in the
+        // host consensus states they query
+        let block_time = 1_700_000_000;
+        let queried_time = Timestamp::from(eclipse_chain::slot_time(block_time).unwrap());
+
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(10, block_time));
+        assert_eq!(ibc_handler.host_timestamp().unwrap(), queried_time);
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        // Well after slot 10, and with slots that took longer than expected
+        let ibc_handler = new_handler(&store, &mut metadata, &clock(30, block_time + 60));
+        for slot in [10, 15] {
+            let consensus_state = ibc_handler
+                .host_consensus_state(&Height::of_slot(slot).unwrap())
+                .unwrap();
+            assert_eq!(consensus_state.timestamp(), queried_time, "slot {slot}");
+        }
+    }
+
+    #[test]
+    fn timestamps_are_bounded_and_never_zero() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let clock = Clock {
+            slot: 1,
+            unix_timestamp: 1_700_000_000,
+            ..Clock::default()
+        };
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock);
+
+        let client_id = ClientId::default();
+        let height = Height::new(0, 1).unwrap();
+        let at_bound = Timestamp::from_nanoseconds(MAX_TIMESTAMP_NANOS).unwrap();
+        let past_bound = Timestamp::from_nanoseconds(MAX_TIMESTAMP_NANOS + 1).unwrap();
+
+        // Update times are only ever real times within the bound
+        for timestamp in [Timestamp::none(), past_bound] {
+            ibc_handler
+                .store_update_time(client_id.clone(), height, timestamp)
+                .unwrap_err();
+        }
+        ibc_handler
+            .store_update_time(client_id.clone(), height, at_bound)
+            .unwrap();
+        assert_eq!(
+            ibc_handler.client_update_time(&client_id, &height).unwrap(),
+            at_bound,
+        );
+
+        // Raw values past the bound, however they got stored, are read errors
+        let update_time_path = ClientUpdateTimePath(client_id.clone(), height);
+        for raw in [MAX_TIMESTAMP_NANOS + 1, u64::MAX] {
+            ibc_handler
+                .state
+                .set(&PlantedPath::<u64>::at(&update_time_path), raw);
+            assert_read_err(
+                ibc_handler.client_update_time(&client_id, &height),
+                &update_time_path,
+            );
+        }
+
+        // Timeouts up to the bound compare against the host time as expected
+        let host_timestamp = ibc_handler.host_timestamp().unwrap();
+        assert!(matches!(
+            host_timestamp.check_expiry(&at_bound),
+            Expiry::NotExpired
+        ));
+        assert!(matches!(
+            at_bound.check_expiry(&host_timestamp),
+            Expiry::Expired
+        ));
+        // A zero timeout is no timeout, which never expires
+        assert!(!matches!(
+            host_timestamp.check_expiry(&Timestamp::none()),
+            Expiry::Expired
+        ));
+
+        // and transfers cannot set a timeout past it
+        let payer = Pubkey::new_unique();
+        let transfer = |timeout_timestamp| {
+            MsgTransfer::try_from(RawMsgTransfer {
+                source_port: PortId::transfer().to_string(),
+                source_channel: ChannelId::new(0).to_string(),
+                token: Some(RawCoin {
+                    denom: "uatom".to_owned(),
+                    amount: "1".to_owned(),
+                }),
+                sender: payer.to_string(),
+                receiver: "cosmos1receiver".to_owned(),
+                timeout_height: None,
+                timeout_timestamp,
+                memo: String::new(),
+            })
+            .unwrap()
+        };
+        let err = ibc_handler
+            .transfer(transfer(MAX_TIMESTAMP_NANOS + 1), &payer)
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("latest supported timestamp"),
+            "{err}"
+        );
+        let err = ibc_handler
+            .transfer(transfer(MAX_TIMESTAMP_NANOS), &payer)
+            .unwrap_err();
+        assert!(
+            !err.to_string().contains("latest supported timestamp"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn corrupt_values_are_read_errors() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+
+        let client_id = ClientId::default();
+        let connection_id = ConnectionId::default();
+        let port_id = PortId::transfer();
+        let channel_id = ChannelId::default();
+        let sequence = Sequence::from(1);
+        let height = Height::new(0, 1).unwrap();
+
+        let client_state_path = ClientStatePath::new(&client_id);
+        let consensus_state_path = ClientConsensusStatePath::new(&client_id, &height);
+        let connection_path = ConnectionPath::new(&connection_id);
+        let channel_end_path = ChannelEndPath::new(&port_id, &channel_id);
+        let seq_send_path = SeqSendPath::new(&port_id, &channel_id);
+        let seq_recv_path = SeqRecvPath::new(&port_id, &channel_id);
+        let seq_ack_path = SeqAckPath::new(&port_id, &channel_id);
+        let commitment_path = CommitmentPath::new(&port_id, &channel_id, sequence);
+        let receipt_path = ReceiptPath::new(&port_id, &channel_id, sequence);
+        let ack_path = AckPath::new(&port_id, &channel_id, sequence);
+        let update_time_path = ClientUpdateTimePath(client_id.clone(), height);
+        let update_height_path = ClientUpdateHeightPath(client_id.clone(), height);
+
+        let state = &mut ibc_handler.state;
+        for path in [
+            &client_state_path as &dyn Display,
+            &consensus_state_path,
+            &connection_path,
+            &commitment_path,
+            &receipt_path,
+            &ack_path,
+        ] {
+            state.set(&PlantedPath::at(path), corrupt_sequence());
+        }
+        for path in [
+            &channel_end_path as &dyn Display,
+            &seq_send_path,
+            &seq_recv_path,
+            &seq_ack_path,
+            &update_time_path,
+            &update_height_path,
+        ] {
+            state.set(&PlantedPath::at(path), corrupt_any());
+        }
+
+        assert_read_err(ibc_handler.client_state(&client_id), &client_state_path);
+        assert_read_err(
+            ValidationContext::consensus_state(&ibc_handler, &consensus_state_path),
+            &consensus_state_path,
+        );
+        assert_read_err(ibc_handler.connection_end(&connection_id), &connection_path);
+        assert_read_err(
+            ibc_handler.channel_end(&channel_end_path),
+            &channel_end_path,
+        );
+        assert_read_err(
+            ibc_handler.get_next_sequence_send(&seq_send_path),
+            &seq_send_path,
+        );
+        assert_read_err(
+            ibc_handler.get_next_sequence_recv(&seq_recv_path),
+            &seq_recv_path,
+        );
+        assert_read_err(
+            ibc_handler.get_next_sequence_ack(&seq_ack_path),
+            &seq_ack_path,
+        );
+        assert_read_err(
+            ibc_handler.get_packet_commitment(&commitment_path),
+            &commitment_path,
+        );
+        assert_read_err(ibc_handler.get_packet_receipt(&receipt_path), &receipt_path);
+        assert_read_err(ibc_handler.get_packet_acknowledgement(&ack_path), &ack_path);
+        assert_read_err(
+            ibc_handler.client_update_time(&client_id, &height),
+            &update_time_path,
+        );
+        assert_read_err(
+            ibc_handler.client_update_height(&client_id, &height),
+            &update_height_path,
+        );
+    }
+
+    #[test]
+    fn zero_sequences_are_rejected() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        let port_id = PortId::transfer();
+        let channel_id = ChannelId::new(0);
+        let seq_send_path = SeqSendPath::new(&port_id, &channel_id);
+        let seq_recv_path = SeqRecvPath::new(&port_id, &channel_id);
+        let seq_ack_path = SeqAckPath::new(&port_id, &channel_id);
+        let zero = Sequence::from(0);
+
+        let write_err = |result: Result<(), ContextError>| {
+            let err = result.unwrap_err();
+            assert!(err.to_string().contains("cannot store sequence 0"), "{err}");
+        };
+        write_err(ibc_handler.store_next_sequence_send(&seq_send_path, zero));
+        write_err(ibc_handler.store_next_sequence_recv(&seq_recv_path, zero));
+        write_err(ibc_handler.store_next_sequence_ack(&seq_ack_path, zero));
+        assert_eq!(ibc_handler.state.get(&seq_send_path).unwrap(), None);
+
+        // A zero written by a bug is treated as corrupt rather than used
+        ibc_handler.state.set(&seq_send_path, zero);
+        ibc_handler.state.set(&seq_recv_path, zero);
+        ibc_handler.state.set(&seq_ack_path, zero);
+        assert_read_err(
+            ibc_handler.get_next_sequence_send(&seq_send_path),
+            &seq_send_path,
+        );
+        assert_read_err(
+            ibc_handler.get_next_sequence_recv(&seq_recv_path),
+            &seq_recv_path,
+        );
+        assert_read_err(
+            ibc_handler.get_next_sequence_ack(&seq_ack_path),
+            &seq_ack_path,
+        );
+    }
+
+    /// Opens `channel-0` on the transfer port with `MsgChannelOpenInit`, over a
+    /// client and connection stored for it.
+    fn dispatch_channel_open_init(ibc_handler: &mut IbcHandler) {
+        let client_id = ClientId::default();
+        let client_state = clients::eclipse_client_state(1);
+        ibc_handler
+            .store_client_state(ClientStatePath::new(&client_id), Box::new(client_state))
+            .unwrap();
+        let connection_id = ConnectionId::default();
+        let connection_end = ConnectionEnd::try_from(RawConnectionEnd {
+            client_id: client_id.to_string(),
+            versions: vec![RawConnectionVersion {
+                identifier: "1".to_owned(),
+                features: vec!["ORDER_ORDERED".to_owned(), "ORDER_UNORDERED".to_owned()],
+            }],
+            state: RawConnectionState::Open as i32,
+            counterparty: Some(RawConnectionCounterparty {
+                client_id: client_id.to_string(),
+                connection_id: connection_id.to_string(),
+                prefix: Some(RawMerklePrefix {
+                    key_prefix: b"ibc".to_vec(),
+                }),
+            }),
+            delay_period: 0,
+        })
+        .unwrap();
+        ibc_handler
+            .store_connection(&ConnectionPath::new(&connection_id), connection_end)
+            .unwrap();
+
+        let msg = RawMsgChannelOpenInit {
+            port_id: PortId::transfer().to_string(),
+            channel: Some(RawChannel {
+                state: RawChannelState::Init as i32,
+                ordering: RawOrder::Unordered as i32,
+                counterparty: Some(RawChannelCounterparty {
+                    port_id: PortId::transfer().to_string(),
+                    channel_id: String::new(),
+                }),
+                connection_hops: vec![connection_id.to_string()],
+                version: "ics20-1".to_owned(),
+            }),
+            signer: Pubkey::new_unique().to_string(),
+        };
+        let envelope = MsgEnvelope::try_from(protobuf::Any {
+            type_url: "/ibc.core.channel.v1.MsgChannelOpenInit".to_owned(),
+            value: msg.encode_to_vec(),
+        })
+        .unwrap();
+        dispatch(ibc_handler, envelope).unwrap();
+    }
+
+    #[test]
+    fn channel_open_init_starts_sequences_at_one() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let clock = Clock {
+            slot: 1,
+            ..Clock::default()
+        };
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock);
+
+        dispatch_channel_open_init(&mut ibc_handler);
+
+        // ibc-rs stores the initial sequences itself when the channel opens
+        let port_id = PortId::transfer();
+        let channel_id = ChannelId::new(0);
+        let one = Sequence::from(1);
+        assert_eq!(
+            ibc_handler
+                .get_next_sequence_send(&SeqSendPath::new(&port_id, &channel_id))
+                .unwrap(),
+            one,
+        );
+        assert_eq!(
+            ibc_handler
+                .get_next_sequence_recv(&SeqRecvPath::new(&port_id, &channel_id))
+                .unwrap(),
+            one,
+        );
+        assert_eq!(
+            ibc_handler
+                .get_next_sequence_ack(&SeqAckPath::new(&port_id, &channel_id))
+                .unwrap(),
+            one,
+        );
+    }
+
+    #[test]
+    fn commits_breaking_chain_params_limits_write_nothing() {
+        let store = store_with_chain_params(ChainParams {
+            forbidden_write_prefixes: vec!["channelEnds/".to_owned()],
+            ..ChainParams::default()
+        });
+        let mut metadata = IbcMetadata::default();
+        let clock = Clock {
+            slot: 1,
+            ..Clock::default()
+        };
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock);
+        dispatch_channel_open_init(&mut ibc_handler);
+
+        let err = ibc_handler.commit().unwrap_err();
+        let rejected = err.downcast_ref::<commit_guard::CommitRejected>().unwrap();
+        assert_eq!(
+            rejected.violations,
+            [commit_guard::CommitViolation::ForbiddenWrite {
+                path: "channelEnds/ports/transfer/channels/channel-0".to_owned(),
+                path_prefix: "channelEnds/".to_owned(),
+            }]
+        );
+        drop(ibc_handler);
+
+        // Not even the writes within the limits
+        assert_eq!(store.read().latest_version(), Some(0));
+        assert_eq!(metadata, IbcMetadata::default());
+        let state = IbcState::new(&store, 1);
+        let port_id = PortId::transfer();
+        let channel_id = ChannelId::new(0);
+        assert!(state
+            .get(&ChannelEndPath::new(&port_id, &channel_id))
+            .unwrap()
+            .is_none());
+        assert!(state
+            .get(&ConnectionPath::new(&ConnectionId::default()))
+            .unwrap()
+            .is_none());
+    }
+
+    /// Records the pending changes of each commit and lets them all through.
+    #[derive(Debug)]
+    struct RecordingValidator(Rc<RefCell<Vec<commit_guard::PendingChange>>>);
+
+    impl PreCommitValidator for RecordingValidator {
+        fn validate(
+            &self,
+            pending_changes: &[commit_guard::PendingChange],
+        ) -> Vec<commit_guard::CommitViolation> {
+            self.0.borrow_mut().extend_from_slice(pending_changes);
+            vec![]
+        }
+    }
+
+    #[test]
+    fn pre_commit_validators_see_every_pending_change() {
+        let store = store_with_chain_params(ChainParams::default());
+        let mut metadata = IbcMetadata::default();
+        let clock = Clock {
+            slot: 1,
+            ..Clock::default()
+        };
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock);
+        let recorded = Rc::default();
+        ibc_handler.add_pre_commit_validator(Box::new(RecordingValidator(Rc::clone(&recorded))));
+        dispatch_channel_open_init(&mut ibc_handler);
+        ibc_handler.commit().unwrap();
+
+        let recorded = recorded.borrow();
+        let channel_end_path = "channelEnds/ports/transfer/channels/channel-0";
+        assert!(
+            recorded
+                .iter()
+                .any(|(path, kind, len)| path == channel_end_path
+                    && *kind == ChangeKind::Set
+                    && *len > 0),
+            "{recorded:?}"
+        );
+        for prefix in ["nextSequenceSend/", "nextSequenceRecv/", "nextSequenceAck/"] {
+            assert!(
+                recorded.iter().any(|(path, _, _)| path.starts_with(prefix)),
+                "{recorded:?}"
+            );
+        }
+        assert!(IbcState::new(&store, 1)
+            .get(&ChannelEndPath::new(
+                &PortId::transfer(),
+                &ChannelId::new(0)
+            ))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn corrupt_port_cannot_be_rebound() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+
+        let port_id = "corrupt".parse::<PortId>().unwrap();
+        ibc_handler.state.set(
+            &PlantedPath::at(&PortPath(port_id.clone())),
+            corrupt_sequence(),
+        );
+
+        assert!(ibc_handler
+            .bind_port(&port_id, &Pubkey::new_unique(), Default::default())
+            .is_err());
+    }
+
+    #[test]
+    fn transfer_port_is_reserved_for_the_ics20_module() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+
+        assert!(matches!(
+            ibc_handler.bind_port(
+                &PortId::transfer(),
+                &Pubkey::new_unique(),
+                Default::default()
+            ),
+            Err(PortError::ImplementationSpecific)
+        ));
+        assert_eq!(
+            ibc_handler.module_by_port(&PortId::transfer()).unwrap(),
+            Some(ics20_module_id())
+        );
+    }
+
+    #[test]
+    fn store_write_batches_are_admin_only() {
+        let admin = Pubkey::new_unique();
+        let chunk = || MsgStoreWriteBatch {
+            chunk_index: 0,
+            writes: vec![StoreWrite {
+                path: SeqSendPath(PortId::transfer(), ChannelId::default()).to_string(),
+                value: Some(Sequence::from(7).encode()),
+            }],
+            next_chunk_hash: None,
+        };
+
+        let store = initialized_store();
+
+        // Without a recorded admin, nobody may write
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        assert!(matches!(
+            ibc_handler.apply_store_write_batch(&admin, chunk()),
+            Err(StoreWriteBatchError::NotAdmin { .. })
+        ));
+
+        ibc_handler.state.set(&AdminPath, admin.to_string());
+        assert!(matches!(
+            ibc_handler.apply_store_write_batch(&Pubkey::new_unique(), chunk()),
+            Err(StoreWriteBatchError::NotAdmin { .. })
+        ));
+        ibc_handler
+            .apply_store_write_batch(&admin, chunk())
+            .unwrap();
+    }
+
+    #[test]
+    fn ports_are_transferred_only_by_their_module_to_an_unbound_module() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+
+        let port_id = "moved".parse::<PortId>().unwrap();
+        let other_port_id = "other".parse::<PortId>().unwrap();
+        let old_program_id = Pubkey::new_unique();
+        let new_program_id = Pubkey::new_unique();
+        let other_program_id = Pubkey::new_unique();
+        let account_templates = ModuleAccountTemplates {
+            templates: vec![PdaSeedTemplate {
+                seeds: vec![SeedPart::Literal(b"escrow".to_vec()), SeedPart::ChannelId],
+            }],
+        };
+        ibc_handler
+            .bind_port(&port_id, &old_program_id, account_templates.clone())
+            .unwrap();
+        ibc_handler
+            .bind_port(&other_port_id, &other_program_id, Default::default())
+            .unwrap();
+
+        // Only the bound module can transfer the port
+        assert!(ibc_handler
+            .transfer_port(&port_id, &new_program_id, &new_program_id)
+            .is_err());
+        // The port's templates would clobber those of an already bound module
+        assert!(ibc_handler
+            .transfer_port(&port_id, &old_program_id, &other_program_id)
+            .is_err());
+        assert!(ibc_handler
+            .transfer_port(&port_id, &old_program_id, &old_program_id)
+            .is_err());
+        assert!(matches!(
+            ibc_handler.transfer_port(
+                &"unbound".parse().unwrap(),
+                &old_program_id,
+                &new_program_id
+            ),
+            Err(PortError::UnknownPort { .. }),
+        ));
+
+        ibc_handler
+            .transfer_port(&port_id, &old_program_id, &new_program_id)
+            .unwrap();
+        let old_module_id = EclipseModuleId::from_pubkey(old_program_id).to_module_id();
+        let new_module_id = EclipseModuleId::from_pubkey(new_program_id).to_module_id();
+        assert_eq!(
+            ibc_handler.bound_module(&port_id).unwrap(),
+            Some(new_module_id.clone())
+        );
+        let all_module_ids = ibc_handler.state.get(&AllModulesPath).unwrap().unwrap();
+        assert!(all_module_ids.modules.contains(&new_module_id));
+        assert!(!all_module_ids.modules.contains(&old_module_id));
+        assert_eq!(
+            ibc_handler
+                .state
+                .get(&ModuleAccountTemplatesPath(new_module_id))
+                .unwrap(),
+            Some(account_templates)
+        );
+        assert_eq!(
+            ibc_handler
+                .state
+                .get(&ModuleAccountTemplatesPath(old_module_id))
+                .unwrap(),
+            None
+        );
+
+        // The old module no longer owns the port
+        assert!(ibc_handler.release_port(&port_id, &old_program_id).is_err());
+        ibc_handler.release_port(&port_id, &new_program_id).unwrap();
+    }
+
+    #[test]
+    fn channel_callbacks_reach_the_module_that_opened_the_channel() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let clock = |slot| Clock {
+            slot,
+            ..Clock::default()
+        };
+        let port_id = "rebound".parse::<PortId>().unwrap();
+        let old_program_id = Pubkey::new_unique();
+        let new_program_id = Pubkey::new_unique();
+        let old_module_id = EclipseModuleId::from_pubkey(old_program_id).to_module_id();
+        let new_module_id = EclipseModuleId::from_pubkey(new_program_id).to_module_id();
+        let channel_end = |state: RawChannelState| {
+            ChannelEnd::try_from(RawChannel {
+                state: state as i32,
+                ordering: RawOrder::Unordered as i32,
+                counterparty: Some(RawChannelCounterparty {
+                    port_id: port_id.to_string(),
+                    channel_id: ChannelId::new(0).to_string(),
+                }),
+                connection_hops: vec![ConnectionId::default().to_string()],
+                version: "v1".to_owned(),
+            })
+            .unwrap()
+        };
+        let recv_packet = |channel_id: &ChannelId| {
+            MsgEnvelope::try_from(protobuf::Any {
+                type_url: "/ibc.core.channel.v1.MsgRecvPacket".to_owned(),
+                value: RawMsgRecvPacket {
+                    packet: Some(RawPacket {
+                        sequence: 1,
+                        source_port: port_id.to_string(),
+                        source_channel: ChannelId::new(0).to_string(),
+                        destination_port: port_id.to_string(),
+                        destination_channel: channel_id.to_string(),
+                        data: vec![1],
+                        timeout_height: Some(RawHeight {
+                            revision_number: 0,
+                            revision_height: 100,
+                        }),
+                        timeout_timestamp: 0,
+                    }),
+                    proof_commitment: vec![1],
+                    proof_height: Some(RawHeight {
+                        revision_number: 0,
+                        revision_height: 1,
+                    }),
+                    signer: Pubkey::new_unique().to_string(),
+                }
+                .encode_to_vec(),
+            })
+            .unwrap()
+        };
+        let channel_id = ChannelId::new(0);
+        let channel_end_path = ChannelEndPath::new(&port_id, &channel_id);
+
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(1));
+        ibc_handler
+            .bind_port(&port_id, &old_program_id, Default::default())
+            .unwrap();
+        ibc_handler
+            .store_channel(&channel_end_path, channel_end(RawChannelState::Init))
+            .unwrap();
+        ibc_handler
+            .store_channel(&channel_end_path, channel_end(RawChannelState::Open))
+            .unwrap();
+
+        // The module cannot leave its open channel without a module to call back
+        assert!(matches!(
+            ibc_handler.release_port(&port_id, &old_program_id),
+            Err(PortError::ImplementationSpecific)
+        ));
+        assert_eq!(
+            ibc_handler.bound_module(&port_id).unwrap(),
+            Some(old_module_id.clone())
+        );
+
+        // Unless the admin forces the release, after which another module binds it
+        ibc_handler.force_release_port(&port_id).unwrap();
+        ibc_handler
+            .bind_port(&port_id, &new_program_id, Default::default())
+            .unwrap();
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(2));
+        assert_eq!(
+            ibc_handler.lookup_module_by_port(&port_id),
+            Some(new_module_id.clone())
+        );
+        // Packets on the existing channel still reach the module that opened it
+        ibc_handler
+            .route_to_channel_owner(&recv_packet(&channel_id))
+            .unwrap();
+        assert_eq!(
+            ibc_handler.lookup_module_by_port(&port_id),
+            Some(old_module_id.clone())
+        );
+        assert!(ibc_handler.has_route(&old_module_id));
+
+        // Channels opened after the rebind belong to the new module
+        let new_channel_id = ChannelId::new(1);
+        ibc_handler
+            .store_channel(
+                &ChannelEndPath::new(&port_id, &new_channel_id),
+                channel_end(RawChannelState::Tryopen),
+            )
+            .unwrap();
+        ibc_handler
+            .route_to_channel_owner(&recv_packet(&new_channel_id))
+            .unwrap();
+        assert_eq!(
+            ibc_handler.lookup_module_by_port(&port_id),
+            Some(new_module_id.clone())
+        );
+
+        // Only the channels the new module opened hold it to the port
+        assert!(ibc_handler.release_port(&port_id, &new_program_id).is_err());
+        ibc_handler
+            .store_channel(
+                &ChannelEndPath::new(&port_id, &new_channel_id),
+                channel_end(RawChannelState::Closed),
+            )
+            .unwrap();
+        ibc_handler.release_port(&port_id, &new_program_id).unwrap();
+    }
+
+    #[test]
+    fn callbacks_are_given_bound_module_pdas() {
+        let store = initialized_store();
+
+        let port_id = "escrowing".parse::<PortId>().unwrap();
+        let channel_id = ChannelId::new(0);
+        let program_id = Pubkey::new_unique();
+        let escrow_template = PdaSeedTemplate {
+            seeds: vec![
+                SeedPart::Literal(b"escrow".to_vec()),
+                SeedPart::PortId,
+                SeedPart::ChannelId,
+            ],
+        };
+        let packet_template = PdaSeedTemplate {
+            seeds: vec![SeedPart::ChannelId, SeedPart::Sequence],
+        };
+        let (escrow, _) = Pubkey::find_program_address(
+            &[
+                b"escrow",
+                port_id.as_str().as_bytes(),
+                channel_id.as_str().as_bytes(),
+            ],
+            &program_id,
+        );
+        let (packet_account, _) = Pubkey::find_program_address(
+            &[channel_id.as_str().as_bytes(), &1_u64.to_be_bytes()],
+            &program_id,
+        );
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        ibc_handler
+            .bind_port(
+                &port_id,
+                &program_id,
+                ModuleAccountTemplates {
+                    templates: vec![escrow_template, packet_template],
+                },
+            )
+            .unwrap();
+
+        // A PDA of the same seeds under another program must not be forwarded
+        let (foreign_escrow, _) = Pubkey::find_program_address(
+            &[
+                b"escrow",
+                port_id.as_str().as_bytes(),
+                channel_id.as_str().as_bytes(),
+            ],
+            &Pubkey::new_unique(),
+        );
+        let module_id = EclipseModuleId::from_pubkey(program_id).to_module_id();
+        let account_templates = ibc_handler
+            .state
+            .get(&ModuleAccountTemplatesPath(module_id))
+            .unwrap()
+            .unwrap();
+        let module = SolanaModule {
+            program_id,
+            account_templates,
+            instruction_accounts: vec![
+                AccountMeta::new_readonly(foreign_escrow, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(packet_account, true),
+            ],
+            skipped_callbacks: None,
+            callback_version: LEGACY_CALLBACK_VERSION,
+        };
+
+        assert_eq!(
+            module.callback_accounts(&port_id, &channel_id, None),
+            vec![AccountMeta::new(escrow, false)],
+        );
+        assert_eq!(
+            module.callback_accounts(&port_id, &channel_id, Some(Sequence::from(1))),
+            vec![
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(packet_account, false),
+            ],
+        );
+        assert_eq!(
+            module.callback_accounts(&port_id, &ChannelId::new(1), Some(Sequence::from(1))),
+            vec![],
+        );
+    }
+
+    #[test]
+    fn disabled_message_families_are_rejected() {
+        let upgrade_client = "/ibc.core.client.v1.MsgUpgradeClient";
+        let update_client = "/ibc.core.client.v1.MsgUpdateClient";
+
+        let store = store_with_chain_params(ChainParams::default());
+        let mut metadata = IbcMetadata::default();
+        let ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        assert_eq!(ibc_handler.disabled_message_prefix(upgrade_client), None);
+
+        let store = store_with_chain_params(ChainParams {
+            disabled_messages: vec![upgrade_client.to_owned(), "/eclipse.ibc.admin".to_owned()],
+            ..ChainParams::default()
+        });
+        let mut metadata = IbcMetadata::default();
+        let clock = |slot| Clock {
+            slot,
+            ..Clock::default()
+        };
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(1));
+        assert_eq!(
+            ibc_handler.disabled_message_prefix(upgrade_client),
+            Some(upgrade_client),
+        );
+        assert_eq!(ibc_handler.disabled_message_prefix(update_client), None);
+        assert_eq!(
+            ibc_handler.disabled_message_prefix(MsgUpdateChainParams::TYPE_URL),
+            None,
+        );
+
+        // Clearing the list takes effect from the next instruction
+        ibc_handler.update_chain_params(ChainParams::default());
+        assert_eq!(
+            ibc_handler.disabled_message_prefix(upgrade_client),
+            Some(upgrade_client),
+        );
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        let ibc_handler = new_handler(&store, &mut metadata, &clock(2));
+        assert_eq!(ibc_handler.disabled_message_prefix(upgrade_client), None);
+    }
+
+    #[test]
+    fn upgrades_are_written_for_future_heights_only() {
+        let store = store_with_chain_params(ChainParams::default());
+        let mut metadata = IbcMetadata::default();
+        let clock = Clock {
+            slot: 10,
+            ..Clock::default()
+        };
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock);
+        let upgraded_header = clients::eclipse_header(21);
+        let msg = |upgrade_height| MsgWriteUpgrade {
+            upgrade_height,
+            upgraded_client_state: clients::eclipse_client_state(21).encode_as_any(),
+            upgraded_consensus_state: EclipseConsensusState::from(upgraded_header.clone())
+                .encode_as_any(),
+        };
+
+        // Slot 10 is at revision height 11, whose state version is already being
+        // written
+        assert!(matches!(
+            ibc_handler.write_upgrade(msg(10)),
+            Err(AdminError::UpgradeInPast {
+                upgrade_height: 10,
+                host_height: 11,
+            })
+        ));
+        assert!(matches!(
+            ibc_handler.write_upgrade(msg(21)),
+            Err(AdminError::InvalidUpgrade { .. })
+        ));
+        assert!(matches!(
+            ibc_handler.write_upgrade(MsgWriteUpgrade {
+                upgraded_client_state: clients::tendermint_client_state_any(21),
+                ..msg(20)
+            }),
+            Err(AdminError::InvalidUpgrade { .. })
+        ));
+
+        ibc_handler.write_upgrade(msg(11)).unwrap();
+        assert_eq!(
+            ibc_handler
+                .state
+                .get_any(&UpgradeClientPath::UpgradedClientState(11))
+                .unwrap(),
+            Some(msg(11).upgraded_client_state)
+        );
+        assert_eq!(
+            ibc_handler
+                .state
+                .get_any(&UpgradeClientPath::UpgradedClientConsensusState(11))
+                .unwrap(),
+            Some(msg(11).upgraded_consensus_state)
+        );
+    }
+
+    #[test]
+    fn admin_changes_wait_for_their_slot_and_the_new_admin() {
+        // Multisigs hold their funds in a PDA, which signs admin messages through
+        // `invoke_signed` and so only ever appears as a signer
+        let (vault, _bump) =
+            Pubkey::find_program_address(&[b"multisig", b"vault"], &Pubkey::new_unique());
+        assert!(!vault.is_on_curve());
+        let first_admin = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+
+        let store = store_with_chain_params(ChainParams::default());
+        let mut metadata = IbcMetadata::default();
+        let clock = |slot| Clock {
+            slot,
+            ..Clock::default()
+        };
+
+        // Until there is an admin, nobody may send admin messages
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(10));
+        assert_eq!(ibc_handler.admin().unwrap(), None);
+        assert!(matches!(
+            ibc_handler.check_admin(MsgUpdateChainParams::TYPE_URL, &stranger),
+            Err(AdminError::NoAdmin { .. })
+        ));
+        // Not even a proposal for a first admin, which is claimed instead
+        assert!(matches!(
+            ibc_handler.propose_admin_change(&stranger, &first_admin, 11),
+            Err(AdminError::NoAdmin { .. })
+        ));
+        assert!(!ibc_handler.state.contains(&PendingAdminChangePath).unwrap());
+
+        let founder = Pubkey::new_unique();
+        ibc_handler.claim_admin(&founder).unwrap();
+        assert!(matches!(
+            ibc_handler.propose_admin_change(&stranger, &first_admin, 11),
+            Err(AdminError::NotAdmin { .. })
+        ));
+        assert!(matches!(
+            ibc_handler.propose_admin_change(&founder, &first_admin, 9),
+            Err(AdminError::ChangeInPast { .. })
+        ));
+        ibc_handler
+            .propose_admin_change(&founder, &first_admin, 11)
+            .unwrap();
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(11));
+        assert!(matches!(
+            ibc_handler.accept_admin_change(&first_admin),
+            Err(AdminError::NotYetEffective {
+                effective_after_slot: 11,
+                current_slot: 11,
+            })
+        ));
+        drop(ibc_handler);
+
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(12));
+        assert!(matches!(
+            ibc_handler.accept_admin_change(&stranger),
+            Err(AdminError::NotProposedAdmin { .. })
+        ));
+        assert_eq!(
+            ibc_handler.accept_admin_change(&first_admin).unwrap(),
+            Some(founder)
+        );
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        // Only the admin may now send admin messages, such as handing over to the
+        // multisig. Accepting is up to the new admin.
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(13));
+        assert!(matches!(
+            ibc_handler.check_admin(MsgProposeAdminChange::TYPE_URL, &stranger),
+            Err(AdminError::NotAdmin { .. })
+        ));
+        ibc_handler
+            .check_admin(MsgProposeAdminChange::TYPE_URL, &first_admin)
+            .unwrap();
+        ibc_handler
+            .check_admin(MsgAcceptAdminChange::TYPE_URL, &vault)
+            .unwrap();
+        ibc_handler
+            .propose_admin_change(&first_admin, &stranger, 20)
+            .unwrap();
+        assert_eq!(
+            ibc_handler.cancel_admin_change().unwrap().new_admin,
+            stranger.to_string()
+        );
+        assert!(matches!(
+            ibc_handler.cancel_admin_change(),
+            Err(AdminError::NoPendingChange)
+        ));
+        ibc_handler
+            .propose_admin_change(&first_admin, &vault, 20)
+            .unwrap();
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(21));
+        assert_eq!(
+            ibc_handler.accept_admin_change(&vault).unwrap(),
+            Some(first_admin)
+        );
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        let ibc_handler = new_handler(&store, &mut metadata, &clock(22));
+        assert_eq!(ibc_handler.admin().unwrap(), Some(vault));
+        ibc_handler
+            .check_admin(MsgSetIbcFrozen::TYPE_URL, &vault)
+            .unwrap();
+        assert!(matches!(
+            ibc_handler.check_admin(MsgSetIbcFrozen::TYPE_URL, &first_admin),
+            Err(AdminError::NotAdmin { .. })
+        ));
+        // Messages that are not admin-only are unaffected
+        ibc_handler
+            .check_admin("/ibc.core.client.v1.MsgUpdateClient", &first_admin)
+            .unwrap();
+    }
+
+    #[test]
+    fn self_client_must_track_this_cluster() {
+        let client_state_with_genesis_hash = |genesis_hash| {
+            EclipseClientState {
+                genesis_hash,
+                ..clients::eclipse_client_state(1)
+            }
+            .encode_as_any()
+        };
+        let our_genesis_hash = Hash::new_from_array([1; 32]);
+        let other_genesis_hash = Hash::new_from_array([2; 32]);
+
+        // Nothing to compare against until our genesis hash is recorded
+        let store = store_with_chain_params(ChainParams::default());
+        let mut metadata = IbcMetadata::default();
+        let ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        ibc_handler
+            .validate_self_client(client_state_with_genesis_hash(Some(other_genesis_hash)))
+            .unwrap();
+
+        let store = store_with_chain_params(ChainParams {
+            genesis_hash: Some(our_genesis_hash.to_bytes()),
+            ..ChainParams::default()
+        });
+        let mut metadata = IbcMetadata::default();
+        let ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        ibc_handler
+            .validate_self_client(client_state_with_genesis_hash(Some(our_genesis_hash)))
+            .unwrap();
+        ibc_handler
+            .validate_self_client(client_state_with_genesis_hash(None))
+            .unwrap();
+        let err = ibc_handler
+            .validate_self_client(client_state_with_genesis_hash(Some(other_genesis_hash)))
+            .unwrap_err();
+        assert!(
+            err.to_string().contains(&other_genesis_hash.to_string()),
+            "{err}"
+        );
+
+        ibc_handler.validate_self_client(corrupt_any()).unwrap_err();
+    }
+
+    #[test]
+    fn self_client_must_be_a_current_unfrozen_client_of_this_chain() {
+        let store = store_with_chain_params(ChainParams {
+            chain_name: Some(clients::CHAIN_NAME.to_owned()),
+            ..ChainParams::default()
+        });
+        let mut metadata = IbcMetadata::default();
+        let clock = Clock {
+            slot: 9,
+            ..Clock::default()
+        };
+        let ibc_handler = new_handler(&store, &mut metadata, &clock);
+        let validate_err = |client_state: protobuf::Any| {
+            ibc_handler
+                .validate_self_client(client_state)
+                .unwrap_err()
+                .to_string()
+        };
+
+        // A client may be at this chain's current height, but not past it
+        let client_state = clients::eclipse_client_state(10);
+        ibc_handler
+            .validate_self_client(client_state.clone().encode_as_any())
+            .unwrap();
+        let err = validate_err(clients::eclipse_client_state(11).encode_as_any());
+        assert!(err.contains("past this chain's height 0-10"), "{err}");
+
+        let err = validate_err(
+            EclipseClientState {
+                chain_id: ChainId::new("cosmoshub".to_owned(), 4),
+                ..client_state.clone()
+            }
+            .encode_as_any(),
+        );
+        assert!(
+            err.contains("cosmoshub-4, which is not an Eclipse chain"),
+            "{err}"
+        );
+        let err = validate_err(
+            EclipseClientState {
+                chain_id: eclipse_chain::chain_id("other"),
+                ..client_state.clone()
+            }
+            .encode_as_any(),
+        );
+        assert!(
+            err.contains("tracks chain eclipse-other-0, but this chain is eclipse-test-0"),
+            "{err}"
+        );
+
+        let err = validate_err(
+            EclipseClientState {
+                frozen_height: Some(Height::new(0, 5).unwrap()),
+                ..client_state
+            }
+            .encode_as_any(),
+        );
+        assert!(err.contains("frozen at height 0-5"), "{err}");
+
+        let err = validate_err(clients::tendermint_client_state_any(10));
+        assert!(
+            err.contains("has type URL /ibc.lightclients.tendermint.v1.ClientState"),
+            "{err}"
+        );
+
+        // Any Eclipse chain name is accepted until this chain's is configured
+        let store = store_with_chain_params(ChainParams::default());
+        let mut metadata = IbcMetadata::default();
+        let ibc_handler = new_handler(&store, &mut metadata, &clock);
+        ibc_handler
+            .validate_self_client(
+                EclipseClientState {
+                    chain_id: eclipse_chain::chain_id("other"),
+                    ..clients::eclipse_client_state(10)
+                }
+                .encode_as_any(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn inflight_packets_are_capped_per_channel() {
+        let store = store_with_chain_params(ChainParams {
+            max_inflight_packets_per_channel: Some(2),
+            ..ChainParams::default()
+        });
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+
+        let port_id = PortId::transfer();
+        let channel_id = ChannelId::new(0);
+        let other_channel_id = ChannelId::new(1);
+        let inflight_packets_path = InflightPacketsPath(port_id.clone(), channel_id.clone());
+        let commitment_path =
+            |channel_id, sequence: u64| CommitmentPath::new(&port_id, channel_id, sequence.into());
+        let commitment = || PacketCommitment::from(vec![1; 32]);
+
+        for sequence in 1..=2 {
+            ibc_handler
+                .store_packet_commitment(&commitment_path(&channel_id, sequence), commitment())
+                .unwrap();
+        }
+        assert_eq!(
+            ibc_handler.state.get(&inflight_packets_path).unwrap(),
+            Some(2)
+        );
+
+        let err = ibc_handler
+            .store_packet_commitment(&commitment_path(&channel_id, 3), commitment())
+            .unwrap_err();
+        assert!(err.to_string().contains("in-flight packets"), "{err}");
+        assert!(ibc_handler
+            .get_packet_commitment(&commitment_path(&channel_id, 3))
+            .is_err());
+
+        // Other channels have their own count
+        ibc_handler
+            .store_packet_commitment(&commitment_path(&other_channel_id, 1), commitment())
+            .unwrap();
+
+        // An ack or timeout deletes a commitment, freeing up room for another send
+        ibc_handler
+            .delete_packet_commitment(&commitment_path(&channel_id, 1))
+            .unwrap();
+        assert_eq!(
+            ibc_handler.state.get(&inflight_packets_path).unwrap(),
+            Some(1)
+        );
+        ibc_handler
+            .store_packet_commitment(&commitment_path(&channel_id, 3), commitment())
+            .unwrap();
+
+        for sequence in 2..=3 {
+            ibc_handler
+                .delete_packet_commitment(&commitment_path(&channel_id, sequence))
+                .unwrap();
+        }
+        assert_eq!(
+            ibc_handler.state.get(&inflight_packets_path).unwrap(),
+            Some(0)
+        );
+    }
 
-        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
-            description: "Return data missing".to_owned(),
-        })?;
+    #[test]
+    fn inflight_packets_of_channels_never_counted_are_backfilled() {
+        let store = store_with_chain_params(ChainParams {
+            max_inflight_packets_per_channel: Some(3),
+            ..ChainParams::default()
+        });
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+
+        let port_id = PortId::transfer();
+        let channel_id = ChannelId::new(1);
+        let inflight_packets_path = InflightPacketsPath(port_id.clone(), channel_id.clone());
+        let commitment_path =
+            |sequence: u64| CommitmentPath::new(&port_id, &channel_id, sequence.into());
+        let commitment = || PacketCommitment::from(vec![1; 32]);
+
+        // Sent before packets were counted, along with one since acknowledged and
+        // one on a channel whose ID shares the prefix
+        for sequence in 1..=3 {
+            ibc_handler
+                .state
+                .set(&commitment_path(sequence), commitment());
+        }
+        ibc_handler.state.set(
+            &CommitmentPath::new(&port_id, &ChannelId::new(10), 1.into()),
+            commitment(),
+        );
+        ibc_handler.commit().unwrap();
+        ibc_handler.state.remove(&commitment_path(1));
 
-        bincode::deserialize(&return_data).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })
+        ibc_handler
+            .store_packet_commitment(&commitment_path(4), commitment())
+            .unwrap();
+        assert_eq!(
+            ibc_handler.state.get(&inflight_packets_path).unwrap(),
+            Some(3)
+        );
+        let err = ibc_handler
+            .store_packet_commitment(&commitment_path(5), commitment())
+            .unwrap_err();
+        assert!(err.to_string().contains("3 in-flight packets"), "{err}");
     }
 
-    fn on_chan_open_init_execute(
-        &mut self,
-        order: Order,
-        connection_hops: &[ConnectionId],
-        port_id: &PortId,
-        channel_id: &ChannelId,
-        counterparty: &Counterparty,
-        version: &Version,
-    ) -> Result<(ModuleExtras, Version), ChannelError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnChanOpenInitExecute(OnChanOpenInitExecute {
-                order,
-                connection_hops: connection_hops.to_vec(),
-                port_id: port_id.clone(),
-                channel_id: channel_id.clone(),
-                counterparty: counterparty.clone(),
-                version: version.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
+    #[test]
+    fn capabilities_refresh_to_those_of_the_running_program() {
+        let store = store_with_chain_params(ChainParams::default());
+        let mut metadata = IbcMetadata::default();
+        let clock = Clock {
+            slot: 1,
+            ..Clock::default()
+        };
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock);
 
-        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })?;
+        // As written by an older program that did not handle packets yet
+        let stale = Capabilities {
+            type_url_prefixes: vec!["/ibc.core.client.v1.".to_owned()],
+            program_version: "0.0.1".to_owned(),
+            build_id: String::new(),
+        };
+        ibc_handler.state.set(&CapabilitiesPath, stale.clone());
+        assert_eq!(ibc_handler.refresh_capabilities().unwrap(), Some(stale));
+
+        let refreshed = ibc_handler.state.get(&CapabilitiesPath).unwrap().unwrap();
+        assert_eq!(refreshed.program_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(refreshed.build_id, crate::build_info::GIT_HASH);
+        assert!(refreshed.supports("/ibc.core.channel.v1.MsgRecvPacket"));
+        assert!(refreshed.supports(MsgRefreshCapabilities::TYPE_URL));
+        assert!(!refreshed.supports("/ibc.core.channel.v1.MsgChannelUpgradeInit"));
+        assert!(ADMIN_ONLY_TYPE_URLS.contains(&MsgRefreshCapabilities::TYPE_URL));
+    }
 
-        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
-            description: "Return data missing".to_owned(),
-        })?;
+    #[test]
+    fn frozen_ibc_rejects_everything_but_unfreezing() {
+        let update_client = "/ibc.core.client.v1.MsgUpdateClient";
+        let store = store_with_chain_params(ChainParams {
+            disabled_messages: vec!["/ibc.core.channel".to_owned()],
+            ..ChainParams::default()
+        });
+        let mut metadata = IbcMetadata::default();
+        let clock = |slot| Clock {
+            slot,
+            ..Clock::default()
+        };
 
-        bincode::deserialize(&return_data).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(1));
+        assert!(!ibc_handler.rejects_while_frozen(update_client));
+        ibc_handler.set_frozen(true);
+        assert!(!ibc_handler.is_frozen());
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(2));
+        assert!(ibc_handler.is_frozen());
+        assert!(ibc_handler.rejects_while_frozen(update_client));
+        assert!(!ibc_handler.rejects_while_frozen(MsgSetIbcFrozen::TYPE_URL));
+        assert!(!ibc_handler.rejects_while_frozen(MsgUpdateChainParams::TYPE_URL));
+        ibc_handler.set_frozen(false);
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        // Freezing leaves the rest of the chain params alone
+        let ibc_handler = new_handler(&store, &mut metadata, &clock(3));
+        assert!(!ibc_handler.rejects_while_frozen(update_client));
+        assert_eq!(
+            ibc_handler.disabled_message_prefix("/ibc.core.channel.v1.MsgChannelOpenInit"),
+            Some("/ibc.core.channel"),
+        );
     }
 
-    fn on_chan_open_try_validate(
-        &self,
-        order: Order,
-        connection_hops: &[ConnectionId],
-        port_id: &PortId,
-        channel_id: &ChannelId,
-        counterparty: &Counterparty,
-        counterparty_version: &Version,
-    ) -> Result<Version, ChannelError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnChanOpenTryValidate(OnChanOpenTryValidate {
-                order,
-                connection_hops: connection_hops.to_vec(),
-                port_id: port_id.clone(),
-                channel_id: channel_id.clone(),
-                counterparty: counterparty.clone(),
-                counterparty_version: counterparty_version.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
-
-        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })?;
+    #[test]
+    fn only_the_admin_may_freeze() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+
+        let admin = Pubkey::new_unique();
+        ibc_handler.state.set(&AdminPath, admin.to_string());
+        for type_url in [MsgSetIbcFrozen::TYPE_URL, MsgUpdateChainParams::TYPE_URL] {
+            assert!(matches!(
+                ibc_handler.check_admin(type_url, &Pubkey::new_unique()),
+                Err(AdminError::NotAdmin { .. })
+            ));
+            ibc_handler.check_admin(type_url, &admin).unwrap();
+        }
+    }
 
-        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
-            description: "Return data missing".to_owned(),
-        })?;
+    #[test]
+    fn endpoints_without_an_admin_reject_admin_messages_until_one_is_claimed() {
+        let store = store_with_chain_params(ChainParams {
+            frozen: true,
+            ..ChainParams::default()
+        });
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        let claimer = Pubkey::new_unique();
+
+        assert_eq!(ibc_handler.admin().unwrap(), None);
+        for type_url in ADMIN_ONLY_TYPE_URLS {
+            assert!(
+                matches!(
+                    ibc_handler.check_admin(type_url, &claimer),
+                    Err(AdminError::NoAdmin { .. })
+                ),
+                "{type_url} was accepted without an admin"
+            );
+        }
 
-        bincode::deserialize(&return_data).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })
+        // Claiming is how an endpoint without an admin gets one, frozen or not
+        ibc_handler
+            .check_admin(MsgClaimAdmin::TYPE_URL, &claimer)
+            .unwrap();
+        assert!(!ibc_handler.rejects_while_frozen(MsgClaimAdmin::TYPE_URL));
+        ibc_handler.claim_admin(&claimer).unwrap();
+        for type_url in ADMIN_ONLY_TYPE_URLS {
+            ibc_handler.check_admin(type_url, &claimer).unwrap();
+        }
+        assert!(matches!(
+            ibc_handler.claim_admin(&Pubkey::new_unique()),
+            Err(AdminError::AlreadySet { admin }) if admin == claimer
+        ));
     }
 
-    fn on_chan_open_try_execute(
-        &mut self,
-        order: Order,
-        connection_hops: &[ConnectionId],
-        port_id: &PortId,
-        channel_id: &ChannelId,
-        counterparty: &Counterparty,
-        counterparty_version: &Version,
-    ) -> Result<(ModuleExtras, Version), ChannelError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnChanOpenTryExecute(OnChanOpenTryExecute {
-                order,
-                connection_hops: connection_hops.to_vec(),
-                port_id: port_id.clone(),
-                channel_id: channel_id.clone(),
-                counterparty: counterparty.clone(),
-                counterparty_version: counterparty_version.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
+    #[test]
+    fn messages_wait_for_a_store_write_batch_to_complete() {
+        let store = store_with_chain_params(ChainParams::default());
+        let mut metadata = IbcMetadata::default();
+        let clock = |slot| Clock {
+            slot,
+            ..Clock::default()
+        };
+        let port_id = "imported".parse::<PortId>().unwrap();
+        let last_chunk = MsgStoreWriteBatch {
+            chunk_index: 1,
+            writes: vec![StoreWrite {
+                path: PortPath(port_id.clone()).to_string(),
+                value: None,
+            }],
+            next_chunk_hash: None,
+        };
+        let first_chunk = MsgStoreWriteBatch {
+            chunk_index: 0,
+            writes: vec![],
+            next_chunk_hash: Some(last_chunk.chunk_hash()),
+        };
 
-        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })?;
+        let admin = Pubkey::new_unique();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(1));
+        ibc_handler.state.set(&AdminPath, admin.to_string());
+        ibc_handler
+            .apply_store_write_batch(&admin, first_chunk)
+            .unwrap();
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        // A message interleaved between the chunks is rejected, but the batch
+        // itself and other admin messages go through
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(2));
+        assert!(ibc_handler
+            .rejects_during_migration(MsgBindPort::TYPE_URL)
+            .unwrap());
+        assert!(ibc_handler
+            .rejects_during_migration("/ibc.core.client.v1.MsgUpdateClient")
+            .unwrap());
+        assert!(!ibc_handler
+            .rejects_during_migration(MsgStoreWriteBatch::TYPE_URL)
+            .unwrap());
+        assert!(!ibc_handler
+            .rejects_during_migration(MsgSetIbcFrozen::TYPE_URL)
+            .unwrap());
+        ibc_handler
+            .apply_store_write_batch(&admin, last_chunk)
+            .unwrap();
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(3));
+        assert!(!ibc_handler
+            .rejects_during_migration(MsgBindPort::TYPE_URL)
+            .unwrap());
+        ibc_handler
+            .bind_port(&port_id, &Pubkey::new_unique(), Default::default())
+            .unwrap();
+    }
 
-        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
-            description: "Return data missing".to_owned(),
-        })?;
+    #[test]
+    fn aborting_a_store_write_batch_lets_messages_through() {
+        let store = store_with_chain_params(ChainParams::default());
+        let mut metadata = IbcMetadata::default();
+        let clock = |slot| Clock {
+            slot,
+            ..Clock::default()
+        };
+        let admin = Pubkey::new_unique();
+        let port_id = "imported".parse::<PortId>().unwrap();
+        let interrupted_chunk = MsgStoreWriteBatch {
+            chunk_index: 0,
+            writes: vec![],
+            next_chunk_hash: Some([7; 32]),
+        };
 
-        bincode::deserialize(&return_data).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })
+        // The rest of the batch never arrives
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(1));
+        ibc_handler.state.set(&AdminPath, admin.to_string());
+        ibc_handler
+            .apply_store_write_batch(&admin, interrupted_chunk)
+            .unwrap();
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(2));
+        assert!(ibc_handler
+            .rejects_during_migration(MsgBindPort::TYPE_URL)
+            .unwrap());
+        assert!(!ibc_handler
+            .rejects_during_migration(MsgAbortStoreWriteBatch::TYPE_URL)
+            .unwrap());
+        ibc_handler
+            .check_admin(MsgAbortStoreWriteBatch::TYPE_URL, &admin)
+            .unwrap();
+        assert_eq!(
+            ibc_handler
+                .abort_store_write_batch()
+                .unwrap()
+                .next_chunk_index,
+            1
+        );
+        ibc_handler.commit().unwrap();
+        drop(ibc_handler);
+
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(3));
+        assert!(!ibc_handler
+            .rejects_during_migration(MsgBindPort::TYPE_URL)
+            .unwrap());
+        ibc_handler
+            .bind_port(&port_id, &Pubkey::new_unique(), Default::default())
+            .unwrap();
+        assert!(matches!(
+            ibc_handler.abort_store_write_batch(),
+            Err(StoreWriteBatchError::NoBatchInProgress)
+        ));
     }
 
-    fn on_chan_open_ack_validate(
-        &self,
-        port_id: &PortId,
-        channel_id: &ChannelId,
-        counterparty_version: &Version,
-    ) -> Result<(), ChannelError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnChanOpenAckValidate(OnChanOpenAckValidate {
-                port_id: port_id.clone(),
-                channel_id: channel_id.clone(),
-                counterparty_version: counterparty_version.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
-
-        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })?;
+    #[test]
+    fn pruning_keeps_the_retention_window_and_client_consensus_states() {
+        let store = store_with_chain_params(ChainParams::default());
+        let mut metadata = IbcMetadata::default();
+        let clock = |slot| Clock {
+            slot,
+            ..Clock::default()
+        };
+        let client_id = ClientId::default();
+        let consensus_state_path =
+            |slot| ClientConsensusStatePath::new(&client_id, &Height::of_slot(slot).unwrap());
+
+        for slot in 1..10 {
+            let mut ibc_handler = new_handler(&store, &mut metadata, &clock(slot));
+            let height = ibc_handler.host_height().unwrap();
+            let consensus_state = ibc_handler.host_consensus_state(&height).unwrap();
+            ibc_handler
+                .store_consensus_state(consensus_state_path(slot), consensus_state)
+                .unwrap();
+            ibc_handler.commit().unwrap();
+        }
 
-        Ok(())
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(10));
+        let pruning = ibc_handler.prune_state(3, usize::MAX).unwrap();
+        assert_eq!(pruning.pruned_versions, 7);
+        assert_eq!(pruning.remaining_nodes, 0);
+        ibc_handler.commit().unwrap();
+
+        ibc_handler
+            .host_consensus_state(&Height::of_slot(5).unwrap())
+            .unwrap_err();
+        ibc_handler
+            .host_consensus_state(&Height::of_slot(8).unwrap())
+            .unwrap();
+        for slot in 1..10 {
+            ValidationContext::consensus_state(&ibc_handler, &consensus_state_path(slot)).unwrap();
+        }
     }
 
-    fn on_chan_open_ack_execute(
-        &mut self,
-        port_id: &PortId,
-        channel_id: &ChannelId,
-        counterparty_version: &Version,
-    ) -> Result<ModuleExtras, ChannelError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnChanOpenAckExecute(OnChanOpenAckExecute {
-                port_id: port_id.clone(),
-                channel_id: channel_id.clone(),
-                counterparty_version: counterparty_version.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
+    #[test]
+    fn recomputed_commitments_match_ibc_rs() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+
+        let packet_data = br#"{"amount":"100","denom":"uatom"}"#;
+        let timeout_timestamp = Timestamp::from_nanoseconds(1_685_577_600_000_000_000).unwrap();
+        for timeout_height in [
+            TimeoutHeight::Never,
+            TimeoutHeight::At(Height::new(1, 42).unwrap()),
+        ] {
+            assert_eq!(
+                eclipse_ibc_state::compute_packet_commitment(
+                    packet_data,
+                    &timeout_height,
+                    &timeout_timestamp,
+                ),
+                ibc_handler.packet_commitment(packet_data, &timeout_height, &timeout_timestamp),
+            );
+        }
 
-        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })?;
+        let ack = br#"{"result":"AQ=="}"#.to_vec();
+        assert_eq!(
+            eclipse_ibc_state::compute_ack_commitment(&ack),
+            ibc_handler.ack_commitment(&Acknowledgement::try_from(ack.clone()).unwrap()),
+        );
+    }
 
-        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
-            description: "Return data missing".to_owned(),
-        })?;
+    #[test]
+    fn empty_module_acks_are_replaced_with_error_acks() {
+        let store = initialized_store();
 
-        bincode::deserialize(&return_data).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })
-    }
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
 
-    fn on_chan_open_confirm_validate(
-        &self,
-        port_id: &PortId,
-        channel_id: &ChannelId,
-    ) -> Result<(), ChannelError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnChanOpenConfirmValidate(OnChanOpenConfirmValidate {
-                port_id: port_id.clone(),
-                channel_id: channel_id.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
+        // Decoded the same way as the return data of module callbacks
+        let decode_ack = |ack: &[u8]| -> Acknowledgement {
+            bincode::deserialize(&bincode::serialize(ack).unwrap()).unwrap()
+        };
+        let ack_path = AckPath::new(
+            &PortId::transfer(),
+            &ChannelId::default(),
+            Sequence::from(1),
+        );
+        for (returned, expected) in [
+            (&b""[..], &br#"{"error":"empty acknowledgement"}"#[..]),
+            (&br#"{"result":"AQ=="}"#[..], &br#"{"result":"AQ=="}"#[..]),
+            (
+                &br#"{"error":"bad denom"}"#[..],
+                &br#"{"error":"bad denom"}"#[..],
+            ),
+        ] {
+            let ack = validated_ack(decode_ack(returned));
+            assert_eq!(ack.as_ref(), expected);
+
+            let ack_commitment = ibc_handler.ack_commitment(&ack);
+            assert_eq!(
+                ack_commitment,
+                eclipse_ibc_state::compute_ack_commitment(expected),
+            );
+            ibc_handler
+                .store_packet_acknowledgement(&ack_path, ack_commitment.clone())
+                .unwrap();
+            assert_eq!(
+                ibc_handler.get_packet_acknowledgement(&ack_path).unwrap(),
+                ack_commitment,
+            );
+        }
+        assert_eq!(
+            validated_ack(success_ack()).as_ref(),
+            success_ack().as_ref()
+        );
+    }
 
-        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })?;
+    thread_local! {
+        /// How the module program invoked by callbacks on this thread behaves.
+        static STUB_MODULE: RefCell<Option<StubModule>> = RefCell::new(None);
+        /// Instructions module programs were invoked with on this thread, in order.
+        static STUB_INVOCATIONS: RefCell<Vec<Instruction>> = RefCell::new(vec![]);
+    }
 
-        Ok(())
+    #[derive(Clone)]
+    enum StubModule {
+        Fails,
+        Returns(Option<Vec<u8>>),
+        /// Answers the callback versions query with these versions
+        Advertises(Vec<u32>),
     }
 
-    fn on_chan_open_confirm_execute(
-        &mut self,
-        port_id: &PortId,
-        channel_id: &ChannelId,
-    ) -> Result<ModuleExtras, ChannelError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnChanOpenConfirmExecute(OnChanOpenConfirmExecute {
-                port_id: port_id.clone(),
-                channel_id: channel_id.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
+    /// Stands in for the runtime when `SolanaModule` invokes a module program,
+    /// answering as the current thread's `STUB_MODULE` says. Return data is
+    /// reported as set by the program invoked last.
+    struct ModuleStubs;
+
+    impl SyscallStubs for ModuleStubs {
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            _account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            STUB_INVOCATIONS.with(|invocations| invocations.borrow_mut().push(instruction.clone()));
+            match STUB_MODULE.with(|stub_module| stub_module.borrow().clone()) {
+                Some(StubModule::Fails) => Err(ProgramError::Custom(1)),
+                _ => Ok(()),
+            }
+        }
 
-        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })?;
+        fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+            let program_id = STUB_INVOCATIONS.with(|invocations| {
+                invocations
+                    .borrow()
+                    .last()
+                    .map(|instruction| instruction.program_id)
+            })?;
+            let return_data = match STUB_MODULE.with(|stub_module| stub_module.borrow().clone()) {
+                Some(StubModule::Returns(return_data)) => return_data,
+                Some(StubModule::Advertises(versions)) => {
+                    Some(bincode::serialize(&versions).unwrap())
+                }
+                _ => None,
+            };
+            return_data.map(|return_data| (program_id, return_data))
+        }
+    }
 
-        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
-            description: "Return data missing".to_owned(),
-        })?;
+    fn use_module_stubs() {
+        static SET_STUBS: Once = Once::new();
+        SET_STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(ModuleStubs));
+        });
+    }
 
-        bincode::deserialize(&return_data).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })
+    /// Takes the instructions module programs were invoked with on this thread.
+    fn take_stub_invocations() -> Vec<Instruction> {
+        STUB_INVOCATIONS.with(|invocations| mem::take(&mut *invocations.borrow_mut()))
     }
 
-    fn on_chan_close_init_validate(
-        &self,
-        port_id: &PortId,
-        channel_id: &ChannelId,
-    ) -> Result<(), ChannelError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnChanCloseInitValidate(OnChanCloseInitValidate {
-                port_id: port_id.clone(),
-                channel_id: channel_id.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
+    #[test]
+    fn failing_modules_get_error_acks_for_received_packets() {
+        use_module_stubs();
+
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+
+        let port_id = PortId::transfer();
+        let channel_id = ChannelId::default();
+        let mut module = SolanaModule {
+            program_id: Pubkey::new_unique(),
+            account_templates: ModuleAccountTemplates::default(),
+            instruction_accounts: vec![],
+            skipped_callbacks: None,
+            callback_version: LEGACY_CALLBACK_VERSION,
+        };
+        let packet = Packet {
+            seq_on_a: Sequence::from(1),
+            port_id_on_a: port_id.clone(),
+            chan_id_on_a: channel_id.clone(),
+            port_id_on_b: port_id.clone(),
+            chan_id_on_b: channel_id.clone(),
+            data: b"data".to_vec(),
+            timeout_height_on_b: TimeoutHeight::At(Height::new(0, 100).unwrap()),
+            timeout_timestamp_on_b: Timestamp::none(),
+        };
+        let relayer = Signer::from(Pubkey::new_unique().to_string());
+        let mut recv_packet = |stub_module| {
+            STUB_MODULE.with(|cell| *cell.borrow_mut() = Some(stub_module));
+            let (extras, ack) = module.on_recv_packet_execute(&packet, &relayer);
+            STUB_MODULE.with(|cell| *cell.borrow_mut() = None);
+            assert!(extras.events.is_empty() && extras.log.is_empty());
+            ack
+        };
 
-        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })?;
+        let returned_ack = bincode::serialize(&(
+            SerializableModuleExtras::default(),
+            Acknowledgement::try_from(b"ack".to_vec()).unwrap(),
+        ))
+        .unwrap();
+        assert_eq!(
+            recv_packet(StubModule::Returns(Some(returned_ack))).as_ref(),
+            b"ack",
+        );
 
-        Ok(())
+        for (stub_module, expected) in [
+            (StubModule::Fails, "module failed to receive the packet"),
+            (
+                StubModule::Returns(None),
+                "module returned no acknowledgement",
+            ),
+            (
+                StubModule::Returns(Some(vec![0xff; 3])),
+                "module returned an undecodable acknowledgement",
+            ),
+        ] {
+            let ack = recv_packet(stub_module);
+            let ack_json = serde_json::from_slice::<serde_json::Value>(ack.as_ref()).unwrap();
+            let description = ack_json["error"].as_str().unwrap();
+            assert!(description.starts_with(expected), "{description}");
+
+            // The receipt and the error acknowledgement are still written
+            let receipt_path = ReceiptPath::new(&port_id, &channel_id, packet.seq_on_a);
+            ibc_handler
+                .store_packet_receipt(&receipt_path, Receipt::Ok)
+                .unwrap();
+            let ack_path = AckPath::new(&port_id, &channel_id, packet.seq_on_a);
+            let ack_commitment = ibc_handler.ack_commitment(&ack);
+            ibc_handler
+                .store_packet_acknowledgement(&ack_path, ack_commitment)
+                .unwrap();
+            assert!(matches!(
+                ibc_handler.get_packet_receipt(&receipt_path).unwrap(),
+                Receipt::Ok
+            ));
+            assert_eq!(
+                ibc_handler.get_packet_acknowledgement(&ack_path).unwrap(),
+                eclipse_ibc_state::compute_ack_commitment(ack.as_ref()),
+            );
+        }
     }
 
-    fn on_chan_close_init_execute(
-        &mut self,
-        port_id: &PortId,
-        channel_id: &ChannelId,
-    ) -> Result<ModuleExtras, ChannelError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnChanCloseInitExecute(OnChanCloseInitExecute {
-                port_id: port_id.clone(),
-                channel_id: channel_id.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
+    #[test]
+    fn callback_versions_are_negotiated_once_per_module() {
+        use_module_stubs();
+
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let clock = |slot| Clock {
+            slot,
+            ..Clock::default()
+        };
+        // Modules advertising each version set, along with the version their
+        // callbacks are then sent in
+        let modules = [
+            ("legacy", StubModule::Fails, Some(LEGACY_CALLBACK_VERSION)),
+            (
+                "garbled",
+                StubModule::Returns(Some(vec![0xff])),
+                Some(LEGACY_CALLBACK_VERSION),
+            ),
+            ("v1", StubModule::Advertises(vec![1]), Some(1)),
+            ("v1v2", StubModule::Advertises(vec![2, 1]), Some(2)),
+            ("v2v5", StubModule::Advertises(vec![2, 5]), Some(2)),
+            ("v5", StubModule::Advertises(vec![5]), None),
+        ]
+        .map(|(port_id, stub_module, negotiated)| {
+            (
+                port_id.parse::<PortId>().unwrap(),
+                Pubkey::new_unique(),
+                stub_module,
+                negotiated,
+            )
+        });
+        let channel_open_init = |port_id: &PortId| {
+            MsgEnvelope::try_from(protobuf::Any {
+                type_url: "/ibc.core.channel.v1.MsgChannelOpenInit".to_owned(),
+                value: RawMsgChannelOpenInit {
+                    port_id: port_id.to_string(),
+                    channel: Some(RawChannel {
+                        state: RawChannelState::Init as i32,
+                        ordering: RawOrder::Unordered as i32,
+                        counterparty: Some(RawChannelCounterparty {
+                            port_id: port_id.to_string(),
+                            channel_id: String::new(),
+                        }),
+                        connection_hops: vec![ConnectionId::default().to_string()],
+                        version: "v1".to_owned(),
+                    }),
+                    signer: Pubkey::new_unique().to_string(),
+                }
+                .encode_to_vec(),
+            })
+            .unwrap()
+        };
 
-        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })?;
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(1));
+        for (port_id, program_id, _, _) in &modules {
+            ibc_handler
+                .bind_port(port_id, program_id, Default::default())
+                .unwrap();
+        }
+        ibc_handler.commit().unwrap();
+
+        let (rebound_port_id, rebound_program_id) = (modules[5].0.clone(), modules[5].1);
+
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock(2));
+        for (port_id, program_id, stub_module, negotiated) in modules {
+            let module_id = EclipseModuleId::from_pubkey(program_id).to_module_id();
+            let envelope = channel_open_init(&port_id);
+            STUB_MODULE.with(|cell| *cell.borrow_mut() = Some(stub_module));
+            let mismatch = ibc_handler.callback_version_mismatch(&envelope).unwrap();
+            assert_eq!(mismatch.is_none(), negotiated.is_some(), "{port_id}");
+            if let Some(mismatch) = mismatch {
+                assert!(
+                    mismatch.starts_with("NoCommonCallbackVersion"),
+                    "{mismatch}"
+                );
+            }
 
-        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
-            description: "Return data missing".to_owned(),
-        })?;
+            // The query is sent in the legacy encoding, and only once
+            let invocations = take_stub_invocations();
+            assert_eq!(invocations.len(), 1, "{port_id}");
+            assert_eq!(invocations[0].program_id, program_id);
+            assert!(matches!(
+                bincode::deserialize(&invocations[0].data).unwrap(),
+                IbcModuleInstruction::SupportedCallbackVersions
+            ));
+            assert!(ibc_handler
+                .state
+                .get(&ModuleCallbackVersionsPath(module_id.clone()))
+                .unwrap()
+                .is_some());
+            ibc_handler.callback_version_mismatch(&envelope).unwrap();
+            assert!(take_stub_invocations().is_empty(), "{port_id}");
+
+            // Callbacks are sent in the negotiated version from then on
+            if let Some(negotiated) = negotiated {
+                let version = Version::new("v1".to_owned());
+                STUB_MODULE.with(|cell| {
+                    *cell.borrow_mut() = Some(StubModule::Returns(Some(
+                        bincode::serialize(&version).unwrap(),
+                    )));
+                });
+                ibc_handler
+                    .get_route(&module_id)
+                    .unwrap()
+                    .on_chan_open_init_validate(
+                        Order::Unordered,
+                        &[ConnectionId::default()],
+                        &port_id,
+                        &ChannelId::new(0),
+                        &Counterparty::new(port_id.clone(), None),
+                        &version,
+                    )
+                    .unwrap();
+                let invocations = take_stub_invocations();
+                let (callback_version, _) = decode_callback(&invocations[0].data).unwrap();
+                assert_eq!(callback_version, negotiated, "{port_id}");
+            }
+            STUB_MODULE.with(|cell| *cell.borrow_mut() = None);
+        }
 
-        bincode::deserialize(&return_data).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })
+        // Rebinding asks the module again, in case it was upgraded
+        ibc_handler
+            .release_port(&rebound_port_id, &rebound_program_id)
+            .unwrap();
+        ibc_handler
+            .bind_port(&rebound_port_id, &rebound_program_id, Default::default())
+            .unwrap();
+        let module_id = EclipseModuleId::from_pubkey(rebound_program_id).to_module_id();
+        assert_eq!(
+            ibc_handler
+                .state
+                .get(&ModuleCallbackVersionsPath(module_id))
+                .unwrap(),
+            None,
+        );
     }
 
-    fn on_chan_close_confirm_validate(
-        &self,
-        port_id: &PortId,
-        channel_id: &ChannelId,
-    ) -> Result<(), ChannelError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnChanCloseConfirmValidate(OnChanCloseConfirmValidate {
-                port_id: port_id.clone(),
-                channel_id: channel_id.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
-
-        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })?;
+    #[test]
+    fn ics20_module_serves_the_transfer_port() {
+        let store = initialized_store();
 
-        Ok(())
+        let mut metadata = IbcMetadata::default();
+        let clock = Clock {
+            slot: 1,
+            ..Clock::default()
+        };
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock);
+        let module_id = ibc_handler
+            .lookup_module_by_port(&PortId::transfer())
+            .unwrap();
+        assert_eq!(module_id, ics20_module_id());
+        assert!(ibc_handler.has_route(&module_id));
+
+        let payer = Pubkey::new_unique();
+        let packet = Packet {
+            seq_on_a: Sequence::from(1),
+            port_id_on_a: PortId::transfer(),
+            chan_id_on_a: ChannelId::new(1),
+            port_id_on_b: PortId::transfer(),
+            chan_id_on_b: ChannelId::new(0),
+            data: serde_json::to_vec(&serde_json::json!({
+                "denom": "uatom",
+                "amount": "50",
+                "sender": "cosmos1sender",
+                "receiver": payer.to_string(),
+            }))
+            .unwrap(),
+            timeout_height_on_b: TimeoutHeight::At(Height::new(0, 100).unwrap()),
+            timeout_timestamp_on_b: Timestamp::none(),
+        };
+        let relayer = Signer::from(payer.to_string());
+        let (_, ack) = ibc_handler
+            .get_route_mut(&module_id)
+            .unwrap()
+            .on_recv_packet_execute(&packet, &relayer);
+        assert_eq!(ack.as_ref(), success_ack().as_ref());
+
+        // Only the payer can send its tokens, and only over an existing channel
+        let transfer = |sender: &Pubkey| {
+            MsgTransfer::try_from(RawMsgTransfer {
+                source_port: PortId::transfer().to_string(),
+                source_channel: ChannelId::new(0).to_string(),
+                token: Some(RawCoin {
+                    denom: "transfer/channel-0/uatom".to_owned(),
+                    amount: "20".to_owned(),
+                }),
+                sender: sender.to_string(),
+                receiver: "cosmos1sender".to_owned(),
+                timeout_height: Some(RawHeight {
+                    revision_number: 0,
+                    revision_height: 100,
+                }),
+                timeout_timestamp: 0,
+                memo: String::new(),
+            })
+            .unwrap()
+        };
+        let err = ibc_handler
+            .transfer(transfer(&Pubkey::new_unique()), &payer)
+            .unwrap_err();
+        assert!(err.to_string().contains("is not the payer"), "{err}");
+        ibc_handler.transfer(transfer(&payer), &payer).unwrap_err();
+
+        // The module's balances are committed along with the handler's state
+        ibc_handler.commit().unwrap();
+        let balance_path = Ics20BalancePath(payer, "transfer/channel-0/uatom".to_owned());
+        assert_eq!(
+            IbcState::new(&store, 1).get(&balance_path).unwrap(),
+            Some("50".to_owned()),
+        );
     }
 
-    fn on_chan_close_confirm_execute(
-        &mut self,
-        port_id: &PortId,
-        channel_id: &ChannelId,
-    ) -> Result<ModuleExtras, ChannelError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnChanCloseConfirmExecute(OnChanCloseConfirmExecute {
-                port_id: port_id.clone(),
-                channel_id: channel_id.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
-
-        invoke(&instruction, &[]).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })?;
+    #[test]
+    fn client_type_index_is_written_and_backfilled() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+
+        let client_state = clients::eclipse_client_state(1);
+
+        // Stored clients are indexed right away
+        let client_id = "xx-eclipse-0".parse::<ClientId>().unwrap();
+        ibc_handler
+            .store_client_state(
+                ClientStatePath::new(&client_id),
+                Box::new(client_state.clone()),
+            )
+            .unwrap();
+        assert_eq!(
+            ibc_handler
+                .state
+                .get(&ClientTypeUrlPath(client_id.clone()))
+                .unwrap()
+                .as_deref(),
+            Some(ECLIPSE_CLIENT_STATE_TYPE_URL),
+        );
 
-        let (_, return_data) = get_return_data().ok_or(ChannelError::Other {
-            description: "Return data missing".to_owned(),
-        })?;
+        // Clients stored before the index are indexed when first looked up
+        let legacy_client_id = "xx-eclipse-1".parse::<ClientId>().unwrap();
+        ibc_handler
+            .state
+            .set_any(
+                &ClientStatePath::new(&legacy_client_id),
+                client_state.encode_as_any(),
+            )
+            .unwrap();
+        let legacy_index_path = ClientTypeUrlPath(legacy_client_id.clone());
+        assert_eq!(ibc_handler.state.get(&legacy_index_path).unwrap(), None);
+        assert_eq!(
+            ibc_handler
+                .client_type_of(&legacy_client_id)
+                .unwrap()
+                .as_deref(),
+            Some(ECLIPSE_CLIENT_STATE_TYPE_URL),
+        );
+        assert_eq!(
+            ibc_handler
+                .state
+                .get(&legacy_index_path)
+                .unwrap()
+                .as_deref(),
+            Some(ECLIPSE_CLIENT_STATE_TYPE_URL),
+        );
 
-        bincode::deserialize(&return_data).map_err(|err| ChannelError::Other {
-            description: err.to_string(),
-        })
+        // The index is trusted without reading the client state
+        let indexed_only_client_id = ClientId::default();
+        ibc_handler.state.set(
+            &ClientTypeUrlPath(indexed_only_client_id.clone()),
+            "/indexed.only".to_owned(),
+        );
+        assert_eq!(
+            ibc_handler
+                .client_type_of(&indexed_only_client_id)
+                .unwrap()
+                .as_deref(),
+            Some("/indexed.only"),
+        );
+        let unknown_client_id = "xx-eclipse-9".parse::<ClientId>().unwrap();
+        assert_eq!(
+            ibc_handler.client_type_of(&unknown_client_id).unwrap(),
+            None
+        );
     }
 
-    fn on_recv_packet_execute(
-        &mut self,
-        packet: &Packet,
-        relayer: &Signer,
-    ) -> (ModuleExtras, Acknowledgement) {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnRecvPacketExecute(OnRecvPacketExecute {
-                packet: packet.clone(),
-                relayer: relayer.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
+    #[test]
+    fn update_with_header_of_another_client_type_is_rejected() {
+        let store = initialized_store();
 
-        // TODO: Check if `.unwrap` makes sense
-        invoke(&instruction, &[]).unwrap();
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
 
-        let (_, return_data) = get_return_data().expect("Return data missing");
+        let client_id = "xx-eclipse-0".parse::<ClientId>().unwrap();
+        ibc_handler.state.set(
+            &ClientTypeUrlPath(client_id.clone()),
+            ECLIPSE_CLIENT_STATE_TYPE_URL.to_owned(),
+        );
+        let update_client = |header_type_url: &str| {
+            MsgEnvelope::Client(ClientMsg::UpdateClient(
+                RawMsgUpdateClient {
+                    client_id: client_id.to_string(),
+                    header: Some(protobuf::Any {
+                        type_url: header_type_url.to_owned(),
+                        value: vec![],
+                    }),
+                    signer: "signer".to_owned(),
+                }
+                .try_into()
+                .unwrap(),
+            ))
+        };
 
-        bincode::deserialize(&return_data).unwrap()
+        let mismatch = ibc_handler
+            .client_type_mismatch(&update_client("/ibc.lightclients.tendermint.v1.Header"))
+            .unwrap()
+            .unwrap();
+        assert!(
+            mismatch.contains(ECLIPSE_CLIENT_STATE_TYPE_URL),
+            "{mismatch}"
+        );
+        assert_eq!(
+            ibc_handler
+                .client_type_mismatch(&update_client(ECLIPSE_HEADER_TYPE_URL))
+                .unwrap(),
+            None,
+        );
+        assert_eq!(
+            ibc_handler
+                .client_type_mismatch(&update_client(ECLIPSE_HEADER_BATCH_TYPE_URL))
+                .unwrap(),
+            None,
+        );
     }
 
-    fn on_acknowledgement_packet_validate(
-        &self,
-        packet: &Packet,
-        acknowledgement: &Acknowledgement,
-        relayer: &Signer,
-    ) -> Result<(), PacketError> {
-        let ibc_module_instruction = IbcModuleInstruction::OnAcknowledgementPacketValidate(
-            OnAcknowledgementPacketValidate {
-                packet: packet.clone(),
-                acknowledgement: acknowledgement.clone(),
-                relayer: relayer.clone(),
-            },
+    #[test]
+    fn legacy_type_urls_are_rewritten_until_rejected() {
+        let legacy = |any: protobuf::Any| {
+            let (legacy_type_url, _) = LEGACY_TYPE_URLS
+                .into_iter()
+                .find(|(_, canonical)| *canonical == any.type_url)
+                .unwrap();
+            protobuf::Any {
+                type_url: legacy_type_url.to_owned(),
+                value: any.value,
+            }
+        };
+        let create_client = || {
+            MsgEnvelope::Client(ClientMsg::CreateClient(
+                RawMsgCreateClient {
+                    client_state: Some(legacy(clients::eclipse_client_state(1).encode_as_any())),
+                    consensus_state: Some(legacy(
+                        EclipseConsensusState::from(clients::eclipse_header(1)).encode_as_any(),
+                    )),
+                    signer: "signer".to_owned(),
+                }
+                .try_into()
+                .unwrap(),
+            ))
+        };
+
+        let store = store_with_chain_params(ChainParams::default());
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        let mut envelope = create_client();
+        assert_eq!(
+            ibc_handler.canonicalize_type_urls(&mut envelope).unwrap(),
+            None
+        );
+        let warnings = ibc_handler.take_event_logs();
+        assert_eq!(warnings.len(), 2);
+        assert!(
+            warnings[0].contains(LEGACY_TYPE_URL_EVENT)
+                && warnings[0].contains("/eclipse.ibc.v1.chain.ClientState"),
+            "{}",
+            warnings[0]
+        );
+        assert_eq!(
+            ibc_handler.state.get(&LegacyTypeUrlCountPath).unwrap(),
+            Some(2)
+        );
+        dispatch(&mut ibc_handler, envelope).unwrap();
+        let client_id = "xx-eclipse-0".parse::<ClientId>().unwrap();
+        assert_eq!(
+            ibc_handler
+                .state
+                .get(&ClientTypeUrlPath(client_id))
+                .unwrap()
+                .as_deref(),
+            Some(ECLIPSE_CLIENT_STATE_TYPE_URL),
         );
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
 
-        invoke(&instruction, &[]).map_err(|_err| {
-            // TODO: Fix the IBC library to include an error message
-            PacketError::ImplementationSpecific
-        })?;
+        // Clients stored before the fix still decode
+        let stored_client_id = "xx-eclipse-1".parse::<ClientId>().unwrap();
+        ibc_handler.state.set(
+            &ClientStatePath::new(&stored_client_id),
+            legacy(clients::eclipse_client_state(1).encode_as_any()),
+        );
+        let stored_client_state = ibc_handler.client_state(&stored_client_id).unwrap();
+        assert_eq!(
+            stored_client_state
+                .as_any()
+                .downcast_ref::<EclipseClientState>(),
+            Some(&clients::eclipse_client_state(1)),
+        );
+        drop(ibc_handler);
 
-        Ok(())
+        // Once the rewrite is turned off, the same message is rejected
+        let store = store_with_chain_params(ChainParams {
+            reject_legacy_type_urls: true,
+            ..ChainParams::default()
+        });
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        let rejection = ibc_handler
+            .canonicalize_type_urls(&mut create_client())
+            .unwrap()
+            .unwrap();
+        assert!(
+            rejection.contains("/eclipse.ibc.v1.chain.ClientState"),
+            "{rejection}"
+        );
+        assert!(ibc_handler.take_event_logs().is_empty());
+        assert_eq!(
+            ibc_handler.state.get(&LegacyTypeUrlCountPath).unwrap(),
+            None
+        );
     }
 
-    fn on_acknowledgement_packet_execute(
-        &mut self,
-        packet: &Packet,
-        acknowledgement: &Acknowledgement,
-        relayer: &Signer,
-    ) -> (ModuleExtras, Result<(), PacketError>) {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnAcknowledgementPacketExecute(OnAcknowledgementPacketExecute {
-                packet: packet.clone(),
-                acknowledgement: acknowledgement.clone(),
-                relayer: relayer.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
+    #[test]
+    fn multihop_channels_are_rejected() {
+        let open_init = |connection_hops: &[&str]| {
+            let msg = RawMsgChannelOpenInit {
+                port_id: PortId::transfer().to_string(),
+                channel: Some(RawChannel {
+                    state: RawChannelState::Init as i32,
+                    ordering: RawOrder::Unordered as i32,
+                    counterparty: Some(RawChannelCounterparty {
+                        port_id: PortId::transfer().to_string(),
+                        channel_id: String::new(),
+                    }),
+                    connection_hops: connection_hops
+                        .iter()
+                        .map(|hop| (*hop).to_owned())
+                        .collect(),
+                    version: "ics20-1".to_owned(),
+                }),
+                signer: Pubkey::new_unique().to_string(),
+            };
+            MsgEnvelope::try_from(protobuf::Any {
+                type_url: "/ibc.core.channel.v1.MsgChannelOpenInit".to_owned(),
+                value: msg.encode_to_vec(),
+            })
+            .unwrap()
+        };
 
-        let result = invoke(&instruction, &[]).map_err(|_err| {
-            // TODO: Fix the IBC library to include an error message
-            PacketError::ImplementationSpecific
-        });
+        assert_eq!(
+            multihop_channel_rejection(&open_init(&["connection-0"])),
+            None
+        );
+        let rejection =
+            multihop_channel_rejection(&open_init(&["connection-0", "connection-1"])).unwrap();
+        assert!(
+            rejection.contains("multihop channels are not yet supported"),
+            "{rejection}"
+        );
+        assert!(
+            rejection.contains("connection-0,connection-1"),
+            "{rejection}"
+        );
+    }
+
+    #[test]
+    fn handshake_steps_meant_for_another_chain_are_rejected() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let clock = Clock {
+            slot: 1,
+            ..Clock::default()
+        };
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock);
+
+        let client_id = "xx-eclipse-0".parse::<ClientId>().unwrap();
+        let header = clients::eclipse_header(1);
+        ibc_handler
+            .store_client_state(
+                ClientStatePath::new(&client_id),
+                Box::new(clients::eclipse_client_state(1)),
+            )
+            .unwrap();
+        ibc_handler
+            .store_consensus_state(
+                ClientConsensusStatePath::new(&client_id, &header.height),
+                Box::new(EclipseConsensusState::from(header)),
+            )
+            .unwrap();
+
+        let connection_id = ConnectionId::default();
+        let connection_end = |state: RawConnectionState| {
+            ConnectionEnd::try_from(RawConnectionEnd {
+                client_id: client_id.to_string(),
+                versions: vec![RawConnectionVersion {
+                    identifier: "1".to_owned(),
+                    features: vec!["ORDER_ORDERED".to_owned(), "ORDER_UNORDERED".to_owned()],
+                }],
+                state: state as i32,
+                counterparty: Some(RawConnectionCounterparty {
+                    client_id: client_id.to_string(),
+                    connection_id: connection_id.to_string(),
+                    prefix: Some(RawMerklePrefix {
+                        key_prefix: b"ibc".to_vec(),
+                    }),
+                }),
+                delay_period: 0,
+            })
+            .unwrap()
+        };
+        ibc_handler
+            .store_connection(
+                &ConnectionPath::new(&connection_id),
+                connection_end(RawConnectionState::Tryopen),
+            )
+            .unwrap();
+
+        let proof_height = |revision_number, revision_height| {
+            Some(RawHeight {
+                revision_number,
+                revision_height,
+            })
+        };
+        let connection_open_confirm = |proof_height| {
+            MsgEnvelope::try_from(protobuf::Any {
+                type_url: "/ibc.core.connection.v1.MsgConnectionOpenConfirm".to_owned(),
+                value: RawMsgConnectionOpenConfirm {
+                    connection_id: connection_id.to_string(),
+                    proof_ack: vec![1],
+                    proof_height,
+                    signer: Pubkey::new_unique().to_string(),
+                }
+                .encode_to_vec(),
+            })
+            .unwrap()
+        };
 
-        // TODO: Fix `ModuleExtras` deserialization upstream
-        (ModuleExtras::empty(), result)
+        assert_eq!(
+            ibc_handler
+                .handshake_chain_mismatch(&connection_open_confirm(proof_height(0, 1)))
+                .unwrap(),
+            None,
+        );
+        // Proven at a height of a chain the connection's client does not track
+        let mismatch = ibc_handler
+            .handshake_chain_mismatch(&connection_open_confirm(proof_height(0, 9)))
+            .unwrap()
+            .unwrap();
+        assert!(mismatch.contains("no consensus state"), "{mismatch}");
+        let mismatch = ibc_handler
+            .handshake_chain_mismatch(&connection_open_confirm(proof_height(4, 1)))
+            .unwrap()
+            .unwrap();
+        assert!(mismatch.contains("revision 0"), "{mismatch}");
+
+        // A connection with the same ID, but at another step of its handshake
+        ibc_handler
+            .store_connection(
+                &ConnectionPath::new(&connection_id),
+                connection_end(RawConnectionState::Open),
+            )
+            .unwrap();
+        let mismatch = ibc_handler
+            .handshake_chain_mismatch(&connection_open_confirm(proof_height(0, 1)))
+            .unwrap()
+            .unwrap();
+        assert!(mismatch.contains("STATE_OPEN"), "{mismatch}");
+
+        let port_id = PortId::transfer();
+        let channel_id = ChannelId::new(0);
+        ibc_handler
+            .store_channel(
+                &ChannelEndPath::new(&port_id, &channel_id),
+                ChannelEnd::try_from(RawChannel {
+                    state: RawChannelState::Tryopen as i32,
+                    ordering: RawOrder::Unordered as i32,
+                    counterparty: Some(RawChannelCounterparty {
+                        port_id: port_id.to_string(),
+                        channel_id: channel_id.to_string(),
+                    }),
+                    connection_hops: vec![connection_id.to_string()],
+                    version: "ics20-1".to_owned(),
+                })
+                .unwrap(),
+            )
+            .unwrap();
+        let channel_open_confirm = |proof_height| {
+            MsgEnvelope::try_from(protobuf::Any {
+                type_url: "/ibc.core.channel.v1.MsgChannelOpenConfirm".to_owned(),
+                value: RawMsgChannelOpenConfirm {
+                    port_id: port_id.to_string(),
+                    channel_id: channel_id.to_string(),
+                    proof_ack: vec![1],
+                    proof_height,
+                    signer: Pubkey::new_unique().to_string(),
+                }
+                .encode_to_vec(),
+            })
+            .unwrap()
+        };
+        assert_eq!(
+            ibc_handler
+                .handshake_chain_mismatch(&channel_open_confirm(proof_height(0, 1)))
+                .unwrap(),
+            None,
+        );
+        let mismatch = ibc_handler
+            .handshake_chain_mismatch(&channel_open_confirm(proof_height(0, 9)))
+            .unwrap()
+            .unwrap();
+        assert!(mismatch.contains("no consensus state"), "{mismatch}");
     }
 
-    fn on_timeout_packet_validate(
-        &self,
-        packet: &Packet,
-        relayer: &Signer,
-    ) -> Result<(), PacketError> {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnTimeoutPacketValidate(OnTimeoutPacketValidate {
-                packet: packet.clone(),
-                relayer: relayer.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
+    #[test]
+    fn client_and_consensus_states_are_stored_as_a_single_any() {
+        let store = initialized_store();
 
-        invoke(&instruction, &[]).map_err(|_err| {
-            // TODO: Fix the IBC library to include an error message
-            PacketError::ImplementationSpecific
-        })?;
+        let mut metadata = IbcMetadata::default();
+        let clock = Clock {
+            slot: 1,
+            ..Clock::default()
+        };
+        let mut ibc_handler = new_handler(&store, &mut metadata, &clock);
+
+        let client_id = ClientId::default();
+        let client_state = clients::eclipse_client_state(1);
+        let consensus_state = TendermintConsensusState::new(
+            CommitmentRoot::from_bytes(&[2; 32]),
+            TendermintTime::unix_epoch(),
+            tendermint::Hash::Sha256([3; 32]),
+        );
+        let client_state_path = ClientStatePath::new(&client_id);
+        let consensus_state_path =
+            ClientConsensusStatePath::new(&client_id, &client_state.latest_header.height);
+        ibc_handler
+            .store_client_state(client_state_path.clone(), Box::new(client_state.clone()))
+            .unwrap();
+        ibc_handler
+            .store_consensus_state(
+                consensus_state_path.clone(),
+                Box::new(consensus_state.clone()),
+            )
+            .unwrap();
+        ibc_handler.commit().unwrap();
+
+        // The stored bytes are what counterparties verify proofs against
+        let expected_client_state = protobuf::Any {
+            type_url: ECLIPSE_CLIENT_STATE_TYPE_URL.to_owned(),
+            value: RawEclipseClientState::from(client_state).encode_to_vec(),
+        };
+        assert_eq!(
+            ibc_handler
+                .state
+                .get_proof(&client_state_path)
+                .unwrap()
+                .value,
+            expected_client_state.encode_to_vec(),
+        );
+        let expected_consensus_state = protobuf::Any {
+            type_url: "/ibc.lightclients.tendermint.v1.ConsensusState".to_owned(),
+            value: RawTmConsensusState::from(consensus_state.clone()).encode_to_vec(),
+        };
+        assert_eq!(
+            ibc_handler
+                .state
+                .get_proof(&consensus_state_path)
+                .unwrap()
+                .value,
+            expected_consensus_state.encode_to_vec(),
+        );
 
-        Ok(())
+        // Consensus states stored before the extra `Any` was dropped are still read
+        let legacy_consensus_state = protobuf::Any {
+            type_url: expected_consensus_state.type_url.clone(),
+            value: expected_consensus_state.encode_to_vec(),
+        };
+        ibc_handler
+            .state
+            .set_any(&consensus_state_path, legacy_consensus_state.clone())
+            .unwrap_err();
+        ibc_handler
+            .state
+            .set(&consensus_state_path, legacy_consensus_state);
+        let decoded_consensus_state =
+            ValidationContext::consensus_state(&ibc_handler, &consensus_state_path).unwrap();
+        assert_eq!(
+            decoded_consensus_state
+                .as_any()
+                .downcast_ref::<TendermintConsensusState>(),
+            Some(&consensus_state),
+        );
     }
 
-    fn on_timeout_packet_execute(
-        &mut self,
-        packet: &Packet,
-        relayer: &Signer,
-    ) -> (ModuleExtras, Result<(), PacketError>) {
-        let ibc_module_instruction =
-            IbcModuleInstruction::OnTimeoutPacketExecute(OnTimeoutPacketExecute {
-                packet: packet.clone(),
-                relayer: relayer.clone(),
-            });
-        let instruction =
-            Instruction::new_with_bincode(self.program_id, &ibc_module_instruction, vec![]);
+    #[test]
+    fn eclipse_clients_follow_revision_bumps() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        let client_id = "xx-eclipse-0".parse::<ClientId>().unwrap();
+        let header = |revision_number, revision_height| EclipseHeader {
+            height: Height::new(revision_number, revision_height).unwrap(),
+            ..clients::eclipse_header(revision_height)
+        };
+        ibc_handler
+            .store_client_state(
+                ClientStatePath::new(&client_id),
+                Box::new(clients::eclipse_client_state(3)),
+            )
+            .unwrap();
+        ibc_handler
+            .store_consensus_state(
+                ClientConsensusStatePath::new(&client_id, &header(0, 3).height),
+                Box::new(EclipseConsensusState::from(header(0, 3))),
+            )
+            .unwrap();
+        let update_client = |header: EclipseHeader| {
+            MsgEnvelope::Client(ClientMsg::UpdateClient(
+                RawMsgUpdateClient {
+                    client_id: client_id.to_string(),
+                    header: Some(header.encode_as_any()),
+                    signer: Pubkey::new_unique().to_string(),
+                }
+                .try_into()
+                .unwrap(),
+            ))
+        };
+        let client_state = |ibc_handler: &IbcHandler| {
+            ibc_handler
+                .client_state(&client_id)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<EclipseClientState>()
+                .unwrap()
+                .clone()
+        };
 
-        let result = invoke(&instruction, &[]).map_err(|_err| {
-            // TODO: Fix the IBC library to include an error message
-            PacketError::ImplementationSpecific
-        });
+        // Moving to the next revision renames the tracked chain
+        dispatch(&mut ibc_handler, update_client(header(1, 10))).unwrap();
+        let bumped = client_state(&ibc_handler);
+        assert_eq!(bumped.revision_number, 1);
+        assert_eq!(
+            bumped.chain_id,
+            eclipse_chain::chain_id_with_revision(clients::CHAIN_NAME, 1)
+        );
+        assert_eq!(bumped.latest_height(), header(1, 10).height);
 
-        // TODO: Fix `ModuleExtras` deserialization upstream
-        (ModuleExtras::empty(), result)
+        // Revisions cannot be skipped, and slots keep counting across them
+        let err = dispatch(&mut ibc_handler, update_client(header(3, 20))).unwrap_err();
+        assert!(
+            err.to_string().contains("does not continue revision 1"),
+            "{err}"
+        );
+        dispatch(&mut ibc_handler, update_client(header(2, 9))).unwrap_err();
+        dispatch(&mut ibc_handler, update_client(header(1, 5))).unwrap_err();
+
+        dispatch(&mut ibc_handler, update_client(header(1, 20))).unwrap();
+        assert_eq!(client_state(&ibc_handler).revision_number, 1);
     }
-}
 
-impl SolanaModule {
-    fn into_box(self) -> Box<dyn Module> {
-        Box::new(self)
+    #[test]
+    fn forged_misbehaviour_does_not_freeze_eclipse_clients() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        let client_id = "xx-eclipse-0".parse::<ClientId>().unwrap();
+        let stored_header = clients::eclipse_header(3);
+        ibc_handler
+            .store_client_state(
+                ClientStatePath::new(&client_id),
+                Box::new(clients::eclipse_client_state(3)),
+            )
+            .unwrap();
+        ibc_handler
+            .store_consensus_state(
+                ClientConsensusStatePath::new(&client_id, &stored_header.height),
+                Box::new(EclipseConsensusState::from(stored_header.clone())),
+            )
+            .unwrap();
+
+        // One header matches the stored consensus state, and the other is made
+        // up, which nothing on chain can tell apart from real evidence
+        let forged_header = EclipseHeader {
+            commitment_root: CommitmentRoot::from_bytes(&[0xff; 32]),
+            ..stored_header.clone()
+        };
+        let misbehaviour =
+            EclipseMisbehaviour::new(client_id.to_string(), stored_header, forged_header).unwrap();
+        let submit_misbehaviour = MsgEnvelope::Client(ClientMsg::Misbehaviour(
+            RawMsgSubmitMisbehaviour {
+                client_id: client_id.to_string(),
+                misbehaviour: Some(misbehaviour.encode_as_any()),
+                signer: Pubkey::new_unique().to_string(),
+            }
+            .try_into()
+            .unwrap(),
+        ));
+
+        let err = dispatch(&mut ibc_handler, submit_misbehaviour).unwrap_err();
+        assert!(err.to_string().contains("cannot be verified"), "{err}");
+        let client_state = ibc_handler.client_state(&client_id).unwrap();
+        let client_state = client_state
+            .as_any()
+            .downcast_ref::<EclipseClientState>()
+            .unwrap();
+        assert_eq!(client_state.frozen_height, None);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn eclipse_header_batches_store_a_consensus_state_per_header() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        let client_id = "xx-eclipse-0".parse::<ClientId>().unwrap();
+        ibc_handler
+            .store_client_state(
+                ClientStatePath::new(&client_id),
+                Box::new(clients::eclipse_client_state(3)),
+            )
+            .unwrap();
+        ibc_handler
+            .store_consensus_state(
+                ClientConsensusStatePath::new(&client_id, &clients::eclipse_header(3).height),
+                Box::new(EclipseConsensusState::from(clients::eclipse_header(3))),
+            )
+            .unwrap();
+        ibc_handler.take_event_logs();
+        // Each header commits to its own root, so that the consensus states can
+        // be told apart
+        let header = |revision_height: u64| EclipseHeader {
+            commitment_root: CommitmentRoot::from_bytes(&[revision_height as u8; 32]),
+            ..clients::eclipse_header(revision_height)
+        };
+        let update_client = |batch: RawEclipseHeaderBatch| {
+            MsgEnvelope::Client(ClientMsg::UpdateClient(
+                RawMsgUpdateClient {
+                    client_id: client_id.to_string(),
+                    header: Some(protobuf::Any {
+                        type_url: ECLIPSE_HEADER_BATCH_TYPE_URL.to_owned(),
+                        value: batch.encode_to_vec(),
+                    }),
+                    signer: Pubkey::new_unique().to_string(),
+                }
+                .try_into()
+                .unwrap(),
+            ))
+        };
+        let batch = |revision_heights: &[u64]| RawEclipseHeaderBatch {
+            headers: revision_heights
+                .iter()
+                .map(|revision_height| header(*revision_height).into())
+                .collect(),
+        };
+
+        let revision_heights = (1..=10).map(|i| i * 10).collect::<Vec<_>>();
+        dispatch(&mut ibc_handler, update_client(batch(&revision_heights))).unwrap();
+        let client_state = ibc_handler.client_state(&client_id).unwrap();
+        assert_eq!(client_state.latest_height(), header(100).height);
+        for revision_height in &revision_heights {
+            let height = header(*revision_height).height;
+            let consensus_state = ValidationContext::consensus_state(
+                &ibc_handler,
+                &ClientConsensusStatePath::new(&client_id, &height),
+            )
+            .unwrap();
+            assert_eq!(
+                consensus_state
+                    .as_any()
+                    .downcast_ref::<EclipseConsensusState>(),
+                Some(&EclipseConsensusState::from(header(*revision_height))),
+            );
+            assert!(ibc_handler
+                .state
+                .contains(&ClientUpdateHeightPath(client_id.clone(), height))
+                .unwrap());
+        }
+        assert_eq!(
+            ibc_handler
+                .state
+                .get(&ConsensusHeightsPath(client_id.clone()))
+                .unwrap()
+                .unwrap()
+                .heights
+                .len(),
+            11
+        );
+        // The update is reported at the latest height
+        let payloads = ibc_handler
+            .take_event_logs()
+            .iter()
+            .filter_map(|log| {
+                let event = ibc_events::parse_event_log(log).unwrap().unwrap();
+                UpdateClientPayload::from_event(&event).unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(payloads[0].consensus_height, "0-100");
+        assert_eq!(payloads[0].previous_latest_height.as_deref(), Some("0-3"));
+
+        // Batches must continue the client, hold increasing heights, and fit the
+        // cap
+        for revision_heights in [
+            vec![100, 110],
+            vec![120, 110],
+            (1..=MAX_HEADERS_PER_BATCH as u64 + 1)
+                .map(|i| 100 + i)
+                .collect(),
+        ] {
+            dispatch(&mut ibc_handler, update_client(batch(&revision_heights))).unwrap_err();
+        }
+        dispatch(&mut ibc_handler, update_client(batch(&[]))).unwrap_err();
+        assert_eq!(
+            ibc_handler
+                .client_state(&client_id)
+                .unwrap()
+                .latest_height(),
+            header(100).height
+        );
+    }
 
     #[test]
-    fn module_id_of_pubkey_to_string() {
-        let pubkey = Pubkey::new_unique();
+    fn update_client_events_name_the_previous_latest_height() {
+        let store = initialized_store();
+
+        let mut metadata = IbcMetadata::default();
+        let mut ibc_handler = new_handler(&store, &mut metadata, &Clock::default());
+        let update_client_payloads = |ibc_handler: &mut IbcHandler| {
+            ibc_handler
+                .take_event_logs()
+                .iter()
+                .filter_map(|log| {
+                    let event = ibc_events::parse_event_log(log).unwrap().unwrap();
+                    UpdateClientPayload::from_event(&event).unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Eclipse clients, updated through ibc-rs, can skip heights
+        let eclipse_client_id = "xx-eclipse-0".parse::<ClientId>().unwrap();
+        let header = clients::eclipse_header;
+        ibc_handler
+            .store_client_state(
+                ClientStatePath::new(&eclipse_client_id),
+                Box::new(clients::eclipse_client_state(3)),
+            )
+            .unwrap();
+        ibc_handler
+            .store_consensus_state(
+                ClientConsensusStatePath::new(&eclipse_client_id, &header(3).height),
+                Box::new(EclipseConsensusState::from(header(3))),
+            )
+            .unwrap();
+        // Creating a client has no previous height
+        assert!(ibc_handler.previous_latest_heights.is_empty());
+
+        let envelope = MsgEnvelope::Client(ClientMsg::UpdateClient(
+            RawMsgUpdateClient {
+                client_id: eclipse_client_id.to_string(),
+                header: Some(header(10).encode_as_any()),
+                signer: Pubkey::new_unique().to_string(),
+            }
+            .try_into()
+            .unwrap(),
+        ));
+        ibc::core::dispatch(&mut ibc_handler, envelope).unwrap();
+        assert_eq!(
+            update_client_payloads(&mut ibc_handler),
+            [UpdateClientPayload {
+                client_id: "xx-eclipse-0".to_owned(),
+                client_type: "xx-eclipse".to_owned(),
+                consensus_height: "0-10".to_owned(),
+                previous_latest_height: Some("0-3".to_owned()),
+            }]
+        );
+        assert!(ibc_handler.previous_latest_heights.is_empty());
+
+        // Tendermint clients are updated in ibc-rs, which stores the new client
+        // state and then emits the event
+        let tendermint_client_id = "07-tendermint-0".parse::<ClientId>().unwrap();
+        let tendermint_client_state = |revision_height| {
+            decode_client_state(clients::tendermint_client_state_any(revision_height)).unwrap()
+        };
+        let tendermint_client_state_path = ClientStatePath::new(&tendermint_client_id);
+        ibc_handler
+            .store_client_state(
+                tendermint_client_state_path.clone(),
+                tendermint_client_state(100),
+            )
+            .unwrap();
+        ibc_handler
+            .store_client_state(tendermint_client_state_path, tendermint_client_state(107))
+            .unwrap();
+        ibc_handler.emit_ibc_event(IbcEvent::UpdateClient(UpdateClient::new(
+            tendermint_client_id,
+            ClientType::new("07-tendermint".to_owned()).unwrap(),
+            Height::new(4, 107).unwrap(),
+            vec![Height::new(4, 107).unwrap()],
+            protobuf::Any::default(),
+        )));
         assert_eq!(
-            module_id_of_pubkey(&pubkey).to_string(),
-            "0000000000000001000000000000000000000000000000000000000000000000",
+            update_client_payloads(&mut ibc_handler),
+            [UpdateClientPayload {
+                client_id: "07-tendermint-0".to_owned(),
+                client_type: "07-tendermint".to_owned(),
+                consensus_height: "4-107".to_owned(),
+                previous_latest_height: Some("4-100".to_owned()),
+            }]
         );
     }
 }
@@ -1,34 +1,61 @@
 use {
     eclipse_ibc_known_proto::{KnownAnyProto, KnownProto, KnownProtoWithFrom},
-    ibc::core::{
-        ics02_client::msgs::ClientMsg,
-        ics03_connection::msgs::ConnectionMsg,
-        ics04_channel::msgs::{ChannelMsg, PacketMsg},
-        Msg as _, MsgEnvelope, RouterError,
+    ibc::{
+        applications::transfer::msgs::transfer::{MsgTransfer, TYPE_URL as TRANSFER_TYPE_URL},
+        core::{
+            ics02_client::msgs::ClientMsg,
+            ics03_connection::msgs::ConnectionMsg,
+            ics04_channel::msgs::{ChannelMsg, PacketMsg},
+            Msg as _, MsgEnvelope, RouterError,
+        },
     },
-    ibc_proto::google::protobuf,
+    ibc_proto::{google::protobuf, ibc::applications::transfer::v1::MsgTransfer as RawMsgTransfer},
+    prost::Message as _,
     thiserror::Error,
 };
 
 pub mod msgs {
     use {
         anyhow::anyhow,
-        core::{convert::Infallible, str::FromStr},
-        eclipse_ibc_known_proto::{KnownAnyProto, KnownProtoWithFrom},
+        core::str::FromStr,
+        eclipse_ibc_extra_types::{ChainParams, ModuleAccountTemplates},
+        eclipse_ibc_known_proto::{KnownAnyProto, KnownProto, KnownProtoWithFrom},
         eclipse_ibc_proto::eclipse::ibc::{
             admin::v1::{
                 msg_write_tx_buffer::Mode as RawMsgWriteTxBufferMode,
+                store_write::Op as RawStoreWriteOp, IndexKind as RawIndexKind,
+                MsgAbortStoreWriteBatch as RawMsgAbortStoreWriteBatch,
+                MsgAcceptAdminChange as RawMsgAcceptAdminChange,
+                MsgCancelAdminChange as RawMsgCancelAdminChange, MsgClaimAdmin as RawMsgClaimAdmin,
+                MsgCloseTxBuffer as RawMsgCloseTxBuffer,
+                MsgForceReleasePort as RawMsgForceReleasePort,
                 MsgInitStorageAccount as RawMsgInitStorageAccount,
-                MsgWriteTxBuffer as RawMsgWriteTxBuffer,
+                MsgProposeAdminChange as RawMsgProposeAdminChange,
+                MsgPruneState as RawMsgPruneState,
+                MsgReallocStorageAccount as RawMsgReallocStorageAccount,
+                MsgRefreshCapabilities as RawMsgRefreshCapabilities,
+                MsgRepairIndices as RawMsgRepairIndices, MsgSetIbcFrozen as RawMsgSetIbcFrozen,
+                MsgSetRevisionNumber as RawMsgSetRevisionNumber,
+                MsgStoreWriteBatch as RawMsgStoreWriteBatch,
+                MsgUpdateChainParams as RawMsgUpdateChainParams,
+                MsgWriteTxBuffer as RawMsgWriteTxBuffer, MsgWriteUpgrade as RawMsgWriteUpgrade,
+                StoreWrite as RawStoreWrite,
+            },
+            port::v1::{
+                MsgBindPort as RawMsgBindPort, MsgReleasePort as RawMsgReleasePort,
+                MsgTransferPort as RawMsgTransferPort,
             },
-            port::v1::{MsgBindPort as RawMsgBindPort, MsgReleasePort as RawMsgReleasePort},
         },
+        eclipse_ibc_state::IndexKind,
         ibc::core::ics24_host::identifier::PortId,
+        ibc_proto::google::protobuf,
+        solana_sdk::{hash::hash, pubkey::Pubkey},
     };
 
     #[derive(Clone, Debug)]
     pub struct MsgBindPort {
         pub port_id: PortId,
+        pub account_templates: ModuleAccountTemplates,
     }
 
     impl MsgBindPort {
@@ -46,18 +73,45 @@ pub mod msgs {
     }
 
     impl TryFrom<RawMsgBindPort> for MsgBindPort {
-        type Error = <PortId as FromStr>::Err;
+        type Error = anyhow::Error;
 
-        fn try_from(RawMsgBindPort { port_id }: RawMsgBindPort) -> Result<Self, Self::Error> {
+        fn try_from(
+            RawMsgBindPort {
+                port_id,
+                account_templates,
+            }: RawMsgBindPort,
+        ) -> Result<Self, Self::Error> {
             let port_id = port_id.parse()?;
-            Ok(Self { port_id })
+            let account_templates = ModuleAccountTemplates::new(
+                account_templates
+                    .into_iter()
+                    .map(TryFrom::try_from)
+                    .collect::<anyhow::Result<_>>()?,
+            )?;
+            Ok(Self {
+                port_id,
+                account_templates,
+            })
         }
     }
 
     impl From<MsgBindPort> for RawMsgBindPort {
-        fn from(MsgBindPort { port_id }: MsgBindPort) -> Self {
+        fn from(
+            MsgBindPort {
+                port_id,
+                account_templates,
+            }: MsgBindPort,
+        ) -> Self {
             let port_id = port_id.to_string();
-            Self { port_id }
+            let account_templates = account_templates
+                .templates
+                .into_iter()
+                .map(Into::into)
+                .collect();
+            Self {
+                port_id,
+                account_templates,
+            }
         }
     }
 
@@ -97,7 +151,67 @@ pub mod msgs {
     }
 
     #[derive(Clone, Debug)]
-    pub struct MsgInitStorageAccount;
+    pub struct MsgTransferPort {
+        pub port_id: PortId,
+        pub new_module_pubkey: Pubkey,
+    }
+
+    impl MsgTransferPort {
+        pub const TYPE_URL: &str = "/eclipse.ibc.port.v1.MsgTransferPort";
+    }
+
+    impl KnownProtoWithFrom for MsgTransferPort {
+        type RawWithFrom = RawMsgTransferPort;
+    }
+
+    impl KnownAnyProto for MsgTransferPort {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl TryFrom<RawMsgTransferPort> for MsgTransferPort {
+        type Error = anyhow::Error;
+
+        fn try_from(
+            RawMsgTransferPort {
+                port_id,
+                new_module_pubkey,
+            }: RawMsgTransferPort,
+        ) -> Result<Self, Self::Error> {
+            let port_id = port_id.parse()?;
+            let new_module_pubkey = new_module_pubkey
+                .parse()
+                .map_err(|err| anyhow!("invalid new module pubkey {new_module_pubkey}: {err}"))?;
+            Ok(Self {
+                port_id,
+                new_module_pubkey,
+            })
+        }
+    }
+
+    impl From<MsgTransferPort> for RawMsgTransferPort {
+        fn from(
+            MsgTransferPort {
+                port_id,
+                new_module_pubkey,
+            }: MsgTransferPort,
+        ) -> Self {
+            Self {
+                port_id: port_id.to_string(),
+                new_module_pubkey: new_module_pubkey.to_string(),
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, Default)]
+    pub struct MsgInitStorageAccount {
+        /// Genesis hash of this cluster, which is recorded in the chain params.
+        pub genesis_hash: Option<[u8; 32]>,
+        /// Whether to succeed without changes if the storage account is already
+        /// initialized. A storage account that exists but is malformed still fails.
+        pub idempotent: bool,
+    }
 
     impl MsgInitStorageAccount {
         pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgInitStorageAccount";
@@ -114,18 +228,39 @@ pub mod msgs {
     }
 
     impl TryFrom<RawMsgInitStorageAccount> for MsgInitStorageAccount {
-        type Error = Infallible;
+        type Error = anyhow::Error;
 
         fn try_from(
-            RawMsgInitStorageAccount {}: RawMsgInitStorageAccount,
+            RawMsgInitStorageAccount {
+                genesis_hash,
+                idempotent,
+            }: RawMsgInitStorageAccount,
         ) -> Result<Self, Self::Error> {
-            Ok(Self)
+            let genesis_hash = if genesis_hash.is_empty() {
+                None
+            } else {
+                Some(genesis_hash.try_into().map_err(|hash: Vec<u8>| {
+                    anyhow!("genesis hash must be 32 bytes, got {}", hash.len())
+                })?)
+            };
+            Ok(Self {
+                genesis_hash,
+                idempotent,
+            })
         }
     }
 
     impl From<MsgInitStorageAccount> for RawMsgInitStorageAccount {
-        fn from(MsgInitStorageAccount: MsgInitStorageAccount) -> Self {
-            Self {}
+        fn from(
+            MsgInitStorageAccount {
+                genesis_hash,
+                idempotent,
+            }: MsgInitStorageAccount,
+        ) -> Self {
+            Self {
+                genesis_hash: genesis_hash.map(Vec::from).unwrap_or_default(),
+                idempotent,
+            }
         }
     }
 
@@ -194,12 +329,670 @@ pub mod msgs {
             }
         }
     }
+
+    /// Closes a tx buffer created by the signing payer.
+    #[derive(Clone, Debug)]
+    pub struct MsgCloseTxBuffer;
+
+    impl MsgCloseTxBuffer {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgCloseTxBuffer";
+    }
+
+    impl KnownProtoWithFrom for MsgCloseTxBuffer {
+        type RawWithFrom = RawMsgCloseTxBuffer;
+    }
+
+    impl KnownAnyProto for MsgCloseTxBuffer {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl From<RawMsgCloseTxBuffer> for MsgCloseTxBuffer {
+        fn from(RawMsgCloseTxBuffer {}: RawMsgCloseTxBuffer) -> Self {
+            Self
+        }
+    }
+
+    impl From<MsgCloseTxBuffer> for RawMsgCloseTxBuffer {
+        fn from(MsgCloseTxBuffer: MsgCloseTxBuffer) -> Self {
+            Self {}
+        }
+    }
+
+    /// A raw store write. A `value` of `None` removes the entry at `path`.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct StoreWrite {
+        pub path: String,
+        pub value: Option<Vec<u8>>,
+    }
+
+    impl TryFrom<RawStoreWrite> for StoreWrite {
+        type Error = anyhow::Error;
+
+        fn try_from(RawStoreWrite { path, op }: RawStoreWrite) -> Result<Self, Self::Error> {
+            let value = match op.ok_or_else(|| anyhow!("Op cannot be None for path {path}"))? {
+                RawStoreWriteOp::SetValue(value) => Some(value),
+                RawStoreWriteOp::Remove(_) => None,
+            };
+            Ok(Self { path, value })
+        }
+    }
+
+    impl From<StoreWrite> for RawStoreWrite {
+        fn from(StoreWrite { path, value }: StoreWrite) -> Self {
+            let op = match value {
+                Some(value) => RawStoreWriteOp::SetValue(value),
+                None => RawStoreWriteOp::Remove(true),
+            };
+            Self { path, op: Some(op) }
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct MsgStoreWriteBatch {
+        pub chunk_index: u64,
+        pub writes: Vec<StoreWrite>,
+        /// `None` for the last chunk in the batch.
+        pub next_chunk_hash: Option<[u8; 32]>,
+    }
+
+    impl MsgStoreWriteBatch {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgStoreWriteBatch";
+
+        /// Hash that the previous chunk in the batch must commit to.
+        #[must_use]
+        pub fn chunk_hash(&self) -> [u8; 32] {
+            hash(&self.clone().encode()).to_bytes()
+        }
+    }
+
+    impl KnownProtoWithFrom for MsgStoreWriteBatch {
+        type RawWithFrom = RawMsgStoreWriteBatch;
+    }
+
+    impl KnownAnyProto for MsgStoreWriteBatch {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl TryFrom<RawMsgStoreWriteBatch> for MsgStoreWriteBatch {
+        type Error = anyhow::Error;
+
+        fn try_from(
+            RawMsgStoreWriteBatch {
+                chunk_index,
+                writes,
+                next_chunk_hash,
+            }: RawMsgStoreWriteBatch,
+        ) -> Result<Self, Self::Error> {
+            let writes = writes
+                .into_iter()
+                .map(StoreWrite::try_from)
+                .collect::<anyhow::Result<_>>()?;
+            let next_chunk_hash = if next_chunk_hash.is_empty() {
+                None
+            } else {
+                Some(next_chunk_hash.try_into().map_err(|hash: Vec<u8>| {
+                    anyhow!("next chunk hash must be 32 bytes, got {}", hash.len())
+                })?)
+            };
+            Ok(Self {
+                chunk_index,
+                writes,
+                next_chunk_hash,
+            })
+        }
+    }
+
+    impl From<MsgStoreWriteBatch> for RawMsgStoreWriteBatch {
+        fn from(
+            MsgStoreWriteBatch {
+                chunk_index,
+                writes,
+                next_chunk_hash,
+            }: MsgStoreWriteBatch,
+        ) -> Self {
+            Self {
+                chunk_index,
+                writes: writes.into_iter().map(Into::into).collect(),
+                next_chunk_hash: next_chunk_hash.map(Vec::from).unwrap_or_default(),
+            }
+        }
+    }
+
+    /// Drops the store write batch in progress, keeping the chunks already
+    /// applied, so that other messages are accepted again.
+    #[derive(Clone, Debug)]
+    pub struct MsgAbortStoreWriteBatch;
+
+    impl MsgAbortStoreWriteBatch {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgAbortStoreWriteBatch";
+    }
+
+    impl KnownProtoWithFrom for MsgAbortStoreWriteBatch {
+        type RawWithFrom = RawMsgAbortStoreWriteBatch;
+    }
+
+    impl KnownAnyProto for MsgAbortStoreWriteBatch {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl From<RawMsgAbortStoreWriteBatch> for MsgAbortStoreWriteBatch {
+        fn from(RawMsgAbortStoreWriteBatch {}: RawMsgAbortStoreWriteBatch) -> Self {
+            Self
+        }
+    }
+
+    impl From<MsgAbortStoreWriteBatch> for RawMsgAbortStoreWriteBatch {
+        fn from(MsgAbortStoreWriteBatch: MsgAbortStoreWriteBatch) -> Self {
+            Self {}
+        }
+    }
+
+    /// Replaces the chain params. This message is never subject to
+    /// `ChainParams::disabled_messages`, so operators cannot lock themselves out.
+    #[derive(Clone, Debug)]
+    pub struct MsgUpdateChainParams {
+        pub params: ChainParams,
+    }
+
+    impl MsgUpdateChainParams {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgUpdateChainParams";
+    }
+
+    impl KnownProtoWithFrom for MsgUpdateChainParams {
+        type RawWithFrom = RawMsgUpdateChainParams;
+    }
+
+    impl KnownAnyProto for MsgUpdateChainParams {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl TryFrom<RawMsgUpdateChainParams> for MsgUpdateChainParams {
+        type Error = anyhow::Error;
+
+        fn try_from(
+            RawMsgUpdateChainParams { params }: RawMsgUpdateChainParams,
+        ) -> Result<Self, Self::Error> {
+            let params = ChainParams::from_raw(params.unwrap_or_default())?;
+            Ok(Self { params })
+        }
+    }
+
+    impl From<MsgUpdateChainParams> for RawMsgUpdateChainParams {
+        fn from(MsgUpdateChainParams { params }: MsgUpdateChainParams) -> Self {
+            Self {
+                params: Some(params.into_raw()),
+            }
+        }
+    }
+
+    /// Pauses or resumes all IBC activity. Like `MsgUpdateChainParams`, this is
+    /// accepted while frozen and is never disabled.
+    #[derive(Clone, Debug)]
+    pub struct MsgSetIbcFrozen {
+        pub frozen: bool,
+    }
+
+    impl MsgSetIbcFrozen {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgSetIbcFrozen";
+    }
+
+    impl KnownProtoWithFrom for MsgSetIbcFrozen {
+        type RawWithFrom = RawMsgSetIbcFrozen;
+    }
+
+    impl KnownAnyProto for MsgSetIbcFrozen {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl From<RawMsgSetIbcFrozen> for MsgSetIbcFrozen {
+        fn from(RawMsgSetIbcFrozen { frozen }: RawMsgSetIbcFrozen) -> Self {
+            Self { frozen }
+        }
+    }
+
+    impl From<MsgSetIbcFrozen> for RawMsgSetIbcFrozen {
+        fn from(MsgSetIbcFrozen { frozen }: MsgSetIbcFrozen) -> Self {
+            Self { frozen }
+        }
+    }
+
+    /// Drops IBC state versions from before the last `retain_slots` slots, and
+    /// client update records whose connection delay windows have passed.
+    #[derive(Clone, Debug)]
+    pub struct MsgPruneState {
+        pub retain_slots: u64,
+    }
+
+    impl MsgPruneState {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgPruneState";
+    }
+
+    impl KnownProtoWithFrom for MsgPruneState {
+        type RawWithFrom = RawMsgPruneState;
+    }
+
+    impl KnownAnyProto for MsgPruneState {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl From<RawMsgPruneState> for MsgPruneState {
+        fn from(RawMsgPruneState { retain_slots }: RawMsgPruneState) -> Self {
+            Self { retain_slots }
+        }
+    }
+
+    impl From<MsgPruneState> for RawMsgPruneState {
+        fn from(MsgPruneState { retain_slots }: MsgPruneState) -> Self {
+            Self { retain_slots }
+        }
+    }
+
+    /// Grows the storage account to `new_size` bytes, with the payer topping up
+    /// its rent. This does not touch the IBC state, so it is accepted while frozen.
+    #[derive(Clone, Debug)]
+    pub struct MsgReallocStorageAccount {
+        pub new_size: u64,
+    }
+
+    impl MsgReallocStorageAccount {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgReallocStorageAccount";
+    }
+
+    impl KnownProtoWithFrom for MsgReallocStorageAccount {
+        type RawWithFrom = RawMsgReallocStorageAccount;
+    }
+
+    impl KnownAnyProto for MsgReallocStorageAccount {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl From<RawMsgReallocStorageAccount> for MsgReallocStorageAccount {
+        fn from(RawMsgReallocStorageAccount { new_size }: RawMsgReallocStorageAccount) -> Self {
+            Self { new_size }
+        }
+    }
+
+    impl From<MsgReallocStorageAccount> for RawMsgReallocStorageAccount {
+        fn from(MsgReallocStorageAccount { new_size }: MsgReallocStorageAccount) -> Self {
+            Self { new_size }
+        }
+    }
+
+    /// Proposes handing the admin authority to `new_admin`, which can accept it
+    /// once the slot is past `effective_after_slot`.
+    #[derive(Clone, Debug)]
+    pub struct MsgProposeAdminChange {
+        pub new_admin: Pubkey,
+        pub effective_after_slot: u64,
+    }
+
+    impl MsgProposeAdminChange {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgProposeAdminChange";
+    }
+
+    impl KnownProtoWithFrom for MsgProposeAdminChange {
+        type RawWithFrom = RawMsgProposeAdminChange;
+    }
+
+    impl KnownAnyProto for MsgProposeAdminChange {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl TryFrom<RawMsgProposeAdminChange> for MsgProposeAdminChange {
+        type Error = anyhow::Error;
+
+        fn try_from(
+            RawMsgProposeAdminChange {
+                new_admin,
+                effective_after_slot,
+            }: RawMsgProposeAdminChange,
+        ) -> Result<Self, Self::Error> {
+            let new_admin = new_admin
+                .parse()
+                .map_err(|err| anyhow!("invalid new admin {new_admin}: {err}"))?;
+            Ok(Self {
+                new_admin,
+                effective_after_slot,
+            })
+        }
+    }
+
+    impl From<MsgProposeAdminChange> for RawMsgProposeAdminChange {
+        fn from(
+            MsgProposeAdminChange {
+                new_admin,
+                effective_after_slot,
+            }: MsgProposeAdminChange,
+        ) -> Self {
+            Self {
+                new_admin: new_admin.to_string(),
+                effective_after_slot,
+            }
+        }
+    }
+
+    /// Makes the signing payer the admin, completing the pending admin change.
+    #[derive(Clone, Debug)]
+    pub struct MsgAcceptAdminChange;
+
+    impl MsgAcceptAdminChange {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgAcceptAdminChange";
+    }
+
+    impl KnownProtoWithFrom for MsgAcceptAdminChange {
+        type RawWithFrom = RawMsgAcceptAdminChange;
+    }
+
+    impl KnownAnyProto for MsgAcceptAdminChange {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl From<RawMsgAcceptAdminChange> for MsgAcceptAdminChange {
+        fn from(RawMsgAcceptAdminChange {}: RawMsgAcceptAdminChange) -> Self {
+            Self
+        }
+    }
+
+    impl From<MsgAcceptAdminChange> for RawMsgAcceptAdminChange {
+        fn from(MsgAcceptAdminChange: MsgAcceptAdminChange) -> Self {
+            Self {}
+        }
+    }
+
+    /// Drops the pending admin change before it is accepted.
+    #[derive(Clone, Debug)]
+    pub struct MsgCancelAdminChange;
+
+    impl MsgCancelAdminChange {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgCancelAdminChange";
+    }
+
+    impl KnownProtoWithFrom for MsgCancelAdminChange {
+        type RawWithFrom = RawMsgCancelAdminChange;
+    }
+
+    impl KnownAnyProto for MsgCancelAdminChange {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl From<RawMsgCancelAdminChange> for MsgCancelAdminChange {
+        fn from(RawMsgCancelAdminChange {}: RawMsgCancelAdminChange) -> Self {
+            Self
+        }
+    }
+
+    impl From<MsgCancelAdminChange> for RawMsgCancelAdminChange {
+        fn from(MsgCancelAdminChange: MsgCancelAdminChange) -> Self {
+            Self {}
+        }
+    }
+
+    /// Makes the signing payer the admin of an endpoint that has none. The payer
+    /// must be the upgrade authority of this program, whose program data account
+    /// follows the clock sysvar.
+    #[derive(Clone, Debug)]
+    pub struct MsgClaimAdmin;
+
+    impl MsgClaimAdmin {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgClaimAdmin";
+    }
+
+    impl KnownProtoWithFrom for MsgClaimAdmin {
+        type RawWithFrom = RawMsgClaimAdmin;
+    }
+
+    impl KnownAnyProto for MsgClaimAdmin {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl From<RawMsgClaimAdmin> for MsgClaimAdmin {
+        fn from(RawMsgClaimAdmin {}: RawMsgClaimAdmin) -> Self {
+            Self
+        }
+    }
+
+    impl From<MsgClaimAdmin> for RawMsgClaimAdmin {
+        fn from(MsgClaimAdmin: MsgClaimAdmin) -> Self {
+            Self {}
+        }
+    }
+
+    /// Stores an upgraded client and consensus state of this chain at the upgrade
+    /// paths of `upgrade_height`, a revision height of this chain.
+    #[derive(Clone, Debug)]
+    pub struct MsgWriteUpgrade {
+        pub upgrade_height: u64,
+        pub upgraded_client_state: protobuf::Any,
+        pub upgraded_consensus_state: protobuf::Any,
+    }
+
+    impl MsgWriteUpgrade {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgWriteUpgrade";
+    }
+
+    impl KnownProtoWithFrom for MsgWriteUpgrade {
+        type RawWithFrom = RawMsgWriteUpgrade;
+    }
+
+    impl KnownAnyProto for MsgWriteUpgrade {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl TryFrom<RawMsgWriteUpgrade> for MsgWriteUpgrade {
+        type Error = anyhow::Error;
+
+        fn try_from(
+            RawMsgWriteUpgrade {
+                upgrade_height,
+                upgraded_client_state,
+                upgraded_consensus_state,
+            }: RawMsgWriteUpgrade,
+        ) -> Result<Self, Self::Error> {
+            Ok(Self {
+                upgrade_height,
+                upgraded_client_state: upgraded_client_state
+                    .ok_or_else(|| anyhow!("Upgraded client state cannot be None"))?,
+                upgraded_consensus_state: upgraded_consensus_state
+                    .ok_or_else(|| anyhow!("Upgraded consensus state cannot be None"))?,
+            })
+        }
+    }
+
+    impl From<MsgWriteUpgrade> for RawMsgWriteUpgrade {
+        fn from(
+            MsgWriteUpgrade {
+                upgrade_height,
+                upgraded_client_state,
+                upgraded_consensus_state,
+            }: MsgWriteUpgrade,
+        ) -> Self {
+            Self {
+                upgrade_height,
+                upgraded_client_state: Some(upgraded_client_state),
+                upgraded_consensus_state: Some(upgraded_consensus_state),
+            }
+        }
+    }
+
+    /// Rewrites the advertised capabilities with those of the running program.
+    #[derive(Clone, Debug)]
+    pub struct MsgRefreshCapabilities;
+
+    impl MsgRefreshCapabilities {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgRefreshCapabilities";
+    }
+
+    impl KnownProtoWithFrom for MsgRefreshCapabilities {
+        type RawWithFrom = RawMsgRefreshCapabilities;
+    }
+
+    impl KnownAnyProto for MsgRefreshCapabilities {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl From<RawMsgRefreshCapabilities> for MsgRefreshCapabilities {
+        fn from(RawMsgRefreshCapabilities {}: RawMsgRefreshCapabilities) -> Self {
+            Self
+        }
+    }
+
+    impl From<MsgRefreshCapabilities> for RawMsgRefreshCapabilities {
+        fn from(MsgRefreshCapabilities: MsgRefreshCapabilities) -> Self {
+            Self {}
+        }
+    }
+
+    /// Unbinds a port whose module still owns open channels, which
+    /// `MsgReleasePort` refuses to do.
+    #[derive(Clone, Debug)]
+    pub struct MsgForceReleasePort {
+        pub port_id: PortId,
+    }
+
+    impl MsgForceReleasePort {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgForceReleasePort";
+    }
+
+    impl KnownProtoWithFrom for MsgForceReleasePort {
+        type RawWithFrom = RawMsgForceReleasePort;
+    }
+
+    impl KnownAnyProto for MsgForceReleasePort {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl TryFrom<RawMsgForceReleasePort> for MsgForceReleasePort {
+        type Error = <PortId as FromStr>::Err;
+
+        fn try_from(
+            RawMsgForceReleasePort { port_id }: RawMsgForceReleasePort,
+        ) -> Result<Self, Self::Error> {
+            let port_id = port_id.parse()?;
+            Ok(Self { port_id })
+        }
+    }
+
+    impl From<MsgForceReleasePort> for RawMsgForceReleasePort {
+        fn from(MsgForceReleasePort { port_id }: MsgForceReleasePort) -> Self {
+            let port_id = port_id.to_string();
+            Self { port_id }
+        }
+    }
+
+    /// Moves the heights of this chain to a later revision.
+    #[derive(Clone, Debug)]
+    pub struct MsgSetRevisionNumber {
+        pub revision_number: u64,
+    }
+
+    impl MsgSetRevisionNumber {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgSetRevisionNumber";
+    }
+
+    impl KnownProtoWithFrom for MsgSetRevisionNumber {
+        type RawWithFrom = RawMsgSetRevisionNumber;
+    }
+
+    impl KnownAnyProto for MsgSetRevisionNumber {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl From<RawMsgSetRevisionNumber> for MsgSetRevisionNumber {
+        fn from(RawMsgSetRevisionNumber { revision_number }: RawMsgSetRevisionNumber) -> Self {
+            Self { revision_number }
+        }
+    }
+
+    impl From<MsgSetRevisionNumber> for RawMsgSetRevisionNumber {
+        fn from(MsgSetRevisionNumber { revision_number }: MsgSetRevisionNumber) -> Self {
+            Self { revision_number }
+        }
+    }
+
+    /// Rebuilds up to `max_writes` entries of an index from the primary data it
+    /// is derived from.
+    #[derive(Clone, Debug)]
+    pub struct MsgRepairIndices {
+        pub kind: IndexKind,
+        pub max_writes: u32,
+    }
+
+    impl MsgRepairIndices {
+        pub const TYPE_URL: &str = "/eclipse.ibc.admin.v1.MsgRepairIndices";
+    }
+
+    impl KnownProtoWithFrom for MsgRepairIndices {
+        type RawWithFrom = RawMsgRepairIndices;
+    }
+
+    impl KnownAnyProto for MsgRepairIndices {
+        fn type_url() -> String {
+            Self::TYPE_URL.to_owned()
+        }
+    }
+
+    impl TryFrom<RawMsgRepairIndices> for MsgRepairIndices {
+        type Error = anyhow::Error;
+
+        fn try_from(
+            RawMsgRepairIndices { kind, max_writes }: RawMsgRepairIndices,
+        ) -> Result<Self, Self::Error> {
+            let kind = match RawIndexKind::from_i32(kind) {
+                Some(RawIndexKind::ClientConnections) => IndexKind::ClientConnections,
+                None => return Err(anyhow!("unknown index kind {kind}")),
+            };
+            Ok(Self { kind, max_writes })
+        }
+    }
+
+    impl From<MsgRepairIndices> for RawMsgRepairIndices {
+        fn from(MsgRepairIndices { kind, max_writes }: MsgRepairIndices) -> Self {
+            let kind = match kind {
+                IndexKind::ClientConnections => RawIndexKind::ClientConnections,
+            };
+            Self {
+                kind: kind as i32,
+                max_writes,
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum PortInstruction {
     Bind(msgs::MsgBindPort),
     Release(msgs::MsgReleasePort),
+    Transfer(msgs::MsgTransferPort),
 }
 
 #[derive(Debug, Error)]
@@ -229,6 +1022,11 @@ impl TryFrom<protobuf::Any> for PortInstruction {
                     .map_err(ProtoError::MalformedMessageBytes)?;
                 Ok(Self::Release(msg))
             }
+            msgs::MsgTransferPort::TYPE_URL => {
+                let msg = msgs::MsgTransferPort::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::Transfer(msg))
+            }
             _ => Err(ProtoError::UnknownMessageTypeUrl {
                 url: any_msg.type_url,
             }),
@@ -241,6 +1039,7 @@ impl From<PortInstruction> for protobuf::Any {
         match port_instruction {
             PortInstruction::Bind(msg) => msg.encode_as_any(),
             PortInstruction::Release(msg) => msg.encode_as_any(),
+            PortInstruction::Transfer(msg) => msg.encode_as_any(),
         }
     }
 }
@@ -249,6 +1048,22 @@ impl From<PortInstruction> for protobuf::Any {
 pub enum AdminInstruction {
     InitStorageAccount(msgs::MsgInitStorageAccount),
     WriteTxBuffer(msgs::MsgWriteTxBuffer),
+    CloseTxBuffer(msgs::MsgCloseTxBuffer),
+    StoreWriteBatch(msgs::MsgStoreWriteBatch),
+    AbortStoreWriteBatch(msgs::MsgAbortStoreWriteBatch),
+    UpdateChainParams(msgs::MsgUpdateChainParams),
+    SetIbcFrozen(msgs::MsgSetIbcFrozen),
+    PruneState(msgs::MsgPruneState),
+    ReallocStorageAccount(msgs::MsgReallocStorageAccount),
+    ProposeAdminChange(msgs::MsgProposeAdminChange),
+    AcceptAdminChange(msgs::MsgAcceptAdminChange),
+    CancelAdminChange(msgs::MsgCancelAdminChange),
+    ClaimAdmin(msgs::MsgClaimAdmin),
+    WriteUpgrade(msgs::MsgWriteUpgrade),
+    RefreshCapabilities(msgs::MsgRefreshCapabilities),
+    ForceReleasePort(msgs::MsgForceReleasePort),
+    SetRevisionNumber(msgs::MsgSetRevisionNumber),
+    RepairIndices(msgs::MsgRepairIndices),
 }
 
 impl KnownProtoWithFrom for AdminInstruction {
@@ -270,6 +1085,86 @@ impl TryFrom<protobuf::Any> for AdminInstruction {
                     .map_err(ProtoError::MalformedMessageBytes)?;
                 Ok(Self::WriteTxBuffer(msg))
             }
+            msgs::MsgCloseTxBuffer::TYPE_URL => {
+                let msg = msgs::MsgCloseTxBuffer::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::CloseTxBuffer(msg))
+            }
+            msgs::MsgStoreWriteBatch::TYPE_URL => {
+                let msg = msgs::MsgStoreWriteBatch::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::StoreWriteBatch(msg))
+            }
+            msgs::MsgAbortStoreWriteBatch::TYPE_URL => {
+                let msg = msgs::MsgAbortStoreWriteBatch::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::AbortStoreWriteBatch(msg))
+            }
+            msgs::MsgUpdateChainParams::TYPE_URL => {
+                let msg = msgs::MsgUpdateChainParams::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::UpdateChainParams(msg))
+            }
+            msgs::MsgSetIbcFrozen::TYPE_URL => {
+                let msg = msgs::MsgSetIbcFrozen::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::SetIbcFrozen(msg))
+            }
+            msgs::MsgPruneState::TYPE_URL => {
+                let msg = msgs::MsgPruneState::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::PruneState(msg))
+            }
+            msgs::MsgReallocStorageAccount::TYPE_URL => {
+                let msg = msgs::MsgReallocStorageAccount::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::ReallocStorageAccount(msg))
+            }
+            msgs::MsgProposeAdminChange::TYPE_URL => {
+                let msg = msgs::MsgProposeAdminChange::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::ProposeAdminChange(msg))
+            }
+            msgs::MsgAcceptAdminChange::TYPE_URL => {
+                let msg = msgs::MsgAcceptAdminChange::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::AcceptAdminChange(msg))
+            }
+            msgs::MsgCancelAdminChange::TYPE_URL => {
+                let msg = msgs::MsgCancelAdminChange::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::CancelAdminChange(msg))
+            }
+            msgs::MsgClaimAdmin::TYPE_URL => {
+                let msg = msgs::MsgClaimAdmin::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::ClaimAdmin(msg))
+            }
+            msgs::MsgWriteUpgrade::TYPE_URL => {
+                let msg = msgs::MsgWriteUpgrade::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::WriteUpgrade(msg))
+            }
+            msgs::MsgRefreshCapabilities::TYPE_URL => {
+                let msg = msgs::MsgRefreshCapabilities::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::RefreshCapabilities(msg))
+            }
+            msgs::MsgForceReleasePort::TYPE_URL => {
+                let msg = msgs::MsgForceReleasePort::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::ForceReleasePort(msg))
+            }
+            msgs::MsgSetRevisionNumber::TYPE_URL => {
+                let msg = msgs::MsgSetRevisionNumber::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::SetRevisionNumber(msg))
+            }
+            msgs::MsgRepairIndices::TYPE_URL => {
+                let msg = msgs::MsgRepairIndices::decode(&*any_msg.value)
+                    .map_err(ProtoError::MalformedMessageBytes)?;
+                Ok(Self::RepairIndices(msg))
+            }
             _ => Err(ProtoError::UnknownMessageTypeUrl {
                 url: any_msg.type_url,
             }),
@@ -282,6 +1177,22 @@ impl From<AdminInstruction> for protobuf::Any {
         match admin_instruction {
             AdminInstruction::InitStorageAccount(msg) => msg.encode_as_any(),
             AdminInstruction::WriteTxBuffer(msg) => msg.encode_as_any(),
+            AdminInstruction::CloseTxBuffer(msg) => msg.encode_as_any(),
+            AdminInstruction::StoreWriteBatch(msg) => msg.encode_as_any(),
+            AdminInstruction::AbortStoreWriteBatch(msg) => msg.encode_as_any(),
+            AdminInstruction::UpdateChainParams(msg) => msg.encode_as_any(),
+            AdminInstruction::SetIbcFrozen(msg) => msg.encode_as_any(),
+            AdminInstruction::PruneState(msg) => msg.encode_as_any(),
+            AdminInstruction::ReallocStorageAccount(msg) => msg.encode_as_any(),
+            AdminInstruction::ProposeAdminChange(msg) => msg.encode_as_any(),
+            AdminInstruction::AcceptAdminChange(msg) => msg.encode_as_any(),
+            AdminInstruction::CancelAdminChange(msg) => msg.encode_as_any(),
+            AdminInstruction::ClaimAdmin(msg) => msg.encode_as_any(),
+            AdminInstruction::WriteUpgrade(msg) => msg.encode_as_any(),
+            AdminInstruction::RefreshCapabilities(msg) => msg.encode_as_any(),
+            AdminInstruction::ForceReleasePort(msg) => msg.encode_as_any(),
+            AdminInstruction::SetRevisionNumber(msg) => msg.encode_as_any(),
+            AdminInstruction::RepairIndices(msg) => msg.encode_as_any(),
         }
     }
 }
@@ -292,6 +1203,10 @@ pub enum IbcInstruction {
     Router(MsgEnvelope),
     Port(PortInstruction),
     Admin(AdminInstruction),
+    /// An ICS-20 token transfer, which is handled by the built-in ICS-20 module.
+    /// Only vouchers received over a channel can be sent, since the module has no
+    /// deposit path for denoms native to this chain.
+    Transfer(MsgTransfer),
 }
 
 #[allow(clippy::module_name_repetitions)]
@@ -305,6 +1220,8 @@ pub enum IbcInstructionError {
         port_err: ProtoError,
         admin_err: ProtoError,
     },
+    #[error("the ICS-20 transfer is malformed and cannot be decoded: {0}")]
+    MalformedTransfer(anyhow::Error),
 }
 
 impl KnownProtoWithFrom for IbcInstruction {
@@ -315,6 +1232,13 @@ impl TryFrom<protobuf::Any> for IbcInstruction {
     type Error = IbcInstructionError;
 
     fn try_from(any_msg: protobuf::Any) -> Result<Self, Self::Error> {
+        if any_msg.type_url == TRANSFER_TYPE_URL {
+            let raw_msg = RawMsgTransfer::decode(&*any_msg.value)
+                .map_err(|err| IbcInstructionError::MalformedTransfer(err.into()))?;
+            let msg = MsgTransfer::try_from(raw_msg)
+                .map_err(|err| IbcInstructionError::MalformedTransfer(anyhow::anyhow!("{err}")))?;
+            return Ok(Self::Transfer(msg));
+        }
         let router_err = match any_msg.clone().try_into() {
             Ok(envelope) => return Ok(Self::Router(envelope)),
             Err(router_err) => router_err,
@@ -384,6 +1308,498 @@ impl From<IbcInstruction> for protobuf::Any {
             }
             IbcInstruction::Port(port_instruction) => port_instruction.into(),
             IbcInstruction::Admin(admin_instruction) => admin_instruction.into(),
+            IbcInstruction::Transfer(domain_msg) => domain_msg.to_any(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{msgs::*, *},
+        crate::capabilities::SUPPORTED_TYPE_URLS,
+        eclipse_ibc_extra_types::{ModuleAccountTemplates, PdaSeedTemplate, SeedPart},
+        ibc_proto::{
+            cosmos::base::v1beta1::Coin as RawCoin,
+            ibc::core::{
+                channel::v1::{
+                    Channel as RawChannel, Counterparty as RawChannelCounterparty,
+                    MsgAcknowledgement as RawMsgAcknowledgement,
+                    MsgChannelCloseConfirm as RawMsgChannelCloseConfirm,
+                    MsgChannelCloseInit as RawMsgChannelCloseInit,
+                    MsgChannelOpenAck as RawMsgChannelOpenAck,
+                    MsgChannelOpenConfirm as RawMsgChannelOpenConfirm,
+                    MsgChannelOpenInit as RawMsgChannelOpenInit,
+                    MsgChannelOpenTry as RawMsgChannelOpenTry, MsgRecvPacket as RawMsgRecvPacket,
+                    MsgTimeout as RawMsgTimeout, MsgTimeoutOnClose as RawMsgTimeoutOnClose,
+                    Order as RawOrder, Packet as RawPacket, State as RawState,
+                },
+                client::v1::{
+                    Height as RawHeight, MsgCreateClient as RawMsgCreateClient,
+                    MsgSubmitMisbehaviour as RawMsgSubmitMisbehaviour,
+                    MsgUpdateClient as RawMsgUpdateClient, MsgUpgradeClient as RawMsgUpgradeClient,
+                },
+                commitment::v1::{MerklePrefix as RawMerklePrefix, MerkleProof as RawMerkleProof},
+                connection::v1::{
+                    Counterparty as RawConnectionCounterparty,
+                    MsgConnectionOpenAck as RawMsgConnectionOpenAck,
+                    MsgConnectionOpenConfirm as RawMsgConnectionOpenConfirm,
+                    MsgConnectionOpenInit as RawMsgConnectionOpenInit,
+                    MsgConnectionOpenTry as RawMsgConnectionOpenTry,
+                    Version as RawConnectionVersion,
+                },
+            },
+            ics23::CommitmentProof as RawCommitmentProof,
+        },
+        prost::Message,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    const SIGNER: &str = "A7NJxtiKpEFL4TSTygkKSkf5b2g719DJbvQPRr4moUHD";
+    const CLIENT_ID: &str = "07-tendermint-0";
+    const CONNECTION_ID: &str = "connection-0";
+    const PORT_ID: &str = "transfer";
+    const CHANNEL_ID: &str = "channel-0";
+
+    /// Converts the raw message into an `IbcInstruction` and back, checking that
+    /// the type URL survives and that a second round trip is byte-for-byte stable.
+    /// Every message that decodes must also be advertised in the capabilities.
+    fn assert_round_trip(type_url: &str, raw_msg: impl Message) {
+        assert!(
+            SUPPORTED_TYPE_URLS.contains(&type_url),
+            "{type_url} is not advertised"
+        );
+        let any_msg = protobuf::Any {
+            type_url: type_url.to_owned(),
+            value: raw_msg.encode_to_vec(),
+        };
+        let ibc_instruction = IbcInstruction::try_from(any_msg).unwrap();
+        let any_msg = protobuf::Any::from(ibc_instruction);
+        assert_eq!(any_msg.type_url, type_url);
+
+        let round_tripped = protobuf::Any::from(IbcInstruction::try_from(any_msg.clone()).unwrap());
+        assert_eq!(round_tripped, any_msg, "{type_url} did not round trip");
+    }
+
+    fn any_state() -> Option<protobuf::Any> {
+        Some(protobuf::Any {
+            type_url: "/eclipse.ibc.v1.chain.ClientState".to_owned(),
+            value: vec![1, 2, 3],
+        })
+    }
+
+    fn proof() -> Vec<u8> {
+        RawMerkleProof {
+            proofs: vec![RawCommitmentProof::default()],
+        }
+        .encode_to_vec()
+    }
+
+    fn height() -> Option<RawHeight> {
+        Some(RawHeight {
+            revision_number: 0,
+            revision_height: 10,
+        })
+    }
+
+    fn connection_version() -> RawConnectionVersion {
+        RawConnectionVersion {
+            identifier: "1".to_owned(),
+            features: vec!["ORDER_ORDERED".to_owned(), "ORDER_UNORDERED".to_owned()],
+        }
+    }
+
+    fn connection_counterparty(connection_id: &str) -> Option<RawConnectionCounterparty> {
+        Some(RawConnectionCounterparty {
+            client_id: CLIENT_ID.to_owned(),
+            connection_id: connection_id.to_owned(),
+            prefix: Some(RawMerklePrefix {
+                key_prefix: b"ibc".to_vec(),
+            }),
+        })
+    }
+
+    fn channel(state: RawState, cpty_channel_id: &str) -> Option<RawChannel> {
+        Some(RawChannel {
+            state: state.into(),
+            ordering: RawOrder::Unordered.into(),
+            counterparty: Some(RawChannelCounterparty {
+                port_id: PORT_ID.to_owned(),
+                channel_id: cpty_channel_id.to_owned(),
+            }),
+            connection_hops: vec![CONNECTION_ID.to_owned()],
+            version: "ics20-1".to_owned(),
+        })
+    }
+
+    fn packet() -> Option<RawPacket> {
+        Some(RawPacket {
+            sequence: 1,
+            source_port: PORT_ID.to_owned(),
+            source_channel: CHANNEL_ID.to_owned(),
+            destination_port: PORT_ID.to_owned(),
+            destination_channel: CHANNEL_ID.to_owned(),
+            data: b"data".to_vec(),
+            timeout_height: height(),
+            timeout_timestamp: 1_000_000,
+        })
+    }
+
+    #[test]
+    fn client_msgs_round_trip() {
+        assert_round_trip(
+            "/ibc.core.client.v1.MsgCreateClient",
+            RawMsgCreateClient {
+                client_state: any_state(),
+                consensus_state: any_state(),
+                signer: SIGNER.to_owned(),
+            },
+        );
+        assert_round_trip(
+            "/ibc.core.client.v1.MsgUpdateClient",
+            RawMsgUpdateClient {
+                client_id: CLIENT_ID.to_owned(),
+                header: any_state(),
+                signer: SIGNER.to_owned(),
+            },
+        );
+        assert_round_trip(
+            "/ibc.core.client.v1.MsgSubmitMisbehaviour",
+            RawMsgSubmitMisbehaviour {
+                client_id: CLIENT_ID.to_owned(),
+                misbehaviour: any_state(),
+                signer: SIGNER.to_owned(),
+            },
+        );
+        assert_round_trip(
+            "/ibc.core.client.v1.MsgUpgradeClient",
+            RawMsgUpgradeClient {
+                client_id: CLIENT_ID.to_owned(),
+                client_state: any_state(),
+                consensus_state: any_state(),
+                proof_upgrade_client: proof(),
+                proof_upgrade_consensus_state: proof(),
+                signer: SIGNER.to_owned(),
+            },
+        );
+    }
+
+    #[test]
+    fn connection_msgs_round_trip() {
+        assert_round_trip(
+            "/ibc.core.connection.v1.MsgConnectionOpenInit",
+            RawMsgConnectionOpenInit {
+                client_id: CLIENT_ID.to_owned(),
+                counterparty: connection_counterparty(""),
+                version: Some(connection_version()),
+                delay_period: 0,
+                signer: SIGNER.to_owned(),
+            },
+        );
+        #[allow(deprecated)]
+        let open_try = RawMsgConnectionOpenTry {
+            client_id: CLIENT_ID.to_owned(),
+            previous_connection_id: "".to_owned(),
+            client_state: any_state(),
+            counterparty: connection_counterparty(CONNECTION_ID),
+            delay_period: 0,
+            counterparty_versions: vec![connection_version()],
+            proof_height: height(),
+            proof_init: proof(),
+            proof_client: proof(),
+            proof_consensus: proof(),
+            consensus_height: height(),
+            signer: SIGNER.to_owned(),
+        };
+        assert_round_trip("/ibc.core.connection.v1.MsgConnectionOpenTry", open_try);
+        assert_round_trip(
+            "/ibc.core.connection.v1.MsgConnectionOpenAck",
+            RawMsgConnectionOpenAck {
+                connection_id: CONNECTION_ID.to_owned(),
+                counterparty_connection_id: CONNECTION_ID.to_owned(),
+                version: Some(connection_version()),
+                client_state: any_state(),
+                proof_height: height(),
+                proof_try: proof(),
+                proof_client: proof(),
+                proof_consensus: proof(),
+                consensus_height: height(),
+                signer: SIGNER.to_owned(),
+            },
+        );
+        assert_round_trip(
+            "/ibc.core.connection.v1.MsgConnectionOpenConfirm",
+            RawMsgConnectionOpenConfirm {
+                connection_id: CONNECTION_ID.to_owned(),
+                proof_ack: proof(),
+                proof_height: height(),
+                signer: SIGNER.to_owned(),
+            },
+        );
+    }
+
+    #[test]
+    fn channel_msgs_round_trip() {
+        assert_round_trip(
+            "/ibc.core.channel.v1.MsgChannelOpenInit",
+            RawMsgChannelOpenInit {
+                port_id: PORT_ID.to_owned(),
+                channel: channel(RawState::Init, ""),
+                signer: SIGNER.to_owned(),
+            },
+        );
+        #[allow(deprecated)]
+        let open_try = RawMsgChannelOpenTry {
+            port_id: PORT_ID.to_owned(),
+            previous_channel_id: "".to_owned(),
+            channel: channel(RawState::Tryopen, CHANNEL_ID),
+            counterparty_version: "ics20-1".to_owned(),
+            proof_init: proof(),
+            proof_height: height(),
+            signer: SIGNER.to_owned(),
+        };
+        assert_round_trip("/ibc.core.channel.v1.MsgChannelOpenTry", open_try);
+        assert_round_trip(
+            "/ibc.core.channel.v1.MsgChannelOpenAck",
+            RawMsgChannelOpenAck {
+                port_id: PORT_ID.to_owned(),
+                channel_id: CHANNEL_ID.to_owned(),
+                counterparty_channel_id: CHANNEL_ID.to_owned(),
+                counterparty_version: "ics20-1".to_owned(),
+                proof_try: proof(),
+                proof_height: height(),
+                signer: SIGNER.to_owned(),
+            },
+        );
+        assert_round_trip(
+            "/ibc.core.channel.v1.MsgChannelOpenConfirm",
+            RawMsgChannelOpenConfirm {
+                port_id: PORT_ID.to_owned(),
+                channel_id: CHANNEL_ID.to_owned(),
+                proof_ack: proof(),
+                proof_height: height(),
+                signer: SIGNER.to_owned(),
+            },
+        );
+        assert_round_trip(
+            "/ibc.core.channel.v1.MsgChannelCloseInit",
+            RawMsgChannelCloseInit {
+                port_id: PORT_ID.to_owned(),
+                channel_id: CHANNEL_ID.to_owned(),
+                signer: SIGNER.to_owned(),
+            },
+        );
+        assert_round_trip(
+            "/ibc.core.channel.v1.MsgChannelCloseConfirm",
+            RawMsgChannelCloseConfirm {
+                port_id: PORT_ID.to_owned(),
+                channel_id: CHANNEL_ID.to_owned(),
+                proof_init: proof(),
+                proof_height: height(),
+                signer: SIGNER.to_owned(),
+            },
+        );
+    }
+
+    #[test]
+    fn packet_msgs_round_trip() {
+        assert_round_trip(
+            "/ibc.core.channel.v1.MsgRecvPacket",
+            RawMsgRecvPacket {
+                packet: packet(),
+                proof_commitment: proof(),
+                proof_height: height(),
+                signer: SIGNER.to_owned(),
+            },
+        );
+        assert_round_trip(
+            "/ibc.core.channel.v1.MsgAcknowledgement",
+            RawMsgAcknowledgement {
+                packet: packet(),
+                acknowledgement: b"ack".to_vec(),
+                proof_acked: proof(),
+                proof_height: height(),
+                signer: SIGNER.to_owned(),
+            },
+        );
+        assert_round_trip(
+            "/ibc.core.channel.v1.MsgTimeout",
+            RawMsgTimeout {
+                packet: packet(),
+                proof_unreceived: proof(),
+                proof_height: height(),
+                next_sequence_recv: 1,
+                signer: SIGNER.to_owned(),
+            },
+        );
+        assert_round_trip(
+            "/ibc.core.channel.v1.MsgTimeoutOnClose",
+            RawMsgTimeoutOnClose {
+                packet: packet(),
+                proof_unreceived: proof(),
+                proof_close: proof(),
+                proof_height: height(),
+                next_sequence_recv: 1,
+                signer: SIGNER.to_owned(),
+            },
+        );
+    }
+
+    #[test]
+    fn transfer_msgs_round_trip() {
+        let raw_msg = RawMsgTransfer {
+            source_port: PORT_ID.to_owned(),
+            source_channel: CHANNEL_ID.to_owned(),
+            token: Some(RawCoin {
+                denom: "transfer/channel-1/uatom".to_owned(),
+                amount: "100".to_owned(),
+            }),
+            sender: SIGNER.to_owned(),
+            receiver: "cosmos1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5lzv7xu".to_owned(),
+            timeout_height: height(),
+            timeout_timestamp: 1_000_000,
+            memo: "memo".to_owned(),
+        };
+        assert_round_trip(TRANSFER_TYPE_URL, raw_msg.clone());
+        assert!(matches!(
+            IbcInstruction::try_from(protobuf::Any {
+                type_url: TRANSFER_TYPE_URL.to_owned(),
+                value: RawMsgTransfer {
+                    token: None,
+                    ..raw_msg
+                }
+                .encode_to_vec(),
+            }),
+            Err(IbcInstructionError::MalformedTransfer(_)),
+        ));
+    }
+
+    #[test]
+    fn port_and_admin_msgs_round_trip() {
+        let port_id: PortId = PORT_ID.parse().unwrap();
+        for any_msg in [
+            MsgBindPort {
+                port_id: port_id.clone(),
+                account_templates: ModuleAccountTemplates::default(),
+            }
+            .encode_as_any(),
+            MsgBindPort {
+                port_id: port_id.clone(),
+                account_templates: ModuleAccountTemplates {
+                    templates: vec![PdaSeedTemplate {
+                        seeds: vec![SeedPart::Literal(b"escrow".to_vec()), SeedPart::ChannelId],
+                    }],
+                },
+            }
+            .encode_as_any(),
+            MsgReleasePort {
+                port_id: port_id.clone(),
+            }
+            .encode_as_any(),
+            MsgTransferPort {
+                port_id,
+                new_module_pubkey: Pubkey::new_unique(),
+            }
+            .encode_as_any(),
+            MsgInitStorageAccount::default().encode_as_any(),
+            MsgInitStorageAccount {
+                genesis_hash: Some([3; 32]),
+                idempotent: true,
+            }
+            .encode_as_any(),
+            MsgWriteTxBuffer {
+                mode: MsgWriteTxBufferMode::Create { buffer_size: 0 },
+                data: vec![1, 2, 3],
+            }
+            .encode_as_any(),
+            MsgWriteTxBuffer {
+                mode: MsgWriteTxBufferMode::Reuse { offset: 825 },
+                data: vec![],
+            }
+            .encode_as_any(),
+            MsgCloseTxBuffer.encode_as_any(),
+            MsgStoreWriteBatch {
+                chunk_index: 1,
+                writes: vec![
+                    StoreWrite {
+                        path: "nextSequenceSend/ports/transfer/channels/channel-0".to_owned(),
+                        value: Some(vec![8, 1]),
+                    },
+                    StoreWrite {
+                        path: "nextSequenceRecv/ports/transfer/channels/channel-0".to_owned(),
+                        value: None,
+                    },
+                ],
+                next_chunk_hash: Some([7; 32]),
+            }
+            .encode_as_any(),
+            MsgAbortStoreWriteBatch.encode_as_any(),
+            MsgUpdateChainParams {
+                params: eclipse_ibc_extra_types::ChainParams {
+                    disabled_messages: vec!["/ibc.core.client.v1.MsgUpgradeClient".to_owned()],
+                    genesis_hash: Some([5; 32]),
+                    max_inflight_packets_per_channel: Some(100),
+                    frozen: true,
+                    skip_root_check_on_load: true,
+                    max_tx_buffer_size: Some(4096),
+                    max_tx_buffer_bytes_per_payer: Some(1 << 16),
+                    chain_name: Some("devnet".to_owned()),
+                    verbose_logs: true,
+                    event_journal_capacity: Some(16),
+                    max_consensus_states_per_client: Some(32),
+                    reject_legacy_type_urls: true,
+                    compress_values_above: Some(256),
+                    value_size_caps: vec![eclipse_ibc_extra_types::ValueSizeCap {
+                        path_prefix: "clients/".to_owned(),
+                        max_size: 8192,
+                    }],
+                    max_commit_bytes: Some(1 << 20),
+                    forbidden_write_prefixes: vec!["internal/ics20/balances/".to_owned()],
+                },
+            }
+            .encode_as_any(),
+            MsgSetIbcFrozen { frozen: true }.encode_as_any(),
+            MsgPruneState { retain_slots: 1000 }.encode_as_any(),
+            MsgReallocStorageAccount { new_size: 20480 }.encode_as_any(),
+            MsgProposeAdminChange {
+                new_admin: Pubkey::new_unique(),
+                effective_after_slot: 1000,
+            }
+            .encode_as_any(),
+            MsgAcceptAdminChange.encode_as_any(),
+            MsgCancelAdminChange.encode_as_any(),
+            MsgClaimAdmin.encode_as_any(),
+            MsgWriteUpgrade {
+                upgrade_height: 1000,
+                upgraded_client_state: protobuf::Any {
+                    type_url: "/eclipse.ibc.v1.chain.ClientState".to_owned(),
+                    value: vec![1; 8],
+                },
+                upgraded_consensus_state: protobuf::Any {
+                    type_url: "/eclipse.ibc.v1.chain.ConsensusState".to_owned(),
+                    value: vec![2; 8],
+                },
+            }
+            .encode_as_any(),
+            MsgRefreshCapabilities.encode_as_any(),
+            MsgForceReleasePort {
+                port_id: "transfer".parse().unwrap(),
+            }
+            .encode_as_any(),
+            MsgSetRevisionNumber { revision_number: 1 }.encode_as_any(),
+            MsgRepairIndices {
+                kind: eclipse_ibc_state::IndexKind::ClientConnections,
+                max_writes: 16,
+            }
+            .encode_as_any(),
+        ] {
+            let type_url = any_msg.type_url.clone();
+            assert!(
+                SUPPORTED_TYPE_URLS.contains(&&*type_url),
+                "{type_url} is not advertised"
+            );
+            let raw_msg = protobuf::Any::from(IbcInstruction::try_from(any_msg.clone()).unwrap());
+            assert_eq!(raw_msg, any_msg, "{type_url} did not round trip");
         }
     }
 }
@@ -1,50 +1,88 @@
 use {
     crate::{
-        ibc_contract_instruction,
-        ibc_handler::IbcHandler,
+        account_spec::{
+            self, CLOSE_TX_BUFFER_ACCOUNTS, CREATE_TX_BUFFER_ACCOUNTS, IBC_HANDLER_ACCOUNTS,
+            INIT_STORAGE_ACCOUNT_ACCOUNTS, REALLOC_STORAGE_ACCOUNT_ACCOUNTS,
+            REUSE_TX_BUFFER_ACCOUNTS,
+        },
+        bounded_log::{self, MAX_TERSE_LOG_LEN, MAX_VERBOSE_LOG_LEN},
+        build_info, capabilities,
+        commit_guard::CommitRejected,
+        errors::IbcProgramError,
+        ibc_contract_instruction, ibc_events,
+        ibc_handler::{self, multihop_channel_rejection, AdminError, IbcHandler},
         ibc_instruction::{
             msgs::{
-                MsgBindPort, MsgInitStorageAccount, MsgReleasePort, MsgWriteTxBuffer,
+                MsgAbortStoreWriteBatch, MsgAcceptAdminChange, MsgBindPort, MsgCancelAdminChange,
+                MsgClaimAdmin, MsgCloseTxBuffer, MsgForceReleasePort, MsgInitStorageAccount,
+                MsgProposeAdminChange, MsgPruneState, MsgReallocStorageAccount,
+                MsgRefreshCapabilities, MsgReleasePort, MsgRepairIndices, MsgSetIbcFrozen,
+                MsgSetRevisionNumber, MsgTransferPort, MsgUpdateChainParams, MsgWriteTxBuffer,
                 MsgWriteTxBufferMode,
             },
             AdminInstruction, IbcInstruction, PortInstruction,
         },
         id,
     },
-    eclipse_ibc_state::{internal_path::StateInitializedPath, IbcAccountData, IbcState},
+    eclipse_ibc_extra_types::ChainParams,
+    eclipse_ibc_state::{
+        internal_path::{
+            AdminPath, CapabilitiesPath, ChainParamsPath, StateInitializedPath, TxBufferBytesPath,
+            TxBufferPayerPath,
+        },
+        IbcAccountData, IbcState, LoadChainParams,
+    },
     ibc::core::dispatch,
     solana_program_runtime::{
         ic_msg, invoke_context::InvokeContext, sysvar_cache::get_sysvar_with_account_check,
     },
     solana_sdk::{
-        instruction::InstructionError,
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        entrypoint::MAX_PERMITTED_DATA_INCREASE,
+        instruction::{AccountMeta, InstructionError},
         pubkey::Pubkey,
         syscalls::MAX_CPI_INSTRUCTION_DATA_LEN,
-        system_instruction,
+        system_instruction::{self, MAX_PERMITTED_DATA_LENGTH},
+        system_program,
         transaction_context::{InstructionContext, TransactionContext},
     },
+    std::{fmt, ops::Range},
 };
 
-const ROUTER_ERR_CODE: u32 = 0x97;
-const PORT_ERR_CODE: u32 = 0x98;
-const STORAGE_ERR_CODE: u32 = 0x99;
-
 pub const STORAGE_KEY: Pubkey = Pubkey::new_from_array([
     135, 90, 195, 29, 90, 182, 162, 153, 214, 170, 125, 126, 161, 2, 167, 102, 196, 107, 28, 247,
     252, 46, 240, 250, 117, 230, 224, 243, 31, 221, 167, 136,
 ]);
 
+/// Most stale tree nodes a `MsgPruneState` drops, which keeps its compute bounded
+/// however many versions it drops. The rest are dropped by later messages.
+pub const PRUNED_NODES_PER_INSTRUCTION: usize = 2048;
+
+/// Runs `f` against the IBC state, rejecting the instruction up front if it is an
+/// admin-only message not signed by the admin, IBC is frozen, or its message
+/// family is disabled in the chain params. Returns the log
+/// lines of the IBC events emitted. Instructions that
+/// do not go through here (storage account init and tx buffer writes) cannot be
+/// rejected this way, since they run before the IBC state is available.
 fn with_ibc_handler<F>(
     invoke_context: &InvokeContext,
     transaction_context: &TransactionContext,
     instruction_context: &InstructionContext,
     account_offset: usize,
+    type_url: &str,
     f: F,
-) -> Result<(), InstructionError>
+) -> Result<Vec<String>, InstructionError>
 where
     F: FnOnce(&mut IbcHandler) -> Result<(), InstructionError>,
 {
     instruction_context.check_number_of_instruction_accounts(account_offset + 3)?;
+    account_spec::check_accounts(
+        invoke_context,
+        transaction_context,
+        instruction_context,
+        account_offset,
+        IBC_HANDLER_ACCOUNTS,
+    )?;
 
     let mut storage_account = instruction_context
         .try_borrow_instruction_account(transaction_context, account_offset + 1)?;
@@ -61,30 +99,249 @@ where
         account_offset + 2,
     )?;
 
+    // Any accounts after the fixed ones are candidates for module callbacks. They are
+    // read without borrowing, since they may alias the payer or storage account.
+    let instruction_accounts = (account_offset + 3
+        ..instruction_context.get_number_of_instruction_accounts())
+        .map(|index_in_instruction| {
+            let index_in_transaction = instruction_context
+                .get_index_of_instruction_account_in_transaction(index_in_instruction)?;
+            Ok(AccountMeta {
+                pubkey: *transaction_context.get_key_of_account_at_index(index_in_transaction)?,
+                is_signer: instruction_context
+                    .is_instruction_account_signer(index_in_instruction)?,
+                is_writable: instruction_context
+                    .is_instruction_account_writable(index_in_instruction)?,
+            })
+        })
+        .collect::<Result<Vec<_>, InstructionError>>()?;
+
     let mut ibc_account_data = IbcAccountData::read_from_account(&storage_account, invoke_context)?;
     let mut ibc_handler = IbcHandler::new(
         &ibc_account_data.store,
         &mut ibc_account_data.metadata,
         &clock,
+        &instruction_accounts,
     )
     .map_err(|err| {
-        ic_msg!(invoke_context, "failed to init IBC handler: {:?}", err);
+        bounded_log::log_trunc(
+            invoke_context,
+            "failed to init IBC handler",
+            format_args!("{err:?}"),
+            MAX_TERSE_LOG_LEN,
+        );
         InstructionError::InvalidAccountData
     })?;
 
+    let signer_key = *transaction_context.get_key_of_account_at_index(
+        instruction_context.get_index_of_instruction_account_in_transaction(account_offset)?,
+    )?;
+    ibc_handler
+        .check_admin(type_url, &signer_key)
+        .map_err(|err| admin_check_failed(invoke_context, err))?;
+
+    let migration_in_progress = ibc_handler
+        .rejects_during_migration(type_url)
+        .map_err(|err| {
+            ic_msg!(
+                invoke_context,
+                "failed to read the store write batch cursor: {:?}",
+                err
+            );
+            InstructionError::from(IbcProgramError::Storage)
+        })?;
+    if migration_in_progress {
+        ic_msg!(
+            invoke_context,
+            "MigrationInProgress {{ type_url: {} }}",
+            bounded_log::truncate(type_url, MAX_TERSE_LOG_LEN)
+        );
+        return Err(InstructionError::from(IbcProgramError::MigrationInProgress));
+    }
+
+    if ibc_handler.rejects_while_frozen(type_url) {
+        ic_msg!(
+            invoke_context,
+            "IbcFrozen {{ type_url: {} }}",
+            bounded_log::truncate(type_url, MAX_TERSE_LOG_LEN)
+        );
+        return Err(InstructionError::from(IbcProgramError::Frozen));
+    }
+
+    if let Some(prefix) = ibc_handler.disabled_message_prefix(type_url) {
+        ic_msg!(
+            invoke_context,
+            "MessageDisabled {{ type_url: {}, prefix: {} }}",
+            bounded_log::truncate(type_url, MAX_TERSE_LOG_LEN),
+            prefix
+        );
+        return Err(InstructionError::from(IbcProgramError::Disabled));
+    }
+
     f(&mut ibc_handler)?;
 
     ibc_handler.commit().map_err(|err| {
+        if let Some(rejected) = err.downcast_ref::<CommitRejected>() {
+            bounded_log::log_trunc(
+                invoke_context,
+                "CommitRejected",
+                rejected,
+                MAX_TERSE_LOG_LEN,
+            );
+            return InstructionError::from(IbcProgramError::CommitRejected);
+        }
         ic_msg!(
             invoke_context,
             "failed to commit the new IBC state Merkle tree: {:?}",
             err
         );
-        InstructionError::Custom(STORAGE_ERR_CODE)
+        InstructionError::from(IbcProgramError::Storage)
     })?;
+    let event_logs = ibc_handler.take_event_logs();
+    let event_journal_capacity = ibc_handler.event_journal_capacity();
+    for event_log in &event_logs {
+        ibc_account_data.event_journal.append(
+            clock.slot,
+            event_log.clone(),
+            event_journal_capacity,
+        );
+    }
 
     ibc_account_data.write_to_account(&mut storage_account, invoke_context)?;
-    Ok(())
+    Ok(event_logs)
+}
+
+/// Runs `update`, which leaves IBC frozen or not according to `frozen`, and logs a
+/// `FrozenChanged` event if that changes whether IBC is frozen.
+fn log_frozen_change(
+    invoke_context: &InvokeContext,
+    ibc_handler: &mut IbcHandler,
+    frozen: bool,
+    update: impl FnOnce(&mut IbcHandler),
+) {
+    let was_frozen = ibc_handler.is_frozen();
+    update(ibc_handler);
+    if was_frozen != frozen {
+        ic_msg!(invoke_context, "FrozenChanged {{ frozen: {} }}", frozen);
+    }
+}
+
+/// Logs why an IBC message failed. Errors often embed fields of the message, so
+/// their debug form is only logged when verbose logs are on, and either form is
+/// truncated.
+fn log_failure<E>(invoke_context: &InvokeContext, verbose: bool, label: &str, err: &E)
+where
+    E: fmt::Debug + fmt::Display,
+{
+    if verbose {
+        bounded_log::log_trunc(
+            invoke_context,
+            label,
+            format_args!("{err:?}"),
+            MAX_VERBOSE_LOG_LEN,
+        );
+    } else {
+        bounded_log::log_trunc(invoke_context, label, err, MAX_TERSE_LOG_LEN);
+    }
+}
+
+/// Whether the storage account already exists and was fully initialized. An
+/// account that exists but is not a valid storage account is an error, with the
+/// reason logged.
+fn storage_account_is_initialized(
+    invoke_context: &InvokeContext,
+    transaction_context: &TransactionContext,
+    instruction_context: &InstructionContext,
+    account_offset: usize,
+) -> Result<bool, InstructionError> {
+    let storage_account = instruction_context
+        .try_borrow_instruction_account(transaction_context, account_offset + 1)?;
+    if *storage_account.get_key() != STORAGE_KEY {
+        return Err(InstructionError::InvalidArgument);
+    }
+    if storage_account.get_lamports() == 0 && system_program::check_id(storage_account.get_owner())
+    {
+        return Ok(false);
+    }
+
+    if *storage_account.get_owner() != id() {
+        ic_msg!(
+            invoke_context,
+            "storage account already exists but is owned by {}",
+            storage_account.get_owner()
+        );
+        return Err(InstructionError::InvalidAccountOwner);
+    }
+    IbcAccountData::check_initialized(storage_account.get_data()).map_err(|err| {
+        ic_msg!(
+            invoke_context,
+            "storage account already exists but is not initialized: {:?}",
+            err
+        );
+        InstructionError::InvalidAccountData
+    })?;
+    Ok(true)
+}
+
+/// Logs why an admin check rejected the instruction and picks the error it fails
+/// with: [`IbcProgramError::NotAdmin`] for a signer other than the admin, or one
+/// not allowed to claim the admin of an endpoint without one.
+fn admin_check_failed(invoke_context: &InvokeContext, err: AdminError) -> InstructionError {
+    ic_msg!(invoke_context, "instruction failed: {}", err);
+    match err {
+        AdminError::NotAdmin { .. }
+        | AdminError::NoAdmin { .. }
+        | AdminError::NotBootstrapAuthority { .. } => IbcProgramError::NotAdmin,
+        _ => IbcProgramError::Admin,
+    }
+    .into()
+}
+
+/// Whether `payer_key` may claim the admin of an endpoint without one, which only
+/// the upgrade authority recorded in the program data account of this program
+/// may. The program data account follows the clock sysvar.
+fn is_bootstrap_authority(
+    transaction_context: &TransactionContext,
+    instruction_context: &InstructionContext,
+    account_offset: usize,
+    payer_key: &Pubkey,
+) -> Result<bool, InstructionError> {
+    if instruction_context.get_number_of_instruction_accounts() <= account_offset + 3 {
+        return Ok(false);
+    }
+    let program_data_account = instruction_context
+        .try_borrow_instruction_account(transaction_context, account_offset + 3)?;
+    if *program_data_account.get_key() != bpf_loader_upgradeable::get_program_data_address(&id())
+        || *program_data_account.get_owner() != bpf_loader_upgradeable::id()
+    {
+        return Ok(false);
+    }
+    match program_data_account.get_state()? {
+        UpgradeableLoaderState::ProgramData {
+            upgrade_authority_address,
+            ..
+        } => Ok(upgrade_authority_address == Some(*payer_key)),
+        _ => Ok(false),
+    }
+}
+
+/// Writes what a newly initialized IBC state starts out with: its admin, the
+/// chain params and the capabilities of this program.
+fn write_initial_state(
+    ibc_state: &mut IbcState<'_>,
+    admin: &Pubkey,
+    genesis_hash: Option<[u8; 32]>,
+) {
+    ibc_state.set(&StateInitializedPath, ());
+    ibc_state.set(&AdminPath, admin.to_string());
+    ibc_state.set(
+        &ChainParamsPath,
+        ChainParams {
+            genesis_hash,
+            ..ChainParams::default()
+        },
+    );
+    ibc_state.set(&CapabilitiesPath, capabilities::capabilities());
 }
 
 fn init_storage_account(
@@ -92,6 +349,7 @@ fn init_storage_account(
     account_offset: usize,
     payer_key: Pubkey,
     min_rent_balance: u64,
+    genesis_hash: Option<[u8; 32]>,
 ) -> Result<(), InstructionError> {
     // System account is at index 4
     invoke_context.native_invoke(
@@ -120,23 +378,397 @@ fn init_storage_account(
         account_offset + 3,
     )?;
 
-    let ibc_account_data = IbcAccountData::default();
+    let mut ibc_account_data = IbcAccountData::default();
 
     let mut ibc_state = IbcState::new(&ibc_account_data.store, clock.slot);
-    ibc_state.set(&StateInitializedPath, ());
-    ibc_state.commit().map_err(|err| {
+    write_initial_state(&mut ibc_state, &payer_key, genesis_hash);
+    let root_hash = ibc_state.commit().map_err(|err| {
         ic_msg!(
             invoke_context,
             "failed to commit the new IBC state Merkle tree: {:?}",
             err
         );
-        InstructionError::Custom(STORAGE_ERR_CODE)
+        InstructionError::from(IbcProgramError::Storage)
     })?;
+    ibc_account_data.metadata.record_commit(root_hash);
 
     ibc_account_data.write_to_account(&mut storage_account, invoke_context)?;
     Ok(())
 }
 
+/// Why the program refuses to grow the storage account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StorageReallocRejection {
+    NotGrowing {
+        current_size: u64,
+        new_size: u64,
+    },
+    TooLarge {
+        current_size: u64,
+        new_size: u64,
+        max_size: u64,
+    },
+}
+
+impl fmt::Display for StorageReallocRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotGrowing {
+                current_size,
+                new_size,
+            } => write!(
+                f,
+                "StorageReallocNotGrowing {{ current_size: {current_size}, new_size: {new_size} }}"
+            ),
+            Self::TooLarge {
+                current_size,
+                new_size,
+                max_size,
+            } => write!(
+                f,
+                "StorageReallocTooLarge {{ current_size: {current_size}, new_size: {new_size}, max_size: {max_size} }}"
+            ),
+        }
+    }
+}
+
+/// Checks that growing the storage account from `current_size` to `new_size`
+/// bytes is a single realloc the runtime allows.
+fn check_storage_realloc(current_size: u64, new_size: u64) -> Result<(), StorageReallocRejection> {
+    if new_size <= current_size {
+        return Err(StorageReallocRejection::NotGrowing {
+            current_size,
+            new_size,
+        });
+    }
+    let max_size = current_size
+        .saturating_add(MAX_PERMITTED_DATA_INCREASE as u64)
+        .min(MAX_PERMITTED_DATA_LENGTH);
+    if new_size > max_size {
+        return Err(StorageReallocRejection::TooLarge {
+            current_size,
+            new_size,
+            max_size,
+        });
+    }
+    Ok(())
+}
+
+/// Grows the storage account to `new_size` bytes, with the payer transferring
+/// whatever lamports it needs to stay rent-exempt. Only the admin may grow it.
+fn realloc_storage_account(
+    invoke_context: &mut InvokeContext,
+    account_offset: usize,
+    payer_key: Pubkey,
+    new_size: u64,
+) -> Result<(), InstructionError> {
+    let top_up = {
+        let transaction_context = &invoke_context.transaction_context;
+        let instruction_context = transaction_context.get_current_instruction_context()?;
+
+        let storage_account = instruction_context
+            .try_borrow_instruction_account(transaction_context, account_offset + 1)?;
+        if *storage_account.get_owner() != id() {
+            return Err(InstructionError::InvalidAccountOwner);
+        }
+        if *storage_account.get_key() != STORAGE_KEY {
+            return Err(InstructionError::InvalidArgument);
+        }
+
+        let ibc_account_data = IbcAccountData::read_from_account(&storage_account, invoke_context)?;
+        let latest_version = ibc_account_data
+            .store
+            .read()
+            .latest_version()
+            .ok_or(InstructionError::UninitializedAccount)?;
+        let ibc_state = IbcState::new(&ibc_account_data.store, latest_version);
+        ibc_handler::check_admin_of(&ibc_state, MsgReallocStorageAccount::TYPE_URL, &payer_key)
+            .map_err(|err| admin_check_failed(invoke_context, err))?;
+
+        let current_size = storage_account.get_data().len() as u64;
+        check_storage_realloc(current_size, new_size).map_err(|rejection| {
+            ic_msg!(invoke_context, "{}", rejection);
+            InstructionError::from(IbcProgramError::StorageRealloc)
+        })?;
+
+        let rent = get_sysvar_with_account_check::rent(
+            invoke_context,
+            instruction_context,
+            account_offset + 2,
+        )?;
+        rent.minimum_balance(new_size as usize)
+            .saturating_sub(storage_account.get_lamports())
+    };
+
+    // System account is at index 3
+    if top_up > 0 {
+        invoke_context.native_invoke(
+            system_instruction::transfer(&payer_key, &STORAGE_KEY, top_up),
+            &[],
+        )?;
+    }
+
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+    let mut storage_account = instruction_context
+        .try_borrow_instruction_account(transaction_context, account_offset + 1)?;
+    let current_size = storage_account.get_data().len();
+    storage_account.set_data_length(new_size as usize)?;
+    ic_msg!(
+        invoke_context,
+        "StorageAccountRealloc {{ current_size: {}, new_size: {}, top_up: {} }}",
+        current_size,
+        new_size,
+        top_up
+    );
+    Ok(())
+}
+
+/// Why the program refuses to create or write to a tx buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TxBufferRejection {
+    TooLarge {
+        buffer_size: u64,
+        max: u64,
+    },
+    DataTooLarge {
+        data_len: u64,
+        buffer_size: u64,
+    },
+    PayerCapExceeded {
+        open_bytes: u64,
+        buffer_size: u64,
+        max: u64,
+    },
+    WriteOutOfBounds {
+        offset: u64,
+        data_len: u64,
+        buffer_size: u64,
+    },
+}
+
+impl TxBufferRejection {
+    fn error(self) -> IbcProgramError {
+        match self {
+            Self::TooLarge { .. } => IbcProgramError::TxBufferTooLarge,
+            Self::DataTooLarge { .. } => IbcProgramError::TxBufferDataTooLarge,
+            Self::PayerCapExceeded { .. } => IbcProgramError::TxBufferPayerCapExceeded,
+            Self::WriteOutOfBounds { .. } => IbcProgramError::TxBufferWriteOutOfBounds,
+        }
+    }
+}
+
+impl fmt::Display for TxBufferRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge { buffer_size, max } => write!(
+                f,
+                "TxBufferTooLarge {{ buffer_size: {buffer_size}, max: {max} }}"
+            ),
+            Self::DataTooLarge {
+                data_len,
+                buffer_size,
+            } => write!(
+                f,
+                "TxBufferDataTooLarge {{ data_len: {data_len}, buffer_size: {buffer_size} }}"
+            ),
+            Self::PayerCapExceeded {
+                open_bytes,
+                buffer_size,
+                max,
+            } => write!(
+                f,
+                "TxBufferPayerCapExceeded {{ open_bytes: {open_bytes}, buffer_size: {buffer_size}, max: {max} }}"
+            ),
+            Self::WriteOutOfBounds {
+                offset,
+                data_len,
+                buffer_size,
+            } => write!(
+                f,
+                "TxBufferWriteOutOfBounds {{ offset: {offset}, data_len: {data_len}, buffer_size: {buffer_size} }}"
+            ),
+        }
+    }
+}
+
+/// Checks a new tx buffer of `buffer_size` bytes, whose first write is
+/// `data_len` bytes, against the chain params and the `open_bytes` of tx buffers
+/// its payer already has.
+fn check_new_tx_buffer(
+    chain_params: &ChainParams,
+    open_bytes: u64,
+    buffer_size: u64,
+    data_len: u64,
+) -> Result<(), TxBufferRejection> {
+    let max = chain_params.max_tx_buffer_size();
+    if buffer_size > max {
+        return Err(TxBufferRejection::TooLarge { buffer_size, max });
+    }
+    if data_len > buffer_size {
+        return Err(TxBufferRejection::DataTooLarge {
+            data_len,
+            buffer_size,
+        });
+    }
+    let max = chain_params.max_tx_buffer_bytes_per_payer();
+    if open_bytes.saturating_add(buffer_size) > max {
+        return Err(TxBufferRejection::PayerCapExceeded {
+            open_bytes,
+            buffer_size,
+            max,
+        });
+    }
+    Ok(())
+}
+
+/// Checks that writing `data_len` bytes at `offset` stays within a tx buffer of
+/// `buffer_size` bytes, returning the range written.
+fn check_tx_buffer_write(
+    buffer_size: u64,
+    offset: u64,
+    data_len: u64,
+) -> Result<Range<usize>, TxBufferRejection> {
+    let rejection = TxBufferRejection::WriteOutOfBounds {
+        offset,
+        data_len,
+        buffer_size,
+    };
+    match offset.checked_add(data_len) {
+        Some(end) if end <= buffer_size => Ok(offset as usize..end as usize),
+        _ => Err(rejection),
+    }
+}
+
+/// Runs `f` against the IBC state for the admin instructions that only touch
+/// internal bookkeeping, and so skip the IBC handler and its frozen and disabled
+/// checks. The storage account and clock sysvar are at the given positions.
+fn with_ibc_state<F>(
+    invoke_context: &InvokeContext,
+    storage_index: usize,
+    clock_index: usize,
+    f: F,
+) -> Result<(), InstructionError>
+where
+    F: FnOnce(&mut IbcState<'_>) -> Result<(), InstructionError>,
+{
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+
+    let mut storage_account =
+        instruction_context.try_borrow_instruction_account(transaction_context, storage_index)?;
+    if *storage_account.get_owner() != id() {
+        return Err(InstructionError::InvalidAccountOwner);
+    }
+    if *storage_account.get_key() != STORAGE_KEY {
+        return Err(InstructionError::InvalidArgument);
+    }
+
+    let clock =
+        get_sysvar_with_account_check::clock(invoke_context, instruction_context, clock_index)?;
+
+    let mut ibc_account_data = IbcAccountData::read_from_account(&storage_account, invoke_context)?;
+    let mut ibc_state = IbcState::new(&ibc_account_data.store, clock.slot);
+    f(&mut ibc_state)?;
+    let root_hash = ibc_state.commit().map_err(|err| {
+        ic_msg!(
+            invoke_context,
+            "failed to commit the new IBC state Merkle tree: {:?}",
+            err
+        );
+        InstructionError::from(IbcProgramError::Storage)
+    })?;
+    ibc_account_data.metadata.record_commit(root_hash);
+
+    ibc_account_data.write_to_account(&mut storage_account, invoke_context)
+}
+
+/// Checks that the tx buffer at `buffer_key` was created by `payer_key`, reading
+/// the IBC state from the storage account at `storage_index` without writing it.
+fn check_tx_buffer_payer(
+    invoke_context: &InvokeContext,
+    storage_index: usize,
+    buffer_key: Pubkey,
+    payer_key: Pubkey,
+) -> Result<(), InstructionError> {
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+
+    let storage_account =
+        instruction_context.try_borrow_instruction_account(transaction_context, storage_index)?;
+    if *storage_account.get_owner() != id() {
+        return Err(InstructionError::InvalidAccountOwner);
+    }
+    if *storage_account.get_key() != STORAGE_KEY {
+        return Err(InstructionError::InvalidArgument);
+    }
+
+    let ibc_account_data = IbcAccountData::read_from_account(&storage_account, invoke_context)?;
+    let state_err = |err: anyhow::Error| {
+        ic_msg!(invoke_context, "failed to read IBC state: {:?}", err);
+        InstructionError::InvalidAccountData
+    };
+    let latest_version = ibc_account_data
+        .store
+        .read()
+        .latest_version()
+        .ok_or(InstructionError::UninitializedAccount)?;
+    let buffer_payer = IbcState::new(&ibc_account_data.store, latest_version)
+        .get(&TxBufferPayerPath(buffer_key))
+        .map_err(state_err)?;
+    if buffer_payer.as_deref() != Some(&*payer_key.to_string()) {
+        ic_msg!(
+            invoke_context,
+            "TxBufferNotOwned {{ buffer: {}, payer: {} }}",
+            buffer_key,
+            payer_key
+        );
+        return Err(InstructionError::from(IbcProgramError::TxBufferNotOwned));
+    }
+    Ok(())
+}
+
+/// Checks a new tx buffer against the limits in the chain params, and records it
+/// against its payer's allowance.
+fn record_new_tx_buffer(
+    invoke_context: &InvokeContext,
+    account_offset: usize,
+    buffer_key: Pubkey,
+    payer_key: Pubkey,
+    buffer_size: u64,
+    data_len: usize,
+) -> Result<(), InstructionError> {
+    // Storage account is at index 4 and clock sysvar at index 5
+    with_ibc_state(
+        invoke_context,
+        account_offset + 4,
+        account_offset + 5,
+        |ibc_state| {
+            let state_err = |err: anyhow::Error| {
+                ic_msg!(invoke_context, "failed to read IBC state: {:?}", err);
+                InstructionError::InvalidAccountData
+            };
+            let chain_params = ChainParams::load_or_default(ibc_state).map_err(state_err)?;
+            let bytes_path = TxBufferBytesPath(payer_key);
+            let open_bytes = ibc_state
+                .get(&bytes_path)
+                .map_err(state_err)?
+                .unwrap_or_default();
+
+            check_new_tx_buffer(&chain_params, open_bytes, buffer_size, data_len as u64).map_err(
+                |rejection| {
+                    ic_msg!(invoke_context, "{}", rejection);
+                    InstructionError::from(rejection.error())
+                },
+            )?;
+
+            ibc_state.set(&bytes_path, open_bytes + buffer_size);
+            ibc_state.set(&TxBufferPayerPath(buffer_key), payer_key.to_string());
+            Ok(())
+        },
+    )
+}
+
 fn create_tx_buffer(
     invoke_context: &mut InvokeContext,
     buffer_key: Pubkey,
@@ -158,10 +790,12 @@ fn create_tx_buffer(
     Ok(())
 }
 
+/// Writes `data` at `data_offset` in the tx buffer, which must be owned by the
+/// program and writable.
 fn write_to_tx_buffer(
     invoke_context: &mut InvokeContext,
     account_offset: usize,
-    data_offset: usize,
+    data_offset: u64,
     data: &[u8],
 ) -> Result<(), InstructionError> {
     let transaction_context = &invoke_context.transaction_context;
@@ -169,18 +803,92 @@ fn write_to_tx_buffer(
 
     let mut buffer_account = instruction_context
         .try_borrow_instruction_account(transaction_context, account_offset + 1)?;
-    let buffer = &mut buffer_account.get_data_mut()?[data_offset..data_offset + data.len()];
+    if *buffer_account.get_owner() != id() {
+        return Err(InstructionError::InvalidAccountOwner);
+    }
+    if !buffer_account.is_writable() {
+        return Err(InstructionError::ReadonlyDataModified);
+    }
 
-    if buffer.len() != data.len() {
-        ic_msg!(
-            invoke_context,
-            "buffer account does not fit entirety of data",
-        );
-        return Err(InstructionError::Custom(STORAGE_ERR_CODE));
+    let buffer_size = buffer_account.get_data().len() as u64;
+    let range = check_tx_buffer_write(buffer_size, data_offset, data.len() as u64).map_err(
+        |rejection| {
+            ic_msg!(invoke_context, "{}", rejection);
+            InstructionError::from(rejection.error())
+        },
+    )?;
+    buffer_account.get_data_mut()?[range].copy_from_slice(data);
+
+    Ok(())
+}
+
+/// Closes a tx buffer created by the payer, moving its lamports to the payer and
+/// releasing its bytes from the payer's allowance.
+fn close_tx_buffer(
+    invoke_context: &InvokeContext,
+    account_offset: usize,
+    payer_key: Pubkey,
+) -> Result<(), InstructionError> {
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+
+    let mut buffer_account = instruction_context
+        .try_borrow_instruction_account(transaction_context, account_offset + 1)?;
+    if *buffer_account.get_owner() != id() {
+        return Err(InstructionError::InvalidAccountOwner);
     }
+    let buffer_key = *buffer_account.get_key();
+    let buffer_size = buffer_account.get_data().len() as u64;
+    let lamports = buffer_account.get_lamports();
+    drop(buffer_account);
+
+    // Storage account is at index 2 and clock sysvar at index 3
+    with_ibc_state(
+        invoke_context,
+        account_offset + 2,
+        account_offset + 3,
+        |ibc_state| {
+            let state_err = |err: anyhow::Error| {
+                ic_msg!(invoke_context, "failed to read IBC state: {:?}", err);
+                InstructionError::InvalidAccountData
+            };
+            let payer_path = TxBufferPayerPath(buffer_key);
+            let buffer_payer = ibc_state.get(&payer_path).map_err(state_err)?;
+            if buffer_payer.as_deref() != Some(&*payer_key.to_string()) {
+                ic_msg!(
+                    invoke_context,
+                    "TxBufferNotOwned {{ buffer: {}, payer: {} }}",
+                    buffer_key,
+                    payer_key
+                );
+                return Err(InstructionError::from(IbcProgramError::TxBufferNotOwned));
+            }
+            ibc_state.remove(&payer_path);
+
+            let bytes_path = TxBufferBytesPath(payer_key);
+            let open_bytes = ibc_state
+                .get(&bytes_path)
+                .map_err(state_err)?
+                .unwrap_or_default()
+                .saturating_sub(buffer_size);
+            if open_bytes == 0 {
+                ibc_state.remove(&bytes_path);
+            } else {
+                ibc_state.set(&bytes_path, open_bytes);
+            }
+            Ok(())
+        },
+    )?;
 
-    buffer.copy_from_slice(data);
+    let mut buffer_account = instruction_context
+        .try_borrow_instruction_account(transaction_context, account_offset + 1)?;
+    buffer_account.set_data(&[])?;
+    buffer_account.set_lamports(0)?;
+    drop(buffer_account);
 
+    let mut payer_account =
+        instruction_context.try_borrow_instruction_account(transaction_context, account_offset)?;
+    payer_account.checked_add_lamports(lamports)?;
     Ok(())
 }
 
@@ -191,10 +899,34 @@ pub fn process_instruction(
     _first_instruction_account: usize,
     invoke_context: &mut InvokeContext,
 ) -> Result<(), InstructionError> {
+    let event_logs = process_ibc_instruction(invoke_context)?;
+    if event_logs.is_empty() {
+        return Ok(());
+    }
+
+    // The events are also in the program logs, which are not truncated
+    let (return_data, included) = ibc_events::encode_return_data(&event_logs);
+    if included < event_logs.len() {
+        ic_msg!(
+            invoke_context,
+            "return data holds {} of {} IBC events",
+            included,
+            event_logs.len()
+        );
+    }
+    invoke_context
+        .transaction_context
+        .set_return_data(id(), return_data)
+}
+
+/// Processes the instruction, returning the log lines of the IBC events emitted.
+fn process_ibc_instruction(
+    invoke_context: &mut InvokeContext,
+) -> Result<Vec<String>, InstructionError> {
     let transaction_context = &invoke_context.transaction_context;
     let instruction_context = transaction_context.get_current_instruction_context()?;
 
-    let (ibc_instruction, account_offset) = ibc_contract_instruction::parse_instruction(
+    let (ibc_instruction, type_url, account_offset) = ibc_contract_instruction::parse_instruction(
         invoke_context,
         transaction_context,
         instruction_context,
@@ -207,53 +939,214 @@ pub fn process_instruction(
     }
     let payer_key = *payer_account.get_key();
 
+    let mut event_logs = vec![];
     match ibc_instruction {
-        IbcInstruction::Router(envelope) => {
-            with_ibc_handler(
+        IbcInstruction::Router(mut envelope) => {
+            event_logs = with_ibc_handler(
                 invoke_context,
                 transaction_context,
                 instruction_context,
                 account_offset,
+                &type_url,
                 |ibc_handler| {
+                    let verbose = ibc_handler.verbose_logs();
+                    let legacy_rejection = ibc_handler
+                        .canonicalize_type_urls(&mut envelope)
+                        .map_err(|err| {
+                            log_failure(
+                                invoke_context,
+                                verbose,
+                                "failed to rewrite legacy type URLs",
+                                &err,
+                            );
+                            InstructionError::InvalidAccountData
+                        })?;
+                    if let Some(rejection) = legacy_rejection {
+                        bounded_log::log_trunc(
+                            invoke_context,
+                            "instruction failed",
+                            rejection,
+                            MAX_TERSE_LOG_LEN,
+                        );
+                        return Err(InstructionError::from(IbcProgramError::Router));
+                    }
+                    if let Some(rejection) = multihop_channel_rejection(&envelope) {
+                        bounded_log::log_trunc(
+                            invoke_context,
+                            "instruction failed",
+                            rejection,
+                            MAX_TERSE_LOG_LEN,
+                        );
+                        return Err(InstructionError::from(IbcProgramError::Channel));
+                    }
+                    let mismatch = ibc_handler
+                        .client_type_mismatch(&envelope)
+                        .and_then(|mismatch| match mismatch {
+                            Some(mismatch) => Ok(Some(mismatch)),
+                            None => ibc_handler.handshake_chain_mismatch(&envelope),
+                        })
+                        .map_err(|err| {
+                            log_failure(
+                                invoke_context,
+                                verbose,
+                                "failed to read client or handshake",
+                                &err,
+                            );
+                            InstructionError::InvalidAccountData
+                        })?;
+                    if let Some(mismatch) = mismatch {
+                        bounded_log::log_trunc(
+                            invoke_context,
+                            "instruction failed",
+                            mismatch,
+                            MAX_TERSE_LOG_LEN,
+                        );
+                        return Err(InstructionError::from(IbcProgramError::Router));
+                    }
+                    ibc_handler
+                        .route_to_channel_owner(&envelope)
+                        .map_err(|err| {
+                            log_failure(
+                                invoke_context,
+                                verbose,
+                                "failed to read channel owner",
+                                &err,
+                            );
+                            InstructionError::InvalidAccountData
+                        })?;
+                    let mismatch =
+                        ibc_handler
+                            .callback_version_mismatch(&envelope)
+                            .map_err(|err| {
+                                log_failure(
+                                    invoke_context,
+                                    verbose,
+                                    "failed to read module callback versions",
+                                    &err,
+                                );
+                                InstructionError::InvalidAccountData
+                            })?;
+                    if let Some(mismatch) = mismatch {
+                        bounded_log::log_trunc(
+                            invoke_context,
+                            "instruction failed",
+                            mismatch,
+                            MAX_TERSE_LOG_LEN,
+                        );
+                        return Err(InstructionError::from(IbcProgramError::Router));
+                    }
+
                     dispatch(ibc_handler, envelope).map_err(|err| {
-                        ic_msg!(invoke_context, "instruction failed: {:?}", err);
-                        InstructionError::Custom(ROUTER_ERR_CODE)
+                        log_failure(invoke_context, verbose, "instruction failed", &err);
+                        InstructionError::from(IbcProgramError::from(&err))
                     })
                 },
             )?;
         }
-        IbcInstruction::Port(PortInstruction::Bind(MsgBindPort { port_id })) => {
-            with_ibc_handler(
+        IbcInstruction::Transfer(msg) => {
+            event_logs = with_ibc_handler(
                 invoke_context,
                 transaction_context,
                 instruction_context,
                 account_offset,
+                &type_url,
                 |ibc_handler| {
-                    ibc_handler.bind_port(&port_id, &payer_key).map_err(|err| {
-                        ic_msg!(invoke_context, "instruction failed: {:?}", err);
-                        InstructionError::Custom(PORT_ERR_CODE)
+                    let verbose = ibc_handler.verbose_logs();
+                    ibc_handler.transfer(msg, &payer_key).map_err(|err| {
+                        log_failure(invoke_context, verbose, "transfer failed", &err);
+                        InstructionError::from(IbcProgramError::Transfer)
                     })
                 },
             )?;
         }
+        IbcInstruction::Port(PortInstruction::Bind(MsgBindPort {
+            port_id,
+            account_templates,
+        })) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    let verbose = ibc_handler.verbose_logs();
+                    ibc_handler
+                        .bind_port(&port_id, &payer_key, account_templates)
+                        .map_err(|err| {
+                            log_failure(invoke_context, verbose, "instruction failed", &err);
+                            InstructionError::from(IbcProgramError::Port)
+                        })
+                },
+            )?;
+        }
         IbcInstruction::Port(PortInstruction::Release(MsgReleasePort { port_id })) => {
-            with_ibc_handler(
+            event_logs = with_ibc_handler(
                 invoke_context,
                 transaction_context,
                 instruction_context,
                 account_offset,
+                &type_url,
                 |ibc_handler| {
+                    let verbose = ibc_handler.verbose_logs();
                     ibc_handler
                         .release_port(&port_id, &payer_key)
                         .map_err(|err| {
-                            ic_msg!(invoke_context, "instruction failed: {:?}", err);
-                            InstructionError::Custom(PORT_ERR_CODE)
+                            log_failure(invoke_context, verbose, "instruction failed", &err);
+                            InstructionError::from(IbcProgramError::Port)
+                        })
+                },
+            )?;
+        }
+        IbcInstruction::Port(PortInstruction::Transfer(MsgTransferPort {
+            port_id,
+            new_module_pubkey,
+        })) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    let verbose = ibc_handler.verbose_logs();
+                    ibc_handler
+                        .transfer_port(&port_id, &payer_key, &new_module_pubkey)
+                        .map_err(|err| {
+                            log_failure(invoke_context, verbose, "instruction failed", &err);
+                            InstructionError::from(IbcProgramError::Port)
                         })
                 },
             )?;
         }
-        IbcInstruction::Admin(AdminInstruction::InitStorageAccount(MsgInitStorageAccount)) => {
+        IbcInstruction::Admin(AdminInstruction::InitStorageAccount(MsgInitStorageAccount {
+            genesis_hash,
+            idempotent,
+        })) => {
             instruction_context.check_number_of_instruction_accounts(account_offset + 5)?;
+            account_spec::check_accounts(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                INIT_STORAGE_ACCOUNT_ACCOUNTS,
+            )?;
+
+            if idempotent
+                && storage_account_is_initialized(
+                    invoke_context,
+                    transaction_context,
+                    instruction_context,
+                    account_offset,
+                )?
+            {
+                ic_msg!(
+                    invoke_context,
+                    "NoopInit {{ storage_account: {} }}",
+                    STORAGE_KEY
+                );
+                return Ok(event_logs);
+            }
 
             let rent = get_sysvar_with_account_check::rent(
                 invoke_context,
@@ -266,7 +1159,13 @@ pub fn process_instruction(
             // requires `&mut invoke_context`.
             drop(payer_account);
 
-            init_storage_account(invoke_context, account_offset, payer_key, min_rent_balance)?;
+            init_storage_account(
+                invoke_context,
+                account_offset,
+                payer_key,
+                min_rent_balance,
+                genesis_hash,
+            )?;
         }
         IbcInstruction::Admin(AdminInstruction::WriteTxBuffer(MsgWriteTxBuffer { mode, data })) => {
             // Accounts need to be dropped because `invoke_context.native_invoke`
@@ -275,11 +1174,28 @@ pub fn process_instruction(
 
             let data_offset = match mode {
                 MsgWriteTxBufferMode::Create { buffer_size } => {
-                    instruction_context.check_number_of_instruction_accounts(account_offset + 4)?;
+                    instruction_context.check_number_of_instruction_accounts(account_offset + 6)?;
+                    account_spec::check_accounts(
+                        invoke_context,
+                        transaction_context,
+                        instruction_context,
+                        account_offset,
+                        CREATE_TX_BUFFER_ACCOUNTS,
+                    )?;
 
                     let buffer_key =
                         *transaction_context.get_key_of_account_at_index(account_offset + 1)?;
 
+                    // Checked before the rent, which could overflow for absurd sizes
+                    record_new_tx_buffer(
+                        invoke_context,
+                        account_offset,
+                        buffer_key,
+                        payer_key,
+                        buffer_size,
+                        data.len(),
+                    )?;
+
                     let rent = get_sysvar_with_account_check::rent(
                         invoke_context,
                         instruction_context,
@@ -298,22 +1214,397 @@ pub fn process_instruction(
                     0
                 }
                 MsgWriteTxBufferMode::Reuse { offset } => {
-                    instruction_context.check_number_of_instruction_accounts(account_offset + 2)?;
+                    instruction_context.check_number_of_instruction_accounts(account_offset + 3)?;
+                    account_spec::check_accounts(
+                        invoke_context,
+                        transaction_context,
+                        instruction_context,
+                        account_offset,
+                        REUSE_TX_BUFFER_ACCOUNTS,
+                    )?;
+
+                    // Storage account is at index 2
+                    let buffer_key = *instruction_context
+                        .try_borrow_instruction_account(transaction_context, account_offset + 1)?
+                        .get_key();
+                    check_tx_buffer_payer(
+                        invoke_context,
+                        account_offset + 2,
+                        buffer_key,
+                        payer_key,
+                    )?;
 
                     offset
                 }
             };
 
-            write_to_tx_buffer(invoke_context, account_offset, data_offset as usize, &data)?;
+            write_to_tx_buffer(invoke_context, account_offset, data_offset, &data)?;
+        }
+        IbcInstruction::Admin(AdminInstruction::CloseTxBuffer(MsgCloseTxBuffer)) => {
+            instruction_context.check_number_of_instruction_accounts(account_offset + 4)?;
+            account_spec::check_accounts(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                CLOSE_TX_BUFFER_ACCOUNTS,
+            )?;
+            drop(payer_account);
+
+            close_tx_buffer(invoke_context, account_offset, payer_key)?;
+        }
+        IbcInstruction::Admin(AdminInstruction::StoreWriteBatch(chunk)) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    ibc_handler
+                        .apply_store_write_batch(&payer_key, chunk)
+                        .map_err(|err| {
+                            ic_msg!(invoke_context, "instruction failed: {}", err);
+                            InstructionError::from(IbcProgramError::Storage)
+                        })
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::AbortStoreWriteBatch(MsgAbortStoreWriteBatch)) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    let cursor = ibc_handler.abort_store_write_batch().map_err(|err| {
+                        ic_msg!(invoke_context, "instruction failed: {}", err);
+                        InstructionError::from(IbcProgramError::Storage)
+                    })?;
+                    ic_msg!(
+                        invoke_context,
+                        "StoreWriteBatchAborted {{ aborted_by: {}, next_chunk_index: {} }}",
+                        payer_key,
+                        cursor.next_chunk_index
+                    );
+                    Ok(())
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::UpdateChainParams(MsgUpdateChainParams {
+            params,
+        })) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    let frozen = params.frozen;
+                    log_frozen_change(invoke_context, ibc_handler, frozen, |ibc_handler| {
+                        ibc_handler.update_chain_params(params);
+                    });
+                    Ok(())
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::SetIbcFrozen(MsgSetIbcFrozen { frozen })) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    log_frozen_change(invoke_context, ibc_handler, frozen, |ibc_handler| {
+                        ibc_handler.set_frozen(frozen);
+                    });
+                    Ok(())
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::ReallocStorageAccount(
+            MsgReallocStorageAccount { new_size },
+        )) => {
+            instruction_context.check_number_of_instruction_accounts(account_offset + 4)?;
+            account_spec::check_accounts(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                REALLOC_STORAGE_ACCOUNT_ACCOUNTS,
+            )?;
+
+            // Accounts need to be dropped because `invoke_context.native_invoke`
+            // requires `&mut invoke_context`.
+            drop(payer_account);
+
+            realloc_storage_account(invoke_context, account_offset, payer_key, new_size)?;
+        }
+        IbcInstruction::Admin(AdminInstruction::PruneState(MsgPruneState { retain_slots })) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    let pruned_client_updates =
+                        ibc_handler.prune_client_updates().map_err(|err| {
+                            ic_msg!(invoke_context, "failed to prune client updates: {:?}", err);
+                            InstructionError::from(IbcProgramError::Storage)
+                        })?;
+                    let pruning = ibc_handler
+                        .prune_state(retain_slots, PRUNED_NODES_PER_INSTRUCTION)
+                        .map_err(|err| {
+                            ic_msg!(invoke_context, "failed to prune the IBC state: {:?}", err);
+                            InstructionError::from(IbcProgramError::Storage)
+                        })?;
+                    ic_msg!(
+                        invoke_context,
+                        "StatePruned {{ versions: {}, nodes: {}, remaining_nodes: {}, \
+                         client_updates: {} }}",
+                        pruning.pruned_versions,
+                        pruning.pruned_nodes,
+                        pruning.remaining_nodes,
+                        pruned_client_updates
+                    );
+                    Ok(())
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::ProposeAdminChange(MsgProposeAdminChange {
+            new_admin,
+            effective_after_slot,
+        })) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    ibc_handler
+                        .propose_admin_change(&payer_key, &new_admin, effective_after_slot)
+                        .map_err(|err| admin_check_failed(invoke_context, err))?;
+                    ic_msg!(
+                        invoke_context,
+                        "AdminChangeProposed {{ proposed_by: {}, new_admin: {}, effective_after_slot: {} }}",
+                        payer_key,
+                        new_admin,
+                        effective_after_slot
+                    );
+                    Ok(())
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::AcceptAdminChange(MsgAcceptAdminChange)) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    let previous_admin =
+                        ibc_handler.accept_admin_change(&payer_key).map_err(|err| {
+                            ic_msg!(invoke_context, "instruction failed: {}", err);
+                            InstructionError::from(IbcProgramError::Admin)
+                        })?;
+                    ic_msg!(
+                        invoke_context,
+                        "AdminChanged {{ previous_admin: {}, new_admin: {} }}",
+                        previous_admin.map_or_else(|| "none".to_owned(), |admin| admin.to_string()),
+                        payer_key
+                    );
+                    Ok(())
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::CancelAdminChange(MsgCancelAdminChange)) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    let cancelled = ibc_handler.cancel_admin_change().map_err(|err| {
+                        ic_msg!(invoke_context, "instruction failed: {}", err);
+                        InstructionError::from(IbcProgramError::Admin)
+                    })?;
+                    ic_msg!(
+                        invoke_context,
+                        "AdminChangeCancelled {{ cancelled_by: {}, new_admin: {}, effective_after_slot: {} }}",
+                        payer_key,
+                        cancelled.new_admin,
+                        cancelled.effective_after_slot
+                    );
+                    Ok(())
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::ClaimAdmin(MsgClaimAdmin)) => {
+            let bootstrap_authority = is_bootstrap_authority(
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &payer_key,
+            )?;
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    if !bootstrap_authority {
+                        return Err(admin_check_failed(
+                            invoke_context,
+                            AdminError::NotBootstrapAuthority { signer: payer_key },
+                        ));
+                    }
+                    ibc_handler
+                        .claim_admin(&payer_key)
+                        .map_err(|err| admin_check_failed(invoke_context, err))?;
+                    ic_msg!(
+                        invoke_context,
+                        "AdminChanged {{ previous_admin: none, new_admin: {} }}",
+                        payer_key
+                    );
+                    Ok(())
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::WriteUpgrade(msg)) => {
+            let upgrade_height = msg.upgrade_height;
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    let verbose = ibc_handler.verbose_logs();
+                    ibc_handler.write_upgrade(msg).map_err(|err| {
+                        // Invalid upgrades embed decode errors of the states
+                        log_failure(invoke_context, verbose, "instruction failed", &err);
+                        InstructionError::from(IbcProgramError::Admin)
+                    })?;
+                    ic_msg!(
+                        invoke_context,
+                        "UpgradeWritten {{ upgrade_height: {} }}",
+                        upgrade_height
+                    );
+                    Ok(())
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::RefreshCapabilities(MsgRefreshCapabilities)) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    let previous = ibc_handler.refresh_capabilities().map_err(|err| {
+                        ic_msg!(invoke_context, "failed to read the capabilities: {:?}", err);
+                        InstructionError::from(IbcProgramError::Storage)
+                    })?;
+                    ic_msg!(
+                        invoke_context,
+                        "CapabilitiesRefreshed {{ previous_version: {}, program_version: {}, build_id: {} }}",
+                        previous
+                            .map_or_else(|| "none".to_owned(), |previous| previous.program_version),
+                        capabilities::PROGRAM_VERSION,
+                        build_info::GIT_HASH
+                    );
+                    Ok(())
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::ForceReleasePort(MsgForceReleasePort {
+            port_id,
+        })) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    let verbose = ibc_handler.verbose_logs();
+                    ibc_handler.force_release_port(&port_id).map_err(|err| {
+                        log_failure(invoke_context, verbose, "instruction failed", &err);
+                        InstructionError::from(IbcProgramError::Port)
+                    })
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::SetRevisionNumber(MsgSetRevisionNumber {
+            revision_number,
+        })) => {
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    let previous =
+                        ibc_handler
+                            .set_revision_number(revision_number)
+                            .map_err(|err| {
+                                ic_msg!(invoke_context, "instruction failed: {}", err);
+                                InstructionError::from(IbcProgramError::Admin)
+                            })?;
+                    ic_msg!(
+                        invoke_context,
+                        "RevisionNumberSet {{ previous: {}, revision_number: {} }}",
+                        previous,
+                        revision_number
+                    );
+                    Ok(())
+                },
+            )?;
+        }
+        IbcInstruction::Admin(AdminInstruction::RepairIndices(msg)) => {
+            let kind = msg.kind;
+            event_logs = with_ibc_handler(
+                invoke_context,
+                transaction_context,
+                instruction_context,
+                account_offset,
+                &type_url,
+                |ibc_handler| {
+                    let repair = ibc_handler.repair_indices(msg).map_err(|err| {
+                        ic_msg!(invoke_context, "instruction failed: {}", err);
+                        InstructionError::from(IbcProgramError::Storage)
+                    })?;
+                    ic_msg!(
+                        invoke_context,
+                        "IndicesRepaired {{ kind: {}, repaired: {}, remaining: {} }}",
+                        kind,
+                        repair.repaired,
+                        repair.remaining
+                    );
+                    Ok(())
+                },
+            )?;
         }
     }
 
-    Ok(())
+    Ok(event_logs)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use {
+        super::*, eclipse_ibc_extra_types::DEFAULT_MAX_TX_BUFFER_SIZE, eclipse_ibc_state::IbcStore,
+    };
 
     const VAULT_SEED: &[u8] = b"eclipse-ibc";
     const BUMP_SEED: u8 = 254;
@@ -333,4 +1624,169 @@ mod tests {
             Pubkey::create_program_address(&[VAULT_SEED, &[BUMP_SEED]], &id()).unwrap();
         assert_eq!(expected_pda, actual_pda);
     }
+
+    #[test]
+    fn tx_buffer_size_is_bounded() {
+        let chain_params = ChainParams::default();
+        let max = DEFAULT_MAX_TX_BUFFER_SIZE;
+        check_new_tx_buffer(&chain_params, 0, max, max).unwrap();
+        assert_eq!(
+            check_new_tx_buffer(&chain_params, 0, max + 1, 0),
+            Err(TxBufferRejection::TooLarge {
+                buffer_size: max + 1,
+                max,
+            }),
+        );
+        assert_eq!(
+            check_new_tx_buffer(&chain_params, 0, u64::MAX, 0)
+                .unwrap_err()
+                .error(),
+            IbcProgramError::TxBufferTooLarge,
+        );
+
+        let chain_params = ChainParams {
+            max_tx_buffer_size: Some(10),
+            ..ChainParams::default()
+        };
+        check_new_tx_buffer(&chain_params, 0, 10, 0).unwrap();
+        check_new_tx_buffer(&chain_params, 0, 11, 0).unwrap_err();
+    }
+
+    #[test]
+    fn tx_buffer_data_must_fit() {
+        let chain_params = ChainParams::default();
+        check_new_tx_buffer(&chain_params, 0, 825, 825).unwrap();
+        assert_eq!(
+            check_new_tx_buffer(&chain_params, 0, 825, 826),
+            Err(TxBufferRejection::DataTooLarge {
+                data_len: 826,
+                buffer_size: 825,
+            }),
+        );
+    }
+
+    #[test]
+    fn storage_reallocs_grow_within_the_runtime_limit() {
+        let current_size = MAX_CPI_INSTRUCTION_DATA_LEN;
+        check_storage_realloc(current_size, current_size + 1).unwrap();
+        check_storage_realloc(
+            current_size,
+            current_size + MAX_PERMITTED_DATA_INCREASE as u64,
+        )
+        .unwrap();
+
+        for new_size in [0, current_size] {
+            assert_eq!(
+                check_storage_realloc(current_size, new_size),
+                Err(StorageReallocRejection::NotGrowing {
+                    current_size,
+                    new_size,
+                }),
+            );
+        }
+        let new_size = current_size + MAX_PERMITTED_DATA_INCREASE as u64 + 1;
+        assert_eq!(
+            check_storage_realloc(current_size, new_size),
+            Err(StorageReallocRejection::TooLarge {
+                current_size,
+                new_size,
+                max_size: new_size - 1,
+            }),
+        );
+        assert!(matches!(
+            check_storage_realloc(MAX_PERMITTED_DATA_LENGTH, MAX_PERMITTED_DATA_LENGTH + 1),
+            Err(StorageReallocRejection::TooLarge { max_size, .. })
+                if max_size == MAX_PERMITTED_DATA_LENGTH,
+        ));
+    }
+
+    #[test]
+    fn tx_buffer_writes_must_stay_in_bounds() {
+        assert_eq!(check_tx_buffer_write(2475, 0, 825), Ok(0..825));
+        assert_eq!(check_tx_buffer_write(2475, 1650, 825), Ok(1650..2475));
+        assert_eq!(
+            check_tx_buffer_write(2475, 1651, 825),
+            Err(TxBufferRejection::WriteOutOfBounds {
+                offset: 1651,
+                data_len: 825,
+                buffer_size: 2475,
+            }),
+        );
+        assert_eq!(
+            check_tx_buffer_write(2475, u64::MAX, 1)
+                .unwrap_err()
+                .error(),
+            IbcProgramError::TxBufferWriteOutOfBounds,
+        );
+    }
+
+    #[test]
+    fn tx_buffer_bytes_are_capped_per_payer() {
+        let chain_params = ChainParams {
+            max_tx_buffer_bytes_per_payer: Some(2000),
+            ..ChainParams::default()
+        };
+        check_new_tx_buffer(&chain_params, 1000, 1000, 0).unwrap();
+        assert_eq!(
+            check_new_tx_buffer(&chain_params, 1001, 1000, 0),
+            Err(TxBufferRejection::PayerCapExceeded {
+                open_bytes: 1001,
+                buffer_size: 1000,
+                max: 2000,
+            }),
+        );
+        // Overflowing the open bytes counts as going over the cap
+        check_new_tx_buffer(&chain_params, u64::MAX, 1, 0).unwrap_err();
+    }
+
+    #[test]
+    fn initial_state_advertises_the_capabilities_of_this_program() {
+        let store = IbcStore::default();
+        let mut ibc_state = IbcState::new(&store, 1);
+        write_initial_state(&mut ibc_state, &Pubkey::new_unique(), Some([7; 32]));
+        ibc_state.commit().unwrap();
+
+        assert_eq!(ibc_state.get(&StateInitializedPath).unwrap(), Some(()));
+        assert_eq!(
+            ibc_state
+                .get(&ChainParamsPath)
+                .unwrap()
+                .unwrap()
+                .genesis_hash,
+            Some([7; 32])
+        );
+        let advertised = ibc_state.get(&CapabilitiesPath).unwrap().unwrap();
+        assert_eq!(advertised, capabilities::capabilities());
+        assert_eq!(advertised.program_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(advertised.build_id, build_info::GIT_HASH);
+        assert!(!advertised.build_id.is_empty());
+        assert!(advertised.supports(MsgInitStorageAccount::TYPE_URL));
+    }
+
+    #[test]
+    fn initializing_payer_is_the_admin() {
+        let payer = Pubkey::new_unique();
+        let store = IbcStore::default();
+        let mut ibc_state = IbcState::new(&store, 1);
+        write_initial_state(&mut ibc_state, &payer, None);
+        ibc_state.commit().unwrap();
+
+        assert_eq!(ibc_handler::admin_of(&ibc_state).unwrap(), Some(payer));
+        for type_url in [
+            MsgReallocStorageAccount::TYPE_URL,
+            MsgSetIbcFrozen::TYPE_URL,
+        ] {
+            ibc_handler::check_admin_of(&ibc_state, type_url, &payer).unwrap();
+            let err = ibc_handler::check_admin_of(&ibc_state, type_url, &Pubkey::new_unique())
+                .unwrap_err();
+            assert!(matches!(err, AdminError::NotAdmin { admin, .. } if admin == payer));
+        }
+        // Tx buffers belong to whoever pays for them
+        ibc_handler::check_admin_of(
+            &ibc_state,
+            MsgWriteTxBuffer::TYPE_URL,
+            &Pubkey::new_unique(),
+        )
+        .unwrap();
+    }
 }
@@ -1,95 +1,762 @@
 use {
+    crate::{
+        bounded_log::{self, MAX_TERSE_LOG_LEN},
+        module_instruction::{error_ack, success_ack},
+    },
+    anyhow::anyhow,
+    core::str::FromStr,
+    eclipse_ibc_extra_types::DenomTrace,
+    eclipse_ibc_state::{
+        internal_path::{DenomTracePath, Ics20BalancePath},
+        IbcState, IbcStore,
+    },
     ibc::{
         applications::transfer::{
-            amount::Amount, coin::PrefixedCoin, denom::PrefixedDenom, error::TokenTransferError,
+            amount::Amount,
+            coin::PrefixedCoin,
+            denom::{is_receiver_chain_source, is_sender_chain_source, PrefixedDenom, TracePrefix},
+            packet::PacketData,
+        },
+        core::{
+            ics04_channel::{
+                channel::{Counterparty, Order},
+                error::{ChannelError, PacketError},
+                packet::{Acknowledgement, Packet},
+                Version,
+            },
+            ics24_host::identifier::{ChannelId, ConnectionId, PortId},
+            router::{Module, ModuleExtras, ModuleId},
         },
-        core::ics24_host::identifier::{ChannelId, PortId},
         Signer,
     },
-    serde::{Deserialize, Serialize},
-    std::collections::{BTreeMap, HashMap},
+    solana_sdk::{clock::Slot, hash::hashv, pubkey::Pubkey},
 };
 
-#[derive(Debug, Default, Deserialize, Serialize)]
-pub(super) struct Ics20Module {
-    port: Option<PortId>,
-    signer_amt_by_token: BTreeMap<PrefixedDenom, HashMap<Signer, Amount>>,
-    is_send_enabled: bool,
-    is_receive_enabled: bool,
+/// ID the built-in ICS-20 module is routed under. It is not a program ID, so no
+/// program can bind a port under it.
+const ICS20_MODULE_ID: &str = "ics20";
+
+const ICS20_VERSION: &str = "ics20-1";
+
+/// First seed of the escrow account of every channel.
+const ESCROW_SEED: &[u8] = b"ics20-escrow";
+
+pub(super) fn ics20_module_id() -> ModuleId {
+    ModuleId::new(ICS20_MODULE_ID.to_owned())
+}
+
+/// Escrow account of the channel, which holds the tokens sent over it that
+/// originate on this chain. It only ever has a ledger balance; nothing is
+/// created or funded at the address. The port and channel IDs are hashed since
+/// they can be longer than the 32 bytes allowed in a seed.
+pub(super) fn escrow_address(port_id: &PortId, channel_id: &ChannelId) -> Pubkey {
+    let channel_hash = hashv(&[
+        port_id.as_str().as_bytes(),
+        b"/",
+        channel_id.as_str().as_bytes(),
+    ]);
+    Pubkey::find_program_address(&[ESCROW_SEED, channel_hash.as_ref()], &crate::id()).0
+}
+
+fn parse_account(signer: &Signer) -> anyhow::Result<Pubkey> {
+    Pubkey::from_str(signer.as_ref())
+        .map_err(|err| anyhow!("{signer} is not a Solana address: {err}"))
 }
 
-impl Ics20Module {
-    pub(super) fn _bind_port(&mut self, port: PortId) {
-        let _old_port = self.port.insert(port);
+fn parse_packet_data(packet: &Packet) -> anyhow::Result<PacketData> {
+    serde_json::from_slice(&packet.data).map_err(|err| anyhow!("invalid ICS-20 packet data: {err}"))
+}
+
+/// Whether `acknowledgement` is an ICS-20 error acknowledgement, which means the
+/// tokens were not received and must be refunded.
+fn is_error_ack(acknowledgement: &Acknowledgement) -> anyhow::Result<bool> {
+    let ack = serde_json::from_slice::<serde_json::Value>(acknowledgement.as_ref())
+        .map_err(|err| anyhow!("invalid ICS-20 acknowledgement: {err}"))?;
+    if ack.get("error").is_some() {
+        Ok(true)
+    } else if ack.get("result").is_some() {
+        Ok(false)
+    } else {
+        Err(anyhow!(
+            "ICS-20 acknowledgement {ack} has no result or error"
+        ))
+    }
+}
+
+fn check_version(version: &Version) -> Result<(), ChannelError> {
+    if version.to_string() == ICS20_VERSION {
+        Ok(())
+    } else {
+        Err(ChannelError::Other {
+            description: format!(
+                "ICS-20 channels must have version {ICS20_VERSION}, not {version}"
+            ),
+        })
+    }
+}
+
+fn check_order(order: Order) -> Result<(), ChannelError> {
+    if order == Order::Unordered {
+        Ok(())
+    } else {
+        Err(ChannelError::Other {
+            description: format!("ICS-20 channels must be unordered, not {order}"),
+        })
+    }
+}
+
+fn packet_err(err: anyhow::Error) -> PacketError {
+    bounded_log::msg_trunc(
+        "ICS-20 packet failed",
+        format_args!("{err:#}"),
+        MAX_TERSE_LOG_LEN,
+    );
+    // TODO: Fix the IBC library to include an error message
+    PacketError::ImplementationSpecific
+}
+
+/// The built-in ICS-20 fungible token transfer module, which serves the
+/// `transfer` port. Balances are kept in the IBC state under `Ics20BalancePath`,
+/// in a view of the store that is merged into the handler's state before it is
+/// committed.
+///
+/// The module is a voucher ledger only: no lamports or SPL tokens move when a
+/// balance is escrowed, minted or burned. Nothing credits a ledger balance of a
+/// denom native to this chain, so only vouchers received over a channel can be
+/// sent; sending a native denom fails for lack of balance until a deposit path
+/// is added.
+#[derive(Debug)]
+pub(super) struct Ics20Module<'a> {
+    state: IbcState<'a>,
+}
+
+impl<'a> Ics20Module<'a> {
+    pub(super) fn new(store: &'a IbcStore, slot: Slot) -> Self {
+        Self {
+            state: IbcState::new(store, slot),
+        }
+    }
+
+    /// The balance changes made so far, which the handler commits with its own.
+    pub(super) fn state_mut(&mut self) -> &mut IbcState<'a> {
+        &mut self.state
+    }
+
+    fn balance(&self, account: &Pubkey, denom: &PrefixedDenom) -> anyhow::Result<Amount> {
+        let balance_path = Ics20BalancePath(*account, denom.to_string());
+        match self.state.get(&balance_path)? {
+            Some(balance) => Amount::from_str(&balance)
+                .map_err(|err| anyhow!("invalid balance {balance:?} at {balance_path}: {err}")),
+            None => Ok(0u64.into()),
+        }
+    }
+
+    fn set_balance(&mut self, account: &Pubkey, denom: &PrefixedDenom, balance: Amount) {
+        let balance_path = Ics20BalancePath(*account, denom.to_string());
+        if balance == 0u64.into() {
+            self.state.remove(&balance_path);
+        } else {
+            self.state.set(&balance_path, balance.to_string());
+        }
+    }
+
+    fn added_balance(&self, account: &Pubkey, coin: &PrefixedCoin) -> anyhow::Result<Amount> {
+        self.balance(account, &coin.denom)?
+            .checked_add(coin.amount)
+            .ok_or_else(|| anyhow!("balance of {} in {account} overflows", coin.denom))
+    }
+
+    fn subtracted_balance(&self, account: &Pubkey, coin: &PrefixedCoin) -> anyhow::Result<Amount> {
+        let balance = self.balance(account, &coin.denom)?;
+        balance.checked_sub(coin.amount).ok_or_else(|| {
+            anyhow!(
+                "{account} holds {balance} {}, less than the {} sent",
+                coin.denom,
+                coin.amount
+            )
+        })
     }
 }
 
 // impl BankKeeper for Ics20Module
-impl Ics20Module {
-    fn _send_coins(
+impl Ics20Module<'_> {
+    fn send_coins(
         &mut self,
-        _from: &Signer,
-        _to: &Signer,
-        _amt: &PrefixedCoin,
-    ) -> Result<(), TokenTransferError> {
-        todo!()
+        from: &Pubkey,
+        to: &Pubkey,
+        coin: &PrefixedCoin,
+    ) -> anyhow::Result<()> {
+        // Both balances are checked before either is written, so that a failed
+        // receive leaves no partial transfer behind its error acknowledgement
+        let from_balance = self.subtracted_balance(from, coin)?;
+        if from == to {
+            return Ok(());
+        }
+        let to_balance = self.added_balance(to, coin)?;
+        self.set_balance(from, &coin.denom, from_balance);
+        self.set_balance(to, &coin.denom, to_balance);
+        Ok(())
     }
 
-    fn _mint_coins(
-        &mut self,
-        account: &Signer,
-        amt: &PrefixedCoin,
-    ) -> Result<(), TokenTransferError> {
-        self.signer_amt_by_token
-            .entry(amt.denom.clone())
-            .or_default()
-            .entry(account.clone())
-            .or_insert_with(|| 0u64.into())
-            .checked_add(amt.amount)
-            .ok_or_else(|| TokenTransferError::InvalidToken)?;
+    fn mint_coins(&mut self, account: &Pubkey, coin: &PrefixedCoin) -> anyhow::Result<()> {
+        // The trace is recorded before the balance is written, so that a failed
+        // receive leaves no voucher behind its error acknowledgement
+        let balance = self.added_balance(account, coin)?;
+        self.record_denom_trace(&coin.denom)?;
+        self.set_balance(account, &coin.denom, balance);
         Ok(())
     }
 
-    fn _burn_coins(
-        &mut self,
-        account: &Signer,
-        amt: &PrefixedCoin,
-    ) -> Result<(), TokenTransferError> {
-        self.signer_amt_by_token
-            .get_mut(&amt.denom)
-            .ok_or_else(|| TokenTransferError::InvalidToken)?
-            .get_mut(account)
-            .ok_or_else(|| TokenTransferError::InvalidToken)?
-            .checked_sub(amt.amount)
-            .ok_or_else(|| TokenTransferError::InvalidToken)?;
+    /// Records the trace of a voucher denom, so that its `ibc/{hash}` denom can
+    /// be resolved. Native denoms have no trace to record.
+    fn record_denom_trace(&mut self, denom: &PrefixedDenom) -> anyhow::Result<()> {
+        let trace = DenomTrace {
+            path: denom.trace_path.to_string(),
+            base_denom: denom.base_denom.to_string(),
+        };
+        if trace.path.is_empty() {
+            return Ok(());
+        }
+        let trace_path = DenomTracePath(trace.hash());
+        if !self.state.contains(&trace_path)? {
+            self.state.set(&trace_path, trace);
+        }
+        Ok(())
+    }
+
+    fn burn_coins(&mut self, account: &Pubkey, coin: &PrefixedCoin) -> anyhow::Result<()> {
+        let balance = self.subtracted_balance(account, coin)?;
+        self.set_balance(account, &coin.denom, balance);
         Ok(())
     }
 }
 
-// impl Ics20Reader for Ics20Module
-impl Ics20Module {
-    fn _get_port(&self) -> Result<PortId, TokenTransferError> {
-        Ok(self
-            .port
-            .as_ref()
-            // TODO: Fix this by finding a better error
-            .ok_or_else(|| TokenTransferError::InvalidToken)?
-            .clone())
+// impl TokenTransferContext for Ics20Module
+impl Ics20Module<'_> {
+    /// Takes the tokens of a transfer sent over `port_id_on_a/chan_id_on_a` from
+    /// the sender. Tokens that originate here are escrowed, and vouchers
+    /// returning to their source chain are burned. Since no native balance is
+    /// ever credited, the tokens escrowed are vouchers sent on over another
+    /// channel.
+    pub(super) fn send_transfer(
+        &mut self,
+        port_id_on_a: &PortId,
+        chan_id_on_a: &ChannelId,
+        packet_data: &PacketData,
+    ) -> anyhow::Result<()> {
+        let sender = parse_account(&packet_data.sender)?;
+        let coin = &packet_data.token;
+        if is_sender_chain_source(port_id_on_a.clone(), chan_id_on_a.clone(), &coin.denom) {
+            self.send_coins(&sender, &escrow_address(port_id_on_a, chan_id_on_a), coin)
+        } else {
+            self.burn_coins(&sender, coin)
+        }
+    }
+
+    /// Gives the receiver the tokens of a received transfer. Tokens returning to
+    /// this chain are released from escrow, and others are minted as vouchers
+    /// whose denom is prefixed with the receiving port and channel.
+    fn receive_transfer(&mut self, packet: &Packet) -> anyhow::Result<()> {
+        let PacketData {
+            token, receiver, ..
+        } = parse_packet_data(packet)?;
+        let receiver = parse_account(&receiver)?;
+        let mut coin = token;
+        if is_receiver_chain_source(
+            packet.port_id_on_a.clone(),
+            packet.chan_id_on_a.clone(),
+            &coin.denom,
+        ) {
+            coin.denom.remove_trace_prefix(&TracePrefix::new(
+                packet.port_id_on_a.clone(),
+                packet.chan_id_on_a.clone(),
+            ));
+            let escrow = escrow_address(&packet.port_id_on_b, &packet.chan_id_on_b);
+            self.send_coins(&escrow, &receiver, &coin)
+        } else {
+            coin.denom.add_trace_prefix(TracePrefix::new(
+                packet.port_id_on_b.clone(),
+                packet.chan_id_on_b.clone(),
+            ));
+            self.mint_coins(&receiver, &coin)
+        }
+    }
+
+    /// Returns the tokens of a transfer that was not received to the sender,
+    /// undoing `send_transfer`.
+    fn refund_transfer(&mut self, packet: &Packet) -> anyhow::Result<()> {
+        let PacketData { token, sender, .. } = parse_packet_data(packet)?;
+        let sender = parse_account(&sender)?;
+        if is_sender_chain_source(
+            packet.port_id_on_a.clone(),
+            packet.chan_id_on_a.clone(),
+            &token.denom,
+        ) {
+            let escrow = escrow_address(&packet.port_id_on_a, &packet.chan_id_on_a);
+            self.send_coins(&escrow, &sender, &token)
+        } else {
+            self.mint_coins(&sender, &token)
+        }
+    }
+}
+
+impl Module for Ics20Module<'_> {
+    fn on_chan_open_init_validate(
+        &self,
+        order: Order,
+        _connection_hops: &[ConnectionId],
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        version: &Version,
+    ) -> Result<Version, ChannelError> {
+        check_order(order)?;
+        // Relayers may leave the version for the module to pick
+        if !version.to_string().is_empty() {
+            check_version(version)?;
+        }
+        Ok(Version::new(ICS20_VERSION.to_owned()))
+    }
+
+    fn on_chan_open_init_execute(
+        &mut self,
+        _order: Order,
+        _connection_hops: &[ConnectionId],
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        _version: &Version,
+    ) -> Result<(ModuleExtras, Version), ChannelError> {
+        Ok((
+            ModuleExtras::empty(),
+            Version::new(ICS20_VERSION.to_owned()),
+        ))
+    }
+
+    fn on_chan_open_try_validate(
+        &self,
+        order: Order,
+        _connection_hops: &[ConnectionId],
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        counterparty_version: &Version,
+    ) -> Result<Version, ChannelError> {
+        check_order(order)?;
+        check_version(counterparty_version)?;
+        Ok(Version::new(ICS20_VERSION.to_owned()))
     }
 
-    fn _get_channel_escrow_address(
+    fn on_chan_open_try_execute(
+        &mut self,
+        _order: Order,
+        _connection_hops: &[ConnectionId],
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty: &Counterparty,
+        _counterparty_version: &Version,
+    ) -> Result<(ModuleExtras, Version), ChannelError> {
+        Ok((
+            ModuleExtras::empty(),
+            Version::new(ICS20_VERSION.to_owned()),
+        ))
+    }
+
+    fn on_chan_open_ack_validate(
         &self,
         _port_id: &PortId,
         _channel_id: &ChannelId,
-    ) -> Result<Signer, TokenTransferError> {
-        todo!()
+        counterparty_version: &Version,
+    ) -> Result<(), ChannelError> {
+        check_version(counterparty_version)
     }
 
-    fn _is_send_enabled(&self) -> bool {
-        self.is_send_enabled
+    fn on_chan_open_ack_execute(
+        &mut self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+        _counterparty_version: &Version,
+    ) -> Result<ModuleExtras, ChannelError> {
+        Ok(ModuleExtras::empty())
+    }
+
+    fn on_chan_open_confirm_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        Ok(())
+    }
+
+    fn on_chan_open_confirm_execute(
+        &mut self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<ModuleExtras, ChannelError> {
+        Ok(ModuleExtras::empty())
+    }
+
+    fn on_chan_close_init_validate(
+        &self,
+        port_id: &PortId,
+        channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        // Closing the channel would strand the tokens in its escrow
+        Err(ChannelError::Other {
+            description: format!("ICS-20 channel {port_id}/{channel_id} cannot be closed"),
+        })
+    }
+
+    fn on_chan_close_init_execute(
+        &mut self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<ModuleExtras, ChannelError> {
+        Ok(ModuleExtras::empty())
+    }
+
+    fn on_chan_close_confirm_validate(
+        &self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<(), ChannelError> {
+        Ok(())
+    }
+
+    fn on_chan_close_confirm_execute(
+        &mut self,
+        _port_id: &PortId,
+        _channel_id: &ChannelId,
+    ) -> Result<ModuleExtras, ChannelError> {
+        Ok(ModuleExtras::empty())
+    }
+
+    fn on_recv_packet_execute(
+        &mut self,
+        packet: &Packet,
+        _relayer: &Signer,
+    ) -> (ModuleExtras, Acknowledgement) {
+        let acknowledgement = match self.receive_transfer(packet) {
+            Ok(()) => success_ack(),
+            Err(err) => {
+                bounded_log::msg_trunc(
+                    "ICS-20 packet was not received",
+                    format_args!("{err:#}"),
+                    MAX_TERSE_LOG_LEN,
+                );
+                error_ack(err)
+            }
+        };
+        (ModuleExtras::empty(), acknowledgement)
+    }
+
+    fn on_acknowledgement_packet_validate(
+        &self,
+        packet: &Packet,
+        acknowledgement: &Acknowledgement,
+        _relayer: &Signer,
+    ) -> Result<(), PacketError> {
+        parse_packet_data(packet).map_err(packet_err)?;
+        is_error_ack(acknowledgement).map_err(packet_err)?;
+        Ok(())
+    }
+
+    fn on_acknowledgement_packet_execute(
+        &mut self,
+        packet: &Packet,
+        acknowledgement: &Acknowledgement,
+        _relayer: &Signer,
+    ) -> (ModuleExtras, Result<(), PacketError>) {
+        let result = is_error_ack(acknowledgement)
+            .and_then(|is_error_ack| {
+                if is_error_ack {
+                    self.refund_transfer(packet)
+                } else {
+                    Ok(())
+                }
+            })
+            .map_err(packet_err);
+        (ModuleExtras::empty(), result)
+    }
+
+    fn on_timeout_packet_validate(
+        &self,
+        packet: &Packet,
+        _relayer: &Signer,
+    ) -> Result<(), PacketError> {
+        parse_packet_data(packet).map_err(packet_err)?;
+        Ok(())
+    }
+
+    fn on_timeout_packet_execute(
+        &mut self,
+        packet: &Packet,
+        _relayer: &Signer,
+    ) -> (ModuleExtras, Result<(), PacketError>) {
+        let result = self.refund_transfer(packet).map_err(packet_err);
+        (ModuleExtras::empty(), result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        ibc::core::{
+            ics02_client::height::Height, ics04_channel::timeout::TimeoutHeight,
+            timestamp::Timestamp,
+        },
+    };
+
+    fn channel(channel_id: u64) -> (PortId, ChannelId) {
+        (PortId::transfer(), ChannelId::new(channel_id))
+    }
+
+    fn denom(denom: &str) -> PrefixedDenom {
+        denom.parse().unwrap()
+    }
+
+    fn packet_data(denom: &str, amount: u64, sender: &str, receiver: &str) -> PacketData {
+        serde_json::from_value(serde_json::json!({
+            "denom": denom,
+            "amount": amount.to_string(),
+            "sender": sender,
+            "receiver": receiver,
+        }))
+        .unwrap()
+    }
+
+    /// A packet sent from `on_a` to `on_b` carrying `packet_data`.
+    fn packet(
+        (port_id_on_a, chan_id_on_a): (PortId, ChannelId),
+        (port_id_on_b, chan_id_on_b): (PortId, ChannelId),
+        packet_data: &PacketData,
+    ) -> Packet {
+        Packet {
+            seq_on_a: 1.into(),
+            port_id_on_a,
+            chan_id_on_a,
+            port_id_on_b,
+            chan_id_on_b,
+            data: serde_json::to_vec(packet_data).unwrap(),
+            timeout_height_on_b: TimeoutHeight::At(Height::new(0, 100).unwrap()),
+            timeout_timestamp_on_b: Timestamp::none(),
+        }
+    }
+
+    fn relayer() -> Signer {
+        Signer::from(Pubkey::new_unique().to_string())
+    }
+
+    fn assert_balance(module: &Ics20Module, account: &Pubkey, denom_str: &str, amount: u64) {
+        assert_eq!(
+            module.balance(account, &denom(denom_str)).unwrap(),
+            amount.into(),
+            "balance of {denom_str} in {account}",
+        );
+    }
+
+    #[test]
+    fn sent_tokens_are_escrowed_and_refunded_on_timeout() {
+        let store = IbcStore::default();
+        let mut module = Ics20Module::new(&store, 0);
+        let sender = Pubkey::new_unique();
+        let escrow = escrow_address(&channel(0).0, &channel(0).1);
+        assert_ne!(escrow, escrow_address(&channel(1).0, &channel(1).1));
+        module
+            .mint_coins(
+                &sender,
+                &PrefixedCoin {
+                    denom: denom("lamports"),
+                    amount: 100u64.into(),
+                },
+            )
+            .unwrap();
+
+        let data = packet_data("lamports", 40, &sender.to_string(), "cosmos1receiver");
+        module
+            .send_transfer(&channel(0).0, &channel(0).1, &data)
+            .unwrap();
+        assert_balance(&module, &sender, "lamports", 60);
+        assert_balance(&module, &escrow, "lamports", 40);
+
+        // Balances cannot go negative
+        let too_much = packet_data("lamports", 61, &sender.to_string(), "cosmos1receiver");
+        module
+            .send_transfer(&channel(0).0, &channel(0).1, &too_much)
+            .unwrap_err();
+        assert_balance(&module, &sender, "lamports", 60);
+
+        let packet = packet(channel(0), channel(1), &data);
+        module
+            .on_timeout_packet_validate(&packet, &relayer())
+            .unwrap();
+        let (_, result) = module.on_timeout_packet_execute(&packet, &relayer());
+        result.unwrap();
+        assert_balance(&module, &sender, "lamports", 100);
+        assert_balance(&module, &escrow, "lamports", 0);
+    }
+
+    #[test]
+    fn received_tokens_are_minted_as_prefixed_vouchers() {
+        let store = IbcStore::default();
+        let mut module = Ics20Module::new(&store, 0);
+        let receiver = Pubkey::new_unique();
+
+        let data = packet_data("uatom", 50, "cosmos1sender", &receiver.to_string());
+        let (_, ack) =
+            module.on_recv_packet_execute(&packet(channel(1), channel(0), &data), &relayer());
+        assert_eq!(ack.as_ref(), success_ack().as_ref());
+        assert_balance(&module, &receiver, "transfer/channel-0/uatom", 50);
+        assert_balance(&module, &receiver, "uatom", 0);
+        let trace = module
+            .state
+            .get(&DenomTracePath(
+                "27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2".to_owned(),
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(trace.full_path(), "transfer/channel-0/uatom");
+
+        // Vouchers sent back to their source chain are burned, and re-minted if
+        // the transfer fails
+        let data = packet_data(
+            "transfer/channel-0/uatom",
+            20,
+            &receiver.to_string(),
+            "cosmos1sender",
+        );
+        module
+            .send_transfer(&channel(0).0, &channel(0).1, &data)
+            .unwrap();
+        assert_balance(&module, &receiver, "transfer/channel-0/uatom", 30);
+        let escrow = escrow_address(&channel(0).0, &channel(0).1);
+        assert_balance(&module, &escrow, "transfer/channel-0/uatom", 0);
+
+        let packet = packet(channel(0), channel(1), &data);
+        let ack = error_ack("bad receiver");
+        module
+            .on_acknowledgement_packet_validate(&packet, &ack, &relayer())
+            .unwrap();
+        let (_, result) = module.on_acknowledgement_packet_execute(&packet, &ack, &relayer());
+        result.unwrap();
+        assert_balance(&module, &receiver, "transfer/channel-0/uatom", 50);
+    }
+
+    #[test]
+    fn failed_mints_record_no_denom_trace() {
+        let store = IbcStore::default();
+        let mut module = Ics20Module::new(&store, 0);
+        let receiver = Pubkey::new_unique();
+        let voucher = denom("transfer/channel-0/uosmo");
+        // The greatest amount, 2^256 - 1, which no receive can add to
+        let max_amount = Amount::from_str(
+            "115792089237316195423570985008687907853269984665640564039457584007913129639935",
+        )
+        .unwrap();
+        module.set_balance(&receiver, &voucher, max_amount);
+
+        let data = packet_data("uosmo", 1, "cosmos1sender", &receiver.to_string());
+        let (_, ack) =
+            module.on_recv_packet_execute(&packet(channel(1), channel(0), &data), &relayer());
+        assert!(is_error_ack(&ack).unwrap());
+        let trace_path = DenomTracePath(
+            DenomTrace {
+                path: voucher.trace_path.to_string(),
+                base_denom: voucher.base_denom.to_string(),
+            }
+            .hash(),
+        );
+        assert!(!module.state.contains(&trace_path).unwrap());
+    }
+
+    #[test]
+    fn returning_tokens_are_released_from_escrow() {
+        let store = IbcStore::default();
+        let mut module = Ics20Module::new(&store, 0);
+        let sender = Pubkey::new_unique();
+        let receiver = Pubkey::new_unique();
+        module
+            .mint_coins(
+                &sender,
+                &PrefixedCoin {
+                    denom: denom("lamports"),
+                    amount: 100u64.into(),
+                },
+            )
+            .unwrap();
+        let data = packet_data("lamports", 40, &sender.to_string(), "cosmos1receiver");
+        module
+            .send_transfer(&channel(0).0, &channel(0).1, &data)
+            .unwrap();
+
+        // A successful ack leaves the tokens in escrow
+        let packet_out = packet(channel(0), channel(1), &data);
+        let (_, result) =
+            module.on_acknowledgement_packet_execute(&packet_out, &success_ack(), &relayer());
+        result.unwrap();
+        let escrow = escrow_address(&channel(0).0, &channel(0).1);
+        assert_balance(&module, &escrow, "lamports", 40);
+
+        // More than the escrow holds cannot come back, and is rejected with an
+        // error ack that leaves the balances unchanged
+        let data = packet_data(
+            "transfer/channel-1/lamports",
+            41,
+            "cosmos1receiver",
+            &receiver.to_string(),
+        );
+        let (_, ack) =
+            module.on_recv_packet_execute(&packet(channel(1), channel(0), &data), &relayer());
+        assert!(is_error_ack(&ack).unwrap());
+        assert_balance(&module, &escrow, "lamports", 40);
+        assert_balance(&module, &receiver, "lamports", 0);
+
+        let data = packet_data(
+            "transfer/channel-1/lamports",
+            30,
+            "cosmos1receiver",
+            &receiver.to_string(),
+        );
+        let (_, ack) =
+            module.on_recv_packet_execute(&packet(channel(1), channel(0), &data), &relayer());
+        assert!(!is_error_ack(&ack).unwrap());
+        assert_balance(&module, &escrow, "lamports", 10);
+        assert_balance(&module, &receiver, "lamports", 30);
+
+        // Receivers must be Solana addresses
+        let data = packet_data("transfer/channel-1/lamports", 10, "cosmos1receiver", "bob");
+        let (_, ack) =
+            module.on_recv_packet_execute(&packet(channel(1), channel(0), &data), &relayer());
+        assert!(is_error_ack(&ack).unwrap());
+        assert_balance(&module, &escrow, "lamports", 10);
     }
 
-    fn _is_receive_enabled(&self) -> bool {
-        self.is_receive_enabled
+    #[test]
+    fn channels_must_be_unordered_ics20_channels() {
+        let store = IbcStore::default();
+        let module = Ics20Module::new(&store, 0);
+        let (port_id, channel_id) = channel(0);
+        let counterparty = Counterparty::new(port_id.clone(), None);
+        let open_init = |order, version: &str| {
+            module.on_chan_open_init_validate(
+                order,
+                &[ConnectionId::default()],
+                &port_id,
+                &channel_id,
+                &counterparty,
+                &Version::new(version.to_owned()),
+            )
+        };
+        assert_eq!(
+            open_init(Order::Unordered, "").unwrap().to_string(),
+            ICS20_VERSION
+        );
+        open_init(Order::Unordered, ICS20_VERSION).unwrap();
+        open_init(Order::Ordered, ICS20_VERSION).unwrap_err();
+        open_init(Order::Unordered, "ics20-2").unwrap_err();
+        module
+            .on_chan_close_init_validate(&port_id, &channel_id)
+            .unwrap_err();
     }
 }
@@ -1,10 +1,21 @@
+pub mod account_spec;
+mod bounded_log;
+pub mod build_info;
+pub mod capabilities;
+mod commit_guard;
+#[cfg(feature = "dry-run")]
+pub mod dry_run;
+mod eclipse_module_id;
+pub mod errors;
 pub mod ibc_contract_instruction;
+pub mod ibc_events;
 mod ibc_handler;
 pub mod ibc_instruction;
 mod ibc_program;
 mod ics20_module;
 pub mod module_instruction;
+mod store_write_batch;
 
 solana_sdk::declare_id!("Ec11pse1bc111111111111111111111111111111111");
 
-pub use ibc_program::{process_instruction, STORAGE_KEY};
+pub use ibc_program::{process_instruction, PRUNED_NODES_PER_INSTRUCTION, STORAGE_KEY};
@@ -1,18 +1,105 @@
 use {
     ibc::{
         core::{
+            events::{ModuleEvent, ModuleEventAttribute},
             ics04_channel::{
                 channel::{Counterparty, Order},
                 packet::{Acknowledgement, Packet},
                 Version,
             },
             ics24_host::identifier::{ChannelId, ConnectionId, PortId},
+            router::ModuleExtras,
         },
         Signer,
     },
     serde::{Deserialize, Serialize},
+    std::fmt::Display,
 };
 
+/// Callback encoding of modules built before versions were negotiated: the
+/// `IbcModuleInstruction` encoded with `bincode` as is. Modules that do not
+/// answer [`IbcModuleInstruction::SupportedCallbackVersions`] get this one.
+pub const LEGACY_CALLBACK_VERSION: u32 = 1;
+
+/// Callback encodings the program can send, oldest first. Versions after the
+/// legacy one are sent in a [`CallbackEnvelope`].
+pub const SUPPORTED_CALLBACK_VERSIONS: [u32; 2] = [LEGACY_CALLBACK_VERSION, 2];
+
+/// Leading field of a [`CallbackEnvelope`]. Read as the variant index of a legacy
+/// `IbcModuleInstruction` it names no variant, so modules can tell enveloped
+/// callbacks from legacy ones.
+pub const CALLBACK_ENVELOPE_TAG: u32 = u32::MAX;
+
+/// Base64 of the single byte `0x01` that ICS-20 puts in successful
+/// acknowledgements.
+const ICS20_SUCCESS_RESULT: &str = "AQ==";
+
+/// Acknowledgement for a packet the module processed successfully, in the ICS-20
+/// JSON format `{"result":"AQ=="}`.
+pub fn success_ack() -> Acknowledgement {
+    json_ack(serde_json::json!({ "result": ICS20_SUCCESS_RESULT }))
+}
+
+/// Acknowledgement for a packet the module failed to process, in the ICS-20
+/// JSON format `{"error":"<description>"}`.
+pub fn error_ack(err: impl Display) -> Acknowledgement {
+    json_ack(serde_json::json!({ "error": err.to_string() }))
+}
+
+fn json_ack(value: serde_json::Value) -> Acknowledgement {
+    Acknowledgement::try_from(value.to_string().into_bytes())
+        .expect("JSON acknowledgements are never empty")
+}
+
+/// An event emitted by a module, as a `ModuleEvent` of the given kind with its
+/// attributes as key/value pairs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SerializableModuleEvent {
+    pub kind: String,
+    pub attributes: Vec<(String, String)>,
+}
+
+/// The `ModuleExtras` that execute callbacks return, in a form modules can encode
+/// with `bincode` into their return data. It has the same encoding as
+/// `ModuleExtras`, which older modules returned directly.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SerializableModuleExtras {
+    pub events: Vec<SerializableModuleEvent>,
+    pub log: Vec<String>,
+}
+
+impl From<ModuleExtras> for SerializableModuleExtras {
+    fn from(ModuleExtras { events, log }: ModuleExtras) -> Self {
+        let events = events
+            .into_iter()
+            .map(|ModuleEvent { kind, attributes }| SerializableModuleEvent {
+                kind,
+                attributes: attributes
+                    .into_iter()
+                    .map(|ModuleEventAttribute { key, value }| (key, value))
+                    .collect(),
+            })
+            .collect();
+        Self { events, log }
+    }
+}
+
+impl From<SerializableModuleExtras> for ModuleExtras {
+    fn from(SerializableModuleExtras { events, log }: SerializableModuleExtras) -> Self {
+        let events = events
+            .into_iter()
+            .map(|SerializableModuleEvent { kind, attributes }| ModuleEvent {
+                kind,
+                attributes: attributes
+                    .into_iter()
+                    .map(|(key, value)| ModuleEventAttribute { key, value })
+                    .collect(),
+            })
+            .collect();
+        Self { events, log }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct OnChanOpenInitValidate {
     pub order: Order,
@@ -31,7 +118,7 @@ pub struct OnChanOpenInitExecute {
     pub channel_id: ChannelId,
     pub counterparty: Counterparty,
     pub version: Version,
-} // -> Result<(ModuleExtras, Version), ChannelError>
+} // -> Result<(SerializableModuleExtras, Version), ChannelError>
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct OnChanOpenTryValidate {
@@ -51,7 +138,7 @@ pub struct OnChanOpenTryExecute {
     pub channel_id: ChannelId,
     pub counterparty: Counterparty,
     pub counterparty_version: Version,
-} // -> Result<(ModuleExtras, Version), ChannelError>
+} // -> Result<(SerializableModuleExtras, Version), ChannelError>
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct OnChanOpenAckValidate {
@@ -65,7 +152,7 @@ pub struct OnChanOpenAckExecute {
     pub port_id: PortId,
     pub channel_id: ChannelId,
     pub counterparty_version: Version,
-} // -> Result<ModuleExtras, ChannelError>
+} // -> Result<SerializableModuleExtras, ChannelError>
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct OnChanOpenConfirmValidate {
@@ -77,7 +164,7 @@ pub struct OnChanOpenConfirmValidate {
 pub struct OnChanOpenConfirmExecute {
     pub port_id: PortId,
     pub channel_id: ChannelId,
-} // -> Result<ModuleExtras, ChannelError>
+} // -> Result<SerializableModuleExtras, ChannelError>
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct OnChanCloseInitValidate {
@@ -89,7 +176,7 @@ pub struct OnChanCloseInitValidate {
 pub struct OnChanCloseInitExecute {
     pub port_id: PortId,
     pub channel_id: ChannelId,
-} // -> Result<ModuleExtras, ChannelError>
+} // -> Result<SerializableModuleExtras, ChannelError>
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct OnChanCloseConfirmValidate {
@@ -101,13 +188,13 @@ pub struct OnChanCloseConfirmValidate {
 pub struct OnChanCloseConfirmExecute {
     pub port_id: PortId,
     pub channel_id: ChannelId,
-} // -> Result<ModuleExtras, ChannelError>
+} // -> Result<SerializableModuleExtras, ChannelError>
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct OnRecvPacketExecute {
     pub packet: Packet,
     pub relayer: Signer,
-} // -> (ModuleExtras, Acknowledgement)
+} // -> (SerializableModuleExtras, Acknowledgement)
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct OnAcknowledgementPacketValidate {
@@ -121,7 +208,7 @@ pub struct OnAcknowledgementPacketExecute {
     pub packet: Packet,
     pub acknowledgement: Acknowledgement,
     pub relayer: Signer,
-} // -> (ModuleExtras, Result<(), PacketError>)
+} // -> Result<(), PacketError>, with SerializableModuleExtras as return data
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct OnTimeoutPacketValidate {
@@ -133,7 +220,7 @@ pub struct OnTimeoutPacketValidate {
 pub struct OnTimeoutPacketExecute {
     pub packet: Packet,
     pub relayer: Signer,
-} // -> (ModuleExtras, Result<(), PacketError>)
+} // -> Result<(), PacketError>, with SerializableModuleExtras as return data
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum IbcModuleInstruction {
@@ -154,4 +241,130 @@ pub enum IbcModuleInstruction {
     OnAcknowledgementPacketExecute(OnAcknowledgementPacketExecute),
     OnTimeoutPacketValidate(OnTimeoutPacketValidate),
     OnTimeoutPacketExecute(OnTimeoutPacketExecute),
+    /// Asks for the callback versions the module can decode. Always sent with the
+    /// legacy encoding, once per module before the program uses a newer one.
+    SupportedCallbackVersions, // -> Vec<u32> as return data
+}
+
+/// A callback in any version after the legacy one. It is encoded with `bincode`,
+/// and `tag` is always [`CALLBACK_ENVELOPE_TAG`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CallbackEnvelope {
+    pub tag: u32,
+    pub version: u32,
+    pub instruction: IbcModuleInstruction,
+}
+
+/// Encodes `instruction` the way modules that negotiated `version` decode it.
+pub fn encode_callback(
+    version: u32,
+    instruction: &IbcModuleInstruction,
+) -> bincode::Result<Vec<u8>> {
+    if version == LEGACY_CALLBACK_VERSION {
+        return bincode::serialize(instruction);
+    }
+    bincode::serialize(&CallbackEnvelope {
+        tag: CALLBACK_ENVELOPE_TAG,
+        version,
+        instruction: instruction.clone(),
+    })
+}
+
+/// Decodes a callback in the legacy encoding or in an envelope, along with its
+/// version, for modules that support more than one.
+pub fn decode_callback(data: &[u8]) -> bincode::Result<(u32, IbcModuleInstruction)> {
+    if data.starts_with(&CALLBACK_ENVELOPE_TAG.to_le_bytes()) {
+        let CallbackEnvelope {
+            version,
+            instruction,
+            ..
+        } = bincode::deserialize(data)?;
+        return Ok((version, instruction));
+    }
+    let instruction = bincode::deserialize(data)?;
+    Ok((LEGACY_CALLBACK_VERSION, instruction))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        ibc::core::{ics04_channel::timeout::TimeoutHeight, timestamp::Timestamp},
+    };
+
+    #[test]
+    fn acks_follow_the_ics20_json_format() {
+        assert_eq!(success_ack().as_ref(), br#"{"result":"AQ=="}"#);
+        assert_eq!(
+            error_ack("insufficient \"funds\"").as_ref(),
+            br#"{"error":"insufficient \"funds\""}"#,
+        );
+        assert_eq!(error_ack("").as_ref(), br#"{"error":""}"#);
+    }
+
+    #[test]
+    fn module_extras_round_trip() {
+        let extras = SerializableModuleExtras {
+            events: vec![
+                SerializableModuleEvent {
+                    kind: "fungible_token_packet".to_owned(),
+                    attributes: vec![
+                        ("module".to_owned(), "transfer".to_owned()),
+                        ("denom".to_owned(), "transfer/channel-0/uatom".to_owned()),
+                        ("success".to_owned(), "true".to_owned()),
+                    ],
+                },
+                SerializableModuleEvent {
+                    kind: "timeout".to_owned(),
+                    attributes: vec![],
+                },
+            ],
+            log: vec!["refunded 5 uatom".to_owned(), String::new()],
+        };
+
+        let encoded = bincode::serialize(&extras).unwrap();
+        let decoded = bincode::deserialize::<SerializableModuleExtras>(&encoded).unwrap();
+        assert_eq!(decoded, extras);
+
+        let module_extras = ModuleExtras::from(decoded);
+        assert_eq!(module_extras.events.len(), 2);
+        assert_eq!(module_extras.events[0].attributes[1].key, "denom");
+        assert_eq!(module_extras.log, extras.log);
+        assert_eq!(SerializableModuleExtras::from(module_extras), extras);
+    }
+
+    #[test]
+    fn callbacks_decode_in_every_version() {
+        let instruction = IbcModuleInstruction::OnTimeoutPacketValidate(OnTimeoutPacketValidate {
+            packet: Packet {
+                seq_on_a: 7.into(),
+                port_id_on_a: PortId::transfer(),
+                chan_id_on_a: ChannelId::new(0),
+                port_id_on_b: PortId::transfer(),
+                chan_id_on_b: ChannelId::new(1),
+                data: b"data".to_vec(),
+                timeout_height_on_b: TimeoutHeight::Never,
+                timeout_timestamp_on_b: Timestamp::none(),
+            },
+            relayer: "relayer".to_owned().into(),
+        });
+
+        // The legacy encoding is what modules built before versioning decode
+        let legacy = encode_callback(LEGACY_CALLBACK_VERSION, &instruction).unwrap();
+        assert_eq!(legacy, bincode::serialize(&instruction).unwrap());
+        for version in SUPPORTED_CALLBACK_VERSIONS {
+            let data = encode_callback(version, &instruction).unwrap();
+            let (decoded_version, decoded) = decode_callback(&data).unwrap();
+            assert_eq!(decoded_version, version);
+            assert!(matches!(
+                decoded,
+                IbcModuleInstruction::OnTimeoutPacketValidate(OnTimeoutPacketValidate { packet, .. })
+                    if packet.seq_on_a == 7.into()
+            ));
+        }
+
+        // Legacy modules fail to decode an envelope rather than misreading it
+        let enveloped = encode_callback(2, &instruction).unwrap();
+        bincode::deserialize::<IbcModuleInstruction>(&enveloped).unwrap_err();
+    }
 }
@@ -0,0 +1,382 @@
+use {
+    crate::ibc_instruction::msgs::{MsgRepairIndices, MsgStoreWriteBatch, StoreWrite},
+    eclipse_ibc_extra_types::StoreWriteBatchCursor,
+    eclipse_ibc_state::{
+        index_rebuild_writes, internal_path::StoreWriteBatchCursorPath, IbcState, IndexKind,
+    },
+    solana_sdk::pubkey::Pubkey,
+    thiserror::Error,
+};
+
+#[derive(Debug, Error)]
+pub(crate) enum StoreWriteBatchError {
+    #[error("failed to read the store write batch cursor: {0:#}")]
+    ReadCursor(anyhow::Error),
+    #[error("expected store write batch chunk {expected}, got chunk {actual}")]
+    UnexpectedChunkIndex { expected: u64, actual: u64 },
+    #[error("store write batch chunk {chunk_index} does not match the hash committed to by the previous chunk")]
+    ChunkHashMismatch { chunk_index: u64 },
+    #[error("no store write batch is in progress")]
+    NoBatchInProgress,
+    #[error("store write batch cannot write to its own cursor path {path}")]
+    WriteToCursor { path: String },
+    #[error("failed to read the admin: {0:#}")]
+    ReadAdmin(anyhow::Error),
+    #[error("store write batches can only be sent by the admin, not {signer}")]
+    NotAdmin { signer: Pubkey },
+    #[error("indices cannot be repaired while a store write batch is in progress")]
+    RepairDuringBatch,
+    #[error("index repairs must allow at least one write")]
+    NoRepairWrites,
+    #[error("failed to rebuild the {kind} index: {err:#}")]
+    RebuildIndex { kind: IndexKind, err: anyhow::Error },
+}
+
+/// How far a `MsgRepairIndices` got with its index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct IndexRepair {
+    pub(crate) repaired: usize,
+    /// Entries still to be repaired by later messages
+    pub(crate) remaining: usize,
+}
+
+/// Applies a single chunk of a store write batch. Chunks are accepted strictly in
+/// order: the first chunk of a batch must have index 0 and can only be applied when
+/// no other batch is in progress, and every later chunk must hash to the value
+/// committed to by its predecessor. A chunk is either applied in full or not at all,
+/// so a failed chunk can simply be resubmitted.
+pub(crate) fn apply_chunk(
+    state: &mut IbcState,
+    chunk: MsgStoreWriteBatch,
+) -> Result<(), StoreWriteBatchError> {
+    let expected_index = match state
+        .get(&StoreWriteBatchCursorPath)
+        .map_err(StoreWriteBatchError::ReadCursor)?
+    {
+        None => 0,
+        Some(StoreWriteBatchCursor {
+            next_chunk_index,
+            next_chunk_hash,
+        }) => {
+            if chunk.chunk_index == next_chunk_index && chunk.chunk_hash() != next_chunk_hash {
+                return Err(StoreWriteBatchError::ChunkHashMismatch {
+                    chunk_index: chunk.chunk_index,
+                });
+            }
+            next_chunk_index
+        }
+    };
+    if chunk.chunk_index != expected_index {
+        return Err(StoreWriteBatchError::UnexpectedChunkIndex {
+            expected: expected_index,
+            actual: chunk.chunk_index,
+        });
+    }
+
+    let MsgStoreWriteBatch {
+        chunk_index,
+        writes,
+        next_chunk_hash,
+    } = chunk;
+    apply_writes(state, writes)?;
+    match next_chunk_hash {
+        Some(next_chunk_hash) => state.set(
+            &StoreWriteBatchCursorPath,
+            StoreWriteBatchCursor {
+                next_chunk_index: chunk_index + 1,
+                next_chunk_hash,
+            },
+        ),
+        None => state.remove(&StoreWriteBatchCursorPath),
+    }
+
+    Ok(())
+}
+
+/// Drops the cursor of the store write batch in progress and returns it, so that
+/// other messages are accepted again and a new batch starts from chunk 0. Chunks
+/// already applied stay applied.
+pub(crate) fn abort_batch(
+    state: &mut IbcState,
+) -> Result<StoreWriteBatchCursor, StoreWriteBatchError> {
+    let cursor = state
+        .get(&StoreWriteBatchCursorPath)
+        .map_err(StoreWriteBatchError::ReadCursor)?
+        .ok_or(StoreWriteBatchError::NoBatchInProgress)?;
+    state.remove(&StoreWriteBatchCursorPath);
+    Ok(cursor)
+}
+
+/// Rewrites up to `max_writes` entries of the `kind` index that disagree with
+/// the primary data it is derived from. Entries are repaired in path order and
+/// the rest are left for later messages, which pick up where this one stopped
+/// since repaired entries no longer disagree.
+pub(crate) fn repair_index(
+    state: &mut IbcState,
+    MsgRepairIndices { kind, max_writes }: MsgRepairIndices,
+) -> Result<IndexRepair, StoreWriteBatchError> {
+    // The index would be rebuilt from half-written primary data
+    if state
+        .get(&StoreWriteBatchCursorPath)
+        .map_err(StoreWriteBatchError::ReadCursor)?
+        .is_some()
+    {
+        return Err(StoreWriteBatchError::RepairDuringBatch);
+    }
+    if max_writes == 0 {
+        return Err(StoreWriteBatchError::NoRepairWrites);
+    }
+
+    let mut writes = index_rebuild_writes(state, kind)
+        .map_err(|err| StoreWriteBatchError::RebuildIndex { kind, err })?;
+    let remaining = writes
+        .len()
+        .saturating_sub(usize::try_from(max_writes).unwrap_or(usize::MAX));
+    writes.truncate(writes.len() - remaining);
+    let repaired = writes.len();
+    apply_writes(
+        state,
+        writes
+            .into_iter()
+            .map(|(path, value)| StoreWrite { path, value })
+            .collect(),
+    )?;
+    Ok(IndexRepair {
+        repaired,
+        remaining,
+    })
+}
+
+/// Writes raw entries, none of which may be the batch cursor that orders the
+/// chunks of a batch.
+fn apply_writes(state: &mut IbcState, writes: Vec<StoreWrite>) -> Result<(), StoreWriteBatchError> {
+    let cursor_path = StoreWriteBatchCursorPath.to_string();
+    if let Some(write) = writes.iter().find(|write| write.path == cursor_path) {
+        return Err(StoreWriteBatchError::WriteToCursor {
+            path: write.path.clone(),
+        });
+    }
+    for write in writes {
+        state.write_raw_entry(&write.path, write.value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        eclipse_ibc_extra_types::ClientConnections,
+        eclipse_ibc_fixtures::FixtureStore,
+        eclipse_ibc_known_proto::KnownProto,
+        eclipse_ibc_state::{check_client_connections, IbcStore},
+        ibc::core::{
+            ics04_channel::packet::Sequence,
+            ics24_host::{
+                identifier::{ChannelId, ClientId, ConnectionId, PortId},
+                path::{ClientConnectionPath, SeqSendPath},
+            },
+        },
+    };
+
+    fn seq_send_path(channel: u64) -> SeqSendPath {
+        SeqSendPath(PortId::transfer(), ChannelId::new(channel))
+    }
+
+    /// Builds a batch that sets the send sequence of `channel-{i}` to `i` for every
+    /// `i` in `0..num_chunks`, one write per chunk.
+    fn plan_batch(num_chunks: u64) -> Vec<MsgStoreWriteBatch> {
+        let mut chunks = Vec::new();
+        let mut next_chunk_hash = None;
+        for chunk_index in (0..num_chunks).rev() {
+            let chunk = MsgStoreWriteBatch {
+                chunk_index,
+                writes: vec![StoreWrite {
+                    path: seq_send_path(chunk_index).to_string(),
+                    value: Some(Sequence::from(chunk_index).encode()),
+                }],
+                next_chunk_hash,
+            };
+            next_chunk_hash = Some(chunk.chunk_hash());
+            chunks.push(chunk);
+        }
+        chunks.reverse();
+        chunks
+    }
+
+    #[test]
+    fn out_of_order_chunks_are_rejected() {
+        let store = IbcStore::default();
+        let mut state = IbcState::new(&store, 0);
+        let chunks = plan_batch(3);
+
+        assert!(matches!(
+            apply_chunk(&mut state, chunks[1].clone()),
+            Err(StoreWriteBatchError::UnexpectedChunkIndex {
+                expected: 0,
+                actual: 1
+            })
+        ));
+
+        apply_chunk(&mut state, chunks[0].clone()).unwrap();
+        assert!(matches!(
+            apply_chunk(&mut state, chunks[0].clone()),
+            Err(StoreWriteBatchError::UnexpectedChunkIndex {
+                expected: 1,
+                actual: 0
+            })
+        ));
+        assert!(matches!(
+            apply_chunk(&mut state, chunks[2].clone()),
+            Err(StoreWriteBatchError::UnexpectedChunkIndex {
+                expected: 1,
+                actual: 2
+            })
+        ));
+
+        // A chunk with the right index but different contents is rejected too
+        let mut forged = chunks[1].clone();
+        forged.writes.clear();
+        assert!(matches!(
+            apply_chunk(&mut state, forged),
+            Err(StoreWriteBatchError::ChunkHashMismatch { chunk_index: 1 })
+        ));
+    }
+
+    #[test]
+    fn batch_resumes_after_failed_chunk() {
+        let store = IbcStore::default();
+        let mut state = IbcState::new(&store, 0);
+        let chunks = plan_batch(3);
+
+        apply_chunk(&mut state, chunks[0].clone()).unwrap();
+        state.commit().unwrap();
+
+        // A failed chunk leaves the cursor where it was
+        let mut forged = chunks[1].clone();
+        forged.writes[0].value = None;
+        apply_chunk(&mut state, forged).unwrap_err();
+        assert_eq!(
+            state.get(&StoreWriteBatchCursorPath).unwrap(),
+            Some(StoreWriteBatchCursor {
+                next_chunk_index: 1,
+                next_chunk_hash: chunks[1].chunk_hash(),
+            }),
+        );
+
+        for chunk in &chunks[1..] {
+            apply_chunk(&mut state, chunk.clone()).unwrap();
+        }
+        state.commit().unwrap();
+
+        assert_eq!(state.get(&StoreWriteBatchCursorPath).unwrap(), None);
+        for i in 0..3 {
+            assert_eq!(
+                state.get(&seq_send_path(i)).unwrap(),
+                Some(Sequence::from(i))
+            );
+        }
+
+        // With the batch finished, a new one can start from chunk 0
+        apply_chunk(&mut state, plan_batch(1).remove(0)).unwrap();
+    }
+
+    #[test]
+    fn aborted_batch_keeps_its_applied_chunks() {
+        let store = IbcStore::default();
+        let mut state = IbcState::new(&store, 0);
+        let chunks = plan_batch(3);
+
+        assert!(matches!(
+            abort_batch(&mut state),
+            Err(StoreWriteBatchError::NoBatchInProgress)
+        ));
+        apply_chunk(&mut state, chunks[0].clone()).unwrap();
+        assert_eq!(
+            abort_batch(&mut state).unwrap(),
+            StoreWriteBatchCursor {
+                next_chunk_index: 1,
+                next_chunk_hash: chunks[1].chunk_hash(),
+            }
+        );
+        state.commit().unwrap();
+
+        assert_eq!(state.get(&StoreWriteBatchCursorPath).unwrap(), None);
+        assert_eq!(
+            state.get(&seq_send_path(0)).unwrap(),
+            Some(Sequence::from(0))
+        );
+        // The rest of the aborted batch is rejected, and a new one starts over
+        assert!(matches!(
+            apply_chunk(&mut state, chunks[1].clone()),
+            Err(StoreWriteBatchError::UnexpectedChunkIndex {
+                expected: 0,
+                actual: 1
+            })
+        ));
+        apply_chunk(&mut state, chunks[0].clone()).unwrap();
+    }
+
+    #[test]
+    fn indices_are_repaired_in_bounded_batches() {
+        let account_data = FixtureStore::with_clients(3).with_channels(1).build();
+        let version = account_data.store.read().latest_version().unwrap();
+        let client_id = |index| format!("xx-eclipse-{index}").parse::<ClientId>().unwrap();
+        let repair = MsgRepairIndices {
+            kind: IndexKind::ClientConnections,
+            max_writes: 2,
+        };
+
+        // Drop `connection-0` from its client's index and list phantom
+        // connections for the other clients
+        let mut state = IbcState::new(&account_data.store, version + 1);
+        state.remove(&ClientConnectionPath::new(&client_id(0)));
+        for index in 1..3 {
+            state.set(
+                &ClientConnectionPath::new(&client_id(index)),
+                ClientConnections {
+                    connections: [ConnectionId::new(index)].into_iter().collect(),
+                },
+            );
+        }
+        state.commit().unwrap();
+        assert!(!check_client_connections(&state).unwrap().is_consistent());
+
+        let mut state = IbcState::new(&account_data.store, version + 2);
+        assert_eq!(
+            repair_index(&mut state, repair.clone()).unwrap(),
+            IndexRepair {
+                repaired: 2,
+                remaining: 1
+            }
+        );
+        state.commit().unwrap();
+        assert!(!check_client_connections(&state).unwrap().is_consistent());
+
+        let mut state = IbcState::new(&account_data.store, version + 3);
+        assert_eq!(
+            repair_index(&mut state, repair.clone()).unwrap(),
+            IndexRepair {
+                repaired: 1,
+                remaining: 0
+            }
+        );
+        state.commit().unwrap();
+        assert!(check_client_connections(&state).unwrap().is_consistent());
+
+        // Nothing is left to repair, and nothing is repaired during a batch
+        let mut state = IbcState::new(&account_data.store, version + 4);
+        assert_eq!(
+            repair_index(&mut state, repair.clone()).unwrap(),
+            IndexRepair {
+                repaired: 0,
+                remaining: 0
+            }
+        );
+        apply_chunk(&mut state, plan_batch(2).remove(0)).unwrap();
+        assert!(matches!(
+            repair_index(&mut state, repair),
+            Err(StoreWriteBatchError::RepairDuringBatch)
+        ));
+    }
+}
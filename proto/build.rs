@@ -3,6 +3,7 @@ use std::io;
 fn main() -> io::Result<()> {
     let mut prost_config = prost_build::Config::new();
     prost_config.extern_path(".ibc", "::ibc_proto::ibc");
+    prost_config.extern_path(".google.protobuf.Any", "::ibc_proto::google::protobuf::Any");
     prost_config.extern_path(
         ".google.protobuf.Timestamp",
         "::tendermint_proto::google::protobuf::Timestamp",
@@ -22,6 +23,7 @@ fn main() -> io::Result<()> {
                 "proto/eclipse/ibc/chain/v1/chain.proto",
                 "proto/eclipse/ibc/client/v1/client.proto",
                 "proto/eclipse/ibc/port/v1/port.proto",
+                "proto/eclipse/ibc/transfer/v1/transfer.proto",
             ],
             &["ibc-go-proto/", "proto/"],
         )?;
@@ -23,5 +23,11 @@ pub mod eclipse {
                 include!(concat!(env!("OUT_DIR"), "/eclipse.ibc.port.v1.rs"));
             }
         }
+
+        pub mod transfer {
+            pub mod v1 {
+                include!(concat!(env!("OUT_DIR"), "/eclipse.ibc.transfer.v1.rs"));
+            }
+        }
     }
 }
@@ -0,0 +1,130 @@
+//! Build script of the CLI and program crates. Generates the `build_info.rs`
+//! their `build_info` modules include: the commit and date of the build and the
+//! versions of the ibc crates it is built against, so that a message or a
+//! deployed program can be traced back to the build that produced it.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Git hash to record instead of asking git, for release builds made outside a
+/// checkout
+const BUILD_ID_ENV: &str = "ECLIPSE_IBC_BUILD_ID";
+/// Unix time of the build, as set by reproducible builds
+const SOURCE_DATE_EPOCH_ENV: &str = "SOURCE_DATE_EPOCH";
+
+const UNKNOWN: &str = "unknown";
+
+fn git_hash(workspace_dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .current_dir(workspace_dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let hash = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+    (!hash.is_empty()).then_some(hash)
+}
+
+/// `YYYY-MM-DD` of a Unix time, in UTC.
+fn date_of_unix_secs(secs: u64) -> String {
+    // Days since 0000-03-01, so that leap days fall at the end of a year
+    let days = i64::try_from(secs / 86_400).unwrap_or(i64::MAX) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+fn build_date() -> String {
+    let secs = match env::var(SOURCE_DATE_EPOCH_ENV) {
+        Ok(secs) => secs.trim().parse().ok(),
+        Err(_) => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|elapsed| elapsed.as_secs()),
+    };
+    secs.map_or_else(|| UNKNOWN.to_owned(), date_of_unix_secs)
+}
+
+/// Version of `package` in `Cargo.lock`, the first one listed if it is locked at
+/// several.
+fn locked_version(cargo_lock: &str, package: &str) -> Option<String> {
+    let name_line = format!("name = \"{package}\"");
+    let mut lines = cargo_lock.lines();
+    lines.find(|line| line.trim() == name_line)?;
+    let version = lines.next()?.trim().strip_prefix("version = \"")?;
+    Some(version.strip_suffix('"')?.to_owned())
+}
+
+fn main() -> io::Result<()> {
+    let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").expect("set by cargo"));
+    let workspace_dir = manifest_dir.parent().unwrap_or(&manifest_dir);
+    let git_dir = workspace_dir.join(".git");
+    let cargo_lock_path = workspace_dir.join("Cargo.lock");
+
+    println!("cargo:rerun-if-env-changed={BUILD_ID_ENV}");
+    println!("cargo:rerun-if-env-changed={SOURCE_DATE_EPOCH_ENV}");
+    println!("cargo:rerun-if-changed={}", cargo_lock_path.display());
+    println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+    // A commit moves the branch HEAD points to, not HEAD itself
+    if let Some(head_ref) = fs::read_to_string(git_dir.join("HEAD"))
+        .ok()
+        .and_then(|head| head.trim().strip_prefix("ref: ").map(str::to_owned))
+    {
+        println!(
+            "cargo:rerun-if-changed={}",
+            git_dir.join(head_ref).display()
+        );
+    }
+
+    let git_hash = env::var(BUILD_ID_ENV)
+        .ok()
+        .filter(|build_id| !build_id.is_empty())
+        .or_else(|| git_hash(workspace_dir))
+        .unwrap_or_else(|| UNKNOWN.to_owned());
+    let build_date = build_date();
+    let cargo_lock = fs::read_to_string(&cargo_lock_path).unwrap_or_default();
+    let ibc_version = locked_version(&cargo_lock, "ibc").unwrap_or_else(|| UNKNOWN.to_owned());
+    let ibc_proto_version =
+        locked_version(&cargo_lock, "ibc-proto").unwrap_or_else(|| UNKNOWN.to_owned());
+    let version = env::var("CARGO_PKG_VERSION").expect("set by cargo");
+    let long_version = format!(
+        "{version}\ngit hash: {git_hash}\nbuild date: {build_date}\nibc: {ibc_version}\n\
+         ibc-proto: {ibc_proto_version}"
+    );
+
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").expect("set by cargo"));
+    fs::write(
+        out_dir.join("build_info.rs"),
+        format!(
+            "/// Version of the crate, from its manifest\n\
+             pub const VERSION: &str = {version:?};\n\
+             /// Git hash of the commit this was built from, or `{UNKNOWN}` outside a checkout\n\
+             pub const GIT_HASH: &str = {git_hash:?};\n\
+             /// UTC date of the build, as `YYYY-MM-DD`\n\
+             pub const BUILD_DATE: &str = {build_date:?};\n\
+             /// Version of the `ibc` crate this was built against\n\
+             pub const IBC_VERSION: &str = {ibc_version:?};\n\
+             /// Version of the `ibc-proto` crate this was built against\n\
+             pub const IBC_PROTO_VERSION: &str = {ibc_proto_version:?};\n\
+             /// The version followed by the rest of the build info, one field per line\n\
+             pub const LONG_VERSION: &str = {long_version:?};\n"
+        ),
+    )
+}
@@ -1,8 +1,9 @@
 use {
     eclipse_ibc_known_proto::KnownAnyProto,
     eclipse_ibc_light_client::{
-        EclipseClientState, EclipseConsensusState, ECLIPSE_CLIENT_STATE_TYPE_URL,
-        ECLIPSE_CONSENSUS_STATE_TYPE_URL,
+        canonical_type_url, EclipseClientState, EclipseConsensusState,
+        ECLIPSE_CLIENT_STATE_TYPE_URL, ECLIPSE_CONSENSUS_STATE_TYPE_URL,
+        ECLIPSE_HEADER_BATCH_TYPE_URL, ECLIPSE_HEADER_TYPE_URL,
     },
     eclipse_ibc_proto::eclipse::ibc::chain::v1::{
         ClientState as RawEclipseClientState, ConsensusState as RawEclipseConsensusState,
@@ -16,6 +17,7 @@ use {
             ics02_client::{
                 client_state::ClientState, consensus_state::ConsensusState, error::ClientError,
             },
+            ics24_host::identifier::ChainId,
             ContextError,
         },
     },
@@ -30,11 +32,40 @@ use {
 
 const TENDERMINT_CLIENT_STATE_TYPE_URL: &str = "/ibc.lightclients.tendermint.v1.ClientState";
 const TENDERMINT_CONSENSUS_STATE_TYPE_URL: &str = "/ibc.lightclients.tendermint.v1.ConsensusState";
+const TENDERMINT_HEADER_TYPE_URL: &str = "/ibc.lightclients.tendermint.v1.Header";
+
+/// Type URL that a stored value of type `type_url` is decoded as. Eclipse client
+/// and consensus states stored before the Eclipse type URLs were fixed keep
+/// their legacy type URLs until they are next written.
+fn stored_type_url(type_url: &str) -> &str {
+    canonical_type_url(type_url).unwrap_or(type_url)
+}
+
+/// Type URL of the headers that update a client whose state has type
+/// `client_state_type_url`, if it is a supported client type.
+#[must_use]
+pub fn header_type_url_of_client(client_state_type_url: &str) -> Option<&'static str> {
+    match stored_type_url(client_state_type_url) {
+        TENDERMINT_CLIENT_STATE_TYPE_URL => Some(TENDERMINT_HEADER_TYPE_URL),
+        ECLIPSE_CLIENT_STATE_TYPE_URL => Some(ECLIPSE_HEADER_TYPE_URL),
+        _ => None,
+    }
+}
+
+/// Type URL of the batches of headers that update a client whose state has type
+/// `client_state_type_url` in one message, if its client type supports them.
+#[must_use]
+pub fn header_batch_type_url_of_client(client_state_type_url: &str) -> Option<&'static str> {
+    match stored_type_url(client_state_type_url) {
+        ECLIPSE_CLIENT_STATE_TYPE_URL => Some(ECLIPSE_HEADER_BATCH_TYPE_URL),
+        _ => None,
+    }
+}
 
 pub fn decode_client_state(
     client_state: protobuf::Any,
 ) -> Result<Box<dyn ClientState>, ContextError> {
-    match &*client_state.type_url {
+    match stored_type_url(&client_state.type_url) {
         TENDERMINT_CLIENT_STATE_TYPE_URL => Ok(Box::new(
             <TendermintClientState as Protobuf<RawTmClientState>>::decode_vec(&client_state.value)
                 .map_err(|err| ClientError::Other {
@@ -77,10 +108,26 @@ pub fn encode_client_state(
     }
 }
 
+/// Chain ID of the chain that `client_state` tracks, if it is a supported client type.
+#[must_use]
+pub fn tracked_chain_id(client_state: &dyn ClientState) -> Option<ChainId> {
+    if let Some(client_state) = client_state
+        .as_any()
+        .downcast_ref::<TendermintClientState>()
+    {
+        Some(client_state.chain_id.clone())
+    } else {
+        client_state
+            .as_any()
+            .downcast_ref::<EclipseClientState>()
+            .map(|client_state| client_state.chain_id.clone())
+    }
+}
+
 pub fn decode_consensus_state(
     consensus_state: protobuf::Any,
 ) -> Result<Box<dyn ConsensusState>, ContextError> {
-    match &*consensus_state.type_url {
+    match stored_type_url(&consensus_state.type_url) {
         TENDERMINT_CONSENSUS_STATE_TYPE_URL => Ok(Box::new(
             <TendermintConsensusState as Protobuf<RawTmConsensusState>>::decode_vec(
                 &consensus_state.value,
@@ -0,0 +1,106 @@
+use {
+    crate::{decode_client_state, internal_path::ClientUpdateTimePath, IbcState},
+    anyhow::anyhow,
+    derive_more::Display,
+    ibc::core::{
+        ics24_host::{identifier::ClientId, path::ClientStatePath},
+        timestamp::Timestamp,
+    },
+};
+
+/// Whether a client can still be updated and verify proofs.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+pub enum ClientStatus {
+    Active,
+    Expired,
+    Frozen,
+}
+
+/// Status of `client_id` at `now`, a time of the host clock. A client expires
+/// once more time than its `ClientState::expired` allows, such as the trusting
+/// period of Tendermint clients, has passed since the host last updated it, as
+/// recorded at its `ClientUpdateTimePath`.
+pub fn client_status(
+    ibc_state: &IbcState<'_>,
+    client_id: &ClientId,
+    now: Timestamp,
+) -> anyhow::Result<ClientStatus> {
+    let client_state = ibc_state
+        .get(&ClientStatePath::new(client_id))?
+        .ok_or_else(|| anyhow!("No client state found for {client_id}"))?;
+    let client_state = decode_client_state(client_state)?;
+    if client_state.is_frozen() {
+        return Ok(ClientStatus::Frozen);
+    }
+
+    let latest_height = client_state.latest_height();
+    let update_time = ibc_state
+        .get(&ClientUpdateTimePath(client_id.clone(), latest_height))?
+        .ok_or_else(|| anyhow!("No update time found for client {client_id} at {latest_height}"))?;
+    // An update time after `now` is from a host clock that ran ahead, not an
+    // expired client
+    let elapsed = now.duration_since(&update_time).unwrap_or_default();
+    if client_state.expired(elapsed) {
+        Ok(ClientStatus::Expired)
+    } else {
+        Ok(ClientStatus::Active)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::IbcStore,
+        core::time::Duration,
+        eclipse_ibc_fixtures::clients,
+        eclipse_ibc_known_proto::KnownAnyProto,
+        eclipse_ibc_light_client::{eclipse_chain::IBC_MESSAGE_VALID_DURATION, EclipseClientState},
+        ibc::core::ics02_client::height::Height,
+    };
+
+    #[test]
+    fn clients_expire_after_their_last_update() {
+        let client_id = "xx-eclipse-0".parse::<ClientId>().unwrap();
+        let updated_at = Timestamp::from_nanoseconds(1_000_000_000_000).unwrap();
+        let store = IbcStore::default();
+        let mut state = IbcState::new(&store, 1);
+        state
+            .set_any(
+                &ClientStatePath::new(&client_id),
+                clients::eclipse_client_state(5).encode_as_any(),
+            )
+            .unwrap();
+        state.set(
+            &ClientUpdateTimePath(client_id.clone(), Height::new(0, 5).unwrap()),
+            updated_at,
+        );
+
+        let status = |now| client_status(&state, &client_id, now).unwrap();
+        let expiry = (updated_at + IBC_MESSAGE_VALID_DURATION).unwrap();
+        assert_eq!(status(updated_at), ClientStatus::Active);
+        assert_eq!(status(expiry), ClientStatus::Active);
+        assert_eq!(
+            status((expiry + Duration::from_secs(1)).unwrap()),
+            ClientStatus::Expired
+        );
+
+        let frozen_client_state = EclipseClientState {
+            frozen_height: Some(Height::new(0, 5).unwrap()),
+            ..clients::eclipse_client_state(5)
+        };
+        state
+            .set_any(
+                &ClientStatePath::new(&client_id),
+                frozen_client_state.encode_as_any(),
+            )
+            .unwrap();
+        assert_eq!(
+            client_status(&state, &client_id, updated_at).unwrap(),
+            ClientStatus::Frozen
+        );
+
+        let unknown_client = "xx-eclipse-1".parse::<ClientId>().unwrap();
+        client_status(&state, &unknown_client, updated_at).unwrap_err();
+    }
+}
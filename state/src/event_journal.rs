@@ -0,0 +1,121 @@
+use {
+    serde::{Deserialize, Serialize},
+    solana_sdk::clock::Slot,
+    std::collections::VecDeque,
+};
+
+/// An IBC event emitted by the program, as the log line it was logged as.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct JournalEntry {
+    pub slot: Slot,
+    /// Position of the event among every event journaled, which keeps counting
+    /// across evictions so that readers can tell when they missed some
+    pub sequence: u64,
+    pub event_log: String,
+}
+
+/// The latest IBC events emitted by the program. Transaction logs are truncated
+/// and eventually dropped by RPC nodes, so relayers that fall behind recover
+/// missed events, such as `send_packet`, from the journal instead. Once the
+/// journal is full, appending evicts the oldest entries.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EventJournal {
+    next_sequence: u64,
+    entries: VecDeque<JournalEntry>,
+}
+
+impl EventJournal {
+    /// Appends the event logged as `event_log` at `slot`, keeping at most
+    /// `capacity` entries.
+    pub fn append(&mut self, slot: Slot, event_log: String, capacity: usize) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.entries.push_back(JournalEntry {
+            slot,
+            sequence,
+            event_log,
+        });
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Entries of events emitted at or after `from_slot`, oldest first.
+    pub fn entries_from(&self, from_slot: Slot) -> impl Iterator<Item = &JournalEntry> {
+        self.entries
+            .iter()
+            .skip_while(move |entry| entry.slot < from_slot)
+    }
+
+    /// Sequence the next event appended will get.
+    #[must_use]
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oldest_entries_are_evicted_when_full() {
+        let mut journal = EventJournal::default();
+        for slot in 0..5 {
+            journal.append(slot, format!("event {slot}"), 3);
+        }
+        assert_eq!(journal.len(), 3);
+        assert_eq!(journal.next_sequence(), 5);
+        let entries = journal.entries_from(0).collect::<Vec<_>>();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.slot, entry.sequence, entry.event_log.as_str()))
+                .collect::<Vec<_>>(),
+            [(2, 2, "event 2"), (3, 3, "event 3"), (4, 4, "event 4")],
+        );
+        assert_eq!(journal.entries_from(4).count(), 1);
+        assert_eq!(journal.entries_from(5).count(), 0);
+
+        // Lowering the capacity evicts down to it on the next append
+        journal.append(5, "event 5".to_owned(), 1);
+        assert_eq!(
+            journal
+                .entries_from(0)
+                .map(|entry| entry.sequence)
+                .collect::<Vec<_>>(),
+            [5]
+        );
+    }
+
+    #[test]
+    fn journal_encoding_is_stable() {
+        let mut journal = EventJournal::default();
+        journal.append(7, "a".to_owned(), 2);
+        // Account data written by earlier program versions must keep decoding, so
+        // the encoding of the journal must not change
+        let mut expected = [
+            1_u64.to_le_bytes(), // next_sequence
+            1_u64.to_le_bytes(), // number of entries
+            7_u64.to_le_bytes(), // slot
+            0_u64.to_le_bytes(), // sequence
+            1_u64.to_le_bytes(), // length of the event log
+        ]
+        .concat();
+        expected.extend_from_slice(b"a");
+        assert_eq!(bincode::serialize(&journal).unwrap(), expected);
+        let decoded: EventJournal =
+            bincode::deserialize(&bincode::serialize(&journal).unwrap()).unwrap();
+        assert_eq!(decoded, journal);
+    }
+}
@@ -1,47 +1,851 @@
 use {
-    crate::{IbcMetadata, IbcStore},
-    core::fmt::Debug,
-    serde::{Deserialize, Serialize},
+    crate::{
+        internal_path::StateInitializedPath, EventJournal, IbcMetadata, IbcMetadataV4, IbcState,
+        IbcStore, LegacyIbcMetadata, LoadChainParams,
+    },
+    anyhow::{anyhow, bail},
+    bincode::Options,
+    core::fmt::{self, Debug},
+    eclipse_ibc_extra_types::ChainParams,
+    serde::{de::DeserializeOwned, Deserialize},
     solana_program_runtime::{ic_msg, invoke_context::InvokeContext},
-    solana_sdk::{instruction::InstructionError, transaction_context::BorrowedAccount},
+    solana_sdk::{
+        entrypoint::MAX_PERMITTED_DATA_INCREASE, instruction::InstructionError, rent::Rent,
+        system_instruction::MAX_PERMITTED_DATA_LENGTH, transaction_context::BorrowedAccount,
+    },
 };
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+/// Marks account data written in the header layout. Legacy account data is a
+/// single bincode-encoded `LegacyAccountData`, which starts with the length of the
+/// store's node map and so never begins with these bytes in practice.
+const MAGIC: [u8; 4] = *b"EIBC";
+const FORMAT_VERSION: u8 = 6;
+
+/// Magic, format version, and the little-endian `u32` length of the metadata
+/// segment. The metadata segment follows, then the store. Since version 2, the
+/// store's recorded path preimages follow the store, and since version 3 the
+/// event journal follows them. Since version 4, the metadata holds the commit
+/// checksum, and since version 5 the revision number. Since version 6, the
+/// store's stale node index follows the event journal; older data has it rebuilt
+/// from the store's nodes.
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4;
+
+/// Fewest bytes an event journal entry is encoded in: its slot, sequence and the
+/// length prefix of its event log.
+const MIN_JOURNAL_ENTRY_LEN: usize = 24;
+
+/// Custom instruction error for IBC account data that decodes, but whose store
+/// no longer matches its latest root, or that declares more than it holds.
+/// Follows on from the IBC program's codes.
+pub const CORRUPTED_STATE_ERR_CODE: u32 = 0x9c;
+
+/// Custom instruction error for IBC account data that no longer fits in the
+/// storage account, even after growing it in place, which
+/// `MsgReallocStorageAccount` can grow further.
+pub const ACCOUNT_FULL_ERR_CODE: u32 = 0xa3;
+
+/// IBC account data that is larger than the account holding it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountFull {
+    pub size: usize,
+    pub max_size: usize,
+}
+
+impl AccountFull {
+    /// Checks that account data of `size` bytes fits in `max_size` bytes.
+    pub fn check(size: usize, max_size: usize) -> Result<(), Self> {
+        if size > max_size {
+            return Err(Self { size, max_size });
+        }
+        Ok(())
+    }
+}
+
+/// Whether an account of `current_size` bytes holding `lamports` can grow in place
+/// to hold `size` bytes of account data: by at most `MAX_PERMITTED_DATA_INCREASE`
+/// bytes, and only if the lamports keep it rent-exempt at the new size.
+fn grows_in_place(size: usize, current_size: usize, lamports: u64, rent: &Rent) -> bool {
+    size > current_size
+        && size - current_size <= MAX_PERMITTED_DATA_INCREASE
+        && size as u64 <= MAX_PERMITTED_DATA_LENGTH
+        && rent.is_exempt(lamports, size)
+}
+
+impl fmt::Display for AccountFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AccountFull {{ size: {}, max_size: {} }}",
+            self.size, self.max_size
+        )
+    }
+}
+
+/// IBC account data that declares more bytes or entries than it holds, which
+/// only corrupted or crafted data does. Decoding fails at the first such length
+/// instead of allocating for it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImplausibleAccountData {
+    pub description: String,
+}
+
+impl fmt::Display for ImplausibleAccountData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ImplausibleAccountData {{ description: {} }}",
+            self.description
+        )
+    }
+}
+
+impl std::error::Error for ImplausibleAccountData {}
+
+/// Bincode options of `bincode::deserialize`, except that at most `limit` bytes
+/// are read, so that a length prefix past the end of the data fails the decode
+/// before anything is allocated for it.
+fn bounded_bincode(limit: usize) -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limit as u64)
+}
+
+/// Decodes a `T` from `data`, reading no more than its bytes.
+fn bounded_deserialize<T: DeserializeOwned>(data: &[u8]) -> anyhow::Result<T> {
+    bounded_bincode(data.len())
+        .deserialize(data)
+        .map_err(decode_err)
+}
+
+/// Reports length prefixes that run past the read limit as
+/// [`ImplausibleAccountData`], and any other failure as is.
+fn decode_err(err: bincode::Error) -> anyhow::Error {
+    match *err {
+        bincode::ErrorKind::SizeLimit => ImplausibleAccountData {
+            description: "a length prefix runs past the end of the data".to_owned(),
+        }
+        .into(),
+        err => err.into(),
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct IbcAccountData {
     pub store: IbcStore,
     pub metadata: IbcMetadata,
+    /// Not part of the legacy layout, which predates it
+    pub event_journal: EventJournal,
+}
+
+/// Account data in the legacy layout, a single bincode-encoded struct.
+#[derive(Deserialize)]
+struct LegacyAccountData {
+    store: IbcStore,
+    metadata: LegacyIbcMetadata,
+}
+
+/// Reads the format version and metadata segment length from the header of
+/// header-layout account data, or returns `None` for legacy account data.
+fn read_header(data: &[u8]) -> anyhow::Result<Option<(u8, usize)>> {
+    if !data.starts_with(&MAGIC) {
+        return Ok(None);
+    }
+    if data.len() < HEADER_LEN {
+        bail!("IBC account data is too short for its header");
+    }
+
+    let version = data[MAGIC.len()];
+    if !(1..=FORMAT_VERSION).contains(&version) {
+        bail!("unsupported IBC account data format version: {version}");
+    }
+    let metadata_len_bytes = data[MAGIC.len() + 1..HEADER_LEN]
+        .try_into()
+        .expect("slice is 4 bytes long");
+    let metadata_len = u32::from_le_bytes(metadata_len_bytes) as usize;
+    Ok(Some((version, metadata_len)))
+}
+
+/// Splits header-layout account data into its format version, metadata segment,
+/// and the rest of the data, or returns `None` for legacy account data.
+fn split_segments(data: &[u8]) -> anyhow::Result<Option<(u8, &[u8], &[u8])>> {
+    let (version, metadata_len) = match read_header(data)? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+
+    let segments = &data[HEADER_LEN..];
+    if segments.len() < metadata_len {
+        bail!(
+            "IBC account metadata segment is truncated; expected {metadata_len} bytes, found {}",
+            segments.len()
+        );
+    }
+    let (metadata, rest) = segments.split_at(metadata_len);
+    Ok(Some((version, metadata, rest)))
+}
+
+/// Decodes the metadata segment of account data at format `version`.
+fn decode_metadata(version: u8, metadata: &[u8]) -> anyhow::Result<IbcMetadata> {
+    if version >= 5 {
+        bounded_deserialize(metadata)
+    } else if version == 4 {
+        bounded_deserialize::<IbcMetadataV4>(metadata).map(IbcMetadata::from)
+    } else {
+        bounded_deserialize::<LegacyIbcMetadata>(metadata).map(IbcMetadata::from)
+    }
 }
 
 impl IbcAccountData {
+    /// Length of the header of header-layout account data.
+    pub const HEADER_LEN: usize = HEADER_LEN;
+
+    /// Decodes account data in either the header layout or the legacy layout.
+    /// Data declaring more than it holds is rejected with
+    /// [`ImplausibleAccountData`].
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        let account_data = match split_segments(data)? {
+            Some((version, metadata, rest)) => {
+                // The segments after the metadata are read back to back, so that
+                // the store can borrow the node bytes from the data
+                let mut deserializer =
+                    bincode::Deserializer::from_slice(rest, bounded_bincode(rest.len()));
+                let store = IbcStore::deserialize(&mut deserializer).map_err(decode_err)?;
+                if version >= 2 {
+                    store.set_preimages(
+                        Deserialize::deserialize(&mut deserializer).map_err(decode_err)?,
+                    )?;
+                }
+                let event_journal = if version >= 3 {
+                    EventJournal::deserialize(&mut deserializer).map_err(decode_err)?
+                } else {
+                    EventJournal::default()
+                };
+                if version >= 6 {
+                    store.set_encoded_stale_nodes(
+                        Deserialize::deserialize(&mut deserializer).map_err(decode_err)?,
+                    )?;
+                } else {
+                    store.index_stale_nodes();
+                }
+                Self {
+                    store,
+                    metadata: decode_metadata(version, metadata)?,
+                    event_journal,
+                }
+            }
+            None => {
+                let LegacyAccountData { store, metadata } = bounded_deserialize(data)?;
+                store.index_stale_nodes();
+                Self {
+                    store,
+                    metadata: metadata.into(),
+                    event_journal: EventJournal::default(),
+                }
+            }
+        };
+        account_data.store.check_entry_counts(data.len())?;
+        let journal_len = account_data.event_journal.len();
+        if journal_len.saturating_mul(MIN_JOURNAL_ENTRY_LEN) > data.len() {
+            return Err(ImplausibleAccountData {
+                description: format!(
+                    "{journal_len} journaled events take at least {MIN_JOURNAL_ENTRY_LEN} bytes \
+                     each, more than the {} bytes of account data",
+                    data.len()
+                ),
+            }
+            .into());
+        }
+        Ok(account_data)
+    }
+
+    /// Decodes only the metadata, without touching the (much larger) store when
+    /// the account data is in the header layout.
+    pub fn read_metadata_only(data: &[u8]) -> anyhow::Result<IbcMetadata> {
+        match split_segments(data)? {
+            Some((version, metadata, _rest)) => decode_metadata(version, metadata),
+            None => Ok(Self::from_bytes(data)?.metadata),
+        }
+    }
+
+    /// Where the metadata segment ends, read from the first [`Self::HEADER_LEN`]
+    /// bytes of header-layout account data, so that the metadata can be fetched
+    /// without the store. Returns `None` for legacy account data.
+    pub fn metadata_end(header: &[u8]) -> anyhow::Result<Option<usize>> {
+        Ok(read_header(header)?.map(|(_version, metadata_len)| HEADER_LEN + metadata_len))
+    }
+
+    /// Encodes the account data in the header layout.
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let metadata = bincode::serialize(&self.metadata)?;
+        let metadata_len = u32::try_from(metadata.len())
+            .map_err(|_| anyhow!("IBC account metadata is too large"))?;
+
+        let mut data = Vec::with_capacity(HEADER_LEN + metadata.len());
+        data.extend_from_slice(&MAGIC);
+        data.push(FORMAT_VERSION);
+        data.extend_from_slice(&metadata_len.to_le_bytes());
+        data.extend_from_slice(&metadata);
+        bincode::serialize_into(&mut data, &self.store)?;
+        bincode::serialize_into(&mut data, &self.store.preimages()?)?;
+        bincode::serialize_into(&mut data, &self.event_journal)?;
+        bincode::serialize_into(&mut data, &self.store.encoded_stale_nodes()?)?;
+        Ok(data)
+    }
+
+    /// Length of [`Self::to_bytes`], computed without encoding the account data.
+    pub fn serialized_size(&self) -> anyhow::Result<usize> {
+        let metadata_len = bincode::serialized_size(&self.metadata)?;
+        let store_len = bincode::serialized_size(&self.store)?;
+        let preimages_len = bincode::serialized_size(&self.store.preimages()?)?;
+        let event_journal_len = bincode::serialized_size(&self.event_journal)?;
+        let stale_nodes_len = bincode::serialized_size(&self.store.encoded_stale_nodes()?)?;
+        Ok(HEADER_LEN
+            + usize::try_from(
+                metadata_len + store_len + preimages_len + event_journal_len + stale_nodes_len,
+            )?)
+    }
+
+    /// Checks that `data` was fully written by `MsgInitStorageAccount`: it is in
+    /// the header layout, decodes, and the latest state version has the
+    /// `StateInitializedPath` marker. The error says which check failed.
+    pub fn check_initialized(data: &[u8]) -> anyhow::Result<()> {
+        if !data.starts_with(&MAGIC) {
+            bail!("IBC account data does not start with the header magic");
+        }
+        let account_data = Self::from_bytes(data)?;
+        let latest_version = account_data
+            .store
+            .read()
+            .latest_version()
+            .ok_or_else(|| anyhow!("IBC account store has no state versions"))?;
+        let state = IbcState::new(&account_data.store, latest_version);
+        if !state.contains(&StateInitializedPath)? {
+            bail!("IBC state at version {latest_version} is missing {StateInitializedPath}");
+        }
+        Ok(())
+    }
+
+    /// Like [`Self::from_bytes`], but also rejects account data whose latest root
+    /// is not the one recorded at its last commit.
+    pub fn from_bytes_verified(data: &[u8]) -> anyhow::Result<Self> {
+        let account_data = Self::from_bytes(data)?;
+        account_data.verify_latest_root()?;
+        Ok(account_data)
+    }
+
+    /// Checks the root of the latest state version, recomputed from its root node,
+    /// against the root the metadata recorded at the last commit. Only the root
+    /// node is read, so this is cheap enough to run on every load. Metadata
+    /// written before the root was recorded passes until its next commit.
+    pub fn verify_latest_root(&self) -> anyhow::Result<()> {
+        let metadata = &self.metadata;
+        if !metadata.has_checksum() {
+            return Ok(());
+        }
+        let latest_version = self
+            .store
+            .read()
+            .latest_version()
+            .ok_or_else(|| anyhow!("IBC account store has no state versions"))?;
+        let jmt::RootHash(root_hash) =
+            jmt::Sha256Jmt::new(&self.store).get_root_hash(latest_version)?;
+        if metadata.root_hash != root_hash {
+            bail!(
+                "metadata root hash {} does not match the root {} at version {latest_version}",
+                hex::encode(metadata.root_hash),
+                hex::encode(root_hash)
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks the root of the latest state version against every key's value and
+    /// the tree nodes along its path. This reads the whole store, so only the
+    /// CLI runs it, on request.
+    pub fn verify_latest_root_deep(&self) -> anyhow::Result<()> {
+        let latest_version = self.store.read().latest_version();
+        match latest_version {
+            Some(latest_version) => self.store.verify_root(latest_version),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the metadata's commit checksum against the rest of the metadata and
+    /// the root of the latest state version. Metadata written before the
+    /// checksum was introduced passes until its next commit.
+    pub fn verify_checksum(&self) -> anyhow::Result<()> {
+        let metadata = &self.metadata;
+        if !metadata.has_checksum() {
+            return Ok(());
+        }
+        let checksum = metadata.compute_checksum();
+        if metadata.checksum != checksum {
+            bail!(
+                "metadata checksum is {:016x}, but its fields hash to {checksum:016x}",
+                metadata.checksum
+            );
+        }
+        self.verify_latest_root()
+    }
+
+    /// Whether the chain params leave the root check on when the program loads
+    /// the account data.
+    fn checks_root_on_load(&self) -> anyhow::Result<bool> {
+        let latest_version = self.store.read().latest_version();
+        let chain_params = match latest_version {
+            Some(latest_version) => {
+                ChainParams::load_or_default(&IbcState::new(&self.store, latest_version))?
+            }
+            None => ChainParams::default(),
+        };
+        Ok(!chain_params.skip_root_check_on_load)
+    }
+
     pub fn read_from_account(
         account: &BorrowedAccount<'_>,
         invoke_context: &InvokeContext,
     ) -> Result<Self, InstructionError> {
-        let account_data = account.get_data();
-        bincode::deserialize::<Self>(account_data).map_err(|err| {
+        let account_data = Self::from_bytes(account.get_data()).map_err(|err| {
             ic_msg!(
                 invoke_context,
                 "failed to deserialize IBC account data: {:?}",
                 err,
             );
+            if err.is::<ImplausibleAccountData>() {
+                InstructionError::Custom(CORRUPTED_STATE_ERR_CODE)
+            } else {
+                InstructionError::InvalidAccountData
+            }
+        })?;
+
+        let checks_root = account_data.checks_root_on_load().map_err(|err| {
+            ic_msg!(invoke_context, "failed to read chain params: {:?}", err);
             InstructionError::InvalidAccountData
-        })
+        })?;
+        if checks_root {
+            account_data.verify_latest_root().map_err(|err| {
+                ic_msg!(invoke_context, "IBC state is corrupted: {:?}", err);
+                InstructionError::Custom(CORRUPTED_STATE_ERR_CODE)
+            })?;
+        }
+        Ok(account_data)
     }
 
+    /// Writes the account data at the start of the account, zeroing the rest. Data
+    /// that no longer fits grows the account in place if the account already holds
+    /// the lamports to stay rent-exempt, which the payer can transfer to it
+    /// beforehand; otherwise it is rejected with [`ACCOUNT_FULL_ERR_CODE`].
     pub fn write_to_account(
         &self,
         account: &mut BorrowedAccount<'_>,
         invoke_context: &InvokeContext,
     ) -> Result<(), InstructionError> {
-        let account_data = bincode::serialize(&self).map_err(|err| {
+        let serialize_err = |err| {
             ic_msg!(
                 invoke_context,
                 "failed to serialize new IBC account data: {:?}",
                 err,
             );
             InstructionError::InvalidAccountData
-        })?;
-        account.set_data(&account_data)?;
+        };
+
+        let size = self.serialized_size().map_err(serialize_err)?;
+        let current_size = account.get_data().len();
+        if size > current_size {
+            let rent = invoke_context.get_sysvar_cache().get_rent()?;
+            if grows_in_place(size, current_size, account.get_lamports(), &rent) {
+                account.set_data_length(size)?;
+                ic_msg!(
+                    invoke_context,
+                    "StorageAccountGrown {{ current_size: {}, new_size: {} }}",
+                    current_size,
+                    size
+                );
+            }
+        }
+        if let Err(account_full) = AccountFull::check(size, account.get_data().len()) {
+            ic_msg!(invoke_context, "{}", account_full);
+            return Err(InstructionError::Custom(ACCOUNT_FULL_ERR_CODE));
+        }
+
+        let account_data = self.to_bytes().map_err(serialize_err)?;
+        let (data, rest) = account.get_data_mut()?.split_at_mut(account_data.len());
+        data.copy_from_slice(&account_data);
+        rest.fill(0);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::internal_path::ChainParamsPath};
+
+    fn account_data() -> IbcAccountData {
+        let mut account_data = IbcAccountData {
+            metadata: IbcMetadata {
+                client_id_counter: 3,
+                connection_id_counter: 2,
+                channel_id_counter: 1,
+                ..IbcMetadata::default()
+            },
+            ..IbcAccountData::default()
+        };
+        {
+            let mut state = IbcState::new(&account_data.store, 7);
+            state.set(&StateInitializedPath, ());
+            let root_hash = state.commit().unwrap();
+            account_data.metadata.record_commit(root_hash);
+        }
+        account_data
+    }
+
+    /// Encodes the account data in the legacy layout.
+    fn stale_nodes_len(account_data: &IbcAccountData) -> u64 {
+        bincode::serialized_size(&account_data.store.encoded_stale_nodes().unwrap()).unwrap()
+    }
+
+    fn legacy_bytes(account_data: &IbcAccountData) -> Vec<u8> {
+        bincode::serialize(&(
+            &account_data.store,
+            LegacyIbcMetadata::from(&account_data.metadata),
+        ))
+        .unwrap()
+    }
+
+    fn assert_metadata_eq(actual: &IbcMetadata, expected: &IbcMetadata) {
+        assert_eq!(actual.client_id_counter, expected.client_id_counter);
+        assert_eq!(actual.connection_id_counter, expected.connection_id_counter);
+        assert_eq!(actual.channel_id_counter, expected.channel_id_counter);
+    }
+
+    #[test]
+    fn serialized_size_matches_encoding() {
+        let account_data = account_data();
+        let size = account_data.serialized_size().unwrap();
+        assert_eq!(account_data.to_bytes().unwrap().len(), size);
+
+        // Trailing zeros left by a larger account are ignored
+        let mut data = account_data.to_bytes().unwrap();
+        data.resize(size + 100, 0);
+        let decoded = IbcAccountData::from_bytes(&data).unwrap();
+        assert_eq!(decoded.store.read().latest_version(), Some(7));
+    }
+
+    #[test]
+    fn nearly_full_accounts_reject_growth() {
+        let account_data = account_data();
+        let size = account_data.serialized_size().unwrap();
+        AccountFull::check(size, size).unwrap();
+
+        let mut state = IbcState::new(&account_data.store, 8);
+        state.set(&ChainParamsPath, ChainParams::default());
+        state.commit().unwrap();
+        let new_size = account_data.serialized_size().unwrap();
+        assert!(new_size > size);
+        assert_eq!(
+            AccountFull::check(new_size, size),
+            Err(AccountFull {
+                size: new_size,
+                max_size: size,
+            }),
+        );
+        AccountFull::check(new_size, new_size).unwrap();
+    }
+
+    #[test]
+    fn accounts_grow_in_place_only_when_rent_is_covered() {
+        let rent = Rent::default();
+        let current_size = 1_000;
+        let size = current_size + 100;
+        let lamports = rent.minimum_balance(size);
+
+        assert!(grows_in_place(size, current_size, lamports, &rent));
+        // The payer has not covered the rent of the larger account
+        assert!(!grows_in_place(size, current_size, lamports - 1, &rent));
+        // Growth beyond a single increase needs `MsgReallocStorageAccount`
+        let size = current_size + MAX_PERMITTED_DATA_INCREASE + 1;
+        assert!(!grows_in_place(
+            size,
+            current_size,
+            rent.minimum_balance(size),
+            &rent
+        ));
+        // Data that fits is written without growing the account
+        assert!(!grows_in_place(current_size, current_size, lamports, &rent));
+    }
+
+    #[test]
+    fn header_layout_round_trips() {
+        let account_data = account_data();
+        let data = account_data.to_bytes().unwrap();
+        assert!(data.starts_with(&MAGIC));
+
+        let decoded = IbcAccountData::from_bytes(&data).unwrap();
+        assert_eq!(decoded.metadata, account_data.metadata);
+        assert_eq!(decoded.store.read().latest_version(), Some(7));
+        decoded.verify_checksum().unwrap();
+
+        let metadata = IbcAccountData::read_metadata_only(&data).unwrap();
+        assert_eq!(metadata, account_data.metadata);
+
+        let metadata_end = IbcAccountData::metadata_end(&data[..IbcAccountData::HEADER_LEN])
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            IbcAccountData::read_metadata_only(&data[..metadata_end]).unwrap(),
+            metadata
+        );
+    }
+
+    #[test]
+    fn metadata_before_version_4_has_no_checksum() {
+        let account_data = account_data();
+        let legacy_metadata =
+            bincode::serialize(&LegacyIbcMetadata::from(&account_data.metadata)).unwrap();
+        let mut data = MAGIC.to_vec();
+        data.push(3);
+        data.extend_from_slice(&u32::try_from(legacy_metadata.len()).unwrap().to_le_bytes());
+        data.extend_from_slice(&legacy_metadata);
+        bincode::serialize_into(&mut data, &account_data.store).unwrap();
+        bincode::serialize_into(&mut data, &account_data.store.preimages().unwrap()).unwrap();
+        bincode::serialize_into(&mut data, &account_data.event_journal).unwrap();
+
+        let decoded = IbcAccountData::from_bytes(&data).unwrap();
+        assert_metadata_eq(&decoded.metadata, &account_data.metadata);
+        assert!(!decoded.metadata.has_checksum());
+        decoded.verify_checksum().unwrap();
+    }
+
+    #[test]
+    fn checksum_mismatches_are_detected() {
+        let mut account_data = account_data();
+        account_data.verify_checksum().unwrap();
+
+        account_data.metadata.channel_id_counter += 1;
+        account_data.verify_checksum().unwrap_err();
+        account_data.metadata.channel_id_counter -= 1;
+
+        // A commit the metadata did not record
+        let mut state = IbcState::new(&account_data.store, 8);
+        state.set(&ChainParamsPath, ChainParams::default());
+        state.commit().unwrap();
+        account_data.verify_checksum().unwrap_err();
+    }
+
+    #[test]
+    fn metadata_reads_skip_the_store() {
+        let account_data = account_data();
+        let mut data = account_data.to_bytes().unwrap();
+
+        // Corrupting the store segment only breaks full reads
+        let metadata_len = bincode::serialized_size(&account_data.metadata).unwrap();
+        let store_offset = HEADER_LEN + usize::try_from(metadata_len).unwrap();
+        data.truncate(store_offset + 1);
+        IbcAccountData::from_bytes(&data).unwrap_err();
+        let metadata = IbcAccountData::read_metadata_only(&data).unwrap();
+        assert_metadata_eq(&metadata, &account_data.metadata);
+    }
+
+    #[test]
+    fn legacy_layout_is_still_readable() {
+        let account_data = account_data();
+        let mut data = legacy_bytes(&account_data);
+        // Bytes past the end of the encoded data are ignored
+        data.resize(data.len() + 64, 0);
+
+        let decoded = IbcAccountData::from_bytes(&data).unwrap();
+        assert_metadata_eq(&decoded.metadata, &account_data.metadata);
+        assert_eq!(decoded.store.read().latest_version(), Some(7));
+
+        let metadata = IbcAccountData::read_metadata_only(&data).unwrap();
+        assert_metadata_eq(&metadata, &account_data.metadata);
+    }
+
+    #[test]
+    fn preimages_round_trip() {
+        let account_data = account_data();
+        let data = account_data.to_bytes().unwrap();
+        let decoded = IbcAccountData::from_bytes(&data).unwrap();
+        assert_eq!(
+            decoded.store.preimages_with_prefix("").unwrap(),
+            vec![StateInitializedPath.to_string()],
+        );
+
+        // Version 1 data ends with the store and decodes without preimages
+        let preimages_len =
+            bincode::serialized_size(&account_data.store.preimages().unwrap()).unwrap();
+        let event_journal_len = bincode::serialized_size(&account_data.event_journal).unwrap();
+        let trailing_len = preimages_len + event_journal_len + stale_nodes_len(&account_data);
+        let mut v1_data = data[..data.len() - usize::try_from(trailing_len).unwrap()].to_vec();
+        v1_data[MAGIC.len()] = 1;
+        let decoded = IbcAccountData::from_bytes(&v1_data).unwrap();
+        assert_eq!(decoded.store.read().latest_version(), Some(7));
+        assert!(decoded.store.preimages_with_prefix("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn event_journal_round_trips() {
+        let mut account_data = account_data();
+        account_data
+            .event_journal
+            .append(7, "send_packet".to_owned(), 8);
+        let data = account_data.to_bytes().unwrap();
+        assert_eq!(account_data.serialized_size().unwrap(), data.len());
+        let decoded = IbcAccountData::from_bytes(&data).unwrap();
+        assert_eq!(decoded.event_journal, account_data.event_journal);
+
+        // Version 2 data ends with the preimages and decodes with an empty journal
+        let event_journal_len = bincode::serialized_size(&account_data.event_journal).unwrap();
+        let trailing_len = event_journal_len + stale_nodes_len(&account_data);
+        let mut v2_data = data[..data.len() - usize::try_from(trailing_len).unwrap()].to_vec();
+        v2_data[MAGIC.len()] = 2;
+        let decoded = IbcAccountData::from_bytes(&v2_data).unwrap();
+        assert!(decoded.event_journal.is_empty());
+        assert_eq!(
+            decoded.store.preimages_with_prefix("").unwrap(),
+            vec![StateInitializedPath.to_string()],
+        );
+    }
+
+    #[test]
+    fn stale_nodes_round_trip() {
+        let mut account_data = account_data();
+        for version in 8..=10 {
+            let mut state = IbcState::new(&account_data.store, version);
+            state.set(&ChainParamsPath, ChainParams::default());
+            let root_hash = state.commit().unwrap();
+            account_data.metadata.record_commit(root_hash);
+        }
+        let stale_nodes = account_data.store.encoded_stale_nodes().unwrap();
+        assert!(!stale_nodes.is_empty());
+        let data = account_data.to_bytes().unwrap();
+        assert_eq!(account_data.serialized_size().unwrap(), data.len());
+        let decoded = IbcAccountData::from_bytes(&data).unwrap();
+        assert_eq!(decoded.store.encoded_stale_nodes().unwrap(), stale_nodes);
+
+        // Version 5 data ends with the journal and has the index rebuilt, which
+        // finds the same nodes when every stale node's path was rewritten
+        let trailing_len = usize::try_from(stale_nodes_len(&account_data)).unwrap();
+        let mut v5_data = data[..data.len() - trailing_len].to_vec();
+        v5_data[MAGIC.len()] = 5;
+        let decoded = IbcAccountData::from_bytes(&v5_data).unwrap();
+        assert_eq!(decoded.store.encoded_stale_nodes().unwrap(), stale_nodes);
+    }
+
+    #[test]
+    fn length_prefixes_past_the_data_are_rejected() {
+        let header = |metadata_len: usize| {
+            let mut data = MAGIC.to_vec();
+            data.push(FORMAT_VERSION);
+            data.extend_from_slice(&u32::try_from(metadata_len).unwrap().to_le_bytes());
+            data
+        };
+        let metadata = bincode::serialize(&IbcMetadata::default()).unwrap();
+        let is_implausible = |data: &[u8]| {
+            IbcAccountData::from_bytes(data)
+                .unwrap_err()
+                .is::<ImplausibleAccountData>()
+        };
+
+        // A node key as long as the address space
+        let mut data = header(metadata.len());
+        data.extend_from_slice(&metadata);
+        data.extend_from_slice(&1_u64.to_le_bytes());
+        data.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(is_implausible(&data));
+
+        // More preimages than there are bytes, after a valid store
+        let mut data = account_data().to_bytes().unwrap();
+        let preimages_len =
+            bincode::serialized_size(&account_data().store.preimages().unwrap()).unwrap();
+        let event_journal_len = bincode::serialized_size(&EventJournal::default()).unwrap();
+        let trailing_len = preimages_len + event_journal_len + stale_nodes_len(&account_data());
+        data.truncate(data.len() - usize::try_from(trailing_len).unwrap());
+        data.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(is_implausible(&data));
+
+        // Legacy data declaring a node map and node key larger than the data
+        let data = [u64::MAX.to_le_bytes(), u64::MAX.to_le_bytes()].concat();
+        assert!(is_implausible(&data));
+
+        // A valid store is too large for fewer bytes than it was encoded in
+        let account_data = account_data();
+        account_data.store.check_entry_counts(1024).unwrap();
+        assert!(account_data
+            .store
+            .check_entry_counts(1)
+            .unwrap_err()
+            .is::<ImplausibleAccountData>());
+    }
+
+    #[test]
+    fn unknown_format_version_is_rejected() {
+        let mut data = account_data().to_bytes().unwrap();
+        data[MAGIC.len()] = FORMAT_VERSION + 1;
+        IbcAccountData::from_bytes(&data).unwrap_err();
+        IbcAccountData::read_metadata_only(&data).unwrap_err();
+    }
+
+    #[test]
+    fn initialized_account_data_is_recognized() {
+        let data = account_data().to_bytes().unwrap();
+        IbcAccountData::check_initialized(&data).unwrap();
+
+        // Freshly created accounts are zero-filled
+        IbcAccountData::check_initialized(&[0; 64]).unwrap_err();
+
+        let legacy_data = legacy_bytes(&account_data());
+        IbcAccountData::check_initialized(&legacy_data).unwrap_err();
+
+        let mut truncated_data = data.clone();
+        truncated_data.truncate(HEADER_LEN + 1);
+        IbcAccountData::check_initialized(&truncated_data).unwrap_err();
+
+        let uninitialized_data = IbcAccountData::default().to_bytes().unwrap();
+        IbcAccountData::check_initialized(&uninitialized_data).unwrap_err();
+    }
+
+    #[test]
+    fn corrupted_store_is_detected_on_load() {
+        let mut account_data = account_data();
+        {
+            let mut state = IbcState::new(&account_data.store, 8);
+            state.set(&ChainParamsPath, ChainParams::default());
+            let root_hash = state.commit().unwrap();
+            account_data.metadata.record_commit(root_hash);
+        }
+        assert!(account_data.checks_root_on_load().unwrap());
+        IbcAccountData::from_bytes_verified(&account_data.to_bytes().unwrap()).unwrap();
+
+        // A root other than the one recorded at the last commit fails the load
+        let mut tampered = IbcAccountData::from_bytes(&account_data.to_bytes().unwrap()).unwrap();
+        tampered.metadata.root_hash = [0xff; 32];
+        IbcAccountData::from_bytes_verified(&tampered.to_bytes().unwrap()).unwrap_err();
+
+        // Swapped leaves leave the root node as it was, so only the deep check
+        // catches them
+        account_data.store.swap_leaf_nodes();
+        let data = account_data.to_bytes().unwrap();
+        let decoded = IbcAccountData::from_bytes(&data).unwrap();
+        decoded.verify_latest_root().unwrap();
+        decoded.verify_latest_root_deep().unwrap_err();
+    }
+
+    #[test]
+    fn root_check_on_load_can_be_skipped() {
+        let account_data = account_data();
+        {
+            let mut state = IbcState::new(&account_data.store, 8);
+            state.set(
+                &ChainParamsPath,
+                ChainParams {
+                    skip_root_check_on_load: true,
+                    ..ChainParams::default()
+                },
+            );
+            state.commit().unwrap();
+        }
+        assert!(!account_data.checks_root_on_load().unwrap());
+    }
+}
@@ -1,8 +1,216 @@
-use serde::{Deserialize, Serialize};
+use {
+    core::hash::Hasher as _,
+    serde::{Deserialize, Serialize},
+    twox_hash::XxHash64,
+};
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
 pub struct IbcMetadata {
     pub client_id_counter: u64,
     pub connection_id_counter: u64,
     pub channel_id_counter: u64,
+    /// Number of commits that changed the root of the IBC store
+    pub write_sequence: u64,
+    /// Root of the latest state version as of the last commit
+    pub root_hash: [u8; 32],
+    /// [`IbcMetadata::compute_checksum`] as of the last commit. Nodes holding the
+    /// same IBC state agree on it, so it can be compared across RPC nodes without
+    /// fetching the store.
+    pub checksum: u64,
+    /// Revision number of this chain's IBC heights, set by `MsgSetRevisionNumber`
+    pub revision_number: u64,
+}
+
+impl IbcMetadata {
+    /// XXH64 of the root hash, the write sequence and the ID counters, followed by
+    /// the revision number once it is bumped.
+    #[must_use]
+    pub fn compute_checksum(&self) -> u64 {
+        let mut hasher = XxHash64::with_seed(0);
+        hasher.write(&self.root_hash);
+        for field in [
+            self.write_sequence,
+            self.client_id_counter,
+            self.connection_id_counter,
+            self.channel_id_counter,
+        ] {
+            hasher.write(&field.to_le_bytes());
+        }
+        // Left out in revision 0, so that checksums recorded before the revision
+        // number was added still match
+        if self.revision_number != 0 {
+            hasher.write(&self.revision_number.to_le_bytes());
+        }
+        hasher.finish()
+    }
+
+    /// Records a commit whose new version has `root_hash` and updates the
+    /// checksum. Commits that leave the root as it was do not advance the write
+    /// sequence, so that the checksum only changes along with committed state.
+    pub fn record_commit(&mut self, jmt::RootHash(root_hash): jmt::RootHash) {
+        if root_hash != self.root_hash {
+            self.write_sequence += 1;
+            self.root_hash = root_hash;
+        }
+        self.checksum = self.compute_checksum();
+    }
+
+    /// Whether a commit was recorded since the checksum was introduced. Metadata
+    /// written by earlier program versions has no checksum until the next commit.
+    #[must_use]
+    pub fn has_checksum(&self) -> bool {
+        self.write_sequence > 0
+    }
+}
+
+/// [`IbcMetadata`] as encoded before account data format version 4, which added
+/// the commit checksum.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LegacyIbcMetadata {
+    pub client_id_counter: u64,
+    pub connection_id_counter: u64,
+    pub channel_id_counter: u64,
+}
+
+impl From<LegacyIbcMetadata> for IbcMetadata {
+    fn from(
+        LegacyIbcMetadata {
+            client_id_counter,
+            connection_id_counter,
+            channel_id_counter,
+        }: LegacyIbcMetadata,
+    ) -> Self {
+        Self {
+            client_id_counter,
+            connection_id_counter,
+            channel_id_counter,
+            ..Self::default()
+        }
+    }
+}
+
+impl From<&IbcMetadata> for LegacyIbcMetadata {
+    fn from(metadata: &IbcMetadata) -> Self {
+        Self {
+            client_id_counter: metadata.client_id_counter,
+            connection_id_counter: metadata.connection_id_counter,
+            channel_id_counter: metadata.channel_id_counter,
+        }
+    }
+}
+
+/// [`IbcMetadata`] as encoded at account data format version 4, before version
+/// 5 added the revision number.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct IbcMetadataV4 {
+    pub client_id_counter: u64,
+    pub connection_id_counter: u64,
+    pub channel_id_counter: u64,
+    pub write_sequence: u64,
+    pub root_hash: [u8; 32],
+    pub checksum: u64,
+}
+
+impl From<IbcMetadataV4> for IbcMetadata {
+    fn from(
+        IbcMetadataV4 {
+            client_id_counter,
+            connection_id_counter,
+            channel_id_counter,
+            write_sequence,
+            root_hash,
+            checksum,
+        }: IbcMetadataV4,
+    ) -> Self {
+        Self {
+            client_id_counter,
+            connection_id_counter,
+            channel_id_counter,
+            write_sequence,
+            root_hash,
+            checksum,
+            ..Self::default()
+        }
+    }
+}
+
+impl From<&IbcMetadata> for IbcMetadataV4 {
+    fn from(metadata: &IbcMetadata) -> Self {
+        Self {
+            client_id_counter: metadata.client_id_counter,
+            connection_id_counter: metadata.connection_id_counter,
+            channel_id_counter: metadata.channel_id_counter,
+            write_sequence: metadata.write_sequence,
+            root_hash: metadata.root_hash,
+            checksum: metadata.checksum,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{internal_path::StateInitializedPath, IbcState, IbcStore},
+    };
+
+    #[test]
+    fn checksum_is_stable() {
+        let metadata = IbcMetadata {
+            client_id_counter: 3,
+            connection_id_counter: 2,
+            channel_id_counter: 1,
+            write_sequence: 5,
+            root_hash: [7; 32],
+            checksum: 0,
+            revision_number: 0,
+        };
+        // Nodes running different program versions must agree on the checksum, so
+        // its inputs and their order must not change
+        assert_eq!(metadata.compute_checksum(), 0x6c25_d278_b290_f7a3);
+
+        let bumped = IbcMetadata {
+            revision_number: 1,
+            ..metadata.clone()
+        };
+        assert_ne!(bumped.compute_checksum(), metadata.compute_checksum());
+    }
+
+    #[test]
+    fn checksum_changes_only_with_committed_state() {
+        let store = IbcStore::default();
+        let mut metadata = IbcMetadata::default();
+        assert!(!metadata.has_checksum());
+
+        let mut state = IbcState::new(&store, 1);
+        state.set(&StateInitializedPath, ());
+        metadata.record_commit(state.commit().unwrap());
+        assert!(metadata.has_checksum());
+        assert_eq!(metadata.checksum, metadata.compute_checksum());
+        let initialized = metadata.clone();
+
+        // Rewriting a value as it was leaves the root, and so the checksum, as is
+        let mut state = IbcState::new(&store, 2);
+        state.set(&StateInitializedPath, ());
+        metadata.record_commit(state.commit().unwrap());
+        assert_eq!(metadata, initialized);
+
+        let mut state = IbcState::new(&store, 3);
+        state.remove(&StateInitializedPath);
+        metadata.record_commit(state.commit().unwrap());
+        assert_eq!(metadata.write_sequence, 2);
+        assert_ne!(metadata.checksum, initialized.checksum);
+
+        // Restoring an earlier root is still a new write
+        let mut state = IbcState::new(&store, 4);
+        state.set(&StateInitializedPath, ());
+        metadata.record_commit(state.commit().unwrap());
+        assert_eq!(metadata.root_hash, initialized.root_hash);
+        assert_ne!(metadata.checksum, initialized.checksum);
+
+        let checksum = metadata.checksum;
+        metadata.client_id_counter += 1;
+        metadata.record_commit(jmt::RootHash(metadata.root_hash));
+        assert_ne!(metadata.checksum, checksum);
+    }
 }
@@ -1,25 +1,56 @@
 use {
-    crate::IbcStore,
+    crate::{internal_path::ChainParamsPath, value_compression, IbcStore},
     anyhow::anyhow,
     core::{
         fmt::{self, Debug},
         mem,
     },
+    eclipse_ibc_extra_types::ChainParams,
     eclipse_ibc_known_path::KnownPath,
     eclipse_ibc_known_proto::KnownProto,
+    eclipse_ibc_light_client::eclipse_chain,
     ibc::core::ics23_commitment::commitment::CommitmentRoot,
+    ibc_proto::google::protobuf,
     ics23::ExistenceProof,
     jmt::{storage::TreeWriter, Sha256Jmt},
     sha2::Sha256,
     solana_sdk::clock::Slot,
-    std::collections::BTreeMap,
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, BTreeSet},
+    },
 };
 
+/// An uncommitted change to a key's value, along with the path that hashes to the
+/// key, which is recorded as its preimage on commit.
+#[derive(Clone, Debug)]
+struct PendingChange {
+    path: String,
+    value: Option<Vec<u8>>,
+}
+
+/// A path whose value differs between two IBC states, along with its encoded
+/// value in each, if it has one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathChange {
+    pub path: String,
+    pub old_value: Option<Vec<u8>>,
+    pub new_value: Option<Vec<u8>>,
+}
+
+/// Whether a pending change sets or removes the value at its path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Set,
+    Remove,
+}
+
 pub struct IbcState<'a> {
     state_jmt: Sha256Jmt<'a, IbcStore>,
     state_store: &'a IbcStore,
-    pending_changes: BTreeMap<jmt::KeyHash, Option<Vec<u8>>>,
+    pending_changes: BTreeMap<jmt::KeyHash, PendingChange>,
     version: jmt::Version,
+    compress_values_above: Option<u64>,
 }
 
 impl Debug for IbcState<'_> {
@@ -29,10 +60,18 @@ impl Debug for IbcState<'_> {
             .field("state_store", &"<opaque>")
             .field("pending_changes", &self.pending_changes)
             .field("version", &self.version)
+            .field("compress_values_above", &self.compress_values_above)
             .finish()
     }
 }
 
+/// Returns the `Any` that `any.value` encodes, if it is one with the same type URL.
+fn nested_any(any: &protobuf::Any) -> Option<protobuf::Any> {
+    <protobuf::Any as prost::Message>::decode(&*any.value)
+        .ok()
+        .filter(|nested_any| nested_any.type_url == any.type_url)
+}
+
 impl<'a> IbcState<'a> {
     #[must_use]
     pub fn new(state_store: &'a IbcStore, slot: Slot) -> Self {
@@ -42,9 +81,18 @@ impl<'a> IbcState<'a> {
             pending_changes: BTreeMap::new(),
             // Slots map directly to versions
             version: slot,
+            compress_values_above: None,
         }
     }
 
+    /// Stores values set from now on at internal paths compressed if their
+    /// encodings are larger than `threshold` bytes, or none of them if it is
+    /// `None`. Values at ICS-24 paths are always stored as they are, and stored
+    /// values read back the same either way.
+    pub fn set_compress_values_above(&mut self, threshold: Option<u64>) {
+        self.compress_values_above = threshold;
+    }
+
     pub fn get_root_option(&self, slot: Slot) -> anyhow::Result<Option<CommitmentRoot>> {
         Ok(self
             .state_jmt
@@ -52,6 +100,14 @@ impl<'a> IbcState<'a> {
             .map(|jmt::RootHash(root_hash)| CommitmentRoot::from_bytes(&root_hash)))
     }
 
+    /// Root that headers and consensus states of this chain commit to at `slot`,
+    /// which proofs wrapped by `eclipse_chain::merkle_proof` verify against.
+    pub fn get_commitment_root_option(&self, slot: Slot) -> anyhow::Result<Option<CommitmentRoot>> {
+        Ok(self
+            .get_root_option(slot)?
+            .map(|state_root| eclipse_chain::commitment_root(&state_root)))
+    }
+
     pub fn get<K>(&self, key: &K) -> anyhow::Result<Option<K::Value>>
     where
         K: KnownPath,
@@ -59,6 +115,33 @@ impl<'a> IbcState<'a> {
         self.get_with_decode(key, |value| KnownProto::decode(value))
     }
 
+    /// The value at `key`, or the default value if there is none.
+    pub fn get_or_default<K>(&self, key: &K) -> anyhow::Result<K::Value>
+    where
+        K: KnownPath,
+        K::Value: Default,
+    {
+        Ok(self.get(key)?.unwrap_or_default())
+    }
+
+    /// Whether there is a value at `key`, counting changes that are not yet
+    /// committed. The value is not read, let alone decoded, so a value that
+    /// would fail to decode still counts.
+    pub fn contains<K>(&self, key: &K) -> anyhow::Result<bool>
+    where
+        K: KnownPath,
+    {
+        Ok(self.contains_path(&key.to_string()))
+    }
+
+    fn contains_path(&self, path: &str) -> bool {
+        let key_hash = jmt::KeyHash::with::<Sha256>(path);
+        match self.pending_changes.get(&key_hash) {
+            Some(pending_change) => pending_change.value.is_some(),
+            None => self.state_store.has_value(self.version, key_hash),
+        }
+    }
+
     pub fn get_raw<K>(&self, key: &K) -> anyhow::Result<Option<<K::Value as KnownProto>::Raw>>
     where
         K: KnownPath,
@@ -66,6 +149,37 @@ impl<'a> IbcState<'a> {
         self.get_with_decode(key, |value| prost::Message::decode(value))
     }
 
+    /// Reads an `Any` stored with [`IbcState::set_any`]. Values that were stored
+    /// wrapped in a second `Any` with the same type URL, which Tendermint client and
+    /// consensus states used to be, are unwrapped.
+    pub fn get_any<K>(&self, key: &K) -> anyhow::Result<Option<protobuf::Any>>
+    where
+        K: KnownPath<Value = protobuf::Any>,
+    {
+        Ok(self.get(key)?.map(|any| match nested_any(&any) {
+            Some(nested_any) => nested_any,
+            None => any,
+        }))
+    }
+
+    /// Stores an `Any` such as a client or consensus state. The stored bytes are
+    /// exactly `Any::encode_to_vec`, which is what counterparties verify proofs
+    /// against, so `any.value` must be the encoding of the message itself. An `Any`
+    /// whose value is another `Any` with the same type URL is rejected.
+    pub fn set_any<K>(&mut self, key: &K, any: protobuf::Any) -> anyhow::Result<()>
+    where
+        K: KnownPath<Value = protobuf::Any>,
+    {
+        if nested_any(&any).is_some() {
+            return Err(anyhow!(
+                "{} value at {key} is wrapped in a second Any",
+                any.type_url
+            ));
+        }
+        self.set(key, any);
+        Ok(())
+    }
+
     fn get_with_decode<K, V, E>(
         &self,
         key: &K,
@@ -75,19 +189,98 @@ impl<'a> IbcState<'a> {
         K: KnownPath,
         anyhow::Error: From<E>,
     {
-        let key_hash = jmt::KeyHash::with::<Sha256>(key.to_string());
-        if let Some(owned_value) = self.pending_changes.get(&key_hash) {
-            return Ok(owned_value
-                .as_ref()
-                .map(|value| decode(value))
-                .transpose()?);
+        let path = key.to_string();
+        match self.get_stored(&path)? {
+            None => Ok(None),
+            Some(stored) => {
+                let value = value_compression::decompress_value(&path, stored)?;
+                Ok(Some(decode(&value)?))
+            }
         }
+    }
 
-        Ok(self
-            .state_jmt
-            .get(key_hash, self.version)?
-            .map(|owned_value| decode(&owned_value))
-            .transpose()?)
+    /// The encoded value at `path`, decompressed if it is stored compressed.
+    fn get_encoded(&self, path: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        self.get_stored(path)?
+            .map(|stored| value_compression::decompress_value(path, stored).map(Cow::into_owned))
+            .transpose()
+    }
+
+    /// The bytes stored at `path`, which proofs of it are over. Pending values
+    /// are borrowed rather than copied.
+    fn get_stored(&self, path: &str) -> anyhow::Result<Option<Cow<'_, [u8]>>> {
+        let key_hash = jmt::KeyHash::with::<Sha256>(path);
+        if let Some(pending_change) = self.pending_changes.get(&key_hash) {
+            return Ok(pending_change.value.as_deref().map(Cow::Borrowed));
+        }
+        Ok(self.state_jmt.get(key_hash, self.version)?.map(Cow::Owned))
+    }
+
+    /// Paths starting with `prefix` that have a value at this state's version.
+    /// Only paths written since the store started recording preimages are known.
+    pub fn paths_with_prefix(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        let mut paths = self
+            .state_store
+            .preimages_with_prefix(prefix)?
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        paths.extend(
+            self.pending_changes
+                .values()
+                .filter(|pending_change| pending_change.path.starts_with(prefix))
+                .map(|pending_change| pending_change.path.clone()),
+        );
+
+        Ok(paths
+            .into_iter()
+            .filter(|path| self.contains_path(path))
+            .collect())
+    }
+
+    /// Like [`IbcState::paths_with_prefix`], along with each path's value decoded as
+    /// `V`. Every path under `prefix` must hold a `V`, so the prefix should be as
+    /// specific as e.g. `connections/`.
+    pub fn iter_paths<V>(&self, prefix: &str) -> anyhow::Result<Vec<(String, V)>>
+    where
+        V: KnownProto,
+    {
+        self.paths_with_prefix(prefix)?
+            .into_iter()
+            .map(|path| {
+                let value = self
+                    .get_encoded(&path)?
+                    .ok_or_else(|| anyhow!("Key {path} does not exist"))?;
+                let value = V::decode(&*value)
+                    .map_err(|err| err.context(format!("failed to decode value at {path}")))?;
+                Ok((path, value))
+            })
+            .collect()
+    }
+
+    /// Paths whose values differ between this state and `newer`, which may be a
+    /// view of another store, such as a later snapshot of the storage account.
+    /// Like [`IbcState::paths_with_prefix`], only paths whose preimages either
+    /// store recorded are compared.
+    pub fn diff(&self, newer: &IbcState<'_>) -> anyhow::Result<Vec<PathChange>> {
+        let mut paths = self
+            .paths_with_prefix("")?
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        paths.extend(newer.paths_with_prefix("")?);
+
+        let mut changes = vec![];
+        for path in paths {
+            let old_value = self.get_encoded(&path)?;
+            let new_value = newer.get_encoded(&path)?;
+            if old_value != new_value {
+                changes.push(PathChange {
+                    path,
+                    old_value,
+                    new_value,
+                });
+            }
+        }
+        Ok(changes)
     }
 
     pub fn get_proof<K>(&self, key: &K) -> anyhow::Result<ExistenceProof>
@@ -104,13 +297,19 @@ impl<'a> IbcState<'a> {
             .get_with_ics23_proof(key.to_string().as_bytes().to_vec(), key_version)
     }
 
+    /// Stores `value` at `key`, compressed if it is at an internal path and larger
+    /// than the threshold set with [`IbcState::set_compress_values_above`].
     pub fn set<K>(&mut self, key: &K, value: K::Value)
     where
         K: KnownPath,
     {
-        let key_hash = jmt::KeyHash::with::<Sha256>(key.to_string());
-        self.pending_changes
-            .insert(key_hash, Some(KnownProto::encode(value)));
+        let path = key.to_string();
+        let value = value_compression::compress_value(
+            &path,
+            KnownProto::encode(value),
+            self.compress_values_above,
+        );
+        self.write_raw_entry(&path, Some(value));
     }
 
     pub fn update<K>(&mut self, key: &K, f: impl FnOnce(&mut K::Value)) -> anyhow::Result<()>
@@ -118,7 +317,7 @@ impl<'a> IbcState<'a> {
         K: KnownPath,
         K::Value: Default,
     {
-        let mut value = self.get(key)?.unwrap_or_default();
+        let mut value = self.get_or_default(key)?;
         f(&mut value);
         self.set(key, value);
         Ok(())
@@ -128,16 +327,141 @@ impl<'a> IbcState<'a> {
     where
         K: KnownPath,
     {
-        let key_hash = jmt::KeyHash::with::<Sha256>(key.to_string());
-        self.pending_changes.insert(key_hash, None);
+        self.write_raw_entry(&key.to_string(), None);
     }
 
-    pub fn commit(&mut self) -> anyhow::Result<()> {
-        let pending_changes = mem::take(&mut self.pending_changes);
-        let (_root_hash, jmt::storage::TreeUpdateBatch { node_batch, .. }) = self
-            .state_jmt
-            .put_value_set(pending_changes, self.version)?;
+    /// The already-encoded value at an arbitrary path, decompressed if it is
+    /// stored compressed, as [`IbcState::write_raw_entry`] takes it.
+    pub fn read_raw_entry(&self, path: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        self.get_encoded(path)
+    }
+
+    /// Sets (or removes, if `value` is `None`) the already-encoded value at an
+    /// arbitrary path, which is stored uncompressed. Only meant for admin
+    /// operations that operate on raw entries.
+    pub fn write_raw_entry(&mut self, path: &str, value: Option<Vec<u8>>) {
+        let key_hash = jmt::KeyHash::with::<Sha256>(path);
+        self.pending_changes.insert(
+            key_hash,
+            PendingChange {
+                path: path.to_owned(),
+                value,
+            },
+        );
+    }
+
+    /// Moves the pending changes of `other`, a view of the same store, into this
+    /// state so that they are committed along with its own. Changes already
+    /// pending here for the same keys are replaced.
+    pub fn absorb_pending_changes(&mut self, other: &mut IbcState<'_>) {
+        self.pending_changes.append(&mut other.pending_changes);
+    }
+
+    /// The changes that [`IbcState::commit`] would write, in key hash order: the
+    /// path of each changed key, whether its value is set or removed, and the
+    /// length of the bytes to be stored, which is zero for removals. Every
+    /// pending change carries its path, so no key hash stands in for one.
+    #[must_use]
+    pub fn pending_summary(&self) -> Vec<(String, ChangeKind, usize)> {
+        self.pending_changes
+            .values()
+            .map(|PendingChange { path, value }| match value {
+                Some(value) => (path.clone(), ChangeKind::Set, value.len()),
+                None => (path.clone(), ChangeKind::Remove, 0),
+            })
+            .collect()
+    }
+
+    /// Writes the pending changes as a new version, recording the path of every
+    /// changed key as its preimage. Preimages of removed keys are kept, since
+    /// older versions and non-membership lookups still refer to them. Returns the
+    /// root of the new version.
+    pub fn commit(&mut self) -> anyhow::Result<jmt::RootHash> {
+        let mut paths = Vec::with_capacity(self.pending_changes.len());
+        let value_set = mem::take(&mut self.pending_changes)
+            .into_iter()
+            .map(|(key_hash, PendingChange { path, value })| {
+                paths.push(path);
+                (key_hash, value)
+            })
+            .collect::<Vec<_>>();
+        let (
+            root_hash,
+            jmt::storage::TreeUpdateBatch {
+                node_batch,
+                stale_node_index_batch,
+                ..
+            },
+        ) = self.state_jmt.put_value_set(value_set, self.version)?;
         self.state_store.write_node_batch(&node_batch)?;
-        Ok(())
+        self.state_store.record_stale_nodes(&stale_node_index_batch);
+        self.state_store.insert_preimages(paths)?;
+        Ok(root_hash)
+    }
+}
+
+/// Reading [`ChainParams`] from the IBC state, which the params type itself cannot
+/// depend on.
+pub trait LoadChainParams: Sized {
+    /// Reads the stored chain params, or the defaults if none are stored yet.
+    /// Stored params that fail to decode are an error rather than the defaults.
+    fn load_or_default(ibc_state: &IbcState<'_>) -> anyhow::Result<Self>;
+}
+
+impl LoadChainParams for ChainParams {
+    fn load_or_default(ibc_state: &IbcState<'_>) -> anyhow::Result<Self> {
+        ibc_state.get_or_default(&ChainParamsPath)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::internal_path::{InflightPacketsPath, LegacyTypeUrlCountPath},
+        ibc::core::ics24_host::identifier::{ChannelId, PortId},
+    };
+
+    #[test]
+    fn contains_counts_pending_insertions_and_removals() {
+        let store = IbcStore::default();
+        let committed_path = LegacyTypeUrlCountPath;
+        let pending_path = InflightPacketsPath(PortId::transfer(), ChannelId::new(0));
+        let mut state = IbcState::new(&store, 0);
+        state.set(&committed_path, 3);
+        state.commit().unwrap();
+
+        let mut state = IbcState::new(&store, 1);
+        assert!(state.contains(&committed_path).unwrap());
+        assert!(!state.contains(&pending_path).unwrap());
+
+        state.set(&pending_path, 1);
+        state.remove(&committed_path);
+        assert!(state.contains(&pending_path).unwrap());
+        assert!(!state.contains(&committed_path).unwrap());
+        assert_eq!(state.get_or_default(&committed_path).unwrap(), 0);
+        assert_eq!(
+            state.paths_with_prefix("internal/").unwrap(),
+            [pending_path.to_string()]
+        );
+
+        // The committed version is untouched until the changes are committed
+        assert!(IbcState::new(&store, 1).contains(&committed_path).unwrap());
+        state.commit().unwrap();
+        let state = IbcState::new(&store, 1);
+        assert!(state.contains(&pending_path).unwrap());
+        assert!(!state.contains(&committed_path).unwrap());
+        // Earlier versions still hold the removed value
+        assert!(IbcState::new(&store, 0).contains(&committed_path).unwrap());
+    }
+
+    #[test]
+    fn update_reads_pending_values() {
+        let store = IbcStore::default();
+        let path = LegacyTypeUrlCountPath;
+        let mut state = IbcState::new(&store, 0);
+        state.update(&path, |count| *count += 1).unwrap();
+        state.update(&path, |count| *count += 1).unwrap();
+        assert_eq!(state.get(&path).unwrap(), Some(2));
     }
 }
@@ -1,11 +1,17 @@
 use {
+    crate::ImplausibleAccountData,
     anyhow::{anyhow, bail},
     core::fmt::Debug,
-    jmt::storage::{TreeReader, TreeWriter},
+    jmt::{
+        storage::{TreeReader, TreeWriter},
+        Sha256Jmt,
+    },
     serde::{Deserialize, Serialize},
+    sha2::Sha256,
     std::{
-        collections::{BTreeMap, HashMap},
-        sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+        collections::{BTreeMap, BTreeSet, HashMap},
+        ops::Bound::{Included, Unbounded},
+        sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
     },
 };
 
@@ -15,6 +21,26 @@ pub struct InnerStore {
     nodes: BTreeMap<jmt::storage::NodeKey, jmt::storage::Node>,
     value_history: HashMap<jmt::KeyHash, BTreeMap<jmt::Version, Option<jmt::OwnedValue>>>,
     versions: Vec<jmt::Version>,
+    /// Paths whose key hashes have been written, so that keys can be listed by
+    /// path. Stored in a segment of its own after the store, so that the store's
+    /// encoding stays readable by older account data formats.
+    #[serde(skip)]
+    preimages: BTreeSet<String>,
+    /// Index of `preimages` by key hash.
+    #[serde(skip)]
+    paths_by_key_hash: HashMap<jmt::KeyHash, String>,
+    /// The leaf with the greatest node key, kept up to date as nodes are written
+    /// so that `get_rightmost_leaf` does not scan every node. `None` until it is
+    /// first needed after the store is decoded or pruned, when it is found by a
+    /// single scan.
+    #[serde(skip)]
+    rightmost_leaf: Option<Option<(jmt::storage::NodeKey, jmt::storage::LeafNode)>>,
+    /// Node keys by the version since which they are no longer part of the tree,
+    /// as jmt reports them at each commit, so that pruning finds the nodes only
+    /// older versions use without walking the tree. Stored in a segment of its
+    /// own after the event journal.
+    #[serde(skip)]
+    stale_nodes: BTreeMap<jmt::Version, Vec<jmt::storage::NodeKey>>,
 }
 
 mod store_nodes {
@@ -92,12 +118,101 @@ impl InnerStore {
         self.versions.last().copied()
     }
 
+    pub fn versions(&self) -> &[jmt::Version] {
+        &self.versions
+    }
+
     pub fn find_version(&self, max_version: jmt::Version) -> Option<jmt::Version> {
         let first_version_past = self
             .versions
             .partition_point(|&version| version <= max_version);
         (first_version_past > 0).then(|| self.versions[first_version_past - 1])
     }
+
+    /// Finds the leaf with the greatest node key by scanning the nodes from the
+    /// end.
+    fn scan_rightmost_leaf(&self) -> Option<(jmt::storage::NodeKey, jmt::storage::LeafNode)> {
+        self.nodes
+            .iter()
+            .rev()
+            .find_map(|(node_key, node)| match node {
+                jmt::storage::Node::Leaf(leaf_node) => Some((node_key.clone(), leaf_node.clone())),
+                _ => None,
+            })
+    }
+
+    /// Updates the cached rightmost leaf for `node` having been written at
+    /// `node_key`. Leaves written at a newer version have greater node keys, so
+    /// they supersede the cached one.
+    fn track_rightmost_leaf(
+        &mut self,
+        node_key: &jmt::storage::NodeKey,
+        node: &jmt::storage::Node,
+    ) {
+        let (is_greater, is_cached_key) = match &self.rightmost_leaf {
+            // Not known yet, so there is nothing to keep up to date
+            None => return,
+            Some(None) => (true, false),
+            Some(Some((rightmost_key, _))) => (node_key > rightmost_key, node_key == rightmost_key),
+        };
+        match node {
+            jmt::storage::Node::Leaf(leaf_node) if is_greater || is_cached_key => {
+                self.rightmost_leaf = Some(Some((node_key.clone(), leaf_node.clone())));
+            }
+            // The cached leaf was overwritten by another kind of node
+            _ if is_cached_key => {
+                self.rightmost_leaf = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Oldest version that pruning the versions older than `min_version` keeps:
+    /// the one in effect at `min_version`, or the oldest if none is.
+    fn first_kept_version(&self, min_version: jmt::Version) -> Option<jmt::Version> {
+        self.find_version(min_version)
+            .or_else(|| self.versions.first().copied())
+    }
+
+    /// Number of stale nodes that no version from `first_kept_version` on uses.
+    fn prunable_node_count(&self, first_kept_version: jmt::Version) -> usize {
+        self.stale_nodes
+            .range(..=first_kept_version)
+            .map(|(_, node_keys)| node_keys.len())
+            .sum()
+    }
+
+    /// Drops the values of `key_hash` replaced by the one in effect at `version`,
+    /// which no version from `version` on reads.
+    fn prune_value_history(&mut self, key_hash: jmt::KeyHash, version: jmt::Version) {
+        if let Some(version_history) = self.value_history.get_mut(&key_hash) {
+            if let Some((&in_effect, _)) = version_history.range(..=version).next_back() {
+                *version_history = version_history.split_off(&in_effect);
+            }
+        }
+    }
+}
+
+/// How far a call of [`IbcStore::prune_versions_older_than`] got.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StorePruning {
+    pub pruned_versions: usize,
+    pub pruned_nodes: usize,
+    /// Stale nodes of the dropped versions left for later calls
+    pub remaining_nodes: usize,
+}
+
+/// Sizes of an [`IbcStore`], for operators to watch it grow.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct IbcStoreStats {
+    pub node_count: usize,
+    /// Values written across all keys and versions, including removals
+    pub value_history_entry_count: usize,
+    pub preimage_count: usize,
+    pub version_count: usize,
+    /// Estimate of the bytes the store and its preimages take in the storage
+    /// account
+    pub estimated_size: u64,
 }
 
 #[derive(Debug, Default, Deserialize, Serialize)]
@@ -107,12 +222,19 @@ pub struct IbcStore {
 }
 
 impl IbcStore {
-    pub fn read(&self) -> anyhow::Result<RwLockReadGuard<'_, InnerStore>> {
-        self.inner.read().map_err(|err| anyhow!("{err}"))
+    /// Locks the store for reading. A panic while the store was locked does not
+    /// poison it. Writes that fail part way, with an error or a panic, already
+    /// leave the store partially written, and the instruction that made them fails
+    /// and discards the store, so poisoning would only keep the rest of the
+    /// process from reading it.
+    pub fn read(&self) -> RwLockReadGuard<'_, InnerStore> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
     }
 
-    fn write(&self) -> anyhow::Result<RwLockWriteGuard<'_, InnerStore>> {
-        self.inner.write().map_err(|err| anyhow!("{err}"))
+    /// Locks the store for writing, recovering from poisoning as
+    /// [`IbcStore::read`] does.
+    fn write(&self) -> RwLockWriteGuard<'_, InnerStore> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
     }
 
     pub fn find_key_version(
@@ -121,7 +243,7 @@ impl IbcStore {
         key_hash: jmt::KeyHash,
     ) -> anyhow::Result<Option<jmt::Version>> {
         Ok(self
-            .read()?
+            .read()
             .value_history
             .get(&key_hash)
             .and_then(|version_history| {
@@ -131,6 +253,408 @@ impl IbcStore {
                     .map(|(&version, _)| version)
             }))
     }
+
+    /// Whether `key_hash` has a value as of `max_version`, found without copying
+    /// the value.
+    pub fn has_value(&self, max_version: jmt::Version, key_hash: jmt::KeyHash) -> bool {
+        self.read()
+            .value_history
+            .get(&key_hash)
+            .and_then(|version_history| version_history.range(..=max_version).next_back())
+            .map_or(false, |(_, value)| value.is_some())
+    }
+
+    /// Records `path` as the preimage of its key hash. Paths are kept after their
+    /// value is removed, since older versions may still hold a value.
+    pub fn insert_preimage(&self, path: String) -> anyhow::Result<()> {
+        self.insert_preimages([path])
+    }
+
+    /// Records each of `paths` as the preimage of its key hash, skipping those
+    /// already recorded. The store is only locked for writing if there are new
+    /// ones.
+    pub fn insert_preimages(&self, paths: impl IntoIterator<Item = String>) -> anyhow::Result<()> {
+        let new_paths = {
+            let inner_store = self.read();
+            paths
+                .into_iter()
+                .filter(|path| !inner_store.preimages.contains(path))
+                .collect::<Vec<_>>()
+        };
+        if new_paths.is_empty() {
+            return Ok(());
+        }
+
+        let mut inner_store = self.write();
+        for path in new_paths {
+            inner_store
+                .paths_by_key_hash
+                .insert(jmt::KeyHash::with::<Sha256>(&path), path.clone());
+            inner_store.preimages.insert(path);
+        }
+        Ok(())
+    }
+
+    pub fn preimages(&self) -> anyhow::Result<BTreeSet<String>> {
+        Ok(self.read().preimages.clone())
+    }
+
+    pub(crate) fn set_preimages(&self, preimages: BTreeSet<String>) -> anyhow::Result<()> {
+        let mut inner_store = self.write();
+        inner_store.paths_by_key_hash = preimages
+            .iter()
+            .map(|path| (jmt::KeyHash::with::<Sha256>(path), path.clone()))
+            .collect();
+        inner_store.preimages = preimages;
+        Ok(())
+    }
+
+    /// Records the nodes that a commit left out of the tree from then on.
+    pub fn record_stale_nodes(&self, stale_node_index_batch: &jmt::storage::StaleNodeIndexBatch) {
+        let mut stale_nodes = BTreeMap::<_, Vec<_>>::new();
+        for stale_node_index in stale_node_index_batch {
+            stale_nodes
+                .entry(stale_node_index.stale_since_version)
+                .or_default()
+                .push(stale_node_index.node_key.clone());
+        }
+        let mut inner_store = self.write();
+        for (stale_since_version, mut node_keys) in stale_nodes {
+            // The batch is a hash set, so its order is not the same on every node
+            node_keys.sort();
+            inner_store
+                .stale_nodes
+                .entry(stale_since_version)
+                .or_default()
+                .extend(node_keys);
+        }
+    }
+
+    /// The stale node index with its node keys encoded, as it is stored.
+    pub(crate) fn encoded_stale_nodes(
+        &self,
+    ) -> anyhow::Result<BTreeMap<jmt::Version, Vec<Vec<u8>>>> {
+        self.read()
+            .stale_nodes
+            .iter()
+            .map(|(&stale_since_version, node_keys)| {
+                let node_keys = node_keys
+                    .iter()
+                    .map(|node_key| {
+                        node_key
+                            .encode()
+                            .map_err(|err| anyhow!("failed to encode node key: {err:?}"))
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                Ok((stale_since_version, node_keys))
+            })
+            .collect()
+    }
+
+    pub(crate) fn set_encoded_stale_nodes(
+        &self,
+        encoded_stale_nodes: BTreeMap<jmt::Version, Vec<Vec<u8>>>,
+    ) -> anyhow::Result<()> {
+        let stale_nodes = encoded_stale_nodes
+            .into_iter()
+            .map(|(stale_since_version, node_keys)| {
+                let node_keys = node_keys
+                    .iter()
+                    .map(|node_key| {
+                        jmt::storage::NodeKey::decode(node_key)
+                            .map_err(|err| anyhow!("failed to decode node key: {err}"))
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                Ok((stale_since_version, node_keys))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        self.write().stale_nodes = stale_nodes;
+        Ok(())
+    }
+
+    /// Rebuilds the stale node index of a store written before it was recorded.
+    /// Every write below a node rewrites the node at its nibble path, so a node is
+    /// stale since the next version with a node at the same path. Nodes whose path
+    /// left the tree without being rewritten are not found, and stay in the store.
+    pub(crate) fn index_stale_nodes(&self) {
+        let mut inner_store = self.write();
+        let mut newer_versions = HashMap::new();
+        let mut stale_nodes = BTreeMap::<_, Vec<_>>::new();
+        // Node keys order by version first, so each path's newer nodes come first
+        for node_key in inner_store.nodes.keys().rev() {
+            if let Some(stale_since_version) =
+                newer_versions.insert(node_key.nibble_path().clone(), node_key.version())
+            {
+                stale_nodes
+                    .entry(stale_since_version)
+                    .or_default()
+                    .push(node_key.clone());
+            }
+        }
+        for node_keys in stale_nodes.values_mut() {
+            node_keys.sort();
+        }
+        inner_store.stale_nodes = stale_nodes;
+    }
+
+    /// The path that hashes to `key_hash`, if it has been recorded. This has the
+    /// shape of jmt's `HasPreimage::preimage`.
+    pub fn preimage(&self, key_hash: jmt::KeyHash) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self
+            .read()
+            .paths_by_key_hash
+            .get(&key_hash)
+            .map(|path| path.as_bytes().to_vec()))
+    }
+
+    /// Recorded paths starting with `prefix`, in order.
+    pub fn preimages_with_prefix(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .read()
+            .preimages
+            .range::<str, _>((Included(prefix), Unbounded))
+            .take_while(|path| path.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+
+    /// Recomputes the root at `version` from every key's value and the tree nodes
+    /// along its path, and checks it against the stored root node. This catches
+    /// store data that decodes fine but was partially written or tampered with.
+    pub fn verify_root(&self, version: jmt::Version) -> anyhow::Result<()> {
+        let key_hashes = self
+            .read()
+            .value_history
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+
+        let state_jmt = Sha256Jmt::new(self);
+        let root_hash = state_jmt.get_root_hash(version)?;
+        for key_hash in key_hashes {
+            let (value, proof) = state_jmt.get_with_proof(key_hash, version)?;
+            proof
+                .verify(root_hash, key_hash, value.as_ref())
+                .map_err(|err| {
+                    anyhow!("root at version {version} does not match key {key_hash:?}: {err}")
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Counts the store's entries and estimates its encoded size from the lengths
+    /// of its values and paths, without encoding the store. Only the tree nodes
+    /// are measured by encoding them, one at a time.
+    pub fn stats(&self) -> anyhow::Result<IbcStoreStats> {
+        // Bincode length prefix of a collection, string or byte vector
+        const LEN_PREFIX: u64 = 8;
+        const KEY_HASH_LEN: u64 = 32;
+        const VERSION_LEN: u64 = 8;
+        // Tag of an optional value
+        const OPTION_TAG_LEN: u64 = 1;
+
+        let inner_store = self.read();
+        let mut estimated_size = 0;
+
+        estimated_size += LEN_PREFIX;
+        for (node_key, node) in &inner_store.nodes {
+            let node_key_len = node_key
+                .encode()
+                .map_err(|err| anyhow!("failed to encode node key: {err:?}"))?
+                .len();
+            let node_len = node
+                .encode()
+                .map_err(|err| anyhow!("failed to encode node: {err:?}"))?
+                .len();
+            estimated_size += 2 * LEN_PREFIX + u64::try_from(node_key_len + node_len)?;
+        }
+
+        let mut value_history_entry_count = 0;
+        estimated_size += LEN_PREFIX;
+        for version_history in inner_store.value_history.values() {
+            value_history_entry_count += version_history.len();
+            estimated_size += KEY_HASH_LEN + LEN_PREFIX;
+            for value in version_history.values() {
+                estimated_size += VERSION_LEN + OPTION_TAG_LEN;
+                if let Some(value) = value {
+                    estimated_size += LEN_PREFIX + u64::try_from(value.len())?;
+                }
+            }
+        }
+
+        estimated_size += LEN_PREFIX + VERSION_LEN * u64::try_from(inner_store.versions.len())?;
+
+        estimated_size += LEN_PREFIX;
+        for path in &inner_store.preimages {
+            estimated_size += LEN_PREFIX + u64::try_from(path.len())?;
+        }
+
+        estimated_size += LEN_PREFIX;
+        for node_keys in inner_store.stale_nodes.values() {
+            estimated_size += VERSION_LEN + LEN_PREFIX;
+            for node_key in node_keys {
+                let node_key_len = node_key
+                    .encode()
+                    .map_err(|err| anyhow!("failed to encode node key: {err:?}"))?
+                    .len();
+                estimated_size += LEN_PREFIX + u64::try_from(node_key_len)?;
+            }
+        }
+
+        Ok(IbcStoreStats {
+            node_count: inner_store.nodes.len(),
+            value_history_entry_count,
+            preimage_count: inner_store.preimages.len(),
+            version_count: inner_store.versions.len(),
+            estimated_size,
+        })
+    }
+
+    /// Checks that the store holds no more entries than `data_len` bytes of
+    /// account data can encode, going by the fewest bytes each entry takes.
+    /// Decoding already fails on data that runs out, so this only guards against
+    /// a decoder that lets a declared length through.
+    pub(crate) fn check_entry_counts(&self, data_len: usize) -> anyhow::Result<()> {
+        // Length prefixes of the node key and node
+        const MIN_NODE_LEN: usize = 16;
+        // Key hash and the length prefix of its history
+        const MIN_KEY_HISTORY_LEN: usize = 32 + 8;
+        // Version and the tag of the optional value
+        const MIN_HISTORY_ENTRY_LEN: usize = 8 + 1;
+        const VERSION_LEN: usize = 8;
+        // Length prefix of the path
+        const MIN_PREIMAGE_LEN: usize = 8;
+        // Length prefix of the node key
+        const MIN_STALE_NODE_LEN: usize = 8;
+
+        let inner_store = self.read();
+        let history_entry_count = inner_store
+            .value_history
+            .values()
+            .map(BTreeMap::len)
+            .sum::<usize>();
+        for (name, count, min_len) in [
+            ("nodes", inner_store.nodes.len(), MIN_NODE_LEN),
+            ("keys", inner_store.value_history.len(), MIN_KEY_HISTORY_LEN),
+            (
+                "value history entries",
+                history_entry_count,
+                MIN_HISTORY_ENTRY_LEN,
+            ),
+            ("versions", inner_store.versions.len(), VERSION_LEN),
+            ("preimages", inner_store.preimages.len(), MIN_PREIMAGE_LEN),
+            (
+                "stale nodes",
+                inner_store.stale_nodes.values().map(Vec::len).sum(),
+                MIN_STALE_NODE_LEN,
+            ),
+        ] {
+            if count.saturating_mul(min_len) > data_len {
+                return Err(ImplausibleAccountData {
+                    description: format!(
+                        "{count} {name} take at least {min_len} bytes each, more than the \
+                         {data_len} bytes of account data"
+                    ),
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of stale nodes that pruning the versions older than `min_version`
+    /// would drop, across as many calls as it takes.
+    pub fn prunable_node_count(&self, min_version: jmt::Version) -> usize {
+        let inner_store = self.read();
+        inner_store
+            .first_kept_version(min_version)
+            .map_or(0, |first_kept_version| {
+                inner_store.prunable_node_count(first_kept_version)
+            })
+    }
+
+    /// Drops the versions older than `min_version`, keeping the version in effect
+    /// at `min_version` and the latest version. Kept versions are left as they
+    /// are, so they keep their roots and proofs.
+    ///
+    /// The tree nodes and values that only the dropped versions use are dropped
+    /// at most `max_nodes` at a time, in the order they went stale, so that the
+    /// work of a call stays bounded as the store grows. The stale node index is
+    /// the cursor: the nodes left over are dropped by later calls.
+    pub fn prune_versions_older_than(
+        &self,
+        min_version: jmt::Version,
+        max_nodes: usize,
+    ) -> anyhow::Result<StorePruning> {
+        let mut inner_store = self.write();
+        let first_kept_version = match inner_store.first_kept_version(min_version) {
+            Some(version) => version,
+            None => return Ok(StorePruning::default()),
+        };
+        let first_kept = inner_store
+            .versions
+            .partition_point(|&version| version < first_kept_version);
+        let pruned_versions = inner_store.versions.drain(..first_kept).count();
+
+        let mut pruned_nodes = 0;
+        while pruned_nodes < max_nodes {
+            let node_key = match inner_store.stale_nodes.first_entry() {
+                Some(mut entry) if *entry.key() <= first_kept_version => {
+                    let node_key = entry.get_mut().pop();
+                    if entry.get().is_empty() {
+                        entry.remove();
+                    }
+                    match node_key {
+                        Some(node_key) => node_key,
+                        None => continue,
+                    }
+                }
+                _ => break,
+            };
+            if let Some(jmt::storage::Node::Leaf(leaf_node)) = inner_store.nodes.remove(&node_key) {
+                // A stale leaf may only have moved, so its value is dropped only
+                // once a later one is in effect at the first kept version
+                inner_store.prune_value_history(leaf_node.key_hash(), first_kept_version);
+                if matches!(
+                    &inner_store.rightmost_leaf,
+                    Some(Some((rightmost_key, _))) if *rightmost_key == node_key
+                ) {
+                    inner_store.rightmost_leaf = None;
+                }
+            }
+            pruned_nodes += 1;
+        }
+
+        let remaining_nodes = inner_store.prunable_node_count(first_kept_version);
+        Ok(StorePruning {
+            pruned_versions,
+            pruned_nodes,
+            remaining_nodes,
+        })
+    }
+
+    /// Swaps the oldest leaf node with the newest leaf node for a different key,
+    /// leaving the store decodable but no longer consistent with its latest root.
+    #[cfg(test)]
+    pub(crate) fn swap_leaf_nodes(&self) {
+        let mut inner = self.write();
+        let leaves = inner
+            .nodes
+            .iter()
+            .filter(|(_, node)| matches!(node, jmt::storage::Node::Leaf(_)))
+            .map(|(node_key, node)| (node_key.clone(), node.clone()))
+            .collect::<Vec<_>>();
+        let (first_key, first_node) = leaves.first().unwrap().clone();
+        let (last_key, last_node) = leaves
+            .iter()
+            .rev()
+            .find(|(_, node)| *node != first_node)
+            .unwrap()
+            .clone();
+
+        inner.nodes.insert(first_key, last_node);
+        inner.nodes.insert(last_key, first_node);
+    }
 }
 
 impl TreeReader for IbcStore {
@@ -138,7 +662,7 @@ impl TreeReader for IbcStore {
         &self,
         node_key: &jmt::storage::NodeKey,
     ) -> anyhow::Result<Option<jmt::storage::Node>> {
-        Ok(self.read()?.nodes.get(node_key).cloned())
+        Ok(self.read().nodes.get(node_key).cloned())
     }
 
     fn get_value_option(
@@ -147,7 +671,7 @@ impl TreeReader for IbcStore {
         key_hash: jmt::KeyHash,
     ) -> anyhow::Result<Option<jmt::OwnedValue>> {
         Ok(self
-            .read()?
+            .read()
             .value_history
             .get(&key_hash)
             .and_then(|version_history| {
@@ -161,23 +685,22 @@ impl TreeReader for IbcStore {
     fn get_rightmost_leaf(
         &self,
     ) -> anyhow::Result<Option<(jmt::storage::NodeKey, jmt::storage::LeafNode)>> {
-        Ok(self
-            .read()?
-            .nodes
-            .iter()
-            .rev()
-            .find_map(|(node_key, node)| match node {
-                jmt::storage::Node::Leaf(leaf_node) => Some((node_key.clone(), leaf_node.clone())),
-                _ => None,
-            }))
+        if let Some(rightmost_leaf) = &self.read().rightmost_leaf {
+            return Ok(rightmost_leaf.clone());
+        }
+        let mut inner = self.write();
+        let rightmost_leaf = inner.scan_rightmost_leaf();
+        inner.rightmost_leaf = Some(rightmost_leaf.clone());
+        Ok(rightmost_leaf)
     }
 }
 
 impl TreeWriter for IbcStore {
     fn write_node_batch(&self, node_batch: &jmt::storage::NodeBatch) -> anyhow::Result<()> {
-        let mut inner = self.write()?;
+        let mut inner = self.write();
         for (node_key, node) in node_batch.nodes() {
             inner.nodes.insert(node_key.clone(), node.clone());
+            inner.track_rightmost_leaf(node_key, node);
         }
 
         for (&(version, key_hash), value) in node_batch.values() {
@@ -204,3 +727,342 @@ impl TreeWriter for IbcStore {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            internal_path::{ChainParamsPath, InflightPacketsPath, StateInitializedPath},
+            IbcState,
+        },
+        eclipse_ibc_extra_types::ChainParams,
+        ibc::core::ics24_host::identifier::{ChannelId, PortId},
+    };
+
+    /// Commits two keys at version 1, so that both leaves are in the latest tree.
+    fn store_with_two_leaves() -> IbcStore {
+        let store = IbcStore::default();
+        let mut state = IbcState::new(&store, 1);
+        state.set(&StateInitializedPath, ());
+        state.set(
+            &ChainParamsPath,
+            ChainParams {
+                frozen: true,
+                ..ChainParams::default()
+            },
+        );
+        state.commit().unwrap();
+        store
+    }
+
+    #[test]
+    fn store_stays_usable_after_a_panic_while_locked() {
+        let store = store_with_two_leaves();
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _inner_store = store.write();
+            panic!("panicking with the store locked for writing");
+        }));
+        assert!(panicked.is_err());
+        assert!(store.inner.is_poisoned());
+
+        let state = IbcState::new(&store, 1);
+        assert_eq!(state.get(&StateInitializedPath).unwrap(), Some(()));
+        let mut state = IbcState::new(&store, 2);
+        state.remove(&StateInitializedPath);
+        state.commit().unwrap();
+        assert_eq!(store.read().versions(), [1, 2]);
+    }
+
+    #[test]
+    fn committed_paths_are_recorded_as_preimages() {
+        let store = store_with_two_leaves();
+        let path = ChainParamsPath.to_string();
+        let key_hash = jmt::KeyHash::with::<Sha256>(&path);
+        assert_eq!(
+            store.preimage(key_hash).unwrap(),
+            Some(path.clone().into_bytes())
+        );
+
+        let state = IbcState::new(&store, 1);
+        let proof = state.get_proof(&ChainParamsPath).unwrap();
+        assert_eq!(proof.key, path.as_bytes());
+
+        // Removing the value keeps the preimage, and writing the same path again
+        // does not record it twice
+        let mut state = IbcState::new(&store, 2);
+        state.remove(&ChainParamsPath);
+        state.commit().unwrap();
+        assert_eq!(
+            store.preimage(key_hash).unwrap(),
+            Some(path.clone().into_bytes())
+        );
+        let mut state = IbcState::new(&store, 3);
+        state.set(&ChainParamsPath, ChainParams::default());
+        state.commit().unwrap();
+        assert_eq!(store.preimages_with_prefix(&path).unwrap(), [path]);
+
+        let unknown_key_hash = jmt::KeyHash::with::<Sha256>("clients/07-tendermint-0/clientState");
+        assert_eq!(store.preimage(unknown_key_hash).unwrap(), None);
+    }
+
+    #[test]
+    fn stats_estimate_the_encoded_size() {
+        let store = store_with_two_leaves();
+        for version in 2..=20 {
+            let mut state = IbcState::new(&store, version);
+            state.set(
+                &ChainParamsPath,
+                ChainParams {
+                    chain_name: Some(format!("chain-{version}")),
+                    ..ChainParams::default()
+                },
+            );
+            if version % 5 == 0 {
+                state.remove(&StateInitializedPath);
+            } else {
+                state.set(&StateInitializedPath, ());
+            }
+            state.commit().unwrap();
+        }
+
+        let stats = store.stats().unwrap();
+        assert_eq!(stats.version_count, 20);
+        assert_eq!(stats.preimage_count, 2);
+        {
+            let inner_store = store.read();
+            assert_eq!(stats.node_count, inner_store.nodes.len());
+            assert_eq!(
+                stats.value_history_entry_count,
+                inner_store
+                    .value_history
+                    .values()
+                    .map(BTreeMap::len)
+                    .sum::<usize>()
+            );
+        }
+
+        let actual_size = bincode::serialized_size(&store).unwrap()
+            + bincode::serialized_size(&store.preimages().unwrap()).unwrap();
+        let tolerance = actual_size / 20;
+        assert!(
+            stats.estimated_size.abs_diff(actual_size) <= tolerance,
+            "estimated {} bytes, encoded in {actual_size}",
+            stats.estimated_size
+        );
+    }
+
+    #[test]
+    fn rightmost_leaf_matches_a_scan_of_every_node() {
+        // Xorshift, so that the write sequences are random but reproducible
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next_random = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        let path = |key| InflightPacketsPath(PortId::transfer(), ChannelId::new(key));
+
+        let store = IbcStore::default();
+        assert_eq!(store.get_rightmost_leaf().unwrap(), None);
+        let mut live_keys = BTreeSet::new();
+        for version in 1..=200 {
+            let mut state = IbcState::new(&store, version);
+            for _ in 0..=next_random() % 8 {
+                let key = next_random() % 64;
+                if next_random() % 4 == 0 && live_keys.remove(&key) {
+                    state.remove(&path(key));
+                } else {
+                    live_keys.insert(key);
+                    state.set(&path(key), next_random());
+                }
+            }
+            state.commit().unwrap();
+
+            let expected = store.read().scan_rightmost_leaf();
+            assert_eq!(
+                store.get_rightmost_leaf().unwrap(),
+                expected,
+                "version {version}"
+            );
+            // Kept up to date by the writes rather than found by scanning again
+            assert!(store.read().rightmost_leaf.is_some());
+        }
+
+        // Decoded and pruned stores find it with a single scan
+        let decoded =
+            bincode::deserialize::<IbcStore>(&bincode::serialize(&store).unwrap()).unwrap();
+        assert!(decoded.read().rightmost_leaf.is_none());
+        assert_eq!(
+            decoded.get_rightmost_leaf().unwrap(),
+            store.get_rightmost_leaf().unwrap()
+        );
+        store.prune_versions_older_than(150, usize::MAX).unwrap();
+        let expected = store.read().scan_rightmost_leaf();
+        assert_eq!(store.get_rightmost_leaf().unwrap(), expected);
+    }
+
+    #[test]
+    fn intact_roots_verify() {
+        let store = store_with_two_leaves();
+        let mut state = IbcState::new(&store, 2);
+        state.remove(&ChainParamsPath);
+        state.commit().unwrap();
+
+        assert_eq!(store.read().versions(), [1, 2]);
+        for version in [1, 2] {
+            store.verify_root(version).unwrap();
+        }
+    }
+
+    #[test]
+    fn pruning_keeps_recent_versions_and_shrinks_the_store() {
+        let store = store_with_two_leaves();
+        for version in 2..=20 {
+            let mut state = IbcState::new(&store, version);
+            state.set(
+                &ChainParamsPath,
+                ChainParams {
+                    max_inflight_packets_per_channel: Some(version),
+                    ..ChainParams::default()
+                },
+            );
+            state.commit().unwrap();
+        }
+        let latest_root = IbcState::new(&store, 20).get_root_option(20).unwrap();
+        let size_before = bincode::serialized_size(&store).unwrap();
+
+        // Version 15 is in effect at 15 as well as at the later slots it covers
+        let pruning = store.prune_versions_older_than(15, usize::MAX).unwrap();
+        assert_eq!(pruning.pruned_versions, 14);
+        assert!(pruning.pruned_nodes > 0);
+        assert_eq!(pruning.remaining_nodes, 0);
+        assert_eq!(store.read().versions(), (15..=20).collect::<Vec<_>>());
+        assert!(bincode::serialized_size(&store).unwrap() < size_before);
+
+        let state = IbcState::new(&store, 20);
+        assert_eq!(state.get_root_option(20).unwrap(), latest_root);
+        let proof = state.get_proof(&ChainParamsPath).unwrap();
+        assert_eq!(proof.key, ChainParamsPath.to_string().as_bytes());
+        assert_eq!(state.get(&StateInitializedPath).unwrap(), Some(()));
+        for version in 15..=20 {
+            store.verify_root(version).unwrap();
+        }
+        assert_eq!(
+            IbcState::new(&store, 15)
+                .get(&ChainParamsPath)
+                .unwrap()
+                .unwrap()
+                .max_inflight_packets_per_channel,
+            Some(15),
+        );
+
+        // Pruning past the latest version still keeps it
+        let pruning = store.prune_versions_older_than(100, usize::MAX).unwrap();
+        assert_eq!(pruning.pruned_versions, 5);
+        assert_eq!(store.read().versions(), [20]);
+        store.verify_root(20).unwrap();
+        assert_eq!(
+            store.prune_versions_older_than(100, usize::MAX).unwrap(),
+            StorePruning::default()
+        );
+
+        let mut state = IbcState::new(&store, 21);
+        state.remove(&ChainParamsPath);
+        state.commit().unwrap();
+        store.verify_root(21).unwrap();
+    }
+
+    #[test]
+    fn pruning_keeps_versions_without_live_values() {
+        let store = IbcStore::default();
+        let mut state = IbcState::new(&store, 1);
+        state.set(&StateInitializedPath, ());
+        state.commit().unwrap();
+        let mut state = IbcState::new(&store, 2);
+        state.remove(&StateInitializedPath);
+        state.commit().unwrap();
+        let mut state = IbcState::new(&store, 3);
+        state.set(&ChainParamsPath, ChainParams::default());
+        state.commit().unwrap();
+        let roots = [2, 3].map(|version| {
+            IbcState::new(&store, version)
+                .get_root_option(version)
+                .unwrap()
+        });
+
+        let pruning = store.prune_versions_older_than(2, usize::MAX).unwrap();
+        assert_eq!(pruning.pruned_versions, 1);
+        assert_eq!(pruning.remaining_nodes, 0);
+        assert_eq!(store.read().versions(), [2, 3]);
+        for (version, root) in [2, 3].into_iter().zip(roots) {
+            assert_eq!(
+                IbcState::new(&store, version)
+                    .get_root_option(version)
+                    .unwrap(),
+                root
+            );
+            store.verify_root(version).unwrap();
+        }
+        assert_eq!(
+            IbcState::new(&store, 2).get(&StateInitializedPath).unwrap(),
+            None
+        );
+        assert!(IbcState::new(&store, 3)
+            .get(&ChainParamsPath)
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn pruning_resumes_where_the_last_batch_stopped() {
+        let store = store_with_two_leaves();
+        for version in 2..=10 {
+            let mut state = IbcState::new(&store, version);
+            state.set(
+                &ChainParamsPath,
+                ChainParams {
+                    max_inflight_packets_per_channel: Some(version),
+                    ..ChainParams::default()
+                },
+            );
+            state.commit().unwrap();
+        }
+        let node_count = store.stats().unwrap().node_count;
+        let prunable_node_count = store.prunable_node_count(10);
+        assert!(prunable_node_count > 2);
+
+        let pruning = store.prune_versions_older_than(10, 2).unwrap();
+        assert_eq!(pruning.pruned_versions, 9);
+        assert_eq!(pruning.pruned_nodes, 2);
+        assert_eq!(pruning.remaining_nodes, prunable_node_count - 2);
+        assert_eq!(store.read().versions(), [10]);
+        store.verify_root(10).unwrap();
+
+        let pruning = store.prune_versions_older_than(10, usize::MAX).unwrap();
+        assert_eq!(pruning.pruned_versions, 0);
+        assert_eq!(pruning.pruned_nodes, prunable_node_count - 2);
+        assert_eq!(pruning.remaining_nodes, 0);
+        assert_eq!(
+            store.stats().unwrap().node_count,
+            node_count - prunable_node_count
+        );
+        store.verify_root(10).unwrap();
+        assert_eq!(
+            IbcState::new(&store, 10)
+                .get(&ChainParamsPath)
+                .unwrap()
+                .unwrap()
+                .max_inflight_packets_per_channel,
+            Some(10),
+        );
+    }
+
+    #[test]
+    fn corrupted_node_is_detected() {
+        let store = store_with_two_leaves();
+        store.swap_leaf_nodes();
+        store.verify_root(1).unwrap_err();
+    }
+}
@@ -0,0 +1,225 @@
+use {
+    crate::IbcState,
+    anyhow::{anyhow, bail},
+    derive_more::Display,
+    eclipse_ibc_extra_types::ClientConnections,
+    eclipse_ibc_known_proto::KnownProto,
+    ibc::core::{
+        ics03_connection::connection::ConnectionEnd,
+        ics24_host::{
+            identifier::{ClientId, ConnectionId},
+            path::ClientConnectionPath,
+        },
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        str::FromStr,
+    },
+};
+
+const CONNECTIONS_PREFIX: &str = "connections/";
+
+/// Indices the program keeps next to the primary IBC data they are derived
+/// from, which can be checked against and rebuilt from that data.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+pub enum IndexKind {
+    /// The connections of each client, at `clients/{client_id}/connections`
+    #[display(fmt = "client-connections")]
+    ClientConnections,
+}
+
+impl FromStr for IndexKind {
+    type Err = anyhow::Error;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "client-connections" => Ok(Self::ClientConnections),
+            _ => bail!("Unknown index kind {kind}"),
+        }
+    }
+}
+
+/// Where the `ClientConnections` index disagrees with the stored connections.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientConnectionsCheck {
+    /// Indexed connections that do not exist, or whose connection end names
+    /// another client
+    pub phantom: Vec<(ClientId, ConnectionId)>,
+    /// Connections whose client's index leaves them out
+    pub missing: Vec<(ClientId, ConnectionId)>,
+}
+
+impl ClientConnectionsCheck {
+    #[must_use]
+    pub fn is_consistent(&self) -> bool {
+        self.phantom.is_empty() && self.missing.is_empty()
+    }
+}
+
+type ConnectionsByClient = BTreeMap<ClientId, BTreeSet<ConnectionId>>;
+
+/// The connections of each client as listed by its `ClientConnections` index.
+fn indexed_client_connections(ibc_state: &IbcState<'_>) -> anyhow::Result<ConnectionsByClient> {
+    let mut index = ConnectionsByClient::new();
+    for path in ibc_state.paths_with_prefix("clients/")? {
+        let client_id = match path
+            .strip_prefix("clients/")
+            .and_then(|path| path.strip_suffix("/connections"))
+        {
+            // Client IDs have no `/`, so anything else is a deeper client path
+            Some(client_id) if !client_id.contains('/') => client_id,
+            _ => continue,
+        };
+        let client_id = client_id
+            .parse::<ClientId>()
+            .map_err(|err| anyhow!("Invalid client ID in index path {path}: {err}"))?;
+        let ClientConnections { connections } = ibc_state
+            .get(&ClientConnectionPath::new(&client_id))?
+            .unwrap_or_default();
+        index.insert(client_id, connections.into_iter().collect());
+    }
+    Ok(index)
+}
+
+/// The connections of each client as named by the stored connection ends.
+fn stored_client_connections(ibc_state: &IbcState<'_>) -> anyhow::Result<ConnectionsByClient> {
+    let mut connections = ConnectionsByClient::new();
+    for (path, connection_end) in ibc_state.iter_paths::<ConnectionEnd>(CONNECTIONS_PREFIX)? {
+        let connection_id = path[CONNECTIONS_PREFIX.len()..]
+            .parse::<ConnectionId>()
+            .map_err(|err| anyhow!("Invalid connection ID in path {path}: {err}"))?;
+        connections
+            .entry(connection_end.client_id().clone())
+            .or_default()
+            .insert(connection_id);
+    }
+    Ok(connections)
+}
+
+/// Compares the `ClientConnections` index of every client with the stored
+/// connection ends. Like [`IbcState::paths_with_prefix`], only sees entries
+/// written since the store started recording preimages.
+pub fn check_client_connections(
+    ibc_state: &IbcState<'_>,
+) -> anyhow::Result<ClientConnectionsCheck> {
+    let indexed = indexed_client_connections(ibc_state)?;
+    let stored = stored_client_connections(ibc_state)?;
+
+    let mut check = ClientConnectionsCheck::default();
+    let no_connections = BTreeSet::new();
+    for client_id in indexed.keys().chain(stored.keys()).collect::<BTreeSet<_>>() {
+        let indexed = indexed.get(client_id).unwrap_or(&no_connections);
+        let stored = stored.get(client_id).unwrap_or(&no_connections);
+        check.phantom.extend(
+            indexed
+                .difference(stored)
+                .map(|connection_id| (client_id.clone(), connection_id.clone())),
+        );
+        check.missing.extend(
+            stored
+                .difference(indexed)
+                .map(|connection_id| (client_id.clone(), connection_id.clone())),
+        );
+    }
+    Ok(check)
+}
+
+/// Raw entries that rebuild the `kind` index from the primary data it is
+/// derived from, as `(path, value)` pairs in path order where a `None` value
+/// removes the entry. Only entries that differ from the stored index are
+/// included, so a consistent index needs no writes.
+pub fn index_rebuild_writes(
+    ibc_state: &IbcState<'_>,
+    kind: IndexKind,
+) -> anyhow::Result<Vec<(String, Option<Vec<u8>>)>> {
+    match kind {
+        IndexKind::ClientConnections => {
+            let indexed = indexed_client_connections(ibc_state)?;
+            let stored = stored_client_connections(ibc_state)?;
+
+            let mut writes = vec![];
+            for client_id in indexed.keys().chain(stored.keys()).collect::<BTreeSet<_>>() {
+                let connections = stored.get(client_id);
+                if indexed.get(client_id) == connections {
+                    continue;
+                }
+                let value = connections.map(|connections| {
+                    ClientConnections {
+                        connections: connections.iter().cloned().collect(),
+                    }
+                    .encode()
+                });
+                writes.push((ClientConnectionPath::new(client_id).to_string(), value));
+            }
+            Ok(writes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, eclipse_ibc_fixtures::FixtureStore};
+
+    #[test]
+    fn corrupted_client_connections_are_detected_and_rebuilt() {
+        let account_data = FixtureStore::with_clients(2).with_channels(1).build();
+        let version = account_data.store.read().latest_version().unwrap();
+        let client_id = |index| format!("xx-eclipse-{index}").parse::<ClientId>().unwrap();
+
+        let ibc_state = IbcState::new(&account_data.store, version);
+        assert!(check_client_connections(&ibc_state)
+            .unwrap()
+            .is_consistent());
+        assert!(
+            index_rebuild_writes(&ibc_state, IndexKind::ClientConnections)
+                .unwrap()
+                .is_empty()
+        );
+
+        // Move `connection-0` to the index of the client it does not belong to,
+        // and list a connection that was never stored
+        let mut ibc_state = IbcState::new(&account_data.store, version + 1);
+        ibc_state.remove(&ClientConnectionPath::new(&client_id(0)));
+        ibc_state.set(
+            &ClientConnectionPath::new(&client_id(1)),
+            ClientConnections {
+                connections: [ConnectionId::new(0), ConnectionId::new(7)]
+                    .into_iter()
+                    .collect(),
+            },
+        );
+        ibc_state.commit().unwrap();
+
+        let ibc_state = IbcState::new(&account_data.store, version + 1);
+        let check = check_client_connections(&ibc_state).unwrap();
+        assert_eq!(
+            check.phantom,
+            [
+                (client_id(1), ConnectionId::new(0)),
+                (client_id(1), ConnectionId::new(7)),
+            ]
+        );
+        assert_eq!(check.missing, [(client_id(0), ConnectionId::new(0))]);
+
+        let mut ibc_state = IbcState::new(&account_data.store, version + 2);
+        let writes = index_rebuild_writes(&ibc_state, IndexKind::ClientConnections).unwrap();
+        assert_eq!(writes.len(), 2);
+        for (path, value) in writes {
+            ibc_state.write_raw_entry(&path, value);
+        }
+        assert!(check_client_connections(&ibc_state)
+            .unwrap()
+            .is_consistent());
+        assert!(ibc_state
+            .get(&ClientConnectionPath::new(&client_id(1)))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn index_kinds_round_trip_through_their_names() {
+        let kind = IndexKind::ClientConnections;
+        assert_eq!(kind.to_string().parse::<IndexKind>().unwrap(), kind);
+        assert!("connection-channels".parse::<IndexKind>().is_err());
+    }
+}
@@ -1,10 +1,17 @@
 use {
     derive_more::Display,
-    eclipse_ibc_extra_types::{AllModuleIds, ConsensusHeights},
+    eclipse_ibc_extra_types::{
+        AllModuleIds, CallbackVersions, Capabilities, ChainParams, ConsensusHeights, DenomTrace,
+        ModuleAccountTemplates, PendingAdminChange, StoreWriteBatchCursor,
+    },
     eclipse_ibc_known_path::KnownPath,
     ibc::core::{
-        ics02_client::height::Height, ics24_host::identifier::ClientId, timestamp::Timestamp,
+        ics02_client::height::Height,
+        ics24_host::identifier::{ChannelId, ClientId, PortId},
+        router::ModuleId,
+        timestamp::Timestamp,
     },
+    solana_sdk::pubkey::Pubkey,
 };
 
 #[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -31,6 +38,39 @@ impl KnownPath for ClientUpdateHeightPath {
     type Value = Height;
 }
 
+/// Clock time of the slot that committed the state version, recorded at every
+/// commit of the handler so that host consensus states of past heights carry the
+/// time of their slot. Absent in versions committed before it was recorded.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/slotTime")]
+pub struct SlotTimePath;
+
+impl KnownPath for SlotTimePath {
+    type Value = Timestamp;
+}
+
+/// Heights at which the client's update time and height were recorded, so that
+/// stale `ClientUpdateTimePath` and `ClientUpdateHeightPath` entries can be pruned
+/// without scanning for them. Entries recorded before this index existed are not
+/// in it and are never pruned.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/clients/{_0}/processedHeights")]
+pub struct ProcessedHeightsPath(pub ClientId);
+
+impl KnownPath for ProcessedHeightsPath {
+    type Value = ConsensusHeights;
+}
+
+/// Type URL of the `Any` stored at the client's `ClientStatePath`, so that the
+/// client type can be found without decoding the whole client state.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/clients/{_0}/type")]
+pub struct ClientTypeUrlPath(pub ClientId);
+
+impl KnownPath for ClientTypeUrlPath {
+    type Value = String;
+}
+
 /// Keeps track of a sorted list of known consensus heights so that `prev_consensus_state`
 /// and `next_consensus_state` can be implemented.
 #[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -51,3 +91,154 @@ pub struct AllModulesPath;
 impl KnownPath for AllModulesPath {
     type Value = AllModuleIds;
 }
+
+/// The seed templates of the module's own PDAs that are passed to its callbacks.
+/// Replaced whenever the module binds a port, moved to the new module when its
+/// port is transferred, and absent when it has none.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/modules/{_0}/accountTemplates")]
+pub struct ModuleAccountTemplatesPath(pub ModuleId);
+
+impl KnownPath for ModuleAccountTemplatesPath {
+    type Value = ModuleAccountTemplates;
+}
+
+/// The callback versions a module advertised when first asked, which callbacks
+/// are encoded by the highest of that the program also supports. Cleared when
+/// the module binds a port or stops being routable, so that a module upgraded to
+/// support other versions is asked again.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/modules/{_0}/callbackVersions")]
+pub struct ModuleCallbackVersionsPath(pub ModuleId);
+
+impl KnownPath for ModuleCallbackVersionsPath {
+    type Value = CallbackVersions;
+}
+
+/// Tracks the next chunk expected by an in-progress store write batch. Absent
+/// when no batch is being applied.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/storeWriteBatchCursor")]
+pub struct StoreWriteBatchCursorPath;
+
+impl KnownPath for StoreWriteBatchCursorPath {
+    type Value = StoreWriteBatchCursor;
+}
+
+/// Base58 public key of the admin, the only signer accepted for privileged admin
+/// messages. Set to the payer that initializes the storage account. Absent on
+/// accounts initialized before that until an admin is claimed, in which case no
+/// signer is accepted.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/admin")]
+pub struct AdminPath;
+
+impl KnownPath for AdminPath {
+    type Value = String;
+}
+
+/// Operator-controlled parameters, such as which message families are disabled.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/chainParams")]
+pub struct ChainParamsPath;
+
+impl KnownPath for ChainParamsPath {
+    type Value = ChainParams;
+}
+
+/// Message families the program handles and its version, written at storage
+/// init and rewritten by `MsgRefreshCapabilities` after a program upgrade. Absent
+/// if the storage account was initialized by a program that predates it.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/capabilities")]
+pub struct CapabilitiesPath;
+
+impl KnownPath for CapabilitiesPath {
+    type Value = Capabilities;
+}
+
+/// The admin change waiting to be accepted by its new admin. Absent when there
+/// is none.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/pendingAdminChange")]
+pub struct PendingAdminChangePath;
+
+impl KnownPath for PendingAdminChangePath {
+    type Value = PendingAdminChange;
+}
+
+/// Counts the packets sent on a channel whose commitments have not yet been deleted
+/// by an acknowledgement or timeout. Absent until the channel's packets are first
+/// counted, which counts the commitments of any sent before then.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/ports/{_0}/channels/{_1}/inflightPackets")]
+pub struct InflightPacketsPath(pub PortId, pub ChannelId);
+
+impl KnownPath for InflightPacketsPath {
+    type Value = u64;
+}
+
+/// The module that opened a channel, which keeps receiving the channel's
+/// callbacks even if its port is released and bound by another module. Absent
+/// for channels opened before owners were recorded, whose callbacks go to the
+/// module bound to their port.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/channelOwners/ports/{_0}/channels/{_1}")]
+pub struct ChannelOwnerPath(pub PortId, pub ChannelId);
+
+impl KnownPath for ChannelOwnerPath {
+    type Value = ModuleId;
+}
+
+/// Number of legacy Eclipse type URLs that client messages carried and that were
+/// rewritten to the fixed ones, so that operators can tell when every tool
+/// submitting messages has been upgraded. Absent until the first is seen.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/legacyTypeUrlCount")]
+pub struct LegacyTypeUrlCountPath;
+
+impl KnownPath for LegacyTypeUrlCountPath {
+    type Value = u64;
+}
+
+/// Total size of the tx buffers a payer has open. Absent when there are none.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/txBufferPayers/{_0}/openBytes")]
+pub struct TxBufferBytesPath(pub Pubkey);
+
+impl KnownPath for TxBufferBytesPath {
+    type Value = u64;
+}
+
+/// The payer that created a tx buffer, which is the only one that can write to or
+/// close it.
+/// Removed when the buffer is closed.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/txBuffers/{_0}/payer")]
+pub struct TxBufferPayerPath(pub Pubkey);
+
+impl KnownPath for TxBufferPayerPath {
+    type Value = String;
+}
+
+/// Balance of `denom` held by an account in the built-in ICS-20 module, as a
+/// decimal amount. Channel escrows are accounts derived from their port and
+/// channel. Absent when the balance is zero.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/ics20/balances/{_0}/{_1}")]
+pub struct Ics20BalancePath(pub Pubkey, pub String);
+
+impl KnownPath for Ics20BalancePath {
+    type Value = String;
+}
+
+/// Trace of the ICS-20 voucher denom whose hash, as uppercase hex, is `_0`, so
+/// that `ibc/{hash}` denoms can be resolved. Written when vouchers of the denom
+/// are first minted, and never removed.
+#[derive(Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "internal/ics20/denomTraces/{_0}")]
+pub struct DenomTracePath(pub String);
+
+impl KnownPath for DenomTracePath {
+    type Value = DenomTrace;
+}
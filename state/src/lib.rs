@@ -1,16 +1,33 @@
 mod client_and_consensus_states;
+mod client_status;
+mod event_journal;
 mod ibc_account_data;
 mod ibc_metadata;
 mod ibc_state;
 mod ibc_store;
+mod index_consistency;
 pub mod internal_path;
+mod packet_commitment;
+mod value_compression;
+mod value_json;
 
 pub use {
     client_and_consensus_states::{
         decode_client_state, decode_consensus_state, encode_client_state, encode_consensus_state,
+        header_batch_type_url_of_client, header_type_url_of_client, tracked_chain_id,
     },
-    ibc_account_data::IbcAccountData,
-    ibc_metadata::IbcMetadata,
-    ibc_state::IbcState,
-    ibc_store::IbcStore,
+    client_status::{client_status, ClientStatus},
+    event_journal::{EventJournal, JournalEntry},
+    ibc_account_data::{
+        AccountFull, IbcAccountData, ImplausibleAccountData, ACCOUNT_FULL_ERR_CODE,
+        CORRUPTED_STATE_ERR_CODE,
+    },
+    ibc_metadata::{IbcMetadata, IbcMetadataV4, LegacyIbcMetadata},
+    ibc_state::{ChangeKind, IbcState, LoadChainParams, PathChange},
+    ibc_store::{IbcStore, IbcStoreStats, StorePruning},
+    index_consistency::{
+        check_client_connections, index_rebuild_writes, ClientConnectionsCheck, IndexKind,
+    },
+    packet_commitment::{compute_ack_commitment, compute_packet_commitment},
+    value_json::stored_value_json,
 };
@@ -0,0 +1,35 @@
+use {
+    ibc::core::{
+        ics04_channel::{
+            commitment::{AcknowledgementCommitment, PacketCommitment},
+            timeout::TimeoutHeight,
+        },
+        timestamp::Timestamp,
+    },
+    sha2::{Digest, Sha256},
+};
+
+/// Computes the ICS-4 commitment to a packet, the same way ibc-rs does when the
+/// packet is sent: the SHA-256 hash of the big-endian timeout timestamp, timeout
+/// revision number and timeout revision height, followed by the SHA-256 hash of the
+/// packet data.
+#[must_use]
+pub fn compute_packet_commitment(
+    packet_data: &[u8],
+    timeout_height: &TimeoutHeight,
+    timeout_timestamp: &Timestamp,
+) -> PacketCommitment {
+    let mut hasher = Sha256::new();
+    hasher.update(timeout_timestamp.nanoseconds().to_be_bytes());
+    hasher.update(timeout_height.commitment_revision_number().to_be_bytes());
+    hasher.update(timeout_height.commitment_revision_height().to_be_bytes());
+    hasher.update(Sha256::digest(packet_data));
+    hasher.finalize().to_vec().into()
+}
+
+/// Computes the ICS-4 commitment to an acknowledgement, which is the SHA-256 hash
+/// of its bytes.
+#[must_use]
+pub fn compute_ack_commitment(ack: &[u8]) -> AcknowledgementCommitment {
+    Sha256::digest(ack).to_vec().into()
+}
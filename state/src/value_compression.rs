@@ -0,0 +1,155 @@
+//! Compression of large values at internal paths of the IBC state.
+//!
+//! A compressed value is stored as a tag byte naming its compression scheme
+//! followed by the compressed bytes. Every value at an internal path is a
+//! protobuf encoding, which is either empty or starts with a field key of at
+//! least `0x08`, so tags are taken from the bytes below that. Values without a
+//! tag, such as those stored before compression was turned on, read back as they
+//! are.
+//!
+//! Values at ICS-24 paths are never compressed: counterparties verify proofs of
+//! their stored bytes, and some of them, such as packet commitments, are raw
+//! bytes that may start with anything.
+
+use {
+    anyhow::{anyhow, ensure},
+    std::{borrow::Cow, io::Read},
+};
+
+/// Prefix of the paths whose values may be stored compressed.
+pub(crate) const COMPRESSIBLE_PATH_PREFIX: &str = "internal/";
+
+/// Tag of values compressed with zstd, the first version of the scheme.
+pub(crate) const ZSTD_V1_TAG: u8 = 0x01;
+
+/// Tags up to this one are reserved for compression schemes, since no protobuf
+/// encoding starts with them.
+const MAX_RESERVED_TAG: u8 = 0x07;
+
+const ZSTD_LEVEL: i32 = 3;
+
+/// Largest value a compressed value may decompress to, the size limit of a
+/// Solana account.
+const MAX_DECOMPRESSED_LEN: u64 = 10 * 1024 * 1024;
+
+/// Whether values at `path` may be stored compressed.
+#[must_use]
+pub(crate) fn is_compressible_path(path: &str) -> bool {
+    path.starts_with(COMPRESSIBLE_PATH_PREFIX)
+}
+
+/// Returns the bytes to store for the encoded `value` at `path`: compressed and
+/// tagged if the path is compressible, the value is larger than `threshold` bytes
+/// and compressing it saves space, or `value` itself otherwise.
+#[must_use]
+pub(crate) fn compress_value(path: &str, value: Vec<u8>, threshold: Option<u64>) -> Vec<u8> {
+    let threshold = match threshold {
+        Some(threshold) if is_compressible_path(path) => threshold,
+        _ => return value,
+    };
+    if value.len() as u64 <= threshold {
+        return value;
+    }
+    match zstd::bulk::compress(&value, ZSTD_LEVEL) {
+        Ok(compressed) if compressed.len() + 1 < value.len() => {
+            let mut stored = Vec::with_capacity(compressed.len() + 1);
+            stored.push(ZSTD_V1_TAG);
+            stored.extend_from_slice(&compressed);
+            stored
+        }
+        // Compression only saves space, so a value it does not shrink is stored
+        // as is
+        _ => value,
+    }
+}
+
+/// Reverses [`compress_value`] on the bytes stored at `path`. Uncompressed values
+/// are returned as they are, without a copy.
+pub(crate) fn decompress_value<'a>(
+    path: &str,
+    stored: Cow<'a, [u8]>,
+) -> anyhow::Result<Cow<'a, [u8]>> {
+    if !is_compressible_path(path) {
+        return Ok(stored);
+    }
+    match stored.first() {
+        Some(&ZSTD_V1_TAG) => {
+            let mut value = vec![];
+            zstd::stream::read::Decoder::new(&stored[1..])?
+                .take(MAX_DECOMPRESSED_LEN + 1)
+                .read_to_end(&mut value)
+                .map_err(|err| anyhow!("failed to decompress value at {path}: {err}"))?;
+            ensure!(
+                value.len() as u64 <= MAX_DECOMPRESSED_LEN,
+                "value at {path} decompresses to more than {MAX_DECOMPRESSED_LEN} bytes"
+            );
+            Ok(Cow::Owned(value))
+        }
+        Some(&tag) if tag <= MAX_RESERVED_TAG => Err(anyhow!(
+            "value at {path} has unknown compression tag {tag:#04x}"
+        )),
+        // Untagged values are stored uncompressed
+        _ => Ok(stored),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::internal_path::ConsensusHeightsPath,
+        eclipse_ibc_extra_types::ConsensusHeights,
+        eclipse_ibc_known_proto::KnownProto,
+        ibc::core::{ics02_client::height::Height, ics24_host::identifier::ClientId},
+    };
+
+    fn consensus_heights(count: u64) -> (String, Vec<u8>) {
+        let client_id = "07-tendermint-0".parse::<ClientId>().unwrap();
+        let heights = (1..=count)
+            .map(|height| Height::new(4, height).unwrap())
+            .collect();
+        (
+            ConsensusHeightsPath(client_id).to_string(),
+            ConsensusHeights { heights }.encode(),
+        )
+    }
+
+    #[test]
+    fn large_internal_values_round_trip_tagged() {
+        let (path, value) = consensus_heights(100);
+        let stored = compress_value(&path, value.clone(), Some(64));
+        assert_eq!(stored[0], ZSTD_V1_TAG);
+        assert!(stored.len() < value.len());
+        assert_eq!(decompress_value(&path, stored.into()).unwrap(), value);
+    }
+
+    #[test]
+    fn legacy_untagged_values_read_back_as_they_are() {
+        for count in [0, 1, 100] {
+            let (path, value) = consensus_heights(count);
+            let read_back = decompress_value(&path, Cow::Borrowed(&value)).unwrap();
+            assert!(matches!(read_back, Cow::Borrowed(_)));
+            assert_eq!(read_back, value);
+            // Values at or below the threshold are not compressed either
+            let stored = compress_value(&path, value.clone(), Some(value.len() as u64));
+            assert_eq!(stored, value);
+        }
+    }
+
+    #[test]
+    fn ics24_values_are_never_compressed() {
+        let path = "commitments/ports/transfer/channels/channel-0/sequences/1";
+        let value = vec![ZSTD_V1_TAG; 1024];
+        let stored = compress_value(path, value.clone(), Some(0));
+        assert_eq!(stored, value);
+        // Even if they start with a compression tag
+        assert_eq!(decompress_value(path, stored.into()).unwrap(), value);
+    }
+
+    #[test]
+    fn unknown_compression_tags_are_rejected() {
+        let (path, _) = consensus_heights(0);
+        assert!(decompress_value(&path, vec![MAX_RESERVED_TAG, 1, 2].into()).is_err());
+        assert!(decompress_value(&path, vec![ZSTD_V1_TAG, 1, 2].into()).is_err());
+    }
+}
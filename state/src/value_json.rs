@@ -0,0 +1,276 @@
+//! JSON views of the values stored in the IBC state, for dumps of the whole
+//! state where the type of each value is only known from its path.
+//!
+//! Each known path pattern is mapped to the decoder of the value its
+//! [`KnownPath`] type stores. Values at unknown paths, and values that fail to
+//! decode, are shown as hex so that one odd entry does not hide the rest.
+
+use {
+    crate::{
+        decode_client_state, decode_consensus_state,
+        internal_path::{
+            AdminPath, AllModulesPath, CapabilitiesPath, ChainParamsPath, ChannelOwnerPath,
+            ClientTypeUrlPath, ClientUpdateHeightPath, ClientUpdateTimePath, ConsensusHeightsPath,
+            DenomTracePath, Ics20BalancePath, InflightPacketsPath, LegacyTypeUrlCountPath,
+            ModuleAccountTemplatesPath, ModuleCallbackVersionsPath, PendingAdminChangePath,
+            ProcessedHeightsPath, SlotTimePath, StateInitializedPath, StoreWriteBatchCursorPath,
+            TxBufferBytesPath, TxBufferPayerPath,
+        },
+        value_compression,
+    },
+    eclipse_ibc_known_path::KnownPath,
+    eclipse_ibc_known_proto::KnownProto,
+    ibc::core::ics24_host::path::{
+        ChannelEndPath, ClientConnectionPath, ConnectionPath, PortPath, SeqAckPath, SeqRecvPath,
+        SeqSendPath,
+    },
+    ibc_proto::google::protobuf,
+    prost::Message as _,
+    serde::Serialize,
+    serde_json::Value,
+    std::borrow::Cow,
+};
+
+type Decoder = fn(&[u8]) -> anyhow::Result<Value>;
+
+/// Path patterns and the decoders of the values at matching paths. A `*` segment
+/// matches any one segment, and a final `**` matches one or more.
+const DECODERS: &[(&str, Decoder)] = &[
+    ("clients/*/clientState", client_state_json),
+    ("clients/*/consensusStates/*", consensus_state_json),
+    (
+        "clients/*/connections",
+        known_path_json::<ClientConnectionPath>,
+    ),
+    ("connections/*", known_path_json::<ConnectionPath>),
+    ("ports/*", known_path_json::<PortPath>),
+    (
+        "channelEnds/ports/*/channels/*",
+        known_path_json::<ChannelEndPath>,
+    ),
+    (
+        "nextSequenceSend/ports/*/channels/*",
+        known_path_json::<SeqSendPath>,
+    ),
+    (
+        "nextSequenceRecv/ports/*/channels/*",
+        known_path_json::<SeqRecvPath>,
+    ),
+    (
+        "nextSequenceAck/ports/*/channels/*",
+        known_path_json::<SeqAckPath>,
+    ),
+    // Commitments are hashes, which read best as they are
+    ("commitments/ports/*/channels/*/sequences/*", hex_json),
+    ("acks/ports/*/channels/*/sequences/*", hex_json),
+    ("receipts/ports/*/channels/*/sequences/*", hex_json),
+    ("upgradedIBCState/*/upgradedClient", client_state_json),
+    ("upgradedIBCState/*/upgradedConsState", consensus_state_json),
+    (
+        "internal/stateInitialized",
+        known_path_json::<StateInitializedPath>,
+    ),
+    ("internal/slotTime", known_path_json::<SlotTimePath>),
+    (
+        "internal/clients/*/updateTime/*",
+        known_path_json::<ClientUpdateTimePath>,
+    ),
+    (
+        "internal/clients/*/updateHeight/*",
+        known_path_json::<ClientUpdateHeightPath>,
+    ),
+    (
+        "internal/clients/*/processedHeights",
+        known_path_json::<ProcessedHeightsPath>,
+    ),
+    (
+        "internal/clients/*/type",
+        known_path_json::<ClientTypeUrlPath>,
+    ),
+    (
+        "internal/clients/*/consensusHeights",
+        known_path_json::<ConsensusHeightsPath>,
+    ),
+    ("internal/allModules", known_path_json::<AllModulesPath>),
+    (
+        "internal/modules/*/accountTemplates",
+        known_path_json::<ModuleAccountTemplatesPath>,
+    ),
+    (
+        "internal/modules/*/callbackVersions",
+        known_path_json::<ModuleCallbackVersionsPath>,
+    ),
+    (
+        "internal/storeWriteBatchCursor",
+        known_path_json::<StoreWriteBatchCursorPath>,
+    ),
+    ("internal/chainParams", known_path_json::<ChainParamsPath>),
+    ("internal/capabilities", known_path_json::<CapabilitiesPath>),
+    ("internal/admin", known_path_json::<AdminPath>),
+    (
+        "internal/pendingAdminChange",
+        known_path_json::<PendingAdminChangePath>,
+    ),
+    (
+        "internal/ports/*/channels/*/inflightPackets",
+        known_path_json::<InflightPacketsPath>,
+    ),
+    (
+        "internal/channelOwners/ports/*/channels/*",
+        known_path_json::<ChannelOwnerPath>,
+    ),
+    (
+        "internal/legacyTypeUrlCount",
+        known_path_json::<LegacyTypeUrlCountPath>,
+    ),
+    (
+        "internal/txBufferPayers/*/openBytes",
+        known_path_json::<TxBufferBytesPath>,
+    ),
+    (
+        "internal/txBuffers/*/payer",
+        known_path_json::<TxBufferPayerPath>,
+    ),
+    // Denominations may themselves contain slashes
+    (
+        "internal/ics20/balances/*/**",
+        known_path_json::<Ics20BalancePath>,
+    ),
+    (
+        "internal/ics20/denomTraces/*",
+        known_path_json::<DenomTracePath>,
+    ),
+];
+
+fn matches_pattern(path: &str, pattern: &str) -> bool {
+    let mut segments = path.split('/');
+    for pattern_segment in pattern.split('/') {
+        match (pattern_segment, segments.next()) {
+            (_, None) => return false,
+            ("**", Some(_)) => return true,
+            ("*", Some(_)) => {}
+            (pattern_segment, Some(segment)) if pattern_segment == segment => {}
+            _ => return false,
+        }
+    }
+    segments.next().is_none()
+}
+
+fn known_path_json<K>(value: &[u8]) -> anyhow::Result<Value>
+where
+    K: KnownPath,
+    <K::Value as KnownProto>::Raw: Serialize,
+{
+    let value = <K::Value as KnownProto>::decode(value)?;
+    Ok(serde_json::to_value(value.into_raw())?)
+}
+
+fn client_state_json(value: &[u8]) -> anyhow::Result<Value> {
+    let client_state = decode_client_state(protobuf::Any::decode(value)?)?;
+    Ok(serde_json::to_value(client_state)?)
+}
+
+fn consensus_state_json(value: &[u8]) -> anyhow::Result<Value> {
+    let consensus_state = decode_consensus_state(protobuf::Any::decode(value)?)?;
+    Ok(serde_json::to_value(consensus_state)?)
+}
+
+fn hex_json(value: &[u8]) -> anyhow::Result<Value> {
+    Ok(Value::String(hex::encode(value)))
+}
+
+/// The value stored at `path`, as JSON decoded according to the path, or as hex
+/// if the path is unknown or the value does not decode. `stored` is the bytes
+/// in the store, which are decompressed first if they are compressed.
+#[must_use]
+pub fn stored_value_json(path: &str, stored: Vec<u8>) -> Value {
+    let value = match value_compression::decompress_value(path, Cow::Borrowed(&stored)) {
+        Ok(value) => value,
+        Err(_) => return Value::String(hex::encode(&stored)),
+    };
+    DECODERS
+        .iter()
+        .find(|(pattern, _)| matches_pattern(path, pattern))
+        .and_then(|(_, decode)| decode(&value).ok())
+        .unwrap_or_else(|| Value::String(hex::encode(&value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        eclipse_ibc_extra_types::ConsensusHeights,
+        eclipse_ibc_fixtures::clients,
+        ibc::core::{
+            ics02_client::height::Height,
+            ics04_channel::packet::Sequence,
+            ics24_host::{
+                identifier::{ChannelId, ClientId, PortId},
+                path::ClientStatePath,
+            },
+        },
+        serde_json::json,
+    };
+
+    #[test]
+    fn values_are_decoded_by_path() {
+        let client_id = "07-tendermint-0".parse::<ClientId>().unwrap();
+        let client_state = clients::tendermint_client_state_any(10).encode_to_vec();
+        let client_state_json =
+            stored_value_json(&ClientStatePath::new(&client_id).to_string(), client_state);
+        assert!(client_state_json.is_object(), "{client_state_json}");
+
+        let seq_send_path = SeqSendPath::new(&PortId::transfer(), &ChannelId::new(0));
+        assert_eq!(
+            stored_value_json(&seq_send_path.to_string(), Sequence::from(5).encode()),
+            json!(5)
+        );
+
+        // Compressed values are decompressed first
+        let heights = (1..=100)
+            .map(|height| Height::new(4, height).unwrap())
+            .collect();
+        let path = ConsensusHeightsPath(client_id).to_string();
+        let stored = value_compression::compress_value(
+            &path,
+            ConsensusHeights { heights }.encode(),
+            Some(0),
+        );
+        let heights_json = stored_value_json(&path, stored);
+        assert_eq!(heights_json["heights"].as_array().map(Vec::len), Some(100));
+    }
+
+    #[test]
+    fn unknown_and_undecodable_values_fall_back_to_hex() {
+        assert_eq!(stored_value_json("unknown/path", vec![0xab]), json!("ab"));
+        assert_eq!(
+            stored_value_json("connections/connection-0", vec![0xff, 0xff]),
+            json!("ffff")
+        );
+        assert_eq!(
+            stored_value_json(
+                "commitments/ports/transfer/channels/channel-0/sequences/1",
+                vec![0x01, 0x02]
+            ),
+            json!("0102")
+        );
+    }
+
+    #[test]
+    fn patterns_match_whole_segments() {
+        assert!(matches_pattern("connections/connection-0", "connections/*"));
+        assert!(!matches_pattern(
+            "connections/connection-0/x",
+            "connections/*"
+        ));
+        assert!(!matches_pattern("connections", "connections/*"));
+        assert!(matches_pattern(
+            "internal/ics20/balances/key/transfer/channel-0/uatom",
+            "internal/ics20/balances/*/**"
+        ));
+        assert!(!matches_pattern(
+            "internal/ics20/balances/key",
+            "internal/ics20/balances/*/**"
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+//! Decodes the account dump goldens in the fixtures crate at every format version
+//! the IBC program has written, so that a format change cannot strand existing
+//! storage accounts.
+
+use {
+    eclipse_ibc_fixtures::{goldens, FixtureStore, ACCOUNT_FORMAT_VERSIONS},
+    eclipse_ibc_state::{IbcAccountData, IbcState},
+    ibc::core::{
+        ics04_channel::packet::Sequence,
+        ics24_host::{
+            identifier::{ChannelId, ClientId, PortId},
+            path::{ClientStatePath, CommitmentPath, SeqSendPath},
+        },
+    },
+};
+
+#[test]
+#[ignore = "binary goldens are not checked in yet; run `cargo run -p eclipse-ibc-fixtures --bin regen`"]
+fn account_dumps_decode_at_every_format_version() {
+    let expected = FixtureStore::canonical().build();
+    let expected_root = IbcState::new(&expected.store, 2)
+        .get_root_option(2)
+        .unwrap();
+    assert!(expected_root.is_some());
+
+    for version in ACCOUNT_FORMAT_VERSIONS {
+        let data = goldens::load(&goldens::account_dump_name(version)).unwrap();
+        let account_data = IbcAccountData::from_bytes(&data).unwrap();
+        account_data.verify_latest_root_deep().unwrap();
+        account_data.verify_checksum().unwrap();
+
+        let metadata = &account_data.metadata;
+        assert_eq!(metadata.client_id_counter, 2, "v{version}");
+        assert_eq!(metadata.connection_id_counter, 1, "v{version}");
+        assert_eq!(metadata.channel_id_counter, 2, "v{version}");
+        let metadata = IbcAccountData::read_metadata_only(&data).unwrap();
+        assert_eq!(metadata.channel_id_counter, 2, "v{version}");
+
+        let state = IbcState::new(&account_data.store, 2);
+        assert_eq!(
+            state.get_root_option(2).unwrap(),
+            expected_root,
+            "v{version}"
+        );
+        let client_id = "xx-eclipse-1".parse::<ClientId>().unwrap();
+        assert!(state
+            .get(&ClientStatePath::new(&client_id))
+            .unwrap()
+            .is_some());
+        let port_id = PortId::transfer();
+        let channel_id = ChannelId::new(1);
+        assert_eq!(
+            state.get(&SeqSendPath::new(&port_id, &channel_id)).unwrap(),
+            Some(Sequence::from(4)),
+        );
+        assert!(state
+            .get(&CommitmentPath::new(
+                &port_id,
+                &channel_id,
+                Sequence::from(3)
+            ))
+            .unwrap()
+            .is_some());
+
+        // The commit checksum is only recorded since version 4
+        assert_eq!(metadata.has_checksum(), version >= 4, "v{version}");
+
+        // Path preimages are only recorded since version 2
+        let preimages = account_data.store.preimages_with_prefix("").unwrap();
+        assert_eq!(preimages.is_empty(), version < 2, "v{version}");
+
+        // Only the header layout is recognized as an initialized account
+        assert_eq!(
+            IbcAccountData::check_initialized(&data).is_ok(),
+            version > 0,
+            "v{version}"
+        );
+    }
+}
+
+#[test]
+fn fixture_stores_grow_with_their_contents() {
+    let empty = FixtureStore::default().build();
+    let clients = FixtureStore::with_clients(4).build();
+    let channels = FixtureStore::with_clients(4).with_channels(4).build();
+    let packets = FixtureStore::with_clients(4)
+        .with_channels(4)
+        .with_packets(8)
+        .build();
+
+    let sizes = [empty, clients, channels, packets]
+        .iter()
+        .map(|account_data| account_data.serialized_size().unwrap())
+        .collect::<Vec<_>>();
+    assert!(sizes.windows(2).all(|pair| pair[0] < pair[1]), "{sizes:?}");
+}
+
+#[test]
+fn diff_lists_paths_whose_values_changed() {
+    let old = FixtureStore::canonical().build();
+    let new = FixtureStore::canonical().with_packets(4).build();
+    let old_state = IbcState::new(&old.store, 2);
+    let new_state = IbcState::new(&new.store, 2);
+
+    assert!(old_state.diff(&old_state).unwrap().is_empty());
+
+    let port_id = PortId::transfer();
+    let mut expected_paths = vec![];
+    for channel in 0..2 {
+        let channel_id = ChannelId::new(channel);
+        expected_paths
+            .push(CommitmentPath::new(&port_id, &channel_id, Sequence::from(4)).to_string());
+        expected_paths.push(SeqSendPath::new(&port_id, &channel_id).to_string());
+    }
+    expected_paths.sort();
+
+    let changes = old_state.diff(&new_state).unwrap();
+    let paths = changes
+        .iter()
+        .map(|change| change.path.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, expected_paths);
+    let added = changes
+        .iter()
+        .filter(|change| change.old_value.is_none())
+        .count();
+    assert_eq!(added, 2);
+
+    // Reversed, the new commitments are removals
+    let changes = new_state.diff(&old_state).unwrap();
+    let removed = changes
+        .iter()
+        .filter(|change| change.new_value.is_none())
+        .count();
+    assert_eq!(removed, 2);
+}
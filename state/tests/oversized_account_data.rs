@@ -0,0 +1,89 @@
+//! Decodes account data with length prefixes far past its end, and checks that
+//! it is rejected without allocating for the declared lengths. Lives in a test
+//! binary of its own since it replaces the global allocator.
+
+use {
+    eclipse_ibc_state::{IbcAccountData, IbcMetadata, ImplausibleAccountData},
+    std::{
+        alloc::{GlobalAlloc, Layout, System},
+        sync::atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// Largest allocation the decoding is allowed to make, well under what any of the
+/// declared lengths would take.
+const MAX_ALLOCATION: usize = 1 << 20;
+
+static LARGEST_ALLOCATION: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, keeping track of the largest allocation made.
+struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LARGEST_ALLOCATION.fetch_max(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        LARGEST_ALLOCATION.fetch_max(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+/// Header-layout account data at the latest format version, with default
+/// metadata, followed by `segments`.
+fn header_layout(segments: &[u64]) -> Vec<u8> {
+    let metadata = bincode::serialize(&IbcMetadata::default()).unwrap();
+    let mut data = b"EIBC".to_vec();
+    data.push(3);
+    data.extend_from_slice(&u32::try_from(metadata.len()).unwrap().to_le_bytes());
+    data.extend_from_slice(&metadata);
+    for segment in segments {
+        data.extend_from_slice(&segment.to_le_bytes());
+    }
+    data
+}
+
+#[test]
+fn oversized_length_prefixes_are_rejected_without_allocating_for_them() {
+    const HUGE: u64 = 1 << 40;
+
+    let payloads = [
+        // A node key of a terabyte
+        header_layout(&[1, HUGE]),
+        // A node map of a trillion entries
+        header_layout(&[HUGE]),
+        // A trillion keys with value histories
+        header_layout(&[0, HUGE]),
+        // A trillion versions
+        header_layout(&[0, 0, HUGE]),
+        // A preimage path of a terabyte
+        header_layout(&[0, 0, 0, 1, HUGE]),
+        // A trillion journaled events
+        header_layout(&[0, 0, 0, 0, 0, HUGE]),
+        // Legacy account data with a node map of a trillion entries
+        HUGE.to_le_bytes().to_vec(),
+    ];
+
+    for (index, data) in payloads.iter().enumerate() {
+        LARGEST_ALLOCATION.store(0, Ordering::Relaxed);
+        let err = IbcAccountData::from_bytes(data).unwrap_err();
+        let largest_allocation = LARGEST_ALLOCATION.load(Ordering::Relaxed);
+        assert!(
+            err.is::<ImplausibleAccountData>(),
+            "payload {index}: {err:?}"
+        );
+        assert!(
+            largest_allocation < MAX_ALLOCATION,
+            "payload {index} allocated {largest_allocation} bytes"
+        );
+    }
+}
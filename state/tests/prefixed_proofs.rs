@@ -0,0 +1,99 @@
+//! Round trips values stored in `IbcState` through the proofs a relayer builds and
+//! `EclipseClientState` verification, with the counterparty's commitment prefix
+//! applied to the path.
+
+use {
+    eclipse_ibc_fixtures::clients,
+    eclipse_ibc_light_client::eclipse_chain,
+    eclipse_ibc_state::{IbcState, IbcStore},
+    ibc::core::{
+        ics02_client::client_state::ClientState,
+        ics04_channel::{commitment::PacketCommitment, packet::Sequence},
+        ics23_commitment::commitment::{CommitmentPrefix, CommitmentProofBytes},
+        ics24_host::{
+            identifier::{ChannelId, ClientId, PortId},
+            path::{ClientStatePath, CommitmentPath, Path},
+        },
+    },
+    ibc_proto::ibc::core::commitment::v1::MerkleProof as RawMerkleProof,
+    prost::Message as _,
+};
+
+const SLOT: u64 = 7;
+
+fn proof_bytes(merkle_proof: RawMerkleProof) -> CommitmentProofBytes {
+    CommitmentProofBytes::try_from(merkle_proof.encode_to_vec()).unwrap()
+}
+
+#[test]
+fn stored_values_verify_under_the_commitment_prefix() {
+    let client_id = "07-tendermint-0".parse::<ClientId>().unwrap();
+    let client_state_path = ClientStatePath::new(&client_id);
+    let client_state_any = clients::tendermint_client_state_any(5);
+    let commitment_path =
+        CommitmentPath::new(&PortId::transfer(), &ChannelId::new(0), Sequence::from(1));
+    let commitment = PacketCommitment::from(vec![3; 32]);
+
+    let store = IbcStore::default();
+    let mut state = IbcState::new(&store, SLOT);
+    state
+        .set_any(&client_state_path, client_state_any.clone())
+        .unwrap();
+    state.set(&commitment_path, commitment.clone());
+    state.commit().unwrap();
+
+    let state_root = state.get_root_option(SLOT).unwrap().unwrap();
+    let commitment_root = state.get_commitment_root_option(SLOT).unwrap().unwrap();
+    assert_ne!(commitment_root, state_root);
+
+    let client_state = clients::eclipse_client_state(SLOT + 1);
+    let prefix = eclipse_chain::commitment_prefix();
+    let cases = [
+        (
+            Path::ClientState(client_state_path.clone()),
+            state.get_proof(&client_state_path).unwrap(),
+            client_state_any.encode_to_vec(),
+        ),
+        (
+            Path::Commitment(commitment_path.clone()),
+            state.get_proof(&commitment_path).unwrap(),
+            commitment.into_vec(),
+        ),
+    ];
+    for (path, existence_proof, value) in cases {
+        let proof = proof_bytes(eclipse_chain::merkle_proof(existence_proof.clone()));
+        client_state
+            .verify_membership(
+                &prefix,
+                &proof,
+                &commitment_root,
+                path.clone(),
+                value.clone(),
+            )
+            .unwrap();
+
+        // The prefix is part of the proven key path
+        let other_prefix = CommitmentPrefix::try_from(b"other".to_vec()).unwrap();
+        client_state
+            .verify_membership(
+                &other_prefix,
+                &proof,
+                &commitment_root,
+                path.clone(),
+                value.clone(),
+            )
+            .unwrap_err();
+
+        // Proofs without the prefix layer no longer verify, against either root
+        let unprefixed = {
+            let mut merkle_proof = eclipse_chain::merkle_proof(existence_proof);
+            merkle_proof.proofs.truncate(1);
+            proof_bytes(merkle_proof)
+        };
+        for root in [&commitment_root, &state_root] {
+            client_state
+                .verify_membership(&prefix, &unprefixed, root, path.clone(), value.clone())
+                .unwrap_err();
+        }
+    }
+}
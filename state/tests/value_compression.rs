@@ -0,0 +1,153 @@
+//! Measures how much compressing values at internal paths shrinks a storage
+//! account holding a Tendermint client with 50 consensus states, and checks that
+//! values at ICS-24 paths are stored and proven as they are either way.
+
+use {
+    eclipse_ibc_fixtures::clients::{
+        self, TENDERMINT_CLIENT_STATE_TYPE_URL, TENDERMINT_CONSENSUS_STATE_TYPE_URL,
+    },
+    eclipse_ibc_state::{
+        internal_path::{ClientTypeUrlPath, ConsensusHeightsPath, ProcessedHeightsPath},
+        IbcAccountData, IbcState,
+    },
+    ibc::core::{
+        ics02_client::height::Height,
+        ics24_host::{
+            identifier::ClientId,
+            path::{ClientConsensusStatePath, ClientStatePath},
+        },
+    },
+    prost::Message as _,
+};
+
+const CONSENSUS_STATES: u64 = 50;
+
+const COMPRESS_VALUES_ABOVE: u64 = 128;
+
+fn client_id() -> ClientId {
+    "07-tendermint-0".parse().unwrap()
+}
+
+fn height(revision_height: u64) -> Height {
+    Height::new(4, revision_height).unwrap()
+}
+
+/// A Tendermint client updated once per slot until it has [`CONSENSUS_STATES`]
+/// consensus states, the way the IBC program stores client updates.
+fn tendermint_client_account(compress_values_above: Option<u64>) -> IbcAccountData {
+    let mut account_data = IbcAccountData::default();
+    let client_id = client_id();
+    for revision_height in 1..=CONSENSUS_STATES {
+        let mut state = IbcState::new(&account_data.store, revision_height);
+        state.set_compress_values_above(compress_values_above);
+        state.set(
+            &ClientTypeUrlPath(client_id.clone()),
+            TENDERMINT_CLIENT_STATE_TYPE_URL.to_owned(),
+        );
+        state
+            .set_any(
+                &ClientStatePath::new(&client_id),
+                clients::tendermint_client_state_any(revision_height),
+            )
+            .unwrap();
+        state
+            .set_any(
+                &ClientConsensusStatePath::new(&client_id, &height(revision_height)),
+                clients::tendermint_consensus_state_any(revision_height),
+            )
+            .unwrap();
+        state
+            .update(&ConsensusHeightsPath(client_id.clone()), |heights| {
+                heights.heights.insert(height(revision_height));
+            })
+            .unwrap();
+        state
+            .update(&ProcessedHeightsPath(client_id.clone()), |heights| {
+                heights.heights.insert(height(revision_height));
+            })
+            .unwrap();
+        let root_hash = state.commit().unwrap();
+        account_data.metadata.record_commit(root_hash);
+    }
+    account_data
+}
+
+#[test]
+fn compressing_internal_values_shrinks_a_tendermint_client_account() {
+    let uncompressed = tendermint_client_account(None);
+    let compressed = tendermint_client_account(Some(COMPRESS_VALUES_ABOVE));
+    let uncompressed_size = uncompressed.serialized_size().unwrap();
+    let compressed_size = compressed.serialized_size().unwrap();
+    println!(
+        "{CONSENSUS_STATES} Tendermint consensus states: {uncompressed_size} bytes \
+         uncompressed, {compressed_size} bytes compressed above {COMPRESS_VALUES_ABOVE} \
+         bytes ({}% saved)",
+        (uncompressed_size - compressed_size) * 100 / uncompressed_size
+    );
+    assert!(compressed_size < uncompressed_size);
+
+    // Both read back the same values
+    let uncompressed_state = IbcState::new(&uncompressed.store, CONSENSUS_STATES);
+    let compressed_state = IbcState::new(&compressed.store, CONSENSUS_STATES);
+    for account_data in [&uncompressed, &compressed] {
+        account_data.verify_latest_root_deep().unwrap();
+    }
+    let consensus_heights_path = ConsensusHeightsPath(client_id());
+    let consensus_heights = compressed_state.get(&consensus_heights_path).unwrap();
+    assert_eq!(
+        consensus_heights
+            .as_ref()
+            .map(|heights| heights.heights.len()),
+        Some(usize::try_from(CONSENSUS_STATES).unwrap())
+    );
+    assert_eq!(
+        uncompressed_state.get(&consensus_heights_path).unwrap(),
+        consensus_heights
+    );
+    assert!(uncompressed_state
+        .diff(&compressed_state)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn ics24_values_are_proven_uncompressed() {
+    let account_data = tendermint_client_account(Some(0));
+    let state = IbcState::new(&account_data.store, CONSENSUS_STATES);
+    let client_id = client_id();
+
+    // Proofs are over the stored bytes, which counterparties compare with the
+    // encoding of the value they expect
+    let client_state_proof = state.get_proof(&ClientStatePath::new(&client_id)).unwrap();
+    assert_eq!(
+        client_state_proof.value,
+        clients::tendermint_client_state_any(CONSENSUS_STATES).encode_to_vec()
+    );
+    let consensus_state_path = ClientConsensusStatePath::new(&client_id, &height(1));
+    let consensus_state_proof = state.get_proof(&consensus_state_path).unwrap();
+    assert_eq!(
+        consensus_state_proof.value,
+        clients::tendermint_consensus_state_any(1).encode_to_vec()
+    );
+    assert_eq!(
+        state
+            .get_any(&consensus_state_path)
+            .unwrap()
+            .unwrap()
+            .type_url,
+        TENDERMINT_CONSENSUS_STATE_TYPE_URL
+    );
+
+    // While internal values are stored compressed
+    let consensus_heights_proof = state
+        .get_proof(&ConsensusHeightsPath(client_id.clone()))
+        .unwrap();
+    let consensus_heights = state
+        .get_raw(&ConsensusHeightsPath(client_id))
+        .unwrap()
+        .unwrap();
+    assert_ne!(
+        consensus_heights_proof.value,
+        consensus_heights.encode_to_vec()
+    );
+}